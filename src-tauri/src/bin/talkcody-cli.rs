@@ -0,0 +1,8 @@
+//! Headless entrypoint: runs one agent task from the terminal
+
+fn main() {
+    // Load the user's shell PATH so tools find the same binaries as in a terminal
+    let _ = fix_path_env::fix();
+    let args = std::env::args().skip(1).collect();
+    std::process::exit(tauri_app_lib::run_headless(args));
+}
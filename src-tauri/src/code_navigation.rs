@@ -11,6 +11,18 @@ use streaming_iterator::StreamingIterator;
 use tauri::{AppHandle, Manager, State};
 use tree_sitter::{Language, Parser, Point, Query, QueryCursor, Tree};
 
+/// Language IDs with a tree-sitter grammar and definition query
+pub(crate) const SUPPORTED_LANGUAGES: &[&str] = &[
+    "python",
+    "rust",
+    "go",
+    "c",
+    "cpp",
+    "java",
+    "typescript",
+    "javascript",
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SymbolInfo {
     pub name: String,
@@ -50,16 +62,26 @@ impl CodeNavigationService {
     }
 
     fn init_languages(&mut self) {
-        self.register_language("python", tree_sitter_python::LANGUAGE.into());
-        self.register_language("rust", tree_sitter_rust::LANGUAGE.into());
-        self.register_language("go", tree_sitter_go::LANGUAGE.into());
-        self.register_language("c", tree_sitter_c::LANGUAGE.into());
-        self.register_language("cpp", tree_sitter_cpp::LANGUAGE.into());
-        self.register_language("java", tree_sitter_java::LANGUAGE.into());
+        for lang_id in SUPPORTED_LANGUAGES {
+            if let Some(language) = Self::language_for(lang_id) {
+                self.register_language(lang_id, language);
+            }
+        }
+    }
 
-        // TypeScript and JavaScript (using TSX parser which handles both TS and TSX/JSX syntax)
-        self.register_language("typescript", tree_sitter_typescript::LANGUAGE_TSX.into());
-        self.register_language("javascript", tree_sitter_typescript::LANGUAGE_TSX.into());
+    /// Tree-sitter grammar for a language ID
+    pub(crate) fn language_for(lang_id: &str) -> Option<Language> {
+        match lang_id {
+            "python" => Some(tree_sitter_python::LANGUAGE.into()),
+            "rust" => Some(tree_sitter_rust::LANGUAGE.into()),
+            "go" => Some(tree_sitter_go::LANGUAGE.into()),
+            "c" => Some(tree_sitter_c::LANGUAGE.into()),
+            "cpp" => Some(tree_sitter_cpp::LANGUAGE.into()),
+            "java" => Some(tree_sitter_java::LANGUAGE.into()),
+            // TypeScript and JavaScript (using TSX parser which handles both TS and TSX/JSX syntax)
+            "typescript" | "javascript" => Some(tree_sitter_typescript::LANGUAGE_TSX.into()),
+            _ => None,
+        }
     }
 
     fn register_language(&mut self, lang_id: &str, language: Language) {
@@ -86,7 +108,7 @@ impl CodeNavigationService {
         self.languages.insert(lang_id.to_string(), language);
     }
 
-    fn get_definition_query(lang_id: &str) -> &'static str {
+    pub(crate) fn get_definition_query(lang_id: &str) -> &'static str {
         match lang_id {
             "python" => {
                 r#"
@@ -154,7 +176,7 @@ impl CodeNavigationService {
         }
     }
 
-    fn get_symbol_kind(capture_name: &str) -> String {
+    pub(crate) fn get_symbol_kind(capture_name: &str) -> String {
         if capture_name.contains("function") {
             "function".to_string()
         } else if capture_name.contains("class") {
@@ -395,7 +417,7 @@ impl CodeNavigationService {
     }
 
     /// Get language ID from file path based on extension
-    fn get_lang_id_from_path(file_path: &str) -> Option<String> {
+    pub(crate) fn get_lang_id_from_path(file_path: &str) -> Option<String> {
         let ext = file_path.rsplit('.').next()?;
         match ext.to_lowercase().as_str() {
             "py" => Some("python".to_string()),
@@ -3,6 +3,9 @@
 /// Default maximum depth for directory traversal
 pub const DEFAULT_MAX_DEPTH: usize = 20;
 
+/// Project-level ignore file (gitignore syntax) applied by every workspace walk
+pub const TALKCODY_IGNORE_FILENAME: &str = ".talkcodyignore";
+
 /// Directories to exclude from file operations
 pub const EXCLUDED_DIRS: &[&str] = &[
     "node_modules",
@@ -0,0 +1,588 @@
+//! Workspace content search (ripgrep-style).
+//!
+//! Walks the workspace with `WorkspaceWalker::build_parallel`, searches each file with the
+//! `grep` searcher and streams results back in batches while the walk is still running.
+
+use crate::platform::types::SearchResult;
+use crate::text_file::{skip_for_search, DEFAULT_MAX_SEARCH_FILE_SIZE};
+use crate::walker::{WalkerConfig, WorkspaceWalker};
+use grep::matcher::Matcher;
+use grep::regex::{RegexMatcher, RegexMatcherBuilder};
+use grep::searcher::{
+    BinaryDetection, Searcher, SearcherBuilder, Sink, SinkContext, SinkContextKind, SinkMatch,
+};
+use ignore::WalkState;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::ffi::OsStr;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Maximum line length kept in results (in bytes)
+const MAX_LINE_LENGTH: usize = 500;
+/// Upper bound for requested context lines
+const MAX_CONTEXT_LINES: usize = 10;
+/// Results per streamed batch
+const BATCH_SIZE: usize = 50;
+/// Flush a partial batch when no new results arrived for this long
+const BATCH_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Options for `search_content`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContentSearchOptions {
+    /// Treat the pattern as a regular expression instead of a literal string
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+    /// Only match on word boundaries
+    pub whole_word: bool,
+    /// Stop searching a file after this many matching lines
+    pub max_matches_per_file: usize,
+    /// Stop the whole search after this many matching lines
+    pub max_results: usize,
+    /// Lines of context captured before and after each match
+    pub context_lines: usize,
+    /// Restrict the search to these file extensions
+    pub file_types: Option<Vec<String>>,
+    /// Directory names excluded in addition to the defaults
+    pub exclude_dirs: Vec<String>,
+    /// Files larger than this (in bytes) are skipped
+    pub max_file_size: u64,
+}
+
+impl Default for ContentSearchOptions {
+    fn default() -> Self {
+        Self {
+            is_regex: false,
+            case_sensitive: false,
+            whole_word: false,
+            max_matches_per_file: 100,
+            max_results: 2000,
+            context_lines: 0,
+            file_types: None,
+            exclude_dirs: Vec::new(),
+            max_file_size: DEFAULT_MAX_SEARCH_FILE_SIZE,
+        }
+    }
+}
+
+/// Payload of the `content-search-results` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentSearchBatch {
+    pub search_id: String,
+    pub results: Vec<SearchResult>,
+    pub done: bool,
+}
+
+fn build_matcher(pattern: &str, options: &ContentSearchOptions) -> Result<RegexMatcher, String> {
+    let pattern = if options.is_regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+
+    RegexMatcherBuilder::new()
+        .case_insensitive(!options.case_sensitive)
+        .word(options.whole_word)
+        .line_terminator(Some(b'\n'))
+        .build(&pattern)
+        .map_err(|e| format!("Invalid search pattern: {}", e))
+}
+
+/// Find the largest valid char boundary <= index
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    if index >= s.len() {
+        return s.len();
+    }
+    let mut i = index;
+    while i > 0 && !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+fn line_text(bytes: &[u8]) -> String {
+    let line = String::from_utf8_lossy(bytes);
+    let line = line.trim_end_matches(['\n', '\r']);
+    if line.len() <= MAX_LINE_LENGTH {
+        return line.to_string();
+    }
+    let end = floor_char_boundary(line, MAX_LINE_LENGTH);
+    format!("{}...", &line[..end])
+}
+
+/// Collects matches and their context lines for a single file
+struct FileSink<'a> {
+    matcher: &'a RegexMatcher,
+    path: String,
+    max_matches: usize,
+    /// Latest lines seen, matched or context, by line number. The searcher
+    /// reports a line between two nearby matches only once, as after-context
+    /// of the first, so the second match takes its before-context from here.
+    recent: VecDeque<(u64, String)>,
+    results: Vec<SearchResult>,
+}
+
+impl FileSink<'_> {
+    fn remember(&mut self, searcher: &Searcher, line_number: Option<u64>, text: &str) {
+        let (Some(line_number), limit) = (line_number, searcher.before_context()) else {
+            return;
+        };
+        if limit == 0 {
+            return;
+        }
+        if self.recent.len() == limit {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((line_number, text.to_string()));
+    }
+}
+
+impl Sink for FileSink<'_> {
+    type Error = std::io::Error;
+
+    fn matched(&mut self, searcher: &Searcher, mat: &SinkMatch<'_>) -> Result<bool, Self::Error> {
+        let bytes = mat.bytes();
+        // 1-based character column of the first match on the line
+        let column = match self.matcher.find(bytes) {
+            Ok(Some(m)) => String::from_utf8_lossy(&bytes[..m.start()]).chars().count() + 1,
+            _ => 1,
+        };
+
+        let line = mat.line_number().unwrap_or(0);
+        let reach = searcher.before_context() as u64;
+        let context_before = self
+            .recent
+            .iter()
+            .filter(|(number, _)| number + reach >= line)
+            .map(|(_, text)| text.clone())
+            .collect();
+        let text = line_text(bytes);
+        self.remember(searcher, mat.line_number(), &text);
+
+        self.results.push(SearchResult {
+            path: self.path.clone(),
+            line: line as usize,
+            column,
+            text,
+            context_before,
+            context_after: Vec::new(),
+        });
+
+        Ok(self.results.len() < self.max_matches)
+    }
+
+    fn context(&mut self, searcher: &Searcher, ctx: &SinkContext<'_>) -> Result<bool, Self::Error> {
+        let text = line_text(ctx.bytes());
+        self.remember(searcher, ctx.line_number(), &text);
+        if *ctx.kind() == SinkContextKind::After {
+            if let Some(last) = self.results.last_mut() {
+                last.context_after.push(text);
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn search_file(
+    matcher: &RegexMatcher,
+    searcher: &mut Searcher,
+    path: &Path,
+    max_matches: usize,
+) -> Vec<SearchResult> {
+    let mut sink = FileSink {
+        matcher,
+        path: path.to_string_lossy().to_string(),
+        max_matches,
+        recent: VecDeque::new(),
+        results: Vec::new(),
+    };
+
+    // Unreadable files are skipped like binary ones
+    if searcher.search_path(matcher, path, &mut sink).is_err() {
+        return Vec::new();
+    }
+    sink.results
+}
+
+/// Search file contents under `root`, calling `on_batch` with partial results while the
+/// walk is in progress. Returns all results sorted by path and line.
+pub fn search_workspace<F>(
+    root: &str,
+    pattern: &str,
+    options: &ContentSearchOptions,
+    mut on_batch: F,
+) -> Result<Vec<SearchResult>, String>
+where
+    F: FnMut(&[SearchResult]),
+{
+    if pattern.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !Path::new(root).is_dir() {
+        return Err(format!("Directory does not exist: {}", root));
+    }
+
+    let matcher = build_matcher(pattern, options)?;
+    let context_lines = options.context_lines.min(MAX_CONTEXT_LINES);
+    let max_results = options.max_results.max(1);
+    let max_matches_per_file = options.max_matches_per_file.max(1);
+    let max_file_size = options.max_file_size;
+    let file_types: Option<HashSet<String>> = options
+        .file_types
+        .as_ref()
+        .map(|types| types.iter().map(|t| t.to_lowercase()).collect());
+
+    let config =
+        WalkerConfig::for_content_search().with_additional_excludes(options.exclude_dirs.clone());
+    let walker = WorkspaceWalker::new(root, config).build_parallel();
+
+    let found = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (tx, rx) = channel::<Vec<SearchResult>>();
+
+    let mut all_results = Vec::new();
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            walker.run(|| {
+                let tx = tx.clone();
+                let matcher = &matcher;
+                let file_types = &file_types;
+                let found = &found;
+                let stop = &stop;
+                let mut searcher = SearcherBuilder::new()
+                    .binary_detection(BinaryDetection::quit(b'\x00'))
+                    .line_number(true)
+                    .before_context(context_lines)
+                    .after_context(context_lines)
+                    .build();
+
+                Box::new(move |result| {
+                    if stop.load(Ordering::Relaxed) {
+                        return WalkState::Quit;
+                    }
+
+                    let entry = match result {
+                        Ok(entry) => entry,
+                        Err(_) => return WalkState::Continue,
+                    };
+                    if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+                        return WalkState::Continue;
+                    }
+
+                    let path = entry.path();
+                    if let Some(types) = file_types {
+                        let matches_type = path
+                            .extension()
+                            .and_then(OsStr::to_str)
+                            .is_some_and(|ext| types.contains(&ext.to_lowercase()));
+                        if !matches_type {
+                            return WalkState::Continue;
+                        }
+                    }
+
+                    let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                    if let Some(skipped) = skip_for_search(path, size, max_file_size) {
+                        log::debug!("{}", skipped.message());
+                        return WalkState::Continue;
+                    }
+
+                    let mut results =
+                        search_file(matcher, &mut searcher, path, max_matches_per_file);
+                    if results.is_empty() {
+                        return WalkState::Continue;
+                    }
+
+                    // Trim to the global limit so the total never overshoots
+                    let previous = found.fetch_add(results.len(), Ordering::Relaxed);
+                    if previous >= max_results {
+                        stop.store(true, Ordering::Relaxed);
+                        return WalkState::Quit;
+                    }
+                    results.truncate(max_results - previous);
+                    if previous + results.len() >= max_results {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+
+                    if tx.send(results).is_err() {
+                        return WalkState::Quit;
+                    }
+                    WalkState::Continue
+                })
+            });
+            drop(tx);
+        });
+
+        // Collector streams batches while the walkers run
+        let mut batch: Vec<SearchResult> = Vec::new();
+        loop {
+            match rx.recv_timeout(BATCH_FLUSH_INTERVAL) {
+                Ok(results) => {
+                    batch.extend(results);
+                    if batch.len() >= BATCH_SIZE {
+                        on_batch(&batch);
+                        all_results.append(&mut batch);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if !batch.is_empty() {
+                        on_batch(&batch);
+                        all_results.append(&mut batch);
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        if !batch.is_empty() {
+            on_batch(&batch);
+            all_results.append(&mut batch);
+        }
+    });
+
+    all_results.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+    Ok(all_results)
+}
+
+/// Search file contents in a workspace. When `search_id` is provided, partial results are
+/// streamed as `content-search-results` events while the search runs.
+#[tauri::command]
+pub async fn search_content(
+    app_handle: AppHandle,
+    root: String,
+    pattern: String,
+    options: Option<ContentSearchOptions>,
+    search_id: Option<String>,
+) -> Result<Vec<SearchResult>, String> {
+    let options = options.unwrap_or_default();
+    let start_time = std::time::Instant::now();
+
+    let task_app = app_handle.clone();
+    let task_search_id = search_id.clone();
+    let results = tauri::async_runtime::spawn_blocking(move || {
+        search_workspace(&root, &pattern, &options, |batch| {
+            if let Some(ref search_id) = task_search_id {
+                let payload = ContentSearchBatch {
+                    search_id: search_id.clone(),
+                    results: batch.to_vec(),
+                    done: false,
+                };
+                if let Err(e) = task_app.emit("content-search-results", &payload) {
+                    log::error!("Failed to emit content search results: {}", e);
+                }
+            }
+        })
+    })
+    .await
+    .map_err(|e| format!("Content search task failed: {}", e))??;
+
+    if let Some(search_id) = search_id {
+        let payload = ContentSearchBatch {
+            search_id,
+            results: Vec::new(),
+            done: true,
+        };
+        if let Err(e) = app_handle.emit("content-search-results", &payload) {
+            log::error!("Failed to emit content search completion: {}", e);
+        }
+    }
+
+    log::info!(
+        "Content search found {} matches in {}ms",
+        results.len(),
+        start_time.elapsed().as_millis()
+    );
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn create_test_directory() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("node_modules/pkg")).unwrap();
+
+        fs::write(
+            temp_dir.path().join("src/main.rs"),
+            "use std::io;\n\nfn main() {\n    let value = compute();\n    println!(\"{}\", value);\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("src/lib.rs"),
+            "pub fn compute() -> i32 {\n    42\n}\n\npub fn computed_total() -> i32 {\n    compute() * 2\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("README.md"),
+            "Call compute to compute.\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("node_modules/pkg/index.js"),
+            "compute()\n",
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("src/data.bin"), b"compute\x00\x01\x02").unwrap();
+
+        temp_dir
+    }
+
+    fn search(dir: &TempDir, pattern: &str, options: ContentSearchOptions) -> Vec<SearchResult> {
+        search_workspace(dir.path().to_str().unwrap(), pattern, &options, |_| {}).unwrap()
+    }
+
+    #[test]
+    fn test_literal_search_skips_excluded_and_binary() {
+        let dir = create_test_directory();
+        let results = search(&dir, "compute()", ContentSearchOptions::default());
+
+        assert!(results.iter().all(|r| !r.path.contains("node_modules")));
+        assert!(results.iter().all(|r| !r.path.ends_with("data.bin")));
+        // Literal mode: the parentheses are not a regex group
+        assert_eq!(results.len(), 3);
+
+        let main = results
+            .iter()
+            .find(|r| r.path.ends_with("main.rs"))
+            .unwrap();
+        assert_eq!(main.line, 4);
+        assert_eq!(main.column, 17);
+    }
+
+    #[test]
+    fn test_regex_and_whole_word() {
+        let dir = create_test_directory();
+
+        let options = ContentSearchOptions {
+            is_regex: true,
+            ..Default::default()
+        };
+        let results = search(&dir, r"fn \w+\(\)", options);
+        assert_eq!(results.len(), 3);
+
+        let options = ContentSearchOptions {
+            whole_word: true,
+            file_types: Some(vec!["rs".to_string()]),
+            ..Default::default()
+        };
+        let results = search(&dir, "compute", options);
+        // `computed_total` is not a whole-word match
+        assert!(results.iter().all(|r| !r.text.contains("computed_total")));
+        assert_eq!(results.len(), 3);
+    }
+
+    #[test]
+    fn test_case_sensitivity() {
+        let dir = create_test_directory();
+
+        let results = search(&dir, "CALL", ContentSearchOptions::default());
+        assert_eq!(results.len(), 1);
+
+        let options = ContentSearchOptions {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        assert!(search(&dir, "CALL", options).is_empty());
+    }
+
+    #[test]
+    fn test_context_lines() {
+        let dir = create_test_directory();
+        let options = ContentSearchOptions {
+            context_lines: 1,
+            file_types: Some(vec!["rs".to_string()]),
+            ..Default::default()
+        };
+        let results = search(&dir, "println", options);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].context_before,
+            vec!["    let value = compute();"]
+        );
+        assert_eq!(results[0].context_after, vec!["}"]);
+    }
+
+    #[test]
+    fn test_context_shared_by_nearby_matches() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("notes.txt"),
+            "alpha\ntarget one\nmiddle\ntarget two\nomega\n",
+        )
+        .unwrap();
+        let options = ContentSearchOptions {
+            context_lines: 1,
+            ..Default::default()
+        };
+        let results = search(&dir, "target", options);
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].context_before, vec!["alpha"]);
+        assert_eq!(results[0].context_after, vec!["middle"]);
+        // The line between the matches is context for both
+        assert_eq!(results[1].context_before, vec!["middle"]);
+        assert_eq!(results[1].context_after, vec!["omega"]);
+    }
+
+    #[test]
+    fn test_limits_and_batches() {
+        let dir = create_test_directory();
+        let options = ContentSearchOptions {
+            max_matches_per_file: 1,
+            ..Default::default()
+        };
+        let results = search(&dir, "compute", options);
+        assert_eq!(results.len(), 3);
+
+        let options = ContentSearchOptions {
+            max_results: 2,
+            ..Default::default()
+        };
+        let mut streamed = 0;
+        let results =
+            search_workspace(dir.path().to_str().unwrap(), "compute", &options, |batch| {
+                streamed += batch.len()
+            })
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(streamed, 2);
+    }
+
+    #[test]
+    fn test_skips_large_files() {
+        let dir = create_test_directory();
+        fs::write(
+            dir.path().join("src/huge.rs"),
+            "fn compute() {}\n".repeat(100),
+        )
+        .unwrap();
+
+        let options = ContentSearchOptions {
+            max_file_size: 1024,
+            file_types: Some(vec!["rs".to_string()]),
+            ..Default::default()
+        };
+        let results = search(&dir, "compute", options);
+        assert!(results.iter().all(|r| !r.path.ends_with("huge.rs")));
+        assert!(!results.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let dir = create_test_directory();
+        let options = ContentSearchOptions {
+            is_regex: true,
+            ..Default::default()
+        };
+        let result = search_workspace(dir.path().to_str().unwrap(), "(", &options, |_| {});
+        assert!(result.is_err());
+    }
+}
@@ -6,17 +6,40 @@
 //! 3. Handles tool calls and dispatches to platform tools
 //! 4. Manages the conversation flow until completion
 
+use crate::core::budget;
+use crate::core::cancellation::CancellationToken;
+use crate::core::compaction;
+use crate::core::llm::LlmClient;
+use crate::core::metrics::RuntimeMetrics;
+use crate::core::plan::{self, PlanDraft};
+use crate::core::stream_state::{self, StreamStateRecorder};
 use crate::core::tools::{ToolContext, ToolDispatchResult, ToolDispatcher, ToolRegistry};
+use crate::core::truncation::{self, TruncationStrategy};
 use crate::core::types::*;
+use crate::llm::ai_services::types::TokenUsage;
+use crate::llm::types::{
+    ContentPart, Message as LlmMessage, MessageContent as LlmMessageContent, StreamEvent,
+    StreamTextRequest, ToolDefinition as LlmToolDefinition,
+};
 use crate::storage::models::*;
+use crate::storage::AttachmentsRepository;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch, RwLock};
 
 /// Agent loop configuration
 pub struct AgentLoop {
     config: AgentLoopConfig,
     tool_dispatcher: Arc<ToolDispatcher>,
+    llm: Arc<dyn LlmClient>,
     event_sender: EventSender,
+    stream_recorder: Option<StreamStateRecorder>,
+    /// Stores tool outputs truncated with the `Attachment` strategy
+    attachments: Option<AttachmentsRepository>,
+    metrics: Option<RuntimeMetrics>,
+    /// Cancelled when the runtime shuts down
+    drain_token: Option<CancellationToken>,
 }
 
 /// Context for a single agent loop execution
@@ -27,7 +50,32 @@ pub struct AgentLoopContext {
     pub workspace_root: String,
     pub worktree_path: Option<String>,
     pub settings: TaskSettings,
+    /// Session history; messages produced by the loop are appended here
     pub messages: Vec<Message>,
+    /// Cancelled when the owning task is cancelled
+    pub cancel_token: CancellationToken,
+    /// Compactions performed during this run, persisted by the runtime
+    pub compactions: Vec<ContextCompaction>,
+    /// Budget consumed by the task, including runs before it last paused
+    pub usage: BudgetUsage,
+    /// Start of the running time not yet added to `usage`
+    pub usage_clock: Instant,
+}
+
+impl AgentLoopContext {
+    /// Add the running time since the last call to `usage`
+    pub fn record_running_time(&mut self) {
+        let now = Instant::now();
+        self.usage.duration_ms += now.duration_since(self.usage_clock).as_millis() as u64;
+        self.usage_clock = now;
+    }
+
+    /// Limits of the task budget that have been reached, if any
+    fn exceeded_budget(&mut self) -> Option<Vec<BudgetLimit>> {
+        self.record_running_time();
+        let exceeded = budget::exceeded_limits(self.settings.budget.as_ref()?, &self.usage);
+        (!exceeded.is_empty()).then_some(exceeded)
+    }
 }
 
 /// Result of agent loop execution
@@ -35,8 +83,20 @@ pub struct AgentLoopContext {
 pub enum AgentLoopResult {
     /// Completed successfully with final response
     Completed { message: String },
-    /// Waiting for user approval of tool call
-    WaitingForApproval { request: ToolRequest },
+    /// Waiting for user approval of tool call; `remaining` are the tool calls
+    /// from the same turn that run after the decision
+    WaitingForApproval {
+        request: ToolRequest,
+        remaining: Vec<ToolRequest>,
+    },
+    /// Paused because the task budget ran out; `remaining` are the tool calls
+    /// that run if the user lets the task continue
+    BudgetExceeded {
+        exceeded: Vec<BudgetLimit>,
+        remaining: Vec<ToolRequest>,
+    },
+    /// A plan-mode run submitted its plan
+    PlanSubmitted { plan: PlanDraft },
     /// Waiting for tool result
     WaitingForToolResult { tool_call_id: ToolCallId },
     /// Error occurred
@@ -45,72 +105,588 @@ pub enum AgentLoopResult {
     MaxIterationsReached,
     /// Cancelled by user
     Cancelled,
+    /// Stopped between iterations because the runtime is shutting down
+    Suspended,
+}
+
+/// Output collected from a single streamed LLM response
+#[derive(Debug, Default)]
+struct StreamedResponse {
+    text: String,
+    tool_calls: Vec<ToolCall>,
+    usage: Option<TokenUsage>,
+    error: Option<String>,
+    cancelled: bool,
+    /// Time until the first token or tool call arrived
+    first_token: Option<Duration>,
+    reasoning: Vec<ReasoningBlock>,
+}
+
+/// A streamed reasoning block with the provider metadata (signatures,
+/// encrypted content) needed to send it back
+#[derive(Debug, Default)]
+struct ReasoningBlock {
+    id: String,
+    text: String,
+    provider_metadata: Option<serde_json::Value>,
+}
+
+impl StreamedResponse {
+    fn reasoning_block(&mut self, id: String) -> &mut ReasoningBlock {
+        match self.reasoning.iter().position(|block| block.id == id) {
+            Some(index) => &mut self.reasoning[index],
+            None => {
+                self.reasoning.push(ReasoningBlock {
+                    id,
+                    ..ReasoningBlock::default()
+                });
+                self.reasoning.last_mut().expect("block was just pushed")
+            }
+        }
+    }
 }
 
 impl AgentLoop {
     pub fn new(
         config: AgentLoopConfig,
         tool_dispatcher: Arc<ToolDispatcher>,
+        llm: Arc<dyn LlmClient>,
         event_sender: EventSender,
     ) -> Self {
         Self {
             config,
             tool_dispatcher,
+            llm,
             event_sender,
+            stream_recorder: None,
+            attachments: None,
+            metrics: None,
+            drain_token: None,
         }
     }
 
-    /// Run the agent loop for a single iteration
-    /// This is a simplified placeholder implementation
-    /// Full implementation would integrate with llm/ module
-    pub async fn run_iteration(&self, ctx: &AgentLoopContext) -> Result<AgentLoopResult, String> {
-        // Build LLM prompt from context
-        let _prompt = self.build_prompt(ctx)?;
+    /// Save streamed output as it arrives so the run survives a crash
+    pub fn with_stream_recorder(mut self, recorder: StreamStateRecorder) -> Self {
+        self.stream_recorder = Some(recorder);
+        self
+    }
 
-        // In a full implementation, this would:
-        // 1. Call LLM streaming API from llm/ module
-        // 2. Stream tokens back via event_sender
-        // 3. Detect tool calls in the response
-        // 4. Return appropriate result
+    /// Store oversized tool outputs as session attachments when a tool's
+    /// truncation strategy asks for it
+    pub fn with_attachments(mut self, attachments: AttachmentsRepository) -> Self {
+        self.attachments = Some(attachments);
+        self
+    }
 
-        // Placeholder: just return completed
-        Ok(AgentLoopResult::Completed {
-            message: "Agent loop placeholder completed".to_string(),
-        })
+    /// Record iterations, LLM latency and tool calls of the run
+    pub fn with_metrics(mut self, metrics: RuntimeMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
     }
 
-    /// Handle a tool call request
-    pub async fn handle_tool_call(
+    /// Stop at the next iteration boundary once `token` is cancelled, so a
+    /// shutdown never cuts a response or tool call short
+    pub fn with_drain_token(mut self, token: CancellationToken) -> Self {
+        self.drain_token = Some(token);
+        self
+    }
+
+    /// Run the agent loop until the model stops calling tools, a tool needs
+    /// approval, the task budget runs out, or the iteration limit is reached
+    pub async fn run(&self, ctx: &mut AgentLoopContext) -> Result<AgentLoopResult, String> {
+        for _ in 0..self.config.max_iterations {
+            if ctx.cancel_token.is_cancelled() {
+                return Ok(AgentLoopResult::Cancelled);
+            }
+            if self
+                .drain_token
+                .as_ref()
+                .is_some_and(|token| token.is_cancelled())
+            {
+                return Ok(AgentLoopResult::Suspended);
+            }
+            self.compact_if_needed(ctx).await;
+            if let Some(result) = self.run_iteration(ctx).await? {
+                return Ok(result);
+            }
+        }
+
+        Ok(AgentLoopResult::MaxIterationsReached)
+    }
+
+    /// Run a single LLM round trip. Returns `None` when tool results were
+    /// appended to the context and the loop should continue.
+    pub async fn run_iteration(
         &self,
-        ctx: &AgentLoopContext,
-        request: ToolRequest,
-    ) -> Result<ToolResult, String> {
-        let tool_context = ToolContext {
+        ctx: &mut AgentLoopContext,
+    ) -> Result<Option<AgentLoopResult>, String> {
+        let model = self.config.model.clone().unwrap_or_default();
+        let interleaved = self
+            .llm
+            .model_config(&model)
+            .await
+            .is_some_and(|config| config.interleaved);
+        let request = StreamTextRequest {
+            model,
+            messages: self.build_messages(ctx, interleaved),
+            tools: self.tool_definitions(&ctx.settings).await,
+            stream: Some(true),
+            temperature: Some(self.config.temperature),
+            max_tokens: self.config.max_tokens.map(|tokens| tokens as i32),
+            top_p: None,
+            top_k: None,
+            provider_options: None,
+            request_id: None,
+            trace_context: None,
+        };
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_iteration(&ctx.task_id, &ctx.session_id);
+        }
+        let _ = self.event_sender.send(RuntimeEvent::IterationStarted {
+            task_id: ctx.task_id.clone(),
+            session_id: ctx.session_id.clone(),
+            iteration: ctx.usage.iterations + 1,
+        });
+        let response = self.stream_response(ctx, request).await?;
+        self.record_usage(ctx, response.usage.as_ref()).await;
+        if let Some(message) = response.error {
+            return Ok(Some(AgentLoopResult::Error { message }));
+        }
+
+        // Interleaved-thinking models expect the reasoning behind their tool
+        // calls back on the following turns
+        if interleaved && !response.cancelled && !response.tool_calls.is_empty() {
+            for block in response.reasoning {
+                if block.text.is_empty() && block.provider_metadata.is_none() {
+                    continue;
+                }
+                let message = new_message(
+                    ctx,
+                    MessageRole::Assistant,
+                    MessageContent::Reasoning {
+                        text: block.text,
+                        provider_metadata: block.provider_metadata,
+                    },
+                    None,
+                );
+                ctx.messages.push(message);
+            }
+        }
+
+        if !response.text.is_empty() {
+            let message = new_message(
+                ctx,
+                MessageRole::Assistant,
+                MessageContent::Text {
+                    text: response.text.clone(),
+                },
+                None,
+            );
+            ctx.messages.push(message);
+        }
+
+        // Keep the partial text the user already saw, but drop any tool calls
+        if response.cancelled {
+            return Ok(Some(AgentLoopResult::Cancelled));
+        }
+
+        if response.tool_calls.is_empty() {
+            // A plan-mode run only ends with a submitted plan
+            if self.config.plan_mode {
+                let reminder = new_message(
+                    ctx,
+                    MessageRole::System,
+                    MessageContent::Text {
+                        text: plan::PLAN_REMINDER.to_string(),
+                    },
+                    None,
+                );
+                ctx.messages.push(reminder);
+                return Ok(None);
+            }
+            return Ok(Some(AgentLoopResult::Completed {
+                message: response.text,
+            }));
+        }
+
+        let message = new_message(
+            ctx,
+            MessageRole::Assistant,
+            MessageContent::ToolCalls {
+                calls: response.tool_calls.clone(),
+            },
+            None,
+        );
+        ctx.messages.push(message);
+
+        let requests = response
+            .tool_calls
+            .into_iter()
+            .map(|call| ToolRequest {
+                tool_call_id: call.id,
+                name: call.name,
+                input: call.input,
+            })
+            .collect();
+        if let Some(exceeded) = ctx.exceeded_budget() {
+            return Ok(Some(AgentLoopResult::BudgetExceeded {
+                exceeded,
+                remaining: requests,
+            }));
+        }
+        self.dispatch_tool_calls(ctx, requests).await
+    }
+
+    /// Add an LLM round trip and its token usage and cost to the task's budget usage
+    async fn record_usage(&self, ctx: &mut AgentLoopContext, usage: Option<&TokenUsage>) {
+        ctx.usage.iterations += 1;
+        let Some(usage) = usage else {
+            return;
+        };
+
+        ctx.usage.input_tokens += u64::from(usage.input_tokens);
+        ctx.usage.output_tokens += u64::from(usage.output_tokens);
+        let model = self.config.model.clone().unwrap_or_default();
+        if let Some(cost) = self.llm.usage_cost(&model, usage).await {
+            ctx.usage.cost_usd += cost;
+        }
+    }
+
+    /// Fold older turns into a summary when the context nears the model's
+    /// context window. Failures are logged and the full context is kept.
+    async fn compact_if_needed(&self, ctx: &mut AgentLoopContext) {
+        let model = self.config.model.clone().unwrap_or_default();
+        let Some(context_length) = self.llm.context_length(&model).await else {
+            return;
+        };
+        let tokens_before = compaction::estimate_tokens(&ctx.messages);
+        if !compaction::needs_compaction(tokens_before, context_length) {
+            return;
+        }
+        let Some(boundary) = compaction::plan_compaction(&ctx.messages) else {
+            return;
+        };
+
+        let (pinned, compacted): (Vec<Message>, Vec<Message>) = ctx.messages[..boundary]
+            .iter()
+            .cloned()
+            .partition(compaction::is_pinned);
+        let history = compaction::format_history(&compacted);
+        let summary = match self
+            .llm
+            .summarize(history, self.config.compaction_model.clone())
+            .await
+        {
+            Ok(summary) if !summary.is_empty() => summary,
+            Ok(_) => {
+                log::warn!("Context compaction returned an empty summary");
+                return;
+            }
+            Err(e) => {
+                log::warn!("Context compaction failed: {}", e);
+                return;
+            }
+        };
+
+        let mut compaction = ContextCompaction {
+            id: format!("compaction_{}", uuid::Uuid::new_v4()),
             session_id: ctx.session_id.clone(),
+            summary,
+            first_kept_message_id: ctx.messages[boundary].id.clone(),
+            // A previous summary is folded into the new one but not counted again
+            compacted_count: compacted
+                .iter()
+                .filter(|message| message.role != MessageRole::System)
+                .count(),
+            tokens_before,
+            tokens_after: 0,
+            created_at: chrono::Utc::now().timestamp(),
+        };
+
+        let mut messages = vec![compaction::summary_message(&compaction)];
+        messages.extend(pinned);
+        messages.extend(ctx.messages.drain(boundary..));
+        compaction.tokens_after = compaction::estimate_tokens(&messages);
+        ctx.messages = messages;
+
+        log::info!(
+            "Compacted {} messages of session {} ({} -> {} tokens)",
+            compaction.compacted_count,
+            ctx.session_id,
+            compaction.tokens_before,
+            compaction.tokens_after
+        );
+        let _ = self.event_sender.send(RuntimeEvent::ContextCompacted {
             task_id: ctx.task_id.clone(),
-            workspace_root: ctx.workspace_root.clone(),
-            worktree_path: ctx.worktree_path.clone(),
-            settings: ctx.settings.clone(),
+            session_id: ctx.session_id.clone(),
+            compaction: compaction.clone(),
+        });
+        ctx.compactions.push(compaction);
+    }
+
+    /// Run tool calls in order, appending their results to the context.
+    /// Stops at the first call that needs approval, or once the task budget
+    /// runs out, and returns the calls after it.
+    /// On cancellation the unfinished calls get an error result so every call
+    /// in the history keeps a matching result.
+    pub async fn dispatch_tool_calls(
+        &self,
+        ctx: &mut AgentLoopContext,
+        requests: Vec<ToolRequest>,
+    ) -> Result<Option<AgentLoopResult>, String> {
+        let mut requests = requests.into_iter();
+        while let Some(request) = requests.next() {
+            if ctx.cancel_token.is_cancelled() {
+                for request in std::iter::once(request).chain(requests) {
+                    let result = ToolResult {
+                        tool_call_id: request.tool_call_id,
+                        success: false,
+                        output: serde_json::Value::Null,
+                        error: Some("Tool call cancelled".to_string()),
+                    };
+                    self.append_tool_result(ctx, result);
+                }
+                return Ok(Some(AgentLoopResult::Cancelled));
+            }
+
+            let result = if self.config.plan_mode && request.name == plan::SUBMIT_PLAN_TOOL {
+                match plan::parse_plan(&request.input) {
+                    Ok(draft) => {
+                        let result = ToolResult {
+                            tool_call_id: request.tool_call_id,
+                            success: true,
+                            output: serde_json::json!({ "submitted": true }),
+                            error: None,
+                        };
+                        self.append_tool_result(ctx, result);
+                        // Later calls of the turn would act before the plan is approved
+                        for request in requests {
+                            let result = ToolResult {
+                                tool_call_id: request.tool_call_id,
+                                success: false,
+                                output: serde_json::Value::Null,
+                                error: Some("Not run: the plan was submitted".to_string()),
+                            };
+                            self.append_tool_result(ctx, result);
+                        }
+                        return Ok(Some(AgentLoopResult::PlanSubmitted { plan: draft }));
+                    }
+                    Err(error) => ToolResult {
+                        tool_call_id: request.tool_call_id,
+                        success: false,
+                        output: serde_json::Value::Null,
+                        error: Some(error),
+                    },
+                }
+            } else if self.is_tool_allowed(&request.name, &ctx.settings).await {
+                match self.handle_tool_call(ctx, request).await? {
+                    ToolDispatchResult::Completed(result) => result,
+                    ToolDispatchResult::PendingApproval(request) => {
+                        return Ok(Some(AgentLoopResult::WaitingForApproval {
+                            request,
+                            remaining: requests.collect(),
+                        }));
+                    }
+                }
+            } else {
+                ToolResult {
+                    tool_call_id: request.tool_call_id,
+                    success: false,
+                    output: serde_json::Value::Null,
+                    error: Some(format!("Tool '{}' is not available", request.name)),
+                }
+            };
+
+            self.append_tool_result(ctx, result);
+
+            if let Some(exceeded) = ctx.exceeded_budget() {
+                return Ok(Some(AgentLoopResult::BudgetExceeded {
+                    exceeded,
+                    remaining: requests.collect(),
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Stream one LLM response, forwarding tokens, reasoning and usage as runtime events
+    async fn stream_response(
+        &self,
+        ctx: &AgentLoopContext,
+        request: StreamTextRequest,
+    ) -> Result<StreamedResponse, String> {
+        let mut response = StreamedResponse::default();
+        let (snapshot_tx, mut snapshot_rx) = watch::channel((String::new(), Vec::new()));
+        let mut last_snapshot: Option<Instant> = None;
+        let started = Instant::now();
+
+        let mut on_event = |event: StreamEvent| {
+            if response.first_token.is_none()
+                && matches!(
+                    event,
+                    StreamEvent::TextDelta { .. }
+                        | StreamEvent::ReasoningDelta { .. }
+                        | StreamEvent::ToolCall { .. }
+                )
+            {
+                response.first_token = Some(started.elapsed());
+            }
+            match event {
+                StreamEvent::TextDelta { text } => {
+                    self.stream_token(&ctx.session_id, &text);
+                    response.text.push_str(&text);
+                }
+                StreamEvent::ReasoningStart {
+                    id,
+                    provider_metadata,
+                } => {
+                    let block = response.reasoning_block(id);
+                    merge_metadata(&mut block.provider_metadata, provider_metadata);
+                }
+                StreamEvent::ReasoningDelta {
+                    id,
+                    text,
+                    provider_metadata,
+                } => {
+                    let block = response.reasoning_block(id);
+                    block.text.push_str(&text);
+                    merge_metadata(&mut block.provider_metadata, provider_metadata);
+                    if !text.is_empty() {
+                        let _ = self.event_sender.send(RuntimeEvent::Reasoning {
+                            session_id: ctx.session_id.clone(),
+                            text,
+                        });
+                    }
+                }
+                StreamEvent::Usage {
+                    input_tokens,
+                    output_tokens,
+                    cached_input_tokens,
+                    cache_creation_input_tokens,
+                    ..
+                } => {
+                    let _ = self.event_sender.send(RuntimeEvent::Usage {
+                        task_id: ctx.task_id.clone(),
+                        input_tokens,
+                        output_tokens,
+                        cached_input_tokens,
+                    });
+                    response.usage = Some(TokenUsage {
+                        input_tokens: input_tokens.max(0) as u32,
+                        output_tokens: output_tokens.max(0) as u32,
+                        cached_input_tokens: cached_input_tokens.map(|tokens| tokens.max(0) as u32),
+                        cache_creation_input_tokens: cache_creation_input_tokens
+                            .map(|tokens| tokens.max(0) as u32),
+                    });
+                }
+                StreamEvent::ToolCall {
+                    tool_call_id,
+                    tool_name,
+                    input,
+                    ..
+                } => response.tool_calls.push(ToolCall {
+                    id: tool_call_id,
+                    name: tool_name,
+                    input,
+                }),
+                StreamEvent::Error { message } => {
+                    response.error.get_or_insert(message);
+                }
+                StreamEvent::SecretsRedacted { redactions } => {
+                    let _ = self.event_sender.send(RuntimeEvent::SecretsRedacted {
+                        task_id: ctx.task_id.clone(),
+                        session_id: ctx.session_id.clone(),
+                        redactions,
+                    });
+                }
+                _ => {}
+            }
+
+            if self.stream_recorder.is_some()
+                && !last_snapshot.is_some_and(|at| at.elapsed() < stream_state::SAVE_INTERVAL)
+            {
+                last_snapshot = Some(Instant::now());
+                let _ = snapshot_tx.send((response.text.clone(), response.tool_calls.clone()));
+            }
+        };
+        let save_snapshots = async {
+            if let Some(recorder) = &self.stream_recorder {
+                while snapshot_rx.changed().await.is_ok() {
+                    let (text, tool_calls) = snapshot_rx.borrow_and_update().clone();
+                    recorder.save(ctx, &text, &tool_calls).await;
+                }
+            }
+            std::future::pending::<()>().await
         };
 
+        // Dropping the stream future closes the underlying HTTP response
+        let result = tokio::select! {
+            result = self.llm.stream(request, &mut on_event) => Some(result),
+            _ = ctx.cancel_token.cancelled() => None,
+            () = save_snapshots => unreachable!("saving snapshots never finishes"),
+        };
+        if let (Some(metrics), Some(result)) = (&self.metrics, &result) {
+            let error = result.as_ref().err().or(response.error.as_ref());
+            metrics.record_llm_call(
+                &ctx.task_id,
+                &ctx.session_id,
+                started.elapsed(),
+                response.first_token,
+                error.map(String::as_str),
+            );
+        }
+        let cancelled = match result {
+            Some(result) => {
+                result?;
+                false
+            }
+            None => true,
+        };
+        response.cancelled = cancelled;
+
+        // Tool calls arrive at the end of the stream, so save the whole response once more
+        if let (Some(recorder), false) = (&self.stream_recorder, cancelled) {
+            recorder
+                .save(ctx, &response.text, &response.tool_calls)
+                .await;
+        }
+        Ok(response)
+    }
+
+    /// Dispatch a tool call, executing it immediately unless it needs approval
+    pub async fn handle_tool_call(
+        &self,
+        ctx: &AgentLoopContext,
+        request: ToolRequest,
+    ) -> Result<ToolDispatchResult, String> {
         // Check auto-approve settings
         let auto_approve = ctx.settings.auto_approve_edits.unwrap_or(false);
+        let tool_name = request.name.clone();
+        let _ = self.event_sender.send(RuntimeEvent::ToolCallStarted {
+            task_id: ctx.task_id.clone(),
+            request: request.clone(),
+        });
 
-        match self
+        let started = Instant::now();
+        let dispatch_result = self
             .tool_dispatcher
-            .dispatch(request, tool_context, auto_approve)
-            .await
-        {
-            Ok(ToolDispatchResult::Completed(result)) => Ok(result),
-            Ok(ToolDispatchResult::PendingApproval(request)) => {
-                // Emit event for approval required
-                let _ = self.event_sender.send(RuntimeEvent::ToolCallRequested {
+            .dispatch(request, Self::tool_context(ctx), auto_approve)
+            .await?;
+
+        match dispatch_result {
+            ToolDispatchResult::Completed(result) => {
+                self.record_tool_call(ctx, &tool_name, started, &result);
+                let _ = self.event_sender.send(RuntimeEvent::ToolCallCompleted {
                     task_id: ctx.task_id.clone(),
-                    request,
+                    result: result.clone(),
                 });
-                Err("Tool requires approval".to_string())
+                Ok(ToolDispatchResult::Completed(
+                    self.truncate_result(ctx, &tool_name, result).await,
+                ))
             }
-            Err(e) => Err(e),
+            pending => Ok(pending),
         }
     }
 
@@ -120,18 +696,17 @@ impl AgentLoop {
         ctx: &AgentLoopContext,
         request: ToolRequest,
     ) -> ToolResult {
-        let tool_context = ToolContext {
-            session_id: ctx.session_id.clone(),
+        let tool_name = request.name.clone();
+        let _ = self.event_sender.send(RuntimeEvent::ToolCallStarted {
             task_id: ctx.task_id.clone(),
-            workspace_root: ctx.workspace_root.clone(),
-            worktree_path: ctx.worktree_path.clone(),
-            settings: ctx.settings.clone(),
-        };
-
+            request: request.clone(),
+        });
+        let started = Instant::now();
         let result = self
             .tool_dispatcher
-            .execute_approved(request.clone(), tool_context)
+            .execute_approved(request, Self::tool_context(ctx))
             .await;
+        self.record_tool_call(ctx, &tool_name, started, &result);
 
         // Emit completion event
         let _ = self.event_sender.send(RuntimeEvent::ToolCallCompleted {
@@ -139,42 +714,284 @@ impl AgentLoop {
             result: result.clone(),
         });
 
+        self.truncate_result(ctx, &tool_name, result).await
+    }
+
+    fn record_tool_call(
+        &self,
+        ctx: &AgentLoopContext,
+        tool_name: &str,
+        started: Instant,
+        result: &ToolResult,
+    ) {
+        if let Some(metrics) = &self.metrics {
+            let error = (!result.success).then(|| result.error.as_deref().unwrap_or("failed"));
+            metrics.record_tool_call(
+                &ctx.task_id,
+                &ctx.session_id,
+                tool_name,
+                started.elapsed(),
+                error,
+            );
+        }
+    }
+
+    /// Cut an oversized tool output down with the tool's truncation strategy.
+    /// Completion events carry the full output; the context gets this one.
+    async fn truncate_result(
+        &self,
+        ctx: &AgentLoopContext,
+        tool_name: &str,
+        mut result: ToolResult,
+    ) -> ToolResult {
+        let truncation = self.config.tool_truncation.for_tool(tool_name);
+        let text = truncation::output_text(&result.output);
+        if text.len() <= truncation.max_chars {
+            return result;
+        }
+
+        let stored = match (truncation.strategy, &self.attachments) {
+            (TruncationStrategy::Attachment, Some(attachments)) => {
+                match store_output(attachments, ctx, tool_name, &result.tool_call_id, &text).await {
+                    Ok(attachment) => Some(attachment),
+                    Err(e) => {
+                        log::warn!("Failed to store output of {}: {}", tool_name, e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        result.output = match stored {
+            Some(attachment) => serde_json::json!({
+                "truncated": true,
+                "attachmentId": attachment.id,
+                "path": attachment.path,
+                "size": text.len(),
+                "preview": truncation::truncate_text(
+                    &text,
+                    TruncationStrategy::Head,
+                    truncation::ATTACHMENT_PREVIEW_CHARS
+                ),
+            }),
+            None => {
+                // Without an attachment store the output is cut in the middle
+                let strategy = match truncation.strategy {
+                    TruncationStrategy::Attachment => TruncationStrategy::MiddleOut,
+                    strategy => strategy,
+                };
+                truncation::truncate_output(&result.output, strategy, truncation.max_chars)
+                    .unwrap_or(result.output)
+            }
+        };
+        result
+    }
+
+    /// Report a tool call the user denied
+    pub fn deny_tool(
+        &self,
+        ctx: &AgentLoopContext,
+        request: ToolRequest,
+        reason: Option<String>,
+    ) -> ToolResult {
+        let error = match reason {
+            Some(reason) => format!("User denied the tool call: {}", reason),
+            None => "User denied the tool call".to_string(),
+        };
+        let result = ToolResult {
+            tool_call_id: request.tool_call_id,
+            success: false,
+            output: serde_json::Value::Null,
+            error: Some(error),
+        };
+
+        let _ = self.event_sender.send(RuntimeEvent::ToolCallCompleted {
+            task_id: ctx.task_id.clone(),
+            result: result.clone(),
+        });
+
         result
     }
 
-    /// Build LLM prompt from context
-    fn build_prompt(&self, ctx: &AgentLoopContext) -> Result<String, String> {
-        // In a full implementation, this would:
-        // 1. Convert messages to LLM format
-        // 2. Add system prompt from agent configuration
-        // 3. Add tool definitions if tools are enabled
-        // 4. Apply any prompt engineering
+    /// Append a tool result message to the context
+    pub fn append_tool_result(&self, ctx: &mut AgentLoopContext, result: ToolResult) {
+        let output = match result.error {
+            Some(error) if !result.success => {
+                let mut output = serde_json::Map::new();
+                output.insert("error".to_string(), serde_json::json!(error));
+                // Keep the error context the dispatcher attached
+                if let serde_json::Value::Object(context) = result.output {
+                    output.extend(context);
+                }
+                serde_json::Value::Object(output)
+            }
+            _ => result.output,
+        };
 
-        let mut prompt = String::new();
+        let message = new_message(
+            ctx,
+            MessageRole::Tool,
+            MessageContent::ToolResult { result: output },
+            Some(result.tool_call_id),
+        );
+        ctx.messages.push(message);
+    }
 
-        // Add messages
-        for message in &ctx.messages {
-            let role_str = match message.role {
-                MessageRole::User => "User",
-                MessageRole::Assistant => "Assistant",
-                MessageRole::System => "System",
-                MessageRole::Tool => "Tool",
-            };
+    fn tool_context(ctx: &AgentLoopContext) -> ToolContext {
+        ToolContext {
+            session_id: ctx.session_id.clone(),
+            task_id: ctx.task_id.clone(),
+            workspace_root: ctx.workspace_root.clone(),
+            worktree_path: ctx.worktree_path.clone(),
+            settings: ctx.settings.clone(),
+            cancel_token: ctx.cancel_token.clone(),
+        }
+    }
 
-            let content_str = match &message.content {
-                MessageContent::Text { text } => text.clone(),
-                MessageContent::ToolCalls { calls } => {
-                    format!("Tool calls: {:?}", calls)
+    fn is_tool_available(&self, name: &str) -> bool {
+        self.config.enable_tools
+            && (self.config.available_tools.is_empty()
+                || self.config.available_tools.iter().any(|tool| tool == name))
+    }
+
+    /// Sessions only get the tools their settings permit; plan mode and
+    /// read-only tasks further limit them to read-only ones
+    async fn is_tool_allowed(&self, name: &str, settings: &TaskSettings) -> bool {
+        let registry = self.tool_dispatcher.registry();
+        self.is_tool_available(name)
+            && registry.is_permitted(name, settings).await
+            && (!self.read_only_tools() || registry.is_read_only(name).await)
+    }
+
+    fn read_only_tools(&self) -> bool {
+        self.config.plan_mode || self.config.read_only_tools
+    }
+
+    /// Tool definitions sent to the model, sorted by name for a stable prompt
+    async fn tool_definitions(&self, settings: &TaskSettings) -> Option<Vec<LlmToolDefinition>> {
+        if !self.config.enable_tools {
+            return None;
+        }
+
+        let registry = self.tool_dispatcher.registry();
+        let registered = if self.read_only_tools() {
+            registry.list_read_only_tools().await
+        } else {
+            registry.list_tools().await
+        };
+        let mut tools: Vec<LlmToolDefinition> = registered
+            .into_iter()
+            .filter(|tool| self.is_tool_available(&tool.name) && tool.is_permitted(settings))
+            .chain(self.config.plan_mode.then(plan::submit_plan_definition))
+            .map(|tool| LlmToolDefinition {
+                tool_type: "function".to_string(),
+                name: tool.name,
+                description: Some(tool.description),
+                parameters: tool.parameters,
+                strict: false,
+            })
+            .collect();
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+
+        (!tools.is_empty()).then_some(tools)
+    }
+
+    /// Convert session history to LLM messages. Consecutive assistant messages
+    /// (reasoning, text, then tool calls) and consecutive tool results are
+    /// merged into single turns, as the providers expect. Reasoning is only
+    /// sent to interleaved-thinking models, and only for the tool calls made
+    /// since the last user message.
+    fn build_messages(&self, ctx: &AgentLoopContext, interleaved: bool) -> Vec<LlmMessage> {
+        let mut messages = Vec::new();
+        if let Some(system_prompt) = &self.config.system_prompt {
+            messages.push(LlmMessage::System {
+                content: system_prompt.clone(),
+                provider_options: None,
+            });
+        }
+
+        let current_turn = ctx
+            .messages
+            .iter()
+            .rposition(|message| message.role == MessageRole::User)
+            .unwrap_or(0);
+        let mut tool_names: HashMap<&str, &str> = HashMap::new();
+        for (index, message) in ctx.messages.iter().enumerate() {
+            match (&message.role, &message.content) {
+                (MessageRole::System, MessageContent::Text { text }) => {
+                    messages.push(LlmMessage::System {
+                        content: text.clone(),
+                        provider_options: None,
+                    });
                 }
-                MessageContent::ToolResult { result } => {
-                    format!("Tool result: {:?}", result)
+                (MessageRole::User, MessageContent::Text { text }) => {
+                    messages.push(LlmMessage::User {
+                        content: LlmMessageContent::Text(text.clone()),
+                        provider_options: None,
+                    });
                 }
-            };
-
-            prompt.push_str(&format!("{}: {}\n", role_str, content_str));
+                (
+                    MessageRole::Assistant,
+                    MessageContent::Reasoning {
+                        text,
+                        provider_metadata,
+                    },
+                ) => {
+                    if interleaved && index > current_turn {
+                        push_assistant_part(
+                            &mut messages,
+                            ContentPart::Reasoning {
+                                text: text.clone(),
+                                provider_options: provider_metadata.clone(),
+                            },
+                        );
+                    }
+                }
+                (MessageRole::Assistant, MessageContent::Text { text }) => {
+                    push_assistant_part(&mut messages, ContentPart::Text { text: text.clone() });
+                }
+                (MessageRole::Assistant, MessageContent::ToolCalls { calls }) => {
+                    for call in calls {
+                        tool_names.insert(&call.id, &call.name);
+                        push_assistant_part(
+                            &mut messages,
+                            ContentPart::ToolCall {
+                                tool_call_id: call.id.clone(),
+                                tool_name: call.name.clone(),
+                                input: call.input.clone(),
+                                provider_metadata: None,
+                            },
+                        );
+                    }
+                }
+                (MessageRole::Tool, MessageContent::ToolResult { result }) => {
+                    let tool_call_id = message.tool_call_id.clone().unwrap_or_default();
+                    let tool_name = tool_names
+                        .get(tool_call_id.as_str())
+                        .copied()
+                        .unwrap_or_default()
+                        .to_string();
+                    push_tool_part(
+                        &mut messages,
+                        ContentPart::ToolResult {
+                            tool_call_id,
+                            tool_name,
+                            output: result.clone(),
+                        },
+                    );
+                }
+                (role, _) => {
+                    log::warn!(
+                        "Skipping message {} with unsupported content for role {:?}",
+                        message.id,
+                        role
+                    );
+                }
+            }
         }
 
-        Ok(prompt)
+        messages
     }
 
     /// Stream a token to the event channel
@@ -186,6 +1003,101 @@ impl AgentLoop {
     }
 }
 
+fn new_message(
+    ctx: &AgentLoopContext,
+    role: MessageRole,
+    content: MessageContent,
+    tool_call_id: Option<ToolCallId>,
+) -> Message {
+    Message {
+        id: format!("msg_{}", uuid::Uuid::new_v4()),
+        session_id: ctx.session_id.clone(),
+        role,
+        content,
+        created_at: chrono::Utc::now().timestamp(),
+        tool_call_id,
+        parent_id: None,
+        pinned: false,
+    }
+}
+
+/// Store a tool output as an attachment of the task's session
+async fn store_output(
+    attachments: &AttachmentsRepository,
+    ctx: &AgentLoopContext,
+    tool_name: &str,
+    tool_call_id: &str,
+    text: &str,
+) -> Result<Attachment, String> {
+    let attachment = Attachment {
+        id: format!("att_{}", uuid::Uuid::new_v4().to_string().replace("-", "")),
+        session_id: ctx.session_id.clone(),
+        filename: format!("{}-{}.txt", tool_name, tool_call_id),
+        mime_type: "text/plain".to_string(),
+        size: text.len() as i64,
+        path: String::new(),
+        created_at: chrono::Utc::now().timestamp(),
+        origin: AttachmentOrigin::ToolOutput,
+    };
+    attachments
+        .create_attachment(&attachment, text.as_bytes())
+        .await?;
+
+    // The repository decides where the file goes
+    attachments
+        .get_attachment(&attachment.id)
+        .await?
+        .ok_or_else(|| format!("Attachment {} was not stored", attachment.id))
+}
+
+/// Merge provider metadata that arrives in pieces over a reasoning block,
+/// such as an item ID at the start and a signature at the end
+fn merge_metadata(target: &mut Option<serde_json::Value>, update: Option<serde_json::Value>) {
+    let Some(update) = update else {
+        return;
+    };
+    match (target.as_mut(), update) {
+        (Some(serde_json::Value::Object(target)), serde_json::Value::Object(update)) => {
+            for (provider, fields) in update {
+                let existing = target.entry(provider).or_insert(serde_json::Value::Null);
+                match (existing, fields) {
+                    (serde_json::Value::Object(existing), serde_json::Value::Object(fields)) => {
+                        existing.extend(fields)
+                    }
+                    (existing, fields) => *existing = fields,
+                }
+            }
+        }
+        (_, update) => *target = Some(update),
+    }
+}
+
+fn push_assistant_part(messages: &mut Vec<LlmMessage>, part: ContentPart) {
+    if let Some(LlmMessage::Assistant {
+        content: LlmMessageContent::Parts(parts),
+        ..
+    }) = messages.last_mut()
+    {
+        parts.push(part);
+        return;
+    }
+    messages.push(LlmMessage::Assistant {
+        content: LlmMessageContent::Parts(vec![part]),
+        provider_options: None,
+    });
+}
+
+fn push_tool_part(messages: &mut Vec<LlmMessage>, part: ContentPart) {
+    if let Some(LlmMessage::Tool { content, .. }) = messages.last_mut() {
+        content.push(part);
+        return;
+    }
+    messages.push(LlmMessage::Tool {
+        content: vec![part],
+        provider_options: None,
+    });
+}
+
 /// Factory for creating agent loops with different configurations
 pub struct AgentLoopFactory;
 
@@ -193,104 +1105,831 @@ impl AgentLoopFactory {
     /// Create a standard agent loop
     pub fn create_standard(
         tool_registry: Arc<ToolRegistry>,
+        llm: Arc<dyn LlmClient>,
         event_sender: EventSender,
     ) -> AgentLoop {
         let config = AgentLoopConfig::default();
         let tool_dispatcher = Arc::new(ToolDispatcher::new(tool_registry));
 
-        AgentLoop::new(config, tool_dispatcher, event_sender)
+        AgentLoop::new(config, tool_dispatcher, llm, event_sender)
     }
 
     /// Create an agent loop with custom configuration
     pub fn create_with_config(
         config: AgentLoopConfig,
         tool_registry: Arc<ToolRegistry>,
+        llm: Arc<dyn LlmClient>,
         event_sender: EventSender,
     ) -> AgentLoop {
         let tool_dispatcher = Arc::new(ToolDispatcher::new(tool_registry));
 
-        AgentLoop::new(config, tool_dispatcher, event_sender)
+        AgentLoop::new(config, tool_dispatcher, llm, event_sender)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::truncation::ToolTruncation;
+    use crate::llm::types::ModelConfig;
+    use async_trait::async_trait;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
 
-    async fn create_test_loop() -> (AgentLoop, mpsc::UnboundedReceiver<RuntimeEvent>) {
-        let (tx, rx) = mpsc::unbounded_channel();
-        let registry = Arc::new(ToolRegistry::create_default().await);
-        let dispatcher = Arc::new(ToolDispatcher::new(registry));
+    /// LLM client that replays scripted responses and records requests
+    struct ScriptedLlm {
+        responses: Mutex<VecDeque<Vec<StreamEvent>>>,
+        requests: Mutex<Vec<StreamTextRequest>>,
+        context_length: Option<u32>,
+        interleaved: bool,
+    }
 
-        let loop_instance = AgentLoop::new(AgentLoopConfig::default(), dispatcher, tx);
+    impl ScriptedLlm {
+        fn new(responses: Vec<Vec<StreamEvent>>) -> Arc<Self> {
+            Self::with_context_length(responses, None)
+        }
 
-        (loop_instance, rx)
+        fn with_context_length(
+            responses: Vec<Vec<StreamEvent>>,
+            context_length: Option<u32>,
+        ) -> Arc<Self> {
+            Arc::new(Self {
+                responses: Mutex::new(responses.into()),
+                requests: Mutex::new(Vec::new()),
+                context_length,
+                interleaved: false,
+            })
+        }
+
+        fn interleaved(responses: Vec<Vec<StreamEvent>>) -> Arc<Self> {
+            Arc::new(Self {
+                responses: Mutex::new(responses.into()),
+                requests: Mutex::new(Vec::new()),
+                context_length: None,
+                interleaved: true,
+            })
+        }
     }
 
-    #[tokio::test]
-    async fn test_agent_loop_placeholder() {
-        let (agent_loop, _rx) = create_test_loop().await;
+    #[async_trait]
+    impl LlmClient for ScriptedLlm {
+        async fn stream(
+            &self,
+            request: StreamTextRequest,
+            on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            self.requests.lock().unwrap().push(request);
+            let events = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or("No scripted response")?;
+            for event in events {
+                on_event(event);
+            }
+            Ok(())
+        }
+
+        async fn context_length(&self, _model: &str) -> Option<u32> {
+            self.context_length
+        }
+
+        async fn model_config(&self, _model: &str) -> Option<ModelConfig> {
+            serde_json::from_value(serde_json::json!({
+                "name": "Scripted",
+                "interleaved": self.interleaved,
+                "providers": ["test"]
+            }))
+            .ok()
+        }
+    }
+
+    /// LLM client that streams one token and then never finishes
+    struct HangingLlm;
+
+    #[async_trait]
+    impl LlmClient for HangingLlm {
+        async fn stream(
+            &self,
+            _request: StreamTextRequest,
+            on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            on_event(text("Partial"));
+            std::future::pending::<()>().await;
+            Ok(())
+        }
+    }
+
+    fn text(text: &str) -> StreamEvent {
+        StreamEvent::TextDelta {
+            text: text.to_string(),
+        }
+    }
+
+    fn tool_call(id: &str, name: &str) -> StreamEvent {
+        StreamEvent::ToolCall {
+            tool_call_id: id.to_string(),
+            tool_name: name.to_string(),
+            input: serde_json::json!({ "path": "README.md" }),
+            provider_metadata: None,
+        }
+    }
+
+    fn done() -> StreamEvent {
+        StreamEvent::Done {
+            finish_reason: Some("stop".to_string()),
+        }
+    }
+
+    fn message(role: MessageRole, content: MessageContent) -> Message {
+        Message {
+            id: format!("msg-{:?}", role),
+            session_id: "test".to_string(),
+            role,
+            content,
+            created_at: 0,
+            tool_call_id: None,
+            parent_id: None,
+            pinned: false,
+        }
+    }
 
-        let ctx = AgentLoopContext {
+    fn create_context(messages: Vec<Message>) -> AgentLoopContext {
+        AgentLoopContext {
             session_id: "test-session".to_string(),
             task_id: "test-task".to_string(),
             workspace_root: "/tmp".to_string(),
             worktree_path: None,
             settings: TaskSettings::default(),
-            messages: vec![],
+            messages,
+            cancel_token: CancellationToken::new(),
+            compactions: vec![],
+            usage: BudgetUsage::default(),
+            usage_clock: Instant::now(),
+        }
+    }
+
+    async fn create_test_loop(
+        config: AgentLoopConfig,
+        llm: Arc<dyn LlmClient>,
+    ) -> (AgentLoop, mpsc::UnboundedReceiver<RuntimeEvent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let registry = Arc::new(ToolRegistry::create_default().await);
+        let dispatcher = Arc::new(ToolDispatcher::new(registry));
+
+        let loop_instance = AgentLoop::new(config, dispatcher, llm, tx);
+
+        (loop_instance, rx)
+    }
+
+    fn drain_events(rx: &mut mpsc::UnboundedReceiver<RuntimeEvent>) -> Vec<RuntimeEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_agent_loop_streams_text_response() {
+        let llm = ScriptedLlm::new(vec![vec![
+            StreamEvent::ReasoningDelta {
+                id: "r1".to_string(),
+                text: "thinking".to_string(),
+                provider_metadata: None,
+            },
+            text("Hello"),
+            text(" world"),
+            StreamEvent::Usage {
+                input_tokens: 10,
+                output_tokens: 2,
+                total_tokens: Some(12),
+                cached_input_tokens: None,
+                cache_creation_input_tokens: None,
+            },
+            done(),
+        ]]);
+        let (agent_loop, mut rx) = create_test_loop(AgentLoopConfig::default(), llm).await;
+        let mut ctx = create_context(vec![message(
+            MessageRole::User,
+            MessageContent::Text {
+                text: "Hi".to_string(),
+            },
+        )]);
+
+        let result = agent_loop.run(&mut ctx).await.unwrap();
+        match result {
+            AgentLoopResult::Completed { message } => assert_eq!(message, "Hello world"),
+            other => panic!("Expected Completed result, got {:?}", other),
+        }
+        assert_eq!(ctx.messages.len(), 2);
+        assert_eq!(ctx.messages[1].role, MessageRole::Assistant);
+
+        let events = drain_events(&mut rx);
+        let tokens: Vec<&str> = events
+            .iter()
+            .filter_map(|event| match event {
+                RuntimeEvent::Token { token, .. } => Some(token.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tokens, vec!["Hello", " world"]);
+        assert!(events.iter().any(
+            |event| matches!(event, RuntimeEvent::Reasoning { text, .. } if text == "thinking")
+        ));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            RuntimeEvent::Usage {
+                input_tokens: 10,
+                output_tokens: 2,
+                ..
+            }
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_agent_loop_executes_tool_calls_and_iterates() {
+        let llm = ScriptedLlm::new(vec![
+            vec![text("Reading"), tool_call("call-1", "read_file"), done()],
+            vec![text("Done"), done()],
+        ]);
+        let (agent_loop, mut rx) = create_test_loop(AgentLoopConfig::default(), llm.clone()).await;
+        let mut ctx = create_context(vec![message(
+            MessageRole::User,
+            MessageContent::Text {
+                text: "Read the readme".to_string(),
+            },
+        )]);
+
+        let result = agent_loop.run(&mut ctx).await.unwrap();
+        assert!(matches!(result, AgentLoopResult::Completed { ref message } if message == "Done"));
+
+        // user, assistant text, assistant tool calls, tool result, final assistant text
+        assert_eq!(ctx.messages.len(), 5);
+        assert!(matches!(
+            ctx.messages[2].content,
+            MessageContent::ToolCalls { ref calls } if calls[0].name == "read_file"
+        ));
+        assert_eq!(ctx.messages[3].role, MessageRole::Tool);
+        assert_eq!(ctx.messages[3].tool_call_id.as_deref(), Some("call-1"));
+
+        let requests = llm.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let tools = requests[0].tools.as_ref().expect("tools should be sent");
+        assert!(tools.iter().any(|tool| tool.name == "read_file"));
+
+        // The follow-up request merges text and tool call into one assistant turn
+        let follow_up = &requests[1].messages;
+        assert_eq!(follow_up.len(), 3);
+        match &follow_up[1] {
+            LlmMessage::Assistant {
+                content: LlmMessageContent::Parts(parts),
+                ..
+            } => assert_eq!(parts.len(), 2),
+            other => panic!("Expected assistant parts, got {:?}", other),
+        }
+        match &follow_up[2] {
+            LlmMessage::Tool { content, .. } => assert!(matches!(
+                &content[0],
+                ContentPart::ToolResult { tool_call_id, tool_name, .. }
+                    if tool_call_id == "call-1" && tool_name == "read_file"
+            )),
+            other => panic!("Expected tool message, got {:?}", other),
+        }
+
+        let events = drain_events(&mut rx);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, RuntimeEvent::IterationStarted { iteration: 1, .. })));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            RuntimeEvent::ToolCallStarted { request, .. } if request.name == "read_file"
+        )));
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, RuntimeEvent::ToolCallCompleted { .. })));
+    }
+
+    fn reasoning_response(signature: &str) -> Vec<StreamEvent> {
+        vec![
+            StreamEvent::ReasoningStart {
+                id: "r1".to_string(),
+                provider_metadata: None,
+            },
+            StreamEvent::ReasoningDelta {
+                id: "r1".to_string(),
+                text: "Check the readme".to_string(),
+                provider_metadata: None,
+            },
+            StreamEvent::ReasoningDelta {
+                id: "r1".to_string(),
+                text: String::new(),
+                provider_metadata: Some(
+                    serde_json::json!({ "anthropic": { "signature": signature } }),
+                ),
+            },
+            StreamEvent::ReasoningEnd {
+                id: "r1".to_string(),
+            },
+            tool_call("call-1", "read_file"),
+            done(),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_interleaved_reasoning_is_sent_back_with_tool_calls() {
+        let llm = ScriptedLlm::interleaved(vec![
+            reasoning_response("sig-1"),
+            vec![text("Done"), done()],
+        ]);
+        let (agent_loop, _rx) = create_test_loop(AgentLoopConfig::default(), llm.clone()).await;
+        let mut ctx = create_context(vec![message(
+            MessageRole::User,
+            MessageContent::Text {
+                text: "Read the readme".to_string(),
+            },
+        )]);
+
+        agent_loop.run(&mut ctx).await.unwrap();
+        assert!(matches!(
+            &ctx.messages[1].content,
+            MessageContent::Reasoning { text, provider_metadata: Some(metadata) }
+                if text == "Check the readme" && metadata["anthropic"]["signature"] == "sig-1"
+        ));
+
+        // The reasoning leads the assistant turn that made the tool call
+        let requests = llm.requests.lock().unwrap();
+        match &requests[1].messages[1] {
+            LlmMessage::Assistant {
+                content: LlmMessageContent::Parts(parts),
+                ..
+            } => {
+                assert!(matches!(
+                    &parts[0],
+                    ContentPart::Reasoning { text, provider_options: Some(options) }
+                        if text == "Check the readme" && options["anthropic"]["signature"] == "sig-1"
+                ));
+                assert!(matches!(&parts[1], ContentPart::ToolCall { .. }));
+            }
+            other => panic!("Expected assistant parts, got {:?}", other),
+        }
+        drop(requests);
+
+        // A new user turn leaves the earlier reasoning out
+        ctx.messages.push(message(
+            MessageRole::User,
+            MessageContent::Text {
+                text: "Thanks".to_string(),
+            },
+        ));
+        let messages = agent_loop.build_messages(&ctx, true);
+        assert!(!messages.iter().any(|message| matches!(
+            message,
+            LlmMessage::Assistant { content: LlmMessageContent::Parts(parts), .. }
+                if parts.iter().any(|part| matches!(part, ContentPart::Reasoning { .. }))
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_is_dropped_for_other_models() {
+        let llm = ScriptedLlm::new(vec![
+            reasoning_response("sig-1"),
+            vec![text("Done"), done()],
+        ]);
+        let (agent_loop, _rx) = create_test_loop(AgentLoopConfig::default(), llm.clone()).await;
+        let mut ctx = create_context(vec![message(
+            MessageRole::User,
+            MessageContent::Text {
+                text: "Read the readme".to_string(),
+            },
+        )]);
+
+        agent_loop.run(&mut ctx).await.unwrap();
+        assert!(!ctx
+            .messages
+            .iter()
+            .any(|message| matches!(message.content, MessageContent::Reasoning { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_tool_output_truncation_per_tool() {
+        let mut config = AgentLoopConfig::default();
+        config.tool_truncation.tools.insert(
+            "read_file".to_string(),
+            ToolTruncation {
+                strategy: TruncationStrategy::Head,
+                max_chars: 100,
+            },
+        );
+        config.tool_truncation.tools.insert(
+            "search_files".to_string(),
+            ToolTruncation {
+                strategy: TruncationStrategy::Attachment,
+                max_chars: 100,
+            },
+        );
+        let (agent_loop, _rx) = create_test_loop(config, ScriptedLlm::new(vec![])).await;
+        let ctx = create_context(vec![]);
+        let result = |output: &str| ToolResult {
+            tool_call_id: "call-1".to_string(),
+            success: true,
+            output: serde_json::json!({ "content": output }),
+            error: None,
         };
+        let long = "line\n".repeat(100);
+
+        let truncated = agent_loop
+            .truncate_result(&ctx, "read_file", result(&long))
+            .await;
+        let content = truncated.output["content"].as_str().unwrap();
+        assert!(content.starts_with("line\nline"));
+        assert!(content.contains("more characters truncated"));
+
+        // Other tools use the default limit
+        let kept = agent_loop
+            .truncate_result(&ctx, "list_files", result(&long))
+            .await;
+        assert_eq!(kept.output["content"], long.as_str());
 
-        let result = agent_loop.run_iteration(&ctx).await;
-        assert!(result.is_ok());
+        // Without an attachment store the output is cut in the middle
+        let truncated = agent_loop
+            .truncate_result(&ctx, "search_files", result(&long))
+            .await;
+        assert!(truncated.output["content"]
+            .as_str()
+            .unwrap()
+            .contains("characters truncated ..."));
+    }
+
+    #[tokio::test]
+    async fn test_agent_loop_waits_for_approval() {
+        let llm = ScriptedLlm::new(vec![vec![
+            tool_call("call-1", "write_file"),
+            tool_call("call-2", "read_file"),
+            done(),
+        ]]);
+        let (agent_loop, _rx) = create_test_loop(AgentLoopConfig::default(), llm).await;
+        let mut ctx = create_context(vec![]);
 
-        match result.unwrap() {
-            AgentLoopResult::Completed { message } => {
-                assert!(!message.is_empty());
+        let result = agent_loop.run(&mut ctx).await.unwrap();
+        match result {
+            AgentLoopResult::WaitingForApproval { request, remaining } => {
+                assert_eq!(request.tool_call_id, "call-1");
+                assert_eq!(request.name, "write_file");
+                // Calls after the pending one wait for the decision
+                assert_eq!(remaining.len(), 1);
+                assert_eq!(remaining[0].tool_call_id, "call-2");
             }
-            _ => panic!("Expected Completed result"),
+            other => panic!("Expected WaitingForApproval, got {:?}", other),
         }
+        assert_eq!(ctx.messages.len(), 1);
     }
 
     #[tokio::test]
-    async fn test_build_prompt() {
-        let (agent_loop, _rx) = create_test_loop().await;
-
-        let messages = vec![
-            Message {
-                id: "msg-1".to_string(),
-                session_id: "test".to_string(),
-                role: MessageRole::User,
-                content: MessageContent::Text {
-                    text: "Hello".to_string(),
-                },
-                created_at: 0,
-                tool_call_id: None,
-                parent_id: None,
+    async fn test_agent_loop_stops_at_max_iterations() {
+        let llm = ScriptedLlm::new(vec![
+            vec![tool_call("call-1", "read_file"), done()],
+            vec![tool_call("call-2", "read_file"), done()],
+        ]);
+        let config = AgentLoopConfig {
+            max_iterations: 2,
+            ..AgentLoopConfig::default()
+        };
+        let (agent_loop, _rx) = create_test_loop(config, llm).await;
+        let mut ctx = create_context(vec![]);
+
+        let result = agent_loop.run(&mut ctx).await.unwrap();
+        assert!(matches!(result, AgentLoopResult::MaxIterationsReached));
+    }
+
+    #[tokio::test]
+    async fn test_agent_loop_suspends_when_draining() {
+        let llm = ScriptedLlm::new(vec![vec![text("never sent"), done()]]);
+        let (agent_loop, _rx) = create_test_loop(AgentLoopConfig::default(), llm).await;
+        let drain_token = CancellationToken::new();
+        let agent_loop = agent_loop.with_drain_token(drain_token.clone());
+        let mut ctx = create_context(vec![]);
+
+        drain_token.cancel();
+        let result = agent_loop.run(&mut ctx).await.unwrap();
+        assert!(matches!(result, AgentLoopResult::Suspended));
+        assert_eq!(ctx.usage.iterations, 0);
+        assert!(ctx.messages.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_agent_loop_pauses_when_budget_exceeded() {
+        let llm = ScriptedLlm::new(vec![vec![
+            tool_call("call-1", "read_file"),
+            tool_call("call-2", "read_file"),
+            StreamEvent::Usage {
+                input_tokens: 900,
+                output_tokens: 150,
+                total_tokens: None,
+                cached_input_tokens: None,
+                cache_creation_input_tokens: None,
             },
-            Message {
-                id: "msg-2".to_string(),
-                session_id: "test".to_string(),
-                role: MessageRole::Assistant,
-                content: MessageContent::Text {
-                    text: "Hi there!".to_string(),
+            done(),
+        ]]);
+        let (agent_loop, _rx) = create_test_loop(AgentLoopConfig::default(), llm).await;
+        let mut ctx = create_context(vec![]);
+        ctx.settings.budget = Some(TaskBudget {
+            max_tokens: Some(1000),
+            ..TaskBudget::default()
+        });
+
+        let result = agent_loop.run(&mut ctx).await.unwrap();
+        match result {
+            AgentLoopResult::BudgetExceeded {
+                exceeded,
+                remaining,
+            } => {
+                assert_eq!(exceeded, vec![BudgetLimit::Tokens]);
+                // No tool runs once the budget is spent
+                assert_eq!(remaining.len(), 2);
+            }
+            other => panic!("Expected BudgetExceeded, got {:?}", other),
+        }
+        assert_eq!(ctx.messages.len(), 1);
+        assert_eq!(ctx.usage.iterations, 1);
+        assert_eq!(ctx.usage.input_tokens + ctx.usage.output_tokens, 1050);
+    }
+
+    #[tokio::test]
+    async fn test_agent_loop_rejects_unavailable_tools() {
+        let llm = ScriptedLlm::new(vec![
+            vec![tool_call("call-1", "execute_shell"), done()],
+            vec![text("ok"), done()],
+        ]);
+        let config = AgentLoopConfig {
+            available_tools: vec!["read_file".to_string()],
+            ..AgentLoopConfig::default()
+        };
+        let (agent_loop, _rx) = create_test_loop(config, llm.clone()).await;
+        let mut ctx = create_context(vec![]);
+
+        let result = agent_loop.run(&mut ctx).await.unwrap();
+        assert!(matches!(result, AgentLoopResult::Completed { .. }));
+        assert!(matches!(
+            &ctx.messages[1].content,
+            MessageContent::ToolResult { result } if result["error"].is_string()
+        ));
+
+        let requests = llm.requests.lock().unwrap();
+        let tools = requests[0].tools.as_ref().unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "read_file");
+    }
+
+    #[tokio::test]
+    async fn test_read_only_tasks_are_refused_mutating_tools() {
+        let llm = ScriptedLlm::new(vec![
+            vec![tool_call("call-1", "write_file"), done()],
+            vec![text("ok"), done()],
+        ]);
+        let config = AgentLoopConfig {
+            read_only_tools: true,
+            ..AgentLoopConfig::default()
+        };
+        let (agent_loop, _rx) = create_test_loop(config, llm.clone()).await;
+        let mut ctx = create_context(vec![]);
+
+        let result = agent_loop.run(&mut ctx).await.unwrap();
+        assert!(matches!(result, AgentLoopResult::Completed { .. }));
+        assert!(matches!(
+            &ctx.messages[1].content,
+            MessageContent::ToolResult { result } if result["error"].is_string()
+        ));
+
+        let requests = llm.requests.lock().unwrap();
+        let tools = requests[0].tools.as_ref().unwrap();
+        assert!(tools.iter().any(|tool| tool.name == "read_file"));
+        assert!(tools.iter().all(|tool| tool.name != "write_file"));
+    }
+
+    #[tokio::test]
+    async fn test_plan_mode_limits_tools_until_plan_is_submitted() {
+        let llm = ScriptedLlm::new(vec![
+            vec![text("The loader lives in config.rs"), done()],
+            vec![tool_call("call-1", "write_file"), done()],
+            vec![
+                StreamEvent::ToolCall {
+                    tool_call_id: "call-2".to_string(),
+                    tool_name: plan::SUBMIT_PLAN_TOOL.to_string(),
+                    input: serde_json::json!({
+                        "summary": "Rename the loader",
+                        "steps": ["Rename config.rs", "Fix imports"]
+                    }),
+                    provider_metadata: None,
                 },
-                created_at: 0,
-                tool_call_id: None,
-                parent_id: None,
+                done(),
+            ],
+        ]);
+        let config = AgentLoopConfig {
+            plan_mode: true,
+            ..AgentLoopConfig::default()
+        };
+        let (agent_loop, _rx) = create_test_loop(config, llm.clone()).await;
+        let mut ctx = create_context(vec![]);
+
+        let result = agent_loop.run(&mut ctx).await.unwrap();
+        match result {
+            AgentLoopResult::PlanSubmitted { plan } => {
+                assert_eq!(plan.summary, "Rename the loader");
+                assert_eq!(plan.steps.len(), 2);
+            }
+            other => panic!("Expected PlanSubmitted, got {:?}", other),
+        }
+
+        // Stopping without a plan adds a reminder, and write_file is refused
+        assert_eq!(ctx.messages[1].role, MessageRole::System);
+        assert!(matches!(
+            &ctx.messages[3].content,
+            MessageContent::ToolResult { result } if result["error"].is_string()
+        ));
+
+        let requests = llm.requests.lock().unwrap();
+        let names: Vec<&str> = requests[0]
+            .tools
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|tool| tool.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["git_status", "read_file", "search_files", "submit_plan"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_agent_loop_reports_stream_errors() {
+        let llm = ScriptedLlm::new(vec![vec![
+            text("Partial"),
+            StreamEvent::Error {
+                message: "rate limited".to_string(),
             },
-        ];
+        ]]);
+        let (agent_loop, _rx) = create_test_loop(AgentLoopConfig::default(), llm).await;
+        let mut ctx = create_context(vec![]);
 
-        let ctx = AgentLoopContext {
-            session_id: "test-session".to_string(),
-            task_id: "test-task".to_string(),
-            workspace_root: "/tmp".to_string(),
-            worktree_path: None,
-            settings: TaskSettings::default(),
-            messages,
+        let result = agent_loop.run(&mut ctx).await.unwrap();
+        assert!(
+            matches!(result, AgentLoopResult::Error { ref message } if message == "rate limited")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_agent_loop_cancels_llm_stream() {
+        let (agent_loop, _rx) =
+            create_test_loop(AgentLoopConfig::default(), Arc::new(HangingLlm)).await;
+        let mut ctx = create_context(vec![]);
+        let cancel_token = ctx.cancel_token.clone();
+
+        let canceller = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            cancel_token.cancel();
+        });
+        let result =
+            tokio::time::timeout(std::time::Duration::from_secs(1), agent_loop.run(&mut ctx))
+                .await
+                .expect("Stream was not cancelled")
+                .unwrap();
+        canceller.await.unwrap();
+
+        assert!(matches!(result, AgentLoopResult::Cancelled));
+        // The partial response the user already saw is kept
+        assert!(matches!(
+            &ctx.messages[0].content,
+            MessageContent::Text { text } if text == "Partial"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_dispatch_closes_tool_calls() {
+        let (agent_loop, _rx) =
+            create_test_loop(AgentLoopConfig::default(), ScriptedLlm::new(vec![])).await;
+        let mut ctx = create_context(vec![]);
+        ctx.cancel_token.cancel();
+
+        let requests = ["call-1", "call-2"]
+            .iter()
+            .map(|id| ToolRequest {
+                tool_call_id: id.to_string(),
+                name: "read_file".to_string(),
+                input: serde_json::json!({}),
+            })
+            .collect();
+        let result = agent_loop
+            .dispatch_tool_calls(&mut ctx, requests)
+            .await
+            .unwrap();
+
+        assert!(matches!(result, Some(AgentLoopResult::Cancelled)));
+        assert_eq!(ctx.messages.len(), 2);
+        for (message, id) in ctx.messages.iter().zip(["call-1", "call-2"]) {
+            assert_eq!(message.tool_call_id.as_deref(), Some(id));
+            assert!(matches!(
+                &message.content,
+                MessageContent::ToolResult { result } if result["error"] == "Tool call cancelled"
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_loop_compacts_long_context() {
+        let llm = ScriptedLlm::with_context_length(
+            vec![
+                vec![text("The user is refactoring the parser"), done()],
+                vec![text("Continuing"), done()],
+            ],
+            Some(1000),
+        );
+        let (agent_loop, mut rx) = create_test_loop(AgentLoopConfig::default(), llm.clone()).await;
+
+        let mut messages: Vec<Message> = (0..10)
+            .map(|i| Message {
+                id: format!("msg-{}", i),
+                ..message(
+                    MessageRole::User,
+                    MessageContent::Text {
+                        text: "x".repeat(400),
+                    },
+                )
+            })
+            .collect();
+        messages[0].pinned = true;
+        let mut ctx = create_context(messages);
+
+        let result = agent_loop.run(&mut ctx).await.unwrap();
+        assert!(matches!(result, AgentLoopResult::Completed { .. }));
+
+        // summary, pinned message, six recent messages, final answer
+        assert_eq!(ctx.messages.len(), 9);
+        assert_eq!(ctx.messages[0].role, MessageRole::System);
+        assert_eq!(ctx.messages[1].id, "msg-0");
+        assert_eq!(ctx.messages[2].id, "msg-4");
+
+        assert_eq!(ctx.compactions.len(), 1);
+        let compaction = &ctx.compactions[0];
+        assert_eq!(compaction.first_kept_message_id, "msg-4");
+        assert_eq!(compaction.compacted_count, 3);
+        assert!(compaction.tokens_after < compaction.tokens_before);
+        assert_eq!(ctx.messages[0].id, compaction.id);
+
+        // The model sees the summary instead of the compacted turns
+        let requests = llm.requests.lock().unwrap();
+        assert!(matches!(
+            &requests[1].messages[0],
+            LlmMessage::System { content, .. } if content.contains("refactoring the parser")
+        ));
+        assert_eq!(requests[1].messages.len(), 8);
+
+        let events = drain_events(&mut rx);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, RuntimeEvent::ContextCompacted { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_build_messages() {
+        let config = AgentLoopConfig {
+            system_prompt: Some("You are helpful".to_string()),
+            ..AgentLoopConfig::default()
         };
+        let (agent_loop, _rx) = create_test_loop(config, ScriptedLlm::new(vec![])).await;
 
-        let prompt = agent_loop.build_prompt(&ctx).unwrap();
-        assert!(prompt.contains("User: Hello"));
-        assert!(prompt.contains("Assistant: Hi there!"));
+        let ctx = create_context(vec![
+            message(
+                MessageRole::User,
+                MessageContent::Text {
+                    text: "Hello".to_string(),
+                },
+            ),
+            message(
+                MessageRole::Assistant,
+                MessageContent::Text {
+                    text: "Hi there!".to_string(),
+                },
+            ),
+        ]);
+
+        let messages = agent_loop.build_messages(&ctx);
+        assert_eq!(messages.len(), 3);
+        assert!(
+            matches!(&messages[0], LlmMessage::System { content, .. } if content == "You are helpful")
+        );
+        assert!(matches!(
+            &messages[1],
+            LlmMessage::User { content: LlmMessageContent::Text(text), .. } if text == "Hello"
+        ));
+        match &messages[2] {
+            LlmMessage::Assistant {
+                content: LlmMessageContent::Parts(parts),
+                ..
+            } => assert!(matches!(&parts[0], ContentPart::Text { text } if text == "Hi there!")),
+            other => panic!("Expected assistant message, got {:?}", other),
+        }
     }
 }
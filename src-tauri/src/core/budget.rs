@@ -0,0 +1,146 @@
+//! Task Budgets
+//!
+//! Limits on what a single task may spend: cost, tokens, LLM round trips and
+//! running time. The agent loop checks them after every LLM response and tool
+//! call and pauses the task for the user instead of silently going over.
+
+use crate::storage::models::{BudgetLimit, BudgetUsage, TaskBudget};
+
+/// Limits of `budget` that `usage` has reached
+pub fn exceeded_limits(budget: &TaskBudget, usage: &BudgetUsage) -> Vec<BudgetLimit> {
+    let mut exceeded = Vec::new();
+    if budget.max_cost_usd.is_some_and(|max| usage.cost_usd >= max) {
+        exceeded.push(BudgetLimit::Cost);
+    }
+    if budget
+        .max_tokens
+        .is_some_and(|max| usage.input_tokens + usage.output_tokens >= max)
+    {
+        exceeded.push(BudgetLimit::Tokens);
+    }
+    if budget
+        .max_iterations
+        .is_some_and(|max| usage.iterations >= max)
+    {
+        exceeded.push(BudgetLimit::Iterations);
+    }
+    if budget
+        .max_duration_secs
+        .is_some_and(|max| usage.duration_ms >= max.saturating_mul(1000))
+    {
+        exceeded.push(BudgetLimit::Duration);
+    }
+
+    exceeded
+}
+
+/// Budget for a task the user allowed to continue: each exceeded limit grants
+/// its original allowance again on top of what was already used
+pub fn extend(budget: &TaskBudget, usage: &BudgetUsage, exceeded: &[BudgetLimit]) -> TaskBudget {
+    let mut extended = budget.clone();
+    for limit in exceeded {
+        match limit {
+            BudgetLimit::Cost => {
+                extended.max_cost_usd = budget.max_cost_usd.map(|max| usage.cost_usd + max);
+            }
+            BudgetLimit::Tokens => {
+                let used = usage.input_tokens + usage.output_tokens;
+                extended.max_tokens = budget.max_tokens.map(|max| used + max);
+            }
+            BudgetLimit::Iterations => {
+                extended.max_iterations = budget.max_iterations.map(|max| usage.iterations + max);
+            }
+            BudgetLimit::Duration => {
+                extended.max_duration_secs = budget
+                    .max_duration_secs
+                    .map(|max| usage.duration_ms / 1000 + max);
+            }
+        }
+    }
+
+    extended
+}
+
+/// Human-readable description of exceeded limits for events and errors
+pub fn describe(exceeded: &[BudgetLimit], budget: &TaskBudget, usage: &BudgetUsage) -> String {
+    exceeded
+        .iter()
+        .map(|limit| match limit {
+            BudgetLimit::Cost => format!(
+                "cost ${:.4} of ${:.4}",
+                usage.cost_usd,
+                budget.max_cost_usd.unwrap_or_default()
+            ),
+            BudgetLimit::Tokens => format!(
+                "{} of {} tokens",
+                usage.input_tokens + usage.output_tokens,
+                budget.max_tokens.unwrap_or_default()
+            ),
+            BudgetLimit::Iterations => format!(
+                "{} of {} iterations",
+                usage.iterations,
+                budget.max_iterations.unwrap_or_default()
+            ),
+            BudgetLimit::Duration => format!(
+                "{}s of {}s running time",
+                usage.duration_ms / 1000,
+                budget.max_duration_secs.unwrap_or_default()
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage() -> BudgetUsage {
+        BudgetUsage {
+            cost_usd: 0.5,
+            input_tokens: 800,
+            output_tokens: 200,
+            iterations: 3,
+            duration_ms: 90_000,
+        }
+    }
+
+    #[test]
+    fn test_exceeded_limits() {
+        assert!(exceeded_limits(&TaskBudget::default(), &usage()).is_empty());
+
+        let budget = TaskBudget {
+            max_cost_usd: Some(0.5),
+            max_tokens: Some(2000),
+            max_iterations: Some(3),
+            max_duration_secs: Some(60),
+        };
+        assert_eq!(
+            exceeded_limits(&budget, &usage()),
+            vec![
+                BudgetLimit::Cost,
+                BudgetLimit::Iterations,
+                BudgetLimit::Duration
+            ]
+        );
+        assert_eq!(
+            describe(&[BudgetLimit::Tokens], &budget, &usage()),
+            "1000 of 2000 tokens"
+        );
+    }
+
+    #[test]
+    fn test_extend_grants_exceeded_limits_again() {
+        let budget = TaskBudget {
+            max_tokens: Some(1000),
+            max_iterations: Some(10),
+            ..TaskBudget::default()
+        };
+
+        let extended = extend(&budget, &usage(), &[BudgetLimit::Tokens]);
+        assert_eq!(extended.max_tokens, Some(2000));
+        // Limits that were not exceeded are left alone
+        assert_eq!(extended.max_iterations, Some(10));
+        assert!(exceeded_limits(&extended, &usage()).is_empty());
+    }
+}
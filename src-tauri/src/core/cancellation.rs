@@ -0,0 +1,137 @@
+//! Task Cancellation
+//!
+//! A cancellation token shared by everything a runtime task starts: the LLM
+//! stream, tool handlers and the processes they spawn. Cancelling the token
+//! wakes every `cancelled()` waiter immediately.
+
+use std::process::Output;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::process::Command;
+use tokio::sync::Notify;
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+/// Cloneable handle to a task's cancellation state
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<TokenState>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the token and wake all waiters
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+        self.state.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolve once the token is cancelled
+    pub async fn cancelled(&self) {
+        loop {
+            // Register before checking the flag so a concurrent cancel is not missed
+            let notified = self.state.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Run a command to completion, killing it if the token is cancelled first
+pub async fn run_command(
+    mut command: Command,
+    token: &CancellationToken,
+) -> Result<Output, String> {
+    if token.is_cancelled() {
+        return Err("Cancelled".to_string());
+    }
+
+    let child = command
+        .kill_on_drop(true)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to spawn command: {}", e))?;
+
+    // Dropping the wait future on cancellation drops the child, which kills it
+    tokio::select! {
+        output = child.wait_with_output() => {
+            output.map_err(|e| format!("Failed to wait for command: {}", e))
+        }
+        _ = token.cancelled() => Err("Cancelled".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_cancelled_wakes_waiters() {
+        let token = CancellationToken::new();
+        let waiter = {
+            let token = token.clone();
+            tokio::spawn(async move { token.cancelled().await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!token.is_cancelled());
+        token.cancel();
+
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("Waiter was not woken")
+            .unwrap();
+        // Already cancelled tokens resolve immediately
+        token.cancelled().await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_command_kills_process_on_cancel() {
+        let token = CancellationToken::new();
+        let mut command = Command::new("sleep");
+        command.arg("30");
+
+        let canceller = {
+            let token = token.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                token.cancel();
+            })
+        };
+
+        let start = Instant::now();
+        let result = run_command(command, &token).await;
+        canceller.await.unwrap();
+
+        assert_eq!(result.unwrap_err(), "Cancelled");
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_command_returns_output() {
+        let mut command = Command::new("echo");
+        command.arg("hello");
+
+        let output = run_command(command, &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "hello");
+    }
+}
@@ -0,0 +1,320 @@
+//! File Checkpoints
+//!
+//! Snapshots the files a tool is about to modify so a session can be rolled
+//! back to the state before any of its tool calls.
+
+use crate::core::patch;
+use crate::core::tools::ToolContext;
+use crate::core::types::ToolRequest;
+use crate::path_guard;
+use crate::storage::{ChatHistoryRepository, Checkpoint, FileSnapshot};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Files touched by a rollback
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointRollback {
+    pub checkpoint_id: String,
+    /// Checkpoints undone by the rollback, newest first
+    pub reverted_checkpoints: Vec<String>,
+    /// Files written back to their original content
+    pub restored_files: Vec<String>,
+    /// Files removed because they did not exist before
+    pub removed_files: Vec<String>,
+}
+
+/// Records and restores file checkpoints for sessions
+#[derive(Clone)]
+pub struct CheckpointManager {
+    chat_history: ChatHistoryRepository,
+}
+
+impl CheckpointManager {
+    pub fn new(chat_history: ChatHistoryRepository) -> Self {
+        Self { chat_history }
+    }
+
+    /// Snapshot the files named by a tool request before it runs.
+    /// Returns `None` when the request names no files.
+    pub async fn record(
+        &self,
+        context: &ToolContext,
+        request: &ToolRequest,
+    ) -> Result<Option<Checkpoint>, String> {
+        let root = context
+            .worktree_path
+            .as_deref()
+            .unwrap_or(&context.workspace_root);
+        let paths = target_paths(&request.input, Path::new(root));
+        if paths.is_empty() {
+            return Ok(None);
+        }
+
+        let files = paths
+            .iter()
+            .map(|path| snapshot_file(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let checkpoint = Checkpoint {
+            id: format!("ckpt_{}", uuid::Uuid::new_v4()),
+            session_id: context.session_id.clone(),
+            task_id: context.task_id.clone(),
+            tool_call_id: request.tool_call_id.clone(),
+            tool_name: request.name.clone(),
+            files,
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        self.chat_history.create_checkpoint(&checkpoint).await?;
+
+        Ok(Some(checkpoint))
+    }
+
+    /// List checkpoints of a session, oldest first
+    pub async fn list(&self, session_id: &str) -> Result<Vec<Checkpoint>, String> {
+        self.chat_history.list_checkpoints(session_id).await
+    }
+
+    /// Restore every file to its state before `checkpoint_id` was taken.
+    /// The checkpoint and all later ones of the session are undone newest
+    /// first and then discarded.
+    pub async fn rollback(
+        &self,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<CheckpointRollback, String> {
+        let checkpoints = self.list(session_id).await?;
+        let start = checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.id == checkpoint_id)
+            .ok_or_else(|| {
+                format!(
+                    "Checkpoint '{}' not found in session '{}'",
+                    checkpoint_id, session_id
+                )
+            })?;
+
+        let mut rollback = CheckpointRollback {
+            checkpoint_id: checkpoint_id.to_string(),
+            reverted_checkpoints: vec![],
+            restored_files: vec![],
+            removed_files: vec![],
+        };
+        for checkpoint in checkpoints[start..].iter().rev() {
+            for file in &checkpoint.files {
+                restore_file(file, &mut rollback)?;
+            }
+            self.chat_history.delete_checkpoint(&checkpoint.id).await?;
+            rollback.reverted_checkpoints.push(checkpoint.id.clone());
+        }
+
+        // A file touched by several tool calls is reported once
+        rollback.restored_files.sort();
+        rollback.restored_files.dedup();
+        rollback.removed_files.sort();
+        rollback.removed_files.dedup();
+        rollback
+            .removed_files
+            .retain(|path| !rollback.restored_files.contains(path));
+
+        Ok(rollback)
+    }
+}
+
+/// Absolute paths named by the `path`, `file_path` or `paths` input of a
+/// tool, or by the files of its `patch`. Paths outside the workspace are
+/// left out; the tool refuses them.
+fn target_paths(input: &serde_json::Value, root: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<&str> = ["path", "file_path"]
+        .iter()
+        .filter_map(|key| input.get(key).and_then(|v| v.as_str()))
+        .collect();
+    if let Some(list) = input.get("paths").and_then(|v| v.as_array()) {
+        paths.extend(list.iter().filter_map(|v| v.as_str()));
+    }
+    let patched = input
+        .get("patch")
+        .and_then(|v| v.as_str())
+        .map(patch::patch_paths)
+        .unwrap_or_default();
+    paths.extend(patched.iter().map(String::as_str));
+
+    let mut resolved: Vec<PathBuf> = paths
+        .into_iter()
+        .filter(|path| !path.is_empty())
+        .filter_map(|path| path_guard::resolve(root, Path::new(path)).ok())
+        .collect();
+    resolved.sort();
+    resolved.dedup();
+    resolved
+}
+
+fn snapshot_file(path: &Path) -> Result<FileSnapshot, String> {
+    let (hash, content) = match std::fs::read(path) {
+        Ok(bytes) => (Some(sha256_hex(&bytes)), Some(STANDARD.encode(&bytes))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (None, None),
+        Err(e) => return Err(format!("Failed to snapshot {}: {}", path.display(), e)),
+    };
+
+    Ok(FileSnapshot {
+        path: path.to_string_lossy().to_string(),
+        hash,
+        content,
+    })
+}
+
+fn restore_file(file: &FileSnapshot, rollback: &mut CheckpointRollback) -> Result<(), String> {
+    let path = Path::new(&file.path);
+    let Some(content) = &file.content else {
+        match std::fs::remove_file(path) {
+            Ok(()) => rollback.removed_files.push(file.path.clone()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to remove {}: {}", file.path, e)),
+        }
+        return Ok(());
+    };
+
+    let bytes = STANDARD
+        .decode(content)
+        .map_err(|e| format!("Corrupt snapshot for {}: {}", file.path, e))?;
+    // Skip files whose content already matches the snapshot
+    let unchanged = std::fs::read(path)
+        .map(|current| Some(sha256_hex(&current)) == file.hash)
+        .unwrap_or(false);
+    if !unchanged {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::write(path, bytes)
+            .map_err(|e| format!("Failed to restore {}: {}", file.path, e))?;
+        rollback.restored_files.push(file.path.clone());
+    }
+
+    Ok(())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::cancellation::CancellationToken;
+    use crate::storage::{Session, SessionStatus, Storage, TaskSettings};
+    use tempfile::TempDir;
+
+    async fn setup() -> (CheckpointManager, ToolContext, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().to_path_buf(),
+            temp_dir.path().join("attachments"),
+        )
+        .await
+        .expect("Failed to create storage");
+        let now = chrono::Utc::now().timestamp();
+        storage
+            .chat_history
+            .create_session(&Session {
+                id: "session-1".to_string(),
+                project_id: None,
+                title: None,
+                summary: None,
+                status: SessionStatus::Running,
+                created_at: now,
+                updated_at: now,
+                last_event_id: None,
+                metadata: None,
+                starred: false,
+                archived_at: None,
+                user_id: None,
+            })
+            .await
+            .unwrap();
+
+        let workspace = temp_dir.path().join("workspace");
+        std::fs::create_dir_all(&workspace).unwrap();
+        let context = ToolContext {
+            session_id: "session-1".to_string(),
+            task_id: "task-1".to_string(),
+            workspace_root: workspace.to_string_lossy().to_string(),
+            worktree_path: None,
+            settings: TaskSettings::default(),
+            cancel_token: CancellationToken::new(),
+        };
+
+        (
+            CheckpointManager::new(storage.chat_history.clone()),
+            context,
+            temp_dir,
+        )
+    }
+
+    fn write_request(id: &str, path: &str) -> ToolRequest {
+        ToolRequest {
+            tool_call_id: id.to_string(),
+            name: "write_file".to_string(),
+            input: serde_json::json!({ "path": path, "content": "new" }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rollback_restores_files_in_order() {
+        let (manager, context, _temp) = setup().await;
+        let workspace = PathBuf::from(&context.workspace_root);
+        std::fs::write(workspace.join("a.txt"), "original").unwrap();
+
+        // First call edits an existing file, the second edits it again and creates another
+        manager
+            .record(&context, &write_request("call-1", "a.txt"))
+            .await
+            .unwrap()
+            .expect("Checkpoint should be recorded");
+        std::fs::write(workspace.join("a.txt"), "edit 1").unwrap();
+
+        let request = ToolRequest {
+            input: serde_json::json!({ "paths": ["a.txt", "b.txt"] }),
+            ..write_request("call-2", "")
+        };
+        manager.record(&context, &request).await.unwrap();
+        std::fs::write(workspace.join("a.txt"), "edit 2").unwrap();
+        std::fs::write(workspace.join("b.txt"), "created").unwrap();
+
+        let checkpoints = manager.list(&context.session_id).await.unwrap();
+        assert_eq!(checkpoints.len(), 2);
+        assert_eq!(checkpoints[1].files.len(), 2);
+        assert!(checkpoints[1].files[1].hash.is_none());
+
+        let rollback = manager
+            .rollback(&context.session_id, &checkpoints[0].id)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(workspace.join("a.txt")).unwrap(),
+            "original"
+        );
+        assert!(!workspace.join("b.txt").exists());
+        assert_eq!(rollback.reverted_checkpoints.len(), 2);
+        assert_eq!(rollback.restored_files.len(), 1);
+        assert_eq!(rollback.removed_files.len(), 1);
+        assert!(manager.list(&context.session_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_record_skips_requests_without_paths() {
+        let (manager, context, _temp) = setup().await;
+        let request = ToolRequest {
+            tool_call_id: "call-1".to_string(),
+            name: "execute_shell".to_string(),
+            input: serde_json::json!({ "command": "ls" }),
+        };
+
+        assert!(manager.record(&context, &request).await.unwrap().is_none());
+        assert!(manager
+            .rollback(&context.session_id, "missing")
+            .await
+            .is_err());
+    }
+}
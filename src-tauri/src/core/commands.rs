@@ -0,0 +1,605 @@
+//! Tauri commands for the core runtime
+
+use crate::core::checkpoints::CheckpointRollback;
+use crate::core::database_query::DatabaseConnection;
+use crate::core::event_log::RebuiltSession;
+use crate::core::hooks::Hook;
+use crate::core::http_request::HttpRequestConfig;
+use crate::core::metrics::RuntimeStats;
+use crate::core::retention::{RetentionPolicy, RetentionReport};
+use crate::core::runtime::CoreRuntime;
+use crate::core::sandbox::SandboxPolicy;
+use crate::core::script_tools::ScriptTool;
+use crate::core::shell_policy::ShellPolicy;
+use crate::core::types::{RuntimeTaskId, ToolRetryPolicy};
+use crate::core::web_search::WebSearchConfig;
+use crate::core::webhooks::Webhook;
+use crate::core::workspace_agents::WorkspaceAgent;
+use crate::git::worktree::MergeResult;
+use crate::security::api_keys::CreatedApiKey;
+use crate::storage::{
+    ApiKey, ApiKeyScope, BudgetPause, Checkpoint, Memory, MemoryKind, MemoryUpdates,
+    PendingApproval, Plan, Project, ProjectUpdates, RuntimeEventRecord, SearchHit, StreamState,
+    TaskSettings, TaskWorktree, TodoList, User, WebhookDelivery,
+};
+use crate::streaming::{StreamingManager, StreamingStats};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::sync::RwLock;
+
+fn runtime(app: &AppHandle) -> Result<CoreRuntime, String> {
+    app.try_state::<CoreRuntime>()
+        .map(|state| state.inner().clone())
+        .ok_or_else(|| "Core runtime is not ready".to_string())
+}
+
+/// Approve a pending tool call; returns the ID of the resumed task
+#[tauri::command]
+pub async fn approve_tool_call(
+    app: AppHandle,
+    tool_call_id: String,
+) -> Result<RuntimeTaskId, String> {
+    let handle = runtime(&app)?.approve_tool_call(&tool_call_id).await?;
+    Ok(handle.task_id)
+}
+
+/// Deny a pending tool call; returns the ID of the resumed task
+#[tauri::command]
+pub async fn deny_tool_call(
+    app: AppHandle,
+    tool_call_id: String,
+    reason: Option<String>,
+) -> Result<RuntimeTaskId, String> {
+    let handle = runtime(&app)?.deny_tool_call(&tool_call_id, reason).await?;
+    Ok(handle.task_id)
+}
+
+/// Type into the terminal of a running shell tool call
+#[tauri::command]
+pub async fn write_tool_input(
+    app: AppHandle,
+    tool_call_id: String,
+    input: String,
+) -> Result<(), String> {
+    runtime(&app)?.write_tool_input(&tool_call_id, &input)
+}
+
+/// List tool calls waiting for approval, optionally for a single session
+#[tauri::command]
+pub async fn list_pending_tool_approvals(
+    app: AppHandle,
+    session_id: Option<String>,
+) -> Result<Vec<PendingApproval>, String> {
+    runtime(&app)?
+        .list_pending_approvals(session_id.as_deref())
+        .await
+}
+
+/// Let a task paused by its budget or interrupted by a restart continue;
+/// returns the ID of the resumed task
+#[tauri::command]
+pub async fn continue_task(app: AppHandle, task_id: String) -> Result<RuntimeTaskId, String> {
+    let handle = runtime(&app)?.continue_task(&task_id).await?;
+    Ok(handle.task_id)
+}
+
+/// List tasks paused by their budget, optionally for a single session
+#[tauri::command]
+pub async fn list_budget_pauses(
+    app: AppHandle,
+    session_id: Option<String>,
+) -> Result<Vec<BudgetPause>, String> {
+    runtime(&app)?
+        .list_budget_pauses(session_id.as_deref())
+        .await
+}
+
+/// List tasks interrupted by a restart, optionally for a single session
+#[tauri::command]
+pub async fn list_interrupted_tasks(
+    app: AppHandle,
+    session_id: Option<String>,
+) -> Result<Vec<StreamState>, String> {
+    runtime(&app)?
+        .list_interrupted_tasks(session_id.as_deref())
+        .await
+}
+
+/// List file checkpoints of a session, oldest first
+#[tauri::command]
+pub async fn list_checkpoints(
+    app: AppHandle,
+    session_id: String,
+) -> Result<Vec<Checkpoint>, String> {
+    runtime(&app)?.list_checkpoints(&session_id).await
+}
+
+/// Undo a session's file changes back to the state before a checkpoint
+#[tauri::command]
+pub async fn rollback_to_checkpoint(
+    app: AppHandle,
+    session_id: String,
+    checkpoint_id: String,
+) -> Result<CheckpointRollback, String> {
+    runtime(&app)?
+        .rollback_to_checkpoint(&session_id, &checkpoint_id)
+        .await
+}
+
+/// Start a task that executes a session's submitted plan; returns its ID
+#[tauri::command]
+pub async fn execute_plan(app: AppHandle, session_id: String) -> Result<RuntimeTaskId, String> {
+    let handle = runtime(&app)?.execute_plan(&session_id).await?;
+    Ok(handle.task_id)
+}
+
+/// List plans submitted in a session, oldest first
+#[tauri::command]
+pub async fn list_plans(app: AppHandle, session_id: String) -> Result<Vec<Plan>, String> {
+    runtime(&app)?.list_plans(&session_id).await
+}
+
+/// Todo list the agent keeps for a session
+#[tauri::command]
+pub async fn get_session_todos(app: AppHandle, session_id: String) -> Result<TodoList, String> {
+    runtime(&app)?.get_todos(&session_id).await
+}
+
+/// List global memories plus those of a project
+#[tauri::command]
+pub async fn list_memories(
+    app: AppHandle,
+    project_id: Option<String>,
+) -> Result<Vec<Memory>, String> {
+    runtime(&app)?.list_memories(project_id.as_deref()).await
+}
+
+/// Add a memory; without a project it is global
+#[tauri::command]
+pub async fn create_memory(
+    app: AppHandle,
+    project_id: Option<String>,
+    kind: MemoryKind,
+    content: String,
+) -> Result<Memory, String> {
+    runtime(&app)?
+        .create_memory(project_id, kind, &content)
+        .await
+}
+
+/// Edit the kind or content of a memory
+#[tauri::command]
+pub async fn update_memory(
+    app: AppHandle,
+    memory_id: String,
+    kind: Option<MemoryKind>,
+    content: Option<String>,
+) -> Result<Memory, String> {
+    runtime(&app)?
+        .update_memory(&memory_id, MemoryUpdates { kind, content })
+        .await
+}
+
+/// Delete a memory
+#[tauri::command]
+pub async fn delete_memory(app: AppHandle, memory_id: String) -> Result<(), String> {
+    runtime(&app)?.delete_memory(&memory_id).await
+}
+
+/// List projects by name
+#[tauri::command]
+pub async fn list_projects(app: AppHandle) -> Result<Vec<Project>, String> {
+    runtime(&app)?.list_projects(None).await
+}
+
+/// Get a project by ID
+#[tauri::command]
+pub async fn get_project(app: AppHandle, project_id: String) -> Result<Option<Project>, String> {
+    runtime(&app)?.get_project(&project_id).await
+}
+
+/// Create a project in an existing workspace directory
+#[tauri::command]
+pub async fn create_project(
+    app: AppHandle,
+    name: String,
+    workspace_path: String,
+    default_settings: Option<TaskSettings>,
+) -> Result<Project, String> {
+    runtime(&app)?
+        .create_project(&name, &workspace_path, default_settings, None)
+        .await
+}
+
+/// Edit the name, workspace or default settings of a project;
+/// `clear_default_settings` removes the settings
+#[tauri::command]
+pub async fn update_project(
+    app: AppHandle,
+    project_id: String,
+    name: Option<String>,
+    workspace_path: Option<String>,
+    default_settings: Option<TaskSettings>,
+    clear_default_settings: Option<bool>,
+) -> Result<Project, String> {
+    let default_settings = if clear_default_settings.unwrap_or(false) {
+        Some(None)
+    } else {
+        default_settings.map(Some)
+    };
+    runtime(&app)?
+        .update_project(
+            &project_id,
+            ProjectUpdates {
+                name,
+                workspace_path,
+                default_settings,
+            },
+        )
+        .await
+}
+
+/// Delete a project; its sessions are kept
+#[tauri::command]
+pub async fn delete_project(app: AppHandle, project_id: String) -> Result<(), String> {
+    runtime(&app)?.delete_project(&project_id).await
+}
+
+/// List the hooks of a project, or those for every project without one
+#[tauri::command]
+pub async fn list_hooks(app: AppHandle, project_id: Option<String>) -> Result<Vec<Hook>, String> {
+    runtime(&app)?.list_hooks(project_id.as_deref()).await
+}
+
+/// Replace the hooks of a project, or those for every project without one
+#[tauri::command]
+pub async fn set_hooks(
+    app: AppHandle,
+    project_id: Option<String>,
+    hooks: Vec<Hook>,
+) -> Result<(), String> {
+    runtime(&app)?.set_hooks(project_id.as_deref(), hooks).await
+}
+
+/// List the webhooks of a project
+#[tauri::command]
+pub async fn list_webhooks(app: AppHandle, project_id: String) -> Result<Vec<Webhook>, String> {
+    runtime(&app)?.list_webhooks(&project_id).await
+}
+
+/// Replace the webhooks of a project; new ones get IDs assigned
+#[tauri::command]
+pub async fn set_webhooks(
+    app: AppHandle,
+    project_id: String,
+    webhooks: Vec<Webhook>,
+) -> Result<Vec<Webhook>, String> {
+    runtime(&app)?.set_webhooks(&project_id, webhooks).await
+}
+
+/// List a project's webhook deliveries, newest first
+#[tauri::command]
+pub async fn list_webhook_deliveries(
+    app: AppHandle,
+    project_id: String,
+    webhook_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<WebhookDelivery>, String> {
+    runtime(&app)?
+        .list_webhook_deliveries(&project_id, webhook_id.as_deref(), limit)
+        .await
+}
+
+/// Get the retry policy applied to a tool's failed calls
+#[tauri::command]
+pub async fn get_tool_retry_policy(
+    app: AppHandle,
+    tool_name: String,
+) -> Result<ToolRetryPolicy, String> {
+    Ok(runtime(&app)?.tool_retry_policy(&tool_name).await)
+}
+
+/// Override the retry policy of a tool; no policy restores the default
+#[tauri::command]
+pub async fn set_tool_retry_policy(
+    app: AppHandle,
+    tool_name: String,
+    policy: Option<ToolRetryPolicy>,
+) -> Result<(), String> {
+    runtime(&app)?
+        .set_tool_retry_policy(&tool_name, policy)
+        .await
+}
+
+/// List the custom agents defined under a workspace's `.talkcody/agents`
+#[tauri::command]
+pub async fn list_workspace_agents(
+    app: AppHandle,
+    workspace_root: String,
+) -> Result<Vec<WorkspaceAgent>, String> {
+    Ok(runtime(&app)?.list_workspace_agents(&workspace_root))
+}
+
+/// List the script tools defined under a workspace's `.talkcody/tools`
+#[tauri::command]
+pub async fn list_script_tools(
+    app: AppHandle,
+    workspace_root: String,
+) -> Result<Vec<ScriptTool>, String> {
+    Ok(runtime(&app)?.list_script_tools(&workspace_root))
+}
+
+/// List the worktrees of a session's isolated tasks awaiting merge or discard
+#[tauri::command]
+pub async fn list_task_worktrees(
+    app: AppHandle,
+    session_id: String,
+) -> Result<Vec<TaskWorktree>, String> {
+    runtime(&app)?.list_task_worktrees(&session_id).await
+}
+
+/// Merge a finished isolated task's branch into the main branch
+#[tauri::command]
+pub async fn merge_task_worktree(
+    app: AppHandle,
+    task_id: String,
+    commit_message: Option<String>,
+) -> Result<MergeResult, String> {
+    runtime(&app)?
+        .merge_task_worktree(&task_id, commit_message)
+        .await
+}
+
+/// Remove a finished isolated task's worktree and branch, dropping its changes
+#[tauri::command]
+pub async fn discard_task_worktree(app: AppHandle, task_id: String) -> Result<(), String> {
+    runtime(&app)?.discard_task_worktree(&task_id).await
+}
+
+/// Counts and timings of tracked tasks, optionally of one session
+#[tauri::command]
+pub async fn get_runtime_stats(
+    app: AppHandle,
+    session_id: Option<String>,
+) -> Result<RuntimeStats, String> {
+    Ok(runtime(&app)?
+        .get_runtime_stats(session_id.as_deref())
+        .await)
+}
+
+/// Create an API key for remote clients of the HTTP server; the full key is
+/// only returned here
+#[tauri::command]
+pub async fn create_api_key(
+    app: AppHandle,
+    name: String,
+    user_id: Option<String>,
+    scopes: Option<Vec<ApiKeyScope>>,
+) -> Result<CreatedApiKey, String> {
+    runtime(&app)?
+        .create_api_key(&name, user_id.as_deref(), scopes.as_deref())
+        .await
+}
+
+/// List the HTTP server's API keys, revoked ones included
+#[tauri::command]
+pub async fn list_api_keys(app: AppHandle) -> Result<Vec<ApiKey>, String> {
+    runtime(&app)?.list_api_keys().await
+}
+
+/// Revoke an API key; returns false when it does not exist or was already
+/// revoked
+#[tauri::command]
+pub async fn revoke_api_key(app: AppHandle, key_id: String) -> Result<bool, String> {
+    runtime(&app)?.revoke_api_key(&key_id).await
+}
+
+/// Create a user of the HTTP server
+#[tauri::command]
+pub async fn create_user(app: AppHandle, name: String) -> Result<User, String> {
+    runtime(&app)?.create_user(&name).await
+}
+
+/// List the users of the HTTP server
+#[tauri::command]
+pub async fn list_users(app: AppHandle) -> Result<Vec<User>, String> {
+    runtime(&app)?.list_users().await
+}
+
+/// Delete a user and revoke their API keys; returns false when the user does
+/// not exist
+#[tauri::command]
+pub async fn delete_user(app: AppHandle, user_id: String) -> Result<bool, String> {
+    runtime(&app)?.delete_user(&user_id).await
+}
+
+/// Buffer stats, dropped and coalesced events, and the lag of each stream
+/// subscriber
+#[tauri::command]
+pub async fn get_streaming_stats(app: AppHandle) -> Result<StreamingStats, String> {
+    let streaming = app
+        .try_state::<Arc<RwLock<StreamingManager>>>()
+        .map(|state| state.inner().clone())
+        .ok_or_else(|| "Streaming layer is not ready".to_string())?;
+    let stats = streaming.read().await.get_stats().await;
+    Ok(stats)
+}
+
+/// A session's logged runtime events, optionally after a sequence
+#[tauri::command]
+pub async fn list_session_events(
+    app: AppHandle,
+    session_id: String,
+    after_sequence: Option<i64>,
+    limit: Option<usize>,
+) -> Result<Vec<RuntimeEventRecord>, String> {
+    runtime(&app)?
+        .list_session_events(&session_id, after_sequence, limit)
+        .await
+}
+
+/// Rebuild a session's state from its event log
+#[tauri::command]
+pub async fn rebuild_session_state(
+    app: AppHandle,
+    session_id: String,
+) -> Result<RebuiltSession, String> {
+    runtime(&app)?.rebuild_session_state(&session_id).await
+}
+
+/// Archive a session, hiding it from default session lists
+#[tauri::command]
+pub async fn archive_session(app: AppHandle, session_id: String) -> Result<(), String> {
+    runtime(&app)?.archive_session(&session_id).await
+}
+
+/// Restore an archived session
+#[tauri::command]
+pub async fn restore_session(app: AppHandle, session_id: String) -> Result<(), String> {
+    runtime(&app)?.restore_session(&session_id).await
+}
+
+/// Search session titles and message text, best matches first
+#[tauri::command]
+pub async fn search_sessions(
+    app: AppHandle,
+    query: String,
+    project_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, String> {
+    runtime(&app)?
+        .search_sessions(&query, project_id.as_deref(), None, limit)
+        .await
+}
+
+/// Star or unstar a session
+#[tauri::command]
+pub async fn set_session_starred(
+    app: AppHandle,
+    session_id: String,
+    starred: bool,
+) -> Result<(), String> {
+    runtime(&app)?
+        .set_session_starred(&session_id, starred)
+        .await
+}
+
+/// Get the session retention policy
+#[tauri::command]
+pub async fn get_retention_policy(app: AppHandle) -> Result<RetentionPolicy, String> {
+    runtime(&app)?.retention_policy().await
+}
+
+/// Replace the session retention policy
+#[tauri::command]
+pub async fn set_retention_policy(app: AppHandle, policy: RetentionPolicy) -> Result<(), String> {
+    runtime(&app)?.set_retention_policy(policy).await
+}
+
+/// Apply the session retention policy right away
+#[tauri::command]
+pub async fn apply_retention_policy(app: AppHandle) -> Result<RetentionReport, String> {
+    runtime(&app)?.apply_retention_policy().await
+}
+
+/// Get the web search settings
+#[tauri::command]
+pub async fn get_web_search_config(app: AppHandle) -> Result<WebSearchConfig, String> {
+    runtime(&app)?.web_search_config().await
+}
+
+/// Choose the web search backend
+#[tauri::command]
+pub async fn set_web_search_config(app: AppHandle, config: WebSearchConfig) -> Result<(), String> {
+    runtime(&app)?.set_web_search_config(config).await
+}
+
+/// List the databases of a project, or those for every project without one
+#[tauri::command]
+pub async fn list_databases(
+    app: AppHandle,
+    project_id: Option<String>,
+) -> Result<Vec<DatabaseConnection>, String> {
+    runtime(&app)?.list_databases(project_id.as_deref()).await
+}
+
+/// Replace the databases of a project, or those for every project without one
+#[tauri::command]
+pub async fn set_databases(
+    app: AppHandle,
+    project_id: Option<String>,
+    connections: Vec<DatabaseConnection>,
+) -> Result<(), String> {
+    runtime(&app)?
+        .set_databases(project_id.as_deref(), connections)
+        .await
+}
+
+/// Get the `http_request` domains and secrets of a project, or those for every
+/// project without one
+#[tauri::command]
+pub async fn get_http_request_config(
+    app: AppHandle,
+    project_id: Option<String>,
+) -> Result<HttpRequestConfig, String> {
+    runtime(&app)?
+        .http_request_config(project_id.as_deref())
+        .await
+}
+
+/// Replace the `http_request` domains and secrets of a project, or those for
+/// every project without one
+#[tauri::command]
+pub async fn set_http_request_config(
+    app: AppHandle,
+    project_id: Option<String>,
+    config: HttpRequestConfig,
+) -> Result<(), String> {
+    runtime(&app)?
+        .set_http_request_config(project_id.as_deref(), config)
+        .await
+}
+
+/// Get the sandbox policy of a project, or the one for every project without one
+#[tauri::command]
+pub async fn get_sandbox_policy(
+    app: AppHandle,
+    project_id: Option<String>,
+) -> Result<Option<SandboxPolicy>, String> {
+    runtime(&app)?.sandbox_policy(project_id.as_deref()).await
+}
+
+/// Set or remove the sandbox policy of a project, or the one for every project
+/// without one
+#[tauri::command]
+pub async fn set_sandbox_policy(
+    app: AppHandle,
+    project_id: Option<String>,
+    policy: Option<SandboxPolicy>,
+) -> Result<(), String> {
+    runtime(&app)?
+        .set_sandbox_policy(project_id.as_deref(), policy)
+        .await
+}
+
+/// Get the shell policy of a project, or the one for every project without one
+#[tauri::command]
+pub async fn get_shell_policy(
+    app: AppHandle,
+    project_id: Option<String>,
+) -> Result<Option<ShellPolicy>, String> {
+    runtime(&app)?.shell_policy(project_id.as_deref()).await
+}
+
+/// Set or remove the shell policy of a project, or the one for every project
+/// without one
+#[tauri::command]
+pub async fn set_shell_policy(
+    app: AppHandle,
+    project_id: Option<String>,
+    policy: Option<ShellPolicy>,
+) -> Result<(), String> {
+    runtime(&app)?
+        .set_shell_policy(project_id.as_deref(), policy)
+        .await
+}
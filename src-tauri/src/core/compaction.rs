@@ -0,0 +1,283 @@
+//! Context Compaction
+//!
+//! Keeps the agent context within the model's context window by folding older
+//! turns into a summary. Pinned messages and the most recent turns are kept verbatim.
+
+use crate::storage::models::*;
+
+/// Fraction of the context window at which older turns are compacted
+pub const COMPACTION_THRESHOLD: f64 = 0.8;
+
+/// Number of trailing messages that are never compacted
+const KEEP_RECENT_MESSAGES: usize = 6;
+
+/// Longest tool output included verbatim in the summarization input
+const MAX_TOOL_OUTPUT_CHARS: usize = 2000;
+
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n\n";
+
+/// Rough token estimate of a message list, about four characters per token
+pub fn estimate_tokens(messages: &[Message]) -> u32 {
+    let chars: usize = messages
+        .iter()
+        .map(|message| match &message.content {
+            MessageContent::Text { text } => text.len(),
+            other => serde_json::to_string(other).map(|s| s.len()).unwrap_or(0),
+        })
+        .sum();
+
+    (chars / 4) as u32
+}
+
+/// Whether a context of `tokens` should be compacted for a model with `context_length`
+pub fn needs_compaction(tokens: u32, context_length: u32) -> bool {
+    context_length > 0 && tokens as f64 >= context_length as f64 * COMPACTION_THRESHOLD
+}
+
+/// Pinned text messages survive compaction; tool traffic never does since a
+/// tool result is meaningless without its call
+pub fn is_pinned(message: &Message) -> bool {
+    message.pinned && matches!(message.content, MessageContent::Text { .. })
+}
+
+/// Index of the first message to keep verbatim, or `None` when there is
+/// nothing worth compacting. The kept tail never starts with a tool result
+/// nor separates an assistant turn from the reasoning before it.
+pub fn plan_compaction(messages: &[Message]) -> Option<usize> {
+    let mut boundary = messages.len().checked_sub(KEEP_RECENT_MESSAGES)?;
+    while boundary > 0
+        && (messages[boundary].role == MessageRole::Tool
+            || matches!(
+                messages[boundary - 1].content,
+                MessageContent::Reasoning { .. }
+            ))
+    {
+        boundary -= 1;
+    }
+
+    let compactable = messages[..boundary]
+        .iter()
+        .filter(|message| !is_pinned(message))
+        .count();
+    // A lone previous summary is not worth summarizing again
+    (compactable > 1).then_some(boundary)
+}
+
+/// Synthetic message carrying a compaction summary in the model context
+pub fn summary_message(compaction: &ContextCompaction) -> Message {
+    Message {
+        id: compaction.id.clone(),
+        session_id: compaction.session_id.clone(),
+        role: MessageRole::System,
+        content: MessageContent::Text {
+            text: format!("{}{}", SUMMARY_PREFIX, compaction.summary),
+        },
+        created_at: compaction.created_at,
+        tool_call_id: None,
+        parent_id: None,
+        pinned: false,
+    }
+}
+
+/// Rebuild the model context of a session from its full history and its
+/// latest compaction: the summary, then pinned messages, then the kept tail
+pub fn apply_compaction(messages: Vec<Message>, compaction: &ContextCompaction) -> Vec<Message> {
+    let Some(boundary) = messages
+        .iter()
+        .position(|message| message.id == compaction.first_kept_message_id)
+    else {
+        log::warn!(
+            "Compaction {} refers to unknown message {}, using full history",
+            compaction.id,
+            compaction.first_kept_message_id
+        );
+        return messages;
+    };
+
+    let mut messages = messages;
+    let tail = messages.split_off(boundary);
+    let mut context = vec![summary_message(compaction)];
+    context.extend(messages.into_iter().filter(is_pinned));
+    context.extend(tail);
+    context
+}
+
+/// Render messages as plain text for the summarization prompt
+pub fn format_history(messages: &[Message]) -> String {
+    let mut lines = Vec::with_capacity(messages.len());
+    for message in messages {
+        let role = message.role.as_str();
+        match &message.content {
+            MessageContent::Text { text } => lines.push(format!("{}: {}", role, text)),
+            MessageContent::ToolCalls { calls } => {
+                for call in calls {
+                    lines.push(format!("{} called {}({})", role, call.name, call.input));
+                }
+            }
+            MessageContent::ToolResult { result } => {
+                let mut output = result.to_string();
+                if output.len() > MAX_TOOL_OUTPUT_CHARS {
+                    let mut end = MAX_TOOL_OUTPUT_CHARS;
+                    while !output.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    output.truncate(end);
+                    output.push_str("...");
+                }
+                lines.push(format!("tool result: {}", output));
+            }
+            // The model's own reasoning adds nothing to the summary
+            MessageContent::Reasoning { .. } => {}
+        }
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, role: MessageRole, content: MessageContent) -> Message {
+        Message {
+            id: id.to_string(),
+            session_id: "session".to_string(),
+            role,
+            content,
+            created_at: 0,
+            tool_call_id: None,
+            parent_id: None,
+            pinned: false,
+        }
+    }
+
+    fn text(id: &str, role: MessageRole, text: &str) -> Message {
+        message(
+            id,
+            role,
+            MessageContent::Text {
+                text: text.to_string(),
+            },
+        )
+    }
+
+    fn compaction(first_kept_message_id: &str) -> ContextCompaction {
+        ContextCompaction {
+            id: "compaction-1".to_string(),
+            session_id: "session".to_string(),
+            summary: "Earlier work".to_string(),
+            first_kept_message_id: first_kept_message_id.to_string(),
+            compacted_count: 2,
+            tokens_before: 100,
+            tokens_after: 10,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_needs_compaction() {
+        assert!(!needs_compaction(700, 1000));
+        assert!(needs_compaction(800, 1000));
+        assert!(!needs_compaction(800, 0));
+    }
+
+    #[test]
+    fn test_plan_compaction_keeps_tool_results_with_calls() {
+        let mut messages: Vec<Message> = (0..4)
+            .map(|i| text(&format!("m{}", i), MessageRole::User, "hello"))
+            .collect();
+        messages.push(message(
+            "call",
+            MessageRole::Assistant,
+            MessageContent::ToolCalls {
+                calls: vec![ToolCall {
+                    id: "call-1".to_string(),
+                    name: "read_file".to_string(),
+                    input: serde_json::json!({}),
+                }],
+            },
+        ));
+        messages.push(message(
+            "result",
+            MessageRole::Tool,
+            MessageContent::ToolResult {
+                result: serde_json::json!("contents"),
+            },
+        ));
+        messages.extend((0..5).map(|i| text(&format!("t{}", i), MessageRole::User, "more")));
+
+        // The kept tail would start at the tool result, so the call is kept too
+        assert_eq!(plan_compaction(&messages), Some(4));
+        assert_eq!(plan_compaction(&messages[..6]), None);
+
+        // Reasoning stays with the tool calls it led to
+        messages.insert(
+            4,
+            message(
+                "reasoning",
+                MessageRole::Assistant,
+                MessageContent::Reasoning {
+                    text: "Read it first".to_string(),
+                    provider_metadata: None,
+                },
+            ),
+        );
+        assert_eq!(plan_compaction(&messages), Some(4));
+    }
+
+    #[test]
+    fn test_plan_compaction_ignores_pinned_messages() {
+        let mut messages: Vec<Message> = (0..8)
+            .map(|i| text(&format!("m{}", i), MessageRole::User, "hello"))
+            .collect();
+        messages[0].pinned = true;
+        messages[1].pinned = true;
+
+        assert_eq!(plan_compaction(&messages), None);
+        messages[1].pinned = false;
+        messages.push(text("m8", MessageRole::User, "hello"));
+        assert_eq!(plan_compaction(&messages), Some(3));
+    }
+
+    #[test]
+    fn test_apply_compaction() {
+        let mut messages = vec![
+            text("m0", MessageRole::User, "Always use tabs"),
+            text("m1", MessageRole::Assistant, "Sure"),
+            text("m2", MessageRole::User, "Refactor"),
+            text("m3", MessageRole::Assistant, "Done"),
+        ];
+        messages[0].pinned = true;
+
+        let context = apply_compaction(messages.clone(), &compaction("m2"));
+        let ids: Vec<&str> = context.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["compaction-1", "m0", "m2", "m3"]);
+        assert_eq!(context[0].role, MessageRole::System);
+        assert!(matches!(
+            &context[0].content,
+            MessageContent::Text { text } if text.ends_with("Earlier work")
+        ));
+
+        // Unknown boundaries fall back to the full history
+        assert_eq!(apply_compaction(messages, &compaction("missing")).len(), 4);
+    }
+
+    #[test]
+    fn test_format_history_truncates_tool_output() {
+        let messages = vec![
+            text("m0", MessageRole::User, "Read it"),
+            message(
+                "m1",
+                MessageRole::Tool,
+                MessageContent::ToolResult {
+                    result: serde_json::json!("x".repeat(5000)),
+                },
+            ),
+        ];
+
+        let history = format_history(&messages);
+        assert!(history.starts_with("user: Read it\ntool result: "));
+        assert!(history.len() < 2100);
+        assert!(history.ends_with("..."));
+    }
+}
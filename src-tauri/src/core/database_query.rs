@@ -0,0 +1,777 @@
+//! Database Query Tools
+//!
+//! `query_database` runs SQL against SQLite files and Postgres servers
+//! configured per project, and `describe_database` lists their tables and
+//! columns. Connections are stored in settings under `databases` for every
+//! project and `databases.<project_id>` for one. A connection is read-only
+//! unless configured otherwise: only single read statements are accepted and
+//! the database itself is told to refuse writes.
+
+use crate::core::tools::{ToolContext, ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::{ToolDefinition, ToolRequest};
+use crate::storage::{ChatHistoryRepository, SettingsRepository, ToolCapability};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Settings key of the connections available to every project
+pub const GLOBAL_DATABASES_KEY: &str = "databases";
+
+pub const QUERY_DATABASE_TOOL: &str = "query_database";
+pub const DESCRIBE_DATABASE_TOOL: &str = "describe_database";
+
+/// Rows returned when neither the connection nor the call sets a limit
+pub const DEFAULT_MAX_ROWS: usize = 100;
+/// Most rows a query may return
+const MAX_ROWS: usize = 1000;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Keywords a read-only statement may start with
+const READ_KEYWORDS: &[&str] = &[
+    "select", "with", "values", "table", "explain", "show", "pragma",
+];
+/// Keywords that make a statement a write wherever they appear
+const WRITE_KEYWORDS: &[&str] = &[
+    "insert", "update", "delete", "drop", "alter", "create", "truncate", "grant", "revoke",
+    "attach", "detach", "vacuum", "reindex", "copy", "merge", "call", "lock",
+];
+
+/// A configured database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseConnection {
+    pub name: String,
+    /// `postgres://…` or `postgresql://…` URL, or `sqlite:` followed by a
+    /// file path, which may be relative to the workspace
+    pub url: String,
+    #[serde(default = "default_read_only")]
+    pub read_only: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rows: Option<usize>,
+}
+
+fn default_read_only() -> bool {
+    true
+}
+
+impl DatabaseConnection {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Database name cannot be empty".to_string());
+        }
+        Backend::parse(&self.url, Path::new("")).map(|_| ())
+    }
+}
+
+/// Where a connection points
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Backend {
+    Sqlite(PathBuf),
+    Postgres(String),
+}
+
+impl Backend {
+    fn parse(url: &str, root: &Path) -> Result<Self, String> {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            return Ok(Backend::Postgres(url.to_string()));
+        }
+        let path = url
+            .strip_prefix("sqlite://")
+            .or_else(|| url.strip_prefix("sqlite:"))
+            .filter(|path| !path.is_empty())
+            .ok_or_else(|| format!("Unsupported database URL: {}", url))?;
+        Ok(Backend::Sqlite(root.join(path)))
+    }
+}
+
+/// Rows returned by a query, or the rows a write changed
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// More rows matched than were returned
+    pub truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows_affected: Option<u64>,
+}
+
+/// Loads a project's database connections and runs the database tools
+#[derive(Clone)]
+pub struct DatabaseManager {
+    settings: SettingsRepository,
+    chat_history: ChatHistoryRepository,
+}
+
+impl DatabaseManager {
+    pub fn new(settings: SettingsRepository, chat_history: ChatHistoryRepository) -> Self {
+        Self {
+            settings,
+            chat_history,
+        }
+    }
+
+    /// Connections configured for a project, or for every project when `None`
+    pub async fn list(&self, project_id: Option<&str>) -> Result<Vec<DatabaseConnection>, String> {
+        self.settings
+            .get_setting_or_default(&databases_key(project_id), Vec::new())
+            .await
+    }
+
+    /// Replace the connections of a project, or those for every project
+    /// when `None`
+    pub async fn set(
+        &self,
+        project_id: Option<&str>,
+        connections: Vec<DatabaseConnection>,
+    ) -> Result<(), String> {
+        for (index, connection) in connections.iter().enumerate() {
+            connection.validate()?;
+            if connections[..index]
+                .iter()
+                .any(|other| other.name == connection.name)
+            {
+                return Err(format!("Duplicate database name: {}", connection.name));
+            }
+        }
+        let value = serde_json::to_value(&connections)
+            .map_err(|e| format!("Failed to serialize databases: {}", e))?;
+        self.settings
+            .set_setting(&databases_key(project_id), &value)
+            .await
+    }
+
+    /// Register `query_database` and `describe_database`
+    pub async fn register_tools(&self, registry: &ToolRegistry) -> Result<(), String> {
+        let manager = self.clone();
+        let query: ToolHandler = Arc::new(move |request, context| {
+            let manager = manager.clone();
+            Box::pin(async move { to_output(manager.query_tool(&request, &context).await) })
+        });
+        let manager = self.clone();
+        let describe: ToolHandler = Arc::new(move |request, context| {
+            let manager = manager.clone();
+            Box::pin(async move { to_output(manager.describe_tool(&request, &context).await) })
+        });
+
+        registry
+            .register(query_database_definition(), query)
+            .await?;
+        registry
+            .register(describe_database_definition(), describe)
+            .await
+    }
+
+    async fn query_tool(
+        &self,
+        request: &ToolRequest,
+        context: &ToolContext,
+    ) -> Result<serde_json::Value, String> {
+        let sql = request
+            .input
+            .get("sql")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'sql'".to_string())?;
+        let connection = self.connection_for(request, context).await?;
+        let limit = connection
+            .max_rows
+            .unwrap_or(DEFAULT_MAX_ROWS)
+            .min(MAX_ROWS);
+        let max_rows = request
+            .input
+            .get("maxRows")
+            .and_then(|v| v.as_u64())
+            .map_or(limit, |n| (n as usize).min(limit));
+
+        let result = run(
+            &connection,
+            workspace_root(context),
+            sql,
+            connection.read_only,
+            max_rows,
+        )
+        .await?;
+        serde_json::to_value(&result).map_err(|e| format!("Failed to serialize rows: {}", e))
+    }
+
+    async fn describe_tool(
+        &self,
+        request: &ToolRequest,
+        context: &ToolContext,
+    ) -> Result<serde_json::Value, String> {
+        let connection = self.connection_for(request, context).await?;
+        let root = workspace_root(context);
+        let table = request.input.get("table").and_then(|v| v.as_str());
+        let sql = describe_sql(&Backend::parse(&connection.url, root)?, table);
+
+        let result = run(&connection, root, &sql, true, MAX_ROWS).await?;
+        serde_json::to_value(&result).map_err(|e| format!("Failed to serialize schema: {}", e))
+    }
+
+    /// The connection a tool call names, which may be omitted when the
+    /// session's project has only one
+    async fn connection_for(
+        &self,
+        request: &ToolRequest,
+        context: &ToolContext,
+    ) -> Result<DatabaseConnection, String> {
+        let project_id = self
+            .chat_history
+            .get_session(&context.session_id)
+            .await?
+            .and_then(|session| session.project_id);
+        // Project connections take precedence over global ones of the same name
+        let mut connections = match &project_id {
+            Some(project_id) => self.list(Some(project_id)).await?,
+            None => vec![],
+        };
+        connections.extend(self.list(None).await?);
+
+        let name = request.input.get("database").and_then(|v| v.as_str());
+        match name {
+            Some(name) => connections
+                .into_iter()
+                .find(|connection| connection.name == name)
+                .ok_or_else(|| format!("No database named '{}' is configured", name)),
+            None if connections.len() == 1 => Ok(connections.remove(0)),
+            None if connections.is_empty() => Err("No databases are configured".to_string()),
+            None => Err(format!(
+                "Several databases are configured; pass 'database' as one of: {}",
+                connections
+                    .iter()
+                    .map(|connection| connection.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+fn databases_key(project_id: Option<&str>) -> String {
+    match project_id {
+        Some(project_id) => format!("{}.{}", GLOBAL_DATABASES_KEY, project_id),
+        None => GLOBAL_DATABASES_KEY.to_string(),
+    }
+}
+
+fn workspace_root(context: &ToolContext) -> &Path {
+    Path::new(
+        context
+            .worktree_path
+            .as_deref()
+            .unwrap_or(&context.workspace_root),
+    )
+}
+
+/// Run one statement, returning at most `max_rows` rows
+async fn run(
+    connection: &DatabaseConnection,
+    root: &Path,
+    sql: &str,
+    read_only: bool,
+    max_rows: usize,
+) -> Result<QueryResult, String> {
+    let statements = split_statements(sql);
+    let [statement] = statements.as_slice() else {
+        return Err(format!(
+            "Expected a single SQL statement, got {}",
+            statements.len()
+        ));
+    };
+    if read_only {
+        check_read_only(statement)?;
+    }
+
+    let query = async {
+        match Backend::parse(&connection.url, root)? {
+            Backend::Sqlite(path) => run_sqlite(&path, &statement.sql, read_only, max_rows).await,
+            Backend::Postgres(url) => run_postgres(&url, statement, read_only, max_rows).await,
+        }
+    };
+    tokio::time::timeout(QUERY_TIMEOUT, query)
+        .await
+        .map_err(|_| format!("Query timed out after {}s", QUERY_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("{} query failed: {}", connection.name, e))
+}
+
+async fn run_sqlite(
+    path: &Path,
+    sql: &str,
+    read_only: bool,
+    max_rows: usize,
+) -> Result<QueryResult, String> {
+    if !path.is_file() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+    let db = libsql::Builder::new_local(path)
+        .build()
+        .await
+        .map_err(|e| e.to_string())?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    if read_only {
+        conn.execute("PRAGMA query_only = ON", ())
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    let stmt = conn.prepare(sql).await.map_err(|e| e.to_string())?;
+    let columns: Vec<String> = stmt
+        .columns()
+        .iter()
+        .map(|column| column.name().to_string())
+        .collect();
+    if columns.is_empty() {
+        let rows_affected = stmt.execute(()).await.map_err(|e| e.to_string())?;
+        return Ok(QueryResult {
+            rows_affected: Some(rows_affected as u64),
+            ..QueryResult::default()
+        });
+    }
+
+    let column_count = columns.len() as i32;
+    let mut result = QueryResult {
+        columns,
+        ..QueryResult::default()
+    };
+    let mut rows = stmt.query(()).await.map_err(|e| e.to_string())?;
+    while let Some(row) = rows.next().await.map_err(|e| e.to_string())? {
+        if result.rows.len() == max_rows {
+            result.truncated = true;
+            break;
+        }
+        let values = (0..column_count)
+            .map(|i| row.get_value(i).map(|value| sqlite_value_to_json(&value)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        result.rows.push(values);
+    }
+    Ok(result)
+}
+
+fn sqlite_value_to_json(value: &libsql::Value) -> serde_json::Value {
+    match value {
+        libsql::Value::Null => serde_json::Value::Null,
+        libsql::Value::Integer(i) => serde_json::json!(i),
+        libsql::Value::Real(f) => serde_json::json!(f),
+        libsql::Value::Text(s) => serde_json::json!(s),
+        libsql::Value::Blob(bytes) => serde_json::json!(STANDARD.encode(bytes)),
+    }
+}
+
+/// Postgres values come back as text through the simple query protocol,
+/// which handles every column type alike
+async fn run_postgres(
+    url: &str,
+    statement: &Statement,
+    read_only: bool,
+    max_rows: usize,
+) -> Result<QueryResult, String> {
+    use tokio_postgres::SimpleQueryMessage;
+
+    let tls = native_tls::TlsConnector::new().map_err(|e| e.to_string())?;
+    let (client, connection) =
+        tokio_postgres::connect(url, postgres_native_tls::MakeTlsConnector::new(tls))
+            .await
+            .map_err(|e| e.to_string())?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            log::warn!("Postgres connection error: {}", e);
+        }
+    });
+
+    if read_only {
+        client
+            .batch_execute("BEGIN TRANSACTION READ ONLY")
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    // Queries are wrapped so the server stops after one row past the limit
+    let sql = match statement.keyword.as_str() {
+        "select" | "with" | "values" | "table" => format!(
+            "SELECT * FROM ({}) AS query LIMIT {}",
+            statement.sql,
+            max_rows + 1
+        ),
+        _ => statement.sql.clone(),
+    };
+    let messages = client.simple_query(&sql).await.map_err(|e| e.to_string());
+    if read_only {
+        let _ = client.batch_execute("ROLLBACK").await;
+    }
+
+    let mut result = QueryResult::default();
+    for message in messages? {
+        match message {
+            SimpleQueryMessage::RowDescription(columns) => {
+                result.columns = columns.iter().map(|c| c.name().to_string()).collect();
+            }
+            SimpleQueryMessage::Row(row) => {
+                if result.columns.is_empty() {
+                    result.columns = row.columns().iter().map(|c| c.name().to_string()).collect();
+                }
+                if result.rows.len() == max_rows {
+                    result.truncated = true;
+                    continue;
+                }
+                result.rows.push(
+                    (0..row.len())
+                        .map(|i| serde_json::json!(row.get(i)))
+                        .collect(),
+                );
+            }
+            SimpleQueryMessage::CommandComplete(count) if result.columns.is_empty() => {
+                result.rows_affected = Some(count);
+            }
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+
+/// SQL listing tables, or the columns of `table`
+fn describe_sql(backend: &Backend, table: Option<&str>) -> String {
+    match (backend, table) {
+        (Backend::Sqlite(_), None) => "SELECT name, type FROM sqlite_master \
+             WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%' ORDER BY name"
+            .to_string(),
+        (Backend::Sqlite(_), Some(table)) => format!(
+            "SELECT name, type, \"notnull\" AS not_null, dflt_value AS default_value, pk \
+             FROM pragma_table_info({})",
+            quote_literal(table)
+        ),
+        (Backend::Postgres(_), None) => "SELECT table_schema, table_name, table_type \
+             FROM information_schema.tables \
+             WHERE table_schema NOT IN ('pg_catalog', 'information_schema') \
+             ORDER BY table_schema, table_name"
+            .to_string(),
+        (Backend::Postgres(_), Some(table)) => {
+            // `schema.table` or just `table`
+            let (schema_filter, table) = match table.split_once('.') {
+                Some((schema, table)) => (
+                    format!("AND table_schema = {} ", quote_literal(schema)),
+                    table,
+                ),
+                None => (String::new(), table),
+            };
+            format!(
+                "SELECT column_name, data_type, is_nullable, column_default \
+                 FROM information_schema.columns WHERE table_name = {} {}\
+                 ORDER BY table_schema, ordinal_position",
+                quote_literal(table),
+                schema_filter
+            )
+        }
+    }
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// A statement of a SQL script
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Statement {
+    /// The statement as written, without the trailing semicolon
+    sql: String,
+    /// First keyword, lowercased
+    keyword: String,
+    /// Lowercased words outside of strings, quoted names and comments
+    words: Vec<String>,
+}
+
+/// Split a script into statements, ignoring semicolons inside strings,
+/// quoted identifiers and comments
+fn split_statements(sql: &str) -> Vec<Statement> {
+    let mut statements = vec![];
+    let mut current = String::new();
+    let mut code = String::new();
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                current.push(c);
+                code.push(' ');
+                for inner in chars.by_ref() {
+                    current.push(inner);
+                    if inner == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for inner in chars.by_ref() {
+                    if inner == '\n' {
+                        current.push('\n');
+                        break;
+                    }
+                }
+                code.push(' ');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for inner in chars.by_ref() {
+                    if previous == '*' && inner == '/' {
+                        break;
+                    }
+                    previous = inner;
+                }
+                current.push(' ');
+                code.push(' ');
+            }
+            ';' => {
+                statements.extend(Statement::new(&current, &code));
+                current.clear();
+                code.clear();
+            }
+            _ => {
+                current.push(c);
+                code.push(c);
+            }
+        }
+    }
+    statements.extend(Statement::new(&current, &code));
+    statements
+}
+
+impl Statement {
+    fn new(sql: &str, code: &str) -> Option<Self> {
+        let words: Vec<String> = code
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        let keyword = words.first()?.clone();
+        Some(Self {
+            sql: sql.trim().to_string(),
+            keyword,
+            words,
+        })
+    }
+}
+
+/// Refuse statements that could write. The database is also put in
+/// read-only mode, so this mainly gives the model a clear error.
+fn check_read_only(statement: &Statement) -> Result<(), String> {
+    if !READ_KEYWORDS.contains(&statement.keyword.as_str()) {
+        return Err(format!(
+            "The connection is read-only; {} statements are not allowed",
+            statement.keyword.to_uppercase()
+        ));
+    }
+    if statement.keyword == "pragma" && statement.sql.contains('=') {
+        return Err("The connection is read-only; PRAGMA assignments are not allowed".to_string());
+    }
+    if let Some(word) = statement
+        .words
+        .iter()
+        .find(|word| WRITE_KEYWORDS.contains(&word.as_str()))
+    {
+        return Err(format!(
+            "The connection is read-only; {} is not allowed",
+            word.to_uppercase()
+        ));
+    }
+    Ok(())
+}
+
+fn to_output(result: Result<serde_json::Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn query_database_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: QUERY_DATABASE_TOOL.to_string(),
+        description: "Run a SQL statement against a configured SQLite or Postgres database. \
+                      Read-only connections only accept single read statements."
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "database": {
+                    "type": "string",
+                    "description": "Name of the database; optional when only one is configured"
+                },
+                "sql": {
+                    "type": "string",
+                    "description": "A single SQL statement"
+                },
+                "maxRows": {
+                    "type": "integer",
+                    "description": "Most rows to return"
+                }
+            },
+            "required": ["sql"]
+        }),
+        requires_approval: false,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::Network, ToolCapability::FilesystemRead],
+    }
+}
+
+fn describe_database_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: DESCRIBE_DATABASE_TOOL.to_string(),
+        description: "List the tables of a configured database, or the columns of one table"
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "database": {
+                    "type": "string",
+                    "description": "Name of the database; optional when only one is configured"
+                },
+                "table": {
+                    "type": "string",
+                    "description": "Table whose columns to list, optionally as schema.table"
+                }
+            }
+        }),
+        requires_approval: false,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::Network, ToolCapability::FilesystemRead],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::TaskSettings;
+    use tempfile::TempDir;
+
+    fn statement(sql: &str) -> Statement {
+        let mut statements = split_statements(sql);
+        assert_eq!(statements.len(), 1, "{}", sql);
+        statements.remove(0)
+    }
+
+    #[test]
+    fn test_read_only_check() {
+        let statements = split_statements(
+            "SELECT 'a;b' AS \"x;y\" -- trailing; comment\n; /* ; */ DELETE FROM users;",
+        );
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0].sql, "SELECT 'a;b' AS \"x;y\"");
+        assert_eq!(statements[1].keyword, "delete");
+
+        assert!(check_read_only(&statement("select * from users where name = 'drop'")).is_ok());
+        assert!(check_read_only(&statement("PRAGMA table_info(users)")).is_ok());
+        assert!(check_read_only(&statement("EXPLAIN SELECT 1")).is_ok());
+        assert!(check_read_only(&statement("UPDATE users SET name = 'x'")).is_err());
+        assert!(check_read_only(&statement("PRAGMA journal_mode = DELETE")).is_err());
+        assert!(check_read_only(&statement(
+            "WITH gone AS (DELETE FROM users RETURNING *) SELECT * FROM gone"
+        ))
+        .is_err());
+
+        assert_eq!(
+            Backend::parse("sqlite:data/app.db", Path::new("/work")).unwrap(),
+            Backend::Sqlite(PathBuf::from("/work/data/app.db"))
+        );
+        assert!(Backend::parse("mysql://localhost/db", Path::new("/work")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_queries() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.db");
+        let db = libsql::Builder::new_local(&path).build().await.unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL); \
+             INSERT INTO users (name) VALUES ('ada'), ('grace'), ('linus');",
+        )
+        .await
+        .unwrap();
+
+        let connection = DatabaseConnection {
+            name: "app".to_string(),
+            url: "sqlite:app.db".to_string(),
+            read_only: true,
+            max_rows: None,
+        };
+        let root = temp_dir.path();
+
+        let result = run(
+            &connection,
+            root,
+            "SELECT id, name FROM users ORDER BY id",
+            true,
+            2,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.columns, vec!["id", "name"]);
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![serde_json::json!(1), serde_json::json!("ada")],
+                vec![serde_json::json!(2), serde_json::json!("grace")],
+            ]
+        );
+        assert!(result.truncated);
+
+        let error = run(&connection, root, "DELETE FROM users", true, 10)
+            .await
+            .unwrap_err();
+        assert!(error.contains("read-only"));
+        assert!(run(&connection, root, "SELECT 1; SELECT 2", true, 10)
+            .await
+            .is_err());
+
+        let schema = describe_sql(&Backend::Sqlite(path.clone()), Some("users"));
+        let result = run(&connection, root, &schema, true, 10).await.unwrap();
+        assert_eq!(result.rows.len(), 2);
+        assert_eq!(result.rows[1][0], "name");
+
+        let result = run(
+            &connection,
+            root,
+            "DELETE FROM users WHERE id = 3",
+            false,
+            10,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.rows_affected, Some(1));
+    }
+
+    #[test]
+    fn test_database_tools_need_file_access_too() {
+        // SQLite databases are local files
+        let read_only = TaskSettings {
+            tool_capabilities: Some(vec![ToolCapability::FilesystemRead]),
+            ..TaskSettings::default()
+        };
+        let network_only = TaskSettings {
+            tool_capabilities: Some(vec![ToolCapability::Network]),
+            ..TaskSettings::default()
+        };
+        let both = TaskSettings {
+            tool_capabilities: Some(vec![
+                ToolCapability::Network,
+                ToolCapability::FilesystemRead,
+            ]),
+            ..TaskSettings::default()
+        };
+        for definition in [query_database_definition(), describe_database_definition()] {
+            assert!(!definition.is_permitted(&read_only));
+            assert!(!definition.is_permitted(&network_only));
+            assert!(definition.is_permitted(&both));
+        }
+    }
+}
@@ -0,0 +1,496 @@
+//! Diagram Tool
+//!
+//! The `render_diagram` tool renders Mermaid or PlantUML source to SVG or PNG
+//! and stores the image as an attachment of the session. Rendering uses the
+//! mermaid-cli (`mmdc`) from the workspace or PATH and `plantuml` from PATH,
+//! so a diagram that renders is known to be valid.
+
+use crate::core::cancellation::{run_command, CancellationToken};
+use crate::core::tools::{ToolContext, ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::{ToolDefinition, ToolRequest};
+use crate::storage::{Attachment, AttachmentOrigin, AttachmentsRepository, ToolCapability};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+
+pub const RENDER_DIAGRAM_TOOL: &str = "render_diagram";
+
+const RENDER_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_SOURCE_BYTES: usize = 100_000;
+
+/// Keywords a Mermaid diagram can start with
+const MERMAID_DIAGRAMS: &[&str] = &[
+    "graph",
+    "flowchart",
+    "sequenceDiagram",
+    "classDiagram",
+    "stateDiagram",
+    "stateDiagram-v2",
+    "erDiagram",
+    "journey",
+    "gantt",
+    "pie",
+    "quadrantChart",
+    "requirementDiagram",
+    "gitGraph",
+    "C4Context",
+    "C4Container",
+    "C4Component",
+    "C4Dynamic",
+    "C4Deployment",
+    "mindmap",
+    "timeline",
+    "sankey-beta",
+    "xychart-beta",
+    "block-beta",
+    "packet-beta",
+    "architecture-beta",
+    "kanban",
+    "radar-beta",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DiagramFormat {
+    Mermaid,
+    Plantuml,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ImageFormat {
+    Svg,
+    Png,
+}
+
+impl ImageFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Svg => "svg",
+            ImageFormat::Png => "png",
+        }
+    }
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            ImageFormat::Svg => "image/svg+xml",
+            ImageFormat::Png => "image/png",
+        }
+    }
+}
+
+/// Input of the `render_diagram` tool
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiagramInput {
+    source: String,
+    /// Detected from the source when left out
+    #[serde(default)]
+    format: Option<DiagramFormat>,
+    #[serde(default)]
+    output: Option<ImageFormat>,
+    /// File name of the image, without extension
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Renders diagrams into session attachments
+#[derive(Clone)]
+pub struct DiagramRenderer {
+    attachments: AttachmentsRepository,
+}
+
+impl DiagramRenderer {
+    pub fn new(attachments: AttachmentsRepository) -> Self {
+        Self { attachments }
+    }
+
+    /// Register the `render_diagram` tool
+    pub async fn register_tool(&self, registry: &ToolRegistry) -> Result<(), String> {
+        let renderer = self.clone();
+        let handler: ToolHandler = Arc::new(move |request, context| {
+            let renderer = renderer.clone();
+            Box::pin(async move { to_output(renderer.render_tool(&request, &context).await) })
+        });
+
+        registry
+            .register(render_diagram_definition(), handler)
+            .await
+    }
+
+    async fn render_tool(
+        &self,
+        request: &ToolRequest,
+        context: &ToolContext,
+    ) -> Result<serde_json::Value, String> {
+        let input: DiagramInput = serde_json::from_value(request.input.clone())
+            .map_err(|e| format!("Invalid input: {}", e))?;
+        let root = context
+            .worktree_path
+            .clone()
+            .unwrap_or_else(|| context.workspace_root.clone());
+        let format = input.format.unwrap_or_else(|| detect_format(&input.source));
+        let output = input.output.unwrap_or(ImageFormat::Svg);
+
+        let image = render(
+            Path::new(&root),
+            &input.source,
+            format,
+            output,
+            &context.cancel_token,
+        )
+        .await?;
+
+        let attachment = Attachment {
+            id: format!("att_{}", uuid::Uuid::new_v4().to_string().replace("-", "")),
+            session_id: context.session_id.clone(),
+            filename: format!(
+                "{}.{}",
+                file_stem(input.name.as_deref()),
+                output.extension()
+            ),
+            mime_type: output.mime_type().to_string(),
+            size: image.len() as i64,
+            path: String::new(),
+            created_at: chrono::Utc::now().timestamp(),
+            origin: AttachmentOrigin::Generated,
+        };
+        self.attachments
+            .create_attachment(&attachment, &image)
+            .await?;
+        // The repository decides where the file goes
+        let attachment = self
+            .attachments
+            .get_attachment(&attachment.id)
+            .await?
+            .ok_or_else(|| format!("Attachment {} was not stored", attachment.id))?;
+
+        Ok(serde_json::json!({
+            "attachmentId": attachment.id,
+            "path": attachment.path,
+            "filename": attachment.filename,
+            "mimeType": attachment.mime_type,
+            "size": attachment.size,
+        }))
+    }
+}
+
+fn detect_format(source: &str) -> DiagramFormat {
+    if source.trim_start().starts_with("@start") {
+        DiagramFormat::Plantuml
+    } else {
+        DiagramFormat::Mermaid
+    }
+}
+
+/// Check Mermaid source names a known diagram type
+fn validate_mermaid(source: &str) -> Result<(), String> {
+    let mut lines = source.lines().map(str::trim);
+    let mut in_front_matter = false;
+    let first = lines.find(|line| {
+        if *line == "---" {
+            in_front_matter = !in_front_matter;
+            return false;
+        }
+        !in_front_matter && !line.is_empty() && !line.starts_with("%%")
+    });
+
+    let keyword = first
+        .and_then(|line| line.split_whitespace().next())
+        .ok_or("Mermaid source has no diagram")?;
+    // e.g. "graph TD;" or "flowchart LR"
+    let keyword = keyword.trim_end_matches(';');
+    if MERMAID_DIAGRAMS.contains(&keyword) {
+        Ok(())
+    } else {
+        Err(format!("Unknown Mermaid diagram type '{}'", keyword))
+    }
+}
+
+/// PlantUML source with its `@startuml`/`@enduml` markers, adding them when missing
+fn plantuml_source(source: &str) -> Result<String, String> {
+    let trimmed = source.trim();
+    let starts = trimmed.starts_with("@start");
+    let ends = trimmed
+        .lines()
+        .last()
+        .is_some_and(|line| line.trim().starts_with("@end"));
+    match (starts, ends) {
+        (true, true) => Ok(format!("{}\n", trimmed)),
+        (false, false) => Ok(format!("@startuml\n{}\n@enduml\n", trimmed)),
+        (true, false) => Err("PlantUML source is missing its @end marker".to_string()),
+        (false, true) => Err("PlantUML source is missing its @start marker".to_string()),
+    }
+}
+
+/// Render a diagram to image bytes
+async fn render(
+    root: &Path,
+    source: &str,
+    format: DiagramFormat,
+    output: ImageFormat,
+    cancel_token: &CancellationToken,
+) -> Result<Vec<u8>, String> {
+    if source.trim().is_empty() {
+        return Err("Missing 'source'".to_string());
+    }
+    if source.len() > MAX_SOURCE_BYTES {
+        return Err(format!(
+            "Diagram source is larger than {} bytes",
+            MAX_SOURCE_BYTES
+        ));
+    }
+
+    let dir = std::env::temp_dir().join(format!("talkcody-diagram-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let result = render_in(&dir, root, source, format, output, cancel_token).await;
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+async fn render_in(
+    dir: &Path,
+    root: &Path,
+    source: &str,
+    format: DiagramFormat,
+    output: ImageFormat,
+    cancel_token: &CancellationToken,
+) -> Result<Vec<u8>, String> {
+    let (name, input, image, mut command) = match format {
+        DiagramFormat::Mermaid => {
+            validate_mermaid(source)?;
+            let program = mermaid_cli(root).ok_or(
+                "Mermaid CLI (mmdc) not found; install @mermaid-js/mermaid-cli in the \
+                 workspace or globally",
+            )?;
+            let input = dir.join("diagram.mmd");
+            let image = dir.join(format!("diagram.{}", output.extension()));
+            let mut command = Command::new(program);
+            command
+                .arg("-i")
+                .arg(&input)
+                .arg("-o")
+                .arg(&image)
+                .arg("-q");
+            ("mmdc", input, image, command)
+        }
+        DiagramFormat::Plantuml => {
+            let program = which::which("plantuml").map_err(|_| "plantuml not found in PATH")?;
+            let input = dir.join("diagram.puml");
+            // PlantUML writes the image next to its input
+            let image = dir.join(format!("diagram.{}", output.extension()));
+            let mut command = Command::new(program);
+            command
+                .arg(format!("-t{}", output.extension()))
+                .arg("-failfast2")
+                .arg(&input);
+            ("plantuml", input, image, command)
+        }
+    };
+
+    let source = match format {
+        DiagramFormat::Mermaid => source.to_string(),
+        DiagramFormat::Plantuml => plantuml_source(source)?,
+    };
+    std::fs::write(&input, source)
+        .map_err(|e| format!("Failed to write {}: {}", input.display(), e))?;
+    command.current_dir(dir).stdin(std::process::Stdio::null());
+
+    let result = tokio::time::timeout(RENDER_TIMEOUT, run_command(command, cancel_token))
+        .await
+        .map_err(|_| format!("{} timed out", name))?
+        .map_err(|e| format!("Failed to run {}: {}", name, e))?;
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        let stdout = String::from_utf8_lossy(&result.stdout);
+        let detail = if stderr.trim().is_empty() {
+            stdout.trim()
+        } else {
+            stderr.trim()
+        };
+        return Err(format!("Diagram failed to render: {}", detail));
+    }
+
+    match std::fs::read(&image) {
+        Ok(bytes) if !bytes.is_empty() => Ok(bytes),
+        _ => Err(format!("{} produced no image", name)),
+    }
+}
+
+/// mermaid-cli from the workspace's node_modules, then from PATH
+fn mermaid_cli(root: &Path) -> Option<PathBuf> {
+    let name = if cfg!(windows) { "mmdc.cmd" } else { "mmdc" };
+    let local = root.join("node_modules/.bin").join(name);
+    if local.is_file() {
+        return Some(local);
+    }
+    which::which("mmdc").ok()
+}
+
+/// Safe file name for an image, defaulting to "diagram"
+fn file_stem(name: Option<&str>) -> String {
+    let stem: String = name
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let stem = stem.trim_matches('-');
+    if stem.is_empty() {
+        "diagram".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+fn to_output(result: Result<serde_json::Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn render_diagram_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: RENDER_DIAGRAM_TOOL.to_string(),
+        description: "Render a Mermaid or PlantUML diagram to an SVG or PNG image stored as a \
+                      session attachment. Fails with the renderer's error when the diagram is \
+                      invalid, so fix the source and retry. Returns the attachment id and path."
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "source": {
+                    "type": "string",
+                    "description": "Diagram source"
+                },
+                "format": {
+                    "type": "string",
+                    "enum": ["mermaid", "plantuml"],
+                    "description": "Diagram language (default: detected from the source)"
+                },
+                "output": {
+                    "type": "string",
+                    "enum": ["svg", "png"],
+                    "description": "Image format (default svg)"
+                },
+                "name": {
+                    "type": "string",
+                    "description": "File name for the image, without extension"
+                }
+            },
+            "required": ["source"]
+        }),
+        requires_approval: false,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::Shell, ToolCapability::FilesystemWrite],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::TaskSettings;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validate_source() {
+        assert_eq!(
+            detect_format("@startuml\nA -> B\n@enduml"),
+            DiagramFormat::Plantuml
+        );
+        assert_eq!(detect_format("graph TD\nA-->B"), DiagramFormat::Mermaid);
+
+        assert!(validate_mermaid("%% comment\nflowchart LR\n  A --> B").is_ok());
+        assert!(validate_mermaid("---\ntitle: Flow\n---\ngraph TD;\nA-->B").is_ok());
+        assert!(validate_mermaid("flowchat LR\nA --> B").is_err());
+        assert!(validate_mermaid("%% only a comment").is_err());
+
+        assert_eq!(
+            plantuml_source("A -> B").unwrap(),
+            "@startuml\nA -> B\n@enduml\n"
+        );
+        assert!(plantuml_source("@startuml\nA -> B\n@enduml").is_ok());
+        assert!(plantuml_source("@startuml\nA -> B").is_err());
+
+        assert_eq!(file_stem(Some("Auth flow/v2")), "Auth-flow-v2");
+        assert_eq!(file_stem(Some("..")), "diagram");
+        assert_eq!(file_stem(None), "diagram");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_render_with_workspace_mermaid_cli() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let bin = root.join("node_modules/.bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let mmdc = bin.join("mmdc");
+        // Stand-in CLI that writes a fixed image to its -o argument
+        std::fs::write(
+            &mmdc,
+            "#!/bin/sh\nwhile [ \"$1\" != \"-o\" ]; do shift; done\nprintf '<svg/>' > \"$2\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&mmdc, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let token = CancellationToken::new();
+        let image = render(
+            root,
+            "graph TD\nA-->B",
+            DiagramFormat::Mermaid,
+            ImageFormat::Svg,
+            &token,
+        )
+        .await
+        .unwrap();
+        assert_eq!(image, b"<svg/>");
+
+        let err = render(
+            root,
+            "grpah TD\nA-->B",
+            DiagramFormat::Mermaid,
+            ImageFormat::Svg,
+            &token,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("grpah"));
+    }
+
+    #[test]
+    fn test_render_diagram_needs_shell_and_write_access() {
+        // Renderers are separate programs, and images are saved as attachments
+        let definition = render_diagram_definition();
+        let permits = |capabilities: Vec<ToolCapability>| {
+            definition.is_permitted(&TaskSettings {
+                tool_capabilities: Some(capabilities),
+                ..TaskSettings::default()
+            })
+        };
+        assert!(!permits(vec![ToolCapability::FilesystemRead]));
+        assert!(!permits(vec![ToolCapability::Shell]));
+        assert!(permits(vec![
+            ToolCapability::Shell,
+            ToolCapability::FilesystemWrite
+        ]));
+    }
+}
@@ -0,0 +1,444 @@
+//! Edit Tool
+//!
+//! The `edit_file` tool replaces a string in a file instead of rewriting the
+//! whole file. The target must appear exactly once unless every occurrence
+//! is replaced. When it doesn't appear verbatim, whole lines matching it up to
+//! whitespace are used instead, and the new text is re-indented to fit. The
+//! result carries a unified diff of the change.
+
+use crate::core::patch;
+use crate::core::tools::{ToolContext, ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::{ToolDefinition, ToolRequest};
+use crate::file_watcher::PauseGuard;
+use crate::path_guard;
+use crate::storage::ToolCapability;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+
+pub const EDIT_FILE_TOOL: &str = "edit_file";
+
+/// Unchanged lines shown around each change in the diff
+const DIFF_CONTEXT: usize = 3;
+
+/// Result of a string replacement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileEdit {
+    #[serde(skip)]
+    pub content: String,
+    pub replacements: usize,
+    /// The target only matched when ignoring whitespace
+    pub fuzzy: bool,
+    pub diff: String,
+}
+
+/// Lines a replacement changed, as `[start, end)` ranges
+#[derive(Debug, Clone, Copy)]
+struct Change {
+    old_start: usize,
+    old_end: usize,
+    new_start: usize,
+    new_end: usize,
+}
+
+/// Register the `edit_file` tool
+pub async fn register_tool(registry: &ToolRegistry) -> Result<(), String> {
+    let handler: ToolHandler = Arc::new(|request, context| {
+        Box::pin(async move { to_output(edit_file_tool(&request, &context)) })
+    });
+
+    registry.register(edit_file_definition(), handler).await
+}
+
+fn edit_file_tool(
+    request: &ToolRequest,
+    context: &ToolContext,
+) -> Result<serde_json::Value, String> {
+    let input = &request.input;
+    let field = |name: &str| {
+        input
+            .get(name)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("Missing '{}'", name))
+    };
+    let path = field("path")?;
+    let old = field("oldString")?;
+    let new = field("newString")?;
+    let replace_all = input
+        .get("replaceAll")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let root = context
+        .worktree_path
+        .as_deref()
+        .unwrap_or(&context.workspace_root);
+
+    let full_path = path_guard::resolve_relative(Path::new(root), path)?;
+    let content = std::fs::read_to_string(&full_path)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let edit = edit(&content, old, new, replace_all, path)?;
+    // Report the temp file write and rename as one change to the file
+    let _pause = PauseGuard::acquire();
+    patch::write_file(&full_path, &edit.content)?;
+
+    let mut data = serde_json::to_value(&edit).map_err(|e| e.to_string())?;
+    data["path"] = serde_json::json!(path);
+    Ok(data)
+}
+
+/// Replace `old` with `new` in a file's content. `path` only labels the diff.
+pub fn edit(
+    content: &str,
+    old: &str,
+    new: &str,
+    replace_all: bool,
+    path: &str,
+) -> Result<FileEdit, String> {
+    if old.is_empty() {
+        return Err("oldString is empty; use write_file to create files".to_string());
+    }
+    if old == new {
+        return Err("oldString and newString are the same".to_string());
+    }
+    // Match on LF line endings and restore CRLF afterwards
+    let crlf = content.contains("\r\n");
+    let text = content.replace("\r\n", "\n");
+    let old = old.replace("\r\n", "\n");
+    let new = new.replace("\r\n", "\n");
+
+    let exact: Vec<(usize, usize)> = text
+        .match_indices(old.as_str())
+        .map(|(start, matched)| (start, start + matched.len()))
+        .collect();
+    let (matches, fuzzy) = if exact.is_empty() {
+        (find_fuzzy(&text, &old), true)
+    } else {
+        (exact, false)
+    };
+
+    match matches.len() {
+        0 => return Err("oldString was not found in the file".to_string()),
+        1 => {}
+        n if fuzzy || !replace_all => {
+            let lines: Vec<String> = matches
+                .iter()
+                .map(|(start, _)| line_number(&text, *start).to_string())
+                .collect();
+            return Err(format!(
+                "oldString matches {} places{} (lines {}); include more surrounding lines to \
+                 pick one{}",
+                n,
+                if fuzzy {
+                    " when ignoring whitespace"
+                } else {
+                    ""
+                },
+                lines.join(", "),
+                if fuzzy { "" } else { " or set replaceAll" }
+            ));
+        }
+        _ => {}
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut changes = vec![];
+    let mut last = 0;
+    // Line shift of the new content after the replacements so far
+    let mut shift: isize = 0;
+    for &(start, end) in &matches {
+        let replacement = if fuzzy {
+            reindent(&new, &old, &text[start..end])
+        } else {
+            new.clone()
+        };
+        let old_start = line_number(&text, start) - 1;
+        let old_end = old_start + text[start..end].matches('\n').count() + 1;
+        let new_start = (old_start as isize + shift) as usize;
+        let new_end = new_start + replacement.matches('\n').count() + 1;
+        shift += (new_end - new_start) as isize - (old_end - old_start) as isize;
+        changes.push(Change {
+            old_start,
+            old_end,
+            new_start,
+            new_end,
+        });
+
+        result.push_str(&text[last..start]);
+        result.push_str(&replacement);
+        last = end;
+    }
+    result.push_str(&text[last..]);
+
+    let diff = unified_diff(path, &text, &result, &changes);
+    Ok(FileEdit {
+        content: if crlf {
+            result.replace('\n', "\r\n")
+        } else {
+            result
+        },
+        replacements: matches.len(),
+        fuzzy,
+        diff,
+    })
+}
+
+/// 1-based line of a byte offset
+fn line_number(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+fn normalize_whitespace(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Byte ranges of the whole-line blocks equal to `old` up to whitespace
+fn find_fuzzy(text: &str, old: &str) -> Vec<(usize, usize)> {
+    let target: Vec<String> = old
+        .trim_matches('\n')
+        .split('\n')
+        .map(normalize_whitespace)
+        .collect();
+    if target.iter().all(|line| line.is_empty()) {
+        return vec![];
+    }
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut line_starts = vec![0];
+    line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+
+    let mut matches = vec![];
+    let mut i = 0;
+    while i + target.len() <= lines.len() {
+        let found = target
+            .iter()
+            .enumerate()
+            .all(|(j, line)| normalize_whitespace(lines[i + j]) == *line);
+        if found {
+            let last = i + target.len() - 1;
+            matches.push((line_starts[i], line_starts[last] + lines[last].len()));
+            i += target.len();
+        } else {
+            i += 1;
+        }
+    }
+    matches
+}
+
+/// Fit `new` into the lines `old` matched when ignoring whitespace: drop the
+/// blank lines `old` had around it, which the match excludes, and move its
+/// lines from `old`'s indentation to the file's
+fn reindent(new: &str, old: &str, matched: &str) -> String {
+    let leading = old.len() - old.trim_start_matches('\n').len();
+    let trailing = old.len() - old.trim_end_matches('\n').len();
+    let mut new = new;
+    for _ in 0..leading {
+        new = new.strip_prefix('\n').unwrap_or(new);
+    }
+    for _ in 0..trailing {
+        new = new.strip_suffix('\n').unwrap_or(new);
+    }
+
+    let indent = |text: &str| -> String {
+        text.split('\n')
+            .find(|line| !line.trim().is_empty())
+            .map(|line| line[..line.len() - line.trim_start().len()].to_string())
+            .unwrap_or_default()
+    };
+    let (from, to) = (indent(old), indent(matched));
+    if from == to {
+        return new.to_string();
+    }
+    new.split('\n')
+        .map(|line| match line.strip_prefix(from.as_str()) {
+            Some(rest) if !line.trim().is_empty() => format!("{}{}", to, rest),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Unified diff of the changed lines, with nearby changes sharing a hunk
+fn unified_diff(path: &str, old: &str, new: &str, changes: &[Change]) -> String {
+    let old_lines: Vec<&str> = old.split('\n').collect();
+    let new_lines: Vec<&str> = new.split('\n').collect();
+
+    // Drop unchanged lines at the edges of each change
+    let changes: Vec<Change> = changes
+        .iter()
+        .map(|&change| {
+            let mut change = change;
+            while change.old_start < change.old_end
+                && change.new_start < change.new_end
+                && old_lines[change.old_start] == new_lines[change.new_start]
+            {
+                change.old_start += 1;
+                change.new_start += 1;
+            }
+            while change.old_end > change.old_start
+                && change.new_end > change.new_start
+                && old_lines[change.old_end - 1] == new_lines[change.new_end - 1]
+            {
+                change.old_end -= 1;
+                change.new_end -= 1;
+            }
+            change
+        })
+        .filter(|change| change.old_start < change.old_end || change.new_start < change.new_end)
+        .collect();
+
+    let mut groups: Vec<Vec<Change>> = vec![];
+    for change in changes {
+        match groups.last_mut() {
+            Some(group)
+                if change.old_start <= group[group.len() - 1].old_end + 2 * DIFF_CONTEXT =>
+            {
+                group.push(change)
+            }
+            _ => groups.push(vec![change]),
+        }
+    }
+
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", path, path);
+    for group in groups {
+        let (first, last) = (group[0], group[group.len() - 1]);
+        let old_from = first.old_start.saturating_sub(DIFF_CONTEXT);
+        let old_to = (last.old_end + DIFF_CONTEXT).min(old_lines.len());
+        let new_from = first.new_start - (first.old_start - old_from);
+        let new_to = last.new_end + (old_to - last.old_end);
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_from + 1,
+            old_to - old_from,
+            new_from + 1,
+            new_to - new_from
+        ));
+
+        let mut position = old_from;
+        for change in &group {
+            for line in &old_lines[position..change.old_start] {
+                diff.push_str(&format!(" {}\n", line));
+            }
+            for line in &old_lines[change.old_start..change.old_end] {
+                diff.push_str(&format!("-{}\n", line));
+            }
+            for line in &new_lines[change.new_start..change.new_end] {
+                diff.push_str(&format!("+{}\n", line));
+            }
+            position = change.old_end;
+        }
+        for line in &old_lines[position..old_to] {
+            diff.push_str(&format!(" {}\n", line));
+        }
+    }
+    diff
+}
+
+fn to_output(result: Result<serde_json::Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn edit_file_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: EDIT_FILE_TOOL.to_string(),
+        description: "Replace a string in a file. oldString must match exactly once, including \
+                      enough surrounding lines to be unique, unless replaceAll is set. If it \
+                      doesn't match exactly, lines matching it up to whitespace are replaced. \
+                      Returns a diff of the change."
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path to the file"
+                },
+                "oldString": {
+                    "type": "string",
+                    "description": "Text to replace"
+                },
+                "newString": {
+                    "type": "string",
+                    "description": "Replacement text"
+                },
+                "replaceAll": {
+                    "type": "boolean",
+                    "description": "Replace every exact occurrence of oldString"
+                }
+            },
+            "required": ["path", "oldString", "newString"]
+        }),
+        requires_approval: true,
+        modifies_files: true,
+        capabilities: vec![ToolCapability::FilesystemWrite],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n";
+
+    #[test]
+    fn test_exact_edit_and_ambiguity() {
+        let edit = edit(SOURCE, "let x = 1;", "let x = 2;", false, "src/main.rs").unwrap();
+        assert_eq!(
+            edit.content,
+            "fn main() {\n    let x = 2;\n    println!(\"{}\", x);\n}\n"
+        );
+        assert!(!edit.fuzzy);
+        assert_eq!(
+            edit.diff,
+            "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,5 +1,5 @@\n fn main() {\n-    \
+             let x = 1;\n+    let x = 2;\n     println!(\"{}\", x);\n }\n \n"
+        );
+
+        let error = super::edit(SOURCE, "x", "y", false, "a").unwrap_err();
+        assert!(error.contains("matches 2 places (lines 2, 3)"), "{}", error);
+        let edit = super::edit(SOURCE, "x", "y", true, "a").unwrap();
+        assert_eq!(edit.replacements, 2);
+        assert!(edit
+            .content
+            .contains("let y = 1;\n    println!(\"{}\", y);"));
+        assert_eq!(edit.diff.matches("@@").count(), 2);
+
+        assert!(super::edit(SOURCE, "missing", "y", false, "a").is_err());
+        assert!(super::edit(SOURCE, "x", "x", false, "a").is_err());
+    }
+
+    #[test]
+    fn test_fuzzy_edit_reindents() {
+        // Indented with tabs and extra spaces, unlike the file
+        let old = "\tlet x =  1;\n\tprintln!(\"{}\", x);\n";
+        let new = "\tlet x = 1;\n\tif x > 0 {\n\t\tprintln!(\"{}\", x);\n\t}\n";
+        let edit = edit(SOURCE, old, new, false, "a").unwrap();
+        assert!(edit.fuzzy);
+        assert_eq!(
+            edit.content,
+            "fn main() {\n    let x = 1;\n    if x > 0 {\n    \tprintln!(\"{}\", x);\n    }\n}\n"
+        );
+
+        let repeated = "a {\n  b\n}\na {\n    b\n}\n";
+        let error = super::edit(repeated, "a {\n b", "c", false, "a").unwrap_err();
+        assert!(error.contains("when ignoring whitespace"), "{}", error);
+    }
+
+    #[test]
+    fn test_crlf_is_kept() {
+        let content = "one\r\ntwo\r\nthree\r\n";
+        let edit = edit(content, "two\n", "2\n", false, "a").unwrap();
+        assert_eq!(edit.content, "one\r\n2\r\nthree\r\n");
+        assert!(edit.diff.contains("-two\n+2\n"));
+    }
+}
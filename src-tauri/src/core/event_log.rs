@@ -0,0 +1,382 @@
+//! Event Log
+//!
+//! Every runtime event is appended to its session's event log before it is
+//! passed on to subscribers, so the log holds everything a client has seen.
+//! A session's messages, tool results and task states can be rebuilt from
+//! the log alone, for resuming streams and for audit. Logged events are also
+//! published, tagged with their session and sequence, to stream subscribers.
+//! Secrets in an event's text are masked before it is logged or passed on.
+
+use crate::core::types::{
+    EventSender, RuntimeEvent, RuntimeTaskId, RuntimeTaskState, TaskHandle, ToolRequest, ToolResult,
+};
+use crate::llm::redaction;
+use crate::storage::{
+    ChatHistoryRepository, Message, MessageRole, RuntimeEventRecord, SessionId, TodoList,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc, RwLock};
+
+/// A runtime event with the session it belongs to and its log sequence
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggedEvent {
+    pub session_id: Option<SessionId>,
+    /// Sequence in the session's event log, when the event was logged
+    pub sequence: Option<i64>,
+    pub event: RuntimeEvent,
+}
+
+/// Session state rebuilt from its event log
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebuiltSession {
+    pub session_id: SessionId,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    /// Messages in the order they were created
+    pub messages: Vec<Message>,
+    pub tool_results: Vec<ToolResult>,
+    /// Tool calls still waiting for approval
+    pub pending_tool_calls: Vec<ToolRequest>,
+    /// Last known state of each task of the session
+    pub task_states: HashMap<RuntimeTaskId, RuntimeTaskState>,
+    /// Text streamed since the last assistant message
+    pub streaming_text: String,
+    /// Latest todo list the agent wrote
+    #[serde(default)]
+    pub todos: Option<TodoList>,
+    /// Sequence of the last event applied; streams resume after it
+    pub last_sequence: Option<i64>,
+}
+
+/// Fold a session's logged events, in sequence order, into its state
+pub fn rebuild_session(
+    session_id: &str,
+    records: &[RuntimeEventRecord],
+) -> Result<RebuiltSession, String> {
+    let mut rebuilt = RebuiltSession {
+        session_id: session_id.to_string(),
+        ..RebuiltSession::default()
+    };
+
+    for record in records {
+        let event: RuntimeEvent = serde_json::from_value(record.event.clone()).map_err(|e| {
+            format!(
+                "Failed to parse logged event {} ({}): {}",
+                record.sequence, record.event_type, e
+            )
+        })?;
+        rebuilt.last_sequence = Some(record.sequence);
+
+        match event {
+            RuntimeEvent::MessageCreated { message, .. } => {
+                if message.role == MessageRole::Assistant {
+                    rebuilt.streaming_text.clear();
+                }
+                match rebuilt.messages.iter_mut().find(|m| m.id == message.id) {
+                    Some(existing) => *existing = message,
+                    None => rebuilt.messages.push(message),
+                }
+            }
+            RuntimeEvent::Token { token, .. } => rebuilt.streaming_text.push_str(&token),
+            RuntimeEvent::ToolCallRequested { request, .. } => {
+                rebuilt.pending_tool_calls.push(request);
+            }
+            RuntimeEvent::ToolCallCompleted { result, .. } => {
+                rebuilt
+                    .pending_tool_calls
+                    .retain(|request| request.tool_call_id != result.tool_call_id);
+                rebuilt.tool_results.push(result);
+            }
+            RuntimeEvent::TaskStateChanged { task_id, state, .. } => {
+                rebuilt.task_states.insert(task_id, state);
+            }
+            RuntimeEvent::SessionUpdated { title, summary, .. } => {
+                if title.is_some() {
+                    rebuilt.title = title;
+                }
+                rebuilt.summary = Some(summary);
+            }
+            RuntimeEvent::TodosUpdated { todos, .. } => rebuilt.todos = Some(todos),
+            _ => {}
+        }
+    }
+
+    Ok(rebuilt)
+}
+
+/// Start appending events to the event log. Returns the sender the runtime
+/// emits on; logged events are forwarded to `downstream` and published to
+/// `published` in order.
+pub fn spawn(
+    chat_history: ChatHistoryRepository,
+    tasks: Arc<RwLock<HashMap<RuntimeTaskId, TaskHandle>>>,
+    downstream: EventSender,
+    published: broadcast::Sender<LoggedEvent>,
+) -> EventSender {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<RuntimeEvent>();
+
+    tokio::spawn(async move {
+        // Sessions of tasks whose events only name the task
+        let mut task_sessions: HashMap<RuntimeTaskId, SessionId> = HashMap::new();
+
+        while let Some(event) = receiver.recv().await {
+            let event = redact(event);
+            let (task_id, session_id) = event_ids(&event);
+            let session_id = match (session_id, task_id) {
+                (Some(session_id), task_id) => {
+                    if let Some(task_id) = task_id {
+                        task_sessions.insert(task_id.to_string(), session_id.to_string());
+                    }
+                    Some(session_id.to_string())
+                }
+                (None, Some(task_id)) => match tasks.read().await.get(task_id) {
+                    Some(handle) => Some(handle.session_id.clone()),
+                    None => task_sessions.get(task_id).cloned(),
+                },
+                (None, None) => None,
+            };
+
+            let sequence = match &session_id {
+                Some(session_id) => append(&chat_history, session_id, task_id, &event).await,
+                None => None,
+            };
+            if let RuntimeEvent::TaskCompleted { task_id, .. } = &event {
+                task_sessions.remove(task_id);
+            }
+
+            // Without subscribers there is nobody to publish to
+            let _ = published.send(LoggedEvent {
+                session_id,
+                sequence,
+                event: event.clone(),
+            });
+            let _ = downstream.send(event);
+        }
+    });
+
+    sender
+}
+
+/// The event with the secrets in its text masked
+fn redact(event: RuntimeEvent) -> RuntimeEvent {
+    let mut value = match serde_json::to_value(&event) {
+        Ok(value) => value,
+        Err(_) => return event,
+    };
+    if redaction::shared().redact_json(&mut value) == 0 {
+        return event;
+    }
+    match serde_json::from_value(value) {
+        Ok(redacted) => redacted,
+        Err(e) => {
+            log::warn!("Failed to redact runtime event: {}", e);
+            event
+        }
+    }
+}
+
+async fn append(
+    chat_history: &ChatHistoryRepository,
+    session_id: &str,
+    task_id: Option<&str>,
+    event: &RuntimeEvent,
+) -> Option<i64> {
+    let value = match serde_json::to_value(event) {
+        Ok(value) => value,
+        Err(e) => {
+            log::warn!("Failed to serialize runtime event: {}", e);
+            return None;
+        }
+    };
+    let event_type = value
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    match chat_history
+        .append_runtime_event(session_id, task_id, &event_type, &value)
+        .await
+    {
+        Ok(sequence) => Some(sequence),
+        Err(e) => {
+            log::warn!(
+                "Failed to log {} event of session {}: {}",
+                event_type,
+                session_id,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Task and session an event belongs to, as far as it names them
+pub(crate) fn event_ids(event: &RuntimeEvent) -> (Option<&str>, Option<&str>) {
+    match event {
+        RuntimeEvent::TaskStateChanged { task_id, .. }
+        | RuntimeEvent::TaskQueuePositionChanged { task_id, .. }
+        | RuntimeEvent::Usage { task_id, .. }
+        | RuntimeEvent::ToolCallStarted { task_id, .. }
+        | RuntimeEvent::ToolCallRequested { task_id, .. }
+        | RuntimeEvent::ToolOutput { task_id, .. }
+        | RuntimeEvent::ShellCommandDecided { task_id, .. }
+        | RuntimeEvent::ToolCallCompleted { task_id, .. } => (Some(task_id), None),
+        RuntimeEvent::ContextCompacted {
+            task_id,
+            session_id,
+            ..
+        }
+        | RuntimeEvent::IterationStarted {
+            task_id,
+            session_id,
+            ..
+        }
+        | RuntimeEvent::BudgetExceeded {
+            task_id,
+            session_id,
+            ..
+        }
+        | RuntimeEvent::PlanReady {
+            task_id,
+            session_id,
+            ..
+        }
+        | RuntimeEvent::TodosUpdated {
+            task_id,
+            session_id,
+            ..
+        }
+        | RuntimeEvent::WorktreeReady {
+            task_id,
+            session_id,
+            ..
+        }
+        | RuntimeEvent::SecretsRedacted {
+            task_id,
+            session_id,
+            ..
+        }
+        | RuntimeEvent::TaskCompleted {
+            task_id,
+            session_id,
+        } => (Some(task_id), Some(session_id)),
+        RuntimeEvent::MessageCreated { session_id, .. }
+        | RuntimeEvent::Token { session_id, .. }
+        | RuntimeEvent::Reasoning { session_id, .. }
+        | RuntimeEvent::SessionUpdated { session_id, .. } => (None, Some(session_id)),
+        RuntimeEvent::Error {
+            task_id,
+            session_id,
+            ..
+        } => (task_id.as_deref(), session_id.as_deref()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MessageContent;
+
+    fn record(sequence: i64, event: RuntimeEvent) -> RuntimeEventRecord {
+        let event = serde_json::to_value(event).unwrap();
+        RuntimeEventRecord {
+            sequence,
+            session_id: "session-1".to_string(),
+            task_id: None,
+            event_type: event["type"].as_str().unwrap().to_string(),
+            event,
+            created_at: 0,
+        }
+    }
+
+    fn message(id: &str, role: MessageRole, text: &str) -> Message {
+        Message {
+            id: id.to_string(),
+            session_id: "session-1".to_string(),
+            role,
+            content: MessageContent::Text {
+                text: text.to_string(),
+            },
+            created_at: 0,
+            tool_call_id: None,
+            parent_id: None,
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn test_rebuild_session() {
+        let records = vec![
+            record(
+                1,
+                RuntimeEvent::MessageCreated {
+                    session_id: "session-1".to_string(),
+                    message: message("msg-1", MessageRole::User, "Write a file"),
+                },
+            ),
+            record(
+                2,
+                RuntimeEvent::TaskStateChanged {
+                    task_id: "task-1".to_string(),
+                    state: RuntimeTaskState::Running,
+                    previous_state: RuntimeTaskState::Pending,
+                },
+            ),
+            record(
+                3,
+                RuntimeEvent::ToolCallRequested {
+                    task_id: "task-1".to_string(),
+                    request: ToolRequest {
+                        tool_call_id: "call-1".to_string(),
+                        name: "write_file".to_string(),
+                        input: serde_json::json!({ "path": "a.txt" }),
+                    },
+                },
+            ),
+            record(
+                4,
+                RuntimeEvent::ToolCallCompleted {
+                    task_id: "task-1".to_string(),
+                    result: ToolResult {
+                        tool_call_id: "call-1".to_string(),
+                        success: true,
+                        output: serde_json::json!("written"),
+                        error: None,
+                    },
+                },
+            ),
+            record(
+                5,
+                RuntimeEvent::Token {
+                    session_id: "session-1".to_string(),
+                    token: "Done".to_string(),
+                },
+            ),
+        ];
+
+        let state = rebuild_session("session-1", &records).unwrap();
+        assert_eq!(state.messages.len(), 1);
+        assert!(state.pending_tool_calls.is_empty());
+        assert_eq!(state.tool_results[0].output, "written");
+        assert_eq!(state.task_states["task-1"], RuntimeTaskState::Running);
+        assert_eq!(state.streaming_text, "Done");
+        assert_eq!(state.last_sequence, Some(5));
+
+        let mut records = records;
+        records.push(record(
+            6,
+            RuntimeEvent::MessageCreated {
+                session_id: "session-1".to_string(),
+                message: message("msg-2", MessageRole::Assistant, "Done"),
+            },
+        ));
+        let state = rebuild_session("session-1", &records).unwrap();
+        assert_eq!(state.messages.len(), 2);
+        assert!(state.streaming_text.is_empty());
+    }
+}
@@ -0,0 +1,391 @@
+//! Format Tool
+//!
+//! `format_code` runs the project's formatter on files: rustfmt for Rust,
+//! prettier for web languages, black for Python and gofmt for Go. A file no
+//! formatter is found for is formatted by its language server instead. Each
+//! file's result carries a unified diff of what the formatter changed.
+
+use crate::core::cancellation::{run_command, CancellationToken};
+use crate::core::patch;
+use crate::core::tools::{ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::ToolDefinition;
+use crate::lsp;
+use crate::path_guard;
+use crate::storage::ToolCapability;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+
+pub const FORMAT_CODE_TOOL: &str = "format_code";
+
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(60);
+/// Unchanged lines shown around each change in the diff
+const DIFF_CONTEXT: usize = 3;
+
+const PRETTIER_EXTENSIONS: &[&str] = &[
+    "js", "jsx", "mjs", "cjs", "ts", "tsx", "mts", "cts", "json", "css", "scss", "less", "html",
+    "vue", "md", "mdx", "yaml", "yml", "graphql",
+];
+/// Files that configure prettier for a project
+const PRETTIER_CONFIGS: &[&str] = &[
+    ".prettierrc",
+    ".prettierrc.json",
+    ".prettierrc.json5",
+    ".prettierrc.yaml",
+    ".prettierrc.yml",
+    ".prettierrc.toml",
+    ".prettierrc.js",
+    ".prettierrc.cjs",
+    ".prettierrc.mjs",
+    "prettier.config.js",
+    "prettier.config.cjs",
+    "prettier.config.mjs",
+];
+
+/// Formatter command found for a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Formatter {
+    name: &'static str,
+    program: PathBuf,
+    /// Arguments before the file path
+    args: Vec<String>,
+}
+
+impl Formatter {
+    fn new(name: &'static str, program: impl Into<PathBuf>, args: &[&str]) -> Self {
+        Self {
+            name,
+            program: program.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+}
+
+/// Register the `format_code` tool
+pub async fn register_tool(registry: &ToolRegistry) -> Result<(), String> {
+    let handler: ToolHandler = Arc::new(|request, context| {
+        Box::pin(async move {
+            let root = context
+                .worktree_path
+                .clone()
+                .unwrap_or_else(|| context.workspace_root.clone());
+            to_output(format_files(Path::new(&root), &request.input, &context.cancel_token).await)
+        })
+    });
+
+    registry.register(format_code_definition(), handler).await
+}
+
+async fn format_files(
+    root: &Path,
+    input: &serde_json::Value,
+    cancel_token: &CancellationToken,
+) -> Result<serde_json::Value, String> {
+    let mut paths: Vec<&str> = input
+        .get("paths")
+        .and_then(|v| v.as_array())
+        .map(|paths| paths.iter().filter_map(|p| p.as_str()).collect())
+        .unwrap_or_default();
+    paths.extend(input.get("path").and_then(|v| v.as_str()));
+    if paths.is_empty() {
+        return Err("Missing 'paths'".to_string());
+    }
+
+    let mut files = Vec::with_capacity(paths.len());
+    let mut failed = 0;
+    for path in paths {
+        match format_file(root, path, cancel_token).await {
+            Ok(file) => files.push(file),
+            Err(e) => {
+                failed += 1;
+                files.push(serde_json::json!({ "path": path, "error": e }));
+            }
+        }
+    }
+    if failed == files.len() {
+        let errors: Vec<&str> = files
+            .iter()
+            .filter_map(|file| file["error"].as_str())
+            .collect();
+        return Err(errors.join("\n"));
+    }
+
+    Ok(serde_json::json!({ "files": files }))
+}
+
+/// Format one file in place
+async fn format_file(
+    root: &Path,
+    path: &str,
+    cancel_token: &CancellationToken,
+) -> Result<serde_json::Value, String> {
+    let full_path = path_guard::resolve_relative(root, path)?;
+    let before = std::fs::read_to_string(&full_path)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    let (formatter, after) = match detect(root, &full_path) {
+        Some(formatter) => {
+            run(&formatter, root, &full_path, cancel_token).await?;
+            let after = std::fs::read_to_string(&full_path)
+                .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            (formatter.name.to_string(), after)
+        }
+        None => {
+            let language = lsp::language_for_path(&full_path)
+                .ok_or_else(|| format!("No formatter found for {}", path))?;
+            let formatted = lsp::format_document(&language, root, &full_path, &before).await?;
+            if let Some(ref formatted) = formatted {
+                patch::write_file(&full_path, formatted)?;
+            }
+            (
+                format!("{} language server", language),
+                formatted.unwrap_or_else(|| before.clone()),
+            )
+        }
+    };
+
+    let changed = before != after;
+    let mut file = serde_json::json!({
+        "path": path,
+        "formatter": formatter,
+        "changed": changed,
+    });
+    if changed {
+        file["diff"] = serde_json::json!(similar::TextDiff::from_lines(&before, &after)
+            .unified_diff()
+            .context_radius(DIFF_CONTEXT)
+            .header(&format!("a/{}", path), &format!("b/{}", path))
+            .to_string());
+    }
+    Ok(file)
+}
+
+async fn run(
+    formatter: &Formatter,
+    root: &Path,
+    path: &Path,
+    cancel_token: &CancellationToken,
+) -> Result<(), String> {
+    let mut command = Command::new(&formatter.program);
+    command
+        .args(&formatter.args)
+        .arg(path)
+        .current_dir(root)
+        .stdin(std::process::Stdio::null());
+
+    let output = tokio::time::timeout(FORMAT_TIMEOUT, run_command(command, cancel_token))
+        .await
+        .map_err(|_| format!("{} timed out", formatter.name))?
+        .map_err(|e| format!("Failed to run {}: {}", formatter.name, e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let detail = if stderr.trim().is_empty() {
+        stdout.trim()
+    } else {
+        stderr.trim()
+    };
+    Err(format!("{} failed: {}", formatter.name, detail))
+}
+
+/// The formatter a project uses for a file, if one is installed
+fn detect(root: &Path, path: &Path) -> Option<Formatter> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    // Directories from the file's up to the workspace root
+    let dirs: Vec<&Path> = path
+        .parent()?
+        .ancestors()
+        .take_while(|dir| dir.starts_with(root))
+        .collect();
+    let executable = |name: &str| {
+        if cfg!(windows) {
+            format!("{}.cmd", name)
+        } else {
+            name.to_string()
+        }
+    };
+
+    match extension.as_str() {
+        "rs" => {
+            let program = which::which("rustfmt").ok()?;
+            let edition = rust_edition(&dirs);
+            Some(Formatter::new("rustfmt", program, &["--edition", &edition]))
+        }
+        "py" => {
+            let venv = if cfg!(windows) {
+                "Scripts/black.exe"
+            } else {
+                "bin/black"
+            };
+            let program = [".venv", "venv"]
+                .iter()
+                .map(|dir| root.join(dir).join(venv))
+                .find(|program| program.is_file())
+                .or_else(|| which::which("black").ok())?;
+            Some(Formatter::new("black", program, &["-q"]))
+        }
+        "go" => {
+            let program = which::which("gofmt").ok()?;
+            Some(Formatter::new("gofmt", program, &["-w"]))
+        }
+        extension if PRETTIER_EXTENSIONS.contains(&extension) => {
+            let local = dirs
+                .iter()
+                .map(|dir| dir.join("node_modules/.bin").join(executable("prettier")))
+                .find(|program| program.is_file());
+            // A global prettier is only used by projects configured for it
+            let program = local.or_else(|| {
+                dirs.iter()
+                    .any(|dir| uses_prettier(dir))
+                    .then(|| which::which("prettier").ok())
+                    .flatten()
+            })?;
+            Some(Formatter::new(
+                "prettier",
+                program,
+                &["--write", "--log-level", "warn"],
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn uses_prettier(dir: &Path) -> bool {
+    if PRETTIER_CONFIGS
+        .iter()
+        .any(|config| dir.join(config).is_file())
+    {
+        return true;
+    }
+    std::fs::read_to_string(dir.join("package.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .is_some_and(|package| {
+            package.get("prettier").is_some()
+                || package["devDependencies"].get("prettier").is_some()
+        })
+}
+
+/// Edition of the nearest Cargo.toml, defaulting to 2021
+fn rust_edition(dirs: &[&Path]) -> String {
+    dirs.iter()
+        .find_map(|dir| std::fs::read_to_string(dir.join("Cargo.toml")).ok())
+        .and_then(|manifest| {
+            manifest.lines().find_map(|line| {
+                let value = line.trim().strip_prefix("edition")?.trim_start();
+                let value = value.strip_prefix('=')?.trim();
+                Some(value.trim_matches('"').to_string())
+            })
+        })
+        .unwrap_or_else(|| "2021".to_string())
+}
+
+fn to_output(result: Result<serde_json::Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn format_code_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: FORMAT_CODE_TOOL.to_string(),
+        description: "Format files with the project's formatter (rustfmt, prettier, black or \
+                      gofmt), falling back to the language server. Returns a diff of each \
+                      changed file."
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "paths": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Files to format, relative to the workspace root"
+                }
+            },
+            "required": ["paths"]
+        }),
+        requires_approval: true,
+        modifies_files: true,
+        capabilities: vec![ToolCapability::FilesystemWrite],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let bin = root.join("web/node_modules/.bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let prettier = bin.join(if cfg!(windows) {
+            "prettier.cmd"
+        } else {
+            "prettier"
+        });
+        std::fs::write(&prettier, "").unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nedition = \"2018\"\n",
+        )
+        .unwrap();
+
+        let formatter = detect(root, &root.join("web/src/App.tsx")).unwrap();
+        assert_eq!(formatter.name, "prettier");
+        assert_eq!(formatter.program, prettier);
+        // Only files under the web project see its prettier
+        assert!(detect(root, &root.join("docs/README.md")).is_none());
+        assert!(detect(root, &root.join("notes.txt")).is_none());
+        assert_eq!(rust_edition(&[&root.join("src"), root]), "2018");
+        assert_eq!(rust_edition(&[&root.join("src")]), "2021");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_format_file_reports_diff() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let bin = root.join("node_modules/.bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let prettier = bin.join("prettier");
+        // Stand-in formatter rewriting the last argument
+        std::fs::write(
+            &prettier,
+            "#!/bin/sh\nfor f; do :; done\nprintf 'const a = 1;\\nconst b = 2;\\n' > \"$f\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&prettier, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(root.join("a.ts"), "const a=1\nconst b = 2;\n").unwrap();
+
+        let token = CancellationToken::new();
+        let file = format_file(root, "a.ts", &token).await.unwrap();
+        assert_eq!(file["formatter"], "prettier");
+        assert_eq!(file["changed"], true);
+        assert!(file["diff"]
+            .as_str()
+            .unwrap()
+            .contains("-const a=1\n+const a = 1;\n"));
+
+        let file = format_file(root, "a.ts", &token).await.unwrap();
+        assert_eq!(file["changed"], false);
+        assert!(format_file(root, "../a.ts", &token).await.is_err());
+    }
+}
@@ -0,0 +1,276 @@
+//! Grep Tool
+//!
+//! The `grep` tool searches file contents with the workspace content search,
+//! so the agent gets regex search without shelling out to `rg`. It uses the
+//! walker's exclusions and `.talkcodyignore`, and caps how many matches it
+//! returns overall and per file.
+
+use crate::content_search::{search_workspace, ContentSearchOptions};
+use crate::core::tools::{ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::ToolDefinition;
+use crate::path_guard;
+use crate::platform::types::SearchResult;
+use crate::storage::ToolCapability;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+
+pub const GREP_TOOL: &str = "grep";
+
+const DEFAULT_CONTEXT_LINES: usize = 2;
+const DEFAULT_MAX_RESULTS: usize = 100;
+const MAX_RESULTS: usize = 500;
+const MAX_MATCHES_PER_FILE: usize = 20;
+
+/// Input of the `grep` tool
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GrepInput {
+    pattern: String,
+    /// Directory to search, relative to the workspace root
+    #[serde(default)]
+    path: Option<String>,
+    /// Only search files with these extensions
+    #[serde(default)]
+    file_types: Option<Vec<String>>,
+    /// Match the pattern as plain text
+    #[serde(default)]
+    literal: bool,
+    #[serde(default)]
+    case_insensitive: bool,
+    #[serde(default)]
+    whole_word: bool,
+    #[serde(default)]
+    context_lines: Option<usize>,
+    #[serde(default)]
+    max_results: Option<usize>,
+}
+
+/// Register the `grep` tool
+pub async fn register_tool(registry: &ToolRegistry) -> Result<(), String> {
+    let handler: ToolHandler = Arc::new(|request, context| {
+        Box::pin(async move {
+            let root = context
+                .worktree_path
+                .clone()
+                .unwrap_or_else(|| context.workspace_root.clone());
+            let result = tokio::task::spawn_blocking(move || grep(Path::new(&root), request.input))
+                .await
+                .unwrap_or_else(|e| Err(format!("Search task failed: {}", e)));
+            to_output(result)
+        })
+    });
+
+    registry.register(grep_definition(), handler).await
+}
+
+/// Search the files under `root` for a tool call's input
+fn grep(root: &Path, input: serde_json::Value) -> Result<serde_json::Value, String> {
+    let input: GrepInput =
+        serde_json::from_value(input).map_err(|e| format!("Invalid input: {}", e))?;
+    if input.pattern.is_empty() {
+        return Err("Missing 'pattern'".to_string());
+    }
+    let dir = match input.path.as_deref().map(str::trim) {
+        None | Some("") | Some(".") => root.to_path_buf(),
+        Some(path) => path_guard::resolve_relative(root, path)?,
+    };
+    if !dir.is_dir() {
+        return Err(format!(
+            "'{}' is not a directory",
+            input.path.unwrap_or_default()
+        ));
+    }
+
+    let max_results = input
+        .max_results
+        .unwrap_or(DEFAULT_MAX_RESULTS)
+        .clamp(1, MAX_RESULTS);
+    let options = ContentSearchOptions {
+        is_regex: !input.literal,
+        case_sensitive: !input.case_insensitive,
+        whole_word: input.whole_word,
+        max_matches_per_file: MAX_MATCHES_PER_FILE,
+        // One extra result tells whether the output was cut off
+        max_results: max_results + 1,
+        context_lines: input.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES),
+        file_types: input.file_types.map(|types| {
+            types
+                .iter()
+                .map(|t| t.trim_start_matches('.').to_string())
+                .collect()
+        }),
+        ..Default::default()
+    };
+
+    let mut results = search_workspace(&dir.to_string_lossy(), &input.pattern, &options, |_| {})?;
+    let truncated = results.len() > max_results;
+    results.truncate(max_results);
+    let files = {
+        let mut paths: Vec<&str> = results.iter().map(|r| r.path.as_str()).collect();
+        paths.dedup();
+        paths.len()
+    };
+    let matches: Vec<serde_json::Value> = results
+        .iter()
+        .map(|result| match_value(root, result))
+        .collect();
+
+    Ok(serde_json::json!({
+        "matches": matches,
+        "count": matches.len(),
+        "files": files,
+        "truncated": truncated,
+    }))
+}
+
+/// A match with its path relative to the workspace root
+fn match_value(root: &Path, result: &SearchResult) -> serde_json::Value {
+    let path = Path::new(&result.path);
+    let path = path.strip_prefix(root).unwrap_or(path);
+    serde_json::json!({
+        "path": path.to_string_lossy().replace('\\', "/"),
+        "line": result.line,
+        "column": result.column,
+        "text": result.text,
+        "contextBefore": result.context_before,
+        "contextAfter": result.context_after,
+    })
+}
+
+fn to_output(result: Result<serde_json::Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn grep_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: GREP_TOOL.to_string(),
+        description: "Search file contents with a regular expression. Skips dependency and \
+                      build directories, binary files and ignored paths. Returns matching lines \
+                      with their path, line number and surrounding lines, sorted by path."
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "description": "Regular expression (Rust regex syntax) to search for"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Directory to search, relative to the workspace root"
+                },
+                "fileTypes": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Only search files with these extensions, e.g. [\"rs\", \"ts\"]"
+                },
+                "literal": {
+                    "type": "boolean",
+                    "description": "Match the pattern as plain text"
+                },
+                "caseInsensitive": {
+                    "type": "boolean",
+                    "description": "Ignore case when matching"
+                },
+                "wholeWord": {
+                    "type": "boolean",
+                    "description": "Only match whole words"
+                },
+                "contextLines": {
+                    "type": "integer",
+                    "description": "Lines shown before and after each match (default 2, max 10)"
+                },
+                "maxResults": {
+                    "type": "integer",
+                    "description": "Maximum matching lines returned (default 100, max 500)"
+                }
+            },
+            "required": ["pattern"]
+        }),
+        requires_approval: false,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::FilesystemRead],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn workspace() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("node_modules/pkg")).unwrap();
+        fs::write(
+            temp_dir.path().join("src/lib.rs"),
+            "pub fn compute() -> i32 {\n    42\n}\n\npub fn Compute_all() {}\n",
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("src/notes.md"), "compute later\n").unwrap();
+        fs::write(
+            temp_dir.path().join("node_modules/pkg/index.js"),
+            "function compute() {}\n",
+        )
+        .unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_grep_matches_with_context() {
+        let temp_dir = workspace();
+        let root = temp_dir.path();
+
+        let result = grep(root, serde_json::json!({ "pattern": r"fn \w+\(" })).unwrap();
+        assert_eq!(result["count"], 2);
+        assert_eq!(result["files"], 1);
+        assert_eq!(result["truncated"], false);
+        let first = &result["matches"][0];
+        assert_eq!(first["path"], "src/lib.rs");
+        assert_eq!(first["line"], 1);
+        assert_eq!(first["contextAfter"][0], "    42");
+
+        let result = grep(
+            root,
+            serde_json::json!({ "pattern": "compute", "caseInsensitive": true, "fileTypes": [".rs"] }),
+        )
+        .unwrap();
+        assert_eq!(result["count"], 2);
+
+        let result = grep(
+            root,
+            serde_json::json!({ "pattern": "compute", "path": "src", "maxResults": 1 }),
+        )
+        .unwrap();
+        assert_eq!(result["count"], 1);
+        assert_eq!(result["truncated"], true);
+    }
+
+    #[test]
+    fn test_grep_rejects_invalid_input() {
+        let temp_dir = workspace();
+        let root = temp_dir.path();
+
+        assert!(grep(root, serde_json::json!({ "pattern": "(" })).is_err());
+        assert!(grep(root, serde_json::json!({ "pattern": "x", "path": "../" })).is_err());
+        assert!(grep(
+            root,
+            serde_json::json!({ "pattern": "x", "path": "src/lib.rs" })
+        )
+        .is_err());
+        assert!(grep(root, serde_json::json!({})).is_err());
+    }
+}
@@ -0,0 +1,431 @@
+//! Lifecycle Hooks
+//!
+//! User-configured shell commands or HTTP callbacks run before and after
+//! each tool call and when a task starts and ends in a session. Hooks are
+//! stored in settings under `hooks` for every project and `hooks.<project_id>`
+//! for one. A failing `pre_tool_call` or `session_start` hook blocks the
+//! action: a command fails by exiting non-zero, a callback by answering with
+//! a non-2xx status. Failures of the other hooks are only logged.
+
+use crate::core::cancellation::{run_command, CancellationToken};
+use crate::storage::{ChatHistoryRepository, SettingsRepository};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Settings key of the hooks that apply to every project
+pub const GLOBAL_HOOKS_KEY: &str = "hooks";
+
+/// Time a hook may take when its config sets no timeout
+pub const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 30;
+
+/// Point of a task at which hooks run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    PreToolCall,
+    PostToolCall,
+    SessionStart,
+    SessionEnd,
+}
+
+impl HookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::PreToolCall => "pre_tool_call",
+            HookEvent::PostToolCall => "post_tool_call",
+            HookEvent::SessionStart => "session_start",
+            HookEvent::SessionEnd => "session_end",
+        }
+    }
+
+    /// Whether a failing hook stops the action it runs before
+    pub fn can_block(&self) -> bool {
+        matches!(self, HookEvent::PreToolCall | HookEvent::SessionStart)
+    }
+}
+
+/// What a hook runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HookAction {
+    /// Shell command run in the workspace root, with the payload as JSON in
+    /// `TALKCODY_HOOK_PAYLOAD`
+    Command { command: String },
+    /// URL the payload is POSTed to as JSON
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+/// A configured hook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hook {
+    pub event: HookEvent,
+    #[serde(flatten)]
+    pub action: HookAction,
+    /// Tools a tool-call hook applies to; every tool when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+impl Hook {
+    fn applies_to(&self, event: HookEvent, tool_name: Option<&str>) -> bool {
+        self.event == event
+            && (self.tools.is_empty()
+                || tool_name.is_some_and(|name| self.tools.iter().any(|tool| tool == name)))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match &self.action {
+            HookAction::Command { command } if command.trim().is_empty() => {
+                Err("Hook command cannot be empty".to_string())
+            }
+            HookAction::Http { url, .. }
+                if !url.starts_with("http://") && !url.starts_with("https://") =>
+            {
+                Err(format!("Hook URL must be http or https: {}", url))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Data a hook receives about the action
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookPayload {
+    pub session_id: String,
+    pub task_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace_root: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_input: Option<serde_json::Value>,
+    /// Outcome of the tool call for `post_tool_call`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_result: Option<serde_json::Value>,
+    /// Final task state for `session_end`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+}
+
+/// Loads the hooks of a session's project and runs them
+#[derive(Clone)]
+pub struct HookManager {
+    settings: SettingsRepository,
+    chat_history: ChatHistoryRepository,
+    http: reqwest::Client,
+}
+
+impl HookManager {
+    pub fn new(settings: SettingsRepository, chat_history: ChatHistoryRepository) -> Self {
+        Self {
+            settings,
+            chat_history,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Hooks configured for a project, or for every project when `None`
+    pub async fn list(&self, project_id: Option<&str>) -> Result<Vec<Hook>, String> {
+        self.settings
+            .get_setting_or_default(&hooks_key(project_id), Vec::new())
+            .await
+    }
+
+    /// Replace the hooks of a project, or those for every project when `None`
+    pub async fn set(&self, project_id: Option<&str>, hooks: Vec<Hook>) -> Result<(), String> {
+        for hook in &hooks {
+            hook.validate()?;
+        }
+        let value = serde_json::to_value(&hooks)
+            .map_err(|e| format!("Failed to serialize hooks: {}", e))?;
+        self.settings
+            .set_setting(&hooks_key(project_id), &value)
+            .await
+    }
+
+    /// Run the hooks for `event` in order. For blocking events the first
+    /// failure is returned and later hooks do not run.
+    pub async fn run(
+        &self,
+        event: HookEvent,
+        payload: &HookPayload,
+        cancel_token: &CancellationToken,
+    ) -> Result<(), String> {
+        let hooks = match self.hooks_for_session(&payload.session_id).await {
+            Ok(hooks) => hooks,
+            Err(e) if event.can_block() => return Err(format!("Failed to load hooks: {}", e)),
+            Err(e) => {
+                log::warn!("Failed to load {} hooks: {}", event.as_str(), e);
+                return Ok(());
+            }
+        };
+
+        for hook in hooks
+            .iter()
+            .filter(|hook| hook.applies_to(event, payload.tool_name.as_deref()))
+        {
+            if let Err(e) = self.run_hook(hook, event, payload, cancel_token).await {
+                if event.can_block() {
+                    return Err(format!("Blocked by {} hook: {}", event.as_str(), e));
+                }
+                log::warn!("{} hook failed: {}", event.as_str(), e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn hooks_for_session(&self, session_id: &str) -> Result<Vec<Hook>, String> {
+        let project_id = self
+            .chat_history
+            .get_session(session_id)
+            .await?
+            .and_then(|session| session.project_id);
+        let mut hooks = self.list(None).await?;
+        if project_id.is_some() {
+            hooks.extend(self.list(project_id.as_deref()).await?);
+        }
+        Ok(hooks)
+    }
+
+    async fn run_hook(
+        &self,
+        hook: &Hook,
+        event: HookEvent,
+        payload: &HookPayload,
+        cancel_token: &CancellationToken,
+    ) -> Result<(), String> {
+        let timeout = Duration::from_secs(hook.timeout_secs.unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS));
+        let mut body = serde_json::to_value(payload)
+            .map_err(|e| format!("Failed to serialize hook payload: {}", e))?;
+        body["event"] = serde_json::json!(event.as_str());
+
+        let result = match &hook.action {
+            HookAction::Command { command } => {
+                tokio::time::timeout(
+                    timeout,
+                    run_shell_hook(command, event, &body, payload, cancel_token),
+                )
+                .await
+            }
+            HookAction::Http { url, headers } => {
+                tokio::time::timeout(timeout, self.post_hook(url, headers, body)).await
+            }
+        };
+        result.unwrap_or_else(|_| Err(format!("Timed out after {}s", timeout.as_secs())))
+    }
+
+    async fn post_hook(
+        &self,
+        url: &str,
+        headers: &HashMap<String, String>,
+        body: serde_json::Value,
+    ) -> Result<(), String> {
+        let mut request = self.http.post(url).json(&body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to call {}: {}", url, e))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let text = response.text().await.unwrap_or_default();
+        Err(failure_message(
+            &format!("{} returned {}", url, status),
+            &text,
+        ))
+    }
+}
+
+fn hooks_key(project_id: Option<&str>) -> String {
+    match project_id {
+        Some(project_id) => format!("{}.{}", GLOBAL_HOOKS_KEY, project_id),
+        None => GLOBAL_HOOKS_KEY.to_string(),
+    }
+}
+
+async fn run_shell_hook(
+    command: &str,
+    event: HookEvent,
+    body: &serde_json::Value,
+    payload: &HookPayload,
+    cancel_token: &CancellationToken,
+) -> Result<(), String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell
+        .arg(command)
+        .stdin(std::process::Stdio::null())
+        .env("TALKCODY_HOOK_EVENT", event.as_str())
+        .env("TALKCODY_HOOK_PAYLOAD", body.to_string())
+        .env("TALKCODY_SESSION_ID", &payload.session_id);
+    if let Some(root) = &payload.workspace_root {
+        shell.current_dir(root);
+    }
+    if let Some(tool_name) = &payload.tool_name {
+        shell.env("TALKCODY_TOOL_NAME", tool_name);
+    }
+
+    let output = run_command(shell, cancel_token).await?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let detail = if stderr.trim().is_empty() {
+        String::from_utf8_lossy(&output.stdout).to_string()
+    } else {
+        stderr.to_string()
+    };
+    let status = match output.status.code() {
+        Some(code) => format!("`{}` exited with code {}", command, code),
+        None => format!("`{}` was terminated", command),
+    };
+    Err(failure_message(&status, &detail))
+}
+
+fn failure_message(status: &str, detail: &str) -> String {
+    let detail = detail.trim();
+    if detail.is_empty() {
+        status.to_string()
+    } else {
+        format!("{}: {}", status, detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{Session, SessionStatus, Storage};
+    use tempfile::TempDir;
+
+    fn command_hook(event: HookEvent, command: &str, tools: &[&str]) -> Hook {
+        Hook {
+            event,
+            action: HookAction::Command {
+                command: command.to_string(),
+            },
+            tools: tools.iter().map(|tool| tool.to_string()).collect(),
+            timeout_secs: None,
+        }
+    }
+
+    #[test]
+    fn test_hook_config_format() {
+        let hook: Hook = serde_json::from_value(serde_json::json!({
+            "event": "pre_tool_call",
+            "type": "http",
+            "url": "https://hooks.example.com/edit",
+            "tools": ["write_file"]
+        }))
+        .unwrap();
+        assert!(hook.applies_to(HookEvent::PreToolCall, Some("write_file")));
+        assert!(!hook.applies_to(HookEvent::PreToolCall, Some("read_file")));
+        assert!(!hook.applies_to(HookEvent::PostToolCall, Some("write_file")));
+
+        let invalid = Hook {
+            action: HookAction::Http {
+                url: "file:///etc/passwd".to_string(),
+                headers: HashMap::new(),
+            },
+            ..hook
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_failing_pre_hook_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().to_path_buf(),
+            temp_dir.path().join("attachments"),
+        )
+        .await
+        .expect("Failed to create storage");
+        let now = chrono::Utc::now().timestamp();
+        storage
+            .chat_history
+            .create_session(&Session {
+                id: "session-1".to_string(),
+                project_id: Some("project-a".to_string()),
+                title: None,
+                summary: None,
+                status: SessionStatus::Running,
+                created_at: now,
+                updated_at: now,
+                last_event_id: None,
+                metadata: None,
+                starred: false,
+                archived_at: None,
+                user_id: None,
+            })
+            .await
+            .unwrap();
+
+        let manager = HookManager::new(storage.settings.clone(), storage.chat_history.clone());
+        manager
+            .set(
+                None,
+                vec![command_hook(HookEvent::PostToolCall, "exit 1", &[])],
+            )
+            .await
+            .unwrap();
+        manager
+            .set(
+                Some("project-a"),
+                vec![command_hook(
+                    HookEvent::PreToolCall,
+                    "echo \"no edits to $TALKCODY_TOOL_NAME\" >&2; exit 2",
+                    &["write_file"],
+                )],
+            )
+            .await
+            .unwrap();
+
+        let token = CancellationToken::new();
+        let payload = |tool: &str| HookPayload {
+            session_id: "session-1".to_string(),
+            task_id: "task-1".to_string(),
+            workspace_root: Some(temp_dir.path().to_string_lossy().to_string()),
+            tool_name: Some(tool.to_string()),
+            ..HookPayload::default()
+        };
+
+        let error = manager
+            .run(HookEvent::PreToolCall, &payload("write_file"), &token)
+            .await
+            .unwrap_err();
+        assert!(error.contains("exited with code 2: no edits to write_file"));
+        assert!(manager
+            .run(HookEvent::PreToolCall, &payload("read_file"), &token)
+            .await
+            .is_ok());
+        // Post hooks cannot block
+        assert!(manager
+            .run(HookEvent::PostToolCall, &payload("write_file"), &token)
+            .await
+            .is_ok());
+    }
+}
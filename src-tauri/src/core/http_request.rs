@@ -0,0 +1,458 @@
+//! HTTP Request Tool
+//!
+//! `http_request` sends a single HTTP request to a domain on the session's
+//! allowlist, so the agent can call internal APIs without shell access. The
+//! allowlist and secrets are stored in settings under `http_request` for
+//! every project and `http_request.<project_id>` for one. `${NAME}` in the
+//! URL, headers or body is replaced by the secret `NAME`, and secret values
+//! are masked again in the response. Redirects are returned, not followed,
+//! so they cannot leave the allowlist.
+
+use crate::core::tools::{ToolContext, ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::{ToolDefinition, ToolRequest};
+use crate::storage::{ChatHistoryRepository, SettingsRepository, ToolCapability};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// Settings key of the configuration for every project
+pub const GLOBAL_HTTP_REQUEST_KEY: &str = "http_request";
+
+pub const HTTP_REQUEST_TOOL: &str = "http_request";
+
+const METHODS: &[&str] = &["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const MAX_TIMEOUT_SECS: u64 = 120;
+/// Response body bytes returned to the agent
+const MAX_BODY_BYTES: usize = 100_000;
+
+/// `${NAME}` reference to a secret
+fn secret_reference() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap())
+}
+
+/// Domains the tool may call and secrets it may send
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HttpRequestConfig {
+    /// Domains requests may go to, with their subdomains. Empty allows none.
+    pub allowed_domains: Vec<String>,
+    /// Values for `${NAME}` references, by name
+    pub secrets: HashMap<String, String>,
+}
+
+impl HttpRequestConfig {
+    fn validate(&self) -> Result<(), String> {
+        for domain in &self.allowed_domains {
+            let domain = domain.trim();
+            if domain.is_empty() || domain.contains('/') || domain.contains(':') {
+                return Err(format!("Invalid domain: '{}'", domain));
+            }
+        }
+        for name in self.secrets.keys() {
+            let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(format!(
+                    "Invalid secret name '{}': use letters, digits and '_'",
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Add `other`'s domains and secrets; secrets already set are kept
+    fn merge(&mut self, other: HttpRequestConfig) {
+        for domain in other.allowed_domains {
+            if !self.allowed_domains.contains(&domain) {
+                self.allowed_domains.push(domain);
+            }
+        }
+        for (name, value) in other.secrets {
+            self.secrets.entry(name).or_insert(value);
+        }
+    }
+
+    fn allows(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_lowercase();
+        self.allowed_domains.iter().any(|domain| {
+            let domain = domain.trim().trim_start_matches("*.").to_lowercase();
+            host == domain || host.ends_with(&format!(".{}", domain))
+        })
+    }
+
+    /// Replace `${NAME}` references with secret values
+    fn inject(&self, text: &str) -> Result<String, String> {
+        if let Some(missing) = secret_reference()
+            .captures_iter(text)
+            .find(|caps| !self.secrets.contains_key(&caps[1]))
+        {
+            return Err(format!("Unknown secret '{}'", &missing[1]));
+        }
+        Ok(secret_reference()
+            .replace_all(text, |caps: &Captures| self.secrets[&caps[1]].clone())
+            .into_owned())
+    }
+
+    /// `inject` applied to every string in a JSON value
+    fn inject_value(&self, value: &serde_json::Value) -> Result<serde_json::Value, String> {
+        Ok(match value {
+            serde_json::Value::String(s) => serde_json::Value::String(self.inject(s)?),
+            serde_json::Value::Array(items) => serde_json::Value::Array(
+                items
+                    .iter()
+                    .map(|item| self.inject_value(item))
+                    .collect::<Result<_, _>>()?,
+            ),
+            serde_json::Value::Object(map) => serde_json::Value::Object(
+                map.iter()
+                    .map(|(key, item)| Ok((key.clone(), self.inject_value(item)?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+            other => other.clone(),
+        })
+    }
+
+    /// Replace secret values in `text` with their `${NAME}` reference
+    fn mask(&self, text: &str) -> String {
+        // Longer values first, so one containing another is masked whole
+        let mut secrets: Vec<(&String, &String)> = self
+            .secrets
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .collect();
+        secrets.sort_by_key(|(_, value)| std::cmp::Reverse(value.len()));
+        secrets
+            .into_iter()
+            .fold(text.to_string(), |text, (name, value)| {
+                text.replace(value.as_str(), &format!("${{{}}}", name))
+            })
+    }
+}
+
+/// Loads the HTTP configuration of a session's project and sends requests
+#[derive(Clone)]
+pub struct HttpRequestManager {
+    settings: SettingsRepository,
+    chat_history: ChatHistoryRepository,
+}
+
+impl HttpRequestManager {
+    pub fn new(settings: SettingsRepository, chat_history: ChatHistoryRepository) -> Self {
+        Self {
+            settings,
+            chat_history,
+        }
+    }
+
+    /// Configuration of a project, or the one for every project when `None`
+    pub async fn config(&self, project_id: Option<&str>) -> Result<HttpRequestConfig, String> {
+        self.settings
+            .get_setting_or_default(&config_key(project_id), HttpRequestConfig::default())
+            .await
+    }
+
+    /// Replace the configuration of a project, or the one for every project
+    /// when `None`
+    pub async fn set_config(
+        &self,
+        project_id: Option<&str>,
+        config: &HttpRequestConfig,
+    ) -> Result<(), String> {
+        config.validate()?;
+        let value = serde_json::to_value(config)
+            .map_err(|e| format!("Failed to serialize HTTP request config: {}", e))?;
+        self.settings
+            .set_setting(&config_key(project_id), &value)
+            .await
+    }
+
+    /// Register the `http_request` tool
+    pub async fn register_tool(&self, registry: &ToolRegistry) -> Result<(), String> {
+        let manager = self.clone();
+        let handler: ToolHandler = Arc::new(move |request, context| {
+            let manager = manager.clone();
+            Box::pin(async move {
+                let result = tokio::select! {
+                    result = manager.request_tool(&request, &context) => result,
+                    _ = context.cancel_token.cancelled() => Err("Cancelled".to_string()),
+                };
+                to_output(result)
+            })
+        });
+
+        registry.register(http_request_definition(), handler).await
+    }
+
+    /// The project's configuration merged with the global one; project
+    /// secrets win over global secrets of the same name
+    async fn config_for_session(&self, session_id: &str) -> Result<HttpRequestConfig, String> {
+        let project_id = self
+            .chat_history
+            .get_session(session_id)
+            .await?
+            .and_then(|session| session.project_id);
+        let mut config = match project_id {
+            Some(project_id) => self.config(Some(&project_id)).await?,
+            None => HttpRequestConfig::default(),
+        };
+        config.merge(self.config(None).await?);
+        Ok(config)
+    }
+
+    async fn request_tool(
+        &self,
+        request: &ToolRequest,
+        context: &ToolContext,
+    ) -> Result<serde_json::Value, String> {
+        let input = &request.input;
+        let config = self.config_for_session(&context.session_id).await?;
+
+        let method = input
+            .get("method")
+            .and_then(|v| v.as_str())
+            .unwrap_or("GET")
+            .to_uppercase();
+        if !METHODS.contains(&method.as_str()) {
+            return Err(format!("Unsupported method: {}", method));
+        }
+        let url = input
+            .get("url")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'url'".to_string())?;
+        let url =
+            url::Url::parse(&config.inject(url)?).map_err(|e| format!("Invalid URL: {}", e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Unsupported URL scheme: {}", url.scheme()));
+        }
+        let host = url.host_str().unwrap_or_default();
+        if !config.allows(host) {
+            return Err(format!(
+                "{} is not an allowed domain; add it to the project's HTTP request settings",
+                host
+            ));
+        }
+
+        let timeout = input
+            .get("timeoutSecs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_TIMEOUT_SECS)
+            .clamp(1, MAX_TIMEOUT_SECS);
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(Duration::from_secs(timeout))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        let method = reqwest::Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string())?;
+        let mut builder = client.request(method, url.clone());
+
+        let mut has_content_type = false;
+        if let Some(headers) = input.get("headers").and_then(|v| v.as_object()) {
+            for (name, value) in headers {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| format!("Header '{}' must be a string", name))?;
+                has_content_type |= name.eq_ignore_ascii_case("content-type");
+                builder = builder.header(name.as_str(), config.inject(value)?);
+            }
+        }
+        match input.get("body") {
+            None | Some(serde_json::Value::Null) => {}
+            Some(serde_json::Value::String(body)) => builder = builder.body(config.inject(body)?),
+            Some(body) => {
+                if !has_content_type {
+                    builder = builder.header("content-type", "application/json");
+                }
+                builder = builder.body(config.inject_value(body)?.to_string());
+            }
+        }
+
+        let mut response = builder
+            .send()
+            .await
+            .map_err(|e| config.mask(&format!("Request failed: {}", e)))?;
+
+        let status = response.status();
+        let headers: BTreeMap<String, String> = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    config.mask(&String::from_utf8_lossy(value.as_bytes())),
+                )
+            })
+            .collect();
+        let mut bytes = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| format!("Failed to read response: {}", e))?
+        {
+            bytes.extend_from_slice(&chunk);
+            if bytes.len() > MAX_BODY_BYTES {
+                bytes.truncate(MAX_BODY_BYTES);
+                truncated = true;
+                break;
+            }
+        }
+
+        let text = config.mask(&String::from_utf8_lossy(&bytes));
+        let is_json = headers
+            .get("content-type")
+            .is_some_and(|content_type| content_type.contains("json"));
+        let body = if is_json && !truncated {
+            serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text))
+        } else {
+            serde_json::Value::String(text)
+        };
+
+        Ok(serde_json::json!({
+            "url": config.mask(url.as_str()),
+            "status": status.as_u16(),
+            "ok": status.is_success(),
+            "headers": headers,
+            "body": body,
+            "truncated": truncated,
+        }))
+    }
+}
+
+fn config_key(project_id: Option<&str>) -> String {
+    match project_id {
+        Some(project_id) => format!("{}.{}", GLOBAL_HTTP_REQUEST_KEY, project_id),
+        None => GLOBAL_HTTP_REQUEST_KEY.to_string(),
+    }
+}
+
+fn to_output(result: Result<serde_json::Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn http_request_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: HTTP_REQUEST_TOOL.to_string(),
+        description: "Send an HTTP request to an allowed domain and return the status, headers \
+                      and body. Write ${NAME} in the URL, headers or body to use a configured \
+                      secret without seeing its value. Redirects are returned, not followed."
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "method": {
+                    "type": "string",
+                    "enum": METHODS,
+                    "description": "HTTP method (default GET)"
+                },
+                "url": {
+                    "type": "string",
+                    "description": "Absolute http or https URL"
+                },
+                "headers": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                    "description": "Request headers"
+                },
+                "body": {
+                    "description": "Request body; objects and arrays are sent as JSON"
+                },
+                "timeoutSecs": {
+                    "type": "integer",
+                    "description": "Seconds to wait for a response (default 30, max 120)"
+                }
+            },
+            "required": ["url"]
+        }),
+        requires_approval: true,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::Network],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HttpRequestConfig {
+        HttpRequestConfig {
+            allowed_domains: vec!["api.internal.dev".to_string()],
+            secrets: HashMap::from([
+                ("API_TOKEN".to_string(), "tok-123".to_string()),
+                ("API_TOKEN_LONG".to_string(), "tok-12345".to_string()),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_allowlist_and_merge() {
+        let mut config = config();
+        assert!(config.allows("api.internal.dev"));
+        assert!(config.allows("v2.API.internal.dev."));
+        assert!(!config.allows("internal.dev"));
+        assert!(!config.allows("api.internal.dev.evil.com"));
+
+        config.merge(HttpRequestConfig {
+            allowed_domains: vec!["*.example.com".to_string()],
+            secrets: HashMap::from([
+                ("API_TOKEN".to_string(), "global".to_string()),
+                ("OTHER".to_string(), "x".to_string()),
+            ]),
+        });
+        assert!(config.allows("docs.example.com"));
+        assert_eq!(config.secrets["API_TOKEN"], "tok-123");
+        assert_eq!(config.secrets["OTHER"], "x");
+
+        assert!(HttpRequestConfig::default().validate().is_ok());
+        let invalid = HttpRequestConfig {
+            allowed_domains: vec!["https://example.com".to_string()],
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+        let invalid = HttpRequestConfig {
+            secrets: HashMap::from([("API-TOKEN".to_string(), "x".to_string())]),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_secret_injection_and_masking() {
+        let config = config();
+        assert_eq!(
+            config.inject("Bearer ${API_TOKEN}").unwrap(),
+            "Bearer tok-123"
+        );
+        assert_eq!(config.inject("$API_TOKEN").unwrap(), "$API_TOKEN");
+        assert_eq!(
+            config.inject("${MISSING}").unwrap_err(),
+            "Unknown secret 'MISSING'"
+        );
+        assert_eq!(
+            config
+                .inject_value(&serde_json::json!({ "auth": ["${API_TOKEN_LONG}", 1] }))
+                .unwrap(),
+            serde_json::json!({ "auth": ["tok-12345", 1] })
+        );
+
+        assert_eq!(
+            config.mask("token=tok-12345&other=tok-123"),
+            "token=${API_TOKEN_LONG}&other=${API_TOKEN}"
+        );
+    }
+}
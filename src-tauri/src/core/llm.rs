@@ -0,0 +1,156 @@
+//! LLM Client
+//!
+//! Abstraction over the llm/ streaming stack used by the agent loop.
+//! The production client resolves the model and streams through `StreamRunner`;
+//! tests substitute a scripted client.
+
+use crate::llm::ai_services::context_compaction_service::ContextCompactionService;
+use crate::llm::ai_services::model_resolver::{resolve_model_identifier, FallbackStrategy};
+use crate::llm::ai_services::pricing_service::PricingService;
+use crate::llm::ai_services::stream_collector::StreamCollector;
+use crate::llm::ai_services::stream_runner::StreamRunner;
+use crate::llm::ai_services::types::{ContextCompactionRequest, TokenUsage};
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::types::{ModelConfig, StreamEvent, StreamTextRequest};
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// Maximum time to wait between two chunks of a response stream
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Streaming LLM client used by the agent loop
+#[async_trait]
+pub trait LlmClient: Send + Sync {
+    /// Stream a completion, invoking `on_event` for every parsed stream event
+    async fn stream(
+        &self,
+        request: StreamTextRequest,
+        on_event: &mut (dyn FnMut(StreamEvent) + Send),
+    ) -> Result<(), String>;
+
+    /// Context window of a model in tokens, when known
+    async fn context_length(&self, model: &str) -> Option<u32> {
+        self.model_config(model).await?.context_length
+    }
+
+    /// Capabilities and limits of a model, when it is configured
+    async fn model_config(&self, _model: &str) -> Option<ModelConfig> {
+        None
+    }
+
+    /// Cost in USD of a response's token usage, when the model's pricing is known
+    async fn usage_cost(&self, _model: &str, _usage: &TokenUsage) -> Option<f64> {
+        None
+    }
+
+    /// API key stored for a provider, such as a web search backend
+    async fn provider_api_key(&self, _provider_id: &str) -> Option<String> {
+        None
+    }
+
+    /// Summarize a rendered conversation for context compaction
+    async fn summarize(
+        &self,
+        conversation_history: String,
+        model: Option<String>,
+    ) -> Result<String, String> {
+        let prompt = ContextCompactionService::new().build_compaction_prompt(&conversation_history);
+        let request = StreamCollector::create_completion_request(model.unwrap_or_default(), prompt);
+
+        let mut summary = String::new();
+        self.stream(request, &mut |event| {
+            if let StreamEvent::TextDelta { text } = event {
+                summary.push_str(&text);
+            }
+        })
+        .await?;
+
+        Ok(summary.trim().to_string())
+    }
+}
+
+/// LLM client backed by the configured providers and API keys
+pub struct ProviderLlmClient {
+    registry: ProviderRegistry,
+    api_keys: ApiKeyManager,
+}
+
+impl ProviderLlmClient {
+    pub fn new(registry: ProviderRegistry, api_keys: ApiKeyManager) -> Self {
+        Self { registry, api_keys }
+    }
+
+    /// Resolve a model the way `stream` does, as `model@provider`
+    async fn resolve(&self, model: &str) -> Option<String> {
+        let preferred = Some(model.to_string()).filter(|model| !model.is_empty());
+        resolve_model_identifier(
+            &self.api_keys,
+            &self.registry,
+            preferred,
+            FallbackStrategy::AnyAvailable,
+        )
+        .await
+        .ok()
+    }
+}
+
+#[async_trait]
+impl LlmClient for ProviderLlmClient {
+    async fn stream(
+        &self,
+        mut request: StreamTextRequest,
+        on_event: &mut (dyn FnMut(StreamEvent) + Send),
+    ) -> Result<(), String> {
+        // An empty model falls back to any model with a configured provider
+        let preferred = Some(request.model.clone()).filter(|model| !model.is_empty());
+        request.model = resolve_model_identifier(
+            &self.api_keys,
+            &self.registry,
+            preferred,
+            FallbackStrategy::AnyAvailable,
+        )
+        .await?;
+
+        let runner = StreamRunner::new(self.registry.clone(), self.api_keys.clone());
+        runner
+            .stream(request, STREAM_IDLE_TIMEOUT, |event| on_event(event))
+            .await
+    }
+
+    async fn model_config(&self, model: &str) -> Option<ModelConfig> {
+        let resolved = self.resolve(model).await?;
+        let model_key = resolved.split('@').next().unwrap_or(&resolved);
+        let mut models = self.api_keys.load_models_config().await.ok()?;
+        models.models.remove(model_key)
+    }
+
+    async fn usage_cost(&self, model: &str, usage: &TokenUsage) -> Option<f64> {
+        let resolved = self.resolve(model).await?;
+        let models = self.api_keys.load_models_config().await.ok()?;
+        PricingService::new()
+            .calculate_cost(&resolved, usage, &models.models)
+            .ok()
+    }
+
+    async fn provider_api_key(&self, provider_id: &str) -> Option<String> {
+        let mut keys = self.api_keys.load_api_keys().await.ok()?;
+        keys.remove(provider_id).filter(|key| !key.is_empty())
+    }
+
+    async fn summarize(
+        &self,
+        conversation_history: String,
+        model: Option<String>,
+    ) -> Result<String, String> {
+        let request = ContextCompactionRequest {
+            conversation_history,
+            model,
+        };
+        let result = ContextCompactionService::new()
+            .compact_context(request, &self.api_keys, &self.registry)
+            .await?;
+
+        Ok(result.compressed_summary)
+    }
+}
@@ -0,0 +1,481 @@
+//! Long-term Memory
+//!
+//! Facts and preferences the agent keeps across sessions, either globally or
+//! for one project. The memories most relevant to a task are added to its
+//! system prompt, and the agent reads and writes them with the memory tools.
+
+use crate::core::tools::{ToolContext, ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::{ToolDefinition, ToolRequest};
+use crate::storage::{
+    ChatHistoryRepository, MemoriesRepository, Memory, MemoryKind, MemoryUpdates, ToolCapability,
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Most memories added to a system prompt or returned by `read_memories`
+pub const MAX_RELEVANT_MEMORIES: usize = 20;
+
+/// Stores memories and selects the ones relevant to a task
+#[derive(Clone)]
+pub struct MemoryManager {
+    memories: MemoriesRepository,
+    chat_history: ChatHistoryRepository,
+}
+
+impl MemoryManager {
+    pub fn new(memories: MemoriesRepository, chat_history: ChatHistoryRepository) -> Self {
+        Self {
+            memories,
+            chat_history,
+        }
+    }
+
+    /// List global memories plus those of `project_id`, oldest first
+    pub async fn list(&self, project_id: Option<&str>) -> Result<Vec<Memory>, String> {
+        self.memories.list_memories(project_id).await
+    }
+
+    /// Store a new memory. A `project_id` of `None` makes it global.
+    pub async fn create(
+        &self,
+        project_id: Option<String>,
+        kind: MemoryKind,
+        content: &str,
+    ) -> Result<Memory, String> {
+        let content = content.trim();
+        if content.is_empty() {
+            return Err("Memory content cannot be empty".to_string());
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let memory = Memory {
+            id: format!("mem_{}", uuid::Uuid::new_v4()),
+            project_id,
+            kind,
+            content: content.to_string(),
+            created_at: now,
+            updated_at: now,
+        };
+        self.memories.create_memory(&memory).await?;
+
+        Ok(memory)
+    }
+
+    /// Update a memory and return it
+    pub async fn update(&self, memory_id: &str, updates: MemoryUpdates) -> Result<Memory, String> {
+        let updates = MemoryUpdates {
+            content: updates.content.map(|content| content.trim().to_string()),
+            ..updates
+        };
+        if updates.content.as_deref() == Some("") {
+            return Err("Memory content cannot be empty".to_string());
+        }
+
+        if !self.memories.update_memory(memory_id, updates).await? {
+            return Err(format!("Memory '{}' not found", memory_id));
+        }
+        self.memories
+            .get_memory(memory_id)
+            .await?
+            .ok_or_else(|| format!("Memory '{}' not found", memory_id))
+    }
+
+    /// Delete a memory
+    pub async fn delete(&self, memory_id: &str) -> Result<(), String> {
+        if !self.memories.delete_memory(memory_id).await? {
+            return Err(format!("Memory '{}' not found", memory_id));
+        }
+        Ok(())
+    }
+
+    /// System prompt section with the memories of a session's project that
+    /// are most relevant to `query`; `None` when there are none
+    pub async fn system_prompt(
+        &self,
+        session_id: &str,
+        query: &str,
+    ) -> Result<Option<String>, String> {
+        let project_id = self.project_for_session(session_id).await?;
+        let memories = self.list(project_id.as_deref()).await?;
+        Ok(render_memories(&select_relevant(
+            memories,
+            query,
+            MAX_RELEVANT_MEMORIES,
+        )))
+    }
+
+    /// Register the `read_memories` and `write_memory` tools
+    pub async fn register_tools(&self, registry: &ToolRegistry) -> Result<(), String> {
+        let manager = self.clone();
+        let read: ToolHandler = Arc::new(move |request, context| {
+            let manager = manager.clone();
+            Box::pin(async move { to_output(manager.read_tool(&request, &context).await) })
+        });
+        let manager = self.clone();
+        let write: ToolHandler = Arc::new(move |request, context| {
+            let manager = manager.clone();
+            Box::pin(async move { to_output(manager.write_tool(&request, &context).await) })
+        });
+
+        registry.register(read_memories_definition(), read).await?;
+        registry.register(write_memory_definition(), write).await
+    }
+
+    async fn project_for_session(&self, session_id: &str) -> Result<Option<String>, String> {
+        Ok(self
+            .chat_history
+            .get_session(session_id)
+            .await?
+            .and_then(|session| session.project_id))
+    }
+
+    async fn read_tool(
+        &self,
+        request: &ToolRequest,
+        context: &ToolContext,
+    ) -> Result<serde_json::Value, String> {
+        let project_id = self.project_for_session(&context.session_id).await?;
+        let mut memories = self.list(project_id.as_deref()).await?;
+
+        if let Some(query) = request.input.get("query").and_then(|v| v.as_str()) {
+            let words = keywords(query);
+            if !words.is_empty() {
+                memories.retain(|memory| overlap(memory, &words) > 0);
+                memories = select_relevant(memories, query, MAX_RELEVANT_MEMORIES);
+            }
+        }
+
+        Ok(serde_json::json!({ "memories": memories }))
+    }
+
+    async fn write_tool(
+        &self,
+        request: &ToolRequest,
+        context: &ToolContext,
+    ) -> Result<serde_json::Value, String> {
+        let input = &request.input;
+        let content = input
+            .get("content")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'content'".to_string())?;
+        let kind = input
+            .get("kind")
+            .and_then(|v| v.as_str())
+            .map(str::parse::<MemoryKind>)
+            .transpose()?;
+        let project_id = self.project_for_session(&context.session_id).await?;
+
+        if let Some(memory_id) = input.get("id").and_then(|v| v.as_str()) {
+            // Only memories visible to this session can be changed
+            let visible = self
+                .memories
+                .get_memory(memory_id)
+                .await?
+                .is_some_and(|memory| {
+                    memory.project_id.is_none() || memory.project_id == project_id
+                });
+            if !visible {
+                return Err(format!("Memory '{}' not found", memory_id));
+            }
+
+            let updates = MemoryUpdates {
+                kind,
+                content: Some(content.to_string()),
+            };
+            let memory = self.update(memory_id, updates).await?;
+            return Ok(serde_json::json!({ "memory": memory }));
+        }
+
+        let project_id = match input.get("scope").and_then(|v| v.as_str()) {
+            Some("global") => None,
+            Some("project") => Some(
+                project_id
+                    .ok_or_else(|| "This session has no project; use scope 'global'".to_string())?,
+            ),
+            Some(scope) => return Err(format!("Unknown memory scope: {}", scope)),
+            None => project_id,
+        };
+        let memory = self
+            .create(project_id, kind.unwrap_or(MemoryKind::Fact), content)
+            .await?;
+
+        Ok(serde_json::json!({ "memory": memory }))
+    }
+}
+
+/// Rank memories for `query` and keep the first `limit`: preferences always
+/// come first, then facts sharing the most words with the query, newest first
+/// within a tie
+pub fn select_relevant(memories: Vec<Memory>, query: &str, limit: usize) -> Vec<Memory> {
+    let words = keywords(query);
+    let mut scored: Vec<(usize, Memory)> = memories
+        .into_iter()
+        .map(|memory| (overlap(&memory, &words), memory))
+        .collect();
+    scored.sort_by(|(a_score, a), (b_score, b)| {
+        (b.kind == MemoryKind::Preference)
+            .cmp(&(a.kind == MemoryKind::Preference))
+            .then(b_score.cmp(a_score))
+            .then(b.updated_at.cmp(&a.updated_at))
+    });
+
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, memory)| memory)
+        .collect()
+}
+
+/// System prompt section listing `memories`; `None` when there are none
+pub fn render_memories(memories: &[Memory]) -> Option<String> {
+    if memories.is_empty() {
+        return None;
+    }
+
+    let mut prompt = "Memories from earlier sessions. Follow the preferences, keep the facts \
+                      in mind and update them with write_memory when they change:"
+        .to_string();
+    for memory in memories {
+        prompt.push_str(&format!(
+            "\n- [{}] {} (id: {})",
+            memory.kind.as_str(),
+            memory.content,
+            memory.id
+        ));
+    }
+
+    Some(prompt)
+}
+
+/// Lowercase words of at least three characters
+fn keywords(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn overlap(memory: &Memory, words: &HashSet<String>) -> usize {
+    keywords(&memory.content).intersection(words).count()
+}
+
+fn to_output(result: Result<serde_json::Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn read_memories_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "read_memories".to_string(),
+        description: "Read long-term memories about this project and the user".to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Only return memories sharing words with this text"
+                }
+            }
+        }),
+        requires_approval: false,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::FilesystemRead],
+    }
+}
+
+fn write_memory_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: "write_memory".to_string(),
+        description:
+            "Remember a fact or preference for future sessions, or update an existing memory"
+                .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "content": {
+                    "type": "string",
+                    "description": "What to remember"
+                },
+                "kind": {
+                    "type": "string",
+                    "enum": ["fact", "preference"],
+                    "description": "Defaults to fact"
+                },
+                "scope": {
+                    "type": "string",
+                    "enum": ["project", "global"],
+                    "description": "Defaults to the session's project, or global without one"
+                },
+                "id": {
+                    "type": "string",
+                    "description": "ID of a memory to update instead of creating one"
+                }
+            },
+            "required": ["content"]
+        }),
+        requires_approval: false,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::FilesystemWrite],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::cancellation::CancellationToken;
+    use crate::storage::{Session, SessionStatus, Storage, TaskSettings};
+    use tempfile::TempDir;
+
+    fn memory(id: &str, kind: MemoryKind, content: &str, updated_at: i64) -> Memory {
+        Memory {
+            id: id.to_string(),
+            project_id: None,
+            kind,
+            content: content.to_string(),
+            created_at: 0,
+            updated_at,
+        }
+    }
+
+    #[test]
+    fn test_select_relevant_ranks_preferences_then_overlap() {
+        let memories = vec![
+            memory("old", MemoryKind::Fact, "Tests run with cargo test", 1),
+            memory("style", MemoryKind::Preference, "Prefers short commits", 2),
+            memory(
+                "db",
+                MemoryKind::Fact,
+                "Database migrations live in storage",
+                3,
+            ),
+            memory("new", MemoryKind::Fact, "Frontend uses pnpm", 4),
+        ];
+
+        let selected = select_relevant(memories, "Add a storage migration and run the tests", 3);
+        let ids: Vec<&str> = selected.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["style", "old", "db"]);
+
+        let prompt = render_memories(&selected).unwrap();
+        assert!(prompt.contains("\n- [preference] Prefers short commits (id: style)"));
+        assert!(render_memories(&[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_memory_tools_scope_to_session_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().to_path_buf(),
+            temp_dir.path().join("attachments"),
+        )
+        .await
+        .expect("Failed to create storage");
+        let now = chrono::Utc::now().timestamp();
+        storage
+            .chat_history
+            .create_session(&Session {
+                id: "session-1".to_string(),
+                project_id: Some("project-a".to_string()),
+                title: None,
+                summary: None,
+                status: SessionStatus::Running,
+                created_at: now,
+                updated_at: now,
+                last_event_id: None,
+                metadata: None,
+                starred: false,
+                archived_at: None,
+                user_id: None,
+            })
+            .await
+            .unwrap();
+
+        let manager = MemoryManager::new(storage.memories.clone(), storage.chat_history.clone());
+        let registry = ToolRegistry::new();
+        manager.register_tools(&registry).await.unwrap();
+        manager
+            .create(Some("project-b".to_string()), MemoryKind::Fact, "Uses yarn")
+            .await
+            .unwrap();
+
+        let context = ToolContext {
+            session_id: "session-1".to_string(),
+            task_id: "task-1".to_string(),
+            workspace_root: temp_dir.path().to_string_lossy().to_string(),
+            worktree_path: None,
+            settings: TaskSettings::default(),
+            cancel_token: CancellationToken::new(),
+        };
+        let write = registry
+            .execute(
+                ToolRequest {
+                    tool_call_id: "call-1".to_string(),
+                    name: "write_memory".to_string(),
+                    input: serde_json::json!({ "content": " Uses pnpm ", "kind": "preference" }),
+                },
+                context.clone(),
+            )
+            .await;
+        assert!(write.success, "{:?}", write.error);
+        assert_eq!(write.output["memory"]["projectId"], "project-a");
+        assert_eq!(write.output["memory"]["content"], "Uses pnpm");
+
+        let read = registry
+            .execute(
+                ToolRequest {
+                    tool_call_id: "call-2".to_string(),
+                    name: "read_memories".to_string(),
+                    input: serde_json::json!({}),
+                },
+                context.clone(),
+            )
+            .await;
+        let memories = read.output["memories"].as_array().unwrap();
+        assert_eq!(memories.len(), 1);
+
+        // Memories of other projects cannot be updated from this session
+        let other = manager.list(Some("project-b")).await.unwrap();
+        let update = registry
+            .execute(
+                ToolRequest {
+                    tool_call_id: "call-3".to_string(),
+                    name: "write_memory".to_string(),
+                    input: serde_json::json!({ "id": other[0].id, "content": "Uses npm" }),
+                },
+                context,
+            )
+            .await;
+        assert!(!update.success);
+
+        let prompt = manager
+            .system_prompt("session-1", "install dependencies")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(prompt.contains("Uses pnpm"));
+        assert!(!prompt.contains("Uses yarn"));
+    }
+
+    #[test]
+    fn test_write_memory_needs_write_access() {
+        let read_only = TaskSettings {
+            tool_capabilities: Some(vec![ToolCapability::FilesystemRead]),
+            ..TaskSettings::default()
+        };
+        assert!(read_memories_definition().is_permitted(&read_only));
+        assert!(!write_memory_definition().is_permitted(&read_only));
+        assert!(write_memory_definition().is_permitted(&TaskSettings {
+            tool_capabilities: Some(vec![ToolCapability::FilesystemWrite]),
+            ..TaskSettings::default()
+        }));
+    }
+}
@@ -0,0 +1,382 @@
+//! Runtime Metrics
+//!
+//! Counts and timings of each task's agent runs: iterations, LLM calls and
+//! their latency, tool calls and their durations, and errors. Kept in memory
+//! for debugging stuck or expensive sessions; the stats of the least recently
+//! active tasks are dropped once too many are tracked. The latest errors of
+//! all tasks are also kept in order for diagnostics.
+
+use crate::core::types::{RuntimeTaskId, RuntimeTaskState};
+use crate::storage::SessionId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Most tasks whose stats are kept
+const MAX_TRACKED_TASKS: usize = 500;
+
+/// Most recent errors kept
+const MAX_RECENT_ERRORS: usize = 100;
+
+/// Count, total and longest of a kind of timed operation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DurationStats {
+    pub count: u64,
+    pub total_ms: u64,
+    pub max_ms: u64,
+    pub last_ms: u64,
+}
+
+impl DurationStats {
+    fn record(&mut self, duration: Duration) {
+        let ms = duration.as_millis() as u64;
+        self.count += 1;
+        self.total_ms += ms;
+        self.max_ms = self.max_ms.max(ms);
+        self.last_ms = ms;
+    }
+
+    /// Average duration, or 0 when nothing was recorded
+    pub fn average_ms(&self) -> u64 {
+        self.total_ms.checked_div(self.count).unwrap_or(0)
+    }
+}
+
+/// Calls of one tool within a task
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolStats {
+    pub errors: u64,
+    pub duration: DurationStats,
+}
+
+/// Stats of one task, across every run it resumed for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskStats {
+    pub task_id: RuntimeTaskId,
+    pub session_id: SessionId,
+    pub iterations: u64,
+    /// Time from sending a request until the stream finished
+    pub llm_latency: DurationStats,
+    /// Time from sending a request until its first token or tool call
+    pub llm_first_token: DurationStats,
+    pub llm_errors: u64,
+    pub tool_calls: u64,
+    pub tool_errors: u64,
+    /// Tool calls by tool name
+    pub tools: HashMap<String, ToolStats>,
+    /// LLM, tool and task errors
+    pub errors: u64,
+    pub last_error: Option<String>,
+    pub started_at: i64,
+    pub last_activity_at: i64,
+    /// Terminal state, once the task finished
+    pub final_state: Option<RuntimeTaskState>,
+    pub finished_at: Option<i64>,
+}
+
+impl TaskStats {
+    fn new(task_id: &str, session_id: &str) -> Self {
+        let now = chrono::Utc::now().timestamp();
+        Self {
+            task_id: task_id.to_string(),
+            session_id: session_id.to_string(),
+            iterations: 0,
+            llm_latency: DurationStats::default(),
+            llm_first_token: DurationStats::default(),
+            llm_errors: 0,
+            tool_calls: 0,
+            tool_errors: 0,
+            tools: HashMap::new(),
+            errors: 0,
+            last_error: None,
+            started_at: now,
+            last_activity_at: now,
+            final_state: None,
+            finished_at: None,
+        }
+    }
+
+    fn record_error(&mut self, message: Option<&str>) {
+        self.errors += 1;
+        if let Some(message) = message {
+            self.last_error = Some(message.to_string());
+        }
+    }
+}
+
+/// Snapshot of the runtime returned by `get_runtime_stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeStats {
+    /// Tasks running or waiting for the user
+    pub active_tasks: usize,
+    /// Tasks waiting for a run slot
+    pub queued_tasks: usize,
+    pub max_concurrent_tasks: usize,
+    /// Tracked tasks, most recently active first
+    pub tasks: Vec<TaskStats>,
+}
+
+/// What failed in a recent error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorSource {
+    Llm,
+    Tool,
+    Task,
+}
+
+/// An error of a task, kept among the latest errors of the runtime
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentError {
+    pub task_id: RuntimeTaskId,
+    pub session_id: SessionId,
+    pub source: ErrorSource,
+    pub message: String,
+    pub occurred_at: i64,
+}
+
+/// Shared store of task stats
+#[derive(Clone, Default)]
+pub struct RuntimeMetrics {
+    tasks: Arc<Mutex<HashMap<RuntimeTaskId, TaskStats>>>,
+    errors: Arc<Mutex<VecDeque<RecentError>>>,
+}
+
+impl RuntimeMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count an LLM round trip of the agent loop
+    pub fn record_iteration(&self, task_id: &str, session_id: &str) {
+        self.update(task_id, session_id, |stats| stats.iterations += 1);
+    }
+
+    /// Record a finished LLM stream; `error` is the message of a failed one
+    pub fn record_llm_call(
+        &self,
+        task_id: &str,
+        session_id: &str,
+        latency: Duration,
+        first_token: Option<Duration>,
+        error: Option<&str>,
+    ) {
+        self.update(task_id, session_id, |stats| {
+            stats.llm_latency.record(latency);
+            if let Some(first_token) = first_token {
+                stats.llm_first_token.record(first_token);
+            }
+            if error.is_some() {
+                stats.llm_errors += 1;
+                stats.record_error(error);
+            }
+        });
+        if let Some(message) = error {
+            self.record_recent_error(task_id, session_id, ErrorSource::Llm, message);
+        }
+    }
+
+    /// Record an executed tool call; `error` is the message of a failed one
+    pub fn record_tool_call(
+        &self,
+        task_id: &str,
+        session_id: &str,
+        tool_name: &str,
+        duration: Duration,
+        error: Option<&str>,
+    ) {
+        self.update(task_id, session_id, |stats| {
+            stats.tool_calls += 1;
+            let tool = stats.tools.entry(tool_name.to_string()).or_default();
+            tool.duration.record(duration);
+            if let Some(message) = error {
+                tool.errors += 1;
+                stats.tool_errors += 1;
+                stats.record_error(Some(&format!("{}: {}", tool_name, message)));
+            }
+        });
+        if let Some(message) = error {
+            let message = format!("{}: {}", tool_name, message);
+            self.record_recent_error(task_id, session_id, ErrorSource::Tool, &message);
+        }
+    }
+
+    /// Record a task reaching a terminal state, with the error it failed with
+    pub fn record_finished(
+        &self,
+        task_id: &str,
+        session_id: &str,
+        state: RuntimeTaskState,
+        error: Option<&str>,
+    ) {
+        self.update(task_id, session_id, |stats| {
+            stats.final_state = Some(state);
+            stats.finished_at = Some(stats.last_activity_at);
+            if state == RuntimeTaskState::Failed {
+                stats.record_error(error);
+            }
+        });
+        if let (RuntimeTaskState::Failed, Some(message)) = (state, error) {
+            self.record_recent_error(task_id, session_id, ErrorSource::Task, message);
+        }
+    }
+
+    /// Latest errors of all tasks, newest first
+    pub fn recent_errors(&self, limit: usize) -> Vec<RecentError> {
+        self.lock_errors()
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    fn record_recent_error(
+        &self,
+        task_id: &str,
+        session_id: &str,
+        source: ErrorSource,
+        message: &str,
+    ) {
+        let mut errors = self.lock_errors();
+        if errors.len() >= MAX_RECENT_ERRORS {
+            errors.pop_front();
+        }
+        errors.push_back(RecentError {
+            task_id: task_id.to_string(),
+            session_id: session_id.to_string(),
+            source,
+            message: message.to_string(),
+            occurred_at: chrono::Utc::now().timestamp(),
+        });
+    }
+
+    /// Stats of one task
+    pub fn task(&self, task_id: &str) -> Option<TaskStats> {
+        self.lock().get(task_id).cloned()
+    }
+
+    /// Stats of every tracked task, optionally of one session, most recently
+    /// active first
+    pub fn tasks(&self, session_id: Option<&str>) -> Vec<TaskStats> {
+        let mut tasks: Vec<TaskStats> = self
+            .lock()
+            .values()
+            .filter(|stats| session_id.is_none_or(|id| stats.session_id == id))
+            .cloned()
+            .collect();
+        tasks.sort_by(|a, b| {
+            b.last_activity_at
+                .cmp(&a.last_activity_at)
+                .then_with(|| a.task_id.cmp(&b.task_id))
+        });
+        tasks
+    }
+
+    fn update(&self, task_id: &str, session_id: &str, apply: impl FnOnce(&mut TaskStats)) {
+        let mut tasks = self.lock();
+        if !tasks.contains_key(task_id) && tasks.len() >= MAX_TRACKED_TASKS {
+            let oldest = tasks
+                .values()
+                .min_by_key(|stats| stats.last_activity_at)
+                .map(|stats| stats.task_id.clone());
+            if let Some(oldest) = oldest {
+                tasks.remove(&oldest);
+            }
+        }
+
+        let stats = tasks
+            .entry(task_id.to_string())
+            .or_insert_with(|| TaskStats::new(task_id, session_id));
+        stats.last_activity_at = chrono::Utc::now().timestamp();
+        apply(stats);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<RuntimeTaskId, TaskStats>> {
+        // Stats are plain counters a panicking holder cannot leave inconsistent
+        self.tasks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_errors(&self) -> std::sync::MutexGuard<'_, VecDeque<RecentError>> {
+        self.errors.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_task_stats() {
+        let metrics = RuntimeMetrics::new();
+        metrics.record_iteration("task-1", "session-1");
+        metrics.record_llm_call(
+            "task-1",
+            "session-1",
+            Duration::from_millis(300),
+            Some(Duration::from_millis(100)),
+            None,
+        );
+        metrics.record_llm_call(
+            "task-1",
+            "session-1",
+            Duration::from_millis(100),
+            None,
+            Some("rate limited"),
+        );
+        metrics.record_tool_call(
+            "task-1",
+            "session-1",
+            "read_file",
+            Duration::from_millis(20),
+            None,
+        );
+        metrics.record_tool_call(
+            "task-1",
+            "session-1",
+            "read_file",
+            Duration::from_millis(40),
+            Some("not found"),
+        );
+        metrics.record_finished("task-1", "session-1", RuntimeTaskState::Completed, None);
+        metrics.record_iteration("task-2", "session-2");
+
+        let stats = metrics.task("task-1").unwrap();
+        assert_eq!(stats.iterations, 1);
+        assert_eq!(stats.llm_latency.count, 2);
+        assert_eq!(stats.llm_latency.average_ms(), 200);
+        assert_eq!(stats.llm_latency.max_ms, 300);
+        assert_eq!(stats.llm_first_token.count, 1);
+        assert_eq!(stats.llm_errors, 1);
+        assert_eq!(stats.tool_calls, 2);
+        assert_eq!(stats.tools["read_file"].errors, 1);
+        assert_eq!(stats.tools["read_file"].duration.total_ms, 60);
+        assert_eq!(stats.errors, 2);
+        assert_eq!(stats.last_error.as_deref(), Some("read_file: not found"));
+        assert_eq!(stats.final_state, Some(RuntimeTaskState::Completed));
+
+        assert_eq!(metrics.tasks(None).len(), 2);
+        assert_eq!(metrics.tasks(Some("session-2"))[0].task_id, "task-2");
+
+        metrics.record_finished(
+            "task-2",
+            "session-2",
+            RuntimeTaskState::Failed,
+            Some("model unavailable"),
+        );
+        let errors = metrics.recent_errors(10);
+        let sources: Vec<ErrorSource> = errors.iter().map(|e| e.source).collect();
+        assert_eq!(
+            sources,
+            vec![ErrorSource::Task, ErrorSource::Tool, ErrorSource::Llm]
+        );
+        assert_eq!(errors[1].message, "read_file: not found");
+        assert_eq!(metrics.recent_errors(1)[0].task_id, "task-2");
+    }
+}
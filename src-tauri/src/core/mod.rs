@@ -4,22 +4,60 @@
 //! and tool execution. This module is the heart of the cloud backend.
 
 pub mod agent_loop;
+pub mod budget;
+pub mod cancellation;
+pub mod checkpoints;
+pub mod commands;
+pub mod compaction;
+pub mod database_query;
+pub mod diagram;
+pub mod edit;
+pub mod event_log;
+pub mod format;
+pub mod grep;
+pub mod hooks;
+pub mod http_request;
+pub mod llm;
+pub mod memory;
+pub mod metrics;
+pub mod notebook;
+pub mod outline;
+pub mod patch;
+pub mod plan;
+pub mod projects;
+pub mod retention;
 pub mod runtime;
+pub mod sandbox;
+pub mod scheduler;
+pub mod script_tools;
 pub mod session;
+pub mod session_summary;
+pub mod shell;
+pub mod shell_policy;
+pub mod stream_state;
+pub mod test_runner;
+pub mod todo;
 pub mod tools;
+pub mod truncation;
 pub mod types;
+pub mod webhooks;
+pub mod web_search;
+pub mod workspace_agents;
 
 // Re-export main types for convenience
 pub use agent_loop::{AgentLoop, AgentLoopContext, AgentLoopFactory, AgentLoopResult};
+pub use cancellation::CancellationToken;
+pub use llm::{LlmClient, ProviderLlmClient};
 pub use runtime::{CoreRuntime, SettingsValidator};
 pub use session::{SessionManager, SessionState};
 pub use tools::{ToolContext, ToolDispatcher, ToolExecutionOutput, ToolHandler, ToolRegistry};
 pub use types::*;
 
-/// Initialize the core runtime with storage
+/// Initialize the core runtime with storage and an LLM client
 pub async fn init_runtime(
     storage: crate::storage::Storage,
+    llm: std::sync::Arc<dyn LlmClient>,
     event_sender: types::EventSender,
 ) -> Result<CoreRuntime, String> {
-    CoreRuntime::new(storage, event_sender).await
+    CoreRuntime::new(storage, llm, event_sender).await
 }
@@ -0,0 +1,581 @@
+//! Notebook Tools
+//!
+//! `read_notebook` lists the cells of a Jupyter notebook and `edit_notebook`
+//! inserts, replaces or deletes a single cell. Notebooks are edited as JSON
+//! documents, so outputs, metadata and fields this module doesn't know about
+//! survive an edit, and they are written back in the layout Jupyter uses.
+
+use crate::core::tools::{ToolContext, ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::{ToolDefinition, ToolRequest};
+use crate::path_guard;
+use crate::storage::ToolCapability;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+
+pub const READ_NOTEBOOK_TOOL: &str = "read_notebook";
+pub const EDIT_NOTEBOOK_TOOL: &str = "edit_notebook";
+
+/// Most characters of a cell's outputs shown by `read_notebook`
+const MAX_OUTPUT_CHARS: usize = 2000;
+
+/// Kind of notebook cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CellType {
+    Code,
+    Markdown,
+    Raw,
+}
+
+impl CellType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CellType::Code => "code",
+            CellType::Markdown => "markdown",
+            CellType::Raw => "raw",
+        }
+    }
+}
+
+/// A change to one cell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotebookEdit {
+    /// Insert a cell before `index`; the cell count appends
+    Insert {
+        index: usize,
+        cell_type: CellType,
+        source: String,
+    },
+    /// Replace a cell's source, keeping its metadata and, unless cleared or
+    /// the cell stops being code, its outputs
+    Replace {
+        index: usize,
+        cell_type: Option<CellType>,
+        source: String,
+        clear_outputs: bool,
+    },
+    Delete {
+        index: usize,
+    },
+}
+
+/// A parsed nbformat 4 notebook
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notebook {
+    value: Value,
+}
+
+impl Notebook {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let value: Value =
+            serde_json::from_str(content).map_err(|e| format!("Invalid notebook JSON: {}", e))?;
+        let nbformat = value.get("nbformat").and_then(|v| v.as_u64());
+        if nbformat != Some(4) {
+            return Err(format!(
+                "Unsupported notebook format {}; only nbformat 4 is supported",
+                nbformat.map_or("(missing)".to_string(), |v| v.to_string())
+            ));
+        }
+        if !value.get("cells").is_some_and(|cells| cells.is_array()) {
+            return Err("Notebook has no cells array".to_string());
+        }
+        Ok(Self { value })
+    }
+
+    fn cells(&self) -> &Vec<Value> {
+        self.value["cells"].as_array().expect("checked in parse")
+    }
+
+    fn cells_mut(&mut self) -> &mut Vec<Value> {
+        self.value["cells"]
+            .as_array_mut()
+            .expect("checked in parse")
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.cells().len()
+    }
+
+    /// Kernel language, e.g. `python`
+    pub fn language(&self) -> Option<&str> {
+        let metadata = &self.value["metadata"];
+        metadata["language_info"]["name"]
+            .as_str()
+            .or(metadata["kernelspec"]["language"].as_str())
+    }
+
+    /// Position of the cell with a given ID
+    pub fn find_cell(&self, id: &str) -> Option<usize> {
+        self.cells()
+            .iter()
+            .position(|cell| cell["id"].as_str() == Some(id))
+    }
+
+    /// Cells as shown to the model
+    pub fn summary(&self, include_outputs: bool) -> Vec<Value> {
+        self.cells()
+            .iter()
+            .enumerate()
+            .map(|(index, cell)| {
+                let mut summary = json!({
+                    "index": index,
+                    "cellType": cell["cell_type"],
+                    "source": multiline(&cell["source"]),
+                });
+                if let Some(id) = cell["id"].as_str() {
+                    summary["id"] = json!(id);
+                }
+                if cell["cell_type"] == "code" {
+                    summary["executionCount"] = cell["execution_count"].clone();
+                    if include_outputs {
+                        summary["outputs"] = json!(outputs_text(&cell["outputs"]));
+                    }
+                }
+                summary
+            })
+            .collect()
+    }
+
+    pub fn apply(&mut self, edit: NotebookEdit) -> Result<(), String> {
+        let count = self.cell_count();
+        let check = |index: usize| {
+            if index < count {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Cell {} does not exist; the notebook has {} cells",
+                    index, count
+                ))
+            }
+        };
+
+        match edit {
+            NotebookEdit::Insert {
+                index,
+                cell_type,
+                source,
+            } => {
+                if index > count {
+                    return Err(format!(
+                        "Cannot insert at {}; the notebook has {} cells",
+                        index, count
+                    ));
+                }
+                let cell = self.new_cell(cell_type, &source);
+                self.cells_mut().insert(index, cell);
+            }
+            NotebookEdit::Replace {
+                index,
+                cell_type,
+                source,
+                clear_outputs,
+            } => {
+                check(index)?;
+                let cell = &mut self.cells_mut()[index];
+                if let Some(cell_type) = cell_type {
+                    set_cell_type(cell, cell_type);
+                }
+                cell["source"] = source_lines(&source);
+                if clear_outputs && cell["cell_type"] == "code" {
+                    cell["outputs"] = json!([]);
+                    cell["execution_count"] = Value::Null;
+                }
+            }
+            NotebookEdit::Delete { index } => {
+                check(index)?;
+                self.cells_mut().remove(index);
+            }
+        }
+        Ok(())
+    }
+
+    /// The notebook laid out as Jupyter writes it, with one-space indents
+    /// and a trailing newline
+    pub fn serialize(&self) -> Result<String, String> {
+        let mut buffer = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b" ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+        self.value
+            .serialize(&mut serializer)
+            .map_err(|e| format!("Failed to serialize notebook: {}", e))?;
+        let mut content = String::from_utf8(buffer).map_err(|e| e.to_string())?;
+        content.push('\n');
+        Ok(content)
+    }
+
+    fn new_cell(&self, cell_type: CellType, source: &str) -> Value {
+        let mut cell = json!({
+            "cell_type": cell_type.as_str(),
+            "metadata": {},
+            "source": source_lines(source),
+        });
+        if cell_type == CellType::Code {
+            cell["outputs"] = json!([]);
+            cell["execution_count"] = Value::Null;
+        }
+        // Cell IDs are required from nbformat 4.5
+        if self.value["nbformat_minor"].as_u64().unwrap_or(0) >= 5 {
+            let id = uuid::Uuid::new_v4().simple().to_string();
+            cell["id"] = json!(id[..8]);
+        }
+        cell
+    }
+}
+
+/// Change a cell's type, adding or dropping the fields only code cells have
+fn set_cell_type(cell: &mut Value, cell_type: CellType) {
+    if cell["cell_type"] == cell_type.as_str() {
+        return;
+    }
+    cell["cell_type"] = json!(cell_type.as_str());
+    if let Some(cell) = cell.as_object_mut() {
+        if cell_type == CellType::Code {
+            cell.insert("outputs".to_string(), json!([]));
+            cell.insert("execution_count".to_string(), Value::Null);
+        } else {
+            cell.remove("outputs");
+            cell.remove("execution_count");
+        }
+    }
+}
+
+/// Text of a multiline field, stored as a string or a list of lines
+fn multiline(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(|line| line.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Source in Jupyter's list-of-lines form
+fn source_lines(source: &str) -> Value {
+    json!(source.split_inclusive('\n').collect::<Vec<_>>())
+}
+
+/// Readable text of a code cell's outputs, truncated
+fn outputs_text(outputs: &Value) -> Vec<String> {
+    let mut remaining = MAX_OUTPUT_CHARS;
+    let mut texts = vec![];
+    for output in outputs.as_array().into_iter().flatten() {
+        if remaining == 0 {
+            texts.push("[more outputs truncated]".to_string());
+            break;
+        }
+        let text = match output["output_type"].as_str() {
+            Some("stream") => multiline(&output["text"]),
+            Some("error") => format!(
+                "{}: {}",
+                output["ename"].as_str().unwrap_or_default(),
+                output["evalue"].as_str().unwrap_or_default()
+            ),
+            _ => match output["data"].get("text/plain") {
+                Some(text) => multiline(text),
+                // Images, HTML and other rich outputs are only named
+                None => output["data"]
+                    .as_object()
+                    .map(|data| {
+                        format!("[{}]", data.keys().cloned().collect::<Vec<_>>().join(", "))
+                    })
+                    .unwrap_or_default(),
+            },
+        };
+        let length = text.chars().count();
+        if length > remaining {
+            let truncated: String = text.chars().take(remaining).collect();
+            texts.push(format!("{}\n[output truncated]", truncated));
+            remaining = 0;
+        } else {
+            texts.push(text);
+            remaining -= length;
+        }
+    }
+    texts
+}
+
+/// Register `read_notebook` and `edit_notebook`
+pub async fn register_tools(registry: &ToolRegistry) -> Result<(), String> {
+    let read: ToolHandler = Arc::new(|request, context| {
+        Box::pin(async move { to_output(read_tool(&request, &context)) })
+    });
+    let edit: ToolHandler = Arc::new(|request, context| {
+        Box::pin(async move { to_output(edit_tool(&request, &context)) })
+    });
+
+    registry.register(read_notebook_definition(), read).await?;
+    registry.register(edit_notebook_definition(), edit).await
+}
+
+fn load(request: &ToolRequest, context: &ToolContext) -> Result<(String, Notebook), String> {
+    let path = request
+        .input
+        .get("path")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "Missing 'path'".to_string())?;
+    let root = context
+        .worktree_path
+        .as_deref()
+        .unwrap_or(&context.workspace_root);
+    let full_path = path_guard::resolve_relative(Path::new(root), path)?;
+    let content = std::fs::read_to_string(&full_path)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    Ok((
+        full_path.to_string_lossy().to_string(),
+        Notebook::parse(&content)?,
+    ))
+}
+
+fn read_tool(request: &ToolRequest, context: &ToolContext) -> Result<Value, String> {
+    let (_, notebook) = load(request, context)?;
+    let include_outputs = request
+        .input
+        .get("includeOutputs")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+
+    Ok(json!({
+        "language": notebook.language(),
+        "cellCount": notebook.cell_count(),
+        "cells": notebook.summary(include_outputs),
+    }))
+}
+
+fn edit_tool(request: &ToolRequest, context: &ToolContext) -> Result<Value, String> {
+    let (full_path, mut notebook) = load(request, context)?;
+    let input = &request.input;
+    let cell_type = match input.get("cellType") {
+        Some(value) => Some(
+            serde_json::from_value::<CellType>(value.clone())
+                .map_err(|_| format!("Invalid cellType: {}", value))?,
+        ),
+        None => None,
+    };
+    let source = input
+        .get("source")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    // Cells are addressed by position or, more robustly, by ID
+    let index = match input.get("cellId").and_then(|v| v.as_str()) {
+        Some(id) => Some(
+            notebook
+                .find_cell(id)
+                .ok_or_else(|| format!("No cell has ID '{}'", id))?,
+        ),
+        None => input
+            .get("index")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize),
+    };
+    let require_index = || index.ok_or_else(|| "Missing 'index' or 'cellId'".to_string());
+    let require_source = || source.clone().ok_or_else(|| "Missing 'source'".to_string());
+
+    let operation = input
+        .get("operation")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "Missing 'operation'".to_string())?;
+    let edit = match operation {
+        "insert" => NotebookEdit::Insert {
+            index: index.unwrap_or(notebook.cell_count()),
+            cell_type: cell_type.unwrap_or(CellType::Code),
+            source: require_source()?,
+        },
+        "replace" => NotebookEdit::Replace {
+            index: require_index()?,
+            cell_type,
+            source: require_source()?,
+            clear_outputs: input
+                .get("clearOutputs")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        },
+        "delete" => NotebookEdit::Delete {
+            index: require_index()?,
+        },
+        other => return Err(format!("Unknown operation: {}", other)),
+    };
+    notebook.apply(edit)?;
+
+    std::fs::write(&full_path, notebook.serialize()?)
+        .map_err(|e| format!("Failed to write {}: {}", full_path, e))?;
+    Ok(json!({
+        "operation": operation,
+        "cellCount": notebook.cell_count(),
+    }))
+}
+
+fn to_output(result: Result<Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn read_notebook_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: READ_NOTEBOOK_TOOL.to_string(),
+        description: "Read a Jupyter notebook (.ipynb) as a list of cells with their index, ID, \
+                      type, source and outputs"
+            .to_string(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Notebook path relative to the workspace"
+                },
+                "includeOutputs": {
+                    "type": "boolean",
+                    "description": "Include the text of code cell outputs (default true)"
+                }
+            },
+            "required": ["path"]
+        }),
+        requires_approval: false,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::FilesystemRead],
+    }
+}
+
+fn edit_notebook_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: EDIT_NOTEBOOK_TOOL.to_string(),
+        description: "Insert, replace or delete one cell of a Jupyter notebook (.ipynb). Use \
+                      this instead of editing notebook JSON directly; outputs and metadata of \
+                      other cells are kept."
+            .to_string(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Notebook path relative to the workspace"
+                },
+                "operation": {
+                    "type": "string",
+                    "enum": ["insert", "replace", "delete"]
+                },
+                "index": {
+                    "type": "integer",
+                    "description": "Cell position; for insert, the new cell's position (default: append)"
+                },
+                "cellId": {
+                    "type": "string",
+                    "description": "Cell ID, instead of index"
+                },
+                "cellType": {
+                    "type": "string",
+                    "enum": ["code", "markdown", "raw"],
+                    "description": "Type of the new cell (default code), or the type to change a replaced cell to"
+                },
+                "source": {
+                    "type": "string",
+                    "description": "Cell source, for insert and replace"
+                },
+                "clearOutputs": {
+                    "type": "boolean",
+                    "description": "Clear the outputs of a replaced code cell"
+                }
+            },
+            "required": ["path", "operation"]
+        }),
+        requires_approval: true,
+        modifies_files: true,
+        capabilities: vec![ToolCapability::FilesystemWrite],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {
+   "cell_type": "code",
+   "execution_count": 3,
+   "id": "a1",
+   "metadata": {"tags": ["setup"]},
+   "outputs": [
+    {"name": "stdout", "output_type": "stream", "text": ["hello\n"]},
+    {"data": {"image/png": "iVBOR"}, "metadata": {}, "output_type": "display_data"}
+   ],
+   "source": ["print('hello')\n", "x = 1"]
+  },
+  {"cell_type": "markdown", "id": "b2", "metadata": {}, "source": "# Title"}
+ ],
+ "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+
+    #[test]
+    fn test_read_notebook() {
+        let notebook = Notebook::parse(NOTEBOOK).unwrap();
+        assert_eq!(notebook.language(), Some("python"));
+        assert_eq!(notebook.find_cell("b2"), Some(1));
+
+        let cells = notebook.summary(true);
+        assert_eq!(cells[0]["source"], "print('hello')\nx = 1");
+        assert_eq!(cells[0]["executionCount"], 3);
+        assert_eq!(cells[0]["outputs"], json!(["hello\n", "[image/png]"]));
+        assert_eq!(cells[1]["source"], "# Title");
+        assert!(cells[1].get("outputs").is_none());
+
+        assert!(Notebook::parse(r#"{"nbformat": 3, "worksheets": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_edit_cells_keeps_outputs_and_metadata() {
+        let mut notebook = Notebook::parse(NOTEBOOK).unwrap();
+        notebook
+            .apply(NotebookEdit::Replace {
+                index: 0,
+                cell_type: None,
+                source: "print('bye')\n".to_string(),
+                clear_outputs: false,
+            })
+            .unwrap();
+        notebook
+            .apply(NotebookEdit::Insert {
+                index: 1,
+                cell_type: CellType::Markdown,
+                source: "Some\nnotes".to_string(),
+            })
+            .unwrap();
+        assert!(notebook.apply(NotebookEdit::Delete { index: 3 }).is_err());
+        notebook.apply(NotebookEdit::Delete { index: 2 }).unwrap();
+
+        let content = notebook.serialize().unwrap();
+        assert!(content.starts_with("{\n \"cells\": [\n  {\n"));
+        let reparsed = Notebook::parse(&content).unwrap();
+        let cells = reparsed.cells();
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[0]["source"], json!(["print('bye')\n"]));
+        assert_eq!(cells[0]["outputs"].as_array().unwrap().len(), 2);
+        assert_eq!(cells[0]["metadata"]["tags"], json!(["setup"]));
+        assert_eq!(cells[1]["source"], json!(["Some\n", "notes"]));
+        assert_eq!(cells[1]["id"].as_str().unwrap().len(), 8);
+        assert!(cells[1].get("outputs").is_none());
+
+        notebook
+            .apply(NotebookEdit::Replace {
+                index: 0,
+                cell_type: Some(CellType::Markdown),
+                source: "text".to_string(),
+                clear_outputs: false,
+            })
+            .unwrap();
+        assert!(notebook.cells()[0].get("outputs").is_none());
+        assert!(notebook.cells()[0].get("execution_count").is_none());
+    }
+}
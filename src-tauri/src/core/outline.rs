@@ -0,0 +1,279 @@
+//! Code Outline Tool
+//!
+//! The `code_outline` tool condenses a file or directory into its imports and
+//! definition signatures using the tree-sitter outlines, so the agent can
+//! survey large files without reading them in full. Each signature carries
+//! its line range for a follow-up `read_file`.
+
+use crate::code_navigation::CodeNavigationService;
+use crate::core::tools::{ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::ToolDefinition;
+use crate::path_guard;
+use crate::storage::ToolCapability;
+use crate::symbol_outline::{extract_imports, extract_outline, OutlineSymbol};
+use crate::text_file::{read_text_file, skip_for_search, TextContent};
+use crate::walker::{WalkerConfig, WorkspaceWalker};
+use serde::Deserialize;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const CODE_OUTLINE_TOOL: &str = "code_outline";
+
+const DEFAULT_MAX_FILES: usize = 50;
+const MAX_FILES: usize = 200;
+/// Files larger than this are not outlined
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+/// Lines of a definition searched for the end of its signature
+const MAX_SIGNATURE_LINES: usize = 4;
+const MAX_SIGNATURE_CHARS: usize = 200;
+
+/// Input of the `code_outline` tool
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OutlineInput {
+    /// File or directory, relative to the workspace root
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    max_files: Option<usize>,
+}
+
+/// Register the `code_outline` tool
+pub async fn register_tool(registry: &ToolRegistry) -> Result<(), String> {
+    let handler: ToolHandler = Arc::new(|request, context| {
+        Box::pin(async move {
+            let root = context
+                .worktree_path
+                .clone()
+                .unwrap_or_else(|| context.workspace_root.clone());
+            let result =
+                tokio::task::spawn_blocking(move || outline(Path::new(&root), request.input))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Outline task failed: {}", e)));
+            to_output(result)
+        })
+    });
+
+    registry.register(code_outline_definition(), handler).await
+}
+
+/// Outline the file or directory named in a tool call's input
+fn outline(root: &Path, input: serde_json::Value) -> Result<serde_json::Value, String> {
+    let input: OutlineInput =
+        serde_json::from_value(input).map_err(|e| format!("Invalid input: {}", e))?;
+    let path = input.path.as_deref().map(str::trim).unwrap_or_default();
+    let target = match path {
+        "" | "." => root.to_path_buf(),
+        path => path_guard::resolve_relative(root, path)?,
+    };
+
+    if target.is_file() {
+        let lang_id =
+            lang_id(&target).ok_or_else(|| format!("Outlines are not supported for '{}'", path))?;
+        let file = outline_file(root, &target, &lang_id)?;
+        return Ok(serde_json::json!({ "files": [file], "truncated": false }));
+    }
+    if !target.is_dir() {
+        return Err(format!("'{}' does not exist", path));
+    }
+
+    let max_files = input
+        .max_files
+        .unwrap_or(DEFAULT_MAX_FILES)
+        .clamp(1, MAX_FILES);
+    let mut files: Vec<(PathBuf, String)> = WorkspaceWalker::new(
+        &target.to_string_lossy(),
+        WalkerConfig::for_content_search(),
+    )
+    .build()
+    .flatten()
+    .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+    .filter_map(|entry| {
+        let path = entry.into_path();
+        let lang_id = lang_id(&path)?;
+        let size = path.metadata().ok()?.len();
+        skip_for_search(&path, size, MAX_FILE_SIZE)
+            .is_none()
+            .then_some((path, lang_id))
+    })
+    .collect();
+    files.sort();
+    let truncated = files.len() > max_files;
+    files.truncate(max_files);
+
+    // Unreadable files and files without definitions are left out of a directory outline
+    let outlines: Vec<serde_json::Value> = files
+        .iter()
+        .filter_map(|(path, lang_id)| outline_file(root, path, lang_id).ok())
+        .filter(|file| file["outline"].as_str().is_some_and(|o| !o.is_empty()))
+        .collect();
+
+    Ok(serde_json::json!({ "files": outlines, "truncated": truncated }))
+}
+
+fn lang_id(path: &Path) -> Option<String> {
+    CodeNavigationService::get_lang_id_from_path(&path.to_string_lossy())
+}
+
+fn outline_file(root: &Path, path: &Path, lang_id: &str) -> Result<serde_json::Value, String> {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    let content = match read_text_file(path, MAX_FILE_SIZE)
+        .map_err(|e| format!("Failed to read {}: {}", relative, e))?
+    {
+        TextContent::Text(content) => content,
+        TextContent::Skipped(skipped) => return Err(skipped.message()),
+    };
+
+    Ok(serde_json::json!({
+        "path": relative,
+        "language": lang_id,
+        "lines": content.lines().count(),
+        "outline": render(&content, lang_id),
+    }))
+}
+
+/// Imports, then one line per definition with nested definitions indented
+fn render(content: &str, lang_id: &str) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let imports = extract_imports(content, lang_id);
+    let symbols = extract_outline(content, lang_id);
+
+    let mut outline = String::new();
+    for import in &imports {
+        let _ = writeln!(outline, "{}", truncate(import));
+    }
+    if !imports.is_empty() && !symbols.is_empty() {
+        outline.push('\n');
+    }
+    for symbol in &symbols {
+        let indent = if symbol.container.is_some() { "  " } else { "" };
+        let _ = writeln!(
+            outline,
+            "{}{}  [L{}-{}]",
+            indent,
+            signature(&lines, symbol),
+            symbol.start_line,
+            symbol.end_line
+        );
+    }
+    outline
+}
+
+/// Head of a definition up to its body
+fn signature(lines: &[&str], symbol: &OutlineSymbol) -> String {
+    let start = symbol.start_line as usize - 1;
+    let count = (symbol.end_line - symbol.start_line) as usize + 1;
+    let mut parts = Vec::new();
+    for line in lines
+        .iter()
+        .skip(start)
+        .take(count.min(MAX_SIGNATURE_LINES))
+    {
+        let line = line.trim();
+        if let Some(brace) = line.find('{') {
+            parts.push(line[..brace].trim_end());
+            break;
+        }
+        parts.push(line);
+        if line.ends_with(':') || line.ends_with(';') {
+            break;
+        }
+    }
+
+    let signature = parts
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if signature.is_empty() {
+        format!("{} {}", symbol.kind, symbol.name)
+    } else {
+        truncate(&signature)
+    }
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_SIGNATURE_CHARS {
+        return text.to_string();
+    }
+    let head: String = text.chars().take(MAX_SIGNATURE_CHARS).collect();
+    format!("{}...", head)
+}
+
+fn to_output(result: Result<serde_json::Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn code_outline_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: CODE_OUTLINE_TOOL.to_string(),
+        description: "Outline the structure of a source file or directory: imports and the \
+                      signatures of classes, functions and other definitions with their line \
+                      ranges. Use it to survey large files before reading the parts you need."
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "File or directory relative to the workspace root (default: the whole workspace)"
+                },
+                "maxFiles": {
+                    "type": "integer",
+                    "description": "Most files to outline in a directory (default 50, max 200)"
+                }
+            }
+        }),
+        requires_approval: false,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::FilesystemRead],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_outline_file_and_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(
+            root.join("src/config.rs"),
+            "use std::fs;\n\npub struct Config {\n    name: String,\n}\n\nimpl Config {\n    pub fn load(\n        path: &str,\n    ) -> Self {\n        todo!()\n    }\n}\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("src/empty.py"), "x = 1\n").unwrap();
+        std::fs::write(root.join("README.md"), "# Readme\n").unwrap();
+
+        let data = outline(root, serde_json::json!({ "path": "src/config.rs" })).unwrap();
+        assert_eq!(
+            data["files"][0]["outline"],
+            "use std::fs;\n\npub struct Config  [L3-5]\n  pub fn load( path: &str, ) -> Self  [L8-12]\n"
+        );
+
+        let data = outline(root, serde_json::json!({})).unwrap();
+        let files = data["files"].as_array().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0]["path"], "src/config.rs");
+        assert_eq!(data["truncated"], false);
+
+        assert!(outline(root, serde_json::json!({ "path": "README.md" })).is_err());
+        assert!(outline(root, serde_json::json!({ "path": "../x.rs" })).is_err());
+    }
+}
@@ -0,0 +1,678 @@
+//! Patch Tool
+//!
+//! The `apply_patch` tool applies a unified diff to files in the workspace.
+//! Hunks are located in the current file contents rather than trusted by
+//! line number: a hunk may have moved, differ in whitespace, or need its
+//! outer context lines dropped. The patch is applied all-or-nothing; when a
+//! hunk can't be placed nothing is written and the rejected hunks are reported.
+
+use crate::core::tools::{ToolContext, ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::{ToolDefinition, ToolRequest};
+use crate::file_watcher::PauseGuard;
+use crate::path_guard;
+use crate::storage::ToolCapability;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const APPLY_PATCH_TOOL: &str = "apply_patch";
+
+/// Most context lines dropped from each end of a hunk that doesn't match
+const MAX_FUZZ: usize = 2;
+
+/// A line of a hunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+/// A hunk of a file patch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// Line the hunk starts at in the original file, 1-based
+    pub old_start: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+/// Changes to a single file; a missing path is `/dev/null`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePatch {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    /// Path the patch writes, or deletes when the file is removed
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+/// A hunk that could not be placed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RejectedHunk {
+    pub path: String,
+    /// Position of the hunk in its file patch, 1-based
+    pub hunk: usize,
+    pub old_start: usize,
+    pub reason: String,
+}
+
+/// What the patch did to a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchedFile {
+    pub path: String,
+    /// `created`, `modified`, `deleted` or `renamed`
+    pub status: &'static str,
+    pub hunks: usize,
+    /// Hunks placed only after ignoring whitespace or context lines
+    pub fuzzy_hunks: usize,
+}
+
+/// Register the `apply_patch` tool
+pub async fn register_tool(registry: &ToolRegistry) -> Result<(), String> {
+    let handler: ToolHandler =
+        Arc::new(|request, context| Box::pin(async move { apply_patch_tool(&request, &context) }));
+
+    registry.register(apply_patch_definition(), handler).await
+}
+
+fn apply_patch_tool(request: &ToolRequest, context: &ToolContext) -> ToolExecutionOutput {
+    let Some(patch) = request.input.get("patch").and_then(|v| v.as_str()) else {
+        return failure(serde_json::Value::Null, "Missing 'patch'".to_string());
+    };
+    let root = context
+        .worktree_path
+        .as_deref()
+        .unwrap_or(&context.workspace_root);
+
+    match apply_patch(Path::new(root), patch) {
+        Ok(files) => ToolExecutionOutput {
+            success: true,
+            data: serde_json::json!({ "files": files }),
+            error: None,
+        },
+        Err(PatchError::Invalid(e)) => failure(serde_json::Value::Null, e),
+        Err(PatchError::Rejected(rejected)) => failure(
+            serde_json::json!({ "rejected": rejected }),
+            format!(
+                "{} hunk(s) did not apply; no files were changed",
+                rejected.len()
+            ),
+        ),
+    }
+}
+
+fn failure(data: serde_json::Value, error: String) -> ToolExecutionOutput {
+    ToolExecutionOutput {
+        success: false,
+        data,
+        error: Some(error),
+    }
+}
+
+/// Why a patch was not applied
+#[derive(Debug)]
+pub enum PatchError {
+    /// The patch can't be parsed or names files it may not touch
+    Invalid(String),
+    /// Hunks that don't fit the current files
+    Rejected(Vec<RejectedHunk>),
+}
+
+/// Apply a unified diff to the files under `root`. Either every file is
+/// written or none is.
+pub fn apply_patch(root: &Path, patch: &str) -> Result<Vec<PatchedFile>, PatchError> {
+    let file_patches = parse_patch(patch).map_err(PatchError::Invalid)?;
+    if file_patches.is_empty() {
+        return Err(PatchError::Invalid("Patch contains no files".to_string()));
+    }
+
+    let mut writes: Vec<(PathBuf, Option<String>)> = vec![];
+    let mut patched = vec![];
+    let mut rejected = vec![];
+
+    for file_patch in &file_patches {
+        let target =
+            path_guard::resolve_relative(root, file_patch.path()).map_err(PatchError::Invalid)?;
+        let source = match &file_patch.old_path {
+            Some(old_path) => {
+                Some(path_guard::resolve_relative(root, old_path).map_err(PatchError::Invalid)?)
+            }
+            None => None,
+        };
+        let reject = |reason: String| RejectedHunk {
+            path: file_patch.path().to_string(),
+            hunk: 0,
+            old_start: 0,
+            reason,
+        };
+
+        let original = match &source {
+            Some(source) => match std::fs::read_to_string(source) {
+                Ok(content) => content,
+                Err(e) => {
+                    rejected.push(reject(format!("Failed to read file: {}", e)));
+                    continue;
+                }
+            },
+            None if target.exists() => {
+                rejected.push(reject("File already exists".to_string()));
+                continue;
+            }
+            None => String::new(),
+        };
+
+        let (content, fuzzy_hunks) = match apply_hunks(&original, &file_patch.hunks) {
+            Ok(applied) => applied,
+            Err(failed) => {
+                rejected.extend(failed.into_iter().map(|(index, reason)| RejectedHunk {
+                    path: file_patch.path().to_string(),
+                    hunk: index + 1,
+                    old_start: file_patch.hunks[index].old_start,
+                    reason,
+                }));
+                continue;
+            }
+        };
+
+        let status = match (&file_patch.old_path, &file_patch.new_path) {
+            (None, _) => "created",
+            (_, None) => {
+                if !content.trim().is_empty() {
+                    rejected.push(reject(
+                        "File has content the deletion doesn't remove".to_string(),
+                    ));
+                    continue;
+                }
+                writes.push((target.clone(), None));
+                "deleted"
+            }
+            (Some(old_path), Some(new_path)) if old_path != new_path => {
+                if let Some(source) = source {
+                    writes.push((source, None));
+                }
+                "renamed"
+            }
+            _ => "modified",
+        };
+        if status != "deleted" {
+            writes.push((target, Some(content)));
+        }
+        patched.push(PatchedFile {
+            path: file_patch.path().to_string(),
+            status,
+            hunks: file_patch.hunks.len(),
+            fuzzy_hunks,
+        });
+    }
+
+    if !rejected.is_empty() {
+        return Err(PatchError::Rejected(rejected));
+    }
+    // Emit one refresh for a multi-file patch instead of an event per file
+    let _pause = (writes.len() > 1).then(PauseGuard::acquire);
+    write_all(&writes).map_err(PatchError::Invalid)?;
+
+    Ok(patched)
+}
+
+/// Paths named by a patch, for checkpoints taken before it runs
+pub fn patch_paths(patch: &str) -> Vec<String> {
+    parse_patch(patch)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|file_patch| [file_patch.old_path, file_patch.new_path])
+        .flatten()
+        .collect()
+}
+
+/// Parse a unified diff. Hunk line counts are ignored since model-written
+/// diffs often get them wrong; a hunk runs until the next header.
+pub fn parse_patch(patch: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = patch
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    let mut files: Vec<FilePatch> = vec![];
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if is_file_header(&lines, i) {
+            files.push(FilePatch {
+                old_path: parse_path(&line[4..]),
+                new_path: parse_path(&lines[i + 1][4..]),
+                hunks: vec![],
+            });
+            i += 2;
+            continue;
+        }
+        if !line.starts_with("@@") {
+            // `diff --git`, `index` and other extended headers
+            i += 1;
+            continue;
+        }
+
+        let file = files
+            .last_mut()
+            .ok_or_else(|| format!("Hunk at line {} has no file header", i + 1))?;
+        let old_start = parse_hunk_start(line)
+            .ok_or_else(|| format!("Invalid hunk header at line {}: {}", i + 1, line))?;
+        let mut hunk = Hunk {
+            old_start,
+            lines: vec![],
+        };
+        i += 1;
+        while i < lines.len() && !lines[i].starts_with("@@") && !is_file_header(&lines, i) {
+            let line = lines[i];
+            if line.starts_with("diff ") {
+                break;
+            }
+            match line.chars().next() {
+                Some('+') => hunk.lines.push(HunkLine::Add(line[1..].to_string())),
+                Some('-') => hunk.lines.push(HunkLine::Remove(line[1..].to_string())),
+                Some(' ') => hunk.lines.push(HunkLine::Context(line[1..].to_string())),
+                // A blank context line whose leading space was stripped
+                None => hunk.lines.push(HunkLine::Context(String::new())),
+                // `\ No newline at end of file`
+                _ => {}
+            }
+            i += 1;
+        }
+        // Blank lines trailing a hunk separate it from what follows
+        while hunk.lines.last() == Some(&HunkLine::Context(String::new())) {
+            hunk.lines.pop();
+        }
+        file.hunks.push(hunk);
+    }
+
+    if let Some(file) = files.iter().find(|file| file.hunks.is_empty()) {
+        return Err(format!("No hunks for {}", file.path()));
+    }
+    Ok(files)
+}
+
+fn is_file_header(lines: &[&str], i: usize) -> bool {
+    lines[i].starts_with("--- ")
+        && lines
+            .get(i + 1)
+            .is_some_and(|next| next.starts_with("+++ "))
+}
+
+/// Path of a `---`/`+++` header without its `a/`/`b/` prefix and timestamp
+fn parse_path(header: &str) -> Option<String> {
+    let path = header.split('\t').next().unwrap_or_default().trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// Old start line of a `@@ -l,s +l,s @@` header
+fn parse_hunk_start(header: &str) -> Option<usize> {
+    let old = header
+        .trim_start_matches('@')
+        .split_whitespace()
+        .next()?
+        .strip_prefix('-')?;
+    old.split(',').next()?.parse().ok()
+}
+
+/// How strictly hunk lines are compared with file lines
+#[derive(Clone, Copy)]
+enum Match {
+    Exact,
+    TrailingWhitespace,
+    Whitespace,
+}
+
+impl Match {
+    fn eq(self, file_line: &str, hunk_line: &str) -> bool {
+        match self {
+            Match::Exact => file_line == hunk_line,
+            Match::TrailingWhitespace => file_line.trim_end() == hunk_line.trim_end(),
+            Match::Whitespace => file_line.trim() == hunk_line.trim(),
+        }
+    }
+}
+
+/// Apply hunks to a file's content. Returns the new content and the number
+/// of hunks that needed fuzzy matching, or the index and reason of every
+/// hunk that could not be placed.
+pub fn apply_hunks(content: &str, hunks: &[Hunk]) -> Result<(String, usize), Vec<(usize, String)>> {
+    let eol = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let trailing_newline = content.is_empty() || content.ends_with('\n');
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    // Line shift of the original lines after the last applied hunk
+    let mut offset: isize = 0;
+    // Hunks apply in order and may not overlap
+    let mut from = 0;
+    let mut fuzzy = 0;
+    let mut failed = vec![];
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        let leading = hunk
+            .lines
+            .iter()
+            .take_while(|l| matches!(l, HunkLine::Context(_)))
+            .count();
+        let trailing = hunk
+            .lines
+            .iter()
+            .rev()
+            .take_while(|l| matches!(l, HunkLine::Context(_)))
+            .count()
+            .min(hunk.lines.len() - leading);
+
+        let mut placed = None;
+        'search: for fuzz in 0..=MAX_FUZZ {
+            let lead = fuzz.min(leading);
+            let trail = fuzz.min(trailing);
+            if fuzz > 0 && lead == 0 && trail == 0 {
+                break;
+            }
+            let section = &hunk.lines[lead..hunk.lines.len() - trail];
+            let old: Vec<&str> = section
+                .iter()
+                .filter_map(|line| match line {
+                    HunkLine::Context(text) | HunkLine::Remove(text) => Some(text.as_str()),
+                    HunkLine::Add(_) => None,
+                })
+                .collect();
+            let expected = (hunk.old_start.saturating_sub(1) + lead) as isize + offset;
+            let expected = expected.max(from as isize) as usize;
+
+            for mode in [Match::Exact, Match::TrailingWhitespace, Match::Whitespace] {
+                if let Some(start) = find(&lines, &old, expected, from, mode) {
+                    let exact = fuzz == 0 && matches!(mode, Match::Exact);
+                    placed = Some((start, section, old.len(), exact, lead));
+                    break 'search;
+                }
+            }
+        }
+
+        let Some((start, section, old_len, exact, lead)) = placed else {
+            failed.push((index, "Context does not match the file".to_string()));
+            continue;
+        };
+
+        // Context lines keep the file's text
+        let mut replacement = vec![];
+        let mut cursor = start;
+        for line in section {
+            match line {
+                HunkLine::Context(_) => {
+                    replacement.push(lines[cursor].clone());
+                    cursor += 1;
+                }
+                HunkLine::Remove(_) => cursor += 1,
+                HunkLine::Add(text) => replacement.push(text.clone()),
+            }
+        }
+        let new_len = replacement.len();
+        lines.splice(start..start + old_len, replacement);
+
+        offset = start as isize - (hunk.old_start.saturating_sub(1) + lead) as isize
+            + new_len as isize
+            - old_len as isize;
+        from = start + new_len;
+        if !exact {
+            fuzzy += 1;
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(failed);
+    }
+    let mut patched = lines.join(eol);
+    if trailing_newline && !lines.is_empty() {
+        patched.push_str(eol);
+    }
+    Ok((patched, fuzzy))
+}
+
+/// Start of `old` in `lines` at or after `from`, closest to `expected`
+fn find(
+    lines: &[String],
+    old: &[&str],
+    expected: usize,
+    from: usize,
+    mode: Match,
+) -> Option<usize> {
+    if old.is_empty() {
+        return Some(expected.min(lines.len()));
+    }
+    let last = lines.len().checked_sub(old.len())?;
+    if from > last {
+        return None;
+    }
+    let expected = expected.clamp(from, last);
+    let matches_at = |start: usize| {
+        old.iter()
+            .zip(&lines[start..])
+            .all(|(hunk_line, file_line)| mode.eq(file_line, hunk_line))
+    };
+
+    (0..=(last - from).max(expected - from)).find_map(|distance| {
+        let after = expected + distance;
+        if after <= last && matches_at(after) {
+            return Some(after);
+        }
+        let before = expected.checked_sub(distance).filter(|&b| b >= from)?;
+        matches_at(before).then_some(before)
+    })
+}
+
+/// Write or delete every file, undoing the writes already made if one fails
+fn write_all(writes: &[(PathBuf, Option<String>)]) -> Result<(), String> {
+    let mut done: Vec<(&PathBuf, Option<Vec<u8>>)> = vec![];
+
+    for (path, content) in writes {
+        let previous = std::fs::read(path).ok();
+        let result = match content {
+            Some(content) => write_file(path, content),
+            None => std::fs::remove_file(path)
+                .map_err(|e| format!("Failed to delete {}: {}", path.display(), e)),
+        };
+        if let Err(e) = result {
+            for (path, previous) in done.into_iter().rev() {
+                let _ = match previous {
+                    Some(bytes) => std::fs::write(path, bytes),
+                    None => std::fs::remove_file(path),
+                };
+            }
+            return Err(e);
+        }
+        done.push((path, previous));
+    }
+
+    Ok(())
+}
+
+/// Write through a temporary file so a file is never left half written
+pub(crate) fn write_file(path: &Path, content: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let temp = path.with_file_name(format!(
+        ".{}.patch-tmp",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    std::fs::write(&temp, content)
+        .and_then(|_| std::fs::rename(&temp, path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&temp);
+            format!("Failed to write {}: {}", path.display(), e)
+        })
+}
+
+fn apply_patch_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: APPLY_PATCH_TOOL.to_string(),
+        description: "Apply a unified diff to files in the workspace. Hunks are matched against \
+                      the current file contents, so line numbers may be approximate. Nothing is \
+                      written unless every hunk applies; rejected hunks are reported."
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "patch": {
+                    "type": "string",
+                    "description": "Unified diff with ---/+++ file headers and @@ hunks"
+                }
+            },
+            "required": ["patch"]
+        }),
+        requires_approval: true,
+        modifies_files: true,
+        capabilities: vec![ToolCapability::FilesystemWrite],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn hunk(old_start: usize, lines: &[&str]) -> Hunk {
+        Hunk {
+            old_start,
+            lines: lines
+                .iter()
+                .map(|line| match line.split_at(1) {
+                    ("+", text) => HunkLine::Add(text.to_string()),
+                    ("-", text) => HunkLine::Remove(text.to_string()),
+                    (_, text) => HunkLine::Context(text.to_string()),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_patch() {
+        let patch = "diff --git a/src/lib.rs b/src/lib.rs\n\
+                     index 123..456 100644\n\
+                     --- a/src/lib.rs\n\
+                     +++ b/src/lib.rs\n\
+                     @@ -1,3 +1,3 @@\n \
+                     fn main() {\n\
+                     -    old();\n\
+                     +    new();\n\
+                     \n\
+                     --- /dev/null\n\
+                     +++ b/notes.txt\t2024-01-01\n\
+                     @@ -0,0 +1 @@\n\
+                     +hello\n";
+
+        let files = parse_patch(patch).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path(), "src/lib.rs");
+        assert_eq!(
+            files[0].hunks,
+            vec![hunk(1, &[" fn main() {", "-    old();", "+    new();"])]
+        );
+        assert_eq!(files[1].old_path, None);
+        assert_eq!(files[1].path(), "notes.txt");
+        assert_eq!(
+            patch_paths(patch),
+            vec!["src/lib.rs", "src/lib.rs", "notes.txt"]
+        );
+
+        assert!(parse_patch("@@ -1 +1 @@\n-a\n+b\n").is_err());
+    }
+
+    #[test]
+    fn test_apply_hunks_tolerates_moved_and_reindented_context() {
+        let content = "// header\n// more\nfn a() {\n    one();\n}\n\nfn b() {\n    two();\n}\n";
+
+        // Line numbers are off by two
+        let (patched, fuzzy) = apply_hunks(
+            content,
+            &[hunk(
+                5,
+                &[" fn b() {", "-    two();", "+    three();", " }"],
+            )],
+        )
+        .unwrap();
+        assert!(patched.contains("    three();\n"));
+        assert_eq!(fuzzy, 0);
+
+        // Indentation differs and the outer context line is wrong
+        let (patched, fuzzy) = apply_hunks(
+            content,
+            &[
+                hunk(3, &[" fn a() {", "-one();", "+uno();", " }"]),
+                hunk(7, &[" fn x() {", " fn b() {", "-    two();", "+    dos();"]),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            patched,
+            "// header\n// more\nfn a() {\nuno();\n}\n\nfn b() {\n    dos();\n}\n"
+        );
+        assert_eq!(fuzzy, 2);
+
+        let failed = apply_hunks(content, &[hunk(1, &["-missing();"])]).unwrap_err();
+        assert_eq!(failed[0].0, 0);
+    }
+
+    #[test]
+    fn test_apply_patch_is_all_or_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(root.join("a.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(root.join("b.txt"), "three\n").unwrap();
+
+        let rejected = "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+2\n\
+                        --- a/b.txt\n+++ b/b.txt\n@@ -1 +1 @@\n-four\n+4\n";
+        let Err(PatchError::Rejected(hunks)) = apply_patch(root, rejected) else {
+            panic!("expected rejected hunks");
+        };
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].path, "b.txt");
+        assert_eq!(
+            std::fs::read_to_string(root.join("a.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+
+        let patch = "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+2\n\
+                     --- a/b.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-three\n\
+                     --- /dev/null\n+++ b/dir/c.txt\n@@ -0,0 +1 @@\n+new\n";
+        let files = apply_patch(root, patch).unwrap();
+        let statuses: Vec<&str> = files.iter().map(|file| file.status).collect();
+        assert_eq!(statuses, vec!["modified", "deleted", "created"]);
+        assert_eq!(
+            std::fs::read_to_string(root.join("a.txt")).unwrap(),
+            "one\n2\n"
+        );
+        assert!(!root.join("b.txt").exists());
+        assert_eq!(
+            std::fs::read_to_string(root.join("dir/c.txt")).unwrap(),
+            "new\n"
+        );
+
+        let escape = "--- a/../x.txt\n+++ b/../x.txt\n@@ -1 +1 @@\n-a\n+b\n";
+        assert!(matches!(
+            apply_patch(root, escape),
+            Err(PatchError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+//! Plan Mode
+//!
+//! A task in plan mode only sees read-only tools and must finish by calling
+//! `submit_plan`. The submitted plan is stored with the session, and
+//! `execute_plan` later starts a task with every tool and the plan in context.
+
+use crate::core::types::ToolDefinition;
+use crate::storage::{Plan, ToolCapability};
+
+/// Tool the agent calls to hand in its plan
+pub const SUBMIT_PLAN_TOOL: &str = "submit_plan";
+
+/// System prompt for tasks running in plan mode
+pub const PLAN_MODE_PROMPT: &str = "You are in plan mode. Investigate with the read-only \
+     tools available and do not try to change anything. When you know what to do, call \
+     submit_plan with a short summary and the ordered steps you will take.";
+
+/// Reminder added when the model stops without submitting a plan
+pub const PLAN_REMINDER: &str =
+    "Plan mode ends only when you call submit_plan with your summary and steps.";
+
+/// Summary and steps of a plan before it is stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanDraft {
+    pub summary: String,
+    pub steps: Vec<String>,
+}
+
+/// Definition of the `submit_plan` tool offered in plan mode
+pub fn submit_plan_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: SUBMIT_PLAN_TOOL.to_string(),
+        description: "Submit the plan for the user to review before anything is changed"
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "summary": {
+                    "type": "string",
+                    "description": "What the plan achieves"
+                },
+                "steps": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Ordered steps to carry out"
+                }
+            },
+            "required": ["summary", "steps"]
+        }),
+        requires_approval: false,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::FilesystemRead],
+    }
+}
+
+/// Parse the input of a `submit_plan` call
+pub fn parse_plan(input: &serde_json::Value) -> Result<PlanDraft, String> {
+    let summary = input
+        .get("summary")
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|summary| !summary.is_empty())
+        .ok_or_else(|| "Missing 'summary'".to_string())?;
+    let steps: Vec<String> = input
+        .get("steps")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| "Missing 'steps'".to_string())?
+        .iter()
+        .filter_map(|step| step.as_str())
+        .map(str::trim)
+        .filter(|step| !step.is_empty())
+        .map(str::to_string)
+        .collect();
+    if steps.is_empty() {
+        return Err("A plan needs at least one step".to_string());
+    }
+
+    Ok(PlanDraft {
+        summary: summary.to_string(),
+        steps,
+    })
+}
+
+/// User message that starts the execution of a plan
+pub fn execution_message(plan: &Plan) -> String {
+    let mut message = format!("Execute the approved plan.\n\n{}\n", plan.summary);
+    for (index, step) in plan.steps.iter().enumerate() {
+        message.push_str(&format!("\n{}. {}", index + 1, step));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::TaskSettings;
+
+    #[test]
+    fn test_parse_plan() {
+        let draft = parse_plan(&serde_json::json!({
+            "summary": " Rename the loader ",
+            "steps": ["Rename module", "", "Fix imports"]
+        }))
+        .unwrap();
+        assert_eq!(draft.summary, "Rename the loader");
+        assert_eq!(draft.steps, vec!["Rename module", "Fix imports"]);
+
+        assert!(parse_plan(&serde_json::json!({ "summary": "x" })).is_err());
+        assert!(parse_plan(&serde_json::json!({ "summary": "x", "steps": [] })).is_err());
+    }
+
+    #[test]
+    fn test_execution_message_lists_steps() {
+        let plan = Plan {
+            id: "plan-1".to_string(),
+            session_id: "session-1".to_string(),
+            task_id: "task-1".to_string(),
+            agent_id: None,
+            summary: "Rename the loader".to_string(),
+            steps: vec!["Rename module".to_string(), "Fix imports".to_string()],
+            settings: TaskSettings::default(),
+            workspace_root: "/tmp".to_string(),
+            worktree_path: None,
+            created_at: 0,
+            executed_at: None,
+        };
+
+        assert_eq!(
+            execution_message(&plan),
+            "Execute the approved plan.\n\nRename the loader\n\n1. Rename module\n2. Fix imports"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Projects
+//!
+//! A project names a workspace directory and the settings its tasks start
+//! with. Tasks of a project that name no workspace or settings run with the
+//! project's; sessions only reference projects, so deleting one keeps them.
+
+use crate::storage::{Project, ProjectUpdates, ProjectsRepository, TaskSettings};
+use std::path::Path;
+
+/// Creates, edits and deletes projects
+#[derive(Clone)]
+pub struct ProjectManager {
+    projects: ProjectsRepository,
+}
+
+impl ProjectManager {
+    pub fn new(projects: ProjectsRepository) -> Self {
+        Self { projects }
+    }
+
+    /// List projects, optionally of one user, by name
+    pub async fn list(&self, user_id: Option<&str>) -> Result<Vec<Project>, String> {
+        self.projects.list_projects(user_id).await
+    }
+
+    /// Get a project by ID
+    pub async fn get(&self, project_id: &str) -> Result<Option<Project>, String> {
+        self.projects.get_project(project_id).await
+    }
+
+    /// Create a project of a user, or of the machine's owner with `None`
+    pub async fn create(
+        &self,
+        name: &str,
+        workspace_path: &str,
+        default_settings: Option<TaskSettings>,
+        user_id: Option<String>,
+    ) -> Result<Project, String> {
+        let now = chrono::Utc::now().timestamp();
+        let project = Project {
+            id: format!("proj_{}", uuid::Uuid::new_v4().simple()),
+            name: validate_name(name)?,
+            workspace_path: validate_workspace(workspace_path)?,
+            default_settings,
+            user_id,
+            created_at: now,
+            updated_at: now,
+        };
+        self.projects.create_project(&project).await?;
+        Ok(project)
+    }
+
+    /// Update a project and return it
+    pub async fn update(
+        &self,
+        project_id: &str,
+        updates: ProjectUpdates,
+    ) -> Result<Project, String> {
+        let updates = ProjectUpdates {
+            name: updates.name.as_deref().map(validate_name).transpose()?,
+            workspace_path: updates
+                .workspace_path
+                .as_deref()
+                .map(validate_workspace)
+                .transpose()?,
+            ..updates
+        };
+
+        if !self.projects.update_project(project_id, updates).await? {
+            return Err(format!("Project '{}' not found", project_id));
+        }
+        self.get(project_id)
+            .await?
+            .ok_or_else(|| format!("Project '{}' not found", project_id))
+    }
+
+    /// Delete a project; its sessions are kept
+    pub async fn delete(&self, project_id: &str) -> Result<(), String> {
+        if !self.projects.delete_project(project_id).await? {
+            return Err(format!("Project '{}' not found", project_id));
+        }
+        Ok(())
+    }
+}
+
+fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Project name cannot be empty".to_string());
+    }
+    Ok(name.to_string())
+}
+
+/// Absolute path of an existing workspace directory
+fn validate_workspace(workspace_path: &str) -> Result<String, String> {
+    let path = Path::new(workspace_path.trim());
+    if !path.is_dir() {
+        return Err(format!(
+            "Workspace path is not a directory: {}",
+            workspace_path
+        ));
+    }
+    path.canonicalize()
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| format!("Failed to resolve {}: {}", workspace_path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_workspace_is_validated() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().to_path_buf(),
+            temp_dir.path().join("attachments"),
+        )
+        .await
+        .unwrap();
+        let manager = ProjectManager::new(storage.projects.clone());
+        let workspace = temp_dir.path().join("app");
+        std::fs::create_dir(&workspace).unwrap();
+        let workspace = workspace.to_string_lossy().to_string();
+
+        assert!(manager.create(" ", &workspace, None, None).await.is_err());
+        assert!(manager
+            .create("App", &format!("{}/missing", workspace), None, None)
+            .await
+            .is_err());
+        let project = manager
+            .create(" App ", &workspace, None, None)
+            .await
+            .unwrap();
+        assert_eq!(project.name, "App");
+
+        let file = temp_dir.path().join("notes.txt");
+        std::fs::write(&file, "").unwrap();
+        let updates = ProjectUpdates {
+            workspace_path: Some(file.to_string_lossy().to_string()),
+            ..ProjectUpdates::default()
+        };
+        assert!(manager.update(&project.id, updates).await.is_err());
+
+        manager.delete(&project.id).await.unwrap();
+        assert!(manager.delete(&project.id).await.is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! Session Retention
+//!
+//! A retention policy archives sessions that have been idle for a number of
+//! days and deletes them after a longer idle period. Archived sessions are
+//! hidden from default session lists but stay restorable until deleted.
+//! The policy is stored in the `session_retention` setting and applied by the
+//! runtime's maintenance task.
+
+use crate::storage::{Session, SessionId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Setting holding the JSON [`RetentionPolicy`]
+pub const SETTINGS_KEY: &str = "session_retention";
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// When idle sessions are archived and deleted. Both steps are off unless
+/// configured.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetentionPolicy {
+    /// Archive sessions idle for more than this many days
+    pub archive_after_days: Option<u32>,
+    /// Delete sessions idle for more than this many days
+    pub delete_after_days: Option<u32>,
+    /// Never archive or delete starred sessions
+    pub exclude_starred: bool,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            archive_after_days: None,
+            delete_after_days: None,
+            exclude_starred: true,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.archive_after_days == Some(0) || self.delete_after_days == Some(0) {
+            return Err("Retention periods must be at least one day".to_string());
+        }
+        if let (Some(archive), Some(delete)) = (self.archive_after_days, self.delete_after_days) {
+            if delete <= archive {
+                return Err(format!(
+                    "deleteAfterDays ({}) must be greater than archiveAfterDays ({})",
+                    delete, archive
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Updated-at cutoff below which a session is a candidate for any step,
+    /// or None when the policy does nothing
+    pub fn idle_cutoff(&self, now: i64) -> Option<i64> {
+        self.archive_after_days
+            .into_iter()
+            .chain(self.delete_after_days)
+            .min()
+            .map(|days| cutoff(now, days))
+    }
+}
+
+/// Sessions a policy run archived and deleted
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionReport {
+    pub archived: Vec<SessionId>,
+    pub deleted: Vec<SessionId>,
+}
+
+/// Decide what happens to idle sessions. Sessions in `busy` have tasks in
+/// the runtime and are left alone.
+pub fn plan(
+    policy: &RetentionPolicy,
+    sessions: &[Session],
+    busy: &HashSet<SessionId>,
+    now: i64,
+) -> RetentionReport {
+    let mut report = RetentionReport::default();
+
+    for session in sessions {
+        if busy.contains(&session.id) || (policy.exclude_starred && session.starred) {
+            continue;
+        }
+        let idle_since =
+            |days: Option<u32>| days.is_some_and(|days| session.updated_at < cutoff(now, days));
+        if idle_since(policy.delete_after_days) {
+            report.deleted.push(session.id.clone());
+        } else if session.archived_at.is_none() && idle_since(policy.archive_after_days) {
+            report.archived.push(session.id.clone());
+        }
+    }
+
+    report
+}
+
+fn cutoff(now: i64, days: u32) -> i64 {
+    now - i64::from(days) * SECONDS_PER_DAY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SessionStatus;
+
+    fn session(id: &str, idle_days: i64, starred: bool, archived: bool) -> Session {
+        let updated_at = 1_000 * SECONDS_PER_DAY - idle_days * SECONDS_PER_DAY - 1;
+        Session {
+            id: id.to_string(),
+            project_id: None,
+            title: None,
+            summary: None,
+            status: SessionStatus::Completed,
+            created_at: updated_at,
+            updated_at,
+            last_event_id: None,
+            metadata: None,
+            starred,
+            archived_at: archived.then_some(updated_at),
+            user_id: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_archives_and_deletes_idle_sessions() {
+        let policy = RetentionPolicy {
+            archive_after_days: Some(30),
+            delete_after_days: Some(90),
+            exclude_starred: true,
+        };
+        let sessions = vec![
+            session("fresh", 5, false, false),
+            session("idle", 30, false, false),
+            session("archived", 60, false, true),
+            session("old", 90, false, true),
+            session("starred", 120, true, false),
+            session("busy", 120, false, false),
+        ];
+        let busy = HashSet::from(["busy".to_string()]);
+
+        let report = plan(&policy, &sessions, &busy, 1_000 * SECONDS_PER_DAY);
+        assert_eq!(report.archived, vec!["idle".to_string()]);
+        assert_eq!(report.deleted, vec!["old".to_string()]);
+
+        let policy = RetentionPolicy {
+            exclude_starred: false,
+            ..policy
+        };
+        let report = plan(&policy, &sessions, &busy, 1_000 * SECONDS_PER_DAY);
+        assert_eq!(
+            report.deleted,
+            vec!["old".to_string(), "starred".to_string()]
+        );
+        assert_eq!(
+            policy.idle_cutoff(1_000 * SECONDS_PER_DAY),
+            Some(970 * SECONDS_PER_DAY)
+        );
+    }
+
+    #[test]
+    fn test_policy_validation() {
+        assert!(RetentionPolicy::default().validate().is_ok());
+        assert_eq!(RetentionPolicy::default().idle_cutoff(0), None);
+        let policy = RetentionPolicy {
+            archive_after_days: Some(30),
+            delete_after_days: Some(30),
+            exclude_starred: true,
+        };
+        assert!(policy.validate().is_err());
+        let policy = RetentionPolicy {
+            archive_after_days: Some(0),
+            delete_after_days: None,
+            exclude_starred: true,
+        };
+        assert!(policy.validate().is_err());
+    }
+}
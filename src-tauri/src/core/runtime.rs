@@ -3,16 +3,63 @@
 //! The main runtime that orchestrates task execution, session management,
 //! agent loops, and tool dispatch. Owns the lifecycle of all runtime tasks.
 
-use crate::core::agent_loop::{AgentLoop, AgentLoopContext, AgentLoopFactory, AgentLoopResult};
-use crate::core::session::SessionManager;
-use crate::core::tools::{ToolContext, ToolRegistry};
+use crate::core::agent_loop::{AgentLoop, AgentLoopContext, AgentLoopResult};
+use crate::core::budget;
+use crate::core::cancellation::CancellationToken;
+use crate::core::checkpoints::{CheckpointManager, CheckpointRollback};
+use crate::core::compaction;
+use crate::core::database_query::{DatabaseConnection, DatabaseManager};
+use crate::core::diagram::DiagramRenderer;
+use crate::core::edit;
+use crate::core::event_log::{self, LoggedEvent, RebuiltSession};
+use crate::core::format;
+use crate::core::grep;
+use crate::core::hooks::{Hook, HookEvent, HookManager, HookPayload};
+use crate::core::http_request::{HttpRequestConfig, HttpRequestManager};
+use crate::core::llm::LlmClient;
+use crate::core::memory::MemoryManager;
+use crate::core::metrics::{RecentError, RuntimeMetrics, RuntimeStats};
+use crate::core::notebook;
+use crate::core::outline;
+use crate::core::patch;
+use crate::core::plan;
+use crate::core::projects::ProjectManager;
+use crate::core::retention::{self, RetentionPolicy, RetentionReport};
+use crate::core::sandbox::{SandboxManager, SandboxPolicy};
+use crate::core::scheduler::{QueuedTask, TaskQueue, DEFAULT_MAX_CONCURRENT_TASKS};
+use crate::core::script_tools::{self, ScriptTool};
+use crate::core::session::{SessionManager, DEFAULT_SESSION_TITLE};
+use crate::core::session_summary;
+use crate::core::shell::ShellTool;
+use crate::core::shell_policy::{ShellPolicy, ShellPolicyManager};
+use crate::core::stream_state::{self, StreamStateRecorder};
+use crate::core::test_runner;
+use crate::core::todo::TodoManager;
+use crate::core::tools::{ToolContext, ToolDispatcher, ToolRegistry, TOOL_RETRY_POLICIES_KEY};
+use crate::core::truncation::TruncationConfig;
 use crate::core::types::*;
+use crate::core::web_search::{WebSearch, WebSearchConfig};
+use crate::core::webhooks::{Webhook, WebhookManager};
+use crate::core::workspace_agents::{WorkspaceAgent, WorkspaceAgentRegistry, AGENTS_DIR};
+use crate::git::worktree::{self, MergeResult};
+use crate::llm::models::model_registry::ModelRegistry;
+use crate::llm::types::ModelConfig;
+use crate::security::api_keys::{ApiKeys, CreatedApiKey};
+use crate::security::jwt::{AuthTokens, JwtAuth};
+use crate::security::pairing::{PairedDevice, Pairing, Pairings};
+use crate::security::AuthenticatedKey;
 use crate::storage::{
-    Message, MessageContent, MessageRole, SessionId, SessionStatus, Storage, TaskSettings,
+    AgentId, ApiKey, ApiKeyScope, AttachmentOrigin, BudgetPause, BudgetUsage, Checkpoint, Memory,
+    MemoryKind, MemoryUpdates, Message, MessageContent, MessageRole, ModelPhase, PendingApproval,
+    Plan, Project, ProjectUpdates, RuntimeEventRecord, SearchHit, SessionId, SessionStatus,
+    Storage, StreamState, TaskSettings, TaskWorktree, TodoList, ToolCall, User, WebhookDelivery,
+    WorkspaceInfo,
 };
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio::task::JoinHandle;
 
 /// Core runtime that manages all tasks and sessions
@@ -24,14 +71,83 @@ pub struct CoreRuntime {
     session_manager: Arc<SessionManager>,
     /// Tool registry
     tool_registry: Arc<ToolRegistry>,
+    /// LLM client used by agent loops
+    llm: Arc<dyn LlmClient>,
+    /// File checkpoints taken before file-modifying tool calls
+    checkpoints: CheckpointManager,
+    /// Long-term memories injected into system prompts
+    memory: MemoryManager,
+    /// Projects tasks take their workspace and default settings from
+    projects: ProjectManager,
+    /// User-configured commands run around tool calls and tasks
+    hooks: HookManager,
+    /// Endpoints notified of the tasks of their project
+    webhooks: WebhookManager,
+    /// Databases of the `query_database` tool
+    databases: DatabaseManager,
+    /// Allowed domains and secrets of the `http_request` tool
+    http_requests: HttpRequestManager,
+    /// Policies bounding what tool calls may access
+    sandbox: SandboxManager,
+    /// Policies deciding on the commands of `execute_shell` calls
+    shell_policy: ShellPolicyManager,
+    /// Backend of the `web_search` tool
+    web_search: WebSearch,
+    /// Terminals of running `execute_shell` calls
+    shell: ShellTool,
+    /// Todo lists the agent keeps per session
+    todos: TodoManager,
+    /// Custom agents defined in workspaces
+    workspace_agents: WorkspaceAgentRegistry,
+    /// Keys accepted by the HTTP server
+    api_keys: ApiKeys,
+    /// Tokens of clients signed in to the HTTP server
+    auth: JwtAuth,
+    /// Codes companion clients pair with
+    pairings: Pairings,
+    /// Counts and timings of task runs
+    metrics: RuntimeMetrics,
     /// Active tasks
     tasks: Arc<RwLock<HashMap<RuntimeTaskId, TaskHandle>>>,
+    /// Tasks waiting for a run slot and the tasks holding one
+    queue: Arc<Mutex<TaskQueue<QueuedRun>>>,
     /// Event broadcaster
     event_sender: EventSender,
+    /// Logged events published to stream subscribers
+    logged_events: broadcast::Sender<LoggedEvent>,
+    /// Cancelled on shutdown; running agent loops stop at their next
+    /// iteration boundary and no task starts afterwards
+    draining: CancellationToken,
     /// Settings for validation
     _settings_validator: SettingsValidator,
 }
 
+/// Logged events kept for subscribers that fall behind
+const LOGGED_EVENT_CAPACITY: usize = 1024;
+
+/// Task setting holding the maximum tokens per response
+const MAX_TOKENS_SETTING: &str = "maxTokens";
+
+/// Hits returned by a session search that sets no limit
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+/// Most hits a session search returns
+const MAX_SEARCH_LIMIT: usize = 100;
+
+/// How often a shutdown checks whether running tasks have stopped
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What a task needs from the model it runs with
+#[derive(Debug, Clone, Default)]
+pub struct ModelRequirements {
+    /// The session has images for the model to read
+    pub image_input: bool,
+    /// The agent is offered tools
+    pub tool_calling: bool,
+    /// Requested maximum tokens per response
+    pub max_tokens: Option<u32>,
+}
+
 /// Settings validator
 #[derive(Clone)]
 pub struct SettingsValidator;
@@ -61,6 +177,59 @@ impl SettingsValidator {
             );
         }
 
+        if let Err(e) = max_tokens_setting(settings) {
+            validation.add_validation_error(ValidationError::new(
+                ValidationErrorCode::InvalidSetting,
+                MAX_TOKENS_SETTING,
+                e,
+            ));
+        }
+
+        validation
+    }
+
+    /// Check that a model supports what a task needs from it
+    pub fn validate_model(
+        &self,
+        model: &ModelConfig,
+        requirements: &ModelRequirements,
+    ) -> SettingsValidation {
+        let mut validation = SettingsValidation::valid();
+
+        if requirements.image_input && !model.image_input {
+            validation.add_validation_error(ValidationError::new(
+                ValidationErrorCode::ImageInputUnsupported,
+                "models",
+                format!(
+                    "Model '{}' does not support image input, but the session has images",
+                    model.name
+                ),
+            ));
+        }
+
+        if requirements.tool_calling && !model.tool_call {
+            validation.add_validation_error(ValidationError::new(
+                ValidationErrorCode::ToolCallingUnsupported,
+                "models",
+                format!("Model '{}' does not support tool calling", model.name),
+            ));
+        }
+
+        if let (Some(max_tokens), Some(context_length)) =
+            (requirements.max_tokens, model.context_length)
+        {
+            if max_tokens > context_length {
+                validation.add_validation_error(ValidationError::new(
+                    ValidationErrorCode::MaxTokensExceedsContext,
+                    MAX_TOKENS_SETTING,
+                    format!(
+                        "maxTokens {} exceeds the {} token context of model '{}'",
+                        max_tokens, context_length, model.name
+                    ),
+                ));
+            }
+        }
+
         validation
     }
 }
@@ -73,33 +242,147 @@ impl Default for SettingsValidator {
 
 impl CoreRuntime {
     /// Create a new CoreRuntime instance
-    pub async fn new(storage: Storage, event_sender: EventSender) -> Result<Self, String> {
+    pub async fn new(
+        storage: Storage,
+        llm: Arc<dyn LlmClient>,
+        event_sender: EventSender,
+    ) -> Result<Self, String> {
         // Create session manager
         let session_manager = Arc::new(SessionManager::new(storage.clone()));
 
         // Create tool registry with default tools
         let tool_registry = Arc::new(ToolRegistry::create_default().await);
+        match storage
+            .settings
+            .get_setting_or_default::<HashMap<String, ToolRetryPolicy>>(
+                TOOL_RETRY_POLICIES_KEY,
+                HashMap::new(),
+            )
+            .await
+        {
+            Ok(policies) => {
+                for (name, policy) in policies {
+                    tool_registry.set_retry_policy(&name, Some(policy)).await;
+                }
+            }
+            Err(e) => log::warn!("Failed to load tool retry policies: {}", e),
+        }
+
+        let checkpoints = CheckpointManager::new(storage.chat_history.clone());
+        let memory = MemoryManager::new(storage.memories.clone(), storage.chat_history.clone());
+        memory.register_tools(&tool_registry).await?;
+        let projects = ProjectManager::new(storage.projects.clone());
+        patch::register_tool(&tool_registry).await?;
+        edit::register_tool(&tool_registry).await?;
+        test_runner::register_tool(&tool_registry).await?;
+        notebook::register_tools(&tool_registry).await?;
+        grep::register_tool(&tool_registry).await?;
+        format::register_tool(&tool_registry).await?;
+        outline::register_tool(&tool_registry).await?;
+        let web_search = WebSearch::new(storage.settings.clone(), llm.clone());
+        web_search.register_tool(&tool_registry).await?;
+        let databases =
+            DatabaseManager::new(storage.settings.clone(), storage.chat_history.clone());
+        databases.register_tools(&tool_registry).await?;
+        let http_requests =
+            HttpRequestManager::new(storage.settings.clone(), storage.chat_history.clone());
+        http_requests.register_tool(&tool_registry).await?;
+        DiagramRenderer::new(storage.attachments.clone())
+            .register_tool(&tool_registry)
+            .await?;
+        let hooks = HookManager::new(storage.settings.clone(), storage.chat_history.clone());
+        let sandbox = SandboxManager::new(storage.settings.clone(), storage.chat_history.clone());
+        let tasks = Arc::new(RwLock::new(HashMap::new()));
+        let (logged_events, _) = broadcast::channel(LOGGED_EVENT_CAPACITY);
+        let event_sender = event_log::spawn(
+            storage.chat_history.clone(),
+            tasks.clone(),
+            event_sender,
+            logged_events.clone(),
+        );
+        let webhooks = WebhookManager::new(storage.settings.clone(), storage.chat_history.clone());
+        webhooks.spawn(logged_events.subscribe());
+        let shell_policy = ShellPolicyManager::new(
+            storage.settings.clone(),
+            storage.chat_history.clone(),
+            event_sender.clone(),
+        );
+        let shell = ShellTool::new(event_sender.clone());
+        shell.register_tool(&tool_registry).await?;
+        let todos = TodoManager::new(storage.chat_history.clone(), event_sender.clone());
+        todos.register_tool(&tool_registry).await?;
+        let api_keys = ApiKeys::new(storage.api_keys.clone());
+        let auth = JwtAuth::load(
+            storage.settings.clone(),
+            api_keys.clone(),
+            storage.api_keys.clone(),
+        )
+        .await?;
 
-        Ok(Self {
+        let runtime = Self {
             storage,
             session_manager,
             tool_registry,
-            tasks: Arc::new(RwLock::new(HashMap::new())),
+            llm,
+            checkpoints,
+            memory,
+            projects,
+            hooks,
+            webhooks,
+            databases,
+            http_requests,
+            sandbox,
+            shell_policy,
+            web_search,
+            shell,
+            todos,
+            workspace_agents: WorkspaceAgentRegistry::new(),
+            api_keys,
+            auth,
+            pairings: Pairings::new(),
+            metrics: RuntimeMetrics::new(),
+            tasks,
+            queue: Arc::new(Mutex::new(TaskQueue::new(DEFAULT_MAX_CONCURRENT_TASKS))),
             event_sender,
+            logged_events,
             _settings_validator: SettingsValidator::new(),
-        })
+            draining: CancellationToken::new(),
+        };
+        runtime.restore_pending_tasks().await?;
+
+        Ok(runtime)
     }
 
-    /// Start a new task
+    /// Submit a new task. It starts right away when a run slot is free and
+    /// is queued by priority otherwise.
     pub async fn start_task(&self, input: TaskInput) -> Result<TaskHandle, String> {
-        // Validate settings if provided
+        if self.draining.is_cancelled() {
+            return Err("Runtime is shutting down".to_string());
+        }
+        let mut input = self.with_project_defaults(input).await?;
+        // Kept in the settings, so the task stays limited when it resumes
+        if input.read_only_tools {
+            input
+                .settings
+                .get_or_insert_with(TaskSettings::default)
+                .read_only_tools = Some(true);
+        }
+        let validation = self.validate_task(&input).await;
+        if !validation.valid {
+            return Err(format!(
+                "Invalid settings: {}",
+                validation.errors.join(", ")
+            ));
+        }
         if let Some(ref settings) = input.settings {
-            let validation = self._settings_validator.validate(settings);
-            if !validation.valid {
-                return Err(format!(
-                    "Invalid settings: {}",
-                    validation.errors.join(", ")
-                ));
+            if let Some(ref agent) = settings.agent {
+                let root = workspace_root(&input);
+                if self.workspace_agents.get(&root, agent).is_none() {
+                    return Err(format!(
+                        "Agent '{}' is not defined in {}/{}",
+                        agent, root, AGENTS_DIR
+                    ));
+                }
             }
         }
 
@@ -115,6 +398,10 @@ impl CoreRuntime {
                 .create_session(input.project_id.clone(), None, input.settings.clone())
                 .await?
         };
+        // Continuing an archived session brings it back
+        if session.archived_at.is_some() {
+            self.restore_session(&session.id).await?;
+        }
 
         let task_id = format!("task_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
         let now = chrono::Utc::now().timestamp();
@@ -137,11 +424,13 @@ impl CoreRuntime {
 
         // Create task handle
         let task_state = Arc::new(RwLock::new(RuntimeTaskState::Pending));
+        let cancel_token = CancellationToken::new();
         let handle = TaskHandle {
             task_id: task_id.clone(),
             session_id: session.id.clone(),
             state: task_state.clone(),
             action_sender: Arc::new(action_tx),
+            cancel_token: cancel_token.clone(),
         };
 
         // Store task handle
@@ -150,19 +439,164 @@ impl CoreRuntime {
             tasks.insert(task_id.clone(), handle.clone());
         }
 
-        // Spawn task execution
-        let runtime_clone = self.clone_for_task();
-        let event_sender = self.event_sender.clone();
-
-        tokio::spawn(async move {
-            runtime_clone
-                .run_task(task, input, task_state, action_rx, event_sender)
-                .await;
-        });
+        // Queue task execution
+        let priority = input.priority;
+        self.lock_queue().push(
+            task_id,
+            priority,
+            QueuedRun {
+                task,
+                input,
+                task_state,
+                action_rx,
+                cancel_token,
+            },
+        );
+        self.schedule();
 
         Ok(handle)
     }
 
+    /// Validate a task's settings and check that the model it would run with
+    /// supports the task. Models without a known config are not checked.
+    pub async fn validate_task(&self, input: &TaskInput) -> SettingsValidation {
+        let settings = input.settings.clone().unwrap_or_default();
+        let mut validation = self._settings_validator.validate(&settings);
+
+        let agent = settings
+            .agent
+            .as_ref()
+            .and_then(|name| self.workspace_agents.get(&workspace_root(input), name));
+        let phase = if settings.plan_mode == Some(true) {
+            ModelPhase::Planning
+        } else {
+            ModelPhase::Coding
+        };
+        // An unset model resolves to any available one, as when streaming
+        let model = ModelRegistry::resolve_phase_model(&settings, phase)
+            .or(agent.and_then(|agent| agent.model))
+            .unwrap_or_default();
+        let Some(config) = self.llm.model_config(&model).await else {
+            return validation;
+        };
+
+        let requirements = ModelRequirements {
+            image_input: self.session_has_images(&input.session_id).await,
+            // The agent loop always offers tools, read-only ones in plan mode
+            tool_calling: AgentLoopConfig::default().enable_tools,
+            max_tokens: max_tokens_setting(&settings).ok().flatten(),
+        };
+        validation.merge(
+            self._settings_validator
+                .validate_model(&config, &requirements),
+        );
+        validation
+    }
+
+    /// Whether the user uploaded images to a session
+    async fn session_has_images(&self, session_id: &str) -> bool {
+        if session_id.is_empty() {
+            return false;
+        }
+        match self
+            .storage
+            .attachments
+            .list_attachments(session_id, None)
+            .await
+        {
+            Ok(attachments) => attachments.iter().any(|attachment| {
+                attachment.origin == AttachmentOrigin::UserUpload
+                    && attachment.mime_type.starts_with("image/")
+            }),
+            Err(e) => {
+                log::warn!(
+                    "Failed to list attachments of session {}: {}",
+                    session_id,
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    /// Start queued tasks while run slots are free and announce the new
+    /// positions of the tasks still waiting
+    fn schedule(&self) {
+        // Queued tasks stay queued once the runtime drains
+        if self.draining.is_cancelled() {
+            return;
+        }
+        let (ready, changes) = {
+            let mut queue = self.lock_queue();
+            let mut ready = Vec::new();
+            while let Some(next) = queue.next_ready() {
+                ready.push(next);
+            }
+            (ready, queue.position_changes())
+        };
+
+        for (task_id, run) in ready {
+            let runtime_clone = self.clone();
+            let event_sender = self.event_sender.clone();
+            tokio::spawn(async move {
+                runtime_clone
+                    .run_task(
+                        run.task,
+                        run.input,
+                        run.task_state,
+                        run.action_rx,
+                        run.cancel_token,
+                        event_sender,
+                    )
+                    .await;
+                runtime_clone.release_slot(&task_id);
+            });
+        }
+
+        for (task_id, position) in changes {
+            let _ = self
+                .event_sender
+                .send(RuntimeEvent::TaskQueuePositionChanged { task_id, position });
+        }
+    }
+
+    /// Free the run slot of a task that finished or paused and start the next one
+    fn release_slot(&self, task_id: &str) {
+        self.lock_queue().finish(task_id);
+        self.schedule();
+    }
+
+    fn lock_queue(&self) -> std::sync::MutexGuard<'_, TaskQueue<QueuedRun>> {
+        // The queue holds no invariants a panicking holder could break halfway
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queued tasks in start order
+    pub fn list_queued_tasks(&self) -> Vec<QueuedTask> {
+        self.lock_queue().list()
+    }
+
+    /// Change the priority of a queued task
+    pub fn set_task_priority(&self, task_id: &str, priority: i32) -> Result<(), String> {
+        self.lock_queue().set_priority(task_id, priority)?;
+        self.schedule();
+        Ok(())
+    }
+
+    /// Move a queued task to a zero-based queue position
+    pub fn move_queued_task(&self, task_id: &str, position: usize) -> Result<(), String> {
+        self.lock_queue().move_to(task_id, position)?;
+        self.schedule();
+        Ok(())
+    }
+
+    /// Set how many tasks may run at once; more queued tasks start right away
+    /// when the limit grows
+    pub fn set_max_concurrent_tasks(&self, max_concurrent: usize) {
+        self.lock_queue().set_max_concurrent(max_concurrent);
+        self.schedule();
+    }
+
     /// Get a task handle by ID
     pub async fn get_task(&self, task_id: &str) -> Option<TaskHandle> {
         let tasks = self.tasks.read().await;
@@ -175,14 +609,55 @@ impl CoreRuntime {
         tasks.values().cloned().collect()
     }
 
-    /// Cancel a task
+    /// Cancel a task. The task's token stops its LLM stream, running tools and
+    /// spawned processes, so the task settles as Cancelled almost immediately.
     pub async fn cancel_task(&self, task_id: &str) -> Result<(), String> {
         let handle = self
             .get_task(task_id)
             .await
             .ok_or_else(|| format!("Task '{}' not found", task_id))?;
+        handle.cancel();
+
+        // A queued task never started; take it out of the queue
+        let queued = self.lock_queue().remove(task_id);
+        if let Some(run) = queued {
+            self.complete_task(
+                &run.task,
+                RuntimeTaskState::Cancelled,
+                None,
+                &self.event_sender,
+            )
+            .await;
+            self.tasks.write().await.remove(task_id);
+            self.schedule();
+            return Ok(());
+        }
+
+        // Nothing runs while a task waits for the user; drop what it waits on instead
+        if *handle.state.read().await == RuntimeTaskState::WaitingForUser {
+            self.storage
+                .chat_history
+                .delete_pending_approvals_for_task(task_id)
+                .await?;
+            self.storage.chat_history.take_budget_pause(task_id).await?;
+            self.storage.chat_history.take_stream_state(task_id).await?;
+            let task = RuntimeTask {
+                id: handle.task_id.clone(),
+                session_id: handle.session_id.clone(),
+                agent_id: None,
+                state: RuntimeTaskState::Cancelled,
+                created_at: chrono::Utc::now().timestamp(),
+                started_at: None,
+                completed_at: None,
+                error_message: None,
+                metadata: HashMap::new(),
+            };
+            self.complete_task(&task, RuntimeTaskState::Cancelled, None, &self.event_sender)
+                .await;
+            self.tasks.write().await.remove(task_id);
+            return Ok(());
+        }
 
-        handle.cancel()?;
         Ok(())
     }
 
@@ -196,13 +671,44 @@ impl CoreRuntime {
         self.tool_registry.clone()
     }
 
+    /// Stop starting tasks and let running agent loops reach their next
+    /// iteration boundary, waiting up to `timeout`. Suspended runs continue
+    /// after a restart. Returns the tasks still running at the timeout.
+    pub async fn shutdown(&self, timeout: Duration) -> Vec<RuntimeTaskId> {
+        self.draining.cancel();
+        let queued = self.lock_queue().list().len();
+        if queued > 0 {
+            log::warn!("Shutting down with {} queued tasks not started", queued);
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut running = Vec::new();
+            for handle in self.list_active_tasks().await {
+                if *handle.state.read().await == RuntimeTaskState::Running {
+                    running.push(handle.task_id);
+                }
+            }
+            if running.is_empty() || Instant::now() >= deadline {
+                return running;
+            }
+            tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Subscribe to runtime events as they are logged
+    pub fn subscribe_events(&self) -> broadcast::Receiver<LoggedEvent> {
+        self.logged_events.subscribe()
+    }
+
     /// Main task execution loop
     async fn run_task(
         &self,
         mut task: RuntimeTask,
         input: TaskInput,
         task_state: Arc<RwLock<RuntimeTaskState>>,
-        mut action_rx: mpsc::UnboundedReceiver<TaskAction>,
+        _action_rx: mpsc::UnboundedReceiver<TaskAction>,
+        cancel_token: CancellationToken,
         event_sender: EventSender,
     ) {
         // Update task state to running
@@ -218,9 +724,63 @@ impl CoreRuntime {
             previous_state: RuntimeTaskState::Pending,
         });
 
+        let workspace_root = workspace_root(&input);
+        let start_payload = HookPayload {
+            session_id: task.session_id.clone(),
+            task_id: task.id.clone(),
+            workspace_root: Some(workspace_root.clone()),
+            ..HookPayload::default()
+        };
+        if let Err(e) = self
+            .hooks
+            .run(HookEvent::SessionStart, &start_payload, &cancel_token)
+            .await
+        {
+            self.complete_task(&task, RuntimeTaskState::Failed, Some(e), &event_sender)
+                .await;
+            return;
+        }
+
+        // An isolated task works in a worktree on its own branch
+        let mut worktree_path = input
+            .workspace
+            .as_ref()
+            .and_then(|w| w.worktree_path.clone());
+        if input.isolate && worktree_path.is_none() {
+            match self.create_task_worktree(&task, &workspace_root).await {
+                Ok(path) => worktree_path = Some(path),
+                Err(e) => {
+                    self.complete_task(&task, RuntimeTaskState::Failed, Some(e), &event_sender)
+                        .await;
+                    return;
+                }
+            }
+        }
+
         // Create agent loop
-        let agent_loop =
-            AgentLoopFactory::create_standard(self.tool_registry.clone(), event_sender.clone());
+        let settings = input.settings.clone().unwrap_or_default();
+        if settings.plan_mode == Some(true) {
+            let _ = self
+                .session_manager
+                .update_session_status(&task.session_id, SessionStatus::Planning, None)
+                .await;
+        }
+        let system_prompt = self
+            .memory_prompt(&task.session_id, &input.initial_message)
+            .await;
+        let agent_loop = match self.create_agent_loop(
+            &settings,
+            &workspace_root,
+            system_prompt,
+            &event_sender,
+        ) {
+            Ok(agent_loop) => agent_loop,
+            Err(e) => {
+                self.complete_task(&task, RuntimeTaskState::Failed, Some(e), &event_sender)
+                    .await;
+                return;
+            }
+        };
 
         // Add initial user message
         let initial_message = Message {
@@ -233,6 +793,7 @@ impl CoreRuntime {
             created_at: now,
             tool_call_id: None,
             parent_id: None,
+            pinned: false,
         };
 
         if let Err(e) = self
@@ -261,100 +822,249 @@ impl CoreRuntime {
         });
 
         // Build agent loop context
-        let workspace_root = input
-            .workspace
-            .as_ref()
-            .map(|w| w.root_path.clone())
-            .unwrap_or_else(|| {
-                std::env::current_dir()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_else(|_| "/".to_string())
-            });
-
-        let ctx = AgentLoopContext {
+        let mut ctx = AgentLoopContext {
             session_id: task.session_id.clone(),
             task_id: task.id.clone(),
             workspace_root,
-            worktree_path: input
-                .workspace
-                .as_ref()
-                .and_then(|w| w.worktree_path.clone()),
-            settings: input.settings.unwrap_or_default(),
+            worktree_path,
+            settings,
             messages: self
-                .session_manager
-                .get_messages(&task.session_id, None, None)
+                .load_context_messages(&task.session_id)
                 .await
                 .unwrap_or_default(),
+            cancel_token,
+            compactions: vec![],
+            usage: BudgetUsage::default(),
+            usage_clock: Instant::now(),
         };
 
         // Run agent loop
-        match agent_loop.run_iteration(&ctx).await {
-            Ok(AgentLoopResult::Completed { message }) => {
-                // Add assistant message
-                let assistant_message = Message {
-                    id: format!("msg_{}", uuid::Uuid::new_v4()),
+        let history = message_ids(&ctx.messages);
+        let agent_loop = agent_loop.with_stream_recorder(self.stream_recorder(&task, &history));
+        let result = agent_loop.run(&mut ctx).await;
+
+        self.finish_run(&task, ctx, history, result, &task_state, &event_sender)
+            .await;
+    }
+
+    /// Persist messages produced by an agent run and settle the task according to its result
+    async fn finish_run(
+        &self,
+        task: &RuntimeTask,
+        mut ctx: AgentLoopContext,
+        history: HashSet<String>,
+        result: Result<AgentLoopResult, String>,
+        task_state: &Arc<RwLock<RuntimeTaskState>>,
+        event_sender: &EventSender,
+    ) {
+        ctx.record_running_time();
+
+        // Persist messages produced by the loop, including partial progress.
+        // Compaction summaries are stored as compactions, not as messages.
+        let summaries: HashSet<String> = ctx.compactions.iter().map(|c| c.id.clone()).collect();
+        let produced = ctx
+            .messages
+            .drain(..)
+            .filter(|message| !history.contains(&message.id) && !summaries.contains(&message.id));
+        for message in produced {
+            if let Err(e) = self.session_manager.add_message(message.clone()).await {
+                log::error!("Failed to persist message {}: {}", message.id, e);
+                continue;
+            }
+            let _ = event_sender.send(RuntimeEvent::MessageCreated {
+                session_id: task.session_id.clone(),
+                message,
+            });
+        }
+        for compaction in &ctx.compactions {
+            if let Err(e) = self
+                .storage
+                .chat_history
+                .create_compaction(compaction)
+                .await
+            {
+                log::error!("Failed to persist compaction {}: {}", compaction.id, e);
+            }
+        }
+        // The run's output is stored, so a restart has nothing to restore
+        if let Err(e) = self.storage.chat_history.take_stream_state(&task.id).await {
+            log::warn!("Failed to clear stream state of task {}: {}", task.id, e);
+        }
+
+        let completed = matches!(
+            result,
+            Ok(AgentLoopResult::Completed { .. }
+                | AgentLoopResult::PlanSubmitted { .. }
+                | AgentLoopResult::MaxIterationsReached)
+        );
+        self.refresh_session_summary(task, &ctx.settings, completed, event_sender);
+
+        match result {
+            Ok(AgentLoopResult::Completed { .. }) => {
+                self.complete_task(task, RuntimeTaskState::Completed, None, event_sender)
+                    .await;
+            }
+            Ok(AgentLoopResult::WaitingForApproval { request, remaining }) => {
+                let approval = PendingApproval {
+                    tool_call_id: request.tool_call_id.clone(),
+                    task_id: task.id.clone(),
                     session_id: task.session_id.clone(),
-                    role: MessageRole::Assistant,
-                    content: MessageContent::Text { text: message },
+                    agent_id: task.agent_id.clone(),
+                    tool_call: to_tool_call(&request),
+                    remaining_calls: remaining.iter().map(to_tool_call).collect(),
+                    settings: ctx.settings,
+                    workspace_root: ctx.workspace_root,
+                    worktree_path: ctx.worktree_path,
+                    usage: ctx.usage,
                     created_at: chrono::Utc::now().timestamp(),
-                    tool_call_id: None,
-                    parent_id: None,
                 };
 
-                let _ = self
-                    .session_manager
-                    .add_message(assistant_message.clone())
+                if let Err(e) = self
+                    .storage
+                    .chat_history
+                    .create_pending_approval(&approval)
+                    .await
+                {
+                    self.complete_task(
+                        task,
+                        RuntimeTaskState::Failed,
+                        Some(format!("Failed to persist pending approval: {}", e)),
+                        event_sender,
+                    )
                     .await;
-                let _ = event_sender.send(RuntimeEvent::MessageCreated {
+                } else {
+                    // The task stays registered until approve_tool_call or deny_tool_call resumes it
+                    let event = RuntimeEvent::ToolCallRequested {
+                        task_id: task.id.clone(),
+                        request,
+                    };
+                    self.wait_for_user(task, task_state, event, event_sender)
+                        .await;
+                    return;
+                }
+            }
+            Ok(AgentLoopResult::BudgetExceeded {
+                exceeded,
+                remaining,
+            }) => {
+                let message = format!(
+                    "Budget exceeded: {}",
+                    budget::describe(
+                        &exceeded,
+                        &ctx.settings.budget.clone().unwrap_or_default(),
+                        &ctx.usage
+                    )
+                );
+                let pause = BudgetPause {
+                    task_id: task.id.clone(),
                     session_id: task.session_id.clone(),
-                    message: assistant_message,
-                });
+                    agent_id: task.agent_id.clone(),
+                    exceeded,
+                    usage: ctx.usage,
+                    remaining_calls: remaining.iter().map(to_tool_call).collect(),
+                    settings: ctx.settings,
+                    workspace_root: ctx.workspace_root,
+                    worktree_path: ctx.worktree_path,
+                    created_at: chrono::Utc::now().timestamp(),
+                };
 
-                self.complete_task(&task, RuntimeTaskState::Completed, None, &event_sender)
+                if let Err(e) = self.storage.chat_history.create_budget_pause(&pause).await {
+                    self.complete_task(
+                        task,
+                        RuntimeTaskState::Failed,
+                        Some(format!("Failed to persist budget pause: {}", e)),
+                        event_sender,
+                    )
                     .await;
+                } else {
+                    // The task stays registered until continue_task or cancel_task settles it
+                    let event = RuntimeEvent::BudgetExceeded {
+                        task_id: task.id.clone(),
+                        session_id: task.session_id.clone(),
+                        exceeded: pause.exceeded,
+                        usage: pause.usage,
+                        message,
+                    };
+                    self.wait_for_user(task, task_state, event, event_sender)
+                        .await;
+                    return;
+                }
             }
-            Ok(AgentLoopResult::WaitingForApproval { request }) => {
-                *task_state.write().await = RuntimeTaskState::WaitingForUser;
-                let _ = event_sender.send(RuntimeEvent::ToolCallRequested {
+            Ok(AgentLoopResult::PlanSubmitted { plan: draft }) => {
+                let plan = Plan {
+                    id: format!("plan_{}", uuid::Uuid::new_v4()),
+                    session_id: task.session_id.clone(),
                     task_id: task.id.clone(),
-                    request,
-                });
-                // Task will wait for user action via action_rx
+                    agent_id: task.agent_id.clone(),
+                    summary: draft.summary,
+                    steps: draft.steps,
+                    settings: ctx.settings,
+                    workspace_root: ctx.workspace_root,
+                    worktree_path: ctx.worktree_path,
+                    created_at: chrono::Utc::now().timestamp(),
+                    executed_at: None,
+                };
+
+                if let Err(e) = self.storage.chat_history.create_plan(&plan).await {
+                    self.complete_task(
+                        task,
+                        RuntimeTaskState::Failed,
+                        Some(format!("Failed to persist plan: {}", e)),
+                        event_sender,
+                    )
+                    .await;
+                } else {
+                    self.complete_task(task, RuntimeTaskState::Completed, None, event_sender)
+                        .await;
+                    // The session waits for execute_plan rather than being done
+                    let _ = self
+                        .session_manager
+                        .update_session_status(&task.session_id, SessionStatus::PlanReady, None)
+                        .await;
+                    let _ = event_sender.send(RuntimeEvent::PlanReady {
+                        task_id: task.id.clone(),
+                        session_id: task.session_id.clone(),
+                        plan,
+                    });
+                }
             }
             Ok(AgentLoopResult::Error { message }) => {
-                self.complete_task(
-                    &task,
-                    RuntimeTaskState::Failed,
-                    Some(message),
-                    &event_sender,
-                )
-                .await;
+                self.complete_task(task, RuntimeTaskState::Failed, Some(message), event_sender)
+                    .await;
             }
             Ok(AgentLoopResult::MaxIterationsReached) => {
                 self.complete_task(
-                    &task,
+                    task,
                     RuntimeTaskState::Completed,
                     Some("Maximum iterations reached".to_string()),
-                    &event_sender,
+                    event_sender,
                 )
                 .await;
             }
             Ok(AgentLoopResult::Cancelled) => {
-                self.complete_task(&task, RuntimeTaskState::Cancelled, None, &event_sender)
+                self.complete_task(task, RuntimeTaskState::Cancelled, None, event_sender)
+                    .await;
+            }
+            Ok(AgentLoopResult::Suspended) => {
+                // Stored like a run cut short by a crash, so the task waits
+                // for the user to continue it after the restart
+                self.stream_recorder(task, &history)
+                    .save(&ctx, "", &[])
                     .await;
+                log::info!("Suspended task {} for shutdown", task.id);
             }
             Ok(AgentLoopResult::WaitingForToolResult { .. }) => {
                 // This shouldn't happen in our simplified implementation
                 self.complete_task(
-                    &task,
+                    task,
                     RuntimeTaskState::Failed,
                     Some("Unexpected tool result wait".to_string()),
-                    &event_sender,
+                    event_sender,
                 )
                 .await;
             }
             Err(e) => {
-                self.complete_task(&task, RuntimeTaskState::Failed, Some(e), &event_sender)
+                self.complete_task(task, RuntimeTaskState::Failed, Some(e), event_sender)
                     .await;
             }
         }
@@ -364,98 +1074,1706 @@ impl CoreRuntime {
         tasks.remove(&task.id);
     }
 
-    /// Complete a task and emit events
-    async fn complete_task(
+    /// Title and summarize the session in the background once its first
+    /// exchange is stored, then again whenever one of its tasks completes
+    fn refresh_session_summary(
         &self,
         task: &RuntimeTask,
-        final_state: RuntimeTaskState,
-        error: Option<String>,
+        settings: &TaskSettings,
+        completed: bool,
         event_sender: &EventSender,
     ) {
-        let previous_state = match self.tasks.read().await.get(&task.id) {
-            Some(handle) => *handle.state.read().await,
-            None => RuntimeTaskState::Running,
+        let runtime = self.clone();
+        let session_id = task.session_id.clone();
+        let model = ModelRegistry::resolve_phase_model(settings, ModelPhase::Title);
+        let event_sender = event_sender.clone();
+        tokio::spawn(async move {
+            if let Err(e) = runtime
+                .summarize_session(&session_id, model, completed, &event_sender)
+                .await
+            {
+                log::warn!("Failed to summarize session {}: {}", session_id, e);
+            }
+        });
+    }
+
+    async fn summarize_session(
+        &self,
+        session_id: &str,
+        model: Option<String>,
+        completed: bool,
+        event_sender: &EventSender,
+    ) -> Result<(), String> {
+        let Some(session) = self.session_manager.get_session(session_id).await? else {
+            return Ok(());
         };
+        // After the first exchange only completed tasks refresh the summary
+        if session.summary.is_some() && !completed {
+            return Ok(());
+        }
+        let messages = self.load_context_messages(session_id).await?;
+        if !messages
+            .iter()
+            .any(|message| message.role == MessageRole::Assistant)
+        {
+            return Ok(());
+        }
 
-        // Update session status
-        let session_status = match final_state {
-            RuntimeTaskState::Completed => SessionStatus::Completed,
-            RuntimeTaskState::Failed => SessionStatus::Error,
-            RuntimeTaskState::Cancelled => SessionStatus::Cancelled,
-            _ => SessionStatus::Running,
+        let generated = session_summary::generate(
+            self.llm.as_ref(),
+            model,
+            &messages,
+            session.summary.as_deref(),
+        )
+        .await?;
+
+        let untitled = session
+            .title
+            .as_deref()
+            .is_none_or(|title| title == DEFAULT_SESSION_TITLE);
+        let title = if untitled {
+            self.session_manager
+                .update_session_title(session_id, &generated.title)
+                .await?;
+            Some(generated.title)
+        } else {
+            session.title
         };
+        self.session_manager
+            .update_session_summary(session_id, &generated.summary)
+            .await?;
 
+        let _ = event_sender.send(RuntimeEvent::SessionUpdated {
+            session_id: session_id.to_string(),
+            title,
+            summary: generated.summary,
+        });
+        Ok(())
+    }
+
+    /// Move a task to WaitingForUser and announce what it waits on
+    async fn wait_for_user(
+        &self,
+        task: &RuntimeTask,
+        task_state: &Arc<RwLock<RuntimeTaskState>>,
+        event: RuntimeEvent,
+        event_sender: &EventSender,
+    ) {
+        let previous_state = std::mem::replace(
+            &mut *task_state.write().await,
+            RuntimeTaskState::WaitingForUser,
+        );
         let _ = self
             .session_manager
-            .update_session_status(&task.session_id, session_status, None)
+            .update_session_status(&task.session_id, SessionStatus::WaitingForAction, None)
             .await;
 
-        // Emit completion event
         let _ = event_sender.send(RuntimeEvent::TaskStateChanged {
             task_id: task.id.clone(),
-            state: final_state,
+            state: RuntimeTaskState::WaitingForUser,
             previous_state,
         });
-
-        let _ = event_sender.send(RuntimeEvent::TaskCompleted {
-            task_id: task.id.clone(),
-            session_id: task.session_id.clone(),
-        });
-
-        if let Some(err) = error {
-            let _ = event_sender.send(RuntimeEvent::Error {
-                task_id: Some(task.id.clone()),
-                session_id: Some(task.session_id.clone()),
-                message: err,
-            });
-        }
+        let _ = event_sender.send(event);
     }
 
-    /// Find existing session for a task input
-    async fn find_session_for_task(&self, input: &TaskInput) -> Option<SessionId> {
-        // If session_id is explicitly provided in input, use that
-        // Otherwise, return None to create a new session
-        None
+    /// Approve a pending tool call and resume its task
+    pub async fn approve_tool_call(&self, tool_call_id: &str) -> Result<TaskHandle, String> {
+        self.resolve_approval(tool_call_id, ApprovalDecision::Approve)
+            .await
     }
 
-    /// Clone runtime state for task execution
-    fn clone_for_task(&self) -> RuntimeTaskContext {
-        RuntimeTaskContext {
-            session_manager: self.session_manager.clone(),
-            tool_registry: self.tool_registry.clone(),
-        }
+    /// Deny a pending tool call and resume its task; the model sees the denial
+    /// as the tool result
+    pub async fn deny_tool_call(
+        &self,
+        tool_call_id: &str,
+        reason: Option<String>,
+    ) -> Result<TaskHandle, String> {
+        self.resolve_approval(tool_call_id, ApprovalDecision::Deny { reason })
+            .await
     }
-}
 
-/// Context passed to task execution (lighter weight than full runtime)
-#[derive(Clone)]
-struct RuntimeTaskContext {
-    session_manager: Arc<SessionManager>,
-    tool_registry: Arc<ToolRegistry>,
-}
+    /// Type into the terminal of a running `execute_shell` call
+    pub fn write_tool_input(&self, tool_call_id: &str, input: &str) -> Result<(), String> {
+        self.shell.write_input(tool_call_id, input)
+    }
 
-impl RuntimeTaskContext {
-    async fn run_task(
+    /// List tool calls waiting for approval, optionally for a single session
+    pub async fn list_pending_approvals(
         &self,
-        _task: RuntimeTask,
-        _input: TaskInput,
-        _task_state: Arc<RwLock<RuntimeTaskState>>,
-        _action_rx: mpsc::UnboundedReceiver<TaskAction>,
-        _event_sender: EventSender,
-    ) {
-        // This is called from the spawned task - actual implementation is in CoreRuntime
-        // This method exists for type compatibility
+        session_id: Option<&str>,
+    ) -> Result<Vec<PendingApproval>, String> {
+        self.storage
+            .chat_history
+            .list_pending_approvals(session_id)
+            .await
     }
-}
+
+    /// Let a task that exceeded its budget or was interrupted by a restart
+    /// continue. Every exceeded limit is extended by its original allowance.
+    pub async fn continue_task(&self, task_id: &str) -> Result<TaskHandle, String> {
+        let Some(mut pause) = self.storage.chat_history.take_budget_pause(task_id).await? else {
+            return self.continue_interrupted_task(task_id).await;
+        };
+
+        pause.settings.budget = pause
+            .settings
+            .budget
+            .as_ref()
+            .map(|task_budget| budget::extend(task_budget, &pause.usage, &pause.exceeded));
+
+        Ok(self
+            .spawn_resume(ResumePoint {
+                task_id: pause.task_id,
+                session_id: pause.session_id,
+                agent_id: pause.agent_id,
+                created_at: pause.created_at,
+                settings: pause.settings,
+                workspace_root: pause.workspace_root,
+                worktree_path: pause.worktree_path,
+                usage: pause.usage,
+                decision: None,
+                remaining_calls: pause.remaining_calls,
+            })
+            .await)
+    }
+
+    /// Resume a run cut short by a restart, running the tool calls it had
+    /// received before continuing the conversation
+    async fn continue_interrupted_task(&self, task_id: &str) -> Result<TaskHandle, String> {
+        let not_paused = || format!("Task '{}' is not paused or interrupted", task_id);
+        // A running task also has a stream state, which must stay in place
+        let handle = self.get_task(task_id).await.ok_or_else(not_paused)?;
+        if *handle.state.read().await != RuntimeTaskState::WaitingForUser {
+            return Err(not_paused());
+        }
+        let state = self
+            .storage
+            .chat_history
+            .take_stream_state(task_id)
+            .await?
+            .ok_or_else(not_paused)?;
+
+        Ok(self
+            .spawn_resume(ResumePoint {
+                task_id: state.task_id,
+                session_id: state.session_id,
+                agent_id: state.agent_id,
+                created_at: state.created_at,
+                settings: state.settings,
+                workspace_root: state.workspace_root,
+                worktree_path: state.worktree_path,
+                usage: state.usage,
+                decision: None,
+                remaining_calls: state.tool_calls,
+            })
+            .await)
+    }
+
+    /// List tasks interrupted by a restart, optionally for a single session
+    pub async fn list_interrupted_tasks(
+        &self,
+        session_id: Option<&str>,
+    ) -> Result<Vec<StreamState>, String> {
+        let states = self
+            .storage
+            .chat_history
+            .list_stream_states(session_id)
+            .await?;
+        Ok(states
+            .into_iter()
+            .filter(|state| state.interrupted_at.is_some())
+            .collect())
+    }
+
+    /// List tasks paused by their budget, optionally for a single session
+    pub async fn list_budget_pauses(
+        &self,
+        session_id: Option<&str>,
+    ) -> Result<Vec<BudgetPause>, String> {
+        self.storage
+            .chat_history
+            .list_budget_pauses(session_id)
+            .await
+    }
+
+    /// List file checkpoints of a session, oldest first
+    pub async fn list_checkpoints(&self, session_id: &str) -> Result<Vec<Checkpoint>, String> {
+        self.checkpoints.list(session_id).await
+    }
+
+    /// Undo the file changes of a session's tool calls back to a checkpoint.
+    /// Refused while a task of the session is running, since it could keep editing.
+    pub async fn rollback_to_checkpoint(
+        &self,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<CheckpointRollback, String> {
+        for handle in self.list_active_tasks().await {
+            if handle.session_id == session_id
+                && *handle.state.read().await == RuntimeTaskState::Running
+            {
+                return Err(format!(
+                    "Cannot roll back while task '{}' is running",
+                    handle.task_id
+                ));
+            }
+        }
+
+        self.checkpoints.rollback(session_id, checkpoint_id).await
+    }
+
+    /// List the worktrees of a session's isolated tasks that were not merged
+    /// or discarded yet
+    pub async fn list_task_worktrees(&self, session_id: &str) -> Result<Vec<TaskWorktree>, String> {
+        self.storage
+            .chat_history
+            .list_task_worktrees(session_id)
+            .await
+    }
+
+    /// Merge the branch of a finished isolated task into the main branch of
+    /// its repository. The worktree is removed after a clean merge and kept
+    /// when the merge stops on conflicts.
+    pub async fn merge_task_worktree(
+        &self,
+        task_id: &str,
+        commit_message: Option<String>,
+    ) -> Result<MergeResult, String> {
+        let record = self.finished_task_worktree(task_id).await?;
+        let (project_path, path, branch) = (
+            record.project_path.clone(),
+            record.path.clone(),
+            record.branch.clone(),
+        );
+        let result = tokio::task::spawn_blocking(move || {
+            worktree::merge_task_worktree(&project_path, &path, &branch, commit_message.as_deref())
+        })
+        .await
+        .map_err(|e| format!("Failed to merge worktree: {}", e))??;
+
+        if result.success {
+            self.remove_task_worktree(&record).await?;
+        }
+        Ok(result)
+    }
+
+    /// Drop a finished isolated task's worktree and branch with their changes
+    pub async fn discard_task_worktree(&self, task_id: &str) -> Result<(), String> {
+        let record = self.finished_task_worktree(task_id).await?;
+        self.remove_task_worktree(&record).await
+    }
+
+    /// Worktree of an isolated task that no longer runs or waits for the user
+    async fn finished_task_worktree(&self, task_id: &str) -> Result<TaskWorktree, String> {
+        if self.get_task(task_id).await.is_some() {
+            return Err(format!("Task '{}' has not finished yet", task_id));
+        }
+        self.storage
+            .chat_history
+            .get_task_worktree(task_id)
+            .await?
+            .ok_or_else(|| format!("Task '{}' has no worktree", task_id))
+    }
+
+    /// Create the worktree of an isolated task from the workspace's HEAD and
+    /// record it. Returns the worktree's path.
+    async fn create_task_worktree(
+        &self,
+        task: &RuntimeTask,
+        project_path: &str,
+    ) -> Result<String, String> {
+        let (project, task_id) = (project_path.to_string(), task.id.clone());
+        let info = tokio::task::spawn_blocking(move || {
+            worktree::create_task_worktree(&project, &task_id, None)
+        })
+        .await
+        .map_err(|e| format!("Failed to create worktree: {}", e))??;
+
+        let record = TaskWorktree {
+            task_id: task.id.clone(),
+            session_id: task.session_id.clone(),
+            project_path: project_path.to_string(),
+            path: info.path,
+            branch: info.branch,
+            base_commit: info.base_commit,
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        if let Err(e) = self
+            .storage
+            .chat_history
+            .create_task_worktree(&record)
+            .await
+        {
+            let _ = self.remove_task_worktree(&record).await;
+            return Err(format!("Failed to persist task worktree: {}", e));
+        }
+        Ok(record.path)
+    }
+
+    /// Remove a task worktree from disk, then its record
+    async fn remove_task_worktree(&self, record: &TaskWorktree) -> Result<(), String> {
+        let (project_path, path, branch) = (
+            record.project_path.clone(),
+            record.path.clone(),
+            record.branch.clone(),
+        );
+        tokio::task::spawn_blocking(move || {
+            worktree::remove_task_worktree(&project_path, &path, &branch)
+        })
+        .await
+        .map_err(|e| format!("Failed to remove worktree: {}", e))??;
+
+        self.storage
+            .chat_history
+            .delete_task_worktree(&record.task_id)
+            .await
+    }
+
+    /// Start a task that carries out the session's submitted plan with every
+    /// tool available. The plan becomes the task's first message.
+    pub async fn execute_plan(&self, session_id: &str) -> Result<TaskHandle, String> {
+        let plan = self
+            .storage
+            .chat_history
+            .get_pending_plan(session_id)
+            .await?
+            .ok_or_else(|| format!("Session '{}' has no plan to execute", session_id))?;
+        let now = chrono::Utc::now().timestamp();
+        if !self
+            .storage
+            .chat_history
+            .mark_plan_executed(&plan.id, now)
+            .await?
+        {
+            return Err(format!("Plan '{}' was already executed", plan.id));
+        }
+
+        let settings = TaskSettings {
+            plan_mode: None,
+            ..plan.settings.clone()
+        };
+        self.start_task(TaskInput {
+            session_id: plan.session_id.clone(),
+            agent_id: plan.agent_id.clone(),
+            project_id: None,
+            initial_message: plan::execution_message(&plan),
+            settings: Some(settings),
+            workspace: Some(WorkspaceInfo {
+                root_path: plan.workspace_root,
+                worktree_path: plan.worktree_path,
+                repository_url: None,
+                branch: None,
+            }),
+            priority: 0,
+            isolate: false,
+            read_only_tools: false,
+        })
+        .await
+    }
+
+    /// List plans submitted in a session, oldest first
+    pub async fn list_plans(&self, session_id: &str) -> Result<Vec<Plan>, String> {
+        self.storage.chat_history.list_plans(session_id).await
+    }
+
+    /// Todo list the agent keeps for a session
+    pub async fn get_todos(&self, session_id: &str) -> Result<TodoList, String> {
+        self.todos.get(session_id).await
+    }
+
+    /// Create an API key for the HTTP server, optionally for a user and with
+    /// every scope unless limited; the full key is only returned here
+    pub async fn create_api_key(
+        &self,
+        name: &str,
+        user_id: Option<&str>,
+        scopes: Option<&[ApiKeyScope]>,
+    ) -> Result<CreatedApiKey, String> {
+        let scopes = scopes.map_or_else(ApiKeyScope::all, <[ApiKeyScope]>::to_vec);
+        self.api_keys.create(name, user_id, &scopes).await
+    }
+
+    /// List the HTTP server's API keys, revoked ones included
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>, String> {
+        self.api_keys.list().await
+    }
+
+    /// Revoke an API key. Returns `false` when it does not exist or was
+    /// already revoked.
+    pub async fn revoke_api_key(&self, key_id: &str) -> Result<bool, String> {
+        self.api_keys.revoke(key_id).await
+    }
+
+    /// The API key a client presented, if it is valid and not revoked
+    pub async fn verify_api_key(&self, key: &str) -> Result<Option<ApiKey>, String> {
+        self.api_keys.verify(key).await
+    }
+
+    /// Sign a client in to the HTTP server with an API key
+    pub async fn login(&self, api_key: &str) -> Result<AuthTokens, String> {
+        self.auth.login(api_key).await
+    }
+
+    /// Exchange a refresh token for new tokens, rotating it
+    pub async fn refresh_auth_tokens(&self, refresh_token: &str) -> Result<AuthTokens, String> {
+        self.auth.refresh(refresh_token).await
+    }
+
+    /// Sign a client out, revoking its refresh tokens
+    pub async fn logout(&self, refresh_token: &str) -> Result<(), String> {
+        self.auth.logout(refresh_token).await
+    }
+
+    /// API key an access token was issued for, if it is valid
+    pub fn verify_access_token(&self, token: &str) -> Option<AuthenticatedKey> {
+        self.auth.verify_access_token(token)
+    }
+
+    /// Create a one-time code a companion client pairs with, its key
+    /// belonging to `user_id` or, without one, to the owner
+    pub async fn create_pairing(&self, user_id: Option<&str>) -> Result<Pairing, String> {
+        if let Some(user_id) = user_id {
+            if self.storage.api_keys.get_user(user_id).await?.is_none() {
+                return Err(format!("User not found: {}", user_id));
+            }
+        }
+        Ok(self.pairings.create(user_id))
+    }
+
+    /// Redeem a pairing code for an API key named after the device, and sign
+    /// in with it
+    pub async fn pair_device(&self, code: &str, device_name: &str) -> Result<PairedDevice, String> {
+        let device_name = device_name.trim();
+        if device_name.is_empty() {
+            return Err("Device name must not be empty".to_string());
+        }
+        let user_id = self.pairings.redeem(code)?;
+        let api_key = self
+            .api_keys
+            .create(
+                &format!("Paired device: {}", device_name),
+                user_id.as_deref(),
+                &ApiKeyScope::all(),
+            )
+            .await?;
+        let tokens = self.auth.login(&api_key.key).await?;
+        Ok(PairedDevice { api_key, tokens })
+    }
+
+    /// Create a user of the HTTP server
+    pub async fn create_user(&self, name: &str) -> Result<User, String> {
+        self.api_keys.create_user(name).await
+    }
+
+    /// List the users of the HTTP server
+    pub async fn list_users(&self) -> Result<Vec<User>, String> {
+        self.api_keys.list_users().await
+    }
+
+    /// Delete a user and revoke their API keys. Returns `false` when the user
+    /// does not exist.
+    pub async fn delete_user(&self, user_id: &str) -> Result<bool, String> {
+        self.api_keys.delete_user(user_id).await
+    }
+
+    /// List global memories plus those of `project_id`, oldest first
+    pub async fn list_memories(&self, project_id: Option<&str>) -> Result<Vec<Memory>, String> {
+        self.memory.list(project_id).await
+    }
+
+    /// Add a memory; a `project_id` of `None` makes it global
+    pub async fn create_memory(
+        &self,
+        project_id: Option<String>,
+        kind: MemoryKind,
+        content: &str,
+    ) -> Result<Memory, String> {
+        self.memory.create(project_id, kind, content).await
+    }
+
+    /// Edit the kind or content of a memory
+    pub async fn update_memory(
+        &self,
+        memory_id: &str,
+        updates: MemoryUpdates,
+    ) -> Result<Memory, String> {
+        self.memory.update(memory_id, updates).await
+    }
+
+    /// Forget a memory
+    pub async fn delete_memory(&self, memory_id: &str) -> Result<(), String> {
+        self.memory.delete(memory_id).await
+    }
+
+    /// List projects, optionally of one user, by name
+    pub async fn list_projects(&self, user_id: Option<&str>) -> Result<Vec<Project>, String> {
+        self.projects.list(user_id).await
+    }
+
+    /// Get a project by ID
+    pub async fn get_project(&self, project_id: &str) -> Result<Option<Project>, String> {
+        self.projects.get(project_id).await
+    }
+
+    /// Create a project; its workspace path must be an existing directory
+    pub async fn create_project(
+        &self,
+        name: &str,
+        workspace_path: &str,
+        default_settings: Option<TaskSettings>,
+        user_id: Option<String>,
+    ) -> Result<Project, String> {
+        self.projects
+            .create(name, workspace_path, default_settings, user_id)
+            .await
+    }
+
+    /// Edit the name, workspace or default settings of a project
+    pub async fn update_project(
+        &self,
+        project_id: &str,
+        updates: ProjectUpdates,
+    ) -> Result<Project, String> {
+        self.projects.update(project_id, updates).await
+    }
+
+    /// Delete a project; its sessions are kept
+    pub async fn delete_project(&self, project_id: &str) -> Result<(), String> {
+        self.projects.delete(project_id).await
+    }
+
+    /// Fill in the workspace and settings a task leaves unset from its project
+    async fn with_project_defaults(&self, mut input: TaskInput) -> Result<TaskInput, String> {
+        let Some(project_id) = input.project_id.as_deref() else {
+            return Ok(input);
+        };
+        let Some(project) = self.projects.get(project_id).await? else {
+            return Ok(input);
+        };
+        if input.workspace.is_none() {
+            input.workspace = Some(WorkspaceInfo {
+                root_path: project.workspace_path,
+                worktree_path: None,
+                repository_url: None,
+                branch: None,
+            });
+        }
+        if input.settings.is_none() {
+            input.settings = project.default_settings;
+        }
+        Ok(input)
+    }
+
+    /// List the hooks of a project, or those for every project when `None`
+    pub async fn list_hooks(&self, project_id: Option<&str>) -> Result<Vec<Hook>, String> {
+        self.hooks.list(project_id).await
+    }
+
+    /// Replace the hooks of a project, or those for every project when `None`
+    pub async fn set_hooks(
+        &self,
+        project_id: Option<&str>,
+        hooks: Vec<Hook>,
+    ) -> Result<(), String> {
+        self.hooks.set(project_id, hooks).await
+    }
+
+    /// List the webhooks of a project
+    pub async fn list_webhooks(&self, project_id: &str) -> Result<Vec<Webhook>, String> {
+        self.webhooks.list(project_id).await
+    }
+
+    /// Replace the webhooks of a project, returning them with their IDs
+    pub async fn set_webhooks(
+        &self,
+        project_id: &str,
+        webhooks: Vec<Webhook>,
+    ) -> Result<Vec<Webhook>, String> {
+        self.webhooks.set(project_id, webhooks).await
+    }
+
+    /// List a project's webhook deliveries, newest first
+    pub async fn list_webhook_deliveries(
+        &self,
+        project_id: &str,
+        webhook_id: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<WebhookDelivery>, String> {
+        self.webhooks
+            .list_deliveries(project_id, webhook_id, limit)
+            .await
+    }
+
+    /// Retry policy applied to a tool's failed calls
+    pub async fn tool_retry_policy(&self, tool_name: &str) -> ToolRetryPolicy {
+        self.tool_registry.retry_policy(tool_name).await
+    }
+
+    /// Override and persist the retry policy of a tool; `None` restores the
+    /// default
+    pub async fn set_tool_retry_policy(
+        &self,
+        tool_name: &str,
+        policy: Option<ToolRetryPolicy>,
+    ) -> Result<(), String> {
+        if policy
+            .as_ref()
+            .is_some_and(|policy| policy.max_attempts == 0)
+        {
+            return Err("maxAttempts must be at least 1".to_string());
+        }
+
+        let mut policies: HashMap<String, ToolRetryPolicy> = self
+            .storage
+            .settings
+            .get_setting_or_default(TOOL_RETRY_POLICIES_KEY, HashMap::new())
+            .await?;
+        match &policy {
+            Some(policy) => policies.insert(tool_name.to_string(), policy.clone()),
+            None => policies.remove(tool_name),
+        };
+        let value = serde_json::to_value(&policies)
+            .map_err(|e| format!("Failed to serialize retry policies: {}", e))?;
+        self.storage
+            .settings
+            .set_setting(TOOL_RETRY_POLICIES_KEY, &value)
+            .await?;
+
+        self.tool_registry.set_retry_policy(tool_name, policy).await;
+        Ok(())
+    }
+
+    /// Counts and timings of tracked tasks, optionally of one session, with
+    /// the current load of the task queue
+    pub async fn get_runtime_stats(&self, session_id: Option<&str>) -> RuntimeStats {
+        let active_tasks = self.tasks.read().await.len();
+        let (queued_tasks, max_concurrent_tasks) = {
+            let queue = self.lock_queue();
+            (queue.list().len(), queue.max_concurrent())
+        };
+        RuntimeStats {
+            active_tasks: active_tasks.saturating_sub(queued_tasks),
+            queued_tasks,
+            max_concurrent_tasks,
+            tasks: self.metrics.tasks(session_id),
+        }
+    }
+
+    /// Latest LLM, tool and task errors, newest first
+    pub fn recent_errors(&self, limit: usize) -> Vec<RecentError> {
+        self.metrics.recent_errors(limit)
+    }
+
+    /// A session's logged runtime events in order, optionally after a sequence
+    pub async fn list_session_events(
+        &self,
+        session_id: &str,
+        after_sequence: Option<i64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<RuntimeEventRecord>, String> {
+        self.storage
+            .chat_history
+            .list_runtime_events(session_id, after_sequence, limit)
+            .await
+    }
+
+    /// Rebuild a session's messages, tool results and task states from its
+    /// event log alone
+    pub async fn rebuild_session_state(&self, session_id: &str) -> Result<RebuiltSession, String> {
+        let records = self.list_session_events(session_id, None, None).await?;
+        event_log::rebuild_session(session_id, &records)
+    }
+
+    /// Settings of the `web_search` tool
+    pub async fn web_search_config(&self) -> Result<WebSearchConfig, String> {
+        self.web_search.config().await
+    }
+
+    /// Choose the `web_search` backend and its defaults
+    pub async fn set_web_search_config(&self, config: WebSearchConfig) -> Result<(), String> {
+        self.web_search.set_config(&config).await
+    }
+
+    /// List the databases of a project, or those for every project when `None`
+    pub async fn list_databases(
+        &self,
+        project_id: Option<&str>,
+    ) -> Result<Vec<DatabaseConnection>, String> {
+        self.databases.list(project_id).await
+    }
+
+    /// Replace the databases of a project, or those for every project when `None`
+    pub async fn set_databases(
+        &self,
+        project_id: Option<&str>,
+        connections: Vec<DatabaseConnection>,
+    ) -> Result<(), String> {
+        self.databases.set(project_id, connections).await
+    }
+
+    /// `http_request` configuration of a project, or the one for every
+    /// project when `None`
+    pub async fn http_request_config(
+        &self,
+        project_id: Option<&str>,
+    ) -> Result<HttpRequestConfig, String> {
+        self.http_requests.config(project_id).await
+    }
+
+    /// Replace the `http_request` configuration of a project, or the one for
+    /// every project when `None`
+    pub async fn set_http_request_config(
+        &self,
+        project_id: Option<&str>,
+        config: HttpRequestConfig,
+    ) -> Result<(), String> {
+        self.http_requests.set_config(project_id, &config).await
+    }
+
+    /// Sandbox policy of a project, or the one for every project when `None`
+    pub async fn sandbox_policy(
+        &self,
+        project_id: Option<&str>,
+    ) -> Result<Option<SandboxPolicy>, String> {
+        self.sandbox.policy(project_id).await
+    }
+
+    /// Set or remove the sandbox policy of a project, or the one for every
+    /// project when `None`
+    pub async fn set_sandbox_policy(
+        &self,
+        project_id: Option<&str>,
+        policy: Option<SandboxPolicy>,
+    ) -> Result<(), String> {
+        self.sandbox.set_policy(project_id, policy).await
+    }
+
+    /// Shell policy of a project, or the one for every project when `None`
+    pub async fn shell_policy(
+        &self,
+        project_id: Option<&str>,
+    ) -> Result<Option<ShellPolicy>, String> {
+        self.shell_policy.policy(project_id).await
+    }
+
+    /// Set or remove the shell policy of a project, or the one for every
+    /// project when `None`
+    pub async fn set_shell_policy(
+        &self,
+        project_id: Option<&str>,
+        policy: Option<ShellPolicy>,
+    ) -> Result<(), String> {
+        self.shell_policy.set_policy(project_id, policy).await
+    }
+
+    /// The session retention policy
+    pub async fn retention_policy(&self) -> Result<RetentionPolicy, String> {
+        self.storage
+            .settings
+            .get_setting_or_default(retention::SETTINGS_KEY, RetentionPolicy::default())
+            .await
+    }
+
+    /// Replace the session retention policy; it applies from the next
+    /// maintenance run
+    pub async fn set_retention_policy(&self, policy: RetentionPolicy) -> Result<(), String> {
+        policy.validate()?;
+        let value = serde_json::to_value(&policy)
+            .map_err(|e| format!("Failed to serialize retention policy: {}", e))?;
+        self.storage
+            .settings
+            .set_setting(retention::SETTINGS_KEY, &value)
+            .await
+    }
+
+    /// Archive and delete idle sessions as the retention policy says.
+    /// Sessions with tasks in the runtime are skipped.
+    pub async fn apply_retention_policy(&self) -> Result<RetentionReport, String> {
+        let policy = self.retention_policy().await?;
+        let now = chrono::Utc::now().timestamp();
+        let Some(cutoff) = policy.idle_cutoff(now) else {
+            return Ok(RetentionReport::default());
+        };
+
+        let sessions = self
+            .storage
+            .chat_history
+            .list_sessions_idle_since(cutoff)
+            .await?;
+        let busy: HashSet<SessionId> = self
+            .tasks
+            .read()
+            .await
+            .values()
+            .map(|handle| handle.session_id.clone())
+            .collect();
+        let report = retention::plan(&policy, &sessions, &busy, now);
+
+        for session_id in &report.archived {
+            self.storage
+                .chat_history
+                .set_session_archived(session_id, Some(now))
+                .await?;
+            self.session_manager.deactivate_session(session_id).await?;
+        }
+        for session_id in &report.deleted {
+            self.session_manager.delete_session(session_id).await?;
+        }
+
+        if !report.archived.is_empty() || !report.deleted.is_empty() {
+            log::info!(
+                "Retention policy archived {} and deleted {} sessions",
+                report.archived.len(),
+                report.deleted.len()
+            );
+        }
+        Ok(report)
+    }
+
+    /// Apply the retention policy now and then every `interval`
+    pub fn spawn_maintenance(&self, interval: Duration) -> JoinHandle<()> {
+        let runtime = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = runtime.apply_retention_policy().await {
+                    log::warn!("Session retention failed: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Archive a session, hiding it from default session lists
+    pub async fn archive_session(&self, session_id: &str) -> Result<(), String> {
+        let busy = self
+            .tasks
+            .read()
+            .await
+            .values()
+            .any(|handle| handle.session_id == session_id);
+        if busy {
+            return Err(format!("Session '{}' has running tasks", session_id));
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        if !self
+            .storage
+            .chat_history
+            .set_session_archived(session_id, Some(now))
+            .await?
+        {
+            return Err(format!("Session '{}' not found", session_id));
+        }
+        self.session_manager.deactivate_session(session_id).await
+    }
+
+    /// Restore an archived session to the default session lists
+    pub async fn restore_session(&self, session_id: &str) -> Result<(), String> {
+        if !self
+            .storage
+            .chat_history
+            .set_session_archived(session_id, None)
+            .await?
+        {
+            return Err(format!("Session '{}' not found", session_id));
+        }
+        Ok(())
+    }
+
+    /// Search session titles and message text, optionally in one project or
+    /// of one user, best matches first
+    pub async fn search_sessions(
+        &self,
+        query: &str,
+        project_id: Option<&str>,
+        user_id: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<SearchHit>, String> {
+        let limit = limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT);
+        self.storage
+            .chat_history
+            .search_sessions(query, project_id, user_id, limit)
+            .await
+    }
+
+    /// Star or unstar a session; starred sessions can be kept by the
+    /// retention policy
+    pub async fn set_session_starred(&self, session_id: &str, starred: bool) -> Result<(), String> {
+        if !self
+            .storage
+            .chat_history
+            .set_session_starred(session_id, starred)
+            .await?
+        {
+            return Err(format!("Session '{}' not found", session_id));
+        }
+        Ok(())
+    }
+
+    /// List the custom agents defined in a workspace
+    pub fn list_workspace_agents(&self, workspace_root: &str) -> Vec<WorkspaceAgent> {
+        self.workspace_agents.list(workspace_root)
+    }
+
+    /// List the script tools defined in a workspace
+    pub fn list_script_tools(&self, workspace_root: &str) -> Vec<ScriptTool> {
+        script_tools::load_tools(Path::new(workspace_root))
+    }
+
+    /// Apply a decision to a stored pending approval
+    async fn resolve_approval(
+        &self,
+        tool_call_id: &str,
+        decision: ApprovalDecision,
+    ) -> Result<TaskHandle, String> {
+        let approval = self
+            .storage
+            .chat_history
+            .take_pending_approval(tool_call_id)
+            .await?
+            .ok_or_else(|| format!("No pending approval for tool call '{}'", tool_call_id))?;
+
+        Ok(self
+            .spawn_resume(ResumePoint {
+                task_id: approval.task_id,
+                session_id: approval.session_id,
+                agent_id: approval.agent_id,
+                created_at: approval.created_at,
+                settings: approval.settings,
+                workspace_root: approval.workspace_root,
+                worktree_path: approval.worktree_path,
+                usage: approval.usage,
+                decision: Some((approval.tool_call, decision)),
+                remaining_calls: approval.remaining_calls,
+            })
+            .await)
+    }
+
+    /// Register a fresh handle for a paused task and resume it in the background.
+    /// The new handle replaces the waiting one (or a restored one after a restart).
+    async fn spawn_resume(&self, point: ResumePoint) -> TaskHandle {
+        let (action_tx, action_rx) = mpsc::unbounded_channel();
+        let task_state = Arc::new(RwLock::new(RuntimeTaskState::WaitingForUser));
+        let cancel_token = CancellationToken::new();
+        let handle = TaskHandle {
+            task_id: point.task_id.clone(),
+            session_id: point.session_id.clone(),
+            state: task_state.clone(),
+            action_sender: Arc::new(action_tx),
+            cancel_token: cancel_token.clone(),
+        };
+        self.tasks
+            .write()
+            .await
+            .insert(point.task_id.clone(), handle.clone());
+
+        // The user is waiting on a resumed task, so it takes a slot without queueing
+        let task_id = point.task_id.clone();
+        self.lock_queue().mark_running(&task_id);
+        let runtime_clone = self.clone();
+        let event_sender = self.event_sender.clone();
+        tokio::spawn(async move {
+            runtime_clone
+                .resume_task(point, task_state, action_rx, cancel_token, event_sender)
+                .await;
+            runtime_clone.release_slot(&task_id);
+        });
+
+        handle
+    }
+
+    /// Resume a paused task, applying the user's decision on its pending tool
+    /// call first when it waited for approval
+    async fn resume_task(
+        &self,
+        point: ResumePoint,
+        task_state: Arc<RwLock<RuntimeTaskState>>,
+        _action_rx: mpsc::UnboundedReceiver<TaskAction>,
+        cancel_token: CancellationToken,
+        event_sender: EventSender,
+    ) {
+        let now = chrono::Utc::now().timestamp();
+        let task = RuntimeTask {
+            id: point.task_id.clone(),
+            session_id: point.session_id.clone(),
+            agent_id: point.agent_id.clone(),
+            state: RuntimeTaskState::Running,
+            created_at: point.created_at,
+            started_at: Some(now),
+            completed_at: None,
+            error_message: None,
+            metadata: HashMap::new(),
+        };
+
+        *task_state.write().await = RuntimeTaskState::Running;
+        let session_status = if point.settings.plan_mode == Some(true) {
+            SessionStatus::Planning
+        } else {
+            SessionStatus::Running
+        };
+        let _ = self
+            .session_manager
+            .update_session_status(&task.session_id, session_status, None)
+            .await;
+        let _ = event_sender.send(RuntimeEvent::TaskStateChanged {
+            task_id: task.id.clone(),
+            state: RuntimeTaskState::Running,
+            previous_state: RuntimeTaskState::WaitingForUser,
+        });
+
+        let messages = match self.load_context_messages(&task.session_id).await {
+            Ok(messages) => messages,
+            Err(e) => {
+                self.complete_task(
+                    &task,
+                    RuntimeTaskState::Failed,
+                    Some(format!("Failed to load session history: {}", e)),
+                    &event_sender,
+                )
+                .await;
+                self.tasks.write().await.remove(&task.id);
+                return;
+            }
+        };
+
+        let query = messages
+            .iter()
+            .rev()
+            .find(|message| message.role == MessageRole::User)
+            .and_then(|message| match &message.content {
+                MessageContent::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let system_prompt = self.memory_prompt(&task.session_id, query).await;
+        let agent_loop = match self.create_agent_loop(
+            &point.settings,
+            &point.workspace_root,
+            system_prompt,
+            &event_sender,
+        ) {
+            Ok(agent_loop) => agent_loop,
+            Err(e) => {
+                self.complete_task(&task, RuntimeTaskState::Failed, Some(e), &event_sender)
+                    .await;
+                self.tasks.write().await.remove(&task.id);
+                return;
+            }
+        };
+        let mut ctx = AgentLoopContext {
+            session_id: task.session_id.clone(),
+            task_id: task.id.clone(),
+            workspace_root: point.workspace_root,
+            worktree_path: point.worktree_path,
+            settings: point.settings,
+            messages,
+            cancel_token,
+            compactions: vec![],
+            usage: point.usage,
+            usage_clock: Instant::now(),
+        };
+        let history = message_ids(&ctx.messages);
+        let agent_loop = agent_loop.with_stream_recorder(self.stream_recorder(&task, &history));
+
+        if let Some((call, decision)) = point.decision {
+            let request = to_tool_request(call);
+            let tool_result = match decision {
+                ApprovalDecision::Approve => agent_loop.execute_approved_tool(&ctx, request).await,
+                ApprovalDecision::Deny { reason } => agent_loop.deny_tool(&ctx, request, reason),
+            };
+            agent_loop.append_tool_result(&mut ctx, tool_result);
+        }
+
+        let remaining = point
+            .remaining_calls
+            .into_iter()
+            .map(to_tool_request)
+            .collect();
+        let result = match agent_loop.dispatch_tool_calls(&mut ctx, remaining).await {
+            Ok(Some(result)) => Ok(result),
+            Ok(None) => agent_loop.run(&mut ctx).await,
+            Err(e) => Err(e),
+        };
+
+        self.finish_run(&task, ctx, history, result, &task_state, &event_sender)
+            .await;
+    }
+
+    /// Register handles for tasks that were waiting for approval or paused by
+    /// their budget when the runtime last stopped, so they can be listed,
+    /// resumed or cancelled
+    async fn restore_pending_tasks(&self) -> Result<(), String> {
+        let approvals = self
+            .storage
+            .chat_history
+            .list_pending_approvals(None)
+            .await?;
+        let pauses = self.storage.chat_history.list_budget_pauses(None).await?;
+        let interrupted = self.restore_interrupted_streams().await?;
+        let waiting = approvals
+            .into_iter()
+            .map(|approval| (approval.task_id, approval.session_id))
+            .chain(
+                pauses
+                    .into_iter()
+                    .map(|pause| (pause.task_id, pause.session_id)),
+            )
+            .chain(
+                interrupted
+                    .into_iter()
+                    .map(|state| (state.task_id, state.session_id)),
+            );
+
+        let mut tasks = self.tasks.write().await;
+        for (task_id, session_id) in waiting {
+            tasks.entry(task_id.clone()).or_insert_with(|| {
+                // Nothing runs for a waiting task, so its action channel is closed
+                let (action_tx, _) = mpsc::unbounded_channel();
+                TaskHandle {
+                    task_id,
+                    session_id,
+                    state: Arc::new(RwLock::new(RuntimeTaskState::WaitingForUser)),
+                    action_sender: Arc::new(action_tx),
+                    cancel_token: CancellationToken::new(),
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Store the output of runs cut short by a crash and mark them
+    /// interrupted, so they wait for the user to continue them
+    async fn restore_interrupted_streams(&self) -> Result<Vec<StreamState>, String> {
+        let now = chrono::Utc::now().timestamp();
+        let mut interrupted = Vec::new();
+        for mut state in self.storage.chat_history.list_stream_states(None).await? {
+            if state.interrupted_at.is_none() {
+                let (messages, partial_id) = stream_state::interrupted_messages(&state);
+                for message in messages {
+                    if let Err(e) = self.session_manager.add_message(message.clone()).await {
+                        log::error!("Failed to restore message {}: {}", message.id, e);
+                    }
+                }
+                state.messages.clear();
+                state.text.clear();
+                state.interrupted_at = Some(now);
+                state.interrupted_message_id = partial_id;
+                self.storage.chat_history.save_stream_state(&state).await?;
+                let _ = self
+                    .session_manager
+                    .update_session_status(&state.session_id, SessionStatus::WaitingForAction, None)
+                    .await;
+                log::info!("Restored interrupted task {}", state.task_id);
+            }
+            interrupted.push(state);
+        }
+        Ok(interrupted)
+    }
+
+    /// Session history as the model sees it, with the latest compaction applied
+    async fn load_context_messages(&self, session_id: &str) -> Result<Vec<Message>, String> {
+        let messages = self
+            .session_manager
+            .get_messages(session_id, None, None)
+            .await?;
+
+        match self
+            .storage
+            .chat_history
+            .get_latest_compaction(session_id)
+            .await?
+        {
+            Some(latest) => Ok(compaction::apply_compaction(messages, &latest)),
+            None => Ok(messages),
+        }
+    }
+
+    /// System prompt with the memories relevant to `query`. Failing to load
+    /// memories does not stop the task.
+    async fn memory_prompt(&self, session_id: &str, query: &str) -> Option<String> {
+        match self.memory.system_prompt(session_id, query).await {
+            Ok(prompt) => prompt,
+            Err(e) => {
+                log::warn!("Failed to load memories for session {}: {}", session_id, e);
+                None
+            }
+        }
+    }
+
+    /// Recorder saving the streamed output of a run that started with `history`
+    fn stream_recorder(
+        &self,
+        task: &RuntimeTask,
+        history: &HashSet<String>,
+    ) -> StreamStateRecorder {
+        StreamStateRecorder::new(
+            self.storage.chat_history.clone(),
+            task.agent_id.clone(),
+            task.created_at,
+            history.clone(),
+        )
+    }
+
+    /// Build the agent loop for a task, applying the workspace agent selected
+    /// in its settings. An explicit `model` setting wins over the agent's.
+    fn create_agent_loop(
+        &self,
+        settings: &TaskSettings,
+        workspace_root: &str,
+        system_prompt: Option<String>,
+        event_sender: &EventSender,
+    ) -> Result<AgentLoop, String> {
+        let agent = match settings.agent {
+            Some(ref name) => self
+                .workspace_agents
+                .get(workspace_root, name)
+                .ok_or_else(|| format!("Agent '{}' is not defined in {}", name, AGENTS_DIR))?,
+            None => WorkspaceAgent::default(),
+        };
+        let plan_mode = settings.plan_mode == Some(true);
+        let phase = if plan_mode {
+            ModelPhase::Planning
+        } else {
+            ModelPhase::Coding
+        };
+        let prompts: Vec<String> = [
+            plan_mode.then(|| plan::PLAN_MODE_PROMPT.to_string()),
+            agent.system_prompt,
+            system_prompt,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let tool_truncation = match settings.extra.get("toolTruncation") {
+            Some(value) => serde_json::from_value(value.clone())
+                .map_err(|e| format!("Invalid toolTruncation setting: {}", e))?,
+            None => TruncationConfig::default(),
+        };
+        let defaults = AgentLoopConfig::default();
+        let config = AgentLoopConfig {
+            max_tokens: max_tokens_setting(settings)?,
+            system_prompt: (!prompts.is_empty()).then(|| prompts.join("\n\n")),
+            plan_mode,
+            read_only_tools: settings.read_only_tools == Some(true),
+            temperature: agent.temperature.unwrap_or(defaults.temperature),
+            available_tools: agent.tools,
+            model: ModelRegistry::resolve_phase_model(settings, phase).or(agent.model),
+            compaction_model: ModelRegistry::resolve_phase_model(
+                settings,
+                ModelPhase::Summarization,
+            ),
+            tool_truncation,
+            ..defaults
+        };
+
+        // Tools defined in the workspace only exist for its tasks
+        let script_tools = script_tools::load_tools(Path::new(workspace_root));
+        let tool_registry = if script_tools.is_empty() {
+            self.tool_registry.clone()
+        } else {
+            Arc::new(ToolRegistry::layered(
+                self.tool_registry.clone(),
+                script_tools
+                    .into_iter()
+                    .map(ScriptTool::into_tool)
+                    .collect(),
+            ))
+        };
+        let tool_dispatcher = ToolDispatcher::new(tool_registry)
+            .with_checkpoints(self.checkpoints.clone())
+            .with_hooks(self.hooks.clone())
+            .with_sandbox(self.sandbox.clone())
+            .with_shell_policy(self.shell_policy.clone());
+
+        Ok(AgentLoop::new(
+            config,
+            Arc::new(tool_dispatcher),
+            self.llm.clone(),
+            event_sender.clone(),
+        )
+        .with_attachments(self.storage.attachments.clone())
+        .with_metrics(self.metrics.clone())
+        .with_drain_token(self.draining.clone()))
+    }
+
+    /// Complete a task and emit events
+    async fn complete_task(
+        &self,
+        task: &RuntimeTask,
+        final_state: RuntimeTaskState,
+        error: Option<String>,
+        event_sender: &EventSender,
+    ) {
+        let previous_state = match self.tasks.read().await.get(&task.id) {
+            Some(handle) => *handle.state.read().await,
+            None => RuntimeTaskState::Running,
+        };
+
+        // Update session status
+        let session_status = match final_state {
+            RuntimeTaskState::Completed => SessionStatus::Completed,
+            RuntimeTaskState::Failed => SessionStatus::Error,
+            RuntimeTaskState::Cancelled => SessionStatus::Cancelled,
+            _ => SessionStatus::Running,
+        };
+
+        let _ = self
+            .session_manager
+            .update_session_status(&task.session_id, session_status, None)
+            .await;
+
+        // Emit completion event
+        let _ = event_sender.send(RuntimeEvent::TaskStateChanged {
+            task_id: task.id.clone(),
+            state: final_state,
+            previous_state,
+        });
+
+        let _ = event_sender.send(RuntimeEvent::TaskCompleted {
+            task_id: task.id.clone(),
+            session_id: task.session_id.clone(),
+        });
+
+        if let Some(err) = &error {
+            let _ = event_sender.send(RuntimeEvent::Error {
+                task_id: Some(task.id.clone()),
+                session_id: Some(task.session_id.clone()),
+                message: err.clone(),
+            });
+        }
+
+        if final_state.is_terminal() {
+            self.metrics
+                .record_finished(&task.id, &task.session_id, final_state, error.as_deref());
+
+            // Offer the changes of an isolated task for merging or discarding
+            match self.storage.chat_history.get_task_worktree(&task.id).await {
+                Ok(Some(worktree)) => {
+                    let _ = event_sender.send(RuntimeEvent::WorktreeReady {
+                        task_id: task.id.clone(),
+                        session_id: task.session_id.clone(),
+                        worktree,
+                    });
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to load worktree of task {}: {}", task.id, e),
+            }
+
+            let payload = HookPayload {
+                session_id: task.session_id.clone(),
+                task_id: task.id.clone(),
+                state: serde_json::to_value(final_state)
+                    .ok()
+                    .and_then(|state| state.as_str().map(str::to_string)),
+                ..HookPayload::default()
+            };
+            let _ = self
+                .hooks
+                .run(HookEvent::SessionEnd, &payload, &CancellationToken::new())
+                .await;
+        }
+    }
+
+    /// Find existing session for a task input
+    async fn find_session_for_task(&self, input: &TaskInput) -> Option<SessionId> {
+        // If session_id is explicitly provided in input and exists, use that
+        // Otherwise, return None to create a new session
+        if input.session_id.is_empty() {
+            return None;
+        }
+        match self.session_manager.get_session(&input.session_id).await {
+            Ok(Some(session)) => Some(session.id),
+            _ => None,
+        }
+    }
+}
+
+/// User decision on a pending tool call
+enum ApprovalDecision {
+    Approve,
+    Deny { reason: Option<String> },
+}
+
+/// Everything needed to start a queued task
+struct QueuedRun {
+    task: RuntimeTask,
+    input: TaskInput,
+    task_state: Arc<RwLock<RuntimeTaskState>>,
+    action_rx: mpsc::UnboundedReceiver<TaskAction>,
+    cancel_token: CancellationToken,
+}
+
+/// Everything needed to resume a task that waited for the user
+struct ResumePoint {
+    task_id: RuntimeTaskId,
+    session_id: SessionId,
+    agent_id: Option<AgentId>,
+    created_at: i64,
+    settings: TaskSettings,
+    workspace_root: String,
+    worktree_path: Option<String>,
+    usage: BudgetUsage,
+    /// Tool call the user decided on, when the task waited for approval
+    decision: Option<(ToolCall, ApprovalDecision)>,
+    remaining_calls: Vec<ToolCall>,
+}
+
+/// Workspace root of a task, falling back to the current directory
+fn workspace_root(input: &TaskInput) -> String {
+    input
+        .workspace
+        .as_ref()
+        .map(|w| w.root_path.clone())
+        .unwrap_or_else(|| {
+            std::env::current_dir()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|_| "/".to_string())
+        })
+}
+
+/// Maximum tokens per response requested in a task's settings
+fn max_tokens_setting(settings: &TaskSettings) -> Result<Option<u32>, String> {
+    settings
+        .extra
+        .get(MAX_TOKENS_SETTING)
+        .map(|value| {
+            serde_json::from_value(value.clone())
+                .map_err(|e| format!("Invalid {} setting: {}", MAX_TOKENS_SETTING, e))
+        })
+        .transpose()
+}
+
+fn message_ids(messages: &[Message]) -> HashSet<String> {
+    messages.iter().map(|message| message.id.clone()).collect()
+}
+
+fn to_tool_call(request: &ToolRequest) -> ToolCall {
+    ToolCall {
+        id: request.tool_call_id.clone(),
+        name: request.name.clone(),
+        input: request.input.clone(),
+    }
+}
+
+fn to_tool_request(call: ToolCall) -> ToolRequest {
+    ToolRequest {
+        tool_call_id: call.id,
+        name: call.name,
+        input: call.input,
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::types::{
+        Message as LlmMessage, MessageContent as LlmContent, StreamEvent, StreamTextRequest,
+    };
+    use crate::storage::{PageRequest, SessionSort};
+    use async_trait::async_trait;
     use tempfile::TempDir;
 
-    async fn create_test_runtime() -> (CoreRuntime, TempDir, mpsc::UnboundedReceiver<RuntimeEvent>)
-    {
-        let temp_dir = TempDir::new().unwrap();
+    /// LLM client that answers every request with a fixed text response
+    struct FixedResponseLlm;
+
+    #[async_trait]
+    impl LlmClient for FixedResponseLlm {
+        async fn stream(
+            &self,
+            _request: StreamTextRequest,
+            on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            on_event(StreamEvent::TextDelta {
+                text: "Hello from the agent".to_string(),
+            });
+            on_event(StreamEvent::Done {
+                finish_reason: Some("stop".to_string()),
+            });
+            Ok(())
+        }
+    }
+
+    /// LLM client that asks to write a file, then answers once it sees the tool result
+    struct WriteFileLlm;
+
+    #[async_trait]
+    impl LlmClient for WriteFileLlm {
+        async fn stream(
+            &self,
+            request: StreamTextRequest,
+            on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            let has_tool_result = request
+                .messages
+                .iter()
+                .any(|message| matches!(message, LlmMessage::Tool { .. }));
+            if has_tool_result {
+                on_event(StreamEvent::TextDelta {
+                    text: "File written".to_string(),
+                });
+            } else {
+                on_event(StreamEvent::ToolCall {
+                    tool_call_id: "call-1".to_string(),
+                    tool_name: "write_file".to_string(),
+                    input: serde_json::json!({ "path": "notes.txt", "content": "hi" }),
+                    provider_metadata: None,
+                });
+            }
+            on_event(StreamEvent::Done {
+                finish_reason: None,
+            });
+            Ok(())
+        }
+    }
+
+    /// LLM client whose model has an 8k context and cannot call tools
+    struct TextOnlyLlm;
+
+    #[async_trait]
+    impl LlmClient for TextOnlyLlm {
+        async fn stream(
+            &self,
+            request: StreamTextRequest,
+            on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            FixedResponseLlm.stream(request, on_event).await
+        }
+
+        async fn model_config(&self, _model: &str) -> Option<ModelConfig> {
+            Some(text_only_model())
+        }
+    }
+
+    fn text_only_model() -> ModelConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": "Text Only",
+            "toolCall": false,
+            "providers": ["test"],
+            "context_length": 8000
+        }))
+        .unwrap()
+    }
+
+    /// LLM client that streams one token and then never finishes
+    struct HangingLlm;
+
+    #[async_trait]
+    impl LlmClient for HangingLlm {
+        async fn stream(
+            &self,
+            _request: StreamTextRequest,
+            on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            on_event(StreamEvent::TextDelta {
+                text: "Thinking".to_string(),
+            });
+            std::future::pending::<()>().await;
+            Ok(())
+        }
+    }
+
+    /// LLM client that submits a plan when offered submit_plan and answers otherwise
+    struct PlanningLlm;
+
+    #[async_trait]
+    impl LlmClient for PlanningLlm {
+        async fn stream(
+            &self,
+            request: StreamTextRequest,
+            on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            let planning = request
+                .tools
+                .iter()
+                .flatten()
+                .any(|tool| tool.name == plan::SUBMIT_PLAN_TOOL);
+            if planning {
+                on_event(StreamEvent::ToolCall {
+                    tool_call_id: "call-plan".to_string(),
+                    tool_name: plan::SUBMIT_PLAN_TOOL.to_string(),
+                    input: serde_json::json!({
+                        "summary": "Rename the loader",
+                        "steps": ["Rename config.rs", "Fix imports"]
+                    }),
+                    provider_metadata: None,
+                });
+            } else {
+                on_event(StreamEvent::TextDelta {
+                    text: "Done".to_string(),
+                });
+            }
+            on_event(StreamEvent::Done {
+                finish_reason: None,
+            });
+            Ok(())
+        }
+    }
+
+    /// LLM client that keeps every request and answers with a fixed text
+    #[derive(Default)]
+    struct RecordingLlm {
+        requests: Mutex<Vec<StreamTextRequest>>,
+    }
+
+    #[async_trait]
+    impl LlmClient for RecordingLlm {
+        async fn stream(
+            &self,
+            request: StreamTextRequest,
+            on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            self.requests.lock().unwrap().push(request);
+            on_event(StreamEvent::TextDelta {
+                text: "Done".to_string(),
+            });
+            on_event(StreamEvent::Done {
+                finish_reason: None,
+            });
+            Ok(())
+        }
+    }
+
+    /// LLM client that answers a fixed text and titles sessions when asked to
+    struct TitlingLlm;
+
+    #[async_trait]
+    impl LlmClient for TitlingLlm {
+        async fn stream(
+            &self,
+            request: StreamTextRequest,
+            on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            let titling = request.messages.iter().any(|message| {
+                matches!(
+                    message,
+                    LlmMessage::User { content: LlmContent::Text(text), .. }
+                        if text.starts_with("Name this conversation")
+                )
+            });
+            let text = if titling {
+                "Title: Greeting The Agent\nSummary: The user said hello."
+            } else {
+                "Hello from the agent"
+            };
+            on_event(StreamEvent::TextDelta {
+                text: text.to_string(),
+            });
+            on_event(StreamEvent::Done {
+                finish_reason: None,
+            });
+            Ok(())
+        }
+    }
+
+    async fn create_runtime_in(
+        temp_dir: &TempDir,
+        llm: Arc<dyn LlmClient>,
+    ) -> (CoreRuntime, mpsc::UnboundedReceiver<RuntimeEvent>) {
         let storage = Storage::new(
             temp_dir.path().to_path_buf(),
             temp_dir.path().join("attachments"),
@@ -464,19 +2782,601 @@ mod tests {
         .expect("Failed to create storage");
 
         let (tx, rx) = mpsc::unbounded_channel();
-        let runtime = CoreRuntime::new(storage, tx)
+        let runtime = CoreRuntime::new(storage, llm, tx)
             .await
             .expect("Failed to create runtime");
 
+        (runtime, rx)
+    }
+
+    async fn create_test_runtime() -> (CoreRuntime, TempDir, mpsc::UnboundedReceiver<RuntimeEvent>)
+    {
+        let temp_dir = TempDir::new().unwrap();
+        let (runtime, rx) = create_runtime_in(&temp_dir, Arc::new(FixedResponseLlm)).await;
+
         (runtime, temp_dir, rx)
     }
 
+    fn task_input(message: &str) -> TaskInput {
+        TaskInput {
+            session_id: String::new(),
+            agent_id: None,
+            project_id: None,
+            initial_message: message.to_string(),
+            settings: None,
+            workspace: None,
+            priority: 0,
+            isolate: false,
+            read_only_tools: false,
+        }
+    }
+
+    async fn wait_for_event(
+        rx: &mut mpsc::UnboundedReceiver<RuntimeEvent>,
+        predicate: impl Fn(&RuntimeEvent) -> bool,
+    ) -> RuntimeEvent {
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while let Some(event) = rx.recv().await {
+                if predicate(&event) {
+                    return event;
+                }
+            }
+            panic!("Event channel closed");
+        })
+        .await
+        .expect("Timed out waiting for event")
+    }
+
     #[tokio::test]
     async fn test_create_runtime() {
         let (_runtime, _temp, _rx) = create_test_runtime().await;
         // Runtime created successfully
     }
 
+    #[tokio::test]
+    async fn test_start_task_runs_agent_loop() {
+        let (runtime, _temp, mut rx) = create_test_runtime().await;
+
+        let handle = runtime
+            .start_task(task_input("Hello"))
+            .await
+            .expect("Failed to start task");
+
+        let completed = wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::TaskCompleted { .. })
+        })
+        .await;
+        assert!(matches!(
+            completed,
+            RuntimeEvent::TaskCompleted { task_id, .. } if task_id == handle.task_id
+        ));
+
+        let messages = runtime
+            .session_manager()
+            .get_messages(&handle.session_id, None, None)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(
+            &messages[1].content,
+            MessageContent::Text { text } if text == "Hello from the agent"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_runtime_stats_track_task_runs() {
+        let (runtime, _temp, mut rx) = create_test_runtime().await;
+
+        let handle = runtime.start_task(task_input("Hello")).await.unwrap();
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::TaskCompleted { .. })
+        })
+        .await;
+
+        let stats = runtime.get_runtime_stats(Some(&handle.session_id)).await;
+        assert_eq!(stats.queued_tasks, 0);
+        assert_eq!(stats.tasks.len(), 1);
+        assert_eq!(stats.tasks[0].task_id, handle.task_id);
+        assert_eq!(stats.tasks[0].iterations, 1);
+        assert_eq!(stats.tasks[0].llm_latency.count, 1);
+        assert_eq!(stats.tasks[0].llm_first_token.count, 1);
+        assert_eq!(stats.tasks[0].errors, 0);
+        assert!(runtime
+            .get_runtime_stats(Some("other-session"))
+            .await
+            .tasks
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_session_state_rebuilds_from_event_log() {
+        let (runtime, _temp, mut rx) = create_test_runtime().await;
+
+        let handle = runtime.start_task(task_input("Hello")).await.unwrap();
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::TaskCompleted { .. })
+        })
+        .await;
+
+        let state = runtime
+            .rebuild_session_state(&handle.session_id)
+            .await
+            .unwrap();
+        let stored = runtime
+            .session_manager()
+            .get_messages(&handle.session_id, None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            message_ids(&state.messages),
+            message_ids(&stored),
+            "the log holds every stored message"
+        );
+        assert_eq!(state.messages[1].role, MessageRole::Assistant);
+        assert_eq!(
+            state.task_states.get(&handle.task_id),
+            Some(&RuntimeTaskState::Completed)
+        );
+        assert!(state.streaming_text.is_empty());
+
+        // Streams resume right after the last event a client saw
+        let events = runtime
+            .list_session_events(&handle.session_id, None, None)
+            .await
+            .unwrap();
+        let completed = events
+            .iter()
+            .position(|e| e.event_type == "taskCompleted")
+            .unwrap();
+        assert!(state.last_sequence >= Some(events[completed].sequence));
+        let resumed = runtime
+            .list_session_events(
+                &handle.session_id,
+                Some(events[completed - 1].sequence),
+                Some(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resumed[0].sequence, events[completed].sequence);
+    }
+
+    #[tokio::test]
+    async fn test_retention_policy_archives_and_deletes_sessions() {
+        let (runtime, _temp, _rx) = create_test_runtime().await;
+        let day = 24 * 60 * 60;
+        let now = chrono::Utc::now().timestamp();
+        for (id, idle_days, starred) in [
+            ("recent", 1, false),
+            ("idle", 40, false),
+            ("old", 100, false),
+            ("kept", 100, true),
+        ] {
+            let updated_at = now - idle_days * day;
+            runtime
+                .storage
+                .chat_history
+                .create_session(&crate::storage::Session {
+                    id: id.to_string(),
+                    project_id: None,
+                    title: None,
+                    summary: None,
+                    status: SessionStatus::Completed,
+                    created_at: updated_at,
+                    updated_at,
+                    last_event_id: None,
+                    metadata: None,
+                    starred,
+                    archived_at: None,
+                    user_id: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        // Nothing happens until a policy is set
+        let report = runtime.apply_retention_policy().await.unwrap();
+        assert_eq!(report, RetentionReport::default());
+
+        runtime
+            .set_retention_policy(RetentionPolicy {
+                archive_after_days: Some(30),
+                delete_after_days: Some(90),
+                exclude_starred: true,
+            })
+            .await
+            .unwrap();
+        let report = runtime.apply_retention_policy().await.unwrap();
+        assert_eq!(report.archived, vec!["idle".to_string()]);
+        assert_eq!(report.deleted, vec!["old".to_string()]);
+
+        let chat_history = &runtime.storage.chat_history;
+        let page = PageRequest::default();
+        let listed = chat_history
+            .list_sessions(None, None, None, false, SessionSort::UpdatedAt, &page)
+            .await
+            .unwrap()
+            .items;
+        let mut ids: Vec<_> = listed.iter().map(|s| s.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["kept", "recent"]);
+        let archived = chat_history
+            .list_sessions(None, None, None, true, SessionSort::UpdatedAt, &page)
+            .await
+            .unwrap()
+            .items;
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].id, "idle");
+        assert!(chat_history.get_session("old").await.unwrap().is_none());
+
+        // Restoring puts the session back in the default list
+        runtime.restore_session("idle").await.unwrap();
+        let restored = chat_history.get_session("idle").await.unwrap().unwrap();
+        assert!(restored.archived_at.is_none());
+        assert!(restored.updated_at >= now);
+        assert!(runtime.restore_session("old").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_session_is_titled_and_summarized() {
+        let temp_dir = TempDir::new().unwrap();
+        let (runtime, mut rx) = create_runtime_in(&temp_dir, Arc::new(TitlingLlm)).await;
+
+        let handle = runtime.start_task(task_input("Hello")).await.unwrap();
+        let event = wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::SessionUpdated { .. })
+        })
+        .await;
+        assert!(matches!(
+            event,
+            RuntimeEvent::SessionUpdated { ref title, ref summary, .. }
+                if title.as_deref() == Some("Greeting The Agent")
+                    && summary == "The user said hello."
+        ));
+
+        let session = runtime
+            .session_manager()
+            .get_session(&handle.session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.title.as_deref(), Some("Greeting The Agent"));
+        assert_eq!(session.summary.as_deref(), Some("The user said hello."));
+    }
+
+    #[tokio::test]
+    async fn test_tool_approval_survives_restart() {
+        let temp_dir = TempDir::new().unwrap();
+        let (runtime, mut rx) = create_runtime_in(&temp_dir, Arc::new(WriteFileLlm)).await;
+
+        let handle = runtime
+            .start_task(task_input("Write a file"))
+            .await
+            .expect("Failed to start task");
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::ToolCallRequested { .. })
+        })
+        .await;
+        assert_eq!(*handle.state.read().await, RuntimeTaskState::WaitingForUser);
+
+        let pending = runtime
+            .list_pending_approvals(Some(&handle.session_id))
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].tool_call.name, "write_file");
+        drop(runtime);
+
+        // A new runtime over the same storage restores the waiting task
+        let (restarted, mut rx) = create_runtime_in(&temp_dir, Arc::new(WriteFileLlm)).await;
+        let restored = restarted
+            .get_task(&handle.task_id)
+            .await
+            .expect("Waiting task should be restored");
+        assert_eq!(
+            *restored.state.read().await,
+            RuntimeTaskState::WaitingForUser
+        );
+
+        restarted
+            .approve_tool_call("call-1")
+            .await
+            .expect("Failed to approve tool call");
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::TaskCompleted { .. })
+        })
+        .await;
+
+        let messages = restarted
+            .session_manager()
+            .get_messages(&handle.session_id, None, None)
+            .await
+            .unwrap();
+        // user, assistant tool call, tool result, final answer
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[2].role, MessageRole::Tool);
+        assert_eq!(messages[2].tool_call_id.as_deref(), Some("call-1"));
+        assert!(matches!(
+            &messages[3].content,
+            MessageContent::Text { text } if text == "File written"
+        ));
+
+        assert!(restarted
+            .list_pending_approvals(None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(restarted.get_task(&handle.task_id).await.is_none());
+        // A decision is only applied once
+        assert!(restarted.approve_tool_call("call-1").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deny_tool_call_reports_reason_to_model() {
+        let temp_dir = TempDir::new().unwrap();
+        let (runtime, mut rx) = create_runtime_in(&temp_dir, Arc::new(WriteFileLlm)).await;
+
+        let handle = runtime
+            .start_task(task_input("Write a file"))
+            .await
+            .expect("Failed to start task");
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::ToolCallRequested { .. })
+        })
+        .await;
+
+        runtime
+            .deny_tool_call("call-1", Some("not now".to_string()))
+            .await
+            .expect("Failed to deny tool call");
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::TaskCompleted { .. })
+        })
+        .await;
+
+        let messages = runtime
+            .session_manager()
+            .get_messages(&handle.session_id, None, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            &messages[2].content,
+            MessageContent::ToolResult { result }
+                if result["error"].as_str().is_some_and(|error| error.contains("not now"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_task_waiting_for_approval() {
+        let temp_dir = TempDir::new().unwrap();
+        let (runtime, mut rx) = create_runtime_in(&temp_dir, Arc::new(WriteFileLlm)).await;
+
+        let handle = runtime
+            .start_task(task_input("Write a file"))
+            .await
+            .expect("Failed to start task");
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::ToolCallRequested { .. })
+        })
+        .await;
+
+        runtime
+            .cancel_task(&handle.task_id)
+            .await
+            .expect("Failed to cancel task");
+        assert!(runtime.get_task(&handle.task_id).await.is_none());
+        assert!(runtime
+            .list_pending_approvals(None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(runtime.approve_tool_call("call-1").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_task_stops_llm_stream() {
+        let temp_dir = TempDir::new().unwrap();
+        let (runtime, mut rx) = create_runtime_in(&temp_dir, Arc::new(HangingLlm)).await;
+
+        let handle = runtime
+            .start_task(task_input("Think forever"))
+            .await
+            .expect("Failed to start task");
+        wait_for_event(&mut rx, |event| matches!(event, RuntimeEvent::Token { .. })).await;
+
+        let cancelled_at = std::time::Instant::now();
+        runtime
+            .cancel_task(&handle.task_id)
+            .await
+            .expect("Failed to cancel task");
+        wait_for_event(&mut rx, |event| {
+            matches!(
+                event,
+                RuntimeEvent::TaskStateChanged {
+                    state: RuntimeTaskState::Cancelled,
+                    ..
+                }
+            )
+        })
+        .await;
+        assert!(cancelled_at.elapsed() < std::time::Duration::from_secs(1));
+        assert!(handle.cancel_token.is_cancelled());
+
+        // The partial response is persisted
+        let messages = runtime
+            .session_manager()
+            .get_messages(&handle.session_id, None, None)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_queue_limits_concurrent_tasks() {
+        let temp_dir = TempDir::new().unwrap();
+        let (runtime, mut rx) = create_runtime_in(&temp_dir, Arc::new(HangingLlm)).await;
+        runtime.set_max_concurrent_tasks(1);
+
+        let first = runtime.start_task(task_input("First")).await.unwrap();
+        let second = runtime.start_task(task_input("Second")).await.unwrap();
+        let third = runtime
+            .start_task(TaskInput {
+                priority: 10,
+                ..task_input("Urgent")
+            })
+            .await
+            .unwrap();
+        wait_for_event(&mut rx, |event| matches!(event, RuntimeEvent::Token { .. })).await;
+
+        // The higher priority task jumps ahead of the earlier one
+        let queued: Vec<String> = runtime
+            .list_queued_tasks()
+            .into_iter()
+            .map(|task| task.task_id)
+            .collect();
+        assert_eq!(queued, vec![third.task_id.clone(), second.task_id.clone()]);
+        assert_eq!(*second.state.read().await, RuntimeTaskState::Pending);
+
+        runtime.move_queued_task(&second.task_id, 0).unwrap();
+        assert_eq!(runtime.list_queued_tasks()[0].task_id, second.task_id);
+        wait_for_event(&mut rx, |event| {
+            matches!(
+                event,
+                RuntimeEvent::TaskQueuePositionChanged { task_id, position: 1 }
+                    if task_id == &third.task_id
+            )
+        })
+        .await;
+
+        // Cancelling a queued task removes it without running it
+        runtime.cancel_task(&third.task_id).await.unwrap();
+        assert_eq!(runtime.list_queued_tasks().len(), 1);
+
+        // Finishing the running task starts the next one
+        runtime.cancel_task(&first.task_id).await.unwrap();
+        wait_for_event(&mut rx, |event| {
+            matches!(
+                event,
+                RuntimeEvent::TaskStateChanged {
+                    task_id,
+                    state: RuntimeTaskState::Running,
+                    ..
+                } if task_id == &second.task_id
+            )
+        })
+        .await;
+        assert!(runtime.list_queued_tasks().is_empty());
+        runtime.cancel_task(&second.task_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_budget_pause_and_continue() {
+        let temp_dir = TempDir::new().unwrap();
+        let (runtime, mut rx) = create_runtime_in(&temp_dir, Arc::new(WriteFileLlm)).await;
+        // Auto-approval needs a sandbox policy
+        runtime
+            .set_sandbox_policy(None, Some(SandboxPolicy::default()))
+            .await
+            .unwrap();
+
+        let input = TaskInput {
+            settings: Some(TaskSettings {
+                auto_approve_edits: Some(true),
+                budget: Some(crate::storage::TaskBudget {
+                    max_iterations: Some(1),
+                    ..Default::default()
+                }),
+                ..TaskSettings::default()
+            }),
+            ..task_input("Write a file")
+        };
+        let handle = runtime
+            .start_task(input)
+            .await
+            .expect("Failed to start task");
+        let event = wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::BudgetExceeded { .. })
+        })
+        .await;
+        assert!(matches!(
+            event,
+            RuntimeEvent::BudgetExceeded { ref exceeded, .. }
+                if exceeded == &vec![crate::storage::BudgetLimit::Iterations]
+        ));
+        assert_eq!(*handle.state.read().await, RuntimeTaskState::WaitingForUser);
+
+        let pauses = runtime
+            .list_budget_pauses(Some(&handle.session_id))
+            .await
+            .unwrap();
+        assert_eq!(pauses.len(), 1);
+        // The tool call from the last response has not run yet
+        assert_eq!(pauses[0].remaining_calls[0].id, "call-1");
+
+        runtime
+            .continue_task(&handle.task_id)
+            .await
+            .expect("Failed to continue task");
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::TaskCompleted { .. })
+        })
+        .await;
+
+        let messages = runtime
+            .session_manager()
+            .get_messages(&handle.session_id, None, None)
+            .await
+            .unwrap();
+        // user, assistant tool call, tool result, final answer
+        assert_eq!(messages.len(), 4);
+        assert!(runtime.list_budget_pauses(None).await.unwrap().is_empty());
+        assert!(runtime.continue_task(&handle.task_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rollback_to_checkpoint_after_approved_write() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path().join("workspace");
+        std::fs::create_dir_all(&workspace).unwrap();
+        let (runtime, mut rx) = create_runtime_in(&temp_dir, Arc::new(WriteFileLlm)).await;
+
+        let input = TaskInput {
+            workspace: Some(crate::storage::WorkspaceInfo {
+                root_path: workspace.to_string_lossy().to_string(),
+                worktree_path: None,
+                repository_url: None,
+                branch: None,
+            }),
+            ..task_input("Write a file")
+        };
+        let handle = runtime
+            .start_task(input)
+            .await
+            .expect("Failed to start task");
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::ToolCallRequested { .. })
+        })
+        .await;
+        runtime.approve_tool_call("call-1").await.unwrap();
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::TaskCompleted { .. })
+        })
+        .await;
+
+        let checkpoints = runtime.list_checkpoints(&handle.session_id).await.unwrap();
+        assert_eq!(checkpoints.len(), 1);
+        assert_eq!(checkpoints[0].tool_call_id, "call-1");
+        assert!(checkpoints[0].files[0].hash.is_none());
+
+        // The placeholder tool does not write, so simulate its effect
+        std::fs::write(workspace.join("notes.txt"), "hi").unwrap();
+        let rollback = runtime
+            .rollback_to_checkpoint(&handle.session_id, &checkpoints[0].id)
+            .await
+            .unwrap();
+        assert_eq!(rollback.removed_files.len(), 1);
+        assert!(!workspace.join("notes.txt").exists());
+    }
+
     #[tokio::test]
     async fn test_settings_validation() {
         let validator = SettingsValidator::new();
@@ -490,10 +3390,320 @@ mod tests {
             auto_approve_edits: Some(true),
             auto_approve_plan: Some(true),
             auto_code_review: None,
+            plan_mode: None,
+            read_only_tools: None,
+            tool_capabilities: None,
+            agent: None,
+            models: None,
+            budget: None,
             extra: HashMap::new(),
         };
         let result = validator.validate(&risky_settings);
         assert!(result.valid); // Still valid, just warnings
         assert_eq!(result.warnings.len(), 2);
+
+        let invalid_max_tokens = TaskSettings {
+            extra: HashMap::from([(MAX_TOKENS_SETTING.to_string(), serde_json::json!("lots"))]),
+            ..TaskSettings::default()
+        };
+        let result = validator.validate(&invalid_max_tokens);
+        assert!(!result.valid);
+        assert_eq!(result.details[0].code, ValidationErrorCode::InvalidSetting);
+        assert_eq!(result.details[0].field, MAX_TOKENS_SETTING);
+
+        let model = text_only_model();
+        let result = validator.validate_model(
+            &model,
+            &ModelRequirements {
+                image_input: true,
+                tool_calling: true,
+                max_tokens: Some(16000),
+            },
+        );
+        let codes: Vec<ValidationErrorCode> = result.details.iter().map(|e| e.code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                ValidationErrorCode::ImageInputUnsupported,
+                ValidationErrorCode::ToolCallingUnsupported,
+                ValidationErrorCode::MaxTokensExceedsContext,
+            ]
+        );
+        assert_eq!(result.errors.len(), 3);
+        assert!(
+            validator
+                .validate_model(
+                    &model,
+                    &ModelRequirements {
+                        max_tokens: Some(4000),
+                        ..ModelRequirements::default()
+                    },
+                )
+                .valid
+        );
+    }
+
+    #[tokio::test]
+    async fn test_start_task_checks_model_capabilities() {
+        let temp_dir = TempDir::new().unwrap();
+        let (runtime, _rx) = create_runtime_in(&temp_dir, Arc::new(TextOnlyLlm)).await;
+
+        let input = TaskInput {
+            settings: Some(TaskSettings {
+                extra: HashMap::from([(MAX_TOKENS_SETTING.to_string(), serde_json::json!(10000))]),
+                ..TaskSettings::default()
+            }),
+            ..task_input("Hello")
+        };
+        let validation = runtime.validate_task(&input).await;
+        assert!(!validation.valid);
+        let fields: Vec<&str> = validation
+            .details
+            .iter()
+            .map(|e| e.field.as_str())
+            .collect();
+        assert_eq!(fields, vec!["models", MAX_TOKENS_SETTING]);
+
+        let error = runtime.start_task(input).await.unwrap_err();
+        assert!(error.contains("does not support tool calling"));
+        assert!(runtime.list_active_tasks().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_plan_mode_then_execute_plan() {
+        let temp_dir = TempDir::new().unwrap();
+        let (runtime, mut rx) = create_runtime_in(&temp_dir, Arc::new(PlanningLlm)).await;
+
+        let handle = runtime
+            .start_task(TaskInput {
+                settings: Some(TaskSettings {
+                    plan_mode: Some(true),
+                    ..TaskSettings::default()
+                }),
+                ..task_input("Rename the loader")
+            })
+            .await
+            .unwrap();
+        let event = wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::PlanReady { .. })
+        })
+        .await;
+        let RuntimeEvent::PlanReady { plan, .. } = event else {
+            unreachable!()
+        };
+        assert_eq!(plan.steps, vec!["Rename config.rs", "Fix imports"]);
+        let session = runtime
+            .storage
+            .chat_history
+            .get_session(&handle.session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.status, SessionStatus::PlanReady);
+
+        // Executing the plan continues the session with every tool available
+        let execution = runtime.execute_plan(&handle.session_id).await.unwrap();
+        assert_eq!(execution.session_id, handle.session_id);
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::TaskCompleted { task_id, .. } if *task_id == execution.task_id)
+        })
+        .await;
+
+        let messages = runtime
+            .session_manager
+            .get_messages(&handle.session_id, None, None)
+            .await
+            .unwrap();
+        assert!(messages.iter().any(|message| matches!(
+            &message.content,
+            MessageContent::Text { text } if text.contains("2. Fix imports")
+        )));
+        let plans = runtime.list_plans(&handle.session_id).await.unwrap();
+        assert!(plans[0].executed_at.is_some());
+        assert!(runtime.execute_plan(&handle.session_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_task_uses_workspace_agent() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path().join("workspace");
+        let agents_dir = workspace.join(AGENTS_DIR);
+        std::fs::create_dir_all(&agents_dir).unwrap();
+        std::fs::write(
+            agents_dir.join("reviewer.md"),
+            "---\nmodel: review-model\ntemperature: 0.1\ntools: read_file\n---\nYou review code.",
+        )
+        .unwrap();
+        let llm = Arc::new(RecordingLlm::default());
+        let (runtime, mut rx) = create_runtime_in(&temp_dir, llm.clone()).await;
+
+        let input = |agent: &str| TaskInput {
+            settings: Some(TaskSettings {
+                agent: Some(agent.to_string()),
+                ..TaskSettings::default()
+            }),
+            workspace: Some(WorkspaceInfo {
+                root_path: workspace.to_string_lossy().to_string(),
+                worktree_path: None,
+                repository_url: None,
+                branch: None,
+            }),
+            ..task_input("Review the diff")
+        };
+        let error = runtime.start_task(input("missing")).await.unwrap_err();
+        assert!(error.contains("'missing'"));
+
+        runtime.start_task(input("reviewer")).await.unwrap();
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::TaskCompleted { .. })
+        })
+        .await;
+
+        let requests = llm.requests.lock().unwrap();
+        assert_eq!(requests[0].model, "review-model");
+        assert_eq!(requests[0].temperature, Some(0.1));
+        let tools: Vec<&str> = requests[0]
+            .tools
+            .iter()
+            .flatten()
+            .map(|tool| tool.name.as_str())
+            .collect();
+        assert_eq!(tools, vec!["read_file"]);
+        assert!(matches!(
+            &requests[0].messages[0],
+            LlmMessage::System { content, .. } if content == "You review code."
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_stream_is_restored_and_continued() {
+        let temp_dir = TempDir::new().unwrap();
+        let (runtime, _rx) = create_runtime_in(&temp_dir, Arc::new(HangingLlm)).await;
+
+        let handle = runtime
+            .start_task(task_input("Hello"))
+            .await
+            .expect("Failed to start task");
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            let chat_history = &runtime.storage.chat_history;
+            while chat_history
+                .list_stream_states(None)
+                .await
+                .unwrap()
+                .is_empty()
+            {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Stream state was not saved");
+
+        // A new runtime over the same storage finds the run cut short
+        let (restarted, mut rx) = create_runtime_in(&temp_dir, Arc::new(FixedResponseLlm)).await;
+        let interrupted = restarted
+            .list_interrupted_tasks(Some(&handle.session_id))
+            .await
+            .unwrap();
+        assert_eq!(interrupted.len(), 1);
+        let messages = restarted
+            .session_manager()
+            .get_messages(&handle.session_id, None, None)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            interrupted[0].interrupted_message_id.as_deref(),
+            Some(messages[1].id.as_str())
+        );
+        assert!(matches!(
+            &messages[1].content,
+            MessageContent::Text { text } if text == "Thinking"
+        ));
+
+        restarted
+            .continue_task(&handle.task_id)
+            .await
+            .expect("Failed to continue task");
+        wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::TaskCompleted { .. })
+        })
+        .await;
+
+        let messages = restarted
+            .session_manager()
+            .get_messages(&handle.session_id, None, None)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 3);
+        assert!(restarted
+            .list_interrupted_tasks(None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_isolated_task_runs_in_worktree_and_merges_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path().join("workspace");
+        std::fs::create_dir_all(&workspace).unwrap();
+        for args in [
+            vec!["init"],
+            vec!["config", "user.email", "test@test.com"],
+            vec!["config", "user.name", "Test User"],
+            vec!["commit", "--allow-empty", "-m", "Initial commit"],
+        ] {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(&workspace)
+                .output()
+                .unwrap();
+        }
+        let (runtime, mut rx) = create_runtime_in(&temp_dir, Arc::new(FixedResponseLlm)).await;
+
+        let input = TaskInput {
+            workspace: Some(WorkspaceInfo {
+                root_path: workspace.to_string_lossy().to_string(),
+                worktree_path: None,
+                repository_url: None,
+                branch: None,
+            }),
+            isolate: true,
+            ..task_input("Add notes")
+        };
+        let handle = runtime.start_task(input).await.unwrap();
+        let worktree = match wait_for_event(&mut rx, |event| {
+            matches!(event, RuntimeEvent::WorktreeReady { .. })
+        })
+        .await
+        {
+            RuntimeEvent::WorktreeReady { worktree, .. } => worktree,
+            _ => unreachable!(),
+        };
+        assert_eq!(worktree.task_id, handle.task_id);
+        assert_eq!(worktree.branch, format!("talkcody-task-{}", handle.task_id));
+        while runtime.get_task(&handle.task_id).await.is_some() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        // Changes stay in the worktree until merged back
+        std::fs::write(std::path::Path::new(&worktree.path).join("notes.txt"), "hi").unwrap();
+        assert!(!workspace.join("notes.txt").exists());
+        let result = runtime
+            .merge_task_worktree(&handle.task_id, Some("Add notes".to_string()))
+            .await
+            .unwrap();
+        assert!(result.success, "Merge should succeed: {:?}", result);
+        assert!(workspace.join("notes.txt").exists());
+        assert!(!std::path::Path::new(&worktree.path).exists());
+        assert!(runtime
+            .list_task_worktrees(&handle.session_id)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(runtime
+            .discard_task_worktree(&handle.task_id)
+            .await
+            .is_err());
     }
 }
@@ -0,0 +1,561 @@
+//! Tool Sandbox
+//!
+//! A sandbox policy bounds what tool calls may touch: file paths must stay in
+//! the workspace or directories the policy allows, shell commands are matched
+//! against allow and deny rules, and URLs against allowed and denied domains.
+//! The tool dispatcher refuses calls that break the policy.
+//!
+//! Policies are stored under `sandbox_policy` for every project and
+//! `sandbox_policy.<project_id>` for one, which replaces the global policy.
+//! Sessions without a policy get the default one, which only scopes paths,
+//! and cannot auto-approve tool calls.
+
+use crate::core::patch;
+use crate::core::shell::EXECUTE_SHELL_TOOL;
+use crate::core::tools::ToolContext;
+use crate::core::types::ToolRequest;
+use crate::path_guard::normalize;
+use crate::storage::{ChatHistoryRepository, SettingsRepository};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Settings key of the policy applying to every project
+pub const GLOBAL_SANDBOX_KEY: &str = "sandbox_policy";
+
+/// What tool calls may access
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SandboxPolicy {
+    /// Refuse file paths outside the workspace and `allowed_paths`
+    pub restrict_filesystem: bool,
+    /// Directories outside the workspace that tools may use
+    pub allowed_paths: Vec<String>,
+    /// When not empty, every shell command must match one of these
+    pub allowed_commands: Vec<CommandRule>,
+    /// Shell commands that never run
+    pub denied_commands: Vec<CommandRule>,
+    /// When not empty, URLs must be on one of these domains or a subdomain
+    pub allowed_domains: Vec<String>,
+    /// Domains, with their subdomains, that URLs may not point to
+    pub denied_domains: Vec<String>,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self {
+            restrict_filesystem: true,
+            allowed_paths: vec![],
+            allowed_commands: vec![],
+            denied_commands: vec![],
+            allowed_domains: vec![],
+            denied_domains: vec![],
+        }
+    }
+}
+
+/// Matches a program and, optionally, its arguments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandRule {
+    /// Program name, e.g. `git`, or `*` for any program
+    pub command: String,
+    /// Regex searched for in the arguments, joined by spaces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<String>,
+    /// `args` compiled on first use; None when it is not a valid regex
+    #[serde(skip)]
+    args_regex: OnceLock<Option<Regex>>,
+}
+
+impl PartialEq for CommandRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.command == other.command && self.args == other.args
+    }
+}
+
+impl Eq for CommandRule {}
+
+impl CommandRule {
+    pub fn new(command: &str, args: Option<&str>) -> Self {
+        Self {
+            command: command.to_string(),
+            args: args.map(str::to_string),
+            args_regex: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.command.trim().is_empty() {
+            return Err("Command rules need a command".to_string());
+        }
+        if let Some(args) = &self.args {
+            Regex::new(args).map_err(|e| format!("Invalid args pattern '{}': {}", args, e))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn matches(&self, program: &str, args: &str) -> bool {
+        if self.command != "*" && self.command != program {
+            return false;
+        }
+        match &self.args {
+            Some(pattern) => self
+                .args_regex
+                .get_or_init(|| Regex::new(pattern).ok())
+                .as_ref()
+                .is_some_and(|re| re.is_match(args)),
+            None => true,
+        }
+    }
+
+    /// The rule as `program` or `program /args/`
+    pub(crate) fn describe(&self) -> String {
+        match &self.args {
+            Some(args) => format!("{} /{}/", self.command, args),
+            None => self.command.clone(),
+        }
+    }
+}
+
+impl SandboxPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        for rule in self.allowed_commands.iter().chain(&self.denied_commands) {
+            rule.validate()?;
+        }
+        for domain in self.allowed_domains.iter().chain(&self.denied_domains) {
+            if domain.trim().is_empty() || domain.contains('/') {
+                return Err(format!("Invalid domain: '{}'", domain));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a tool call whose relative paths resolve against `root`
+    pub fn check(&self, request: &ToolRequest, root: &Path) -> Result<(), String> {
+        let input = &request.input;
+        if self.restrict_filesystem {
+            for path in input_paths(input) {
+                self.check_path(root, &path)?;
+            }
+        }
+
+        let mut urls = strings(input, "url", "urls");
+        if request.name == EXECUTE_SHELL_TOOL {
+            if let Some(command) = input.get("command").and_then(|v| v.as_str()) {
+                self.check_command(command)?;
+                // Catch curl, wget and the like
+                let url_pattern = Regex::new(r#"https?://[^\s'"<>`)]+"#).expect("valid regex");
+                urls.extend(
+                    url_pattern
+                        .find_iter(command)
+                        .map(|m| m.as_str().to_string()),
+                );
+            }
+        }
+        for url in urls {
+            self.check_url(&url)?;
+        }
+        Ok(())
+    }
+
+    fn check_path(&self, root: &Path, path: &str) -> Result<(), String> {
+        let resolved = normalize(&root.join(path));
+        let inside = std::iter::once(normalize(root))
+            .chain(
+                self.allowed_paths
+                    .iter()
+                    .map(|dir| normalize(&root.join(dir))),
+            )
+            .any(|dir| resolved.starts_with(dir));
+        if inside {
+            Ok(())
+        } else {
+            Err(format!("{} is outside the workspace", path))
+        }
+    }
+
+    fn check_command(&self, command: &str) -> Result<(), String> {
+        for segment in command_segments(command) {
+            let Some((program, args)) = program_and_args(&segment) else {
+                continue;
+            };
+
+            if self
+                .denied_commands
+                .iter()
+                .any(|rule| rule.matches(&program, &args))
+            {
+                return Err(format!("`{}` is a denied command", segment.trim()));
+            }
+            if !self.allowed_commands.is_empty()
+                && !self
+                    .allowed_commands
+                    .iter()
+                    .any(|rule| rule.matches(&program, &args))
+            {
+                return Err(format!("`{}` is not an allowed command", segment.trim()));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_url(&self, url: &str) -> Result<(), String> {
+        let Some(host) = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+        else {
+            return Err(format!("Cannot check the domain of {}", url));
+        };
+        let on_domain = |domain: &String| {
+            let domain = domain.trim_start_matches("*.").to_lowercase();
+            host == domain || host.ends_with(&format!(".{}", domain))
+        };
+
+        if self.denied_domains.iter().any(on_domain) {
+            return Err(format!("{} is a denied domain", host));
+        }
+        if !self.allowed_domains.is_empty() && !self.allowed_domains.iter().any(on_domain) {
+            return Err(format!("{} is not an allowed domain", host));
+        }
+        Ok(())
+    }
+}
+
+/// Loads sandbox policies and checks tool calls against them
+#[derive(Clone)]
+pub struct SandboxManager {
+    settings: SettingsRepository,
+    chat_history: ChatHistoryRepository,
+}
+
+impl SandboxManager {
+    pub fn new(settings: SettingsRepository, chat_history: ChatHistoryRepository) -> Self {
+        Self {
+            settings,
+            chat_history,
+        }
+    }
+
+    /// Policy of a project, or the one for every project when `None`
+    pub async fn policy(&self, project_id: Option<&str>) -> Result<Option<SandboxPolicy>, String> {
+        self.settings
+            .get_setting_or_default(&sandbox_key(project_id), None)
+            .await
+    }
+
+    /// Set or, with `None`, remove the policy of a project, or the one for
+    /// every project
+    pub async fn set_policy(
+        &self,
+        project_id: Option<&str>,
+        policy: Option<SandboxPolicy>,
+    ) -> Result<(), String> {
+        let key = sandbox_key(project_id);
+        let Some(policy) = policy else {
+            return self.settings.delete_setting(&key).await;
+        };
+        policy.validate()?;
+        let value = serde_json::to_value(&policy)
+            .map_err(|e| format!("Failed to serialize sandbox policy: {}", e))?;
+        self.settings.set_setting(&key, &value).await
+    }
+
+    /// Policy configured for a session's project, falling back to the
+    /// global one
+    pub async fn policy_for_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<SandboxPolicy>, String> {
+        let project_id = self
+            .chat_history
+            .get_session(session_id)
+            .await?
+            .and_then(|session| session.project_id);
+        if let Some(project_id) = project_id {
+            if let Some(policy) = self.policy(Some(&project_id)).await? {
+                return Ok(Some(policy));
+            }
+        }
+        self.policy(None).await
+    }
+
+    /// Check a tool call against its session's policy
+    pub async fn check(&self, request: &ToolRequest, context: &ToolContext) -> Result<(), String> {
+        let policy = self
+            .policy_for_session(&context.session_id)
+            .await
+            .map_err(|e| format!("Failed to load sandbox policy: {}", e))?
+            .unwrap_or_default();
+        let root = context
+            .worktree_path
+            .as_deref()
+            .unwrap_or(&context.workspace_root);
+        policy
+            .check(request, Path::new(root))
+            .map_err(|e| format!("Blocked by sandbox policy: {}", e))
+    }
+}
+
+fn sandbox_key(project_id: Option<&str>) -> String {
+    match project_id {
+        Some(project_id) => format!("{}.{}", GLOBAL_SANDBOX_KEY, project_id),
+        None => GLOBAL_SANDBOX_KEY.to_string(),
+    }
+}
+
+/// A string field and a string list field of a tool input
+fn strings(input: &serde_json::Value, key: &str, list_key: &str) -> Vec<String> {
+    let list = input.get(list_key).and_then(|v| v.as_array());
+    input
+        .get(key)
+        .into_iter()
+        .chain(list.into_iter().flatten())
+        .filter_map(|v| v.as_str())
+        .map(str::to_string)
+        .collect()
+}
+
+/// File paths a tool input names
+fn input_paths(input: &serde_json::Value) -> Vec<String> {
+    let mut paths = strings(input, "path", "paths");
+    for key in ["file_path", "cwd"] {
+        paths.extend(strings(input, key, ""));
+    }
+    if let Some(patch) = input.get("patch").and_then(|v| v.as_str()) {
+        paths.extend(patch::patch_paths(patch));
+    }
+    paths.retain(|path| !path.is_empty());
+    paths
+}
+
+/// Split a shell command into the simple commands it runs. Command
+/// substitutions and subshells become commands of their own, so they are
+/// checked too.
+pub(crate) fn command_segments(command: &str) -> Vec<String> {
+    let mut segments = vec![];
+    let mut current = String::new();
+    let mut single_quoted = false;
+    let mut double_quoted = false;
+
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' if !double_quoted => single_quoted = !single_quoted,
+            '"' if !single_quoted => double_quoted = !double_quoted,
+            // Substitutions run even inside double quotes
+            '`' | '(' | ')' if !single_quoted => {
+                segments.push(std::mem::take(&mut current));
+                continue;
+            }
+            // Redirections such as `2>&1` and `&>` do not end a command
+            '&' if current.ends_with('>') || chars.peek() == Some(&'>') => {}
+            ';' | '&' | '|' | '\n' if !single_quoted && !double_quoted => {
+                segments.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    segments.push(current);
+    segments
+        .into_iter()
+        .map(|segment| segment.trim().trim_start_matches('$').to_string())
+        .filter(|segment| !segment.trim_matches('"').trim().is_empty())
+        .collect()
+}
+
+/// Program name and arguments, joined by spaces, of a simple command
+pub(crate) fn program_and_args(segment: &str) -> Option<(String, String)> {
+    let words = split_words(segment);
+    // Skip `NAME=value` environment assignments
+    let mut words = words
+        .iter()
+        .skip_while(|word| word.contains('=') && !word.starts_with('='));
+    let program = words.next()?;
+    let program = Path::new(program)
+        .file_name()
+        .map_or(program.clone(), |name| name.to_string_lossy().to_string());
+    let args = words.cloned().collect::<Vec<_>>().join(" ");
+    Some((program, args))
+}
+
+/// Shells and wrappers running a command given in their arguments, which
+/// the segments of a command line do not show
+const COMMAND_RUNNERS: &[&str] = &[
+    "sh", "bash", "zsh", "dash", "ksh", "fish", "csh", "tcsh", "env", "command", "exec", "nohup",
+    "xargs", "eval", "sudo", "doas", "time", "nice", "timeout", "setsid", "stdbuf", "builtin",
+    "source", ".",
+];
+
+/// Whether a command line may push commits: it runs `git push`, or a shell
+/// or wrapper such as `sh -c` or `xargs` that could run it
+pub(crate) fn may_git_push(command: &str) -> bool {
+    command_segments(command)
+        .iter()
+        .map(String::as_str)
+        .filter_map(program_and_args)
+        .any(|(program, args)| {
+            COMMAND_RUNNERS.contains(&program.as_str())
+                || (program == "git" && git_subcommand(&args) == Some("push"))
+        })
+}
+
+/// First git argument that is neither a global option nor an option's value
+fn git_subcommand(args: &str) -> Option<&str> {
+    let mut words = args.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "-C" | "-c" | "--git-dir" | "--work-tree" | "--namespace" => {
+                words.next();
+            }
+            word if word.starts_with('-') => {}
+            word => return Some(word),
+        }
+    }
+    None
+}
+
+/// Split a simple command into words, removing quotes
+fn split_words(segment: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut current = String::new();
+    let mut quote = None;
+    for c in segment.chars() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (c, None) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            (c, _) => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(name: &str, input: serde_json::Value) -> ToolRequest {
+        ToolRequest {
+            tool_call_id: "call-1".to_string(),
+            name: name.to_string(),
+            input,
+        }
+    }
+
+    fn rule(command: &str, args: Option<&str>) -> CommandRule {
+        CommandRule::new(command, args)
+    }
+
+    #[test]
+    fn test_path_scoping() {
+        let root = Path::new("/work/project");
+        let policy = SandboxPolicy {
+            allowed_paths: vec!["/tmp/scratch".to_string()],
+            ..SandboxPolicy::default()
+        };
+        let check = |input| policy.check(&request("write_file", input), root);
+
+        assert!(check(json!({ "path": "src/main.rs" })).is_ok());
+        assert!(check(json!({ "path": "/work/project/a/../b.txt" })).is_ok());
+        assert!(check(json!({ "path": "/tmp/scratch/out.txt" })).is_ok());
+        assert!(check(json!({ "path": "../other/secret" })).is_err());
+        assert!(check(json!({ "paths": ["ok.txt", "/etc/passwd"] })).is_err());
+        assert!(
+            check(json!({ "patch": "--- /dev/null\n+++ b/../../x\n@@ -0,0 +1 @@\n+hi\n" }))
+                .is_err()
+        );
+
+        let open = SandboxPolicy {
+            restrict_filesystem: false,
+            ..SandboxPolicy::default()
+        };
+        assert!(open
+            .check(
+                &request("write_file", json!({ "path": "/etc/hosts" })),
+                root
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_command_and_domain_rules() {
+        let root = Path::new("/work");
+        let policy = SandboxPolicy {
+            allowed_commands: vec![
+                rule("git", Some(r"^(status|diff|log)\b")),
+                rule("cargo", None),
+                rule("curl", None),
+                rule("echo", None),
+                rule("tail", None),
+            ],
+            denied_commands: vec![rule("cargo", Some("publish"))],
+            allowed_domains: vec!["crates.io".to_string()],
+            denied_domains: vec!["static.crates.io".to_string()],
+            ..SandboxPolicy::default()
+        };
+        let shell = |command: &str| {
+            policy.check(
+                &request(EXECUTE_SHELL_TOOL, json!({ "command": command })),
+                root,
+            )
+        };
+
+        assert!(shell("git status && RUST_LOG=debug cargo test | tail -5").is_ok());
+        assert!(shell("cargo test 2>&1 | tail -5").is_ok());
+        assert!(shell("git push origin main").is_err());
+        assert!(shell("cargo publish --dry-run").is_err());
+        assert!(shell("echo 'a; rm -rf /'").is_ok());
+        assert!(shell("echo \"$(rm -rf /)\"").is_err());
+        assert!(shell("/usr/bin/git diff").is_ok());
+
+        assert!(shell("curl https://index.crates.io/config.json").is_ok());
+        assert!(shell("curl https://static.crates.io/x").is_err());
+        assert!(shell("curl https://example.com").is_err());
+        assert!(policy
+            .check(
+                &request("fetch", json!({ "url": "https://evil.test/" })),
+                root
+            )
+            .is_err());
+
+        assert!(rule("git", Some("(")).validate().is_err());
+    }
+
+    #[test]
+    fn test_may_git_push() {
+        assert!(may_git_push("git push origin main"));
+        assert!(may_git_push("cargo test && git -C ../repo push --force"));
+        assert!(may_git_push("/usr/bin/git -c push.default=current push"));
+        assert!(!may_git_push("git log --grep push"));
+        assert!(!may_git_push("git status; echo 'git push'"));
+        assert!(!may_git_push("git stash push -m wip"));
+
+        // Shells and wrappers could run anything
+        for command in [
+            "sh -c 'git push'",
+            "bash -c \"git push origin main\"",
+            "/bin/zsh ./release.sh",
+            "env git push",
+            "FOO=1 env -i git push",
+            "command git push",
+            "exec git push",
+            "nohup git push &",
+            "echo origin | xargs git push",
+            "cargo build; timeout 60 git status",
+        ] {
+            assert!(may_git_push(command), "{}", command);
+        }
+    }
+}
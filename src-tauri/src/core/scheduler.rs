@@ -0,0 +1,247 @@
+//! Task Scheduler
+//!
+//! Queue of submitted tasks waiting for a run slot. At most `max_concurrent`
+//! tasks run at once; queued tasks start in priority order (highest first,
+//! first come first served within a priority).
+
+use crate::core::types::RuntimeTaskId;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Number of tasks that run at once unless configured otherwise
+pub const DEFAULT_MAX_CONCURRENT_TASKS: usize = 4;
+
+/// A queued task as reported to clients
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedTask {
+    pub task_id: RuntimeTaskId,
+    pub priority: i32,
+    /// Zero-based position; position 0 starts next
+    pub position: usize,
+    pub enqueued_at: i64,
+}
+
+struct QueueEntry<T> {
+    task_id: RuntimeTaskId,
+    priority: i32,
+    enqueued_at: i64,
+    payload: T,
+}
+
+/// Priority queue of tasks plus the set of tasks holding a run slot.
+/// `T` is whatever the runtime needs to start a task.
+pub struct TaskQueue<T> {
+    /// Queued tasks in start order, sorted by descending priority
+    entries: Vec<QueueEntry<T>>,
+    running: HashSet<RuntimeTaskId>,
+    max_concurrent: usize,
+    /// Positions last reported through `position_changes`
+    reported: HashMap<RuntimeTaskId, usize>,
+}
+
+impl<T> TaskQueue<T> {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            running: HashSet::new(),
+            max_concurrent: max_concurrent.max(1),
+            reported: HashMap::new(),
+        }
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    pub fn set_max_concurrent(&mut self, max_concurrent: usize) {
+        self.max_concurrent = max_concurrent.max(1);
+    }
+
+    /// Number of tasks holding a run slot
+    pub fn running_count(&self) -> usize {
+        self.running.len()
+    }
+
+    /// Queue a task behind every task of the same or higher priority
+    pub fn push(&mut self, task_id: RuntimeTaskId, priority: i32, payload: T) {
+        let entry = QueueEntry {
+            task_id,
+            priority,
+            enqueued_at: chrono::Utc::now().timestamp(),
+            payload,
+        };
+        self.insert_by_priority(entry);
+    }
+
+    /// Take the next task if a run slot is free; the task holds the slot until `finish`
+    pub fn next_ready(&mut self) -> Option<(RuntimeTaskId, T)> {
+        if self.running.len() >= self.max_concurrent || self.entries.is_empty() {
+            return None;
+        }
+
+        let entry = self.entries.remove(0);
+        self.running.insert(entry.task_id.clone());
+        Some((entry.task_id, entry.payload))
+    }
+
+    /// Give a slot to a task that bypasses the queue, such as a resumed task
+    pub fn mark_running(&mut self, task_id: &str) {
+        self.running.insert(task_id.to_string());
+    }
+
+    /// Release the run slot of a task
+    pub fn finish(&mut self, task_id: &str) {
+        self.running.remove(task_id);
+    }
+
+    /// Remove a task from the queue, returning its payload if it was queued
+    pub fn remove(&mut self, task_id: &str) -> Option<T> {
+        let index = self.index_of(task_id)?;
+        Some(self.entries.remove(index).payload)
+    }
+
+    pub fn contains(&self, task_id: &str) -> bool {
+        self.index_of(task_id).is_some()
+    }
+
+    /// Change the priority of a queued task, moving it behind the tasks of
+    /// its new priority
+    pub fn set_priority(&mut self, task_id: &str, priority: i32) -> Result<(), String> {
+        let index = self
+            .index_of(task_id)
+            .ok_or_else(|| format!("Task '{}' is not queued", task_id))?;
+        let mut entry = self.entries.remove(index);
+        entry.priority = priority;
+        self.insert_by_priority(entry);
+        Ok(())
+    }
+
+    /// Move a queued task to `position`. Its priority is adjusted to the
+    /// priorities around its new place so later pushes keep the order.
+    pub fn move_to(&mut self, task_id: &str, position: usize) -> Result<(), String> {
+        let index = self
+            .index_of(task_id)
+            .ok_or_else(|| format!("Task '{}' is not queued", task_id))?;
+        let mut entry = self.entries.remove(index);
+        let position = position.min(self.entries.len());
+
+        if let Some(next) = self.entries.get(position) {
+            entry.priority = entry.priority.max(next.priority);
+        }
+        if let Some(previous) = position.checked_sub(1).and_then(|i| self.entries.get(i)) {
+            entry.priority = entry.priority.min(previous.priority);
+        }
+        self.entries.insert(position, entry);
+        Ok(())
+    }
+
+    /// Queued tasks in start order
+    pub fn list(&self) -> Vec<QueuedTask> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(position, entry)| QueuedTask {
+                task_id: entry.task_id.clone(),
+                priority: entry.priority,
+                position,
+                enqueued_at: entry.enqueued_at,
+            })
+            .collect()
+    }
+
+    /// Queued tasks whose position changed since the last call
+    pub fn position_changes(&mut self) -> Vec<(RuntimeTaskId, usize)> {
+        let current: HashMap<RuntimeTaskId, usize> = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(position, entry)| (entry.task_id.clone(), position))
+            .collect();
+
+        let changes = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(position, entry)| self.reported.get(&entry.task_id) != Some(position))
+            .map(|(position, entry)| (entry.task_id.clone(), position))
+            .collect();
+        self.reported = current;
+        changes
+    }
+
+    fn index_of(&self, task_id: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.task_id == task_id)
+    }
+
+    fn insert_by_priority(&mut self, entry: QueueEntry<T>) {
+        let index = self
+            .entries
+            .iter()
+            .position(|queued| queued.priority < entry.priority)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(index, entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(queue: &TaskQueue<()>) -> Vec<String> {
+        queue.list().into_iter().map(|task| task.task_id).collect()
+    }
+
+    #[test]
+    fn test_queue_orders_by_priority_and_limits_concurrency() {
+        let mut queue = TaskQueue::new(1);
+        queue.push("a".to_string(), 0, ());
+        queue.push("b".to_string(), 5, ());
+        queue.push("c".to_string(), 0, ());
+        assert_eq!(ids(&queue), vec!["b", "a", "c"]);
+
+        assert_eq!(queue.next_ready().map(|(id, _)| id), Some("b".to_string()));
+        // The only slot is taken
+        assert!(queue.next_ready().is_none());
+        queue.finish("b");
+        assert_eq!(queue.next_ready().map(|(id, _)| id), Some("a".to_string()));
+        assert_eq!(queue.running_count(), 1);
+    }
+
+    #[test]
+    fn test_reordering_keeps_priority_order() {
+        let mut queue = TaskQueue::new(1);
+        for id in ["a", "b", "c"] {
+            queue.push(id.to_string(), 0, ());
+        }
+
+        queue.set_priority("c", 1).unwrap();
+        assert_eq!(ids(&queue), vec!["c", "a", "b"]);
+
+        queue.move_to("b", 0).unwrap();
+        assert_eq!(ids(&queue), vec!["b", "c", "a"]);
+        assert_eq!(queue.list()[0].priority, 1);
+
+        // New tasks of the same priority still go behind the moved one
+        queue.push("d".to_string(), 1, ());
+        assert_eq!(ids(&queue), vec!["b", "c", "d", "a"]);
+        assert!(queue.move_to("missing", 0).is_err());
+    }
+
+    #[test]
+    fn test_position_changes() {
+        let mut queue = TaskQueue::new(1);
+        queue.push("a".to_string(), 0, ());
+        queue.push("b".to_string(), 0, ());
+        assert_eq!(
+            queue.position_changes(),
+            vec![("a".to_string(), 0), ("b".to_string(), 1)]
+        );
+        assert!(queue.position_changes().is_empty());
+
+        queue.remove("a");
+        assert_eq!(queue.position_changes(), vec![("b".to_string(), 0)]);
+    }
+}
@@ -0,0 +1,356 @@
+//! Script Tools
+//!
+//! Custom tools defined in a workspace under `.talkcody/tools`, one `*.json`
+//! file per tool. A tool runs a shell command from the workspace root with
+//! its input in `TALKCODY_TOOL_INPUT` and each top-level argument in a
+//! `TALKCODY_ARG_<NAME>` variable. Its stdout is the result, parsed as JSON
+//! when possible, and a non-zero exit fails the call. Definitions are loaded
+//! when a task starts, so edits apply to the next task.
+
+use crate::core::cancellation::run_command;
+use crate::core::tools::{ToolExecutionOutput, ToolHandler};
+use crate::core::types::ToolDefinition;
+use crate::storage::ToolCapability;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Directory under the workspace root holding tool definitions
+pub const TOOLS_DIR: &str = ".talkcody/tools";
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+const MAX_TIMEOUT_SECS: u64 = 600;
+
+/// Tool backed by a command, defined by a file in the workspace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptTool {
+    /// Defaults to the file name without its extension
+    #[serde(default)]
+    pub name: String,
+    pub description: String,
+    /// Shell command run from the workspace root
+    pub command: String,
+    /// JSON schema of the tool input
+    #[serde(default = "empty_schema")]
+    pub parameters: serde_json::Value,
+    /// Extra environment variables for the command
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default = "default_requires_approval")]
+    pub requires_approval: bool,
+    #[serde(default)]
+    pub modifies_files: bool,
+    /// File the definition was loaded from
+    #[serde(default)]
+    pub path: String,
+}
+
+fn empty_schema() -> serde_json::Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+fn default_requires_approval() -> bool {
+    true
+}
+
+impl ScriptTool {
+    fn validate(&self) -> Result<(), String> {
+        let valid_name = !self.name.is_empty()
+            && self.name.len() <= 64
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_name {
+            return Err(format!(
+                "name '{}' must be 1-64 letters, digits, '_' or '-'",
+                self.name
+            ));
+        }
+        if self.command.trim().is_empty() {
+            return Err("command is empty".to_string());
+        }
+        if self.parameters.get("type").and_then(|v| v.as_str()) != Some("object") {
+            return Err("parameters must be a JSON schema of type object".to_string());
+        }
+        Ok(())
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.timeout_secs
+                .unwrap_or(DEFAULT_TIMEOUT_SECS)
+                .clamp(1, MAX_TIMEOUT_SECS),
+        )
+    }
+
+    pub fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            parameters: self.parameters.clone(),
+            requires_approval: self.requires_approval,
+            modifies_files: self.modifies_files,
+            capabilities: vec![ToolCapability::Shell, ToolCapability::GitPush],
+        }
+    }
+
+    /// Definition and handler for a tool registry
+    pub fn into_tool(self) -> (ToolDefinition, ToolHandler) {
+        let definition = self.definition();
+        let tool = Arc::new(self);
+        let handler: ToolHandler = Arc::new(move |request, context| {
+            let tool = tool.clone();
+            Box::pin(async move {
+                let root = context
+                    .worktree_path
+                    .clone()
+                    .unwrap_or_else(|| context.workspace_root.clone());
+                let mut command = tool.command(Path::new(&root), &request.input);
+                command
+                    .env("TALKCODY_SESSION_ID", &context.session_id)
+                    .env("TALKCODY_TASK_ID", &context.task_id);
+
+                let result = tokio::time::timeout(
+                    tool.timeout(),
+                    run_command(command, &context.cancel_token),
+                )
+                .await
+                .unwrap_or_else(|_| Err(format!("Timed out after {}s", tool.timeout().as_secs())));
+                match result.and_then(|output| tool.result(output)) {
+                    Ok(data) => ToolExecutionOutput {
+                        success: true,
+                        data,
+                        error: None,
+                    },
+                    Err(e) => ToolExecutionOutput {
+                        success: false,
+                        data: serde_json::Value::Null,
+                        error: Some(e),
+                    },
+                }
+            })
+        });
+        (definition, handler)
+    }
+
+    /// The command for a call, with its input in the environment
+    fn command(&self, root: &Path, input: &serde_json::Value) -> Command {
+        let mut command = if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.arg("/C");
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c");
+            command
+        };
+        command
+            .arg(&self.command)
+            .current_dir(root)
+            .stdin(std::process::Stdio::null())
+            .envs(&self.env)
+            .env("TALKCODY_TOOL_NAME", &self.name)
+            .env("TALKCODY_TOOL_INPUT", input.to_string())
+            .env("TALKCODY_WORKSPACE_ROOT", root);
+        for (name, value) in input.as_object().into_iter().flatten() {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            command.env(arg_variable(name), value);
+        }
+        command
+    }
+
+    fn result(&self, output: std::process::Output) -> Result<serde_json::Value, String> {
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() {
+            return Ok(serde_json::from_str(&stdout).unwrap_or(serde_json::Value::String(stdout)));
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let detail = if stderr.trim().is_empty() {
+            stdout
+        } else {
+            stderr.trim().to_string()
+        };
+        let status = match output.status.code() {
+            Some(code) => format!("{} exited with code {}", self.name, code),
+            None => format!("{} was terminated", self.name),
+        };
+        Err(if detail.is_empty() {
+            status
+        } else {
+            format!("{}: {}", status, detail)
+        })
+    }
+}
+
+/// `TALKCODY_ARG_<NAME>`, with the argument name upper-cased and anything
+/// but letters and digits replaced by `_`
+fn arg_variable(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("TALKCODY_ARG_{}", name)
+}
+
+/// Parse a tool definition file
+pub fn parse_tool_file(path: &Path, content: &str) -> Result<ScriptTool, String> {
+    let mut tool: ScriptTool = serde_json::from_str(content)
+        .map_err(|e| format!("Invalid tool definition {}: {}", path.display(), e))?;
+    tool.name = tool.name.trim().to_string();
+    if tool.name.is_empty() {
+        tool.name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+    }
+    tool.validate()
+        .map_err(|e| format!("Invalid tool definition {}: {}", path.display(), e))?;
+    tool.path = path.to_string_lossy().to_string();
+    Ok(tool)
+}
+
+/// Load the tools of a workspace, sorted by file name. Invalid files and
+/// names defined twice are skipped with a warning.
+pub fn load_tools(workspace_root: &Path) -> Vec<ScriptTool> {
+    let Ok(entries) = std::fs::read_dir(workspace_root.join(TOOLS_DIR)) else {
+        return vec![];
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some("json")
+        })
+        .collect();
+    paths.sort();
+
+    let mut names = HashSet::new();
+    let mut tools = Vec::new();
+    for path in paths {
+        let tool = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+            .and_then(|content| parse_tool_file(&path, &content));
+        match tool {
+            Ok(tool) if names.insert(tool.name.clone()) => tools.push(tool),
+            Ok(tool) => log::warn!(
+                "Skipping {}: tool '{}' is already defined",
+                path.display(),
+                tool.name
+            ),
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+
+    tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::cancellation::CancellationToken;
+    use crate::core::tools::{ToolContext, ToolRegistry};
+    use crate::core::types::ToolRequest;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_tools() {
+        let temp_dir = TempDir::new().unwrap();
+        let tools_dir = temp_dir.path().join(TOOLS_DIR);
+        std::fs::create_dir_all(&tools_dir).unwrap();
+        std::fs::write(
+            tools_dir.join("deploy_preview.json"),
+            r#"{ "description": "Deploy a preview", "command": "./deploy.sh",
+                 "parameters": { "type": "object", "properties": { "branch": { "type": "string" } } } }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            tools_dir.join("invalid.json"),
+            r#"{ "name": "bad name", "description": "x", "command": "true" }"#,
+        )
+        .unwrap();
+        std::fs::write(tools_dir.join("notes.txt"), "ignored").unwrap();
+
+        let tools = load_tools(temp_dir.path());
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "deploy_preview");
+        assert!(tools[0].requires_approval);
+        assert_eq!(tools[0].timeout(), Duration::from_secs(60));
+        assert_eq!(arg_variable("base-url"), "TALKCODY_ARG_BASE_URL");
+        assert!(load_tools(&temp_dir.path().join("missing")).is_empty());
+
+        // Scripts could push commits, so sessions must permit that too
+        assert_eq!(
+            tools[0].definition().capabilities,
+            vec![ToolCapability::Shell, ToolCapability::GitPush]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_script_tool_runs_in_layered_registry() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = ScriptTool {
+            name: "greet".to_string(),
+            description: "Greet someone".to_string(),
+            command: r#"printf '{"greeting": "%s %s"}' "$GREETING" "$TALKCODY_ARG_NAME""#
+                .to_string(),
+            parameters: empty_schema(),
+            env: HashMap::from([("GREETING".to_string(), "hello".to_string())]),
+            timeout_secs: None,
+            requires_approval: false,
+            modifies_files: false,
+            path: String::new(),
+        };
+        let failing = ScriptTool {
+            name: "fail".to_string(),
+            command: "echo broken >&2; exit 3".to_string(),
+            ..tool.clone()
+        };
+
+        let builtin = Arc::new(ToolRegistry::create_default().await);
+        let registry = ToolRegistry::layered(builtin, vec![tool.into_tool(), failing.into_tool()]);
+        assert!(registry.get_definition("read_file").await.is_some());
+        assert!(!registry.requires_approval("greet").await);
+
+        let context = ToolContext {
+            session_id: "session-1".to_string(),
+            task_id: "task-1".to_string(),
+            workspace_root: temp_dir.path().to_string_lossy().to_string(),
+            worktree_path: None,
+            settings: Default::default(),
+            cancel_token: CancellationToken::new(),
+        };
+        let call = |name: &str| ToolRequest {
+            tool_call_id: "call-1".to_string(),
+            name: name.to_string(),
+            input: serde_json::json!({ "name": "ada" }),
+        };
+
+        let result = registry.execute(call("greet"), context.clone()).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.output["greeting"], "hello ada");
+
+        let result = registry.execute(call("fail"), context).await;
+        assert!(!result.success);
+        assert_eq!(
+            result.error.as_deref(),
+            Some("fail exited with code 3: broken")
+        );
+    }
+}
@@ -5,12 +5,16 @@
 
 use crate::core::types::*;
 use crate::storage::{
-    Message, MessageContent, MessageRole, Session, SessionId, SessionStatus, Storage, TaskSettings,
+    Message, MessageContent, MessageRole, Page, PageRequest, Session, SessionId, SessionSort,
+    SessionStatus, Storage, TaskSettings,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Title of a session created without one
+pub const DEFAULT_SESSION_TITLE: &str = "New Session";
+
 /// Session manager handles session lifecycle and operations
 pub struct SessionManager {
     storage: Storage,
@@ -47,12 +51,16 @@ impl SessionManager {
         let session = Session {
             id: session_id.clone(),
             project_id,
-            title: title.or_else(|| Some("New Session".to_string())),
+            title: title.or_else(|| Some(DEFAULT_SESSION_TITLE.to_string())),
+            summary: None,
             status: SessionStatus::Created,
             created_at: now,
             updated_at: now,
             last_event_id: None,
             metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
         };
 
         // Persist session
@@ -226,6 +234,26 @@ impl SessionManager {
         Ok(())
     }
 
+    /// Update session summary
+    pub async fn update_session_summary(
+        &self,
+        session_id: &str,
+        summary: &str,
+    ) -> Result<(), String> {
+        self.storage
+            .chat_history
+            .update_session_summary(session_id, summary)
+            .await?;
+
+        let active = self.active_sessions.read().await;
+        if let Some(state) = active.get(session_id) {
+            let mut state = state.write().await;
+            state.session.summary = Some(summary.to_string());
+        }
+
+        Ok(())
+    }
+
     /// Add a message to a session
     pub async fn add_message(&self, message: Message) -> Result<(), String> {
         // Persist message
@@ -254,17 +282,19 @@ impl SessionManager {
             .await
     }
 
-    /// List sessions with optional filters
+    /// List a page of sessions with optional filters
     pub async fn list_sessions(
         &self,
         project_id: Option<&str>,
+        user_id: Option<&str>,
         status: Option<SessionStatus>,
-        limit: Option<usize>,
-        offset: Option<usize>,
-    ) -> Result<Vec<Session>, String> {
+        archived: bool,
+        sort: SessionSort,
+        page: &PageRequest,
+    ) -> Result<Page<Session>, String> {
         self.storage
             .chat_history
-            .list_sessions(project_id, status, limit, offset)
+            .list_sessions(project_id, user_id, status, archived, sort, page)
             .await
     }
 
@@ -0,0 +1,113 @@
+//! Session Titles and Summaries
+//!
+//! After a session's first exchange and whenever one of its tasks completes,
+//! the task's title model names the session and writes a running summary of
+//! the conversation. A title the user chose is kept.
+
+use crate::core::compaction;
+use crate::core::llm::LlmClient;
+use crate::llm::ai_services::stream_collector::StreamCollector;
+use crate::llm::types::StreamEvent;
+use crate::storage::Message;
+
+/// Longest conversation excerpt sent to the model; older text is dropped
+const MAX_HISTORY_CHARS: usize = 16_000;
+
+/// Generated title and summary of a session
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSummary {
+    pub title: String,
+    pub summary: String,
+}
+
+/// Prompt asking for a title and a summary of the conversation, building on
+/// the previous summary when there is one
+pub fn build_prompt(messages: &[Message], previous_summary: Option<&str>) -> String {
+    let history = compaction::format_history(messages);
+    let mut start = history.len().saturating_sub(MAX_HISTORY_CHARS);
+    while !history.is_char_boundary(start) {
+        start += 1;
+    }
+
+    let previous = previous_summary
+        .map(|summary| format!("Previous summary:\n{}\n\n", summary))
+        .unwrap_or_default();
+    format!(
+        "Name this conversation between a user and a coding assistant and summarize it.\n\n\
+         {}Conversation:\n{}\n\n\
+         Reply in exactly this format:\n\
+         Title: <3-8 words in title case>\n\
+         Summary: <2-4 sentences on what the user wants and what has been done so far>",
+        previous,
+        &history[start..]
+    )
+}
+
+/// Parse a `Title: ...` / `Summary: ...` reply; the summary may span lines
+pub fn parse_response(text: &str) -> Option<SessionSummary> {
+    let mut title = None;
+    let mut summary: Option<Vec<&str>> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(value) = strip_label(trimmed, "title:") {
+            title = Some(value.trim_matches(['"', '“', '”', '*']).trim());
+        } else if let Some(value) = strip_label(trimmed, "summary:") {
+            summary = Some(vec![value]);
+        } else if let Some(lines) = summary.as_mut().filter(|_| !trimmed.is_empty()) {
+            lines.push(trimmed);
+        }
+    }
+
+    let title = title.filter(|title| !title.is_empty())?.to_string();
+    let summary = summary?.join(" ").trim().to_string();
+    (!summary.is_empty()).then_some(SessionSummary { title, summary })
+}
+
+fn strip_label<'a>(line: &'a str, label: &str) -> Option<&'a str> {
+    let line = line.trim_start_matches(['*', '#']).trim_start();
+    let head = line.get(..label.len())?;
+    head.eq_ignore_ascii_case(label)
+        .then(|| line[label.len()..].trim_start_matches('*').trim())
+}
+
+/// Ask `model` for a title and summary of the conversation
+pub async fn generate(
+    llm: &dyn LlmClient,
+    model: Option<String>,
+    messages: &[Message],
+    previous_summary: Option<&str>,
+) -> Result<SessionSummary, String> {
+    let prompt = build_prompt(messages, previous_summary);
+    let request = StreamCollector::create_completion_request(model.unwrap_or_default(), prompt);
+
+    let mut text = String::new();
+    llm.stream(request, &mut |event| {
+        if let StreamEvent::TextDelta { text: delta } = event {
+            text.push_str(&delta);
+        }
+    })
+    .await?;
+
+    parse_response(&text).ok_or_else(|| format!("Unexpected session summary reply: {}", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let parsed = parse_response(
+            "**Title:** \"Fix Login Redirect\"\nSummary: The user reported a redirect loop.\nThe session cookie was fixed.",
+        )
+        .unwrap();
+        assert_eq!(parsed.title, "Fix Login Redirect");
+        assert_eq!(
+            parsed.summary,
+            "The user reported a redirect loop. The session cookie was fixed."
+        );
+
+        assert!(parse_response("Fix Login Redirect").is_none());
+        assert!(parse_response("Title: Fix Login\nSummary:").is_none());
+    }
+}
@@ -0,0 +1,486 @@
+//! Shell Tool
+//!
+//! `execute_shell` runs a command in a pseudo-terminal, so programs behave as
+//! they would for a user: colors, progress output and prompts all work.
+//! Output is streamed as `ToolOutput` events while the command runs, input
+//! can be written to its terminal from outside, and cancelling the task
+//! kills it.
+
+use crate::core::tools::{ToolContext, ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::{EventSender, RuntimeEvent, ToolCallId, ToolDefinition, ToolRequest};
+use crate::path_guard;
+use crate::storage::ToolCapability;
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+pub const EXECUTE_SHELL_TOOL: &str = "execute_shell";
+
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+const MAX_TIMEOUT_SECS: u64 = 3600;
+/// Output returned to the model, in characters; the end is kept
+const MAX_OUTPUT_CHARS: usize = 30_000;
+/// How long output is still read after the command exited, since processes
+/// it started in the background may keep the terminal open
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+
+const PTY_SIZE: PtySize = PtySize {
+    rows: 40,
+    cols: 120,
+    pixel_width: 0,
+    pixel_height: 0,
+};
+
+/// Runs `execute_shell` calls and holds the terminals of running ones
+#[derive(Clone)]
+pub struct ShellTool {
+    event_sender: EventSender,
+    /// Terminal input of running commands by tool call
+    writers: Arc<Mutex<HashMap<ToolCallId, Box<dyn Write + Send>>>>,
+}
+
+impl ShellTool {
+    pub fn new(event_sender: EventSender) -> Self {
+        Self {
+            event_sender,
+            writers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Register the `execute_shell` tool
+    pub async fn register_tool(&self, registry: &ToolRegistry) -> Result<(), String> {
+        let shell = self.clone();
+        let handler: ToolHandler = Arc::new(move |request, context| {
+            let shell = shell.clone();
+            Box::pin(async move { shell.execute(&request, &context).await })
+        });
+
+        registry.register(execute_shell_definition(), handler).await
+    }
+
+    /// Write to the terminal of a running `execute_shell` call
+    pub fn write_input(&self, tool_call_id: &str, input: &str) -> Result<(), String> {
+        let mut writers = self
+            .writers
+            .lock()
+            .map_err(|e| format!("Failed to lock shell terminals: {}", e))?;
+        let writer = writers
+            .get_mut(tool_call_id)
+            .ok_or_else(|| format!("No running command for tool call '{}'", tool_call_id))?;
+        writer
+            .write_all(input.as_bytes())
+            .and_then(|_| writer.flush())
+            .map_err(|e| format!("Failed to write to the command: {}", e))
+    }
+
+    async fn execute(&self, request: &ToolRequest, context: &ToolContext) -> ToolExecutionOutput {
+        let input = &request.input;
+        let Some(command) = input.get("command").and_then(|v| v.as_str()) else {
+            return failure(serde_json::Value::Null, "Missing 'command'".to_string());
+        };
+        let root = context
+            .worktree_path
+            .as_deref()
+            .unwrap_or(&context.workspace_root);
+        let cwd = match input.get("cwd").and_then(|v| v.as_str()) {
+            Some(cwd) => match path_guard::resolve_relative(Path::new(root), cwd) {
+                Ok(cwd) => cwd,
+                Err(e) => return failure(serde_json::Value::Null, e),
+            },
+            None => PathBuf::from(root),
+        };
+        let timeout_secs = input
+            .get("timeoutSecs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_TIMEOUT_SECS)
+            .clamp(1, MAX_TIMEOUT_SECS);
+        let stdin = input.get("input").and_then(|v| v.as_str());
+
+        let mut output = TerminalOutput::default();
+        let result = self
+            .run(
+                request,
+                context,
+                command,
+                &cwd,
+                stdin,
+                timeout_secs,
+                &mut output,
+            )
+            .await;
+        if let Ok(mut writers) = self.writers.lock() {
+            writers.remove(&request.tool_call_id);
+        }
+
+        match result {
+            Ok(exit_code) => {
+                let data = output.to_json(exit_code);
+                match exit_code {
+                    Some(0) => ToolExecutionOutput {
+                        success: true,
+                        data,
+                        error: None,
+                    },
+                    Some(code) => failure(data, format!("Command exited with code {}", code)),
+                    None => failure(data, "Command was terminated".to_string()),
+                }
+            }
+            Err(e) => failure(output.to_json(None), e),
+        }
+    }
+
+    /// Run a command to completion, streaming its output into `output`.
+    /// Returns the exit code, or None when a signal ended the command.
+    #[allow(clippy::too_many_arguments)]
+    async fn run(
+        &self,
+        request: &ToolRequest,
+        context: &ToolContext,
+        command: &str,
+        cwd: &Path,
+        stdin: Option<&str>,
+        timeout_secs: u64,
+        output: &mut TerminalOutput,
+    ) -> Result<Option<u32>, String> {
+        let pair = native_pty_system()
+            .openpty(PTY_SIZE)
+            .map_err(|e| format!("Failed to open a terminal: {}", e))?;
+        let mut builder = if cfg!(windows) {
+            let mut builder = CommandBuilder::new("cmd");
+            builder.args(["/C", command]);
+            builder
+        } else {
+            let mut builder = CommandBuilder::new("sh");
+            builder.args(["-c", command]);
+            builder
+        };
+        builder.cwd(cwd);
+        builder.env("TERM", "xterm-256color");
+
+        let mut child = pair
+            .slave
+            .spawn_command(builder)
+            .map_err(|e| format!("Failed to spawn command: {}", e))?;
+        // The reader only ends once no process holds the terminal open
+        drop(pair.slave);
+        let mut killer = child.clone_killer();
+
+        let terminal = pair.master.try_clone_reader().and_then(|reader| {
+            let writer = pair.master.take_writer()?;
+            Ok((reader, writer))
+        });
+        let (mut reader, mut writer) = match terminal {
+            Ok(terminal) => terminal,
+            Err(e) => {
+                let _ = killer.kill();
+                return Err(format!("Failed to attach to the terminal: {}", e));
+            }
+        };
+        if let Some(stdin) = stdin {
+            let _ = writer
+                .write_all(stdin.as_bytes())
+                .and_then(|_| writer.flush());
+        }
+        if let Ok(mut writers) = self.writers.lock() {
+            writers.insert(request.tool_call_id.clone(), writer);
+        }
+
+        let (chunk_sender, mut chunks) = mpsc::unbounded_channel::<Vec<u8>>();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    // Reading fails with EIO on Linux once the terminal closes
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if chunk_sender.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        let mut exit = tokio::task::spawn_blocking(move || child.wait());
+        let deadline = tokio::time::sleep(Duration::from_secs(timeout_secs));
+        tokio::pin!(deadline);
+
+        let mut status = None;
+        loop {
+            tokio::select! {
+                chunk = chunks.recv() => match chunk {
+                    Some(bytes) => self.emit(request, context, output, &bytes),
+                    None => break,
+                },
+                result = &mut exit, if status.is_none() => status = Some(result),
+                _ = context.cancel_token.cancelled() => {
+                    let _ = killer.kill();
+                    return Err("Cancelled".to_string());
+                }
+                _ = &mut deadline => {
+                    let _ = killer.kill();
+                    return Err(format!("Command was stopped after {}s", timeout_secs));
+                }
+            }
+            if status.is_some() {
+                while let Ok(Some(bytes)) = tokio::time::timeout(DRAIN_TIMEOUT, chunks.recv()).await
+                {
+                    self.emit(request, context, output, &bytes);
+                }
+                break;
+            }
+        }
+
+        let status = match status {
+            Some(status) => status,
+            None => exit.await,
+        };
+        match status {
+            Ok(Ok(status)) => Ok(status.signal().is_none().then(|| status.exit_code())),
+            Ok(Err(e)) => Err(format!("Failed to wait for command: {}", e)),
+            Err(e) => Err(format!("Failed to wait for command: {}", e)),
+        }
+    }
+
+    /// Record a chunk of terminal output and stream it to subscribers
+    fn emit(
+        &self,
+        request: &ToolRequest,
+        context: &ToolContext,
+        output: &mut TerminalOutput,
+        bytes: &[u8],
+    ) {
+        let text = output.push(bytes);
+        if text.is_empty() {
+            return;
+        }
+        let _ = self.event_sender.send(RuntimeEvent::ToolOutput {
+            task_id: context.task_id.clone(),
+            tool_call_id: request.tool_call_id.clone(),
+            data: text,
+        });
+    }
+}
+
+/// Terminal output of a command, decoded as it arrives
+#[derive(Debug, Default)]
+struct TerminalOutput {
+    text: String,
+    /// Bytes of a character split across chunks
+    pending: Vec<u8>,
+    /// Whether the start of the output was dropped
+    truncated: bool,
+}
+
+impl TerminalOutput {
+    /// Decode a chunk and return its text
+    fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut text = String::new();
+        loop {
+            match std::str::from_utf8(&self.pending) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    self.pending.clear();
+                    break;
+                }
+                Err(e) => {
+                    let valid = e.valid_up_to();
+                    text.push_str(&String::from_utf8_lossy(&self.pending[..valid]));
+                    match e.error_len() {
+                        // An incomplete character at the end; wait for the rest
+                        None => {
+                            self.pending.drain(..valid);
+                            break;
+                        }
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            self.pending.drain(..valid + len);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.text.push_str(&text);
+        // Keep some slack so escape sequences cut at the start are rare
+        if self.text.len() > MAX_OUTPUT_CHARS * 8 {
+            let mut start = self.text.len() - MAX_OUTPUT_CHARS * 4;
+            while !self.text.is_char_boundary(start) {
+                start += 1;
+            }
+            self.text.drain(..start);
+            self.truncated = true;
+        }
+        text
+    }
+
+    fn to_json(&self, exit_code: Option<u32>) -> serde_json::Value {
+        let plain = strip_ansi(&self.text);
+        let count = plain.chars().count();
+        let (output, truncated) = if count > MAX_OUTPUT_CHARS {
+            (plain.chars().skip(count - MAX_OUTPUT_CHARS).collect(), true)
+        } else {
+            (plain, self.truncated)
+        };
+        serde_json::json!({
+            "exitCode": exit_code,
+            "output": output,
+            "truncated": truncated,
+        })
+    }
+}
+
+/// Remove ANSI escape sequences and carriage-return overwrites, leaving the
+/// text a terminal would show
+pub fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            plain.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters up to a final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: up to BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' {
+                        break;
+                    }
+                    if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    plain
+        .split('\n')
+        .map(|line| {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            // A carriage return redraws the line, as progress bars do
+            line.rsplit('\r').next().unwrap_or(line)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn failure(data: serde_json::Value, error: String) -> ToolExecutionOutput {
+    ToolExecutionOutput {
+        success: false,
+        data,
+        error: Some(error),
+    }
+}
+
+fn execute_shell_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: EXECUTE_SHELL_TOOL.to_string(),
+        description: "Execute a shell command in a terminal and return its exit code and output"
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "command": {
+                    "type": "string",
+                    "description": "Command to execute"
+                },
+                "cwd": {
+                    "type": "string",
+                    "description": "Working directory relative to the workspace"
+                },
+                "input": {
+                    "type": "string",
+                    "description": "Text typed into the terminal after the command starts"
+                },
+                "timeoutSecs": {
+                    "type": "integer",
+                    "description": "Seconds after which the command is stopped (default 600)"
+                }
+            },
+            "required": ["command"]
+        }),
+        requires_approval: true,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::Shell],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::cancellation::CancellationToken;
+    use crate::storage::TaskSettings;
+
+    #[test]
+    fn test_strip_ansi() {
+        let text = "\u{1b}[1;32mok\u{1b}[0m done\r\n\u{1b}]0;title\u{7}10%\r50%\r100%\r\n";
+        assert_eq!(strip_ansi(text), "ok done\n100%\n");
+
+        let mut output = TerminalOutput::default();
+        let bytes = "héllo".as_bytes();
+        assert_eq!(output.push(&bytes[..2]), "h");
+        assert_eq!(output.push(&bytes[2..]), "éllo");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_streams_output_and_takes_input() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let shell = ShellTool::new(sender);
+        let context = ToolContext {
+            session_id: "session-1".to_string(),
+            task_id: "task-1".to_string(),
+            workspace_root: temp_dir.path().to_string_lossy().to_string(),
+            worktree_path: None,
+            settings: TaskSettings::default(),
+            cancel_token: CancellationToken::new(),
+        };
+        let request = ToolRequest {
+            tool_call_id: "call-1".to_string(),
+            name: EXECUTE_SHELL_TOOL.to_string(),
+            input: serde_json::json!({
+                "command": "read name; printf '\\033[1mhi %s\\033[0m\\n' \"$name\"; exit 3",
+                "input": "there\n",
+            }),
+        };
+
+        let result = shell.execute(&request, &context).await;
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("Command exited with code 3"));
+        assert_eq!(result.data["exitCode"], 3);
+        assert!(result.data["output"].as_str().unwrap().contains("hi there"));
+        assert!(!result.data["output"].as_str().unwrap().contains('\u{1b}'));
+
+        let mut streamed = String::new();
+        while let Ok(RuntimeEvent::ToolOutput { data, .. }) = receiver.try_recv() {
+            streamed.push_str(&data);
+        }
+        assert!(streamed.contains("\u{1b}[1mhi there"));
+        assert!(shell.write_input("call-1", "x").is_err());
+
+        let request = ToolRequest {
+            input: serde_json::json!({ "command": "sleep 30" }),
+            ..request
+        };
+        context.cancel_token.cancel();
+        let result = shell.execute(&request, &context).await;
+        assert_eq!(result.error.as_deref(), Some("Cancelled"));
+    }
+}
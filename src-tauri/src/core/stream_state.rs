@@ -0,0 +1,162 @@
+//! Durable Streaming State
+//!
+//! While a response streams, the agent loop hands its partial output to a
+//! [`StreamStateRecorder`], which stores it with the messages of the run that
+//! are not saved yet. A state still stored on startup belongs to a run cut
+//! short by a crash: its output is written to the session, the partial
+//! message is marked as interrupted, and the task waits for the user to
+//! continue it.
+
+use crate::core::agent_loop::AgentLoopContext;
+use crate::storage::{
+    AgentId, ChatHistoryRepository, Message, MessageContent, MessageRole, StreamState, ToolCall,
+};
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Shortest time between two saves of a streaming response
+pub const SAVE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Saves the streamed output of one agent run
+#[derive(Clone)]
+pub struct StreamStateRecorder {
+    chat_history: ChatHistoryRepository,
+    agent_id: Option<AgentId>,
+    created_at: i64,
+    /// Messages already stored when the run started
+    history: HashSet<String>,
+}
+
+impl StreamStateRecorder {
+    pub fn new(
+        chat_history: ChatHistoryRepository,
+        agent_id: Option<AgentId>,
+        created_at: i64,
+        history: HashSet<String>,
+    ) -> Self {
+        Self {
+            chat_history,
+            agent_id,
+            created_at,
+            history,
+        }
+    }
+
+    /// Store the output streamed so far. Failures are logged and do not
+    /// stop the run.
+    pub async fn save(&self, ctx: &AgentLoopContext, text: &str, tool_calls: &[ToolCall]) {
+        let summaries: HashSet<&str> = ctx.compactions.iter().map(|c| c.id.as_str()).collect();
+        let state = StreamState {
+            task_id: ctx.task_id.clone(),
+            session_id: ctx.session_id.clone(),
+            agent_id: self.agent_id.clone(),
+            messages: ctx
+                .messages
+                .iter()
+                .filter(|message| {
+                    !self.history.contains(&message.id) && !summaries.contains(message.id.as_str())
+                })
+                .cloned()
+                .collect(),
+            text: text.to_string(),
+            tool_calls: tool_calls.to_vec(),
+            usage: ctx.usage.clone(),
+            settings: ctx.settings.clone(),
+            workspace_root: ctx.workspace_root.clone(),
+            worktree_path: ctx.worktree_path.clone(),
+            created_at: self.created_at,
+            updated_at: chrono::Utc::now().timestamp(),
+            interrupted_at: None,
+            interrupted_message_id: None,
+        };
+
+        if let Err(e) = self.chat_history.save_stream_state(&state).await {
+            log::warn!("Failed to save stream state of task {}: {}", ctx.task_id, e);
+        }
+    }
+}
+
+/// Messages to store for an interrupted run: the messages it had produced,
+/// then its partial text and the tool calls it had received. Also returns
+/// the ID of the partial text message, if there was any text.
+pub fn interrupted_messages(state: &StreamState) -> (Vec<Message>, Option<String>) {
+    let now = chrono::Utc::now().timestamp();
+    let new_message = |content: MessageContent| Message {
+        id: format!("msg_{}", uuid::Uuid::new_v4()),
+        session_id: state.session_id.clone(),
+        role: MessageRole::Assistant,
+        content,
+        created_at: now,
+        tool_call_id: None,
+        parent_id: None,
+        pinned: false,
+    };
+
+    let mut messages = state.messages.clone();
+    let mut partial_id = None;
+    if !state.text.is_empty() {
+        let message = new_message(MessageContent::Text {
+            text: state.text.clone(),
+        });
+        partial_id = Some(message.id.clone());
+        messages.push(message);
+    }
+    if !state.tool_calls.is_empty() {
+        messages.push(new_message(MessageContent::ToolCalls {
+            calls: state.tool_calls.clone(),
+        }));
+    }
+
+    (messages, partial_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{BudgetUsage, TaskSettings};
+
+    #[test]
+    fn test_interrupted_messages() {
+        let state = StreamState {
+            task_id: "task-1".to_string(),
+            session_id: "session-1".to_string(),
+            agent_id: None,
+            messages: vec![],
+            text: "Looking at the".to_string(),
+            tool_calls: vec![ToolCall {
+                id: "call-1".to_string(),
+                name: "read_file".to_string(),
+                input: serde_json::json!({ "path": "src/main.rs" }),
+            }],
+            usage: BudgetUsage::default(),
+            settings: TaskSettings::default(),
+            workspace_root: "/tmp".to_string(),
+            worktree_path: None,
+            created_at: 0,
+            updated_at: 0,
+            interrupted_at: None,
+            interrupted_message_id: None,
+        };
+
+        let (messages, partial_id) = interrupted_messages(&state);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(partial_id.as_deref(), Some(messages[0].id.as_str()));
+        assert!(matches!(
+            &messages[0].content,
+            MessageContent::Text { text } if text == "Looking at the"
+        ));
+        assert!(matches!(
+            &messages[1].content,
+            MessageContent::ToolCalls { calls } if calls[0].id == "call-1"
+        ));
+
+        let empty = StreamState {
+            text: String::new(),
+            tool_calls: vec![],
+            ..state
+        };
+        let (messages, partial_id) = interrupted_messages(&empty);
+        assert!(messages.is_empty());
+        assert!(partial_id.is_none());
+    }
+}
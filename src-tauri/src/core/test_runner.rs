@@ -0,0 +1,543 @@
+//! Test Runner Tool
+//!
+//! The `run_tests` tool detects the workspace's test framework, runs the
+//! selected tests and parses the output into pass/fail counts and a compact
+//! list of failures, so the agent doesn't have to read whole test logs.
+
+use crate::core::cancellation::run_command;
+use crate::core::tools::{ToolContext, ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::{ToolDefinition, ToolRequest};
+use crate::storage::ToolCapability;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::process::Command;
+
+pub const RUN_TESTS_TOOL: &str = "run_tests";
+
+/// Longest failure message kept, in characters
+const MAX_MESSAGE_CHARS: usize = 1500;
+/// Output kept when a run fails without reporting any tests
+const MAX_OUTPUT_CHARS: usize = 4000;
+/// Most failures reported by one run
+const MAX_FAILURES: usize = 50;
+
+/// Supported test frameworks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestFramework {
+    Cargo,
+    Pytest,
+    Vitest,
+    Go,
+}
+
+impl TestFramework {
+    /// Detect the framework from the files at the workspace root
+    pub fn detect(root: &Path) -> Option<Self> {
+        if root.join("Cargo.toml").is_file() {
+            return Some(Self::Cargo);
+        }
+        if root.join("go.mod").is_file() {
+            return Some(Self::Go);
+        }
+        let uses_vitest = std::fs::read_to_string(root.join("package.json"))
+            .is_ok_and(|package| package.contains("\"vitest\""));
+        if uses_vitest {
+            return Some(Self::Vitest);
+        }
+        let pytest_files = [
+            "pytest.ini",
+            "conftest.py",
+            "pyproject.toml",
+            "setup.cfg",
+            "tox.ini",
+        ];
+        if pytest_files.iter().any(|file| root.join(file).is_file()) {
+            return Some(Self::Pytest);
+        }
+        None
+    }
+
+    /// Program and arguments running the tests whose names match `filter`
+    fn command(&self, filter: Option<&str>) -> (&'static str, Vec<String>) {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let (program, mut args, filter_flag) = match self {
+            Self::Cargo => ("cargo", args(&["test", "--no-fail-fast"]), None),
+            Self::Pytest => ("pytest", args(&["-q", "-rfE"]), Some("-k")),
+            Self::Vitest => (
+                if cfg!(windows) { "npx.cmd" } else { "npx" },
+                args(&["vitest", "run", "--reporter=json"]),
+                Some("-t"),
+            ),
+            Self::Go => ("go", args(&["test", "-json", "./..."]), Some("-run")),
+        };
+        if let Some(filter) = filter {
+            args.extend(filter_flag.map(str::to_string));
+            args.push(filter.to_string());
+        }
+        (program, args)
+    }
+
+    /// Parse the output of a test run
+    pub fn parse(&self, stdout: &str, stderr: &str) -> TestReport {
+        let mut report = match self {
+            Self::Cargo => parse_cargo(stdout),
+            Self::Pytest => parse_pytest(stdout),
+            Self::Vitest => parse_vitest(stdout),
+            Self::Go => parse_go(stdout),
+        };
+        report.framework = Some(*self);
+        report.failures.truncate(MAX_FAILURES);
+        for failure in &mut report.failures {
+            failure.message = truncate(failure.message.trim(), MAX_MESSAGE_CHARS, false);
+        }
+        if report.total() == 0 {
+            let output = format!("{}\n{}", stdout.trim(), stderr.trim());
+            report.output = Some(truncate(output.trim(), MAX_OUTPUT_CHARS, true));
+        }
+        report
+    }
+}
+
+/// A failed test
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestFailure {
+    pub name: String,
+    /// File, or file and line, of the failure when the output names it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    pub message: String,
+}
+
+/// Outcome of a test run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestReport {
+    pub framework: Option<TestFramework>,
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub failures: Vec<TestFailure>,
+    /// Tail of the output when no test results could be parsed, such as
+    /// after a build error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+impl TestReport {
+    fn total(&self) -> usize {
+        self.passed + self.failed + self.skipped
+    }
+}
+
+/// Register the `run_tests` tool
+pub async fn register_tool(registry: &ToolRegistry) -> Result<(), String> {
+    let handler: ToolHandler = Arc::new(|request, context| {
+        Box::pin(async move { to_output(run_tests_tool(&request, &context).await) })
+    });
+
+    registry.register(run_tests_definition(), handler).await
+}
+
+async fn run_tests_tool(
+    request: &ToolRequest,
+    context: &ToolContext,
+) -> Result<serde_json::Value, String> {
+    let root = Path::new(
+        context
+            .worktree_path
+            .as_deref()
+            .unwrap_or(&context.workspace_root),
+    );
+    let framework = match request.input.get("framework") {
+        Some(framework) => serde_json::from_value(framework.clone())
+            .map_err(|_| format!("Unsupported test framework: {}", framework))?,
+        None => TestFramework::detect(root)
+            .ok_or_else(|| "No supported test framework found in the workspace".to_string())?,
+    };
+    let filter = request
+        .input
+        .get("filter")
+        .and_then(|v| v.as_str())
+        .filter(|filter| !filter.is_empty());
+
+    let (program, args) = framework.command(filter);
+    let mut command = Command::new(program);
+    command
+        .args(&args)
+        .current_dir(root)
+        .stdin(std::process::Stdio::null());
+    let output = run_command(command, &context.cancel_token).await?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let report = framework.parse(&stdout, &stderr);
+    if report.total() == 0 && !output.status.success() {
+        return Err(format!(
+            "`{} {}` failed before running tests:\n{}",
+            program,
+            args.join(" "),
+            report.output.unwrap_or_default()
+        ));
+    }
+
+    serde_json::to_value(&report).map_err(|e| format!("Failed to serialize test report: {}", e))
+}
+
+/// Parse `cargo test` output
+fn parse_cargo(stdout: &str) -> TestReport {
+    let mut report = TestReport::default();
+    let mut failed_names = vec![];
+    let mut details: HashMap<String, Vec<&str>> = HashMap::new();
+    // Test whose `---- name stdout ----` section is being read
+    let mut current: Option<String> = None;
+
+    for line in stdout.lines() {
+        if let Some(rest) = line.strip_prefix("test ") {
+            if let Some((name, outcome)) = rest.split_once(" ... ") {
+                match outcome.trim() {
+                    "ok" => report.passed += 1,
+                    "FAILED" => {
+                        report.failed += 1;
+                        failed_names.push(name.to_string());
+                    }
+                    outcome if outcome.starts_with("ignored") => report.skipped += 1,
+                    _ => {}
+                }
+                continue;
+            }
+        }
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        {
+            current = Some(name.to_string());
+            continue;
+        }
+        if line == "failures:" || line.starts_with("test result:") {
+            current = None;
+            continue;
+        }
+        if let Some(name) = &current {
+            details.entry(name.clone()).or_default().push(line);
+        }
+    }
+
+    report.failures = failed_names
+        .into_iter()
+        .map(|name| {
+            let lines = details.remove(&name).unwrap_or_default();
+            let location = lines.iter().find_map(|line| {
+                let (_, rest) = line.split_once("panicked at ")?;
+                Some(rest.trim_end_matches(':').to_string())
+            });
+            TestFailure {
+                name,
+                location,
+                message: lines.join("\n"),
+            }
+        })
+        .collect();
+    report
+}
+
+/// Parse `pytest -q -rfE` output
+fn parse_pytest(stdout: &str) -> TestReport {
+    let mut report = TestReport::default();
+
+    for line in stdout.lines() {
+        let failure = line
+            .strip_prefix("FAILED ")
+            .or_else(|| line.strip_prefix("ERROR "));
+        if let Some(failure) = failure {
+            let (name, message) = failure.split_once(" - ").unwrap_or((failure, ""));
+            report.failures.push(TestFailure {
+                name: name.to_string(),
+                location: name.split("::").next().map(str::to_string),
+                message: message.to_string(),
+            });
+        }
+    }
+
+    // Summary line such as `1 failed, 3 passed, 1 skipped in 0.12s`
+    let summary = stdout
+        .lines()
+        .rev()
+        .map(|line| line.trim_matches(|c: char| c == '=' || c.is_whitespace()))
+        .find(|line| line.contains(" in ") && line.split_whitespace().nth(1).is_some());
+    for part in summary.unwrap_or_default().split(", ") {
+        let mut words = part.split_whitespace();
+        let (Some(count), Some(kind)) = (words.next(), words.next()) else {
+            continue;
+        };
+        let Ok(count) = count.parse::<usize>() else {
+            continue;
+        };
+        match kind {
+            "passed" | "xpassed" => report.passed += count,
+            "failed" | "error" | "errors" => report.failed += count,
+            "skipped" | "xfailed" | "deselected" => report.skipped += count,
+            _ => {}
+        }
+    }
+    report
+}
+
+/// Parse the JSON report of `vitest run --reporter=json`
+fn parse_vitest(stdout: &str) -> TestReport {
+    let mut report = TestReport::default();
+    let json = match (stdout.find('{'), stdout.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &stdout[start..=end],
+        _ => return report,
+    };
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(json) else {
+        return report;
+    };
+
+    for file in value["testResults"].as_array().into_iter().flatten() {
+        let location = file["name"].as_str().map(str::to_string);
+        for test in file["assertionResults"].as_array().into_iter().flatten() {
+            match test["status"].as_str().unwrap_or_default() {
+                "passed" => report.passed += 1,
+                "failed" => {
+                    report.failed += 1;
+                    let messages: Vec<&str> = test["failureMessages"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|message| message.as_str())
+                        .collect();
+                    report.failures.push(TestFailure {
+                        name: test["fullName"].as_str().unwrap_or_default().to_string(),
+                        location: location.clone(),
+                        message: messages.join("\n"),
+                    });
+                }
+                _ => report.skipped += 1,
+            }
+        }
+    }
+    report
+}
+
+/// Parse the event stream of `go test -json`
+fn parse_go(stdout: &str) -> TestReport {
+    let mut report = TestReport::default();
+    let mut outputs: HashMap<(String, String), String> = HashMap::new();
+    let mut failed_packages = vec![];
+
+    for line in stdout.lines() {
+        let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        let package = event["Package"].as_str().unwrap_or_default().to_string();
+        let test = event["Test"].as_str().unwrap_or_default().to_string();
+        let key = (package.clone(), test.clone());
+
+        match event["Action"].as_str().unwrap_or_default() {
+            "output" => {
+                let output = event["Output"].as_str().unwrap_or_default();
+                if !output.starts_with("=== ") {
+                    outputs.entry(key).or_default().push_str(output);
+                }
+            }
+            "pass" if !test.is_empty() => report.passed += 1,
+            "skip" if !test.is_empty() => report.skipped += 1,
+            "fail" if test.is_empty() => failed_packages.push(package),
+            "fail" => {
+                report.failed += 1;
+                report.failures.push(TestFailure {
+                    name: test,
+                    location: Some(package),
+                    message: outputs.remove(&key).unwrap_or_default(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // Packages that failed without a failing test, such as on build errors
+    for package in failed_packages {
+        if report
+            .failures
+            .iter()
+            .any(|failure| failure.location.as_deref() == Some(package.as_str()))
+        {
+            continue;
+        }
+        report.failed += 1;
+        let message = outputs
+            .remove(&(package.clone(), String::new()))
+            .unwrap_or_default();
+        report.failures.push(TestFailure {
+            name: package.clone(),
+            location: Some(package),
+            message,
+        });
+    }
+    report
+}
+
+/// Shorten text to `max` characters, keeping its start or its end
+fn truncate(text: &str, max: usize, keep_end: bool) -> String {
+    let count = text.chars().count();
+    if count <= max {
+        return text.to_string();
+    }
+    if keep_end {
+        let tail: String = text.chars().skip(count - max).collect();
+        format!("…{}", tail)
+    } else {
+        let head: String = text.chars().take(max).collect();
+        format!("{}…", head)
+    }
+}
+
+fn to_output(result: Result<serde_json::Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn run_tests_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: RUN_TESTS_TOOL.to_string(),
+        description: "Run the workspace's tests (cargo test, pytest, vitest or go test, \
+                      detected automatically) and return pass/fail counts with the failures"
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "filter": {
+                    "type": "string",
+                    "description": "Only run tests whose names match this pattern"
+                },
+                "framework": {
+                    "type": "string",
+                    "enum": ["cargo", "pytest", "vitest", "go"],
+                    "description": "Framework to use instead of the detected one"
+                }
+            }
+        }),
+        requires_approval: true,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::Shell],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect_framework() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        assert_eq!(TestFramework::detect(root), None);
+
+        std::fs::write(root.join("pyproject.toml"), "[project]\n").unwrap();
+        assert_eq!(TestFramework::detect(root), Some(TestFramework::Pytest));
+        std::fs::write(
+            root.join("package.json"),
+            r#"{ "devDependencies": { "vitest": "^1.0.0" } }"#,
+        )
+        .unwrap();
+        assert_eq!(TestFramework::detect(root), Some(TestFramework::Vitest));
+        std::fs::write(root.join("Cargo.toml"), "[package]\n").unwrap();
+        assert_eq!(TestFramework::detect(root), Some(TestFramework::Cargo));
+
+        let (program, args) = TestFramework::Go.command(Some("TestParse"));
+        assert_eq!(program, "go");
+        assert_eq!(args, vec!["test", "-json", "./...", "-run", "TestParse"]);
+    }
+
+    #[test]
+    fn test_parse_cargo_and_go_output() {
+        let stdout = "running 3 tests\n\
+                      test tests::adds ... ok\n\
+                      test tests::slow ... ignored, too slow\n\
+                      test tests::subtracts ... FAILED\n\
+                      \n\
+                      failures:\n\
+                      \n\
+                      ---- tests::subtracts stdout ----\n\
+                      thread 'tests::subtracts' panicked at src/lib.rs:10:5:\n\
+                      assertion failed: 1 == 2\n\
+                      \n\
+                      failures:\n    tests::subtracts\n\
+                      \n\
+                      test result: FAILED. 1 passed; 1 failed; 1 ignored\n";
+        let report = TestFramework::Cargo.parse(stdout, "");
+        assert_eq!((report.passed, report.failed, report.skipped), (1, 1, 1));
+        assert_eq!(report.failures[0].name, "tests::subtracts");
+        assert_eq!(
+            report.failures[0].location.as_deref(),
+            Some("src/lib.rs:10:5")
+        );
+        assert!(report.failures[0]
+            .message
+            .ends_with("assertion failed: 1 == 2"));
+        assert_eq!(report.output, None);
+
+        let report = TestFramework::Cargo.parse("", "error[E0425]: cannot find value `x`");
+        assert_eq!(report.total(), 0);
+        assert!(report.output.unwrap().contains("E0425"));
+
+        let stdout = r#"{"Action":"run","Package":"example/calc","Test":"TestAdd"}
+{"Action":"output","Package":"example/calc","Test":"TestAdd","Output":"=== RUN   TestAdd\n"}
+{"Action":"pass","Package":"example/calc","Test":"TestAdd"}
+{"Action":"output","Package":"example/calc","Test":"TestSub","Output":"    calc_test.go:12: got 3, want 1\n"}
+{"Action":"fail","Package":"example/calc","Test":"TestSub"}
+{"Action":"fail","Package":"example/calc"}
+{"Action":"output","Package":"example/broken","Output":"calc.go:3:1: syntax error\n"}
+{"Action":"fail","Package":"example/broken"}"#;
+        let report = TestFramework::Go.parse(stdout, "");
+        assert_eq!((report.passed, report.failed), (1, 2));
+        assert_eq!(report.failures[0].name, "TestSub");
+        assert_eq!(report.failures[0].message, "calc_test.go:12: got 3, want 1");
+        assert_eq!(report.failures[1].name, "example/broken");
+    }
+
+    #[test]
+    fn test_parse_pytest_and_vitest_output() {
+        let stdout = "..F.s\n\
+                      =========== short test summary info ===========\n\
+                      FAILED tests/test_calc.py::test_sub - AssertionError: assert 3 == 1\n\
+                      ======= 1 failed, 3 passed, 1 skipped in 0.12s =======\n";
+        let report = TestFramework::Pytest.parse(stdout, "");
+        assert_eq!((report.passed, report.failed, report.skipped), (3, 1, 1));
+        assert_eq!(report.failures[0].name, "tests/test_calc.py::test_sub");
+        assert_eq!(
+            report.failures[0].location.as_deref(),
+            Some("tests/test_calc.py")
+        );
+        assert_eq!(report.failures[0].message, "AssertionError: assert 3 == 1");
+
+        let stdout = r#"Some log line
+{"numTotalTests":3,"testResults":[{"name":"/app/src/calc.test.ts","assertionResults":[
+  {"fullName":"calc adds","status":"passed","failureMessages":[]},
+  {"fullName":"calc subtracts","status":"failed","failureMessages":["expected 3 to be 1"]},
+  {"fullName":"calc divides","status":"skipped","failureMessages":[]}
+]}]}"#;
+        let report = TestFramework::Vitest.parse(stdout, "");
+        assert_eq!((report.passed, report.failed, report.skipped), (1, 1, 1));
+        assert_eq!(report.failures[0].name, "calc subtracts");
+        assert_eq!(
+            report.failures[0].location.as_deref(),
+            Some("/app/src/calc.test.ts")
+        );
+    }
+}
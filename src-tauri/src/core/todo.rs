@@ -0,0 +1,275 @@
+//! Todo Tool
+//!
+//! The `todo` tool lets the agent keep a task list for its session. The list
+//! is stored with the session and survives across tasks, and every change is
+//! emitted as a `TodosUpdated` event so clients can render progress.
+
+use crate::core::tools::{ToolContext, ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::{EventSender, RuntimeEvent, ToolDefinition, ToolRequest};
+use crate::storage::{ChatHistoryRepository, TodoItem, TodoList, TodoStatus, ToolCapability};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+pub const TODO_TOOL: &str = "todo";
+
+/// Most items a todo list may hold
+const MAX_TODOS: usize = 50;
+
+/// Item as written by the agent; ids and statuses may be left out
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TodoInput {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    status: Option<TodoStatus>,
+}
+
+/// Stores session todo lists and announces their changes
+#[derive(Clone)]
+pub struct TodoManager {
+    chat_history: ChatHistoryRepository,
+    event_sender: EventSender,
+}
+
+impl TodoManager {
+    pub fn new(chat_history: ChatHistoryRepository, event_sender: EventSender) -> Self {
+        Self {
+            chat_history,
+            event_sender,
+        }
+    }
+
+    /// Register the `todo` tool
+    pub async fn register_tool(&self, registry: &ToolRegistry) -> Result<(), String> {
+        let manager = self.clone();
+        let handler: ToolHandler = Arc::new(move |request, context| {
+            let manager = manager.clone();
+            Box::pin(async move { to_output(manager.todo_tool(&request, &context).await) })
+        });
+
+        registry.register(todo_definition(), handler).await
+    }
+
+    /// Todo list of a session; empty when the agent has not written one
+    pub async fn get(&self, session_id: &str) -> Result<TodoList, String> {
+        Ok(self
+            .chat_history
+            .get_todos(session_id)
+            .await?
+            .unwrap_or_else(|| TodoList {
+                session_id: session_id.to_string(),
+                items: vec![],
+                updated_at: 0,
+            }))
+    }
+
+    async fn todo_tool(
+        &self,
+        request: &ToolRequest,
+        context: &ToolContext,
+    ) -> Result<serde_json::Value, String> {
+        let parse = |name: &str| -> Result<Option<Vec<TodoInput>>, String> {
+            request
+                .input
+                .get(name)
+                .filter(|v| !v.is_null())
+                .map(|v| {
+                    serde_json::from_value(v.clone())
+                        .map_err(|e| format!("Invalid '{}': {}", name, e))
+                })
+                .transpose()
+        };
+        let todos = parse("todos")?;
+        let updates = parse("updates")?;
+
+        let mut list = self.get(&context.session_id).await?;
+        let items = match (todos, updates) {
+            (Some(_), Some(_)) => return Err("Pass either 'todos' or 'updates'".to_string()),
+            (Some(todos), None) => Some(replace(todos)?),
+            (None, Some(updates)) => Some(update(&list.items, updates)?),
+            (None, None) => None,
+        };
+
+        if let Some(items) = items {
+            list.items = items;
+            list.updated_at = chrono::Utc::now().timestamp();
+            self.chat_history.save_todos(&list).await?;
+            let _ = self.event_sender.send(RuntimeEvent::TodosUpdated {
+                task_id: context.task_id.clone(),
+                session_id: context.session_id.clone(),
+                todos: list.clone(),
+            });
+        }
+
+        let (completed, total) = list.progress();
+        Ok(serde_json::json!({
+            "todos": list.items,
+            "completed": completed,
+            "total": total,
+        }))
+    }
+}
+
+/// A new list from `todos`, numbering items without an id
+fn replace(todos: Vec<TodoInput>) -> Result<Vec<TodoItem>, String> {
+    if todos.len() > MAX_TODOS {
+        return Err(format!("A todo list holds at most {} items", MAX_TODOS));
+    }
+
+    let mut ids = HashSet::new();
+    let mut items = Vec::with_capacity(todos.len());
+    for (index, todo) in todos.into_iter().enumerate() {
+        let id = todo
+            .id
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| (index + 1).to_string());
+        if !ids.insert(id.clone()) {
+            return Err(format!("Duplicate todo id '{}'", id));
+        }
+        let content = todo.content.as_deref().map(str::trim).unwrap_or_default();
+        if content.is_empty() {
+            return Err(format!("Todo '{}' has no content", id));
+        }
+        items.push(TodoItem {
+            id,
+            content: content.to_string(),
+            status: todo.status.unwrap_or(TodoStatus::Pending),
+        });
+    }
+    Ok(items)
+}
+
+/// `items` with the status or content of the items named in `updates` changed
+fn update(items: &[TodoItem], updates: Vec<TodoInput>) -> Result<Vec<TodoItem>, String> {
+    let mut items = items.to_vec();
+    for todo in updates {
+        let id = todo.id.ok_or("Every update needs an 'id'")?;
+        let item = items
+            .iter_mut()
+            .find(|item| item.id == id.trim())
+            .ok_or_else(|| format!("No todo with id '{}'", id))?;
+        if let Some(status) = todo.status {
+            item.status = status;
+        }
+        if let Some(content) = todo.content.as_deref().map(str::trim) {
+            if content.is_empty() {
+                return Err(format!("Todo '{}' has no content", id));
+            }
+            item.content = content.to_string();
+        }
+    }
+    Ok(items)
+}
+
+fn to_output(result: Result<serde_json::Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn todo_definition() -> ToolDefinition {
+    let item = |required: &[&str]| {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "content": { "type": "string" },
+                "status": {
+                    "type": "string",
+                    "enum": ["pending", "in_progress", "completed", "cancelled"]
+                }
+            },
+            "required": required
+        })
+    };
+
+    ToolDefinition {
+        name: TODO_TOOL.to_string(),
+        description: "Keep a todo list for multi-step work so the user can follow your \
+                      progress. Pass 'todos' to write the whole list, or 'updates' to change \
+                      the status or content of items by id. Call without arguments to read the \
+                      list. Keep one item in_progress at a time and mark items completed as \
+                      soon as they are done."
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "todos": {
+                    "type": "array",
+                    "items": item(&["content"]),
+                    "description": "The full list, replacing the current one"
+                },
+                "updates": {
+                    "type": "array",
+                    "items": item(&["id"]),
+                    "description": "Changes to existing items"
+                }
+            }
+        }),
+        requires_approval: false,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::FilesystemRead],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(value: serde_json::Value) -> Vec<TodoInput> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_replace_and_update() {
+        let items = replace(input(serde_json::json!([
+            { "content": "Read the parser" },
+            { "content": "Fix the bug", "status": "in_progress" },
+            { "id": "tests", "content": "Add tests" }
+        ])))
+        .unwrap();
+        assert_eq!(
+            items.iter().map(|i| i.id.as_str()).collect::<Vec<_>>(),
+            ["1", "2", "tests"]
+        );
+        assert_eq!(items[0].status, TodoStatus::Pending);
+
+        let items = update(
+            &items,
+            input(serde_json::json!([
+                { "id": "2", "status": "completed" },
+                { "id": "tests", "content": "Add regression tests", "status": "cancelled" }
+            ])),
+        )
+        .unwrap();
+        assert_eq!(items[1].status, TodoStatus::Completed);
+        assert_eq!(items[2].content, "Add regression tests");
+        let list = TodoList {
+            session_id: "session-1".to_string(),
+            items: items.clone(),
+            updated_at: 0,
+        };
+        assert_eq!(list.progress(), (1, 2));
+
+        assert!(update(&items, input(serde_json::json!([{ "id": "9" }]))).is_err());
+        assert!(replace(input(
+            serde_json::json!([{ "id": "a", "content": "x" }, { "id": "a", "content": "y" }])
+        ))
+        .is_err());
+        assert!(replace(input(serde_json::json!([{ "content": " " }]))).is_err());
+    }
+}
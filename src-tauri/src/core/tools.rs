@@ -3,6 +3,12 @@
 //! Provides a registry of available tools and dispatch mechanism for tool execution.
 //! Tools execute on the backend host (filesystem, git, shell, LSP, search).
 
+use crate::core::cancellation::CancellationToken;
+use crate::core::checkpoints::CheckpointManager;
+use crate::core::hooks::{HookEvent, HookManager, HookPayload};
+use crate::core::sandbox::{self, SandboxManager};
+use crate::core::shell::EXECUTE_SHELL_TOOL;
+use crate::core::shell_policy::{ShellDecision, ShellPolicyManager};
 use crate::core::types::*;
 use crate::storage::models::*;
 use std::collections::HashMap;
@@ -17,6 +23,9 @@ pub struct ToolContext {
     pub workspace_root: String,
     pub worktree_path: Option<String>,
     pub settings: TaskSettings,
+    /// Cancelled when the owning task is cancelled; handlers spawning
+    /// processes should run them through `cancellation::run_command`
+    pub cancel_token: CancellationToken,
 }
 
 /// Result of tool execution
@@ -36,10 +45,17 @@ pub type ToolHandler = Arc<
 
 use futures_util::future::BoxFuture;
 
+/// Settings key of the retry policies overriding the default per tool
+pub const TOOL_RETRY_POLICIES_KEY: &str = "tool_retry_policies";
+
 /// Tool registry containing all available tools
 pub struct ToolRegistry {
     tools: RwLock<HashMap<String, ToolDefinition>>,
     handlers: RwLock<HashMap<String, ToolHandler>>,
+    /// Retry policies overriding the default for single tools
+    retry_policies: RwLock<HashMap<String, ToolRetryPolicy>>,
+    /// Registry whose tools this one adds to
+    parent: Option<Arc<ToolRegistry>>,
 }
 
 impl ToolRegistry {
@@ -47,6 +63,25 @@ impl ToolRegistry {
         Self {
             tools: RwLock::new(HashMap::new()),
             handlers: RwLock::new(HashMap::new()),
+            retry_policies: RwLock::new(HashMap::new()),
+            parent: None,
+        }
+    }
+
+    /// Registry adding `tools` to those of `parent`, whose tools win when
+    /// names clash. Only one level of layering is supported.
+    pub fn layered(parent: Arc<ToolRegistry>, tools: Vec<(ToolDefinition, ToolHandler)>) -> Self {
+        let mut definitions = HashMap::new();
+        let mut handlers = HashMap::new();
+        for (definition, handler) in tools {
+            handlers.insert(definition.name.clone(), handler);
+            definitions.insert(definition.name.clone(), definition);
+        }
+        Self {
+            tools: RwLock::new(definitions),
+            handlers: RwLock::new(handlers),
+            retry_policies: RwLock::new(HashMap::new()),
+            parent: Some(parent),
         }
     }
 
@@ -88,31 +123,94 @@ impl ToolRegistry {
 
     /// Get tool definition
     pub async fn get_definition(&self, name: &str) -> Option<ToolDefinition> {
+        if let Some(parent) = &self.parent {
+            if let Some(definition) = parent.tools.read().await.get(name) {
+                return Some(definition.clone());
+            }
+        }
         let tools = self.tools.read().await;
         tools.get(name).cloned()
     }
 
     /// List all registered tools
     pub async fn list_tools(&self) -> Vec<ToolDefinition> {
-        let tools = self.tools.read().await;
-        tools.values().cloned().collect()
+        let mut tools = self.tools.read().await.clone();
+        if let Some(parent) = &self.parent {
+            tools.extend(parent.tools.read().await.clone());
+        }
+        tools.into_values().collect()
+    }
+
+    /// List the tools allowed in plan mode
+    pub async fn list_read_only_tools(&self) -> Vec<ToolDefinition> {
+        self.list_tools()
+            .await
+            .into_iter()
+            .filter(|def| def.is_read_only())
+            .collect()
+    }
+
+    /// Check if a session's settings permit a tool; unknown tools are left
+    /// to fail when they run
+    pub async fn is_permitted(&self, name: &str, settings: &TaskSettings) -> bool {
+        self.get_definition(name)
+            .await
+            .map_or(true, |def| def.is_permitted(settings))
+    }
+
+    /// Check if a tool is read-only; unknown tools are not
+    pub async fn is_read_only(&self, name: &str) -> bool {
+        self.get_definition(name)
+            .await
+            .is_some_and(|def| def.is_read_only())
+    }
+
+    /// Check if a tool modifies files named in its input
+    pub async fn modifies_files(&self, name: &str) -> bool {
+        self.get_definition(name)
+            .await
+            .map(|def| def.modifies_files)
+            .unwrap_or(false)
     }
 
     /// Check if a tool requires approval
     pub async fn requires_approval(&self, name: &str) -> bool {
-        let tools = self.tools.read().await;
-        tools
-            .get(name)
+        self.get_definition(name)
+            .await
             .map(|def| def.requires_approval)
             .unwrap_or(true) // Default to requiring approval for unknown tools
     }
 
+    /// Retry policy of a tool, the default one unless overridden
+    pub async fn retry_policy(&self, name: &str) -> ToolRetryPolicy {
+        if let Some(parent) = &self.parent {
+            if let Some(policy) = parent.retry_policies.read().await.get(name) {
+                return policy.clone();
+            }
+        }
+        let policies = self.retry_policies.read().await;
+        policies.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Override the retry policy of a tool; `None` restores the default
+    pub async fn set_retry_policy(&self, name: &str, policy: Option<ToolRetryPolicy>) {
+        let mut policies = self.retry_policies.write().await;
+        match policy {
+            Some(policy) => policies.insert(name.to_string(), policy),
+            None => policies.remove(name),
+        };
+    }
+
     /// Execute a tool
     pub async fn execute(&self, request: ToolRequest, context: ToolContext) -> ToolResult {
+        let inherited = match &self.parent {
+            Some(parent) => parent.handlers.read().await.get(&request.name).cloned(),
+            None => None,
+        };
         let handler = {
             let handlers = self.handlers.read().await;
-            match handlers.get(&request.name) {
-                Some(h) => h.clone(),
+            match inherited.or_else(|| handlers.get(&request.name).cloned()) {
+                Some(h) => h,
                 None => {
                     return ToolResult {
                         tool_call_id: request.tool_call_id,
@@ -124,7 +222,16 @@ impl ToolRegistry {
             }
         };
 
-        let output = handler(request.clone(), context).await;
+        // Dropping the handler future on cancellation aborts its pending work
+        let cancel_token = context.cancel_token.clone();
+        let output = tokio::select! {
+            output = handler(request.clone(), context) => output,
+            _ = cancel_token.cancelled() => ToolExecutionOutput {
+                success: false,
+                data: serde_json::Value::Null,
+                error: Some("Tool execution cancelled".to_string()),
+            },
+        };
 
         ToolResult {
             tool_call_id: request.tool_call_id,
@@ -157,6 +264,8 @@ impl ToolRegistry {
                     "required": ["path"]
                 }),
                 requires_approval: false,
+                modifies_files: false,
+                capabilities: vec![ToolCapability::FilesystemRead],
             },
             ToolDefinition {
                 name: "write_file".to_string(),
@@ -176,6 +285,8 @@ impl ToolRegistry {
                     "required": ["path", "content"]
                 }),
                 requires_approval: true,
+                modifies_files: true,
+                capabilities: vec![ToolCapability::FilesystemWrite],
             },
             ToolDefinition {
                 name: "search_files".to_string(),
@@ -195,25 +306,8 @@ impl ToolRegistry {
                     "required": ["pattern"]
                 }),
                 requires_approval: false,
-            },
-            ToolDefinition {
-                name: "execute_shell".to_string(),
-                description: "Execute a shell command".to_string(),
-                parameters: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "command": {
-                            "type": "string",
-                            "description": "Command to execute"
-                        },
-                        "cwd": {
-                            "type": "string",
-                            "description": "Working directory"
-                        }
-                    },
-                    "required": ["command"]
-                }),
-                requires_approval: true,
+                modifies_files: false,
+                capabilities: vec![ToolCapability::FilesystemRead],
             },
             ToolDefinition {
                 name: "git_status".to_string(),
@@ -228,6 +322,8 @@ impl ToolRegistry {
                     }
                 }),
                 requires_approval: false,
+                modifies_files: false,
+                capabilities: vec![ToolCapability::FilesystemRead],
             },
         ];
 
@@ -267,11 +363,47 @@ impl Default for ToolRegistry {
 /// Tool dispatcher that manages tool execution with approval workflow
 pub struct ToolDispatcher {
     registry: Arc<ToolRegistry>,
+    checkpoints: Option<CheckpointManager>,
+    hooks: Option<HookManager>,
+    sandbox: Option<SandboxManager>,
+    shell_policy: Option<ShellPolicyManager>,
 }
 
 impl ToolDispatcher {
     pub fn new(registry: Arc<ToolRegistry>) -> Self {
-        Self { registry }
+        Self {
+            registry,
+            checkpoints: None,
+            hooks: None,
+            sandbox: None,
+            shell_policy: None,
+        }
+    }
+
+    /// Snapshot files before file-modifying tools run
+    pub fn with_checkpoints(mut self, checkpoints: CheckpointManager) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    /// Run the pre and post tool-call hooks around every tool
+    pub fn with_hooks(mut self, hooks: HookManager) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Refuse tool calls that break the session's sandbox policy, and only
+    /// auto-approve calls in sessions that have one
+    pub fn with_sandbox(mut self, sandbox: SandboxManager) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Decide on shell commands before they run: denied commands never run
+    /// and allowed ones skip approval
+    pub fn with_shell_policy(mut self, shell_policy: ShellPolicyManager) -> Self {
+        self.shell_policy = Some(shell_policy);
+        self
     }
 
     /// Dispatch a tool execution request
@@ -282,23 +414,236 @@ impl ToolDispatcher {
         context: ToolContext,
         auto_approve: bool,
     ) -> Result<ToolDispatchResult, String> {
+        if let Err(e) = self.check_permitted(&request, &context).await {
+            return Ok(ToolDispatchResult::Completed(ToolResult {
+                tool_call_id: request.tool_call_id,
+                success: false,
+                output: serde_json::Value::Null,
+                error: Some(e),
+            }));
+        }
+
         // Check if tool requires approval
-        let requires_approval = self.registry.requires_approval(&request.name).await;
+        let mut requires_approval = self.registry.requires_approval(&request.name).await;
+        if let Some(shell_policy) = &self.shell_policy {
+            if request.name == EXECUTE_SHELL_TOOL {
+                let decision = shell_policy.decide(&request, &context).await;
+                match decision.decision {
+                    ShellDecision::Deny => {
+                        let rule = decision.rule.clone().unwrap_or_default();
+                        return Ok(ToolDispatchResult::Completed(ToolResult {
+                            tool_call_id: request.tool_call_id,
+                            success: false,
+                            output: serde_json::json!({ "shellPolicy": decision }),
+                            error: Some(format!("Command blocked by shell policy rule {}", rule)),
+                        }));
+                    }
+                    ShellDecision::Allow => requires_approval = false,
+                    ShellDecision::Ask => {}
+                }
+            }
+        }
+        let auto_approve =
+            auto_approve && requires_approval && self.can_auto_approve(&context).await?;
 
         if requires_approval && !auto_approve {
             // Return pending for approval
             Ok(ToolDispatchResult::PendingApproval(request))
         } else {
             // Execute immediately
-            let result = self.registry.execute(request, context).await;
+            let result = self.execute(request, context).await;
             Ok(ToolDispatchResult::Completed(result))
         }
     }
 
-    /// Execute a tool that was pending approval
+    /// Whether a session may skip approvals; with a sandbox, only sessions
+    /// that have a policy may
+    async fn can_auto_approve(&self, context: &ToolContext) -> Result<bool, String> {
+        match &self.sandbox {
+            Some(sandbox) => Ok(sandbox
+                .policy_for_session(&context.session_id)
+                .await?
+                .is_some()),
+            None => Ok(true),
+        }
+    }
+
+    /// Whether the session's settings permit a tool call; shell commands
+    /// pushing commits need the `gitPush` capability too
+    async fn check_permitted(
+        &self,
+        request: &ToolRequest,
+        context: &ToolContext,
+    ) -> Result<(), String> {
+        if !self
+            .registry
+            .is_permitted(&request.name, &context.settings)
+            .await
+        {
+            return Err(format!(
+                "Tool '{}' is not permitted in this session",
+                request.name
+            ));
+        }
+        let pushes = request.name == EXECUTE_SHELL_TOOL
+            && request
+                .input
+                .get("command")
+                .and_then(|v| v.as_str())
+                .is_some_and(sandbox::may_git_push);
+        if pushes && !context.settings.permits(ToolCapability::GitPush) {
+            return Err("git push, or a shell that may run it, is not permitted here".to_string());
+        }
+        Ok(())
+    }
+
+    /// Execute a tool that was pending approval, unless the session's
+    /// settings stopped permitting it meanwhile
     pub async fn execute_approved(&self, request: ToolRequest, context: ToolContext) -> ToolResult {
-        self.registry.execute(request, context).await
+        if let Err(e) = self.check_permitted(&request, &context).await {
+            return ToolResult {
+                tool_call_id: request.tool_call_id,
+                success: false,
+                output: serde_json::Value::Null,
+                error: Some(e),
+            };
+        }
+        self.execute(request, context).await
+    }
+
+    /// Execute a tool, recording a checkpoint first if it modifies files.
+    /// A tool blocked by the sandbox or a pre-call hook, or whose checkpoint
+    /// cannot be recorded, does not run.
+    async fn execute(&self, request: ToolRequest, context: ToolContext) -> ToolResult {
+        if let Some(sandbox) = &self.sandbox {
+            if let Err(e) = sandbox.check(&request, &context).await {
+                return ToolResult {
+                    tool_call_id: request.tool_call_id,
+                    success: false,
+                    output: serde_json::Value::Null,
+                    error: Some(e),
+                };
+            }
+        }
+
+        let payload = HookPayload {
+            session_id: context.session_id.clone(),
+            task_id: context.task_id.clone(),
+            workspace_root: Some(
+                context
+                    .worktree_path
+                    .clone()
+                    .unwrap_or_else(|| context.workspace_root.clone()),
+            ),
+            tool_name: Some(request.name.clone()),
+            tool_input: Some(request.input.clone()),
+            ..HookPayload::default()
+        };
+        if let Some(hooks) = &self.hooks {
+            if let Err(e) = hooks
+                .run(HookEvent::PreToolCall, &payload, &context.cancel_token)
+                .await
+            {
+                return ToolResult {
+                    tool_call_id: request.tool_call_id,
+                    success: false,
+                    output: serde_json::Value::Null,
+                    error: Some(e),
+                };
+            }
+        }
+
+        if let Some(checkpoints) = &self.checkpoints {
+            if self.registry.modifies_files(&request.name).await {
+                if let Err(e) = checkpoints.record(&context, &request).await {
+                    return ToolResult {
+                        tool_call_id: request.tool_call_id,
+                        success: false,
+                        output: serde_json::Value::Null,
+                        error: Some(format!("Failed to record checkpoint: {}", e)),
+                    };
+                }
+            }
+        }
+
+        let cancel_token = context.cancel_token.clone();
+        let result = self.execute_with_retries(request, context).await;
+        if let Some(hooks) = &self.hooks {
+            let payload = HookPayload {
+                tool_result: serde_json::to_value(&result).ok(),
+                ..payload
+            };
+            // Post-call hooks only log their failures
+            let _ = hooks
+                .run(HookEvent::PostToolCall, &payload, &cancel_token)
+                .await;
+        }
+        result
+    }
+
+    /// Run a tool, repeating it while its retry policy allows. A final
+    /// failure carries the error kind and attempt count for the model.
+    async fn execute_with_retries(&self, request: ToolRequest, context: ToolContext) -> ToolResult {
+        let policy = self.registry.retry_policy(&request.name).await;
+        let cancel_token = context.cancel_token.clone();
+        let mut attempt = 1;
+        loop {
+            let result = self
+                .registry
+                .execute(request.clone(), context.clone())
+                .await;
+            if result.success {
+                return result;
+            }
+
+            let kind = ToolErrorKind::classify(result.error.as_deref().unwrap_or_default());
+            if !policy.should_retry(kind, attempt) {
+                return with_error_context(result, kind, attempt);
+            }
+            let delay = policy.delay(attempt);
+            log::info!(
+                "Retrying tool '{}' in {}ms after {:?} failure (attempt {} of {})",
+                request.name,
+                delay.as_millis(),
+                kind,
+                attempt + 1,
+                policy.max_attempts
+            );
+            let cancelled = tokio::select! {
+                _ = tokio::time::sleep(delay) => false,
+                _ = cancel_token.cancelled() => true,
+            };
+            if cancelled {
+                return with_error_context(result, kind, attempt);
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Registry backing this dispatcher
+    pub fn registry(&self) -> &Arc<ToolRegistry> {
+        &self.registry
+    }
+}
+
+/// Add the error kind and attempt count to the output of a failed result
+fn with_error_context(mut result: ToolResult, kind: ToolErrorKind, attempts: u32) -> ToolResult {
+    let mut context = serde_json::Map::new();
+    match result.output.take() {
+        serde_json::Value::Object(output) => context.extend(output),
+        serde_json::Value::Null => {}
+        output => {
+            context.insert("output".to_string(), output);
+        }
     }
+    context.insert("errorKind".to_string(), serde_json::json!(kind));
+    context.insert(
+        "transient".to_string(),
+        serde_json::json!(kind.is_transient()),
+    );
+    context.insert("attempts".to_string(), serde_json::json!(attempts));
+    result.output = serde_json::Value::Object(context);
+    result
 }
 
 /// Result of tool dispatch
@@ -323,6 +668,8 @@ mod tests {
             description: "A test tool".to_string(),
             parameters: serde_json::json!({}),
             requires_approval: false,
+            modifies_files: false,
+            capabilities: vec![ToolCapability::FilesystemRead],
         };
 
         let handler: ToolHandler = Arc::new(|_req, _ctx| {
@@ -354,6 +701,8 @@ mod tests {
             description: "Test".to_string(),
             parameters: serde_json::json!({}),
             requires_approval: false,
+            modifies_files: false,
+            capabilities: vec![ToolCapability::FilesystemRead],
         };
 
         let handler: ToolHandler = Arc::new(|_req, _ctx| {
@@ -374,6 +723,58 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_execute_stops_on_cancel() {
+        let registry = ToolRegistry::new();
+
+        let tool = ToolDefinition {
+            name: "hang".to_string(),
+            description: "Never finishes".to_string(),
+            parameters: serde_json::json!({}),
+            requires_approval: false,
+            modifies_files: false,
+            capabilities: vec![ToolCapability::FilesystemRead],
+        };
+
+        let handler: ToolHandler = Arc::new(|_req, _ctx| {
+            Box::pin(async move {
+                std::future::pending::<()>().await;
+                unreachable!()
+            })
+        });
+        registry.register(tool, handler).await.unwrap();
+
+        let cancel_token = CancellationToken::new();
+        let context = ToolContext {
+            session_id: "session".to_string(),
+            task_id: "task".to_string(),
+            workspace_root: "/tmp".to_string(),
+            worktree_path: None,
+            settings: TaskSettings::default(),
+            cancel_token: cancel_token.clone(),
+        };
+        let request = ToolRequest {
+            tool_call_id: "call-1".to_string(),
+            name: "hang".to_string(),
+            input: serde_json::json!({}),
+        };
+
+        let canceller = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            cancel_token.cancel();
+        });
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            registry.execute(request, context),
+        )
+        .await
+        .expect("Tool was not cancelled");
+        canceller.await.unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("Tool execution cancelled"));
+    }
+
     #[tokio::test]
     async fn test_default_registry() {
         let registry = ToolRegistry::create_default().await;
@@ -391,4 +792,192 @@ mod tests {
         assert!(write_file_def.is_some());
         assert!(write_file_def.unwrap().requires_approval);
     }
+
+    #[tokio::test]
+    async fn test_read_only_tools() {
+        let registry = ToolRegistry::create_default().await;
+
+        let mut names: Vec<String> = registry
+            .list_read_only_tools()
+            .await
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["git_status", "read_file", "search_files"]);
+        assert!(registry.is_read_only("read_file").await);
+        assert!(!registry.is_read_only("write_file").await);
+        assert!(!registry.is_read_only("missing").await);
+    }
+
+    #[tokio::test]
+    async fn test_dispatcher_retries_transient_failures() {
+        let registry = Arc::new(ToolRegistry::new());
+        let calls = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        for name in ["flaky", "broken"] {
+            let tool = ToolDefinition {
+                name: name.to_string(),
+                description: "Fails on its first calls".to_string(),
+                parameters: serde_json::json!({}),
+                requires_approval: false,
+                modifies_files: false,
+                capabilities: vec![ToolCapability::FilesystemRead],
+            };
+            let calls = calls.clone();
+            let handler: ToolHandler = Arc::new(move |req, _ctx| {
+                let attempt = calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                Box::pin(async move {
+                    match (req.name.as_str(), attempt) {
+                        ("flaky", 1 | 2) => ToolExecutionOutput {
+                            success: false,
+                            data: serde_json::Value::Null,
+                            error: Some("File is locked".to_string()),
+                        },
+                        ("flaky", _) => ToolExecutionOutput {
+                            success: true,
+                            data: serde_json::json!({ "ok": true }),
+                            error: None,
+                        },
+                        _ => ToolExecutionOutput {
+                            success: false,
+                            data: serde_json::Value::Null,
+                            error: Some("Missing 'path'".to_string()),
+                        },
+                    }
+                })
+            });
+            registry.register(tool, handler).await.unwrap();
+        }
+        let policy = ToolRetryPolicy {
+            initial_delay_ms: 1,
+            ..ToolRetryPolicy::default()
+        };
+        registry.set_retry_policy("flaky", Some(policy)).await;
+
+        let context = ToolContext {
+            session_id: "session".to_string(),
+            task_id: "task".to_string(),
+            workspace_root: "/tmp".to_string(),
+            worktree_path: None,
+            settings: TaskSettings::default(),
+            cancel_token: CancellationToken::new(),
+        };
+        let request = |name: &str| ToolRequest {
+            tool_call_id: "call-1".to_string(),
+            name: name.to_string(),
+            input: serde_json::json!({}),
+        };
+        let dispatcher = ToolDispatcher::new(registry);
+
+        let result = dispatcher
+            .execute_approved(request("flaky"), context.clone())
+            .await;
+        assert!(result.success);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        calls.store(0, std::sync::atomic::Ordering::SeqCst);
+        let result = dispatcher
+            .execute_approved(request("broken"), context)
+            .await;
+        assert!(!result.success);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(
+            result.output,
+            serde_json::json!({ "errorKind": "invalid_input", "transient": false, "attempts": 1 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_capabilities() {
+        let registry = Arc::new(ToolRegistry::create_default().await);
+        let shell = ToolDefinition {
+            name: EXECUTE_SHELL_TOOL.to_string(),
+            description: "Run a command".to_string(),
+            parameters: serde_json::json!({ "type": "object" }),
+            requires_approval: false,
+            modifies_files: false,
+            capabilities: vec![ToolCapability::Shell],
+        };
+        let handler: ToolHandler = Arc::new(|_req, _ctx| {
+            Box::pin(async {
+                ToolExecutionOutput {
+                    success: true,
+                    data: serde_json::Value::Null,
+                    error: None,
+                }
+            })
+        });
+        registry.register(shell, handler).await.unwrap();
+
+        let review_only = TaskSettings {
+            tool_capabilities: Some(vec![ToolCapability::FilesystemRead]),
+            ..TaskSettings::default()
+        };
+        assert!(registry.is_permitted("read_file", &review_only).await);
+        assert!(!registry.is_permitted("write_file", &review_only).await);
+        assert!(
+            registry
+                .is_permitted("write_file", &TaskSettings::default())
+                .await
+        );
+
+        let context = |settings: TaskSettings| ToolContext {
+            session_id: "session".to_string(),
+            task_id: "task".to_string(),
+            workspace_root: "/tmp".to_string(),
+            worktree_path: None,
+            settings,
+            cancel_token: CancellationToken::new(),
+        };
+        let request = |name: &str, input: serde_json::Value| ToolRequest {
+            tool_call_id: "call-1".to_string(),
+            name: name.to_string(),
+            input,
+        };
+        let dispatcher = ToolDispatcher::new(registry);
+
+        let refused = dispatcher
+            .execute_approved(
+                request("write_file", serde_json::json!({})),
+                context(review_only),
+            )
+            .await;
+        assert!(!refused.success);
+        assert!(refused.error.unwrap().contains("not permitted"));
+
+        // Running commands does not permit pushing commits
+        let shell_only = TaskSettings {
+            tool_capabilities: Some(vec![ToolCapability::Shell]),
+            ..TaskSettings::default()
+        };
+        let shell = |command: &str| {
+            request(
+                EXECUTE_SHELL_TOOL,
+                serde_json::json!({ "command": command }),
+            )
+        };
+        let push = dispatcher
+            .dispatch(
+                shell("git push origin main"),
+                context(shell_only.clone()),
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(push, ToolDispatchResult::Completed(result) if !result.success));
+        let wrapped = dispatcher
+            .dispatch(
+                shell("sh -c 'git push origin main'"),
+                context(shell_only.clone()),
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(wrapped, ToolDispatchResult::Completed(result) if !result.success));
+        let status = dispatcher
+            .dispatch(shell("git status"), context(shell_only), false)
+            .await
+            .unwrap();
+        assert!(matches!(status, ToolDispatchResult::Completed(result) if result.success));
+    }
 }
@@ -0,0 +1,271 @@
+//! Tool Output Truncation
+//!
+//! Cuts large tool outputs down before they enter the agent context. Each tool
+//! may use its own strategy and limit; outputs within the limit are kept as is.
+//! Limits count bytes of the output's JSON text.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Output size above which a tool result is truncated
+pub const DEFAULT_MAX_OUTPUT_CHARS: usize = 30_000;
+
+/// Size of the preview passed along with an output stored as an attachment
+pub const ATTACHMENT_PREVIEW_CHARS: usize = 2_000;
+
+/// How an oversized tool output is cut down
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TruncationStrategy {
+    /// Keep the start
+    Head,
+    /// Keep the end
+    Tail,
+    /// Keep the start and the end, dropping the middle
+    #[default]
+    MiddleOut,
+    /// Keep evenly spaced lines from the whole output
+    LineSampling,
+    /// Store the full output as a session attachment and pass a reference
+    /// with a preview of its start
+    Attachment,
+}
+
+/// Truncation applied to one tool's output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ToolTruncation {
+    pub strategy: TruncationStrategy,
+    pub max_chars: usize,
+}
+
+impl Default for ToolTruncation {
+    fn default() -> Self {
+        Self {
+            strategy: TruncationStrategy::default(),
+            max_chars: DEFAULT_MAX_OUTPUT_CHARS,
+        }
+    }
+}
+
+/// Truncation of every tool, with overrides by tool name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TruncationConfig {
+    /// Used for tools without an entry in `tools`
+    pub default: ToolTruncation,
+    pub tools: HashMap<String, ToolTruncation>,
+}
+
+impl TruncationConfig {
+    pub fn for_tool(&self, name: &str) -> ToolTruncation {
+        self.tools.get(name).copied().unwrap_or(self.default)
+    }
+}
+
+/// Room left for the notes marking what was cut
+const MARKER_RESERVE: usize = 64;
+
+/// Cut an output down to `max_chars` with a text strategy. The longest
+/// strings are cut first, keeping the output's shape; if that is not enough,
+/// the output is replaced by its truncated JSON text. `None` when it already
+/// fits.
+pub fn truncate_output(
+    output: &serde_json::Value,
+    strategy: TruncationStrategy,
+    max_chars: usize,
+) -> Option<serde_json::Value> {
+    if output_text(output).len() <= max_chars {
+        return None;
+    }
+
+    let mut truncated = output.clone();
+    // A few rounds, as the size of a cut string is only estimated
+    for _ in 0..4 {
+        let size = output_text(&truncated).len();
+        if size <= max_chars {
+            return Some(truncated);
+        }
+        let Some(pointer) = longest_string(&truncated, String::new()).map(|(p, _)| p) else {
+            break;
+        };
+        let Some(serde_json::Value::String(text)) = truncated.pointer_mut(&pointer) else {
+            break;
+        };
+
+        // Nested strings count with their JSON escapes
+        let len = text.len();
+        let json_len = if pointer.is_empty() {
+            len
+        } else {
+            serde_json::Value::String(text.clone()).to_string().len()
+        };
+        let excess = size - max_chars + MARKER_RESERVE;
+        if json_len <= excess {
+            break;
+        }
+        *text = truncate_text(text, strategy, len * (json_len - excess) / json_len);
+    }
+
+    let text = output_text(&truncated);
+    if text.len() <= max_chars {
+        return Some(truncated);
+    }
+    Some(serde_json::Value::String(truncate_text(
+        &text,
+        strategy,
+        max_chars.saturating_sub(MARKER_RESERVE),
+    )))
+}
+
+/// Text of an output: strings as they are, other values as JSON
+pub fn output_text(output: &serde_json::Value) -> String {
+    match output {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// JSON pointer and length of the longest string in a value
+fn longest_string(value: &serde_json::Value, pointer: String) -> Option<(String, usize)> {
+    let children: Box<dyn Iterator<Item = (String, &serde_json::Value)> + '_> = match value {
+        serde_json::Value::String(text) => return Some((pointer, text.len())),
+        serde_json::Value::Array(items) => Box::new(
+            items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| (index.to_string(), item)),
+        ),
+        serde_json::Value::Object(fields) => Box::new(
+            fields
+                .iter()
+                .map(|(key, field)| (key.replace('~', "~0").replace('/', "~1"), field)),
+        ),
+        _ => return None,
+    };
+
+    children
+        .filter_map(|(key, child)| longest_string(child, format!("{}/{}", pointer, key)))
+        .max_by_key(|(_, len)| *len)
+}
+
+/// Cut a text down to about `max_chars`, marking what was left out.
+/// `Attachment` cuts like `Head`; storing the output is up to the caller.
+pub fn truncate_text(text: &str, strategy: TruncationStrategy, max_chars: usize) -> String {
+    if text.len() <= max_chars {
+        return text.to_string();
+    }
+
+    match strategy {
+        TruncationStrategy::Head | TruncationStrategy::Attachment => {
+            let head = prefix(text, max_chars);
+            format!(
+                "{}\n[... {} more characters truncated]",
+                head,
+                text.len() - head.len()
+            )
+        }
+        TruncationStrategy::Tail => {
+            let tail = suffix(text, max_chars);
+            format!(
+                "[{} earlier characters truncated ...]\n{}",
+                text.len() - tail.len(),
+                tail
+            )
+        }
+        TruncationStrategy::MiddleOut => {
+            let head = prefix(text, max_chars / 2);
+            let tail = suffix(text, max_chars - head.len());
+            format!(
+                "{}\n[... {} characters truncated ...]\n{}",
+                head,
+                text.len() - head.len() - tail.len(),
+                tail
+            )
+        }
+        TruncationStrategy::LineSampling => sample_lines(text, max_chars),
+    }
+}
+
+/// Keep every n-th line, picking n so the kept lines fit in `max_chars`
+fn sample_lines(text: &str, max_chars: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.len() < 2 {
+        return truncate_text(text, TruncationStrategy::MiddleOut, max_chars);
+    }
+
+    let step = text.len().div_ceil(max_chars.max(1));
+    let kept: Vec<&str> = lines.iter().step_by(step).copied().collect();
+    let sampled = prefix(&kept.join("\n"), max_chars).to_string();
+    format!(
+        "[Showing every {} line(s): {} of {} lines]\n{}",
+        step,
+        kept.len(),
+        lines.len(),
+        sampled
+    )
+}
+
+fn prefix(text: &str, max_chars: usize) -> &str {
+    let mut end = max_chars.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+fn suffix(text: &str, max_chars: usize) -> &str {
+    let mut start = text.len().saturating_sub(max_chars);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_text_strategies() {
+        let text = "abcdefghij".repeat(10);
+
+        let head = truncate_text(&text, TruncationStrategy::Head, 20);
+        assert!(head.starts_with("abcdefghijabcdefghij\n"));
+        assert!(head.ends_with("[... 80 more characters truncated]"));
+
+        let tail = truncate_text(&text, TruncationStrategy::Tail, 20);
+        assert!(tail.starts_with("[80 earlier characters truncated ...]\n"));
+
+        let middle = truncate_text(&text, TruncationStrategy::MiddleOut, 20);
+        assert!(middle.starts_with("abcdefghij\n[... 80 characters truncated ...]\n"));
+        assert!(middle.ends_with("\nabcdefghij"));
+
+        let lines: Vec<String> = (0..100).map(|i| format!("line {:02}", i)).collect();
+        let sampled = truncate_text(&lines.join("\n"), TruncationStrategy::LineSampling, 200);
+        assert!(sampled.starts_with("[Showing every 4 line(s): 25 of 100 lines]\nline 00\nline 04"));
+
+        // Multi-byte characters are never split
+        let wide = "é".repeat(50);
+        assert!(truncate_text(&wide, TruncationStrategy::MiddleOut, 11).contains("é\n[..."));
+    }
+
+    #[test]
+    fn test_truncate_output() {
+        let small = serde_json::json!({ "content": "short" });
+        assert!(truncate_output(&small, TruncationStrategy::Head, 100).is_none());
+
+        // The longest string is cut in place, keeping the output's shape
+        let output = serde_json::json!({ "path": "a.txt", "content": "x".repeat(500) });
+        let truncated = truncate_output(&output, TruncationStrategy::Head, 150).unwrap();
+        assert_eq!(truncated["path"], "a.txt");
+        assert!(truncated["content"].as_str().unwrap().starts_with("xxxxx"));
+        assert!(truncated.to_string().len() <= 150);
+
+        // Many small values are cut as JSON text
+        let items: Vec<String> = (0..100).map(|i| format!("item {}", i)).collect();
+        let truncated =
+            truncate_output(&serde_json::json!(items), TruncationStrategy::Head, 100).unwrap();
+        assert!(truncated.as_str().unwrap().starts_with("[\"item 0\""));
+    }
+}
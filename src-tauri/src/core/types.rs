@@ -1,6 +1,10 @@
 //! Core Runtime Types
 //! Types used by the core runtime for task/session lifecycle and agent loop
 
+use crate::core::cancellation::CancellationToken;
+use crate::core::shell_policy::ShellPolicyDecision;
+use crate::core::truncation::TruncationConfig;
+use crate::llm::redaction::Redaction;
 use crate::storage::models::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -71,6 +75,16 @@ pub struct TaskInput {
     pub initial_message: String,
     pub settings: Option<TaskSettings>,
     pub workspace: Option<WorkspaceInfo>,
+    /// Queue priority; higher priorities start first
+    #[serde(default)]
+    pub priority: i32,
+    /// Run the task in a git worktree on its own branch
+    #[serde(default)]
+    pub isolate: bool,
+    /// Limit the task to read-only tools whatever its settings say, for
+    /// clients that may not run tools
+    #[serde(default)]
+    pub read_only_tools: bool,
 }
 
 /// User action on a waiting task
@@ -112,6 +126,101 @@ pub struct ToolResult {
     pub error: Option<String>,
 }
 
+/// Category of a tool failure, reported to the model with the error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolErrorKind {
+    Timeout,
+    /// The file or resource was locked or busy
+    Locked,
+    NotFound,
+    PermissionDenied,
+    InvalidInput,
+    Cancelled,
+    Other,
+}
+
+impl ToolErrorKind {
+    /// Classify a tool error message
+    pub fn classify(message: &str) -> Self {
+        let message = message.to_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|needle| message.contains(needle));
+        if has(&["cancelled", "canceled"]) {
+            ToolErrorKind::Cancelled
+        } else if has(&["timed out", "timeout"]) {
+            ToolErrorKind::Timeout
+        } else if has(&[
+            "locked",
+            "resource busy",
+            "used by another process",
+            "temporarily unavailable",
+            "try again",
+        ]) {
+            ToolErrorKind::Locked
+        } else if has(&["permission denied", "access is denied"]) {
+            ToolErrorKind::PermissionDenied
+        } else if has(&["not found", "no such file"]) {
+            ToolErrorKind::NotFound
+        } else if has(&["invalid", "missing"]) {
+            ToolErrorKind::InvalidInput
+        } else {
+            ToolErrorKind::Other
+        }
+    }
+
+    /// Failures that may go away when the call is repeated
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ToolErrorKind::Timeout | ToolErrorKind::Locked)
+    }
+}
+
+/// How the dispatcher retries a failed tool call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ToolRetryPolicy {
+    /// Attempts including the first one; 1 disables retries
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_delay_ms: u64,
+    /// Factor the delay grows by after each retry
+    pub backoff_factor: f64,
+    pub max_delay_ms: u64,
+    /// Error kinds worth retrying
+    pub retry_on: Vec<ToolErrorKind>,
+}
+
+impl ToolRetryPolicy {
+    /// Policy that runs a tool once
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before the given retry, counting from 1
+    pub fn delay(&self, retry: u32) -> std::time::Duration {
+        let delay = self.initial_delay_ms as f64 * self.backoff_factor.powi(retry as i32 - 1);
+        std::time::Duration::from_millis(delay.min(self.max_delay_ms as f64) as u64)
+    }
+
+    pub fn should_retry(&self, kind: ToolErrorKind, attempt: u32) -> bool {
+        attempt < self.max_attempts && self.retry_on.contains(&kind)
+    }
+}
+
+impl Default for ToolRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay_ms: 250,
+            backoff_factor: 2.0,
+            max_delay_ms: 2000,
+            retry_on: vec![ToolErrorKind::Timeout, ToolErrorKind::Locked],
+        }
+    }
+}
+
 /// Tool definition for the tool registry
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -120,6 +229,26 @@ pub struct ToolDefinition {
     pub description: String,
     pub parameters: serde_json::Value, // JSON Schema
     pub requires_approval: bool,
+    /// Files named by the `path`/`paths` input are snapshotted before the tool runs
+    #[serde(default)]
+    pub modifies_files: bool,
+    /// What the tool does; sessions only get the tools whose capabilities
+    /// their settings all permit
+    pub capabilities: Vec<ToolCapability>,
+}
+
+impl ToolDefinition {
+    /// Tools that neither need approval nor modify files can run in plan mode
+    pub fn is_read_only(&self) -> bool {
+        !self.requires_approval && !self.modifies_files
+    }
+
+    /// Whether a session's settings permit every capability of the tool
+    pub fn is_permitted(&self, settings: &TaskSettings) -> bool {
+        self.capabilities
+            .iter()
+            .all(|capability| settings.permits(*capability))
+    }
 }
 
 /// Configuration for the agent loop
@@ -134,8 +263,23 @@ pub struct AgentLoopConfig {
     pub temperature: f32,
     /// Whether to enable tool use
     pub enable_tools: bool,
-    /// Tools available to the agent
+    /// Tools available to the agent (all registered tools when empty)
     pub available_tools: Vec<String>,
+    /// Model identifier; any available model is used when unset
+    pub model: Option<String>,
+    /// System prompt prepended to the conversation
+    pub system_prompt: Option<String>,
+    /// Model used to summarize older turns; a cheap long-context model is
+    /// picked when unset
+    pub compaction_model: Option<String>,
+    /// Only offer read-only tools and finish once the model submits a plan
+    pub plan_mode: bool,
+    /// Only offer read-only tools
+    #[serde(default)]
+    pub read_only_tools: bool,
+    /// How oversized tool outputs are cut down before entering the context
+    #[serde(default)]
+    pub tool_truncation: TruncationConfig,
 }
 
 impl Default for AgentLoopConfig {
@@ -146,6 +290,12 @@ impl Default for AgentLoopConfig {
             temperature: 0.7,
             enable_tools: true,
             available_tools: vec![],
+            model: None,
+            system_prompt: None,
+            compaction_model: None,
+            plan_mode: false,
+            read_only_tools: false,
+            tool_truncation: TruncationConfig::default(),
         }
     }
 }
@@ -160,6 +310,11 @@ pub enum RuntimeEvent {
         state: RuntimeTaskState,
         previous_state: RuntimeTaskState,
     },
+    /// A queued task moved in the run queue; position 0 starts next
+    TaskQueuePositionChanged {
+        task_id: RuntimeTaskId,
+        position: usize,
+    },
     /// New message in session
     MessageCreated {
         session_id: SessionId,
@@ -170,11 +325,90 @@ pub enum RuntimeEvent {
         session_id: SessionId,
         token: String,
     },
+    /// Reasoning text from LLM stream
+    Reasoning { session_id: SessionId, text: String },
+    /// Token usage reported for an LLM response
+    Usage {
+        task_id: RuntimeTaskId,
+        input_tokens: i32,
+        output_tokens: i32,
+        cached_input_tokens: Option<i32>,
+    },
+    /// The agent started an LLM round trip; the first is iteration 1
+    IterationStarted {
+        task_id: RuntimeTaskId,
+        session_id: SessionId,
+        iteration: u32,
+    },
+    /// Older turns were folded into a summary to fit the context window
+    ContextCompacted {
+        task_id: RuntimeTaskId,
+        session_id: SessionId,
+        compaction: ContextCompaction,
+    },
+    /// The task exceeded its budget and waits for the user to let it continue
+    BudgetExceeded {
+        task_id: RuntimeTaskId,
+        session_id: SessionId,
+        exceeded: Vec<BudgetLimit>,
+        usage: BudgetUsage,
+        message: String,
+    },
+    /// A plan-mode task submitted its plan, which waits for `execute_plan`
+    PlanReady {
+        task_id: RuntimeTaskId,
+        session_id: SessionId,
+        plan: Plan,
+    },
+    /// The agent changed the session's todo list
+    TodosUpdated {
+        task_id: RuntimeTaskId,
+        session_id: SessionId,
+        todos: TodoList,
+    },
+    /// An isolated task finished; its worktree waits to be merged or discarded
+    WorktreeReady {
+        task_id: RuntimeTaskId,
+        session_id: SessionId,
+        worktree: TaskWorktree,
+    },
+    /// Secrets were masked in a request before it was sent to the model
+    SecretsRedacted {
+        task_id: RuntimeTaskId,
+        session_id: SessionId,
+        redactions: Vec<Redaction>,
+    },
+    /// A session got a generated title or summary
+    SessionUpdated {
+        session_id: SessionId,
+        title: Option<String>,
+        summary: String,
+    },
+    /// A tool call was dispatched; one that needs approval is then requested
+    /// with `ToolCallRequested`
+    ToolCallStarted {
+        task_id: RuntimeTaskId,
+        request: ToolRequest,
+    },
     /// Tool execution requested
     ToolCallRequested {
         task_id: RuntimeTaskId,
         request: ToolRequest,
     },
+    /// Output a running tool printed, such as terminal output of a shell
+    /// command, including ANSI escape sequences
+    ToolOutput {
+        task_id: RuntimeTaskId,
+        tool_call_id: ToolCallId,
+        data: String,
+    },
+    /// The shell policy decided on the command of an `execute_shell` call
+    ShellCommandDecided {
+        task_id: RuntimeTaskId,
+        tool_call_id: ToolCallId,
+        command: String,
+        decision: ShellPolicyDecision,
+    },
     /// Tool execution completed
     ToolCallCompleted {
         task_id: RuntimeTaskId,
@@ -206,6 +440,7 @@ pub struct TaskHandle {
     pub session_id: SessionId,
     pub state: Arc<RwLock<RuntimeTaskState>>,
     pub action_sender: Arc<mpsc::UnboundedSender<TaskAction>>,
+    pub cancel_token: CancellationToken,
 }
 
 impl TaskHandle {
@@ -216,9 +451,11 @@ impl TaskHandle {
             .map_err(|_| "Task channel closed".to_string())
     }
 
-    /// Cancel the task
-    pub fn cancel(&self) -> Result<(), String> {
-        self.send_action(TaskAction::Cancel)
+    /// Cancel the task, stopping its LLM stream and running tools
+    pub fn cancel(&self) {
+        self.cancel_token.cancel();
+        // The task may already be parked or finished; the token is authoritative
+        let _ = self.send_action(TaskAction::Cancel);
     }
 }
 
@@ -233,11 +470,15 @@ pub struct TaskExecutionResult {
 }
 
 /// Validation result for settings
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SettingsValidation {
     pub valid: bool,
     pub errors: Vec<String>,
     pub warnings: Vec<String>,
+    /// Structured form of the errors found by capability checks
+    #[serde(default)]
+    pub details: Vec<ValidationError>,
 }
 
 impl SettingsValidation {
@@ -246,6 +487,7 @@ impl SettingsValidation {
             valid: true,
             errors: vec![],
             warnings: vec![],
+            details: vec![],
         }
     }
 
@@ -254,6 +496,7 @@ impl SettingsValidation {
             valid: false,
             errors: vec![error],
             warnings: vec![],
+            details: vec![],
         }
     }
 
@@ -262,6 +505,7 @@ impl SettingsValidation {
             valid: true,
             errors: vec![],
             warnings: vec![warning],
+            details: vec![],
         }
     }
 
@@ -273,6 +517,58 @@ impl SettingsValidation {
     pub fn add_warning(&mut self, warning: String) {
         self.warnings.push(warning);
     }
+
+    /// Add a structured error, also listing its message in `errors`
+    pub fn add_validation_error(&mut self, error: ValidationError) {
+        self.add_error(error.message.clone());
+        self.details.push(error);
+    }
+
+    /// Merge the findings of another validation into this one
+    pub fn merge(&mut self, other: SettingsValidation) {
+        self.valid &= other.valid;
+        self.errors.extend(other.errors);
+        self.warnings.extend(other.warnings);
+        self.details.extend(other.details);
+    }
+}
+
+/// Kind of a settings validation error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationErrorCode {
+    /// A setting could not be parsed
+    InvalidSetting,
+    /// The task has images but the model cannot read them
+    ImageInputUnsupported,
+    /// The agent has tools but the model cannot call them
+    ToolCallingUnsupported,
+    /// The requested response size does not fit the model's context window
+    MaxTokensExceedsContext,
+}
+
+/// A settings error a task cannot start with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationError {
+    pub code: ValidationErrorCode,
+    /// Setting the error is about
+    pub field: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub fn new(
+        code: ValidationErrorCode,
+        field: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            code,
+            field: field.into(),
+            message: message.into(),
+        }
+    }
 }
 
 #[cfg(test)]
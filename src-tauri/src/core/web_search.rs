@@ -0,0 +1,340 @@
+//! Web Search
+//!
+//! The `web_search` tool queries the search backend selected with the
+//! `web_search` setting (Brave, Tavily, SearXNG or Bing) and returns
+//! normalized results. API keys are the ones stored for the backend's
+//! provider ID, so they are managed like any other provider key.
+
+use crate::core::llm::LlmClient;
+use crate::core::tools::{ToolContext, ToolExecutionOutput, ToolHandler, ToolRegistry};
+use crate::core::types::{ToolDefinition, ToolRequest};
+use crate::storage::{SettingsRepository, ToolCapability};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Setting holding the JSON [`WebSearchConfig`]
+pub const SETTINGS_KEY: &str = "web_search";
+
+pub const WEB_SEARCH_TOOL: &str = "web_search";
+
+/// Most results a single search returns
+const MAX_RESULTS: usize = 20;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Search backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchBackend {
+    #[default]
+    Brave,
+    Tavily,
+    Searxng,
+    Bing,
+}
+
+impl SearchBackend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchBackend::Brave => "brave",
+            SearchBackend::Tavily => "tavily",
+            SearchBackend::Searxng => "searxng",
+            SearchBackend::Bing => "bing",
+        }
+    }
+
+    /// Whether the backend needs an API key; SearXNG instances are open
+    fn needs_api_key(&self) -> bool {
+        !matches!(self, SearchBackend::Searxng)
+    }
+}
+
+/// Web search settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WebSearchConfig {
+    pub backend: SearchBackend,
+    /// Base URL of the SearXNG instance
+    pub searxng_url: Option<String>,
+    /// Results returned when the model doesn't ask for a number
+    pub max_results: usize,
+}
+
+impl Default for WebSearchConfig {
+    fn default() -> Self {
+        Self {
+            backend: SearchBackend::default(),
+            searxng_url: None,
+            max_results: 5,
+        }
+    }
+}
+
+/// A search result, the same for every backend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+/// Runs web searches for the `web_search` tool
+#[derive(Clone)]
+pub struct WebSearch {
+    settings: SettingsRepository,
+    llm: Arc<dyn LlmClient>,
+    client: reqwest::Client,
+}
+
+impl WebSearch {
+    pub fn new(settings: SettingsRepository, llm: Arc<dyn LlmClient>) -> Self {
+        Self {
+            settings,
+            llm,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Register the `web_search` tool
+    pub async fn register_tool(&self, registry: &ToolRegistry) -> Result<(), String> {
+        let search = self.clone();
+        let handler: ToolHandler = Arc::new(move |request, context| {
+            let search = search.clone();
+            Box::pin(async move { to_output(search.search_tool(&request, &context).await) })
+        });
+
+        registry.register(web_search_definition(), handler).await
+    }
+
+    pub async fn config(&self) -> Result<WebSearchConfig, String> {
+        self.settings
+            .get_setting_or_default(SETTINGS_KEY, WebSearchConfig::default())
+            .await
+    }
+
+    pub async fn set_config(&self, config: &WebSearchConfig) -> Result<(), String> {
+        if config.backend == SearchBackend::Searxng && config.searxng_url.is_none() {
+            return Err("SearXNG needs searxngUrl".to_string());
+        }
+        let value = serde_json::to_value(config)
+            .map_err(|e| format!("Failed to serialize web search settings: {}", e))?;
+        self.settings.set_setting(SETTINGS_KEY, &value).await
+    }
+
+    async fn search_tool(
+        &self,
+        request: &ToolRequest,
+        _context: &ToolContext,
+    ) -> Result<serde_json::Value, String> {
+        let query = request
+            .input
+            .get("query")
+            .and_then(|v| v.as_str())
+            .filter(|query| !query.trim().is_empty())
+            .ok_or_else(|| "Missing 'query'".to_string())?;
+        let config = self.config().await?;
+        let max_results = request
+            .input
+            .get("maxResults")
+            .and_then(|v| v.as_u64())
+            .map_or(config.max_results, |n| n as usize)
+            .clamp(1, MAX_RESULTS);
+
+        let results = self.search(&config, query, max_results).await?;
+        Ok(serde_json::json!({
+            "backend": config.backend,
+            "results": results,
+        }))
+    }
+
+    /// Search with the configured backend
+    pub async fn search(
+        &self,
+        config: &WebSearchConfig,
+        query: &str,
+        max_results: usize,
+    ) -> Result<Vec<SearchResult>, String> {
+        let backend = config.backend;
+        let api_key = if backend.needs_api_key() {
+            let key = self.llm.provider_api_key(backend.as_str()).await;
+            Some(key.ok_or_else(|| format!("No API key is set for {}", backend.as_str()))?)
+        } else {
+            None
+        };
+        let api_key = api_key.unwrap_or_default();
+        let count = max_results.to_string();
+
+        let request = match backend {
+            SearchBackend::Brave => self
+                .client
+                .get("https://api.search.brave.com/res/v1/web/search")
+                .query(&[("q", query), ("count", count.as_str())])
+                .header("Accept", "application/json")
+                .header("X-Subscription-Token", api_key),
+            SearchBackend::Tavily => self
+                .client
+                .post("https://api.tavily.com/search")
+                .bearer_auth(api_key)
+                .json(&serde_json::json!({ "query": query, "max_results": max_results })),
+            SearchBackend::Searxng => {
+                let base_url = config
+                    .searxng_url
+                    .as_deref()
+                    .ok_or_else(|| "SearXNG needs searxngUrl".to_string())?;
+                self.client
+                    .get(format!("{}/search", base_url.trim_end_matches('/')))
+                    .query(&[("q", query), ("format", "json")])
+            }
+            SearchBackend::Bing => self
+                .client
+                .get("https://api.bing.microsoft.com/v7.0/search")
+                .query(&[("q", query), ("count", count.as_str())])
+                .header("Ocp-Apim-Subscription-Key", api_key),
+        };
+
+        let response = request
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| format!("{} search failed: {}", backend.as_str(), e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!(
+                "{} search failed with {}: {}",
+                backend.as_str(),
+                status,
+                body
+            ));
+        }
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid {} response: {}", backend.as_str(), e))?;
+
+        Ok(parse_results(backend, &body, max_results))
+    }
+}
+
+/// Normalize a backend's response body into search results
+pub fn parse_results(
+    backend: SearchBackend,
+    body: &serde_json::Value,
+    max_results: usize,
+) -> Vec<SearchResult> {
+    // Where each backend keeps its results and how it names their fields
+    let (results, title, snippet) = match backend {
+        SearchBackend::Brave => (&body["web"]["results"], "title", "description"),
+        SearchBackend::Tavily | SearchBackend::Searxng => (&body["results"], "title", "content"),
+        SearchBackend::Bing => (&body["webPages"]["value"], "name", "snippet"),
+    };
+    let field = |item: &serde_json::Value, name: &str| {
+        item.get(name)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    results
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|item| SearchResult {
+            title: field(item, title),
+            url: field(item, "url"),
+            snippet: field(item, snippet),
+        })
+        .filter(|result| !result.url.is_empty())
+        .take(max_results)
+        .collect()
+}
+
+fn to_output(result: Result<serde_json::Value, String>) -> ToolExecutionOutput {
+    match result {
+        Ok(data) => ToolExecutionOutput {
+            success: true,
+            data,
+            error: None,
+        },
+        Err(e) => ToolExecutionOutput {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(e),
+        },
+    }
+}
+
+fn web_search_definition() -> ToolDefinition {
+    ToolDefinition {
+        name: WEB_SEARCH_TOOL.to_string(),
+        description: "Search the web; returns the title, URL and a snippet of each result"
+            .to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Search query"
+                },
+                "maxResults": {
+                    "type": "integer",
+                    "description": "Number of results to return (1-20)"
+                }
+            },
+            "required": ["query"]
+        }),
+        requires_approval: false,
+        modifies_files: false,
+        capabilities: vec![ToolCapability::Network],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_results_per_backend() {
+        let brave = json!({ "web": { "results": [
+            { "title": "Rust", "url": "https://www.rust-lang.org", "description": "A language" },
+            { "title": "No URL" }
+        ] } });
+        assert_eq!(
+            parse_results(SearchBackend::Brave, &brave, 5),
+            vec![SearchResult {
+                title: "Rust".to_string(),
+                url: "https://www.rust-lang.org".to_string(),
+                snippet: "A language".to_string(),
+            }]
+        );
+
+        let tavily = json!({ "results": [
+            { "title": "A", "url": "https://a.example", "content": "first" },
+            { "title": "B", "url": "https://b.example", "content": "second" }
+        ] });
+        let results = parse_results(SearchBackend::Tavily, &tavily, 1);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].snippet, "first");
+        assert_eq!(parse_results(SearchBackend::Searxng, &tavily, 5).len(), 2);
+
+        let bing = json!({ "webPages": { "value": [
+            { "name": "Bing", "url": "https://bing.example", "snippet": "found" }
+        ] } });
+        let results = parse_results(SearchBackend::Bing, &bing, 5);
+        assert_eq!(results[0].title, "Bing");
+        assert_eq!(results[0].snippet, "found");
+
+        assert!(parse_results(SearchBackend::Bing, &json!({}), 5).is_empty());
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config: WebSearchConfig =
+            serde_json::from_value(json!({ "backend": "searxng" })).unwrap();
+        assert_eq!(config.backend, SearchBackend::Searxng);
+        assert_eq!(config.max_results, 5);
+        assert!(!SearchBackend::Searxng.needs_api_key());
+    }
+}
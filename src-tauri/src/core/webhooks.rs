@@ -0,0 +1,529 @@
+//! Webhooks
+//!
+//! HTTP endpoints of a project notified when its tasks start, complete or
+//! fail and when a tool call waits for approval. Webhooks are stored in
+//! settings under `webhooks.<project_id>`. Each event is POSTed as JSON and
+//! signed with the webhook's secret: `X-TalkCody-Signature` holds `sha256=`
+//! and the hex HMAC-SHA256 of `<timestamp>.<body>`, with the timestamp sent
+//! in `X-TalkCody-Timestamp`. Failed deliveries are retried with backoff, and
+//! every delivery is logged with the outcome of its latest attempt.
+
+use crate::core::event_log::LoggedEvent;
+use crate::core::types::{RuntimeEvent, RuntimeTaskId, RuntimeTaskState};
+use crate::storage::{
+    ChatHistoryRepository, SettingsRepository, WebhookDelivery, WebhookDeliveryStatus,
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Settings key prefix of a project's webhooks
+pub const WEBHOOKS_KEY: &str = "webhooks";
+
+/// Header holding the signature of a delivery
+pub const SIGNATURE_HEADER: &str = "X-TalkCody-Signature";
+
+/// Header holding the time a delivery was signed at
+pub const TIMESTAMP_HEADER: &str = "X-TalkCody-Timestamp";
+
+/// Time an endpoint may take to answer one attempt
+const DELIVERY_TIMEOUT_SECS: u64 = 10;
+
+/// Waits before each retry of a failed delivery
+const RETRY_DELAYS_SECS: [u64; 3] = [10, 60, 300];
+
+/// Task event a webhook can receive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    TaskStarted,
+    TaskCompleted,
+    TaskFailed,
+    ApprovalRequested,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::TaskStarted => "task_started",
+            WebhookEvent::TaskCompleted => "task_completed",
+            WebhookEvent::TaskFailed => "task_failed",
+            WebhookEvent::ApprovalRequested => "approval_requested",
+        }
+    }
+
+    /// Webhook event a runtime event stands for, with its task
+    fn from_runtime_event(event: &RuntimeEvent) -> Option<(Self, &RuntimeTaskId)> {
+        match event {
+            RuntimeEvent::TaskStateChanged {
+                task_id,
+                state,
+                previous_state,
+            } => match (state, previous_state) {
+                (RuntimeTaskState::Running, RuntimeTaskState::Pending) => {
+                    Some((WebhookEvent::TaskStarted, task_id))
+                }
+                (RuntimeTaskState::Completed, _) => Some((WebhookEvent::TaskCompleted, task_id)),
+                (RuntimeTaskState::Failed, _) => Some((WebhookEvent::TaskFailed, task_id)),
+                _ => None,
+            },
+            RuntimeEvent::ToolCallRequested { task_id, .. } => {
+                Some((WebhookEvent::ApprovalRequested, task_id))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A configured webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    /// Assigned when the webhook is saved
+    #[serde(default)]
+    pub id: String,
+    pub url: String,
+    /// Key deliveries are signed with
+    pub secret: String,
+    /// Events the webhook receives; every event when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<WebhookEvent>,
+}
+
+impl Webhook {
+    fn receives(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            return Err(format!("Webhook URL must be http or https: {}", self.url));
+        }
+        if self.secret.trim().is_empty() {
+            return Err("Webhook secret cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Body POSTed to a webhook
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    /// ID of the delivery; the same across retries
+    pub id: String,
+    pub event: WebhookEvent,
+    pub project_id: String,
+    pub session_id: String,
+    pub task_id: RuntimeTaskId,
+    pub timestamp: i64,
+    /// The runtime event that triggered the delivery
+    pub data: RuntimeEvent,
+}
+
+/// Stores the webhooks of projects and delivers task events to them
+#[derive(Clone)]
+pub struct WebhookManager {
+    settings: SettingsRepository,
+    chat_history: ChatHistoryRepository,
+    http: reqwest::Client,
+    retry_delays: Vec<Duration>,
+}
+
+impl WebhookManager {
+    pub fn new(settings: SettingsRepository, chat_history: ChatHistoryRepository) -> Self {
+        Self {
+            settings,
+            chat_history,
+            http: reqwest::Client::new(),
+            retry_delays: RETRY_DELAYS_SECS
+                .iter()
+                .map(|secs| Duration::from_secs(*secs))
+                .collect(),
+        }
+    }
+
+    /// Webhooks of a project
+    pub async fn list(&self, project_id: &str) -> Result<Vec<Webhook>, String> {
+        self.settings
+            .get_setting_or_default(&webhooks_key(project_id), Vec::new())
+            .await
+    }
+
+    /// Replace the webhooks of a project. Returns them with IDs assigned to
+    /// new ones.
+    pub async fn set(
+        &self,
+        project_id: &str,
+        mut webhooks: Vec<Webhook>,
+    ) -> Result<Vec<Webhook>, String> {
+        for webhook in &mut webhooks {
+            webhook.validate()?;
+            if webhook.id.is_empty() {
+                webhook.id = format!("wh_{}", uuid::Uuid::new_v4().simple());
+            }
+        }
+        let value = serde_json::to_value(&webhooks)
+            .map_err(|e| format!("Failed to serialize webhooks: {}", e))?;
+        self.settings
+            .set_setting(&webhooks_key(project_id), &value)
+            .await?;
+        Ok(webhooks)
+    }
+
+    /// Logged deliveries of a project, newest first
+    pub async fn list_deliveries(
+        &self,
+        project_id: &str,
+        webhook_id: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<WebhookDelivery>, String> {
+        self.chat_history
+            .list_webhook_deliveries(project_id, webhook_id, limit)
+            .await
+    }
+
+    /// Deliver webhooks for logged runtime events until the runtime is gone
+    pub fn spawn(&self, mut events: broadcast::Receiver<LoggedEvent>) {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(logged) => manager.notify(&logged).await,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("Webhooks missed {} runtime events", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Start deliveries of an event to the webhooks of its session's project
+    async fn notify(&self, logged: &LoggedEvent) {
+        let Some((event, task_id)) = WebhookEvent::from_runtime_event(&logged.event) else {
+            return;
+        };
+        let Some(session_id) = &logged.session_id else {
+            return;
+        };
+        let (project_id, webhooks) = match self.webhooks_for_session(session_id).await {
+            Ok(Some(found)) => found,
+            Ok(None) => return,
+            Err(e) => {
+                log::warn!("Failed to load webhooks of session {}: {}", session_id, e);
+                return;
+            }
+        };
+
+        for webhook in webhooks.into_iter().filter(|w| w.receives(event)) {
+            let now = chrono::Utc::now().timestamp();
+            let payload = WebhookPayload {
+                id: format!("whd_{}", uuid::Uuid::new_v4().simple()),
+                event,
+                project_id: project_id.clone(),
+                session_id: session_id.clone(),
+                task_id: task_id.clone(),
+                timestamp: now,
+                data: logged.event.clone(),
+            };
+            let delivery = WebhookDelivery {
+                id: payload.id.clone(),
+                webhook_id: webhook.id.clone(),
+                project_id: project_id.clone(),
+                session_id: Some(session_id.clone()),
+                task_id: Some(task_id.clone()),
+                event: event.as_str().to_string(),
+                payload: serde_json::to_value(&payload).unwrap_or_default(),
+                status: WebhookDeliveryStatus::Pending,
+                attempts: 0,
+                response_status: None,
+                error: None,
+                created_at: now,
+                updated_at: now,
+            };
+            if let Err(e) = self.chat_history.create_webhook_delivery(&delivery).await {
+                log::warn!("Failed to log webhook delivery {}: {}", delivery.id, e);
+            }
+
+            let manager = self.clone();
+            tokio::spawn(async move { manager.deliver(&webhook, delivery).await });
+        }
+    }
+
+    /// Project of a session and its webhooks, when it has any
+    async fn webhooks_for_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<(String, Vec<Webhook>)>, String> {
+        let Some(project_id) = self
+            .chat_history
+            .get_session(session_id)
+            .await?
+            .and_then(|session| session.project_id)
+        else {
+            return Ok(None);
+        };
+        let webhooks = self.list(&project_id).await?;
+        Ok((!webhooks.is_empty()).then_some((project_id, webhooks)))
+    }
+
+    /// POST a delivery until it is accepted or its retries run out
+    async fn deliver(&self, webhook: &Webhook, mut delivery: WebhookDelivery) {
+        let body = delivery.payload.to_string();
+        for attempt in 0..=self.retry_delays.len() {
+            if attempt > 0 {
+                tokio::time::sleep(self.retry_delays[attempt - 1]).await;
+            }
+
+            let result = self.post(webhook, &delivery, &body).await;
+            delivery.attempts += 1;
+            delivery.updated_at = chrono::Utc::now().timestamp();
+            let delivered = match result {
+                Ok(status) => {
+                    delivery.response_status = Some(status);
+                    delivery.error = None;
+                    delivery.status = WebhookDeliveryStatus::Delivered;
+                    true
+                }
+                Err((status, error)) => {
+                    delivery.response_status = status;
+                    delivery.error = Some(error);
+                    if attempt == self.retry_delays.len() {
+                        delivery.status = WebhookDeliveryStatus::Failed;
+                    }
+                    false
+                }
+            };
+            if let Err(e) = self.chat_history.update_webhook_delivery(&delivery).await {
+                log::warn!("Failed to log webhook delivery {}: {}", delivery.id, e);
+            }
+            if delivered {
+                return;
+            }
+        }
+        log::warn!(
+            "Webhook {} failed to receive {} after {} attempts: {}",
+            webhook.url,
+            delivery.event,
+            delivery.attempts,
+            delivery.error.as_deref().unwrap_or_default()
+        );
+    }
+
+    /// POST a delivery once. Returns the response status, or it and why the
+    /// attempt failed.
+    async fn post(
+        &self,
+        webhook: &Webhook,
+        delivery: &WebhookDelivery,
+        body: &str,
+    ) -> Result<i64, (Option<i64>, String)> {
+        let timestamp = chrono::Utc::now().timestamp();
+        let response = self
+            .http
+            .post(&webhook.url)
+            .timeout(Duration::from_secs(DELIVERY_TIMEOUT_SECS))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-TalkCody-Event", &delivery.event)
+            .header("X-TalkCody-Delivery", &delivery.id)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, sign(&webhook.secret, timestamp, body))
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| (None, format!("Failed to call {}: {}", webhook.url, e)))?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(i64::from(status.as_u16()))
+        } else {
+            Err((
+                Some(i64::from(status.as_u16())),
+                format!("{} returned {}", webhook.url, status),
+            ))
+        }
+    }
+}
+
+fn webhooks_key(project_id: &str) -> String {
+    format!("{}.{}", WEBHOOKS_KEY, project_id)
+}
+
+/// Signature of a delivery body sent at a time, as `sha256=<hex HMAC>`
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{Session, SessionStatus, Storage};
+    use axum::http::{HeaderMap, StatusCode};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_events_and_signature() {
+        let event = |state, previous_state| RuntimeEvent::TaskStateChanged {
+            task_id: "task-1".to_string(),
+            state,
+            previous_state,
+        };
+        let mapped = |event: &RuntimeEvent| WebhookEvent::from_runtime_event(event).map(|e| e.0);
+        assert_eq!(
+            mapped(&event(RuntimeTaskState::Running, RuntimeTaskState::Pending)),
+            Some(WebhookEvent::TaskStarted)
+        );
+        // Resuming after an approval is not a start
+        assert_eq!(
+            mapped(&event(
+                RuntimeTaskState::Running,
+                RuntimeTaskState::WaitingForUser
+            )),
+            None
+        );
+        assert_eq!(
+            mapped(&event(RuntimeTaskState::Failed, RuntimeTaskState::Running)),
+            Some(WebhookEvent::TaskFailed)
+        );
+
+        assert_eq!(
+            sign("s3cret", 1700000000, r#"{"event":"task_started"}"#),
+            "sha256=772cd712237f649c6be3491c820669c7618a20c2d6cc2565bb8cfdc8ac96293b"
+        );
+        assert_ne!(sign("a", 1, "{}"), sign("b", 1, "{}"));
+        assert_ne!(sign("a", 1, "{}"), sign("a", 2, "{}"));
+    }
+
+    #[tokio::test]
+    async fn test_delivery_is_signed_retried_and_logged() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().to_path_buf(),
+            temp_dir.path().join("attachments"),
+        )
+        .await
+        .expect("Failed to create storage");
+        let now = chrono::Utc::now().timestamp();
+        storage
+            .chat_history
+            .create_session(&Session {
+                id: "session-1".to_string(),
+                project_id: Some("project-a".to_string()),
+                title: None,
+                summary: None,
+                status: SessionStatus::Running,
+                created_at: now,
+                updated_at: now,
+                last_event_id: None,
+                metadata: None,
+                starred: false,
+                archived_at: None,
+                user_id: None,
+            })
+            .await
+            .unwrap();
+
+        // The endpoint fails the first attempt and accepts the retry
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(Mutex::new(Vec::<(HeaderMap, String)>::new()));
+        let app = axum::Router::new().route(
+            "/hook",
+            axum::routing::post({
+                let attempts = attempts.clone();
+                let received = received.clone();
+                move |headers: HeaderMap, body: String| async move {
+                    received.lock().unwrap().push((headers, body));
+                    if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    } else {
+                        StatusCode::NO_CONTENT
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let mut manager =
+            WebhookManager::new(storage.settings.clone(), storage.chat_history.clone());
+        manager.retry_delays = vec![Duration::ZERO];
+        let webhook = Webhook {
+            id: String::new(),
+            url,
+            secret: "s3cret".to_string(),
+            events: vec![WebhookEvent::TaskCompleted],
+        };
+        assert!(manager
+            .set(
+                "project-a",
+                vec![Webhook {
+                    url: "ftp://example.com".to_string(),
+                    ..webhook.clone()
+                }]
+            )
+            .await
+            .is_err());
+        let saved = manager.set("project-a", vec![webhook]).await.unwrap();
+        assert!(saved[0].id.starts_with("wh_"));
+
+        let logged = |state| LoggedEvent {
+            session_id: Some("session-1".to_string()),
+            sequence: None,
+            event: RuntimeEvent::TaskStateChanged {
+                task_id: "task-1".to_string(),
+                state,
+                previous_state: RuntimeTaskState::Running,
+            },
+        };
+        // Filtered out by the webhook's events
+        manager.notify(&logged(RuntimeTaskState::Failed)).await;
+        manager.notify(&logged(RuntimeTaskState::Completed)).await;
+
+        let delivery = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let deliveries = manager
+                    .list_deliveries("project-a", None, None)
+                    .await
+                    .unwrap();
+                if let Some(delivery) = deliveries
+                    .into_iter()
+                    .find(|d| d.status != WebhookDeliveryStatus::Pending)
+                {
+                    return delivery;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("Delivery did not finish");
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Delivered);
+        assert_eq!(delivery.event, "task_completed");
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.response_status, Some(204));
+        assert_eq!(delivery.webhook_id, saved[0].id);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        let (headers, body) = &received[1];
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().unwrap(),
+            sign("s3cret", timestamp, body)
+        );
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["event"], "task_completed");
+        assert_eq!(payload["projectId"], "project-a");
+        assert_eq!(payload["id"], delivery.id);
+    }
+}
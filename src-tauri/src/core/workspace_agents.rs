@@ -0,0 +1,327 @@
+//! Workspace Agents
+//!
+//! Custom agents defined in a workspace under `.talkcody/agents`. A `*.json`
+//! file holds one definition; a `*.md` file has `key: value` front matter
+//! between `---` lines and uses its body as the system prompt. Definitions
+//! are watched and reloaded on change, and a task picks one by name through
+//! the `agent` task setting.
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Directory under the workspace root holding agent definitions
+pub const AGENTS_DIR: &str = ".talkcody/agents";
+
+/// Agent defined by a file in the workspace
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WorkspaceAgent {
+    /// Defaults to the file name without its extension
+    pub name: String,
+    pub description: Option<String>,
+    pub system_prompt: Option<String>,
+    /// Tools the agent may use; every registered tool when empty
+    pub tools: Vec<String>,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    /// File the definition was loaded from
+    pub path: String,
+}
+
+/// Parse an agent definition; the format follows the file extension
+pub fn parse_agent_file(path: &Path, content: &str) -> Result<WorkspaceAgent, String> {
+    let parsed = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str::<WorkspaceAgent>(content).map_err(|e| e.to_string()),
+        Some("md") => parse_markdown_agent(content),
+        _ => return Err(format!("Unsupported agent file: {}", path.display())),
+    };
+    let mut agent =
+        parsed.map_err(|e| format!("Invalid agent definition {}: {}", path.display(), e))?;
+
+    agent.name = agent.name.trim().to_string();
+    if agent.name.is_empty() {
+        agent.name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+    }
+    if let Some(temperature) = agent.temperature {
+        if !(0.0..=2.0).contains(&temperature) {
+            return Err(format!(
+                "Invalid agent definition {}: temperature {} is outside 0-2",
+                path.display(),
+                temperature
+            ));
+        }
+    }
+    agent.path = path.to_string_lossy().to_string();
+
+    Ok(agent)
+}
+
+fn parse_markdown_agent(content: &str) -> Result<WorkspaceAgent, String> {
+    let content = content.trim_start_matches('\u{feff}');
+    let mut agent = WorkspaceAgent::default();
+    let mut lines = content.lines();
+
+    let body = if lines.next().map(str::trim) == Some("---") {
+        let mut closed = false;
+        for line in lines.by_ref() {
+            let line = line.trim();
+            if line == "---" {
+                closed = true;
+                break;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("Expected 'key: value', got '{}'", line))?;
+            let value = unquote(value.trim());
+            match key.trim() {
+                "name" => agent.name = value.to_string(),
+                "description" => agent.description = Some(value.to_string()),
+                "model" => agent.model = Some(value.to_string()),
+                "temperature" => {
+                    agent.temperature = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid temperature '{}'", value))?,
+                    );
+                }
+                "tools" => agent.tools = parse_list(value),
+                other => log::debug!("Ignoring unknown agent field '{}'", other),
+            }
+        }
+        if !closed {
+            return Err("Front matter is not closed with '---'".to_string());
+        }
+        lines.collect::<Vec<_>>().join("\n")
+    } else {
+        content.to_string()
+    };
+
+    let body = body.trim();
+    if !body.is_empty() {
+        agent.system_prompt = Some(body.to_string());
+    }
+
+    Ok(agent)
+}
+
+/// `a, b` or `[a, "b"]`
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|item| unquote(item.trim()))
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+/// Load the agents of a workspace, sorted by file name. Invalid files and
+/// names defined twice are skipped with a warning.
+pub fn load_agents(workspace_root: &Path) -> Vec<WorkspaceAgent> {
+    let Ok(entries) = std::fs::read_dir(workspace_root.join(AGENTS_DIR)) else {
+        return vec![];
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && matches!(
+                    path.extension().and_then(|ext| ext.to_str()),
+                    Some("json" | "md")
+                )
+        })
+        .collect();
+    paths.sort();
+
+    let mut names = HashSet::new();
+    let mut agents = Vec::new();
+    for path in paths {
+        let agent = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+            .and_then(|content| parse_agent_file(&path, &content));
+        match agent {
+            Ok(agent) if names.insert(agent.name.clone()) => agents.push(agent),
+            Ok(agent) => log::warn!(
+                "Skipping {}: agent '{}' is already defined",
+                path.display(),
+                agent.name
+            ),
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+
+    agents
+}
+
+/// Agents of each workspace, reloaded when their definitions change
+#[derive(Clone, Default)]
+pub struct WorkspaceAgentRegistry {
+    agents: Arc<RwLock<HashMap<PathBuf, Vec<WorkspaceAgent>>>>,
+    watchers: Arc<Mutex<HashMap<PathBuf, RecommendedWatcher>>>,
+}
+
+impl WorkspaceAgentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Agents defined in a workspace
+    pub fn list(&self, workspace_root: &str) -> Vec<WorkspaceAgent> {
+        let root = PathBuf::from(workspace_root);
+        if let Some(agents) = self.read_agents().get(&root) {
+            return agents.clone();
+        }
+
+        let agents = load_agents(&root);
+        // Without the directory nothing is cached, so it is picked up once created
+        if root.join(AGENTS_DIR).is_dir() {
+            self.write_agents().insert(root.clone(), agents.clone());
+            self.watch(&root);
+        }
+        agents
+    }
+
+    /// Find an agent of a workspace by name
+    pub fn get(&self, workspace_root: &str, name: &str) -> Option<WorkspaceAgent> {
+        self.list(workspace_root)
+            .into_iter()
+            .find(|agent| agent.name == name)
+    }
+
+    /// Reload the workspace's agents whenever its agents directory changes
+    fn watch(&self, root: &Path) {
+        let agents = self.agents.clone();
+        let watched_root = root.to_path_buf();
+        let handler = move |result: notify::Result<notify::Event>| {
+            if result.is_err() {
+                return;
+            }
+            let mut cache = agents.write().unwrap_or_else(|e| e.into_inner());
+            if watched_root.join(AGENTS_DIR).is_dir() {
+                let reloaded = load_agents(&watched_root);
+                log::info!(
+                    "Reloaded {} agents of {}",
+                    reloaded.len(),
+                    watched_root.display()
+                );
+                cache.insert(watched_root.clone(), reloaded);
+            } else {
+                // The next lookup watches the directory again if it is recreated
+                cache.remove(&watched_root);
+            }
+        };
+
+        let watcher =
+            RecommendedWatcher::new(handler, notify::Config::default()).and_then(|mut watcher| {
+                watcher.watch(&root.join(AGENTS_DIR), RecursiveMode::NonRecursive)?;
+                Ok(watcher)
+            });
+        match watcher {
+            Ok(watcher) => {
+                self.watchers
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(root.to_path_buf(), watcher);
+            }
+            Err(e) => {
+                // Unwatched agents are loaded again on every lookup instead
+                log::warn!("Failed to watch agents of {}: {}", root.display(), e);
+                self.write_agents().remove(root);
+            }
+        }
+    }
+
+    fn read_agents(&self) -> std::sync::RwLockReadGuard<'_, HashMap<PathBuf, Vec<WorkspaceAgent>>> {
+        self.agents.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write_agents(
+        &self,
+    ) -> std::sync::RwLockWriteGuard<'_, HashMap<PathBuf, Vec<WorkspaceAgent>>> {
+        self.agents.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_markdown_agent() {
+        let content = "---\nname: reviewer\nmodel: \"gpt-4o\"\ntemperature: 0.2\ntools: [read_file, 'search_files']\n---\n\nYou review diffs.\n";
+        let agent = parse_agent_file(Path::new("/w/.talkcody/agents/r.md"), content).unwrap();
+
+        assert_eq!(agent.name, "reviewer");
+        assert_eq!(agent.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(agent.temperature, Some(0.2));
+        assert_eq!(agent.tools, vec!["read_file", "search_files"]);
+        assert_eq!(agent.system_prompt.as_deref(), Some("You review diffs."));
+
+        // A file without front matter is all prompt and named after the file
+        let agent = parse_agent_file(Path::new("docs.md"), "Write docs.").unwrap();
+        assert_eq!(agent.name, "docs");
+        assert!(agent.tools.is_empty());
+
+        assert!(parse_agent_file(Path::new("x.md"), "---\nname: x\n").is_err());
+        assert!(parse_agent_file(Path::new("x.md"), "---\ntemperature: 3\n---\n").is_err());
+    }
+
+    #[test]
+    fn test_load_agents_skips_invalid_and_duplicate_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join(AGENTS_DIR);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("a.json"),
+            r#"{ "name": "tester", "systemPrompt": "Write tests", "tools": ["read_file"] }"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("b.md"), "---\nname: tester\n---\nDuplicate").unwrap();
+        std::fs::write(dir.join("c.json"), "{ not json").unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let agents = load_agents(temp_dir.path());
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].name, "tester");
+        assert_eq!(agents[0].system_prompt.as_deref(), Some("Write tests"));
+    }
+
+    #[test]
+    fn test_registry_picks_up_created_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().to_string_lossy().to_string();
+        let registry = WorkspaceAgentRegistry::new();
+        assert!(registry.list(&root).is_empty());
+
+        let dir = temp_dir.path().join(AGENTS_DIR);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("docs.md"), "Write docs.").unwrap();
+
+        assert!(registry.get(&root, "docs").is_some());
+        assert!(registry.get(&root, "missing").is_none());
+    }
+}
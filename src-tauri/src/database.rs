@@ -1,4 +1,5 @@
 // Database module using libsql for Turso integration
+use crate::llm::auth::api_key_manager::LlmState;
 use libsql::Builder;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
@@ -231,6 +232,39 @@ impl Database {
         })
     }
 
+    /// Run a script of several `;`-separated statements and then `statement`
+    /// in one transaction, so either all of them apply or none does.
+    /// `execute` only runs the first statement of a script.
+    pub async fn execute_script(
+        &self,
+        script: &str,
+        statement: &str,
+        params: Vec<serde_json::Value>,
+    ) -> Result<(), String> {
+        let lock = self.conn.lock().await;
+        let conn = lock.as_ref().ok_or("Database not connected")?;
+        let libsql_params: Vec<libsql::Value> = params.iter().map(json_to_libsql_value).collect();
+
+        let tx = conn
+            .transaction()
+            .await
+            .map_err(|e| format!("Transaction error: {}", e))?;
+        let result = match tx.execute_batch(script).await {
+            Ok(_) => tx.execute(statement, libsql_params).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => tx
+                .commit()
+                .await
+                .map_err(|e| format!("Commit error: {}", e)),
+            Err(e) => {
+                let _ = tx.rollback().await;
+                Err(format!("Execute error: {}", e))
+            }
+        }
+    }
+
     pub async fn batch(
         &self,
         statements: Vec<(String, Vec<serde_json::Value>)>,
@@ -326,8 +360,22 @@ fn base64_encode(data: &[u8]) -> String {
 
 // Tauri commands
 #[tauri::command]
-pub async fn db_connect(db: State<'_, Arc<Database>>) -> Result<(), String> {
-    db.connect().await
+pub async fn db_connect(
+    db: State<'_, Arc<Database>>,
+    llm: State<'_, LlmState>,
+) -> Result<(), String> {
+    db.connect().await?;
+
+    let api_keys = {
+        let guard = llm.api_keys.lock().await;
+        guard.clone()
+    };
+    if let Err(e) = api_keys.migrate_credentials().await {
+        log::warn!("Failed to move credentials into the keychain: {}", e);
+    }
+    // Logs and runtime events mask the stored provider keys and tokens
+    crate::llm::redaction::refresh_shared(&api_keys).await;
+    Ok(())
 }
 
 #[tauri::command]
@@ -1,3 +1,7 @@
+use crate::integrations::router::{ChatRouter, ChatRouting};
+use crate::integrations::types::{ChannelType, IncomingMessage};
+use crate::integrations::{FeishuAdapter, FeishuWebhook, IntegrationAdapter};
+use axum::http::HeaderMap;
 #[cfg(feature = "feishu-websocket")]
 use open_lark::client::ws_client::LarkWsClient;
 use open_lark::prelude::{
@@ -8,10 +12,10 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tauri::{AppHandle, Manager, Runtime, State};
 use tokio::runtime::Builder;
 use tokio::sync::{watch, Mutex};
 use tokio::time::sleep;
@@ -22,6 +26,8 @@ pub struct MessageResourceResponse {
     pub data: Vec<u8>,
 }
 
+/// Integration ID of the gateway's messages, and key of its access list
+const FEISHU_INTEGRATION_ID: &str = "feishu";
 const FEISHU_ATTACHMENTS_DIR: &str = "attachments";
 const FEISHU_MEDIA_PREFIX: &str = "feishu";
 const DEFAULT_ERROR_BACKOFF_MS: u64 = 1500;
@@ -65,17 +71,6 @@ pub struct FeishuRemoteAttachment {
     pub caption: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct FeishuInboundMessage {
-    pub chat_id: String,
-    pub message_id: String,
-    pub text: String,
-    pub open_id: String,
-    pub date: i64,
-    pub attachments: Option<Vec<FeishuRemoteAttachment>>,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FeishuSendMessageRequest {
@@ -133,7 +128,7 @@ impl FeishuGateway {
     }
 }
 
-type FeishuGatewayState = Arc<Mutex<FeishuGateway>>;
+pub type FeishuGatewayState = Arc<Mutex<FeishuGateway>>;
 
 fn now_ms() -> i64 {
     SystemTime::now()
@@ -332,6 +327,17 @@ fn parse_text_content(content: &str) -> String {
         .unwrap_or_else(|| content.to_string())
 }
 
+/// Text of a message, from its JSON content
+fn message_text(message_type: &str, content: &str) -> String {
+    if message_type == "text" {
+        return parse_text_content(content);
+    }
+    serde_json::from_str::<Value>(content)
+        .ok()
+        .and_then(|value| value.get("text")?.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
 async fn build_message_payload(
     app_handle: &AppHandle,
     client: &LarkClient,
@@ -344,14 +350,9 @@ async fn build_message_payload(
 
     let parsed = serde_json::from_str::<Value>(content).ok();
 
-    if message_type == "text" {
-        text_parts.push(parse_text_content(content));
-    } else if let Some(text) = parsed
-        .as_ref()
-        .and_then(|value| value.get("text"))
-        .and_then(|value| value.as_str())
-    {
-        text_parts.push(text.to_string());
+    let text = message_text(message_type, content);
+    if message_type == "text" || !text.is_empty() {
+        text_parts.push(text);
     }
 
     let Some(attachments_dir) = attachments_root(app_handle).await? else {
@@ -515,6 +516,176 @@ async fn start_ws_connection(
     start_ws_connection_impl(app_handle, state, config).await
 }
 
+/// Adapter verifying webhook deliveries, with the configuration it was made
+/// with; kept so its replay guard remembers earlier events
+static WEBHOOK_ADAPTER: OnceLock<std::sync::Mutex<Option<(String, Arc<FeishuAdapter>)>>> =
+    OnceLock::new();
+
+fn adapter(config: &FeishuConfig) -> FeishuAdapter {
+    let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+    FeishuAdapter::new(
+        FEISHU_INTEGRATION_ID,
+        crate::integrations::FeishuConfig {
+            app_id: config.app_id.clone(),
+            app_secret: config.app_secret.clone(),
+            webhook_url: None,
+            verification_token: non_empty(&config.verification_token),
+            encrypt_key: non_empty(&config.encrypt_key),
+        },
+    )
+}
+
+fn webhook_adapter(config: &FeishuConfig) -> Arc<FeishuAdapter> {
+    let credentials = format!(
+        "{}:{}:{}:{}",
+        config.app_id, config.app_secret, config.verification_token, config.encrypt_key
+    );
+    let mut cached = WEBHOOK_ADAPTER
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some((made_with, adapter)) = cached.as_ref() {
+        if *made_with == credentials {
+            return adapter.clone();
+        }
+    }
+    let webhook_adapter = Arc::new(adapter(config));
+    *cached = Some((credentials, webhook_adapter.clone()));
+    webhook_adapter
+}
+
+/// Verify a webhook delivery and pass its message on to the agent. Returns
+/// the answer to the delivery: the challenge of a URL verification, or an
+/// empty object; the agent's reply is sent once its task finishes.
+pub async fn receive_webhook(
+    app_handle: &AppHandle,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Value, String> {
+    let gateway = app_handle
+        .try_state::<FeishuGatewayState>()
+        .ok_or_else(|| "Feishu gateway is not ready".to_string())?;
+    let config = gateway.lock().await.config.clone();
+    let messages = match webhook_adapter(&config).receive_webhook(headers, body)? {
+        FeishuWebhook::Challenge(challenge) => return Ok(json!({ "challenge": challenge })),
+        FeishuWebhook::Messages(messages) => messages,
+    };
+
+    let routing = app_handle
+        .try_state::<ChatRouting>()
+        .ok_or_else(|| "Core runtime is not ready".to_string())?;
+    let router = chat_router(&routing, &config);
+    for message in messages {
+        if !is_open_id_allowed(&config.allowed_open_ids, &message.sender_id) {
+            log::debug!(
+                "[FeishuGateway] Open id not in allowlist open_id={}",
+                message.sender_id
+            );
+            continue;
+        }
+        let router = router.clone();
+        tauri::async_runtime::spawn(async move { router.receive(message).await });
+    }
+    Ok(json!({}))
+}
+
+/// Router of the gateway's messages, answering through the app
+fn chat_router(routing: &ChatRouting, config: &FeishuConfig) -> Arc<ChatRouter> {
+    let credentials = format!("{}:{}", config.app_id, config.app_secret);
+    routing.router(FEISHU_INTEGRATION_ID, &credentials, || {
+        Arc::new(adapter(config)) as Arc<dyn IntegrationAdapter>
+    })
+}
+
+/// A direct message as the router takes it, before attachments are added
+fn incoming_message(
+    open_id: &str,
+    message_id: &str,
+    message_type: &str,
+    content: &str,
+    create_time: &str,
+) -> IncomingMessage {
+    IncomingMessage {
+        integration_id: FEISHU_INTEGRATION_ID.to_string(),
+        channel_type: ChannelType::Feishu,
+        sender_id: open_id.to_string(),
+        sender_name: None,
+        chat_id: open_id.to_string(),
+        message_id: message_id.to_string(),
+        content: message_text(message_type, content),
+        timestamp: create_time.parse::<i64>().unwrap_or_else(|_| now_ms()),
+        reply_to: None,
+        media: Vec::new(),
+    }
+}
+
+/// Pass a message on to the agent if its sender is on the access list,
+/// with the paths of its downloaded attachments
+async fn route_message(
+    router: Arc<ChatRouter>,
+    app_handle: AppHandle,
+    client: Arc<LarkClient>,
+    mut incoming: IncomingMessage,
+    message_type: String,
+    content: String,
+) {
+    if !router.admit(&incoming).await {
+        log::debug!(
+            "[FeishuGateway] Dropped message open_id={} message_id={}: sender not allowed",
+            incoming.sender_id,
+            incoming.message_id
+        );
+        return;
+    }
+
+    let attachments = match build_message_payload(
+        &app_handle,
+        &client,
+        &message_type,
+        &content,
+        &incoming.message_id,
+    )
+    .await
+    {
+        Ok((_, attachments)) => attachments,
+        Err(error) => {
+            log::warn!("[FeishuGateway] Failed to build message payload: {error}");
+            Vec::new()
+        }
+    };
+    if incoming.content.trim().is_empty() && attachments.is_empty() {
+        log::debug!(
+            "[FeishuGateway] Ignoring empty message open_id={} message_id={}",
+            incoming.sender_id,
+            incoming.message_id
+        );
+        return;
+    }
+    for attachment in &attachments {
+        incoming.content.push_str(&format!(
+            "\n[Attached {}: {}]",
+            attachment.attachment_type, attachment.file_path
+        ));
+    }
+
+    log::debug!(
+        "[FeishuGateway] Inbound message open_id={} message_id={} text_len={} attachments={}",
+        incoming.sender_id,
+        incoming.message_id,
+        incoming.content.len(),
+        attachments.len()
+    );
+    let (open_id, message_id) = (incoming.sender_id.clone(), incoming.message_id.clone());
+    if let Err(error) = router.handle(incoming).await {
+        log::warn!(
+            "[FeishuGateway] Failed to handle message open_id={} message_id={}: {}",
+            open_id,
+            message_id,
+            error
+        );
+    }
+}
+
 #[cfg(not(feature = "feishu-websocket"))]
 async fn start_ws_connection_impl(
     _app_handle: AppHandle,
@@ -535,6 +706,7 @@ async fn start_ws_connection_impl(
     let open_id_allowlist = config.allowed_open_ids.clone();
     let verification_token = config.verification_token.clone();
     let encrypt_key = config.encrypt_key.clone();
+    let gateway_config = config.clone();
 
     let handler_app = app_handle.clone();
     let handler = EventDispatcherHandler::builder()
@@ -542,6 +714,7 @@ async fn start_ws_connection_impl(
             let client = client.clone();
             let app_handle = handler_app.clone();
             let open_id_allowlist = open_id_allowlist.clone();
+            let config = gateway_config.clone();
             let state = state.clone();
             tokio::spawn(async move {
                 let sender = event.event.sender;
@@ -579,70 +752,37 @@ async fn start_ws_connection_impl(
                     message.message_type
                 );
 
-                let (text, attachments) = match build_message_payload(
-                    &app_handle,
-                    &client,
-                    &message.message_type,
-                    &message.content,
-                    &message.message_id,
-                )
-                .await
-                {
-                    Ok(payload) => payload,
-                    Err(error) => {
-                        log::warn!("[FeishuGateway] Failed to build message payload: {error}");
-                        (String::new(), Vec::new())
-                    }
-                };
-
-                if text.trim().is_empty() && attachments.is_empty() {
-                    log::debug!(
-                        "[FeishuGateway] Ignoring empty message open_id={} message_id={}",
-                        open_id,
-                        message.message_id
+                // Access is checked before attachments are downloaded, on the
+                // app's runtime, where the agent's tasks run
+                let Some(routing) = app_handle.try_state::<ChatRouting>() else {
+                    log::warn!(
+                        "[FeishuGateway] Dropping message open_id={}: the runtime is not ready",
+                        open_id
                     );
                     return;
-                }
-
-                log::debug!(
-                    "[FeishuGateway] Inbound message open_id={} message_id={} text_len={} attachments={}",
-                    open_id,
-                    message.message_id,
-                    text.len(),
-                    attachments.len()
-                );
-
-                let date = message
-                    .create_time
-                    .parse::<i64>()
-                    .unwrap_or_else(|_| now_ms());
-
-                let message_id = message.message_id.clone();
-                let payload = FeishuInboundMessage {
-                    chat_id: open_id.clone(),
-                    message_id: message_id.clone(),
-                    text,
-                    open_id: open_id.clone(),
-                    date,
-                    attachments: if attachments.is_empty() {
-                        None
-                    } else {
-                        Some(attachments)
-                    },
                 };
-
-                match app_handle.emit("feishu-inbound-message", payload) {
-                    Ok(_) => {
-                        log::debug!(
-                            "[FeishuGateway] Emitted inbound message open_id={} message_id={}",
-                            open_id,
-                            message_id
-                        );
-                    }
-                    Err(error) => {
-                        log::error!("[FeishuGateway] Failed to emit message: {}", error);
-                    }
-                }
+                let routing = routing.inner().clone();
+                let incoming = incoming_message(
+                    &open_id,
+                    &message.message_id,
+                    &message.message_type,
+                    &message.content,
+                    &message.create_time,
+                );
+                let route_app = app_handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    // Created here so its outbound queue runs on the app's runtime
+                    let router = chat_router(&routing, &config);
+                    route_message(
+                        router,
+                        route_app,
+                        client,
+                        incoming,
+                        message.message_type,
+                        message.content,
+                    )
+                    .await
+                });
 
                 let mut gateway = state.lock().await;
                 gateway.last_event_at_ms = Some(now_ms());
@@ -774,14 +914,19 @@ pub struct FeishuGatewayStatus {
 pub async fn feishu_get_status(
     state: State<'_, FeishuGatewayState>,
 ) -> Result<FeishuGatewayStatus, String> {
+    Ok(gateway_status(&state).await)
+}
+
+/// Status of the gateway, also reported by the server's diagnostics
+pub async fn gateway_status(state: &FeishuGatewayState) -> FeishuGatewayStatus {
     let gateway = state.lock().await;
-    Ok(FeishuGatewayStatus {
+    FeishuGatewayStatus {
         running: gateway.running,
         last_event_at_ms: gateway.last_event_at_ms,
         last_error: gateway.last_error.clone(),
         last_error_at_ms: gateway.last_error_at_ms,
         backoff_ms: gateway.backoff_ms,
-    })
+    }
 }
 
 #[tauri::command]
@@ -867,8 +1012,8 @@ pub fn default_state() -> FeishuGatewayState {
 #[cfg(test)]
 mod tests {
     use super::{
-        build_attachment_filename, chat_kind, is_open_id_allowed, parse_text_content, sender_kind,
-        FeishuChatKind, FeishuSenderKind,
+        build_attachment_filename, chat_kind, incoming_message, is_open_id_allowed,
+        parse_text_content, sender_kind, FeishuChatKind, FeishuSenderKind,
     };
     use serde_json::{json, Value};
 
@@ -883,6 +1028,24 @@ mod tests {
         assert!(!is_open_id_allowed(&allowed, "ou_other"));
     }
 
+    #[test]
+    fn incoming_message_takes_text_from_content() {
+        let message = incoming_message(
+            "ou_sender",
+            "om_1",
+            "text",
+            r#"{"text":"/pair 123456"}"#,
+            "1700000000000",
+        );
+        assert_eq!(message.sender_id, "ou_sender");
+        assert_eq!(message.chat_id, "ou_sender");
+        assert_eq!(message.content, "/pair 123456");
+        assert_eq!(message.timestamp, 1_700_000_000_000);
+
+        let image = incoming_message("ou_sender", "om_2", "image", r#"{"image_key":"k"}"#, "");
+        assert!(image.content.is_empty());
+    }
+
     #[test]
     fn sender_kind_filters_non_user() {
         assert_eq!(sender_kind("user"), FeishuSenderKind::User);
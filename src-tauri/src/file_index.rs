@@ -0,0 +1,680 @@
+//! Persistent file index for instant filename and glob queries.
+//!
+//! Each indexed root keeps an in-memory map of relative paths to metadata that is
+//! persisted in the `file_index` table. The index is built once with the parallel
+//! walker, kept current by FileWatcher events, and reloaded from the database on
+//! the next launch (then reconciled in the background), so queries on large
+//! monorepos no longer re-walk the tree on every keystroke.
+
+use crate::constants::should_exclude_dir;
+use crate::database::Database;
+use crate::file_search::{FileSearchResult, HighPerformanceFileSearch};
+use crate::file_watcher::FileSystemChange;
+use crate::glob::{GlobResult, HighPerformanceGlob};
+use crate::walker::{WalkerConfig, WorkspaceWalker};
+use ignore::WalkState;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Manager};
+
+/// Rows written per INSERT statement when persisting the index
+const INSERT_CHUNK_SIZE: usize = 200;
+
+const DEFAULT_MAX_SEARCH_RESULTS: usize = 200;
+const DEFAULT_MAX_GLOB_RESULTS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedEntry {
+    pub is_directory: bool,
+    pub size: u64,
+    /// Seconds since the Unix epoch
+    pub modified_time: u64,
+}
+
+/// Summary returned after (re)building an index
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileIndexStats {
+    pub root: String,
+    pub files: usize,
+    pub directories: usize,
+    pub build_time_ms: u64,
+}
+
+/// Entries added/updated and paths removed by a batch of watcher changes
+#[derive(Debug, Default, PartialEq)]
+struct IndexDelta {
+    upserts: Vec<(String, IndexedEntry)>,
+    removed: Vec<String>,
+}
+
+/// Index for a single workspace root, keyed by `/`-separated relative path
+#[derive(Debug, Default)]
+struct RootIndex {
+    entries: BTreeMap<String, IndexedEntry>,
+}
+
+impl RootIndex {
+    fn stats(&self, root: &Path, build_time_ms: u64) -> FileIndexStats {
+        let directories = self.entries.values().filter(|e| e.is_directory).count();
+        FileIndexStats {
+            root: root.to_string_lossy().to_string(),
+            files: self.entries.len() - directories,
+            directories,
+            build_time_ms,
+        }
+    }
+
+    /// Remove `relative` and everything below it
+    fn remove_tree(&mut self, relative: &str, delta: &mut IndexDelta) {
+        if self.entries.remove(relative).is_some() {
+            delta.removed.push(relative.to_string());
+        }
+        let prefix = format!("{}/", relative);
+        let children: Vec<String> = self
+            .entries
+            .range(prefix.clone()..)
+            .take_while(|(path, _)| path.starts_with(&prefix))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for child in children {
+            self.entries.remove(&child);
+            delta.removed.push(child);
+        }
+    }
+
+    /// Add or refresh `path`; directories that appear (e.g. moved in) are walked
+    fn upsert_tree(&mut self, root: &Path, path: &Path, delta: &mut IndexDelta) {
+        let Some(relative) = relative_key(root, path) else {
+            return;
+        };
+        let Ok(metadata) = std::fs::symlink_metadata(path) else {
+            return;
+        };
+
+        let entry = entry_from_metadata(&metadata);
+        let is_new_directory = entry.is_directory && !self.entries.contains_key(&relative);
+        self.entries.insert(relative.clone(), entry.clone());
+        delta.upserts.push((relative, entry));
+
+        if is_new_directory {
+            for (child, child_entry) in walk_entries(root, Some(path)) {
+                self.entries.insert(child.clone(), child_entry.clone());
+                delta.upserts.push((child, child_entry));
+            }
+        }
+    }
+
+    fn apply_changes(&mut self, root: &Path, changes: &[FileSystemChange]) -> IndexDelta {
+        let mut delta = IndexDelta::default();
+        for change in changes {
+            match change {
+                FileSystemChange::Created { path } | FileSystemChange::Modified { path } => {
+                    self.upsert_tree(root, path, &mut delta);
+                }
+                FileSystemChange::Deleted { path } => {
+                    if let Some(relative) = relative_key(root, path) {
+                        self.remove_tree(&relative, &mut delta);
+                    }
+                }
+                FileSystemChange::Renamed { old_path, new_path } => {
+                    if let Some(relative) = relative_key(root, old_path) {
+                        self.remove_tree(&relative, &mut delta);
+                    }
+                    self.upsert_tree(root, new_path, &mut delta);
+                }
+            }
+        }
+        delta
+    }
+}
+
+static INDEXES: OnceLock<RwLock<HashMap<PathBuf, RootIndex>>> = OnceLock::new();
+
+fn indexes() -> &'static RwLock<HashMap<PathBuf, RootIndex>> {
+    INDEXES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+fn normalize_root(root: &str) -> PathBuf {
+    let trimmed = root.trim_end_matches(['/', '\\']);
+    PathBuf::from(if trimmed.is_empty() { root } else { trimmed })
+}
+
+fn relative_key(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    if relative.as_os_str().is_empty() {
+        return None;
+    }
+    // Paths inside excluded directories never enter the index
+    let excluded = relative
+        .components()
+        .any(|c| c.as_os_str().to_str().is_some_and(should_exclude_dir));
+    if excluded {
+        return None;
+    }
+    Some(relative.to_string_lossy().replace('\\', "/"))
+}
+
+fn entry_from_metadata(metadata: &std::fs::Metadata) -> IndexedEntry {
+    let modified_time = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    IndexedEntry {
+        is_directory: metadata.is_dir(),
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+        modified_time,
+    }
+}
+
+/// Walk `start` (defaults to `root`) and return entries keyed relative to `root`
+fn walk_entries(root: &Path, start: Option<&Path>) -> Vec<(String, IndexedEntry)> {
+    let start = start.unwrap_or(root);
+    let config = WalkerConfig::for_file_search();
+    let walker = WorkspaceWalker::new(&start.to_string_lossy(), config).build_parallel();
+
+    let (tx, rx) = channel();
+    walker.run(|| {
+        let tx = tx.clone();
+        Box::new(move |result| {
+            let Ok(entry) = result else {
+                return WalkState::Continue;
+            };
+            if entry.depth() == 0 {
+                return WalkState::Continue;
+            }
+            let Some(relative) = relative_key(root, entry.path()) else {
+                return WalkState::Continue;
+            };
+            if let Ok(metadata) = entry.metadata() {
+                let _ = tx.send((relative, entry_from_metadata(&metadata)));
+            }
+            WalkState::Continue
+        })
+    });
+    drop(tx);
+
+    rx.into_iter().collect()
+}
+
+async fn ensure_schema(db: &Database) -> Result<(), String> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS file_index (root TEXT NOT NULL, path TEXT NOT NULL, is_directory INTEGER NOT NULL, size INTEGER NOT NULL, modified_time INTEGER NOT NULL, PRIMARY KEY (root, path))",
+        vec![],
+    )
+    .await?;
+    Ok(())
+}
+
+async fn load_from_db(db: &Database, root: &Path) -> Result<RootIndex, String> {
+    ensure_schema(db).await?;
+    let result = db
+        .query(
+            "SELECT path, is_directory, size, modified_time FROM file_index WHERE root = $1",
+            vec![serde_json::Value::String(
+                root.to_string_lossy().to_string(),
+            )],
+        )
+        .await?;
+
+    let entries = result
+        .rows
+        .iter()
+        .filter_map(|row| {
+            let path = row.get("path")?.as_str()?.to_string();
+            let entry = IndexedEntry {
+                is_directory: row.get("is_directory")?.as_i64()? != 0,
+                size: row.get("size")?.as_u64().unwrap_or(0),
+                modified_time: row.get("modified_time")?.as_u64().unwrap_or(0),
+            };
+            Some((path, entry))
+        })
+        .collect();
+    Ok(RootIndex { entries })
+}
+
+async fn upsert_rows(
+    db: &Database,
+    root: &Path,
+    rows: &[(String, IndexedEntry)],
+) -> Result<(), String> {
+    let root = serde_json::Value::String(root.to_string_lossy().to_string());
+    for chunk in rows.chunks(INSERT_CHUNK_SIZE) {
+        let placeholders: Vec<String> = (0..chunk.len())
+            .map(|i| {
+                let base = i * 5;
+                format!(
+                    "(${}, ${}, ${}, ${}, ${})",
+                    base + 1,
+                    base + 2,
+                    base + 3,
+                    base + 4,
+                    base + 5
+                )
+            })
+            .collect();
+        let mut params = Vec::with_capacity(chunk.len() * 5);
+        for (path, entry) in chunk {
+            params.push(root.clone());
+            params.push(serde_json::Value::String(path.clone()));
+            params.push(serde_json::Value::Bool(entry.is_directory));
+            params.push(serde_json::Value::Number(entry.size.into()));
+            params.push(serde_json::Value::Number(entry.modified_time.into()));
+        }
+        db.execute(
+            &format!(
+                "INSERT OR REPLACE INTO file_index (root, path, is_directory, size, modified_time) VALUES {}",
+                placeholders.join(", ")
+            ),
+            params,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn persist_full(
+    db: &Database,
+    root: &Path,
+    rows: &[(String, IndexedEntry)],
+) -> Result<(), String> {
+    ensure_schema(db).await?;
+    db.execute("BEGIN", vec![]).await?;
+    let result = async {
+        db.execute(
+            "DELETE FROM file_index WHERE root = $1",
+            vec![serde_json::Value::String(
+                root.to_string_lossy().to_string(),
+            )],
+        )
+        .await?;
+        upsert_rows(db, root, rows).await
+    }
+    .await;
+
+    match result {
+        Ok(()) => db.execute("COMMIT", vec![]).await.map(|_| ()),
+        Err(e) => {
+            let _ = db.execute("ROLLBACK", vec![]).await;
+            Err(e)
+        }
+    }
+}
+
+async fn persist_delta(db: &Database, root: &Path, delta: &IndexDelta) -> Result<(), String> {
+    ensure_schema(db).await?;
+    for path in &delta.removed {
+        db.execute(
+            "DELETE FROM file_index WHERE root = $1 AND path = $2",
+            vec![
+                serde_json::Value::String(root.to_string_lossy().to_string()),
+                serde_json::Value::String(path.clone()),
+            ],
+        )
+        .await?;
+    }
+    upsert_rows(db, root, &delta.upserts).await
+}
+
+/// Walk `root`, replace its in-memory index and persist it
+pub async fn rebuild(db: &Database, root: &Path) -> Result<FileIndexStats, String> {
+    let start_time = std::time::Instant::now();
+    let walk_root = root.to_path_buf();
+    let rows = tauri::async_runtime::spawn_blocking(move || walk_entries(&walk_root, None))
+        .await
+        .map_err(|e| format!("Failed to walk {}: {}", root.display(), e))?;
+
+    let index = RootIndex {
+        entries: rows.iter().cloned().collect(),
+    };
+    let stats = index.stats(root, start_time.elapsed().as_millis() as u64);
+    indexes()
+        .write()
+        .map_err(|e| format!("File index lock poisoned: {}", e))?
+        .insert(root.to_path_buf(), index);
+
+    persist_full(db, root, &rows).await?;
+    log::info!(
+        "Indexed {} files and {} directories under {} in {}ms",
+        stats.files,
+        stats.directories,
+        stats.root,
+        stats.build_time_ms
+    );
+    Ok(stats)
+}
+
+/// Make sure `root` has an in-memory index. A persisted snapshot is served right
+/// away and reconciled with the disk in the background; otherwise the index is built.
+pub async fn ensure_index(db: &Arc<Database>, root: &Path) -> Result<(), String> {
+    let loaded = indexes()
+        .read()
+        .map(|map| map.contains_key(root))
+        .unwrap_or(false);
+    if loaded {
+        return Ok(());
+    }
+
+    let snapshot = load_from_db(db, root).await?;
+    if snapshot.entries.is_empty() {
+        rebuild(db, root).await?;
+        return Ok(());
+    }
+
+    log::info!(
+        "Loaded {} indexed paths for {} from database",
+        snapshot.entries.len(),
+        root.display()
+    );
+    if let Ok(mut map) = indexes().write() {
+        map.entry(root.to_path_buf()).or_insert(snapshot);
+    }
+
+    // Changes made while the app was closed are picked up by a background rebuild
+    let db = db.clone();
+    let root = root.to_path_buf();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = rebuild(&db, &root).await {
+            log::warn!(
+                "Failed to reconcile file index for {}: {}",
+                root.display(),
+                e
+            );
+        }
+    });
+    Ok(())
+}
+
+/// Apply watcher changes to an indexed root and persist them. Roots that have
+/// never been indexed are ignored.
+pub fn apply_changes(app_handle: &AppHandle, root: &Path, changes: &[FileSystemChange]) {
+    if changes.is_empty() {
+        return;
+    }
+
+    let delta = {
+        let Ok(mut map) = indexes().write() else {
+            return;
+        };
+        let Some(index) = map.get_mut(root) else {
+            return;
+        };
+        index.apply_changes(root, changes)
+    };
+    if delta == IndexDelta::default() {
+        return;
+    }
+
+    if let Some(db) = app_handle.try_state::<Arc<Database>>() {
+        let db = db.inner().clone();
+        let root = root.to_path_buf();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = persist_delta(&db, &root, &delta).await {
+                log::warn!("Failed to persist file index changes: {}", e);
+            }
+        });
+    }
+}
+
+/// Rebuild an indexed root after changes were too numerous to track individually
+pub fn invalidate(app_handle: &AppHandle, root: &Path) {
+    let loaded = indexes()
+        .read()
+        .map(|map| map.contains_key(root))
+        .unwrap_or(false);
+    if !loaded {
+        return;
+    }
+
+    if let Some(db) = app_handle.try_state::<Arc<Database>>() {
+        let db = db.inner().clone();
+        let root = root.to_path_buf();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = rebuild(&db, &root).await {
+                log::warn!("Failed to rebuild file index for {}: {}", root.display(), e);
+            }
+        });
+    }
+}
+
+fn search_index(root: &Path, query: &str, max_results: usize) -> Vec<FileSearchResult> {
+    let Ok(map) = indexes().read() else {
+        return Vec::new();
+    };
+    let Some(index) = map.get(root) else {
+        return Vec::new();
+    };
+
+    let files = index
+        .entries
+        .iter()
+        .filter(|(_, entry)| !entry.is_directory)
+        .map(|(path, _)| path.as_str());
+    HighPerformanceFileSearch::new()
+        .with_max_results(max_results)
+        .search_relative_paths(root, files, query)
+}
+
+fn glob_index(root: &Path, pattern: &str, max_results: usize) -> Vec<GlobResult> {
+    let Ok(map) = indexes().read() else {
+        return Vec::new();
+    };
+    let Some(index) = map.get(root) else {
+        return Vec::new();
+    };
+
+    let glob = HighPerformanceGlob::new();
+    let pattern = pattern.replace('\\', "/");
+    let mut matches: Vec<(&String, &IndexedEntry)> = index
+        .entries
+        .iter()
+        .filter(|(path, _)| glob.glob_match(path, &pattern))
+        .collect();
+
+    // Most recently modified first, matching `search_files_by_glob`
+    matches.sort_unstable_by(|a, b| b.1.modified_time.cmp(&a.1.modified_time));
+    matches.truncate(max_results);
+
+    matches
+        .into_iter()
+        .map(|(relative, entry)| {
+            let path = root.join(relative);
+            let path_str = path.to_string_lossy().to_string();
+            GlobResult {
+                canonical_path: path
+                    .canonicalize()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_else(|_| path_str.clone()),
+                path: path_str,
+                is_directory: entry.is_directory,
+                modified_time: entry.modified_time,
+            }
+        })
+        .collect()
+}
+
+/// Build (or rebuild) the persistent index for a workspace root
+#[tauri::command]
+pub async fn file_index_build(
+    root_path: String,
+    database: tauri::State<'_, Arc<Database>>,
+) -> Result<FileIndexStats, String> {
+    rebuild(&database, &normalize_root(&root_path)).await
+}
+
+/// Fuzzy filename search served from the index
+#[tauri::command]
+pub async fn file_index_search(
+    root_path: String,
+    query: String,
+    max_results: Option<usize>,
+    database: tauri::State<'_, Arc<Database>>,
+) -> Result<Vec<FileSearchResult>, String> {
+    let root = normalize_root(&root_path);
+    ensure_index(&database, &root).await?;
+    Ok(search_index(
+        &root,
+        &query,
+        max_results.unwrap_or(DEFAULT_MAX_SEARCH_RESULTS),
+    ))
+}
+
+/// Glob query served from the index
+#[tauri::command]
+pub async fn file_index_glob(
+    root_path: String,
+    pattern: String,
+    max_results: Option<usize>,
+    database: tauri::State<'_, Arc<Database>>,
+) -> Result<Vec<GlobResult>, String> {
+    if pattern.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let root = normalize_root(&root_path);
+    ensure_index(&database, &root).await?;
+    Ok(glob_index(
+        &root,
+        &pattern,
+        max_results.unwrap_or(DEFAULT_MAX_GLOB_RESULTS),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn create_test_directory() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/components")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("node_modules/pkg")).unwrap();
+        fs::write(temp_dir.path().join("src/main.ts"), "main").unwrap();
+        fs::write(temp_dir.path().join("src/components/Button.tsx"), "button").unwrap();
+        fs::write(temp_dir.path().join("node_modules/pkg/index.js"), "").unwrap();
+        temp_dir
+    }
+
+    fn build_index(root: &Path) -> RootIndex {
+        RootIndex {
+            entries: walk_entries(root, None).into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_walk_entries_excludes_default_dirs() {
+        let dir = create_test_directory();
+        let index = build_index(dir.path());
+
+        assert!(index.entries.contains_key("src/main.ts"));
+        assert!(index.entries.contains_key("src/components/Button.tsx"));
+        assert!(index.entries["src/components"].is_directory);
+        assert!(index.entries.keys().all(|p| !p.contains("node_modules")));
+        assert_eq!(index.entries["src/main.ts"].size, 4);
+    }
+
+    #[test]
+    fn test_apply_changes_create_delete_rename() {
+        let dir = create_test_directory();
+        let root = dir.path();
+        let mut index = build_index(root);
+
+        // New directory moved in: its children are indexed as well
+        fs::create_dir_all(root.join("lib/utils")).unwrap();
+        fs::write(root.join("lib/utils/format.ts"), "").unwrap();
+        let delta = index.apply_changes(
+            root,
+            &[FileSystemChange::Created {
+                path: root.join("lib"),
+            }],
+        );
+        assert!(index.entries.contains_key("lib/utils/format.ts"));
+        assert!(delta
+            .upserts
+            .iter()
+            .any(|(p, _)| p == "lib/utils/format.ts"));
+
+        // Renaming a directory moves the whole subtree
+        fs::rename(root.join("src/components"), root.join("src/ui")).unwrap();
+        let delta = index.apply_changes(
+            root,
+            &[FileSystemChange::Renamed {
+                old_path: root.join("src/components"),
+                new_path: root.join("src/ui"),
+            }],
+        );
+        assert!(!index.entries.contains_key("src/components/Button.tsx"));
+        assert!(index.entries.contains_key("src/ui/Button.tsx"));
+        assert!(delta
+            .removed
+            .contains(&"src/components/Button.tsx".to_string()));
+
+        fs::remove_file(root.join("src/main.ts")).unwrap();
+        index.apply_changes(
+            root,
+            &[FileSystemChange::Deleted {
+                path: root.join("src/main.ts"),
+            }],
+        );
+        assert!(!index.entries.contains_key("src/main.ts"));
+    }
+
+    #[test]
+    fn test_search_and_glob_from_index() {
+        let dir = create_test_directory();
+        let root = dir.path().to_path_buf();
+        indexes()
+            .write()
+            .unwrap()
+            .insert(root.clone(), build_index(&root));
+
+        let results = search_index(&root, "button", 10);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("Button.tsx"));
+
+        let results = glob_index(&root, "src/**/*.tsx", 10);
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].is_directory);
+
+        indexes().write().unwrap().remove(&root);
+    }
+
+    #[tokio::test]
+    async fn test_persist_and_reload() {
+        let dir = create_test_directory();
+        let db_dir = TempDir::new().unwrap();
+        let db = Database::new(db_dir.path().join("test.db").to_string_lossy().to_string());
+        db.connect().await.unwrap();
+
+        let root = dir.path();
+        let rows = walk_entries(root, None);
+        persist_full(&db, root, &rows).await.unwrap();
+
+        let loaded = load_from_db(&db, root).await.unwrap();
+        assert_eq!(loaded.entries.len(), rows.len());
+        assert_eq!(
+            loaded.entries["src/main.ts"],
+            rows.iter().find(|(p, _)| p == "src/main.ts").unwrap().1
+        );
+
+        let delta = IndexDelta {
+            upserts: Vec::new(),
+            removed: vec!["src/main.ts".to_string()],
+        };
+        persist_delta(&db, root, &delta).await.unwrap();
+        let loaded = load_from_db(&db, root).await.unwrap();
+        assert!(!loaded.entries.contains_key("src/main.ts"));
+    }
+
+    #[test]
+    fn test_normalize_root() {
+        assert_eq!(normalize_root("/repo/"), PathBuf::from("/repo"));
+        assert_eq!(normalize_root("/"), PathBuf::from("/"));
+    }
+}
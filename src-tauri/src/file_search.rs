@@ -89,9 +89,43 @@ impl HighPerformanceFileSearch {
         }
 
         let mut final_results = results;
+        Self::sort_results(&mut final_results);
+        final_results.truncate(self.max_results);
+        Ok(final_results)
+    }
+
+    /// Rank already-known relative paths (e.g. from the persistent file index)
+    /// without walking the tree. Unlike `search_files`, every path is scored
+    /// before truncating, so the best matches are never cut off early.
+    pub fn search_relative_paths<'a>(
+        &self,
+        root_path: &Path,
+        relative_paths: impl Iterator<Item = &'a str>,
+        query: &str,
+    ) -> Vec<FileSearchResult> {
+        let keywords = Self::parse_query(query);
+        if keywords.is_empty() {
+            return vec![];
+        }
+
+        let mut results: Vec<FileSearchResult> = relative_paths
+            .filter_map(|relative_path| {
+                let full_path = root_path.join(relative_path);
+                if !self.is_code_file(&full_path) {
+                    return None;
+                }
+                self.match_path(relative_path, &full_path, &keywords)
+            })
+            .collect();
+
+        Self::sort_results(&mut results);
+        results.truncate(self.max_results);
+        results
+    }
 
-        // Sort by score (descending) and then by name length (ascending)
-        final_results.par_sort_unstable_by(|a, b| {
+    /// Sort by score (descending) and then by name length (ascending)
+    fn sort_results(results: &mut [FileSearchResult]) {
+        results.par_sort_unstable_by(|a, b| {
             let score_cmp = b
                 .score
                 .partial_cmp(&a.score)
@@ -102,9 +136,6 @@ impl HighPerformanceFileSearch {
                 a.name.len().cmp(&b.name.len())
             }
         });
-
-        final_results.truncate(self.max_results);
-        Ok(final_results)
     }
 
     /// Parse search query into keywords, splitting on spaces and non-alphanumeric chars
@@ -1,20 +1,608 @@
 use crate::constants::{BINARY_EXTENSIONS, EXCLUDED_DIRS};
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
-use std::path::Path;
+use crate::database::Database;
+use crate::glob::HighPerformanceGlob;
+use crate::lsp::FileChangeType;
+use notify::event::{MetadataKind, ModifyKind, RenameMode};
+use notify::{
+    Config, EventHandler, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    mpsc, Arc,
+    mpsc, Arc, Mutex, OnceLock, RwLock,
 };
 use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Settings key under which the watcher configuration is persisted
+const FILE_WATCHER_CONFIG_KEY: &str = "file_watcher_config";
+
+/// Bounds for the configurable debounce duration (milliseconds)
+const MIN_DEBOUNCE_MS: u64 = 50;
+const MAX_DEBOUNCE_MS: u64 = 10_000;
+
+/// Bounds for the polling backend interval (milliseconds)
+const MIN_POLL_INTERVAL_MS: u64 = 250;
+const MAX_POLL_INTERVAL_MS: u64 = 60_000;
+
+/// Filesystem types on which native change notifications are unreliable
+const NETWORK_FS_TYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb",
+    "smb2",
+    "smb3",
+    "smbfs",
+    "afpfs",
+    "webdav",
+    "9p",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "davfs",
+];
+
+/// How file changes are detected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatcherBackend {
+    /// Native notifications, or polling when the root is on a network filesystem
+    #[default]
+    Auto,
+    Native,
+    Poll,
+}
+
+/// Runtime configuration shared by all file watchers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FileWatcherConfig {
+    /// Trailing-edge debounce before change events are emitted
+    pub debounce_ms: u64,
+    /// Directory names excluded in addition to EXCLUDED_DIRS
+    pub excluded_dirs: Vec<String>,
+    /// Globs (relative to the watched root) that are watched even when the
+    /// default rules would exclude them, e.g. `build/generated/**`
+    pub include_globs: Vec<String>,
+    pub backend: WatcherBackend,
+    /// Scan interval used by the polling backend
+    pub poll_interval_ms: u64,
+}
+
+impl Default for FileWatcherConfig {
+    fn default() -> Self {
+        Self {
+            debounce_ms: 500,
+            excluded_dirs: Vec::new(),
+            include_globs: Vec::new(),
+            backend: WatcherBackend::Auto,
+            poll_interval_ms: 2000,
+        }
+    }
+}
+
+impl FileWatcherConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_DEBOUNCE_MS..=MAX_DEBOUNCE_MS).contains(&self.debounce_ms) {
+            return Err(format!(
+                "debounceMs must be between {} and {}",
+                MIN_DEBOUNCE_MS, MAX_DEBOUNCE_MS
+            ));
+        }
+        if self
+            .excluded_dirs
+            .iter()
+            .any(|dir| dir.trim().is_empty() || dir.contains('/') || dir.contains('\\'))
+        {
+            return Err("excludedDirs must contain plain directory names".to_string());
+        }
+        if !(MIN_POLL_INTERVAL_MS..=MAX_POLL_INTERVAL_MS).contains(&self.poll_interval_ms) {
+            return Err(format!(
+                "pollIntervalMs must be between {} and {}",
+                MIN_POLL_INTERVAL_MS, MAX_POLL_INTERVAL_MS
+            ));
+        }
+        if self.include_globs.iter().any(|glob| glob.trim().is_empty()) {
+            return Err("includeGlobs must not contain empty patterns".to_string());
+        }
+        Ok(())
+    }
+
+    fn debounce_duration(&self) -> Duration {
+        Duration::from_millis(self.debounce_ms)
+    }
+}
+
+/// A single change reported by the `file-system-changes` event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum FileSystemChange {
+    Created {
+        path: PathBuf,
+    },
+    Modified {
+        path: PathBuf,
+    },
+    Deleted {
+        path: PathBuf,
+    },
+    #[serde(rename_all = "camelCase")]
+    Renamed {
+        old_path: PathBuf,
+        new_path: PathBuf,
+    },
+}
+
+impl FileSystemChange {
+    fn from_change_type(path: PathBuf, change: FileChangeType) -> Self {
+        match change {
+            FileChangeType::Created => Self::Created { path },
+            FileChangeType::Changed => Self::Modified { path },
+            FileChangeType::Deleted => Self::Deleted { path },
+        }
+    }
+
+    /// Path this change is keyed by while coalescing
+    fn key(&self) -> &Path {
+        match self {
+            Self::Created { path } | Self::Modified { path } | Self::Deleted { path } => path,
+            Self::Renamed { new_path, .. } => new_path,
+        }
+    }
+
+    /// LSP view of the change; renames become a delete plus a create
+    fn lsp_changes(&self) -> Vec<(PathBuf, FileChangeType)> {
+        match self {
+            Self::Created { path } => vec![(path.clone(), FileChangeType::Created)],
+            Self::Modified { path } => vec![(path.clone(), FileChangeType::Changed)],
+            Self::Deleted { path } => vec![(path.clone(), FileChangeType::Deleted)],
+            Self::Renamed { old_path, new_path } => vec![
+                (old_path.clone(), FileChangeType::Deleted),
+                (new_path.clone(), FileChangeType::Created),
+            ],
+        }
+    }
+}
+
+/// Ordered, per-path coalesced changes collected during one debounce window
+#[derive(Debug, Default)]
+struct PendingChanges {
+    /// Changes in arrival order; removed changes leave an empty slot
+    changes: Vec<Option<FileSystemChange>>,
+    /// Slot in `changes` of the live change for each key path
+    index: HashMap<PathBuf, usize>,
+}
+
+impl PendingChanges {
+    fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    fn push(&mut self, change: FileSystemChange) {
+        self.index
+            .insert(change.key().to_path_buf(), self.changes.len());
+        self.changes.push(Some(change));
+    }
+
+    fn remove(&mut self, path: &Path) -> Option<FileSystemChange> {
+        let slot = self.index.remove(path)?;
+        self.changes[slot].take()
+    }
+
+    fn record(&mut self, change: FileSystemChange) {
+        if let FileSystemChange::Renamed { old_path, new_path } = &change {
+            // A file created in this window and then renamed is still just a new file
+            let created_here = matches!(
+                self.remove(old_path),
+                Some(FileSystemChange::Created { .. })
+            );
+            // Backends that report both halves of a rename separately as well
+            // as the paired event would otherwise produce duplicates
+            self.remove(new_path);
+            if created_here {
+                self.push(FileSystemChange::Created {
+                    path: new_path.clone(),
+                });
+            } else {
+                self.push(change);
+            }
+            return;
+        }
+
+        let Some(&slot) = self.index.get(change.key()) else {
+            self.push(change);
+            return;
+        };
+
+        let merged = match (&self.changes[slot], &change) {
+            (Some(FileSystemChange::Created { .. }), FileSystemChange::Modified { .. }) => None,
+            (Some(FileSystemChange::Created { .. }), FileSystemChange::Deleted { .. }) => {
+                self.remove(change.key());
+                return;
+            }
+            (Some(FileSystemChange::Deleted { path }), FileSystemChange::Created { .. }) => {
+                Some(FileSystemChange::Modified { path: path.clone() })
+            }
+            (
+                Some(FileSystemChange::Renamed { old_path, .. }),
+                FileSystemChange::Deleted { .. },
+            ) => {
+                // Only the deletion of the original path remains, which re-keys
+                // the slot; a file since recreated there is merged back in after
+                let old_path = old_path.clone();
+                self.index.remove(change.key());
+                let recreated = self.remove(&old_path);
+                self.index.insert(old_path.clone(), slot);
+                self.changes[slot] = Some(FileSystemChange::Deleted { path: old_path });
+                if let Some(recreated) = recreated {
+                    self.record(recreated);
+                }
+                return;
+            }
+            (Some(FileSystemChange::Renamed { .. }), FileSystemChange::Modified { .. }) => None,
+            _ => Some(change),
+        };
+
+        if let Some(merged) = merged {
+            self.changes[slot] = Some(merged);
+        }
+    }
+
+    fn take(&mut self) -> Vec<FileSystemChange> {
+        self.index.clear();
+        std::mem::take(&mut self.changes)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+/// Kind of change detected inside the `.git` directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GitChangeKind {
+    /// Staging area (git add/reset)
+    Index,
+    /// Current branch or detached commit (git checkout)
+    Head,
+    /// Local branch tips (git commit)
+    LocalRef,
+    /// Remote-tracking branches (git fetch/pull)
+    RemoteRef,
+    /// MERGE_HEAD, REBASE_HEAD, CHERRY_PICK_HEAD or ORIG_HEAD
+    OperationState,
+}
+
+/// Payload of the `git-status-changed` event, accumulated over one debounce window
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitStatusChange {
+    pub kinds: BTreeSet<GitChangeKind>,
+    /// Affected refs relative to the git directory, e.g. `HEAD` or `refs/heads/main`
+    pub refs: BTreeSet<String>,
+    /// Changed files inside the git directory
+    pub paths: BTreeSet<String>,
+}
+
+impl GitStatusChange {
+    /// Record a changed path; returns false if it does not affect git status
+    fn record(&mut self, path: &Path) -> bool {
+        let Some(kind) = FileWatcher::git_change_kind(path) else {
+            return false;
+        };
+        self.kinds.insert(kind);
+        if let Some(ref_name) = FileWatcher::git_ref_name(path, kind) {
+            self.refs.insert(ref_name);
+        }
+        self.paths.insert(path.to_string_lossy().to_string());
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+
+    /// Whether the diff against HEAD/index may have changed (not just remote refs)
+    fn affects_worktree_diff(&self) -> bool {
+        self.kinds
+            .iter()
+            .any(|kind| *kind != GitChangeKind::RemoteRef)
+    }
+}
+
+/// Changes buffered while paused before falling back to a full resync
+const MAX_PAUSED_CHANGES: usize = 1000;
+
+/// A pause that is never resumed would silently disable watching, so pauses expire
+const MAX_PAUSE_DURATION: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Default)]
+struct PauseState {
+    depth: usize,
+    since: Option<Instant>,
+}
+
+static PAUSE_STATE: Mutex<PauseState> = Mutex::new(PauseState {
+    depth: 0,
+    since: None,
+});
+
+/// Pause change emission for all watchers. Pauses nest; each call needs a matching `resume`.
+pub fn pause() {
+    if let Ok(mut state) = PAUSE_STATE.lock() {
+        state.depth += 1;
+        state.since.get_or_insert_with(Instant::now);
+        log::debug!("File watching paused (depth {})", state.depth);
+    }
+}
+
+/// Undo one `pause`. Returns true when watching is active again.
+pub fn resume() -> bool {
+    match PAUSE_STATE.lock() {
+        Ok(mut state) => {
+            state.depth = state.depth.saturating_sub(1);
+            if state.depth == 0 {
+                state.since = None;
+                log::debug!("File watching resumed");
+            }
+            state.depth == 0
+        }
+        Err(_) => true,
+    }
+}
+
+pub fn is_paused() -> bool {
+    let Ok(mut state) = PAUSE_STATE.lock() else {
+        return false;
+    };
+    match state.since {
+        Some(since) if since.elapsed() >= MAX_PAUSE_DURATION => {
+            log::warn!(
+                "File watching was paused for over {:?}, resuming automatically",
+                MAX_PAUSE_DURATION
+            );
+            *state = PauseState::default();
+            false
+        }
+        Some(_) => state.depth > 0,
+        None => false,
+    }
+}
+
+/// Keeps file watching paused for the lifetime of a bulk operation
+pub struct PauseGuard(());
+
+impl PauseGuard {
+    pub fn acquire() -> Self {
+        pause();
+        Self(())
+    }
+}
+
+impl Drop for PauseGuard {
+    fn drop(&mut self) {
+        resume();
+    }
+}
+
+static WATCHER_CONFIG: OnceLock<RwLock<FileWatcherConfig>> = OnceLock::new();
+static WATCHER_CONFIG_LOADED: AtomicBool = AtomicBool::new(false);
+
+fn watcher_config() -> &'static RwLock<FileWatcherConfig> {
+    WATCHER_CONFIG.get_or_init(|| RwLock::new(FileWatcherConfig::default()))
+}
+
+/// Get the active watcher configuration
+pub fn current_config() -> FileWatcherConfig {
+    watcher_config()
+        .read()
+        .map(|config| config.clone())
+        .unwrap_or_default()
+}
+
+/// Replace the active watcher configuration. Running watchers pick it up immediately.
+fn set_config(config: FileWatcherConfig) {
+    if let Ok(mut current) = watcher_config().write() {
+        *current = config;
+    }
+}
+
+/// Load the persisted configuration once the settings database is available
+pub async fn ensure_config_loaded(db: &Database) {
+    if WATCHER_CONFIG_LOADED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let result = db
+        .query(
+            "SELECT value FROM settings WHERE key = $1",
+            vec![serde_json::Value::String(
+                FILE_WATCHER_CONFIG_KEY.to_string(),
+            )],
+        )
+        .await;
+
+    match result {
+        Ok(result) => {
+            let stored = result
+                .rows
+                .first()
+                .and_then(|row| row.get("value"))
+                .and_then(|v| v.as_str())
+                .map(serde_json::from_str::<FileWatcherConfig>);
+            match stored {
+                Some(Ok(config)) if config.validate().is_ok() => set_config(config),
+                Some(_) => log::warn!("Ignoring invalid persisted file watcher config"),
+                None => {}
+            }
+            WATCHER_CONFIG_LOADED.store(true, Ordering::Relaxed);
+        }
+        Err(e) => log::debug!("File watcher config not loaded yet: {}", e),
+    }
+}
+
+/// Load the persisted configuration without blocking the caller. Running
+/// watchers read the config on every event, so a late load still applies.
+pub fn load_config_in_background(app_handle: &AppHandle) {
+    if WATCHER_CONFIG_LOADED.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Some(db) = app_handle.try_state::<Arc<Database>>() {
+        let db = db.inner().clone();
+        tauri::async_runtime::spawn(async move {
+            ensure_config_loaded(&db).await;
+        });
+    }
+}
+
+/// Validate, persist, and apply a new watcher configuration
+pub async fn configure(db: &Database, config: FileWatcherConfig) -> Result<(), String> {
+    config.validate()?;
+
+    let value = serde_json::to_string(&config)
+        .map_err(|e| format!("Failed to serialize file watcher config: {}", e))?;
+    db.execute(
+        "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES ($1, $2, $3)",
+        vec![
+            serde_json::Value::String(FILE_WATCHER_CONFIG_KEY.to_string()),
+            serde_json::Value::String(value),
+            serde_json::Value::Number(chrono::Utc::now().timestamp_millis().into()),
+        ],
+    )
+    .await
+    .map_err(|e| format!("Failed to persist file watcher config: {}", e))?;
+
+    log::info!("File watcher config updated: {:?}", config);
+    set_config(config);
+    WATCHER_CONFIG_LOADED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Parse `/proc/mounts` into (mount point, filesystem type) pairs
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_mounts(content: &str) -> Vec<(PathBuf, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            // Spaces in mount points are octal-escaped
+            let mount_point = fields.next()?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            Some((PathBuf::from(mount_point), fs_type.to_string()))
+        })
+        .collect()
+}
+
+/// Parse BSD/macOS `mount` output lines such as
+/// `//user@server/share on /Volumes/share (smbfs, nodev, nosuid, mounted by user)`
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_bsd_mount_output(content: &str) -> Vec<(PathBuf, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (mount_point, options) = rest.rsplit_once(" (")?;
+            let fs_type = options.split([',', ')']).next()?.trim();
+            Some((PathBuf::from(mount_point), fs_type.to_string()))
+        })
+        .collect()
+}
+
+/// Filesystem type of the most specific mount containing `path`
+#[cfg_attr(windows, allow(dead_code))]
+fn mount_fs_type<'a>(mounts: &'a [(PathBuf, String)], path: &Path) -> Option<&'a str> {
+    mounts
+        .iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .map(|(_, fs_type)| fs_type.as_str())
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+fn is_network_fs_type(fs_type: &str) -> bool {
+    let fs_type = fs_type.to_ascii_lowercase();
+    NETWORK_FS_TYPES.contains(&fs_type.as_str())
+}
+
+/// Best-effort detection of roots on NFS/SMB and similar mounts
+fn is_network_filesystem(path: &Path) -> bool {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string("/proc/mounts")
+            .map(|content| {
+                mount_fs_type(&parse_proc_mounts(&content), &path).is_some_and(is_network_fs_type)
+            })
+            .unwrap_or(false)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("/sbin/mount")
+            .output()
+            .map(|output| {
+                let content = String::from_utf8_lossy(&output.stdout);
+                mount_fs_type(&parse_bsd_mount_output(&content), &path)
+                    .is_some_and(is_network_fs_type)
+            })
+            .unwrap_or(false)
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Storage::FileSystem::GetDriveTypeW;
+
+        const DRIVE_REMOTE: u32 = 4;
+
+        let path_str = path.to_string_lossy();
+        // canonicalize() yields `\\?\UNC\server\share` for network shares
+        if path_str.starts_with("\\\\?\\UNC\\")
+            || (path_str.starts_with("\\\\") && !path_str.starts_with("\\\\?\\"))
+        {
+            return true;
+        }
+        use std::path::{Component, Prefix};
+
+        let drive = match path.components().next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => letter,
+                _ => return false,
+            },
+            _ => return false,
+        };
+        let root: Vec<u16> = std::ffi::OsStr::new(&format!("{}:\\", drive as char))
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        // SAFETY: `root` is a valid, NUL-terminated UTF-16 string
+        unsafe { GetDriveTypeW(root.as_ptr()) == DRIVE_REMOTE }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    {
+        false
+    }
+}
 
 pub struct FileWatcher {
-    _watcher: RecommendedWatcher,
+    _watcher: Box<dyn Watcher + Send>,
     _thread_handle: Option<JoinHandle<()>>,
     _stop_flag: Arc<AtomicBool>,
     // Git watcher (separate from main file watcher)
-    _git_watcher: Option<RecommendedWatcher>,
+    _git_watcher: Option<Box<dyn Watcher + Send>>,
     _git_thread_handle: Option<JoinHandle<()>>,
     _git_stop_flag: Arc<AtomicBool>,
 }
@@ -33,7 +621,7 @@ impl FileWatcher {
         )?;
 
         Ok(Self {
-            _watcher: watcher,
+            _watcher: Box::new(watcher),
             _thread_handle: None,
             _stop_flag: Arc::new(AtomicBool::new(false)),
             _git_watcher: None,
@@ -58,19 +646,17 @@ impl FileWatcher {
 
         let (sender, receiver) = mpsc::channel();
 
-        // Create a new watcher
-        let mut watcher = RecommendedWatcher::new(
+        // Create a new watcher and start watching
+        let watcher = Self::create_watcher(
             move |result| {
                 if let Err(e) = sender.send(result) {
                     log::error!("Failed to send file watcher event: {}", e);
                 }
             },
-            Config::default(),
+            path.as_ref(),
+            &current_config(),
         )?;
 
-        // Start watching
-        watcher.watch(path.as_ref(), RecursiveMode::Recursive)?;
-
         // Replace the old watcher
         self._watcher = watcher;
 
@@ -83,14 +669,18 @@ impl FileWatcher {
         let file_window_label = window_label.clone();
 
         // Spawn thread to handle events with proper trailing-edge debounce
+        let watch_root = repo_path.clone();
         let thread_handle = thread::spawn(move || {
-            let debounce_duration = Duration::from_millis(500);
             let check_interval = Duration::from_millis(100);
 
-            // Trailing-edge debounce state
-            let mut pending_emit = false;
+            // Trailing-edge debounce state; changes accumulate until the window closes
+            let mut pending_change = GitStatusChange::default();
             let mut last_event_time = Instant::now();
             let mut pending_paths: Vec<std::path::PathBuf> = Vec::new();
+            // Structured changes for `file-system-changes` and LSP servers
+            let mut pending_changes = PendingChanges::default();
+            // Set when too many changes arrived while paused to track individually
+            let mut needs_resync = false;
 
             loop {
                 // Check stop flag first
@@ -101,21 +691,39 @@ impl FileWatcher {
 
                 // Use short timeout to allow checking for pending events
                 match receiver.recv_timeout(check_interval) {
+                    Ok(Ok(_)) if needs_resync => {
+                        // A full refresh is already pending; individual events add nothing
+                        pending_emit = true;
+                        last_event_time = Instant::now();
+                    }
                     Ok(Ok(event)) => {
+                        let config = current_config();
                         // Filter events we care about
                         match event.kind {
                             notify::EventKind::Create(_)
                             | notify::EventKind::Remove(_)
                             | notify::EventKind::Modify(notify::event::ModifyKind::Name(_))
-                            | notify::EventKind::Modify(notify::event::ModifyKind::Data(_)) => {
+                            | notify::EventKind::Modify(notify::event::ModifyKind::Data(_))
+                            | notify::EventKind::Modify(notify::event::ModifyKind::Any)
+                            | notify::EventKind::Modify(notify::event::ModifyKind::Metadata(
+                                MetadataKind::WriteTime,
+                            )) => {
                                 // Check if the event is for files we care about
                                 let relevant_paths: Vec<_> = event
                                     .paths
                                     .iter()
-                                    .filter(|path| Self::should_watch_path(path))
+                                    .filter(|path| {
+                                        Self::should_watch_path_with(path, &watch_root, &config)
+                                    })
                                     .cloned()
                                     .collect();
 
+                                for change in Self::structured_changes(&event, |path| {
+                                    Self::should_watch_path_with(path, &watch_root, &config)
+                                }) {
+                                    pending_changes.record(change);
+                                }
+
                                 if !relevant_paths.is_empty() {
                                     // Mark pending and update last event time
                                     pending_emit = true;
@@ -123,6 +731,15 @@ impl FileWatcher {
                                     // Collect paths for logging/debugging
                                     pending_paths.extend(relevant_paths);
                                 }
+
+                                if is_paused() && pending_changes.len() > MAX_PAUSED_CHANGES {
+                                    log::info!(
+                                        "Too many changes while file watching is paused, falling back to a full refresh"
+                                    );
+                                    needs_resync = true;
+                                    pending_changes.take();
+                                    pending_paths.clear();
+                                }
                             }
                             _ => {}
                         }
@@ -141,25 +758,61 @@ impl FileWatcher {
 
                 // Check if we should emit the pending event (trailing-edge debounce)
                 // Emit after debounce_duration has passed since the last event
-                if pending_emit {
+                if pending_emit && !is_paused() {
                     let elapsed = Instant::now().duration_since(last_event_time);
-                    if elapsed >= debounce_duration {
+                    if elapsed >= current_config().debounce_duration() {
+                        let resync = needs_resync;
+                        if resync {
+                            // The root path makes listeners refresh the whole tree;
+                            // `file-system-changes` is sent empty in this case
+                            pending_paths = vec![watch_root.clone()];
+                            needs_resync = false;
+                        }
+
                         log::debug!(
                             "Emitting debounced file-system-changed event for {} paths to {:?}",
                             pending_paths.len(),
                             file_window_label
                         );
 
-                        // Emit to specific window if label provided, otherwise broadcast
+                        let changes = pending_changes.take();
+
+                        // Emit to specific window if label provided, otherwise broadcast.
+                        // `file-system-changed` keeps the plain path list for older listeners.
                         let result = if let Some(ref label) = file_window_label {
-                            file_app_handle.emit_to(label, "file-system-changed", &pending_paths)
+                            file_app_handle
+                                .emit_to(label, "file-system-changed", &pending_paths)
+                                .and_then(|_| {
+                                    file_app_handle.emit_to(label, "file-system-changes", &changes)
+                                })
                         } else {
-                            file_app_handle.emit("file-system-changed", &pending_paths)
+                            file_app_handle
+                                .emit("file-system-changed", &pending_paths)
+                                .and_then(|_| file_app_handle.emit("file-system-changes", &changes))
                         };
 
                         if let Err(e) = result {
                             log::error!("Failed to emit file system change event: {}", e);
                         }
+
+                        // Keep language servers in sync with external edits
+                        crate::lsp::forward_watched_file_changes(
+                            &file_app_handle,
+                            changes.iter().flat_map(|c| c.lsp_changes()).collect(),
+                        );
+
+                        if resync {
+                            crate::file_index::invalidate(&file_app_handle, &watch_root);
+                            crate::symbol_outline::invalidate_root(&watch_root);
+                        } else {
+                            crate::symbol_outline::invalidate_changes(&changes);
+                            crate::file_index::apply_changes(
+                                &file_app_handle,
+                                &watch_root,
+                                &changes,
+                            );
+                        }
+
                         pending_emit = false;
                         pending_paths.clear();
                     }
@@ -204,19 +857,17 @@ impl FileWatcher {
 
         let (sender, receiver) = mpsc::channel();
 
-        // Create a new watcher for .git directory
-        let mut watcher = RecommendedWatcher::new(
+        // Create a new watcher for .git directory, watched recursively
+        let watcher = Self::create_watcher(
             move |result| {
                 if let Err(e) = sender.send(result) {
                     log::error!("Failed to send git watcher event: {}", e);
                 }
             },
-            Config::default(),
+            &git_path,
+            &current_config(),
         )?;
 
-        // Watch the .git directory recursively
-        watcher.watch(&git_path, RecursiveMode::Recursive)?;
-
         self._git_watcher = Some(watcher);
 
         // Create new stop flag for git watcher
@@ -225,11 +876,10 @@ impl FileWatcher {
 
         // Spawn thread to handle git events with proper trailing-edge debounce
         let git_thread_handle = thread::spawn(move || {
-            let debounce_duration = Duration::from_millis(500);
             let check_interval = Duration::from_millis(100);
 
-            // Trailing-edge debounce state
-            let mut pending_emit = false;
+            // Trailing-edge debounce state; changes accumulate until the window closes
+            let mut pending_change = GitStatusChange::default();
             let mut last_event_time = Instant::now();
 
             loop {
@@ -242,16 +892,15 @@ impl FileWatcher {
                 // Use short timeout to allow checking for pending events
                 match receiver.recv_timeout(check_interval) {
                     Ok(Ok(event)) => {
-                        // Check if this is a git status-related file change
-                        let is_git_status_change = event
-                            .paths
-                            .iter()
-                            .any(|path| Self::is_git_status_file(path));
+                        // Record git status-related file changes
+                        let mut is_git_status_change = false;
+                        for path in &event.paths {
+                            is_git_status_change |= pending_change.record(path);
+                        }
 
                         if is_git_status_change {
                             log::debug!("Git status change detected: {:?}", event.paths);
-                            // Mark pending and update last event time (trailing-edge debounce)
-                            pending_emit = true;
+                            // Update last event time (trailing-edge debounce)
                             last_event_time = Instant::now();
                         }
                     }
@@ -269,25 +918,30 @@ impl FileWatcher {
 
                 // Check if we should emit the pending event (trailing-edge debounce)
                 // Emit after debounce_duration has passed since the last event
-                if pending_emit {
+                if !pending_change.is_empty() && !is_paused() {
                     let elapsed = Instant::now().duration_since(last_event_time);
-                    if elapsed >= debounce_duration {
+                    if elapsed >= current_config().debounce_duration() {
+                        let change = std::mem::take(&mut pending_change);
                         log::info!(
-                            "Emitting debounced git-status-changed event to {:?}",
-                            window_label
+                            "Emitting debounced git-status-changed event to {:?}: {:?}",
+                            window_label,
+                            change.kinds
                         );
 
+                        if change.affects_worktree_diff() {
+                            crate::git::diff::invalidate_line_changes(&git_path);
+                        }
+
                         // Emit to specific window if label provided, otherwise broadcast
                         let result = if let Some(ref label) = window_label {
-                            app_handle.emit_to(label, "git-status-changed", ())
+                            app_handle.emit_to(label, "git-status-changed", &change)
                         } else {
-                            app_handle.emit("git-status-changed", ())
+                            app_handle.emit("git-status-changed", &change)
                         };
 
                         if let Err(e) = result {
                             log::error!("Failed to emit git-status-changed event: {}", e);
                         }
-                        pending_emit = false;
                     }
                 }
             }
@@ -298,6 +952,59 @@ impl FileWatcher {
         Ok(())
     }
 
+    /// Create a watcher for `path` using the configured backend. In auto mode,
+    /// network filesystems are polled and native failures fall back to polling.
+    fn create_watcher<F: EventHandler + Clone>(
+        handler: F,
+        path: &Path,
+        config: &FileWatcherConfig,
+    ) -> notify::Result<Box<dyn Watcher + Send>> {
+        let poll_interval = Duration::from_millis(config.poll_interval_ms);
+        let start_polling = |handler: F| -> notify::Result<Box<dyn Watcher + Send>> {
+            let mut watcher =
+                PollWatcher::new(handler, Config::default().with_poll_interval(poll_interval))?;
+            watcher.watch(path, RecursiveMode::Recursive)?;
+            log::info!("Polling {:?} for changes every {:?}", path, poll_interval);
+            Ok(Box::new(watcher))
+        };
+
+        let use_polling = match config.backend {
+            WatcherBackend::Poll => true,
+            WatcherBackend::Native => false,
+            WatcherBackend::Auto => {
+                let network = is_network_filesystem(path);
+                if network {
+                    log::info!(
+                        "{:?} is on a network filesystem, using polling watcher",
+                        path
+                    );
+                }
+                network
+            }
+        };
+        if use_polling {
+            return start_polling(handler);
+        }
+
+        let native =
+            RecommendedWatcher::new(handler.clone(), Config::default()).and_then(|mut watcher| {
+                watcher.watch(path, RecursiveMode::Recursive)?;
+                Ok(watcher)
+            });
+        match native {
+            Ok(watcher) => Ok(Box::new(watcher)),
+            Err(e) if config.backend == WatcherBackend::Auto => {
+                log::warn!(
+                    "Native watcher failed for {:?} ({}), falling back to polling",
+                    path,
+                    e
+                );
+                start_polling(handler)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Stop the git watcher
     fn stop_git_watcher(&mut self) {
         // Set stop flag to signal thread to exit
@@ -330,6 +1037,36 @@ impl FileWatcher {
         }
     }
 
+    /// Check if a path should be watched under the given configuration.
+    /// Include globs take precedence over both default and configured excludes.
+    fn should_watch_path_with(path: &Path, root: &Path, config: &FileWatcherConfig) -> bool {
+        if !config.include_globs.is_empty() {
+            if let Ok(relative) = path.strip_prefix(root) {
+                let relative = relative.to_string_lossy().replace('\\', "/");
+                let glob = HighPerformanceGlob::new();
+                if config
+                    .include_globs
+                    .iter()
+                    .any(|pattern| glob.glob_match(&relative, pattern))
+                {
+                    return true;
+                }
+            }
+        }
+
+        let in_excluded_dir = path.components().any(|component| {
+            component
+                .as_os_str()
+                .to_str()
+                .is_some_and(|name| config.excluded_dirs.iter().any(|dir| dir == name))
+        });
+        if in_excluded_dir {
+            return false;
+        }
+
+        Self::should_watch_path(path)
+    }
+
     /// Check if a path should be watched (not ignored)
     fn should_watch_path(path: &Path) -> bool {
         // Check if any component of the path is in EXCLUDED_DIRS
@@ -373,13 +1110,73 @@ impl FileWatcher {
         true
     }
 
-    /// Check if a path is a git status-related file
-    fn is_git_status_file(path: &Path) -> bool {
+    /// Map a notify event to the LSP change type for one of its paths.
+    /// `index` is the position of `path` within the event (renames report `[from, to]`).
+    fn classify_change(kind: &EventKind, path: &Path, index: usize) -> Option<FileChangeType> {
+        match kind {
+            EventKind::Create(_) => Some(FileChangeType::Created),
+            EventKind::Remove(_) => Some(FileChangeType::Deleted),
+            EventKind::Modify(ModifyKind::Data(_)) => Some(FileChangeType::Changed),
+            // Some backends and the polling watcher don't say what changed
+            EventKind::Modify(ModifyKind::Any)
+            | EventKind::Modify(ModifyKind::Metadata(MetadataKind::WriteTime)) => {
+                Some(FileChangeType::Changed)
+            }
+            EventKind::Modify(ModifyKind::Name(mode)) => match mode {
+                RenameMode::From => Some(FileChangeType::Deleted),
+                RenameMode::To => Some(FileChangeType::Created),
+                RenameMode::Both if index == 0 => Some(FileChangeType::Deleted),
+                RenameMode::Both => Some(FileChangeType::Created),
+                _ if path.exists() => Some(FileChangeType::Created),
+                _ => Some(FileChangeType::Deleted),
+            },
+            _ => None,
+        }
+    }
+
+    /// Convert a notify event into structured changes for the paths accepted by `keep`
+    fn structured_changes(
+        event: &notify::Event,
+        keep: impl Fn(&Path) -> bool,
+    ) -> Vec<FileSystemChange> {
+        if let (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [old_path, new_path]) =
+            (&event.kind, event.paths.as_slice())
+        {
+            return match (keep(old_path), keep(new_path)) {
+                (true, true) => vec![FileSystemChange::Renamed {
+                    old_path: old_path.clone(),
+                    new_path: new_path.clone(),
+                }],
+                // Moved into or out of a watched location
+                (false, true) => vec![FileSystemChange::Created {
+                    path: new_path.clone(),
+                }],
+                (true, false) => vec![FileSystemChange::Deleted {
+                    path: old_path.clone(),
+                }],
+                (false, false) => Vec::new(),
+            };
+        }
+
+        event
+            .paths
+            .iter()
+            .enumerate()
+            .filter(|(_, path)| keep(path))
+            .filter_map(|(index, path)| {
+                Self::classify_change(&event.kind, path, index)
+                    .map(|change| FileSystemChange::from_change_type(path.clone(), change))
+            })
+            .collect()
+    }
+
+    /// Classify a path inside `.git`; `None` if it does not affect git status
+    fn git_change_kind(path: &Path) -> Option<GitChangeKind> {
         let path_str = path.to_string_lossy();
 
         // Ignore lock files - they are temporary and don't indicate status changes
         if path_str.ends_with(".lock") {
-            return false;
+            return None;
         }
 
         // Files that indicate git status changes
@@ -392,30 +1189,42 @@ impl FileWatcher {
         // .git/CHERRY_PICK_HEAD - cherry-pick state
         // .git/ORIG_HEAD - original head before dangerous operations
 
-        if path_str.ends_with(".git/index")
-            || (path_str.contains(".git/index") && !path_str.ends_with(".lock"))
-        {
-            return true;
+        if path_str.contains(".git/index") {
+            return Some(GitChangeKind::Index);
         }
         // Only match .git/HEAD, not logs/HEAD or other HEAD files
         if path_str.ends_with(".git/HEAD") {
-            return true;
+            return Some(GitChangeKind::Head);
         }
         if path_str.contains(".git/refs/heads/") {
-            return true;
+            return Some(GitChangeKind::LocalRef);
         }
         if path_str.contains(".git/refs/remotes/") {
-            return true;
+            return Some(GitChangeKind::RemoteRef);
         }
         if path_str.ends_with("MERGE_HEAD")
             || path_str.ends_with("REBASE_HEAD")
             || path_str.ends_with("CHERRY_PICK_HEAD")
             || path_str.ends_with("ORIG_HEAD")
         {
-            return true;
+            return Some(GitChangeKind::OperationState);
         }
 
-        false
+        None
+    }
+
+    /// Ref name for a changed path, relative to the git directory (`HEAD`, `refs/heads/main`)
+    fn git_ref_name(path: &Path, kind: GitChangeKind) -> Option<String> {
+        let path_str = path.to_string_lossy().replace('\\', "/");
+        match kind {
+            GitChangeKind::Index => None,
+            GitChangeKind::Head | GitChangeKind::OperationState => path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string()),
+            GitChangeKind::LocalRef | GitChangeKind::RemoteRef => path_str
+                .find(".git/refs/")
+                .map(|index| path_str[index + ".git/".len()..].to_string()),
+        }
     }
 }
 
@@ -431,89 +1240,108 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_is_git_status_file_matches_index() {
-        assert!(FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/index"
-        )));
+    fn test_git_change_kind_matches_index() {
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/index")).is_some());
     }
 
     #[test]
-    fn test_is_git_status_file_matches_head() {
-        assert!(FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/HEAD"
-        )));
+    fn test_git_change_kind_matches_head() {
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/HEAD")).is_some());
         // refs/heads/HEAD is actually a branch named HEAD (rare but valid), still matches via refs/heads/
-        assert!(FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/refs/heads/HEAD"
-        )));
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/refs/heads/HEAD")).is_some());
     }
 
     #[test]
-    fn test_is_git_status_file_matches_refs_heads() {
-        assert!(FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/refs/heads/main"
-        )));
-        assert!(FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/refs/heads/feature/branch"
-        )));
+    fn test_git_change_kind_matches_refs_heads() {
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/refs/heads/main")).is_some());
+        assert!(
+            FileWatcher::git_change_kind(Path::new("/repo/.git/refs/heads/feature/branch"))
+                .is_some()
+        );
     }
 
     #[test]
-    fn test_is_git_status_file_matches_refs_remotes() {
-        assert!(FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/refs/remotes/origin/main"
-        )));
-        assert!(FileWatcher::is_git_status_file(Path::new(
+    fn test_git_change_kind_matches_refs_remotes() {
+        assert!(
+            FileWatcher::git_change_kind(Path::new("/repo/.git/refs/remotes/origin/main"))
+                .is_some()
+        );
+        assert!(FileWatcher::git_change_kind(Path::new(
             "/repo/.git/refs/remotes/upstream/develop"
-        )));
+        ))
+        .is_some());
     }
 
     #[test]
-    fn test_is_git_status_file_matches_special_heads() {
-        assert!(FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/MERGE_HEAD"
-        )));
-        assert!(FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/REBASE_HEAD"
-        )));
-        assert!(FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/CHERRY_PICK_HEAD"
-        )));
-        assert!(FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/ORIG_HEAD"
-        )));
+    fn test_git_change_kind_matches_special_heads() {
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/MERGE_HEAD")).is_some());
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/REBASE_HEAD")).is_some());
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/CHERRY_PICK_HEAD")).is_some());
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/ORIG_HEAD")).is_some());
     }
 
     #[test]
-    fn test_is_git_status_file_ignores_lock_files() {
-        assert!(!FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/index.lock"
-        )));
-        assert!(!FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/refs/heads/main.lock"
-        )));
-        assert!(!FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/HEAD.lock"
-        )));
+    fn test_git_change_kind_ignores_lock_files() {
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/index.lock")).is_none());
+        assert!(
+            FileWatcher::git_change_kind(Path::new("/repo/.git/refs/heads/main.lock")).is_none()
+        );
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/HEAD.lock")).is_none());
     }
 
     #[test]
-    fn test_is_git_status_file_ignores_other_git_files() {
-        assert!(!FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/config"
-        )));
-        assert!(!FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/description"
-        )));
-        assert!(!FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/objects/pack/pack-abc.idx"
-        )));
-        assert!(!FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/COMMIT_EDITMSG"
-        )));
-        assert!(!FileWatcher::is_git_status_file(Path::new(
-            "/repo/.git/logs/HEAD"
-        )));
+    fn test_git_change_kind_ignores_other_git_files() {
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/config")).is_none());
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/description")).is_none());
+        assert!(
+            FileWatcher::git_change_kind(Path::new("/repo/.git/objects/pack/pack-abc.idx"))
+                .is_none()
+        );
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/COMMIT_EDITMSG")).is_none());
+        assert!(FileWatcher::git_change_kind(Path::new("/repo/.git/logs/HEAD")).is_none());
+    }
+
+    #[test]
+    fn test_git_change_kind_classifies_paths() {
+        let cases = [
+            ("/repo/.git/index", GitChangeKind::Index),
+            ("/repo/.git/HEAD", GitChangeKind::Head),
+            ("/repo/.git/refs/heads/main", GitChangeKind::LocalRef),
+            (
+                "/repo/.git/refs/remotes/origin/main",
+                GitChangeKind::RemoteRef,
+            ),
+            ("/repo/.git/MERGE_HEAD", GitChangeKind::OperationState),
+        ];
+        for (path, kind) in cases {
+            assert_eq!(FileWatcher::git_change_kind(Path::new(path)), Some(kind));
+        }
+    }
+
+    #[test]
+    fn test_git_status_change_records_refs_and_paths() {
+        let mut change = GitStatusChange::default();
+        assert!(change.record(Path::new("/repo/.git/refs/remotes/origin/feature/x")));
+        assert!(!change.record(Path::new("/repo/.git/objects/ab/cdef")));
+        assert!(!change.record(Path::new("/repo/.git/index.lock")));
+        assert!(!change.affects_worktree_diff());
+
+        assert!(change.record(Path::new("/repo/.git/HEAD")));
+        assert!(change.record(Path::new("/repo/.git/index")));
+        assert!(change.affects_worktree_diff());
+
+        assert_eq!(
+            serde_json::to_value(&change).unwrap(),
+            serde_json::json!({
+                "kinds": ["index", "head", "remoteRef"],
+                "refs": ["HEAD", "refs/remotes/origin/feature/x"],
+                "paths": [
+                    "/repo/.git/HEAD",
+                    "/repo/.git/index",
+                    "/repo/.git/refs/remotes/origin/feature/x"
+                ]
+            })
+        );
     }
 
     #[test]
@@ -647,6 +1475,355 @@ mod tests {
         assert!(FileWatcher::should_watch_path(Path::new("/repo/README.md")));
     }
 
+    #[test]
+    fn test_file_watcher_config_validation() {
+        assert!(FileWatcherConfig::default().validate().is_ok());
+
+        let too_fast = FileWatcherConfig {
+            debounce_ms: 10,
+            ..Default::default()
+        };
+        assert!(too_fast.validate().is_err());
+
+        let nested_exclude = FileWatcherConfig {
+            excluded_dirs: vec!["a/b".to_string()],
+            ..Default::default()
+        };
+        assert!(nested_exclude.validate().is_err());
+    }
+
+    #[test]
+    fn test_file_watcher_config_deserialize_defaults() {
+        let config: FileWatcherConfig =
+            serde_json::from_str(r#"{"excludedDirs":["generated"]}"#).unwrap();
+        assert_eq!(config.debounce_ms, 500);
+        assert_eq!(config.excluded_dirs, vec!["generated".to_string()]);
+        assert!(config.include_globs.is_empty());
+    }
+
+    #[test]
+    fn test_should_watch_path_with_config() {
+        let root = Path::new("/repo");
+        let config = FileWatcherConfig {
+            debounce_ms: 500,
+            excluded_dirs: vec!["generated".to_string()],
+            include_globs: vec!["build/reports/**".to_string()],
+        };
+
+        assert!(FileWatcher::should_watch_path_with(
+            Path::new("/repo/src/main.rs"),
+            root,
+            &config
+        ));
+        assert!(!FileWatcher::should_watch_path_with(
+            Path::new("/repo/generated/api.ts"),
+            root,
+            &config
+        ));
+        // build/ is excluded by default, but the include glob re-enables this subtree
+        assert!(FileWatcher::should_watch_path_with(
+            Path::new("/repo/build/reports/index.html"),
+            root,
+            &config
+        ));
+        assert!(!FileWatcher::should_watch_path_with(
+            Path::new("/repo/build/output.js"),
+            root,
+            &config
+        ));
+    }
+
+    #[test]
+    fn test_parse_proc_mounts() {
+        let content = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+            server:/export /mnt/shared nfs4 rw,relatime 0 0\n\
+            //nas/share /mnt/my\\040files cifs rw 0 0\n";
+        let mounts = parse_proc_mounts(content);
+
+        assert_eq!(
+            mount_fs_type(&mounts, Path::new("/home/user/project")),
+            Some("ext4")
+        );
+        assert_eq!(
+            mount_fs_type(&mounts, Path::new("/mnt/shared/project")),
+            Some("nfs4")
+        );
+        assert_eq!(
+            mount_fs_type(&mounts, Path::new("/mnt/my files/project")),
+            Some("cifs")
+        );
+        // Prefix matching is per path component
+        assert_eq!(
+            mount_fs_type(&mounts, Path::new("/mnt/shared2")),
+            Some("ext4")
+        );
+    }
+
+    #[test]
+    fn test_parse_bsd_mount_output() {
+        let content = "/dev/disk3s1s1 on / (apfs, sealed, local, read-only, journaled)\n\
+            //user@nas._smb._tcp.local/Projects on /Volumes/Projects (smbfs, nodev, nosuid, mounted by user)\n";
+        let mounts = parse_bsd_mount_output(content);
+
+        assert_eq!(
+            mount_fs_type(&mounts, Path::new("/Volumes/Projects/app")),
+            Some("smbfs")
+        );
+        assert_eq!(
+            mount_fs_type(&mounts, Path::new("/Users/me/app")),
+            Some("apfs")
+        );
+    }
+
+    #[test]
+    fn test_network_fs_types() {
+        assert!(is_network_fs_type("nfs"));
+        assert!(is_network_fs_type("SMBFS"));
+        assert!(is_network_fs_type("fuse.sshfs"));
+        assert!(!is_network_fs_type("ext4"));
+        assert!(!is_network_fs_type("apfs"));
+    }
+
+    #[test]
+    fn test_file_watcher_config_backend() {
+        let config: FileWatcherConfig =
+            serde_json::from_str(r#"{"backend":"poll","pollIntervalMs":5000}"#).unwrap();
+        assert_eq!(config.backend, WatcherBackend::Poll);
+        assert_eq!(config.poll_interval_ms, 5000);
+        assert!(config.validate().is_ok());
+
+        let too_fast = FileWatcherConfig {
+            poll_interval_ms: 10,
+            ..Default::default()
+        };
+        assert!(too_fast.validate().is_err());
+    }
+
+    #[test]
+    fn test_pause_guard_nests() {
+        assert!(!is_paused());
+        {
+            let _outer = PauseGuard::acquire();
+            {
+                let _inner = PauseGuard::acquire();
+                assert!(is_paused());
+            }
+            assert!(is_paused());
+        }
+        assert!(!is_paused());
+        // Unbalanced resumes never underflow
+        assert!(resume());
+        assert!(!is_paused());
+    }
+
+    #[test]
+    fn test_structured_changes_rename_pair() {
+        let event = notify::Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(PathBuf::from("/repo/src/old.rs"))
+            .add_path(PathBuf::from("/repo/src/new.rs"));
+
+        let changes = FileWatcher::structured_changes(&event, |_| true);
+        assert_eq!(
+            changes,
+            vec![FileSystemChange::Renamed {
+                old_path: PathBuf::from("/repo/src/old.rs"),
+                new_path: PathBuf::from("/repo/src/new.rs"),
+            }]
+        );
+
+        // Renaming into an ignored directory looks like a delete
+        let changes = FileWatcher::structured_changes(&event, |p| p.ends_with("old.rs"));
+        assert_eq!(
+            changes,
+            vec![FileSystemChange::Deleted {
+                path: PathBuf::from("/repo/src/old.rs"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_pending_changes_coalesce() {
+        let a = PathBuf::from("/repo/a.rs");
+        let b = PathBuf::from("/repo/b.rs");
+        let mut pending = PendingChanges::default();
+
+        pending.record(FileSystemChange::Created { path: a.clone() });
+        pending.record(FileSystemChange::Modified { path: a.clone() });
+        pending.record(FileSystemChange::Deleted { path: b.clone() });
+        pending.record(FileSystemChange::Created { path: b.clone() });
+        assert_eq!(
+            pending.take(),
+            vec![
+                FileSystemChange::Created { path: a.clone() },
+                FileSystemChange::Modified { path: b.clone() },
+            ]
+        );
+
+        pending.record(FileSystemChange::Created { path: a.clone() });
+        pending.record(FileSystemChange::Deleted { path: a.clone() });
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_pending_changes_rename_supersedes_halves() {
+        let old = PathBuf::from("/repo/old.rs");
+        let new = PathBuf::from("/repo/new.rs");
+        let mut pending = PendingChanges::default();
+
+        pending.record(FileSystemChange::Deleted { path: old.clone() });
+        pending.record(FileSystemChange::Created { path: new.clone() });
+        pending.record(FileSystemChange::Renamed {
+            old_path: old.clone(),
+            new_path: new.clone(),
+        });
+        let changes = pending.take();
+        assert_eq!(
+            changes,
+            vec![FileSystemChange::Renamed {
+                old_path: old.clone(),
+                new_path: new.clone(),
+            }]
+        );
+        assert_eq!(
+            changes[0].lsp_changes(),
+            vec![
+                (old.clone(), FileChangeType::Deleted),
+                (new.clone(), FileChangeType::Created),
+            ]
+        );
+
+        // Created then renamed within one window is just a new file
+        pending.record(FileSystemChange::Created { path: old.clone() });
+        pending.record(FileSystemChange::Renamed {
+            old_path: old,
+            new_path: new.clone(),
+        });
+        assert_eq!(
+            pending.take(),
+            vec![FileSystemChange::Created { path: new }]
+        );
+    }
+
+    #[test]
+    fn test_pending_changes_renamed_then_deleted() {
+        let a = PathBuf::from("/repo/a.rs");
+        let b = PathBuf::from("/repo/b.rs");
+        let c = PathBuf::from("/repo/c.rs");
+        let mut pending = PendingChanges::default();
+
+        pending.record(FileSystemChange::Renamed {
+            old_path: a.clone(),
+            new_path: b.clone(),
+        });
+        pending.record(FileSystemChange::Modified { path: c.clone() });
+        pending.record(FileSystemChange::Deleted { path: b.clone() });
+        assert_eq!(pending.len(), 2);
+        assert_eq!(
+            pending.take(),
+            vec![
+                FileSystemChange::Deleted { path: a.clone() },
+                FileSystemChange::Modified { path: c.clone() },
+            ]
+        );
+
+        // A file recreated at the original path folds into the deletion
+        pending.record(FileSystemChange::Renamed {
+            old_path: a.clone(),
+            new_path: b.clone(),
+        });
+        pending.record(FileSystemChange::Created { path: a.clone() });
+        pending.record(FileSystemChange::Deleted { path: b });
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending.take(), vec![FileSystemChange::Modified { path: a }]);
+    }
+
+    #[test]
+    fn test_file_system_change_serialization() {
+        let change = FileSystemChange::Renamed {
+            old_path: PathBuf::from("/repo/a"),
+            new_path: PathBuf::from("/repo/b"),
+        };
+        assert_eq!(
+            serde_json::to_value(&change).unwrap(),
+            serde_json::json!({ "kind": "renamed", "oldPath": "/repo/a", "newPath": "/repo/b" })
+        );
+    }
+
+    #[test]
+    fn test_classify_change_basic_kinds() {
+        use notify::event::{CreateKind, DataChange, RemoveKind};
+        let path = Path::new("/repo/src/main.rs");
+
+        assert_eq!(
+            FileWatcher::classify_change(&EventKind::Create(CreateKind::File), path, 0),
+            Some(FileChangeType::Created)
+        );
+        assert_eq!(
+            FileWatcher::classify_change(&EventKind::Remove(RemoveKind::File), path, 0),
+            Some(FileChangeType::Deleted)
+        );
+        assert_eq!(
+            FileWatcher::classify_change(
+                &EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+                path,
+                0
+            ),
+            Some(FileChangeType::Changed)
+        );
+        assert_eq!(
+            FileWatcher::classify_change(&EventKind::Modify(ModifyKind::Any), path, 0),
+            Some(FileChangeType::Changed)
+        );
+        assert_eq!(
+            FileWatcher::classify_change(
+                &EventKind::Modify(ModifyKind::Metadata(MetadataKind::WriteTime)),
+                path,
+                0
+            ),
+            Some(FileChangeType::Changed)
+        );
+        assert_eq!(
+            FileWatcher::classify_change(
+                &EventKind::Modify(ModifyKind::Metadata(MetadataKind::Permissions)),
+                path,
+                0
+            ),
+            None
+        );
+        assert_eq!(
+            FileWatcher::classify_change(
+                &EventKind::Access(notify::event::AccessKind::Any),
+                path,
+                0
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_classify_change_renames() {
+        let from = Path::new("/repo/src/old.rs");
+        let to = Path::new("/repo/src/new.rs");
+        let both = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+
+        assert_eq!(
+            FileWatcher::classify_change(&both, from, 0),
+            Some(FileChangeType::Deleted)
+        );
+        assert_eq!(
+            FileWatcher::classify_change(&both, to, 1),
+            Some(FileChangeType::Created)
+        );
+        assert_eq!(
+            FileWatcher::classify_change(
+                &EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+                from,
+                0
+            ),
+            Some(FileChangeType::Deleted)
+        );
+    }
+
     // Test for trailing-edge debounce behavior simulation
     #[test]
     fn test_trailing_edge_debounce_logic() {
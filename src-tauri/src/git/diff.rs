@@ -3,6 +3,7 @@ use git2::{Diff, DiffOptions, Error as GitError, Repository};
 use lazy_static::lazy_static;
 use lru::LruCache;
 use std::num::NonZeroUsize;
+use std::path::Path;
 use std::sync::Mutex;
 
 lazy_static! {
@@ -194,6 +195,29 @@ pub fn get_line_changes(
     Ok(changes)
 }
 
+/// Drops cached line changes for one repository, e.g. after its index or HEAD moved
+pub fn invalidate_line_changes(git_dir: &Path) {
+    let git_dir = git_dir.to_string_lossy();
+    let git_dir = git_dir.trim_end_matches(['/', '\\']);
+    let belongs_to_repo = |key: &str| {
+        key.strip_prefix(git_dir)
+            .is_some_and(|rest| rest.trim_start_matches(['/', '\\']).starts_with(':'))
+    };
+
+    if let Ok(mut cache) = LINE_CHANGES_CACHE.lock() {
+        let stale: Vec<String> = cache
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| belongs_to_repo(key))
+            .cloned()
+            .collect();
+        for key in &stale {
+            cache.pop(key);
+        }
+        log::debug!("Invalidated {} cached line changes", stale.len());
+    }
+}
+
 /// Generates raw diff text for all changed files (working directory vs HEAD)
 /// Returns a string similar to `git diff` output, suitable for AI processing
 pub fn get_raw_diff_text(repo: &Repository) -> Result<String, GitError> {
@@ -610,4 +634,28 @@ mod tests {
         assert!(diff_text.contains("README.md"), "Should contain README.md");
         assert!(diff_text.contains("code.rs"), "Should contain code.rs");
     }
+
+    #[test]
+    fn test_invalidate_line_changes() {
+        let temp_dir = create_temp_git_repo_with_commit();
+        std::fs::write(temp_dir.path().join("README.md"), "# Changed\n").unwrap();
+
+        let repo = Repository::open(temp_dir.path()).unwrap();
+        let before = get_line_changes(&repo, "README.md").unwrap();
+        assert!(!before.is_empty());
+
+        // Stage the change and commit it; the cached entry is now stale
+        Command::new("git")
+            .args(["commit", "-am", "Update README"])
+            .current_dir(temp_dir.path())
+            .output()
+            .unwrap();
+        assert_eq!(
+            get_line_changes(&repo, "README.md").unwrap().len(),
+            before.len()
+        );
+
+        invalidate_line_changes(&temp_dir.path().join(".git"));
+        assert!(get_line_changes(&repo, "README.md").unwrap().is_empty());
+    }
 }
@@ -110,6 +110,8 @@ pub async fn git_acquire_worktree(
     force: Option<bool>,
     worktree_root: Option<String>,
 ) -> Result<WorktreeInfo, String> {
+    // Creating or resetting a worktree checks out every file at once
+    let _pause = crate::file_watcher::PauseGuard::acquire();
     worktree::acquire_worktree(
         &project_path,
         pool_index,
@@ -164,6 +166,8 @@ pub async fn git_merge_worktree(
     commit_message: Option<String>,
     worktree_root: Option<String>,
 ) -> Result<MergeResult, String> {
+    // Merges and rebases rewrite many files at once; emit one refresh afterwards
+    let _pause = crate::file_watcher::PauseGuard::acquire();
     worktree::merge_worktree_to_main(
         &project_path,
         pool_index,
@@ -175,6 +179,7 @@ pub async fn git_merge_worktree(
 /// Abort an in-progress merge
 #[tauri::command]
 pub async fn git_abort_merge(project_path: String) -> Result<(), String> {
+    let _pause = crate::file_watcher::PauseGuard::acquire();
     worktree::abort_merge(&project_path)
 }
 
@@ -184,6 +189,7 @@ pub async fn git_continue_merge(
     project_path: String,
     message: Option<String>,
 ) -> Result<MergeResult, String> {
+    let _pause = crate::file_watcher::PauseGuard::acquire();
     worktree::continue_merge(&project_path, message.as_deref())
 }
 
@@ -203,6 +209,7 @@ pub async fn git_sync_worktree_from_main(
     pool_index: u32,
     worktree_root: Option<String>,
 ) -> Result<SyncResult, String> {
+    let _pause = crate::file_watcher::PauseGuard::acquire();
     worktree::sync_worktree_from_main(&project_path, pool_index, worktree_root.as_deref())
 }
 
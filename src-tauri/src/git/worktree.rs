@@ -16,6 +16,9 @@ const MAX_POOL_SIZE: u32 = 3;
 /// Branch name prefix for worktree branches
 const BRANCH_PREFIX: &str = "talkcody-pool";
 
+/// Branch name prefix for the worktrees of isolated tasks
+const TASK_BRANCH_PREFIX: &str = "talkcody-task";
+
 // ============================================================================
 // Types
 // ============================================================================
@@ -40,6 +43,18 @@ pub struct WorktreeInfo {
     pub changes_count: usize,
 }
 
+/// Worktree created for a single task, on a branch of its own
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskWorktreeInfo {
+    /// Absolute path to the worktree directory
+    pub path: String,
+    /// Branch name (e.g., "talkcody-task-task_abc")
+    pub branch: String,
+    /// Commit the branch started from
+    pub base_commit: String,
+}
+
 /// Status of the entire worktree pool for a project
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -401,13 +416,31 @@ pub fn remove_worktree(
     }
 
     let worktree_path = get_worktree_path(project_path, pool_index, worktree_root);
-    let worktree_path_str = worktree_path.to_string_lossy().to_string();
     let branch_name = get_branch_name(pool_index);
 
     // Clear task_id first
     set_task_id(project_path, pool_index, None);
 
-    if !worktree_exists(&worktree_path) {
+    remove_worktree_at(project_path, &worktree_path, &branch_name)?;
+
+    log::info!(
+        "Removed worktree pool-{} for project {}",
+        pool_index,
+        project_path
+    );
+
+    Ok(())
+}
+
+/// Remove the worktree at a path and delete its branch
+fn remove_worktree_at(
+    project_path: &str,
+    worktree_path: &Path,
+    branch_name: &str,
+) -> Result<(), String> {
+    let worktree_path_str = worktree_path.to_string_lossy().to_string();
+
+    if !worktree_exists(worktree_path) {
         return Ok(()); // Already removed
     }
 
@@ -425,22 +458,21 @@ pub fn remove_worktree(
             String::from_utf8_lossy(&output.stderr)
         );
 
-        fs::remove_dir_all(&worktree_path)
+        fs::remove_dir_all(worktree_path)
             .map_err(|e| format!("Failed to remove worktree directory: {}", e))?;
+        // Drop the registration of the directory that is gone
+        let _ = Command::new("git")
+            .args(["worktree", "prune"])
+            .current_dir(project_path)
+            .output();
     }
 
     // Try to delete the branch (may fail if not fully merged, that's ok)
     let _ = Command::new("git")
-        .args(["branch", "-D", &branch_name])
+        .args(["branch", "-D", branch_name])
         .current_dir(project_path)
         .output();
 
-    log::info!(
-        "Removed worktree pool-{} for project {}",
-        pool_index,
-        project_path
-    );
-
     Ok(())
 }
 
@@ -640,11 +672,27 @@ pub fn merge_worktree_to_main(
         ));
     }
 
+    merge_branch_to_main(
+        project_path,
+        &worktree_path_str,
+        &branch_name,
+        commit_message,
+    )
+}
+
+/// Commit pending changes in a worktree, then merge its branch into the
+/// main branch of the project
+fn merge_branch_to_main(
+    project_path: &str,
+    worktree_path_str: &str,
+    branch_name: &str,
+    commit_message: Option<&str>,
+) -> Result<MergeResult, String> {
     // First, commit any uncommitted changes in the worktree
-    let changes = get_worktree_changes(&worktree_path_str)?;
+    let changes = get_worktree_changes(worktree_path_str)?;
     if changes.has_uncommitted_changes {
         let msg = commit_message.unwrap_or("Auto-commit before merge");
-        match commit_worktree(&worktree_path_str, msg) {
+        match commit_worktree(worktree_path_str, msg) {
             Ok(_) => log::info!("Auto-committed changes before merge"),
             Err(e) => {
                 if !e.contains("Nothing to commit") {
@@ -677,7 +725,7 @@ pub fn merge_worktree_to_main(
 
     // First try fast-forward merge (no merge commit needed)
     let ff_output = Command::new("git")
-        .args(["merge", "--ff-only", &branch_name])
+        .args(["merge", "--ff-only", branch_name])
         .current_dir(project_path)
         .output()
         .map_err(|e| format!("Failed to merge: {}", e))?;
@@ -695,7 +743,7 @@ pub fn merge_worktree_to_main(
         let default_merge_msg = format!("Merge {} into {}", branch_name, main_branch);
         let merge_msg = commit_message.unwrap_or(&default_merge_msg);
         Command::new("git")
-            .args(["merge", &branch_name, "-m", merge_msg])
+            .args(["merge", branch_name, "-m", merge_msg])
             .current_dir(project_path)
             .output()
             .map_err(|e| format!("Failed to merge: {}", e))?
@@ -754,6 +802,98 @@ pub fn merge_worktree_to_main(
     })
 }
 
+/// Get the path of an isolated task's worktree
+fn get_task_worktree_path(project_path: &str, task_id: &str, custom_root: Option<&str>) -> PathBuf {
+    get_pool_dir(project_path, custom_root)
+        .join("tasks")
+        .join(task_id)
+}
+
+/// Create a worktree for an isolated task on a new branch from HEAD
+pub fn create_task_worktree(
+    project_path: &str,
+    task_id: &str,
+    worktree_root: Option<&str>,
+) -> Result<TaskWorktreeInfo, String> {
+    let worktree_path = get_task_worktree_path(project_path, task_id, worktree_root);
+    let worktree_path_str = worktree_path.to_string_lossy().to_string();
+    let branch_name = format!("{}-{}", TASK_BRANCH_PREFIX, task_id);
+
+    let repo =
+        Repository::open(project_path).map_err(|e| format!("Failed to open repository: {}", e))?;
+    let head_commit =
+        get_head_commit(&repo).map_err(|e| format!("Failed to get HEAD commit: {}", e))?;
+
+    if let Some(parent) = worktree_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create worktree directory: {}", e))?;
+    }
+
+    let output = Command::new("git")
+        .args([
+            "worktree",
+            "add",
+            "-b",
+            &branch_name,
+            &worktree_path_str,
+            &head_commit,
+        ])
+        .current_dir(project_path)
+        .output()
+        .map_err(|e| format!("Failed to create worktree: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to create worktree: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    log::info!(
+        "Created worktree {} on {} for task {}",
+        worktree_path_str,
+        branch_name,
+        task_id
+    );
+
+    Ok(TaskWorktreeInfo {
+        path: worktree_path_str,
+        branch: branch_name,
+        base_commit: head_commit,
+    })
+}
+
+/// Merge an isolated task's branch into the main branch
+pub fn merge_task_worktree(
+    project_path: &str,
+    worktree_path: &str,
+    branch_name: &str,
+    commit_message: Option<&str>,
+) -> Result<MergeResult, String> {
+    if !worktree_exists(Path::new(worktree_path)) {
+        return Err(format!("Worktree does not exist at {}", worktree_path));
+    }
+
+    merge_branch_to_main(project_path, worktree_path, branch_name, commit_message)
+}
+
+/// Remove an isolated task's worktree and delete its branch
+pub fn remove_task_worktree(
+    project_path: &str,
+    worktree_path: &str,
+    branch_name: &str,
+) -> Result<(), String> {
+    remove_worktree_at(project_path, Path::new(worktree_path), branch_name)?;
+
+    log::info!(
+        "Removed worktree {} for project {}",
+        worktree_path,
+        project_path
+    );
+
+    Ok(())
+}
+
 /// Abort an in-progress merge
 pub fn abort_merge(project_path: &str) -> Result<(), String> {
     let output = Command::new("git")
@@ -1566,4 +1706,35 @@ mod tests {
         // Clean up
         let _ = cleanup_all_worktrees(&project_path, None);
     }
+
+    #[test]
+    fn test_task_worktree_merge_and_remove() {
+        let temp_dir = create_test_repo();
+        let project_path = temp_dir.path().to_string_lossy().to_string();
+
+        let worktree = create_task_worktree(&project_path, "task_iso", None).unwrap();
+        assert_eq!(worktree.branch, "talkcody-task-task_iso");
+        assert!(Path::new(&worktree.path).join("README.md").exists());
+
+        // Uncommitted changes are committed on the task branch, then merged
+        std::fs::write(Path::new(&worktree.path).join("task.txt"), "done").unwrap();
+        let result = merge_task_worktree(
+            &project_path,
+            &worktree.path,
+            &worktree.branch,
+            Some("Task changes"),
+        )
+        .unwrap();
+        assert!(result.success, "Merge should succeed: {:?}", result);
+        assert!(temp_dir.path().join("task.txt").exists());
+
+        remove_task_worktree(&project_path, &worktree.path, &worktree.branch).unwrap();
+        assert!(!Path::new(&worktree.path).exists());
+        let branches = Command::new("git")
+            .args(["branch", "--list", &worktree.branch])
+            .current_dir(&project_path)
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&branches.stdout).trim().is_empty());
+    }
 }
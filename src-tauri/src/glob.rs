@@ -142,7 +142,7 @@ impl HighPerformanceGlob {
 
     /// Simple glob pattern matching implementation
     /// Supports: *, **, ?, [abc], [a-z], {a,b,c}
-    fn glob_match(&self, path: &str, pattern: &str) -> bool {
+    pub(crate) fn glob_match(&self, path: &str, pattern: &str) -> bool {
         // Handle ** patterns specially
         if pattern.contains("**") {
             return self.glob_match_with_recursive(path, pattern);
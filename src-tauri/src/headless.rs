@@ -0,0 +1,501 @@
+//! Headless Task Runner
+//!
+//! Runs one agent task against a workspace from the terminal, without the
+//! Tauri UI, for CI and scripting. Tokens stream to stdout and progress to
+//! stderr; the exit code reports how the task ended. Uses the app's data
+//! directory, so the API keys and models configured in the app apply.
+
+use crate::core::types::{RuntimeEvent, RuntimeTaskState, TaskInput};
+use crate::core::{CoreRuntime, ProviderLlmClient};
+use crate::database::Database;
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::providers::provider_configs::builtin_providers;
+use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::storage::{PhaseModels, Storage, TaskSettings, WorkspaceInfo};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Exit code of a completed task
+pub const EXIT_COMPLETED: i32 = 0;
+/// Exit code of a failed task, or of a runtime that could not start
+pub const EXIT_FAILED: i32 = 1;
+/// Exit code of invalid arguments
+pub const EXIT_USAGE: i32 = 2;
+/// Exit code of a task that stopped to wait for the user
+pub const EXIT_WAITING: i32 = 3;
+/// Exit code of a cancelled task
+pub const EXIT_CANCELLED: i32 = 130;
+
+const USAGE: &str = "Usage: talkcody-cli [OPTIONS] [PROMPT]...
+
+Runs one agent task and streams its output. The prompt is read from stdin
+when it is omitted or `-`.
+
+Options:
+  -w, --workspace <PATH>   Workspace to run in (default: current directory)
+  -m, --model <MODEL>      Model as `model` or `model@provider`
+  -a, --agent <NAME>       Workspace agent from .talkcody/agents
+  -s, --settings <JSON>    Task settings as JSON, or a path to a JSON file
+      --max-tokens <N>     Maximum tokens per response
+      --plan               Run in plan mode and print the submitted plan
+      --auto-approve       Approve every tool call without asking
+      --isolate            Run in a git worktree on its own branch
+      --data-dir <PATH>    App data directory (default: the app's)
+      --json               Print runtime events as JSON lines
+  -h, --help               Show this help";
+
+/// Options of a headless run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeadlessArgs {
+    pub prompt: Option<String>,
+    pub workspace: Option<PathBuf>,
+    pub model: Option<String>,
+    pub agent: Option<String>,
+    pub settings: Option<String>,
+    pub max_tokens: Option<u32>,
+    pub plan: bool,
+    pub auto_approve: bool,
+    pub isolate: bool,
+    pub data_dir: Option<PathBuf>,
+    pub json: bool,
+    pub help: bool,
+}
+
+impl HeadlessArgs {
+    /// Parse command line arguments, without the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut prompt = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value)),
+                _ => (arg.clone(), None),
+            };
+            let mut value = |name: &str| {
+                inline
+                    .map(str::to_string)
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("{} requires a value", name))
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "-w" | "--workspace" => parsed.workspace = Some(value(&flag)?.into()),
+                "-m" | "--model" => parsed.model = Some(value(&flag)?),
+                "-a" | "--agent" => parsed.agent = Some(value(&flag)?),
+                "-s" | "--settings" => parsed.settings = Some(value(&flag)?),
+                "--max-tokens" => {
+                    let raw = value(&flag)?;
+                    let tokens = raw
+                        .parse()
+                        .map_err(|_| format!("Invalid --max-tokens value: {}", raw))?;
+                    parsed.max_tokens = Some(tokens);
+                }
+                "--plan" => parsed.plan = true,
+                "--auto-approve" => parsed.auto_approve = true,
+                "--isolate" => parsed.isolate = true,
+                "--data-dir" => parsed.data_dir = Some(value(&flag)?.into()),
+                "--json" => parsed.json = true,
+                "--" => prompt.extend(args.by_ref()),
+                "-" => prompt.push(arg),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+                _ => prompt.push(arg),
+            }
+        }
+
+        if !prompt.is_empty() && prompt != ["-"] {
+            parsed.prompt = Some(prompt.join(" "));
+        }
+        Ok(parsed)
+    }
+
+    /// Task settings from `--settings` with the other flags applied on top
+    pub fn task_settings(&self) -> Result<TaskSettings, String> {
+        let mut settings: TaskSettings = match &self.settings {
+            Some(raw) if raw.trim_start().starts_with('{') => {
+                serde_json::from_str(raw).map_err(|e| format!("Invalid --settings JSON: {}", e))?
+            }
+            Some(path) => {
+                let content = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read settings file {}: {}", path, e))?;
+                serde_json::from_str(&content)
+                    .map_err(|e| format!("Invalid settings file {}: {}", path, e))?
+            }
+            None => TaskSettings::default(),
+        };
+
+        if let Some(model) = &self.model {
+            settings
+                .models
+                .get_or_insert_with(PhaseModels::default)
+                .coding = Some(model.clone());
+        }
+        if let Some(agent) = &self.agent {
+            settings.agent = Some(agent.clone());
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            settings
+                .extra
+                .insert("maxTokens".to_string(), serde_json::json!(max_tokens));
+        }
+        if self.plan {
+            settings.plan_mode = Some(true);
+        }
+        if self.auto_approve {
+            settings.auto_approve_edits = Some(true);
+        }
+        Ok(settings)
+    }
+}
+
+/// Run a task from command line arguments and return the process exit code
+pub fn run_headless(args: Vec<String>) -> i32 {
+    let args = match HeadlessArgs::parse(args) {
+        Ok(args) if args.help => {
+            println!("{}", USAGE);
+            return EXIT_COMPLETED;
+        }
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start async runtime: {}", e);
+            return EXIT_FAILED;
+        }
+    };
+
+    runtime.block_on(async move {
+        match run(args).await {
+            Ok(code) => code,
+            Err((code, e)) => {
+                eprintln!("{}", e);
+                code
+            }
+        }
+    })
+}
+
+async fn run(args: HeadlessArgs) -> Result<i32, (i32, String)> {
+    let usage = |e: String| (EXIT_USAGE, e);
+    let failed = |e: String| (EXIT_FAILED, e);
+
+    let settings = args.task_settings().map_err(usage)?;
+    let prompt = match args.prompt.clone() {
+        Some(prompt) => prompt,
+        None => {
+            let mut prompt = String::new();
+            std::io::stdin()
+                .read_to_string(&mut prompt)
+                .map_err(|e| usage(format!("Failed to read prompt from stdin: {}", e)))?;
+            prompt
+        }
+    };
+    if prompt.trim().is_empty() {
+        return Err(usage("No prompt given".to_string()));
+    }
+
+    let workspace = match &args.workspace {
+        Some(path) => path.clone(),
+        None => std::env::current_dir().map_err(|e| failed(e.to_string()))?,
+    };
+    let workspace = std::fs::canonicalize(&workspace).map_err(|e| {
+        usage(format!(
+            "Workspace {} is not accessible: {}",
+            workspace.display(),
+            e
+        ))
+    })?;
+    let data_dir = match &args.data_dir {
+        Some(path) => path.clone(),
+        None => dirs::data_dir()
+            .ok_or_else(|| failed("Failed to get app data directory".to_string()))?
+            .join("com.talkcody"),
+    };
+
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let core = create_runtime(&data_dir, event_tx).await.map_err(failed)?;
+
+    let input = TaskInput {
+        session_id: String::new(),
+        agent_id: None,
+        project_id: None,
+        initial_message: prompt,
+        settings: Some(settings),
+        workspace: Some(WorkspaceInfo {
+            root_path: workspace.to_string_lossy().to_string(),
+            worktree_path: None,
+            repository_url: None,
+            branch: None,
+        }),
+        priority: 0,
+        isolate: args.isolate,
+        read_only_tools: false,
+    };
+    let validation = core.validate_task(&input).await;
+    for warning in &validation.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    let handle = core.start_task(input).await.map_err(failed)?;
+
+    let mut reporter = Reporter {
+        task_id: handle.task_id.clone(),
+        session_id: handle.session_id.clone(),
+        json: args.json,
+        final_state: None,
+    };
+    let mut cancelled = false;
+
+    loop {
+        tokio::select! {
+            event = event_rx.recv() => {
+                let Some(event) = event else {
+                    return Err(failed("Runtime stopped unexpectedly".to_string()));
+                };
+                if !reporter.is_own(&event) {
+                    continue;
+                }
+                reporter.print(&event);
+
+                match event {
+                    RuntimeEvent::ToolCallRequested { request, .. } => {
+                        let resumed = if args.auto_approve {
+                            core.approve_tool_call(&request.tool_call_id).await
+                        } else {
+                            eprintln!(
+                                "Denied {}: pass --auto-approve to allow tools that need approval",
+                                request.name
+                            );
+                            core.deny_tool_call(
+                                &request.tool_call_id,
+                                Some("Tool calls needing approval are denied in headless mode".to_string()),
+                            )
+                            .await
+                        };
+                        resumed.map_err(failed)?;
+                    }
+                    RuntimeEvent::BudgetExceeded { message, .. } => {
+                        eprintln!("{}; continue the task from the app", message);
+                        return Ok(EXIT_WAITING);
+                    }
+                    RuntimeEvent::TaskCompleted { .. } => {
+                        wait_until_finished(&core, &reporter.task_id).await;
+                        while let Ok(event) = event_rx.try_recv() {
+                            if reporter.is_own(&event) {
+                                reporter.print(&event);
+                            }
+                        }
+                        return Ok(exit_code(reporter.final_state));
+                    }
+                    _ => {}
+                }
+            }
+            _ = tokio::signal::ctrl_c(), if !cancelled => {
+                cancelled = true;
+                eprintln!("Cancelling task...");
+                core.cancel_task(&handle.task_id).await.map_err(failed)?;
+            }
+        }
+    }
+}
+
+/// Runtime over the app's storage, API keys and built-in providers
+async fn create_runtime(
+    data_dir: &std::path::Path,
+    event_sender: crate::core::types::EventSender,
+) -> Result<CoreRuntime, String> {
+    let database = Arc::new(Database::new(
+        data_dir.join("talkcody.db").to_string_lossy().to_string(),
+    ));
+    database.connect().await?;
+
+    let api_keys = ApiKeyManager::new(database, data_dir.to_path_buf());
+    if let Err(e) = api_keys.migrate_credentials().await {
+        eprintln!(
+            "warning: failed to move credentials into the keychain: {}",
+            e
+        );
+    }
+    crate::llm::redaction::refresh_shared(&api_keys).await;
+    let llm = Arc::new(ProviderLlmClient::new(
+        ProviderRegistry::new(builtin_providers()),
+        api_keys,
+    ));
+    let storage = Storage::new(data_dir.to_path_buf(), data_dir.join("attachments")).await?;
+    CoreRuntime::new(storage, llm, event_sender).await
+}
+
+/// Wait for the runtime to drop a completed task, so the events it sends
+/// while completing are all queued
+async fn wait_until_finished(core: &CoreRuntime, task_id: &str) {
+    for _ in 0..100 {
+        if core.get_task(task_id).await.is_none() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+fn exit_code(state: Option<RuntimeTaskState>) -> i32 {
+    match state {
+        Some(RuntimeTaskState::Completed) => EXIT_COMPLETED,
+        Some(RuntimeTaskState::Cancelled) => EXIT_CANCELLED,
+        Some(RuntimeTaskState::WaitingForUser) => EXIT_WAITING,
+        _ => EXIT_FAILED,
+    }
+}
+
+/// Prints the events of the headless task
+struct Reporter {
+    task_id: String,
+    session_id: String,
+    json: bool,
+    final_state: Option<RuntimeTaskState>,
+}
+
+impl Reporter {
+    fn is_own(&self, event: &RuntimeEvent) -> bool {
+        match event {
+            RuntimeEvent::TaskStateChanged { task_id, .. }
+            | RuntimeEvent::TaskQueuePositionChanged { task_id, .. }
+            | RuntimeEvent::Usage { task_id, .. }
+            | RuntimeEvent::IterationStarted { task_id, .. }
+            | RuntimeEvent::ContextCompacted { task_id, .. }
+            | RuntimeEvent::BudgetExceeded { task_id, .. }
+            | RuntimeEvent::PlanReady { task_id, .. }
+            | RuntimeEvent::TodosUpdated { task_id, .. }
+            | RuntimeEvent::WorktreeReady { task_id, .. }
+            | RuntimeEvent::SecretsRedacted { task_id, .. }
+            | RuntimeEvent::ToolCallStarted { task_id, .. }
+            | RuntimeEvent::ToolCallRequested { task_id, .. }
+            | RuntimeEvent::ToolOutput { task_id, .. }
+            | RuntimeEvent::ShellCommandDecided { task_id, .. }
+            | RuntimeEvent::ToolCallCompleted { task_id, .. }
+            | RuntimeEvent::TaskCompleted { task_id, .. } => *task_id == self.task_id,
+            RuntimeEvent::MessageCreated { session_id, .. }
+            | RuntimeEvent::Token { session_id, .. }
+            | RuntimeEvent::Reasoning { session_id, .. }
+            | RuntimeEvent::SessionUpdated { session_id, .. } => *session_id == self.session_id,
+            RuntimeEvent::Error {
+                task_id,
+                session_id,
+                ..
+            } => {
+                task_id.as_deref() == Some(self.task_id.as_str())
+                    || session_id.as_deref() == Some(self.session_id.as_str())
+            }
+        }
+    }
+
+    fn print(&mut self, event: &RuntimeEvent) {
+        if let RuntimeEvent::TaskStateChanged { state, .. } = event {
+            self.final_state = Some(*state);
+        }
+
+        if self.json {
+            match serde_json::to_string(event) {
+                Ok(line) => println!("{}", line),
+                Err(e) => eprintln!("Failed to serialize event: {}", e),
+            }
+            return;
+        }
+
+        match event {
+            RuntimeEvent::Token { token, .. } => {
+                let mut stdout = std::io::stdout();
+                let _ = stdout.write_all(token.as_bytes());
+                let _ = stdout.flush();
+            }
+            RuntimeEvent::ToolCallCompleted { result, .. } => match &result.error {
+                Some(error) => eprintln!("\n[tool {} failed: {}]", result.tool_call_id, error),
+                None => eprintln!("\n[tool {} done]", result.tool_call_id),
+            },
+            RuntimeEvent::PlanReady { plan, .. } => {
+                println!("\n\n{}", plan.summary);
+                for (index, step) in plan.steps.iter().enumerate() {
+                    println!("{}. {}", index + 1, step);
+                }
+            }
+            RuntimeEvent::WorktreeReady { worktree, .. } => {
+                eprintln!(
+                    "\nChanges are on branch {} in {}",
+                    worktree.branch, worktree.path
+                );
+            }
+            RuntimeEvent::Error { message, .. } => eprintln!("\nerror: {}", message),
+            RuntimeEvent::TaskCompleted { .. } => println!(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = HeadlessArgs::parse(args(&[
+            "--model=gpt-4o@openai",
+            "-w",
+            "/tmp/project",
+            "--max-tokens",
+            "4000",
+            "--plan",
+            "fix",
+            "the",
+            "build",
+        ]))
+        .unwrap();
+        assert_eq!(parsed.prompt.as_deref(), Some("fix the build"));
+        assert_eq!(parsed.workspace, Some(PathBuf::from("/tmp/project")));
+        assert_eq!(parsed.max_tokens, Some(4000));
+        assert!(parsed.plan);
+
+        let settings = HeadlessArgs {
+            settings: Some(r#"{"autoApproveEdits": false, "agent": "reviewer"}"#.to_string()),
+            auto_approve: true,
+            ..parsed
+        }
+        .task_settings()
+        .unwrap();
+        assert_eq!(
+            settings.models.unwrap().coding.as_deref(),
+            Some("gpt-4o@openai")
+        );
+        assert_eq!(settings.agent.as_deref(), Some("reviewer"));
+        assert_eq!(settings.auto_approve_edits, Some(true));
+        assert_eq!(settings.plan_mode, Some(true));
+        assert_eq!(settings.extra["maxTokens"], 4000);
+
+        assert_eq!(HeadlessArgs::parse(args(&["-"])).unwrap().prompt, None);
+        assert!(HeadlessArgs::parse(args(&["--model"])).is_err());
+        assert!(HeadlessArgs::parse(args(&["--max-tokens", "many"])).is_err());
+        assert!(HeadlessArgs::parse(args(&["--verbose"])).is_err());
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(exit_code(Some(RuntimeTaskState::Completed)), EXIT_COMPLETED);
+        assert_eq!(exit_code(Some(RuntimeTaskState::Failed)), EXIT_FAILED);
+        assert_eq!(exit_code(Some(RuntimeTaskState::Cancelled)), EXIT_CANCELLED);
+        assert_eq!(exit_code(None), EXIT_FAILED);
+    }
+}
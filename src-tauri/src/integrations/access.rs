@@ -0,0 +1,324 @@
+//! Integration Access Control
+//!
+//! Who may use the agent through each integration. An integration's access
+//! list holds sender IDs (Telegram user IDs, Feishu open_ids, Slack user IDs)
+//! and chat IDs, and is stored in settings under
+//! `integrations.access.<integration_id>`. Messages from anyone not on it are
+//! dropped before they reach the runtime, so a new integration answers nobody
+//! until users are added. A user adds themselves from the chat with
+//! `/pair <code>`, redeeming a one-time code the owner created; codes are
+//! kept in memory only.
+
+use crate::integrations::commands::ChatCommand;
+use crate::integrations::types::*;
+use crate::security::pairing::{generate_code, normalize_code, PAIRING_TTL};
+use crate::storage::SettingsRepository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Settings key prefix of an integration's access list
+pub const ACCESS_KEY: &str = "integrations.access";
+
+/// Users and chats allowed to use an integration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessList {
+    /// Senders allowed in any chat
+    #[serde(default)]
+    pub allowed_users: Vec<String>,
+    /// Chats everyone in is allowed, such as a team's group
+    #[serde(default)]
+    pub allowed_chats: Vec<String>,
+}
+
+impl AccessList {
+    pub fn allows(&self, message: &IncomingMessage) -> bool {
+        self.allowed_users.contains(&message.sender_id)
+            || self.allowed_chats.contains(&message.chat_id)
+    }
+}
+
+/// What becomes of an incoming message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboundAccess {
+    /// Pass it on to the runtime
+    Allowed,
+    /// Its sender redeemed a pairing code and is now allowed; confirm that
+    /// to them instead of passing the message on
+    Paired,
+    /// Drop it without an answer
+    Denied,
+}
+
+/// A pairing code for an integration, waiting to be sent from a chat
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrationPairing {
+    pub code: String,
+    pub integration_id: IntegrationId,
+    pub expires_at: i64,
+}
+
+struct PendingPairing {
+    integration_id: IntegrationId,
+    expires: Instant,
+}
+
+/// Access lists of integrations, and the pairing codes adding to them
+#[derive(Clone)]
+pub struct AccessControl {
+    settings: SettingsRepository,
+    pending: Arc<Mutex<HashMap<String, PendingPairing>>>,
+}
+
+impl AccessControl {
+    pub fn new(settings: SettingsRepository) -> Self {
+        Self {
+            settings,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Access list of an integration; empty until users are added
+    pub async fn get(&self, integration_id: &str) -> Result<AccessList, String> {
+        self.settings
+            .get_setting_or_default(&access_key(integration_id), AccessList::default())
+            .await
+    }
+
+    /// Replace the access list of an integration
+    pub async fn set(&self, integration_id: &str, list: &AccessList) -> Result<(), String> {
+        let value = serde_json::to_value(list)
+            .map_err(|e| format!("Failed to serialize access list: {}", e))?;
+        self.settings
+            .set_setting(&access_key(integration_id), &value)
+            .await
+    }
+
+    /// Allow a user in any chat of an integration
+    pub async fn allow_user(&self, integration_id: &str, user_id: &str) -> Result<(), String> {
+        let mut list = self.get(integration_id).await?;
+        if !list.allowed_users.iter().any(|id| id == user_id) {
+            list.allowed_users.push(user_id.to_string());
+            self.set(integration_id, &list).await?;
+        }
+        Ok(())
+    }
+
+    /// Remove a user from the access list of an integration
+    pub async fn revoke_user(&self, integration_id: &str, user_id: &str) -> Result<(), String> {
+        let mut list = self.get(integration_id).await?;
+        let count = list.allowed_users.len();
+        list.allowed_users.retain(|id| id != user_id);
+        if list.allowed_users.len() != count {
+            self.set(integration_id, &list).await?;
+        }
+        Ok(())
+    }
+
+    /// Create a code that adds whoever sends it on the integration
+    pub fn create_pairing(&self, integration_id: &str) -> IntegrationPairing {
+        self.create_pairing_at(integration_id, Instant::now())
+    }
+
+    /// Decide what becomes of an incoming message, redeeming a pairing code
+    /// it carries from a sender not yet allowed
+    pub async fn check(&self, message: &IncomingMessage) -> Result<InboundAccess, String> {
+        self.check_at(message, Instant::now()).await
+    }
+
+    fn create_pairing_at(&self, integration_id: &str, now: Instant) -> IntegrationPairing {
+        let code = generate_code();
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, pairing| pairing.expires > now);
+        pending.insert(
+            code.clone(),
+            PendingPairing {
+                integration_id: integration_id.to_string(),
+                expires: now + PAIRING_TTL,
+            },
+        );
+
+        IntegrationPairing {
+            code,
+            integration_id: integration_id.to_string(),
+            expires_at: chrono::Utc::now().timestamp() + PAIRING_TTL.as_secs() as i64,
+        }
+    }
+
+    async fn check_at(
+        &self,
+        message: &IncomingMessage,
+        now: Instant,
+    ) -> Result<InboundAccess, String> {
+        if self.get(&message.integration_id).await?.allows(message) {
+            return Ok(InboundAccess::Allowed);
+        }
+
+        if let Some(ChatCommand::Pair { code: Some(code) }) = ChatCommand::from_message(message) {
+            if self.redeem(&message.integration_id, &code, now) {
+                self.allow_user(&message.integration_id, &message.sender_id)
+                    .await?;
+                log::info!(
+                    "Paired {} with integration {}",
+                    message.sender_id,
+                    message.integration_id
+                );
+                return Ok(InboundAccess::Paired);
+            }
+        }
+
+        log::debug!(
+            "Dropped message from {} in chat {} of integration {}: not on its access list",
+            message.sender_id,
+            message.chat_id,
+            message.integration_id
+        );
+        Ok(InboundAccess::Denied)
+    }
+
+    /// Consume a code if it was created for the integration and is unexpired
+    fn redeem(&self, integration_id: &str, code: &str, now: Instant) -> bool {
+        let code = normalize_code(code);
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, pairing| pairing.expires > now);
+        let valid = pending
+            .get(&code)
+            .is_some_and(|pairing| pairing.integration_id == integration_id);
+        if valid {
+            pending.remove(&code);
+        }
+        valid
+    }
+}
+
+fn access_key(integration_id: &str) -> String {
+    format!("{}.{}", ACCESS_KEY, integration_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::storage::migrations::{settings_migrations, MigrationRunner};
+    use tempfile::TempDir;
+
+    fn incoming(
+        integration_id: &str,
+        sender_id: &str,
+        chat_id: &str,
+        content: &str,
+    ) -> IncomingMessage {
+        IncomingMessage {
+            integration_id: integration_id.to_string(),
+            channel_type: ChannelType::Telegram,
+            sender_id: sender_id.to_string(),
+            sender_name: None,
+            chat_id: chat_id.to_string(),
+            message_id: "1".to_string(),
+            content: content.to_string(),
+            timestamp: 0,
+            reply_to: None,
+            media: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_access_lists_and_pairing() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("settings.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect().await.unwrap();
+        let registry = settings_migrations();
+        MigrationRunner::new(&db, &registry)
+            .migrate()
+            .await
+            .unwrap();
+        let access = AccessControl::new(SettingsRepository::new(db));
+        let start = Instant::now();
+
+        // Nobody is allowed on a new integration
+        let prompt = incoming("telegram", "1001", "chat-1", "fix the tests");
+        assert_eq!(
+            access.check_at(&prompt, start).await.unwrap(),
+            InboundAccess::Denied
+        );
+
+        let expired = access.create_pairing_at("telegram", start);
+        let now = start + PAIRING_TTL;
+        let pairing = access.create_pairing_at("telegram", now);
+        let pair = |integration_id, code: &str| {
+            incoming(
+                integration_id,
+                "1001",
+                "chat-1",
+                &format!("/pair {}", code.to_lowercase()),
+            )
+        };
+        // Codes only pair on their integration, and only before they expire
+        assert_eq!(
+            access
+                .check_at(&pair("feishu", &pairing.code), now)
+                .await
+                .unwrap(),
+            InboundAccess::Denied
+        );
+        assert_eq!(
+            access
+                .check_at(&pair("telegram", &expired.code), now)
+                .await
+                .unwrap(),
+            InboundAccess::Denied
+        );
+        assert_eq!(
+            access
+                .check_at(&pair("telegram", &pairing.code), now)
+                .await
+                .unwrap(),
+            InboundAccess::Paired
+        );
+        assert_eq!(
+            access.check_at(&prompt, now).await.unwrap(),
+            InboundAccess::Allowed
+        );
+        assert_eq!(
+            access.get("telegram").await.unwrap().allowed_users,
+            vec!["1001".to_string()]
+        );
+
+        // A code is redeemed once
+        let other = incoming(
+            "telegram",
+            "1002",
+            "chat-2",
+            &format!("/pair {}", pairing.code),
+        );
+        assert_eq!(
+            access.check_at(&other, now).await.unwrap(),
+            InboundAccess::Denied
+        );
+
+        // Everyone in an allowed chat is allowed
+        access
+            .set(
+                "telegram",
+                &AccessList {
+                    allowed_users: Vec::new(),
+                    allowed_chats: vec!["chat-2".to_string()],
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            access.check_at(&other, now).await.unwrap(),
+            InboundAccess::Allowed
+        );
+        assert_eq!(
+            access.check_at(&prompt, now).await.unwrap(),
+            InboundAccess::Denied
+        );
+    }
+}
@@ -0,0 +1,41 @@
+//! Tauri commands for who may use the agent through each integration
+
+use tauri::{AppHandle, Manager};
+
+use crate::integrations::access::{AccessList, IntegrationPairing};
+use crate::integrations::router::ChatRouting;
+
+fn routing(app: &AppHandle) -> Result<ChatRouting, String> {
+    app.try_state::<ChatRouting>()
+        .map(|state| state.inner().clone())
+        .ok_or_else(|| "Core runtime is not ready".to_string())
+}
+
+/// Create a one-time code a chat sends with `/pair <code>` to be allowed to
+/// use the agent through the integration
+#[tauri::command]
+pub async fn create_integration_pairing(
+    app: AppHandle,
+    integration_id: String,
+) -> Result<IntegrationPairing, String> {
+    Ok(routing(&app)?.access().create_pairing(&integration_id))
+}
+
+/// Users and chats allowed to use the agent through an integration
+#[tauri::command]
+pub async fn get_integration_access(
+    app: AppHandle,
+    integration_id: String,
+) -> Result<AccessList, String> {
+    routing(&app)?.access().get(&integration_id).await
+}
+
+/// Replace who may use the agent through an integration
+#[tauri::command]
+pub async fn set_integration_access(
+    app: AppHandle,
+    integration_id: String,
+    access: AccessList,
+) -> Result<(), String> {
+    routing(&app)?.access().set(&integration_id, &access).await
+}
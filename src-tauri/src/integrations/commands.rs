@@ -0,0 +1,184 @@
+//! Chat Commands
+//!
+//! Commands users type in a chat to manage sessions and tasks instead of
+//! prompting the agent. Slack, Discord and Matrix clients handle `/` commands
+//! themselves, so commands there start with `!`; `/` is accepted everywhere.
+
+use crate::integrations::types::*;
+
+/// A command typed in a chat
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatCommand {
+    /// Start a new session, with its first prompt if given
+    New {
+        prompt: Option<String>,
+    },
+    /// Show the state of the chat's session and task
+    Status,
+    /// Cancel the running task
+    Cancel,
+    /// Approve a pending tool call; without an ID, the only one pending
+    Approve {
+        tool_call_id: Option<String>,
+    },
+    /// Deny a pending tool call; without an ID, the only one pending
+    Deny {
+        tool_call_id: Option<String>,
+    },
+    /// Get access to the agent with a pairing code
+    Pair {
+        code: Option<String>,
+    },
+    Help,
+    /// A command that is not known
+    Unknown(String),
+}
+
+/// Usage and description of each command, in the order help lists them
+const COMMANDS: [(&str, &str); 7] = [
+    ("new [prompt]", "Start a new session"),
+    ("status", "Show the session and its task"),
+    ("cancel", "Cancel the running task"),
+    ("approve [id]", "Approve the pending tool call"),
+    ("deny [id]", "Deny the pending tool call"),
+    ("pair <code>", "Get access with a pairing code"),
+    ("help", "Show this help"),
+];
+
+impl ChatCommand {
+    /// Parse a message as a command; `None` if it is a prompt
+    pub fn parse(channel: ChannelType, text: &str) -> Option<Self> {
+        let mut text = text.trim_start();
+        // Feishu puts mentions of the bot in front as `@_user_1`
+        while let Some(mention) = text.strip_prefix("@_user_") {
+            text = mention
+                .trim_start_matches(|c: char| !c.is_whitespace())
+                .trim_start();
+        }
+        let rest = text
+            .strip_prefix('/')
+            .or_else(|| text.strip_prefix(command_prefix(channel)))?;
+        let (word, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        // Telegram addresses commands in groups as `/status@BotName`
+        let name = word.split('@').next().unwrap_or_default().to_lowercase();
+        // A prompt may start with a path such as `/src/main.rs`
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return None;
+        }
+        let arg = Some(args.trim().to_string()).filter(|arg| !arg.is_empty());
+
+        Some(match name.as_str() {
+            "new" => ChatCommand::New { prompt: arg },
+            "status" => ChatCommand::Status,
+            "cancel" | "stop" => ChatCommand::Cancel,
+            "approve" => ChatCommand::Approve { tool_call_id: arg },
+            "deny" | "reject" => ChatCommand::Deny { tool_call_id: arg },
+            "pair" => ChatCommand::Pair { code: arg },
+            "help" | "start" => ChatCommand::Help,
+            _ => ChatCommand::Unknown(name),
+        })
+    }
+
+    /// Parse an incoming message as a command of its channel
+    pub fn from_message(message: &IncomingMessage) -> Option<Self> {
+        Self::parse(message.channel_type, &message.content)
+    }
+}
+
+/// Prefix commands are shown with on a channel
+pub fn command_prefix(channel: ChannelType) -> char {
+    match channel {
+        ChannelType::Slack | ChannelType::Discord | ChannelType::Matrix => '!',
+        _ => '/',
+    }
+}
+
+/// List of the commands, formatted for a channel
+pub fn help(channel: ChannelType) -> String {
+    let prefix = command_prefix(channel);
+    let markdown = matches!(
+        channel,
+        ChannelType::Slack | ChannelType::Discord | ChannelType::Matrix
+    );
+    let lines: Vec<String> = COMMANDS
+        .iter()
+        .map(|(usage, description)| {
+            if markdown {
+                format!("`{}{}` {}", prefix, usage, description)
+            } else {
+                format!("{}{} - {}", prefix, usage, description)
+            }
+        })
+        .collect();
+    format!(
+        "Send a message to prompt the agent; reply to a message to continue its session.\n\n{}",
+        lines.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        let parse = |text| ChatCommand::parse(ChannelType::Telegram, text);
+
+        assert_eq!(parse("fix the tests"), None);
+        assert_eq!(parse("/"), None);
+        assert_eq!(parse("/src/main.rs does not build"), None);
+        assert_eq!(parse("/status"), Some(ChatCommand::Status));
+        assert_eq!(parse("/Status@TalkCodyBot"), Some(ChatCommand::Status));
+        assert_eq!(parse("/stop"), Some(ChatCommand::Cancel));
+        assert_eq!(
+            parse("/new  fix the tests "),
+            Some(ChatCommand::New {
+                prompt: Some("fix the tests".to_string())
+            })
+        );
+        assert_eq!(
+            parse("/new\nfix the tests\nthen lint"),
+            Some(ChatCommand::New {
+                prompt: Some("fix the tests\nthen lint".to_string())
+            })
+        );
+        assert_eq!(parse("/new"), Some(ChatCommand::New { prompt: None }));
+        assert_eq!(
+            parse("/approve call-1"),
+            Some(ChatCommand::Approve {
+                tool_call_id: Some("call-1".to_string())
+            })
+        );
+        assert_eq!(
+            parse("/reject"),
+            Some(ChatCommand::Deny { tool_call_id: None })
+        );
+        assert_eq!(
+            parse("/pair abcd-2345"),
+            Some(ChatCommand::Pair {
+                code: Some("abcd-2345".to_string())
+            })
+        );
+        assert_eq!(
+            parse("/deploy prod"),
+            Some(ChatCommand::Unknown("deploy".to_string()))
+        );
+        // `!` is only a command prefix where `/` is taken by the client
+        assert_eq!(parse("!status"), None);
+
+        assert_eq!(
+            ChatCommand::parse(ChannelType::Feishu, "@_user_1 /cancel"),
+            Some(ChatCommand::Cancel)
+        );
+        assert_eq!(
+            ChatCommand::parse(ChannelType::Slack, "!help"),
+            Some(ChatCommand::Help)
+        );
+    }
+
+    #[test]
+    fn test_help_per_channel() {
+        assert!(help(ChannelType::Telegram).contains("\n/new [prompt] - Start a new session"));
+        assert!(help(ChannelType::Slack).contains("\n`!cancel` Cancel the running task"));
+    }
+}
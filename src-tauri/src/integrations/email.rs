@@ -0,0 +1,410 @@
+//! Email Integration Adapter
+//!
+//! Sends task summaries and approval requests over SMTP, as plain markdown
+//! with an HTML rendering alongside. When an IMAP inbox is configured, each
+//! email flagged there becomes an incoming message that starts a session;
+//! the flag is cleared so it does so once.
+
+use crate::integrations::markdown;
+use crate::integrations::types::*;
+use async_imap::types::Fetch;
+use futures_util::TryStreamExt;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use mail_parser::MessageParser;
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+
+const DEFAULT_SMTP_PORT: u16 = 587;
+/// Port of SMTP over implicit TLS; other ports upgrade with STARTTLS
+const SMTPS_PORT: u16 = 465;
+const MAX_SUBJECT_CHARS: usize = 78;
+
+/// Email adapter configuration
+#[derive(Debug, Clone)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    /// Login for both SMTP and IMAP
+    pub username: String,
+    pub password: String,
+    /// Address mail is sent from, e.g. `TalkCody <bot@example.com>`
+    pub from_address: String,
+    /// Inbox polled for flagged emails; polling is off without one
+    pub imap: Option<ImapConfig>,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            smtp_host: String::new(),
+            smtp_port: DEFAULT_SMTP_PORT,
+            username: String::new(),
+            password: String::new(),
+            from_address: String::new(),
+            imap: None,
+        }
+    }
+}
+
+/// IMAP inbox polled for flagged emails
+#[derive(Debug, Clone)]
+pub struct ImapConfig {
+    pub host: String,
+    /// Port of IMAP over TLS, usually 993
+    pub port: u16,
+    pub mailbox: String,
+}
+
+/// Email integration adapter
+pub struct EmailAdapter {
+    id: IntegrationId,
+    config: EmailConfig,
+    connected: RwLock<bool>,
+}
+
+impl EmailAdapter {
+    pub fn new(id: impl Into<IntegrationId>, config: EmailConfig) -> Self {
+        Self {
+            id: id.into(),
+            config,
+            connected: RwLock::new(false),
+        }
+    }
+
+    /// Send markdown `content` with a subject of its own, as a reply to the
+    /// email `in_reply_to` when given. Returns the Message-ID of the email.
+    pub async fn send_email(
+        &self,
+        recipient: &str,
+        subject: &str,
+        content: &str,
+        in_reply_to: Option<&str>,
+    ) -> Result<MessageId, String> {
+        let (message_id, email) = self.build_email(recipient, subject, content, in_reply_to)?;
+        self.transport()?
+            .send(email)
+            .await
+            .map_err(|e| format!("Failed to send email: {}", e))?;
+        Ok(message_id)
+    }
+
+    /// Turn the emails flagged in the inbox into incoming messages, clearing
+    /// their flags
+    pub async fn poll_flagged(&self) -> Result<Vec<IncomingMessage>, String> {
+        let Some(imap) = &self.config.imap else {
+            return Ok(Vec::new());
+        };
+
+        let tcp = TcpStream::connect((imap.host.as_str(), imap.port))
+            .await
+            .map_err(|e| format!("Failed to connect to IMAP server: {}", e))?;
+        let tls = async_native_tls::TlsConnector::new()
+            .connect(imap.host.as_str(), tcp)
+            .await
+            .map_err(|e| format!("Failed to connect to IMAP server: {}", e))?;
+        let mut session = async_imap::Client::new(tls)
+            .login(&self.config.username, &self.config.password)
+            .await
+            .map_err(|(e, _)| format!("IMAP login failed: {}", e))?;
+
+        let fetched = async {
+            session
+                .select(&imap.mailbox)
+                .await
+                .map_err(|e| format!("Failed to open mailbox {}: {}", imap.mailbox, e))?;
+            let mut uids: Vec<u32> = session
+                .uid_search("FLAGGED")
+                .await
+                .map_err(|e| format!("IMAP search failed: {}", e))?
+                .into_iter()
+                .collect();
+            if uids.is_empty() {
+                return Ok(Vec::new());
+            }
+            uids.sort_unstable();
+            let uid_set = uids
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+
+            let fetches: Vec<Fetch> = session
+                .uid_fetch(&uid_set, "(UID BODY.PEEK[])")
+                .await
+                .map_err(|e| format!("IMAP fetch failed: {}", e))?
+                .try_collect()
+                .await
+                .map_err(|e| format!("IMAP fetch failed: {}", e))?;
+            session
+                .uid_store(&uid_set, "-FLAGS.SILENT (\\Flagged)")
+                .await
+                .map_err(|e| format!("Failed to clear IMAP flags: {}", e))?
+                .try_collect::<Vec<_>>()
+                .await
+                .map_err(|e| format!("Failed to clear IMAP flags: {}", e))?;
+            Ok::<_, String>(fetches)
+        }
+        .await;
+        if let Err(e) = session.logout().await {
+            log::debug!("IMAP logout failed: {}", e);
+        }
+
+        Ok(fetched?
+            .iter()
+            .filter_map(Fetch::body)
+            .filter_map(|raw| parse_email(&self.id, raw))
+            .collect())
+    }
+
+    fn build_email(
+        &self,
+        recipient: &str,
+        subject: &str,
+        content: &str,
+        in_reply_to: Option<&str>,
+    ) -> Result<(MessageId, Message), String> {
+        let from: Mailbox = self
+            .config
+            .from_address
+            .parse()
+            .map_err(|e| format!("Invalid sender address: {}", e))?;
+        let to: Mailbox = recipient
+            .parse()
+            .map_err(|e| format!("Invalid recipient address '{}': {}", recipient, e))?;
+        let message_id = format!("{}@{}", uuid::Uuid::new_v4(), from.email.domain());
+
+        let mut builder = Message::builder()
+            .from(from)
+            .to(to)
+            .subject(subject)
+            .message_id(Some(format!("<{}>", message_id)));
+        if let Some(in_reply_to) = in_reply_to {
+            builder = builder
+                .in_reply_to(format!("<{}>", in_reply_to))
+                .references(format!("<{}>", in_reply_to));
+        }
+        let html = format!(
+            "<!DOCTYPE html><html><body>{}</body></html>",
+            markdown::to_html(content)
+        );
+        let email = builder
+            .multipart(MultiPart::alternative_plain_html(content.to_string(), html))
+            .map_err(|e| format!("Failed to build email: {}", e))?;
+        Ok((message_id, email))
+    }
+
+    fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, String> {
+        let builder = if self.config.smtp_port == SMTPS_PORT {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&self.config.smtp_host)
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.config.smtp_host)
+        }
+        .map_err(|e| format!("Invalid SMTP server: {}", e))?;
+        Ok(builder
+            .port(self.config.smtp_port)
+            .credentials(Credentials::new(
+                self.config.username.clone(),
+                self.config.password.clone(),
+            ))
+            .build())
+    }
+}
+
+/// Subject of an email carrying `content`: its first line without heading
+/// marks, cut to a line's length
+fn subject_for(content: &str) -> String {
+    let line = content
+        .lines()
+        .map(|line| line.trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or("TalkCody");
+    if line.chars().count() <= MAX_SUBJECT_CHARS {
+        return line.to_string();
+    }
+    let cut: String = line.chars().take(MAX_SUBJECT_CHARS - 3).collect();
+    format!("{}...", cut.trim_end())
+}
+
+/// An email as an incoming message: the subject heads the content, and
+/// Message-IDs stand in for message IDs
+fn parse_email(integration_id: &IntegrationId, raw: &[u8]) -> Option<IncomingMessage> {
+    let email = MessageParser::default().parse(raw)?;
+    let sender = email.from()?.first()?;
+    let sender_id = sender.address()?.to_string();
+    let subject = email.subject().unwrap_or_default().trim();
+    let body = email
+        .body_text(0)
+        .map(|body| body.trim().to_string())
+        .unwrap_or_default();
+    let content = match (subject.is_empty(), body.is_empty()) {
+        (false, false) => format!("{}\n\n{}", subject, body),
+        (false, true) => subject.to_string(),
+        _ => body,
+    };
+
+    Some(IncomingMessage {
+        integration_id: integration_id.clone(),
+        channel_type: ChannelType::Email,
+        sender_name: sender.name().map(str::to_string),
+        chat_id: sender_id.clone(),
+        sender_id,
+        message_id: email.message_id()?.to_string(),
+        content,
+        timestamp: email
+            .date()
+            .map(|date| date.to_timestamp() * 1000)
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis()),
+        reply_to: email.in_reply_to().as_text().map(str::to_string),
+        media: Vec::new(),
+    })
+}
+
+#[async_trait::async_trait]
+impl IntegrationAdapter for EmailAdapter {
+    fn id(&self) -> &IntegrationId {
+        &self.id
+    }
+
+    fn channel_type(&self) -> ChannelType {
+        ChannelType::Email
+    }
+
+    async fn start(&self) -> Result<(), String> {
+        if self.config.smtp_host.is_empty() || self.config.from_address.is_empty() {
+            return Err("Email needs an SMTP server and sender address".to_string());
+        }
+        let reachable = self
+            .transport()?
+            .test_connection()
+            .await
+            .map_err(|e| format!("Failed to reach SMTP server: {}", e))?;
+        if !reachable {
+            return Err(format!(
+                "SMTP server {} did not respond",
+                self.config.smtp_host
+            ));
+        }
+
+        let mut connected = self.connected.write().await;
+        *connected = true;
+        Ok(())
+    }
+
+    async fn stop(&self) -> Result<(), String> {
+        let mut connected = self.connected.write().await;
+        *connected = false;
+        Ok(())
+    }
+
+    async fn send_message(&self, recipient: &str, content: &str) -> Result<MessageId, String> {
+        self.send_email(recipient, &subject_for(content), content, None)
+            .await
+    }
+
+    async fn edit_message(
+        &self,
+        _recipient: &str,
+        _message_id: &str,
+        _new_content: &str,
+    ) -> Result<(), String> {
+        Err("Sent emails cannot be edited".to_string())
+    }
+
+    async fn is_connected(&self) -> bool {
+        *self.connected.read().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> EmailConfig {
+        EmailConfig {
+            smtp_host: "smtp.example.com".to_string(),
+            username: "bot@example.com".to_string(),
+            password: "secret".to_string(),
+            from_address: "TalkCody <bot@example.com>".to_string(),
+            ..EmailConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_email_adapter_creation() {
+        let adapter = EmailAdapter::new("email-1", config());
+        assert_eq!(adapter.id(), "email-1");
+        assert_eq!(adapter.channel_type(), ChannelType::Email);
+        assert!(!adapter.is_connected().await);
+
+        let unconfigured = EmailAdapter::new("email-2", EmailConfig::default());
+        assert!(unconfigured.start().await.is_err());
+        // Without an inbox there is nothing to poll
+        assert!(unconfigured.poll_flagged().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_subject_for() {
+        assert_eq!(
+            subject_for("## Task finished\n\nAll tests pass"),
+            "Task finished"
+        );
+        assert_eq!(subject_for("\n\n"), "TalkCody");
+        let long = subject_for(&"word ".repeat(40));
+        assert!(long.ends_with("..."));
+        assert!(long.chars().count() <= MAX_SUBJECT_CHARS);
+    }
+
+    #[test]
+    fn test_build_email() {
+        let adapter = EmailAdapter::new("email-1", config());
+        let (message_id, email) = adapter
+            .build_email(
+                "ada@example.com",
+                "Approve edit?",
+                "Edit **src/main.rs**?",
+                Some("previous@example.com"),
+            )
+            .unwrap();
+        assert!(message_id.ends_with("@example.com"));
+
+        let raw = String::from_utf8(email.formatted()).unwrap();
+        assert!(raw.contains(&format!("Message-ID: <{}>", message_id)));
+        assert!(raw.contains("In-Reply-To: <previous@example.com>"));
+        assert!(raw.contains("Subject: Approve edit?"));
+        assert!(raw.contains("Content-Type: text/plain"));
+        assert!(raw.contains("<strong>src/main.rs</strong>"));
+
+        assert!(adapter
+            .build_email("not an address", "Subject", "Body", None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_email() {
+        let raw = concat!(
+            "From: Ada Lovelace <ada@example.com>\r\n",
+            "To: bot@example.com\r\n",
+            "Subject: Fix the login bug\r\n",
+            "Message-ID: <abc123@example.com>\r\n",
+            "In-Reply-To: <previous@example.com>\r\n",
+            "Date: Tue, 14 Nov 2023 22:13:20 +0000\r\n",
+            "Content-Type: text/plain; charset=utf-8\r\n",
+            "\r\n",
+            "Users cannot log in with SSO.\r\n",
+        );
+
+        let message = parse_email(&"email-1".to_string(), raw.as_bytes()).unwrap();
+        assert_eq!(message.sender_id, "ada@example.com");
+        assert_eq!(message.sender_name.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(message.chat_id, "ada@example.com");
+        assert_eq!(message.message_id, "abc123@example.com");
+        assert_eq!(message.reply_to.as_deref(), Some("previous@example.com"));
+        assert_eq!(
+            message.content,
+            "Fix the login bug\n\nUsers cannot log in with SSO."
+        );
+        assert_eq!(message.timestamp, 1_700_000_000_000);
+    }
+}
@@ -1,24 +1,63 @@
 //! Feishu Integration Adapter
 //!
-//! Wraps existing feishu_gateway.rs for cloud backend integration.
+//! Wraps existing feishu_gateway.rs for cloud backend integration, and
+//! answers chats through the Open API as the app's bot.
 
 use crate::integrations::types::*;
-use std::sync::Arc;
+use crate::integrations::verification::{
+    decrypt_feishu_event, verify_feishu_signature, ReplayGuard,
+};
+use crate::security::api_keys::constant_time_eq;
+use axum::http::HeaderMap;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
+/// Open API server; Lark apps use `https://open.larksuite.com`
+const FEISHU_API_BASE: &str = "https://open.feishu.cn";
+
+/// Time before a tenant access token expires that a new one is fetched
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// How long event IDs are remembered. Feishu sends an event it got no
+/// answer to again 15 seconds, 5 minutes, 1 hour and 6 hours later.
+const EVENT_RETRY_WINDOW_SECS: i64 = 7 * 60 * 60;
+
 /// Feishu adapter configuration
 #[derive(Debug, Clone)]
 pub struct FeishuConfig {
     pub app_id: String,
     pub app_secret: String,
     pub webhook_url: Option<String>,
+    /// Token Feishu puts in each event; checked when set
+    pub verification_token: Option<String>,
+    /// Key events are encrypted and signed with; when set, events that are
+    /// not are refused. Webhooks need this or the verification token.
+    pub encrypt_key: Option<String>,
+}
+
+/// What a Feishu webhook delivery asks for
+#[derive(Debug, Clone)]
+pub enum FeishuWebhook {
+    /// Request URL verification, answered with `{"challenge": ...}`
+    Challenge(String),
+    /// Event delivery; events other than received messages yield none
+    Messages(Vec<IncomingMessage>),
 }
 
 /// Feishu integration adapter
 pub struct FeishuAdapter {
     id: IntegrationId,
     config: FeishuConfig,
+    client: Client,
+    api_base: String,
+    /// Tenant access token, and when it stops being used
+    tenant_token: RwLock<Option<(String, Instant)>>,
     connected: RwLock<bool>,
+    replay_guard: ReplayGuard,
 }
 
 impl FeishuAdapter {
@@ -26,10 +65,20 @@ impl FeishuAdapter {
         Self {
             id: id.into(),
             config,
+            client: Client::new(),
+            api_base: FEISHU_API_BASE.to_string(),
+            tenant_token: RwLock::new(None),
             connected: RwLock::new(false),
+            replay_guard: ReplayGuard::default().remembering(EVENT_RETRY_WINDOW_SECS),
         }
     }
 
+    /// Send Open API calls to another server, such as Lark's
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into();
+        self
+    }
+
     /// Create adapter from existing gateway state
     pub fn from_gateway(id: impl Into<IntegrationId>) -> Self {
         Self {
@@ -38,10 +87,269 @@ impl FeishuAdapter {
                 app_id: String::new(),
                 app_secret: String::new(),
                 webhook_url: None,
+                verification_token: None,
+                encrypt_key: None,
             },
+            client: Client::new(),
+            api_base: FEISHU_API_BASE.to_string(),
+            tenant_token: RwLock::new(None),
             connected: RwLock::new(false),
+            replay_guard: ReplayGuard::default().remembering(EVENT_RETRY_WINDOW_SECS),
+        }
+    }
+
+    /// Verify a webhook delivery, decrypting it if an encrypt key is set.
+    /// Events already received are refused, as are signed deliveries that
+    /// are stale.
+    pub fn receive_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &str,
+    ) -> Result<FeishuWebhook, String> {
+        let verification_token = non_empty(&self.config.verification_token);
+        let encrypt_key = non_empty(&self.config.encrypt_key);
+
+        // Time the delivery was signed at, in seconds
+        let (envelope, signed_at): (FeishuEnvelope, Option<i64>) = match encrypt_key {
+            Some(encrypt_key) => {
+                let encrypted: FeishuEncrypted = serde_json::from_str(body)
+                    .map_err(|_| "Feishu webhook is not encrypted".to_string())?;
+                let envelope: FeishuEnvelope =
+                    parse_envelope(&decrypt_feishu_event(encrypt_key, &encrypted.encrypt)?)?;
+                // URL verification requests are encrypted but not signed
+                if envelope.kind.as_deref() == Some("url_verification") {
+                    (envelope, None)
+                } else {
+                    let (signed_at, _) = verify_feishu_signature(headers, encrypt_key, body)?;
+                    (envelope, Some(signed_at))
+                }
+            }
+            None if verification_token.is_some() => (parse_envelope(body)?, None),
+            None => {
+                return Err("Feishu webhooks need a verification token or encrypt key".to_string())
+            }
+        };
+
+        let token = envelope
+            .header
+            .as_ref()
+            .map(|header| header.token.as_str())
+            .or(envelope.token.as_deref())
+            .unwrap_or("");
+        if let Some(expected) = verification_token {
+            if !constant_time_eq(token.as_bytes(), expected.as_bytes()) {
+                return Err("Feishu webhook verification token does not match".to_string());
+            }
         }
+
+        if envelope.kind.as_deref() == Some("url_verification") {
+            return envelope
+                .challenge
+                .map(FeishuWebhook::Challenge)
+                .ok_or_else(|| "Feishu URL verification has no challenge".to_string());
+        }
+
+        let header = envelope
+            .header
+            .ok_or_else(|| "Feishu webhook is not a 2.0 event".to_string())?;
+        // A retry keeps the event's create_time, but is signed anew
+        match signed_at {
+            Some(signed_at) => self.replay_guard.check(&header.event_id, signed_at)?,
+            None => self.replay_guard.check_once(&header.event_id)?,
+        }
+        let created_at = header.create_time.parse::<i64>().unwrap_or_default();
+        if header.event_type != "im.message.receive_v1" {
+            return Ok(FeishuWebhook::Messages(Vec::new()));
+        }
+
+        let event: FeishuMessageEvent = envelope
+            .event
+            .ok_or_else(|| "Feishu message event has no event".to_string())
+            .and_then(|event| {
+                serde_json::from_value(event)
+                    .map_err(|e| format!("Invalid Feishu message event: {}", e))
+            })?;
+        let message = event.message;
+        // Only text is acted on; its content is JSON of its own
+        let content = (message.message_type == "text")
+            .then(|| serde_json::from_str::<FeishuText>(&message.content).ok())
+            .flatten()
+            .map(|text| text.text);
+        let Some(content) = content else {
+            return Ok(FeishuWebhook::Messages(Vec::new()));
+        };
+
+        Ok(FeishuWebhook::Messages(vec![IncomingMessage {
+            integration_id: self.id.clone(),
+            channel_type: ChannelType::Feishu,
+            sender_id: event.sender.sender_id.open_id,
+            sender_name: None,
+            chat_id: message.chat_id,
+            message_id: message.message_id,
+            content,
+            timestamp: message.create_time.parse::<i64>().unwrap_or(created_at),
+            reply_to: message.parent_id.filter(|id| !id.is_empty()),
+            media: Vec::new(),
+        }]))
     }
+
+    fn api_url(&self, path: &str) -> String {
+        format!("{}/open-apis/{}", self.api_base.trim_end_matches('/'), path)
+    }
+
+    /// Tenant access token the bot calls the API with, fetched again
+    /// shortly before it expires
+    async fn tenant_access_token(&self) -> Result<String, String> {
+        if let Some((token, refresh_at)) = self.tenant_token.read().await.as_ref() {
+            if Instant::now() < *refresh_at {
+                return Ok(token.clone());
+            }
+        }
+        if self.config.app_id.is_empty() || self.config.app_secret.is_empty() {
+            return Err("Feishu app_id or app_secret is not configured".to_string());
+        }
+
+        let response = self
+            .client
+            .post(self.api_url("auth/v3/tenant_access_token/internal"))
+            .json(&json!({
+                "app_id": self.config.app_id,
+                "app_secret": self.config.app_secret,
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to get Feishu tenant access token: {}", e))?;
+        let token: TenantAccessToken = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Feishu token response: {}", e))?;
+        if token.code != 0 {
+            return Err(format!("Feishu API error ({}): {}", token.code, token.msg));
+        }
+        let refresh_at = Instant::now()
+            + Duration::from_secs(token.expire.max(0) as u64).saturating_sub(TOKEN_REFRESH_MARGIN);
+        *self.tenant_token.write().await = Some((token.tenant_access_token.clone(), refresh_at));
+        Ok(token.tenant_access_token)
+    }
+}
+
+async fn read_response<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, String> {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    let payload: FeishuResponse<T> = serde_json::from_str(&body)
+        .map_err(|_| format!("Feishu API error ({}): {}", status, body))?;
+    if payload.code != 0 {
+        return Err(format!(
+            "Feishu API error ({}): {}",
+            payload.code, payload.msg
+        ));
+    }
+    payload
+        .data
+        .ok_or_else(|| "Feishu API returned no data".to_string())
+}
+
+/// ID type of a recipient: user open_ids start with `ou_`, chat IDs with `oc_`
+fn receive_id_type(recipient: &str) -> &'static str {
+    if recipient.starts_with("ou_") {
+        "open_id"
+    } else {
+        "chat_id"
+    }
+}
+
+/// Content of a text message, which Feishu takes as a JSON string
+fn text_content(text: &str) -> String {
+    json!({ "text": text }).to_string()
+}
+
+#[derive(Deserialize)]
+struct TenantAccessToken {
+    code: i64,
+    #[serde(default)]
+    msg: String,
+    #[serde(default)]
+    tenant_access_token: String,
+    /// Seconds
+    #[serde(default)]
+    expire: i64,
+}
+
+#[derive(Deserialize)]
+struct FeishuResponse<T> {
+    code: i64,
+    #[serde(default)]
+    msg: String,
+    data: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct SentMessage {
+    message_id: String,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|value| !value.is_empty())
+}
+
+fn parse_envelope(json: &str) -> Result<FeishuEnvelope, String> {
+    serde_json::from_str(json).map_err(|e| format!("Invalid Feishu webhook: {}", e))
+}
+
+#[derive(Deserialize)]
+struct FeishuEncrypted {
+    encrypt: String,
+}
+
+/// A URL verification request, or a 2.0 event with its header
+#[derive(Deserialize)]
+struct FeishuEnvelope {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    token: Option<String>,
+    challenge: Option<String>,
+    header: Option<FeishuEventHeader>,
+    event: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct FeishuEventHeader {
+    event_id: String,
+    event_type: String,
+    /// Milliseconds
+    create_time: String,
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct FeishuMessageEvent {
+    sender: FeishuSender,
+    message: FeishuMessage,
+}
+
+#[derive(Deserialize)]
+struct FeishuSender {
+    sender_id: FeishuSenderId,
+}
+
+#[derive(Deserialize)]
+struct FeishuSenderId {
+    open_id: String,
+}
+
+#[derive(Deserialize)]
+struct FeishuMessage {
+    message_id: String,
+    parent_id: Option<String>,
+    chat_id: String,
+    message_type: String,
+    content: String,
+    create_time: String,
+}
+
+#[derive(Deserialize)]
+struct FeishuText {
+    text: String,
 }
 
 #[async_trait::async_trait]
@@ -72,11 +380,22 @@ impl IntegrationAdapter for FeishuAdapter {
     }
 
     async fn send_message(&self, recipient: &str, content: &str) -> Result<MessageId, String> {
-        // Use existing feishu_gateway functionality
-        // This is a placeholder - real implementation would call feishu_gateway
-
-        let message_id = format!("fs_msg_{}", uuid::Uuid::new_v4());
-        Ok(message_id)
+        let token = self.tenant_access_token().await?;
+        let response = self
+            .client
+            .post(self.api_url("im/v1/messages"))
+            .query(&[("receive_id_type", receive_id_type(recipient))])
+            .bearer_auth(token)
+            .json(&json!({
+                "receive_id": recipient,
+                "msg_type": "text",
+                "content": text_content(content),
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send Feishu message: {}", e))?;
+        let sent: SentMessage = read_response(response).await?;
+        Ok(sent.message_id)
     }
 
     async fn edit_message(
@@ -85,9 +404,19 @@ impl IntegrationAdapter for FeishuAdapter {
         message_id: &str,
         new_content: &str,
     ) -> Result<(), String> {
-        // Edit message via existing feishu_gateway
-        // Placeholder implementation
-        let _ = (message_id, new_content);
+        let token = self.tenant_access_token().await?;
+        let response = self
+            .client
+            .put(self.api_url(&format!("im/v1/messages/{}", message_id)))
+            .bearer_auth(token)
+            .json(&json!({
+                "msg_type": "text",
+                "content": text_content(new_content),
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to edit Feishu message: {}", e))?;
+        let _: Value = read_response(response).await?;
         Ok(())
     }
 
@@ -99,6 +428,13 @@ impl IntegrationAdapter for FeishuAdapter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::integrations::verification::feishu_signature;
+    use axum::extract::State;
+    use axum::http::{Method, Uri};
+    use axum::routing::{post, put};
+    use axum::Json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
 
     #[tokio::test]
     async fn test_feishu_adapter_creation() {
@@ -106,6 +442,8 @@ mod tests {
             app_id: "test_app_id".to_string(),
             app_secret: "test_secret".to_string(),
             webhook_url: None,
+            verification_token: None,
+            encrypt_key: None,
         };
 
         let adapter = FeishuAdapter::new("feishu-1", config);
@@ -120,6 +458,8 @@ mod tests {
             app_id: "test_app_id".to_string(),
             app_secret: "test_secret".to_string(),
             webhook_url: None,
+            verification_token: None,
+            encrypt_key: None,
         };
 
         let adapter = FeishuAdapter::new("feishu-1", config);
@@ -130,4 +470,235 @@ mod tests {
         adapter.stop().await.expect("Failed to stop");
         assert!(!adapter.is_connected().await);
     }
+
+    fn message_event(event_id: &str, token: &str, create_time: i64) -> String {
+        serde_json::json!({
+            "schema": "2.0",
+            "header": {
+                "event_id": event_id,
+                "event_type": "im.message.receive_v1",
+                "create_time": create_time.to_string(),
+                "token": token,
+            },
+            "event": {
+                "sender": {"sender_id": {"open_id": "ou_123"}},
+                "message": {
+                    "message_id": "om_1",
+                    "parent_id": "",
+                    "chat_id": "oc_1",
+                    "message_type": "text",
+                    "content": r#"{"text":"run the tests"}"#,
+                    "create_time": create_time.to_string(),
+                }
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_receive_webhook() {
+        let mut config = FeishuConfig {
+            app_id: "test_app_id".to_string(),
+            app_secret: "test_secret".to_string(),
+            webhook_url: None,
+            verification_token: None,
+            encrypt_key: None,
+        };
+        let now = chrono::Utc::now().timestamp_millis();
+        let headers = HeaderMap::new();
+
+        let adapter = FeishuAdapter::new("feishu-1", config.clone());
+        assert!(adapter
+            .receive_webhook(&headers, &message_event("evt-1", "tok", now))
+            .is_err());
+
+        config.verification_token = Some("tok".to_string());
+        let adapter = FeishuAdapter::new("feishu-1", config);
+        let challenge = r#"{"type":"url_verification","token":"tok","challenge":"abc"}"#;
+        assert!(matches!(
+            adapter.receive_webhook(&headers, challenge).unwrap(),
+            FeishuWebhook::Challenge(challenge) if challenge == "abc"
+        ));
+
+        let FeishuWebhook::Messages(messages) = adapter
+            .receive_webhook(&headers, &message_event("evt-1", "tok", now))
+            .unwrap()
+        else {
+            panic!("expected messages");
+        };
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].sender_id, "ou_123");
+        assert_eq!(messages[0].chat_id, "oc_1");
+        assert_eq!(messages[0].content, "run the tests");
+        assert_eq!(messages[0].reply_to, None);
+
+        // Replayed, or with the wrong token
+        assert!(adapter
+            .receive_webhook(&headers, &message_event("evt-1", "tok", now))
+            .is_err());
+        assert!(adapter
+            .receive_webhook(&headers, &message_event("evt-3", "guess", now))
+            .is_err());
+        // Retries keep create_time, so unsigned deliveries are not refused by it
+        assert!(adapter
+            .receive_webhook(&headers, &message_event("evt-2", "tok", now - 3_600_000))
+            .is_ok());
+    }
+
+    /// Encrypt an event as Feishu does
+    fn encrypt_event(encrypt_key: &str, plaintext: &str) -> String {
+        use aes::cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
+        use base64::Engine;
+        use sha2::{Digest, Sha256};
+
+        let key = Sha256::digest(encrypt_key.as_bytes());
+        let iv = [7u8; 16];
+        let mut buf = plaintext.as_bytes().to_vec();
+        buf.resize(plaintext.len() + 16, 0);
+        let ciphertext = cbc::Encryptor::<aes::Aes256>::new(key.as_slice().into(), (&iv).into())
+            .encrypt_padded_mut::<Pkcs7>(&mut buf, plaintext.len())
+            .unwrap();
+        base64::engine::general_purpose::STANDARD.encode([iv.as_slice(), ciphertext].concat())
+    }
+
+    #[test]
+    fn test_receive_signed_webhook() {
+        let config = FeishuConfig {
+            app_id: "test_app_id".to_string(),
+            app_secret: "test_secret".to_string(),
+            webhook_url: None,
+            verification_token: None,
+            encrypt_key: Some("key".to_string()),
+        };
+        let adapter = FeishuAdapter::new("feishu-1", config);
+        let now = chrono::Utc::now().timestamp();
+        // A retry of an event created an hour ago
+        let event = message_event("evt-1", "", (now - 3_600) * 1000);
+        let body = json!({"encrypt": encrypt_event("key", &event)}).to_string();
+        let signed = |timestamp: i64| {
+            let timestamp = timestamp.to_string();
+            let mut headers = HeaderMap::new();
+            headers.insert("x-lark-request-timestamp", timestamp.parse().unwrap());
+            headers.insert("x-lark-request-nonce", "n0nce".parse().unwrap());
+            let signature = feishu_signature(&timestamp, "n0nce", "key", &body);
+            headers.insert("x-lark-signature", signature.parse().unwrap());
+            headers
+        };
+
+        // Stale by when it was signed, not when the event was created
+        assert!(adapter
+            .receive_webhook(&signed(now - 3_600), &body)
+            .is_err());
+        let FeishuWebhook::Messages(messages) =
+            adapter.receive_webhook(&signed(now), &body).unwrap()
+        else {
+            panic!("expected messages");
+        };
+        assert_eq!(messages.len(), 1);
+        // The same event again, however it is signed
+        assert!(adapter.receive_webhook(&signed(now), &body).is_err());
+    }
+
+    /// Method and path, query, and body of a request
+    type Request = (String, Option<String>, Value);
+
+    #[derive(Clone, Default)]
+    struct MockApi {
+        token_requests: Arc<AtomicUsize>,
+        requests: Arc<Mutex<Vec<Request>>>,
+    }
+
+    async fn tenant_access_token(
+        State(api): State<MockApi>,
+        Json(body): Json<Value>,
+    ) -> Json<Value> {
+        api.token_requests.fetch_add(1, Ordering::SeqCst);
+        if body["app_secret"] != "test_secret" {
+            return Json(json!({"code": 10014, "msg": "app secret invalid"}));
+        }
+        Json(json!({"code": 0, "msg": "ok", "tenant_access_token": "t-1", "expire": 7200}))
+    }
+
+    async fn messages(
+        State(api): State<MockApi>,
+        method: Method,
+        uri: Uri,
+        headers: HeaderMap,
+        Json(body): Json<Value>,
+    ) -> Json<Value> {
+        if headers["authorization"] != "Bearer t-1" {
+            return Json(json!({"code": 99991663, "msg": "Invalid access token"}));
+        }
+        api.requests.lock().unwrap().push((
+            format!("{} {}", method, uri.path()),
+            uri.query().map(str::to_string),
+            body,
+        ));
+        Json(json!({"code": 0, "msg": "success", "data": {"message_id": "om_42"}}))
+    }
+
+    /// An Open API server recording the messages it is sent
+    async fn mock_open_api() -> (String, MockApi) {
+        let api = MockApi::default();
+        let app = axum::Router::new()
+            .route(
+                "/open-apis/auth/v3/tenant_access_token/internal",
+                post(tenant_access_token),
+            )
+            .route("/open-apis/im/v1/messages", post(messages))
+            .route("/open-apis/im/v1/messages/:message_id", put(messages))
+            .with_state(api.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, api)
+    }
+
+    #[tokio::test]
+    async fn test_send_and_edit() {
+        let (api_base, api) = mock_open_api().await;
+        let config = FeishuConfig {
+            app_id: "test_app_id".to_string(),
+            app_secret: "test_secret".to_string(),
+            webhook_url: None,
+            verification_token: None,
+            encrypt_key: None,
+        };
+        let adapter = FeishuAdapter::new("feishu-1", config.clone()).with_api_base(&api_base);
+
+        let message_id = adapter.send_message("oc_1", "Working...").await.unwrap();
+        assert_eq!(message_id, "om_42");
+        adapter.send_message("ou_123", "Hello").await.unwrap();
+        adapter
+            .edit_message("oc_1", &message_id, "Done")
+            .await
+            .unwrap();
+        // Feishu has no typing indicator
+        adapter.send_typing("oc_1").await.unwrap();
+
+        // The token is fetched once and reused
+        assert_eq!(api.token_requests.load(Ordering::SeqCst), 1);
+        let requests = api.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].0, "POST /open-apis/im/v1/messages");
+        assert_eq!(requests[0].1.as_deref(), Some("receive_id_type=chat_id"));
+        assert_eq!(requests[0].2["receive_id"], "oc_1");
+        assert_eq!(requests[0].2["msg_type"], "text");
+        assert_eq!(requests[0].2["content"], r#"{"text":"Working..."}"#);
+        assert_eq!(requests[1].1.as_deref(), Some("receive_id_type=open_id"));
+        assert_eq!(requests[2].0, "PUT /open-apis/im/v1/messages/om_42");
+        assert_eq!(requests[2].2["content"], r#"{"text":"Done"}"#);
+
+        // API errors are reported with their code
+        let adapter = FeishuAdapter::new(
+            "feishu-1",
+            FeishuConfig {
+                app_secret: "wrong".to_string(),
+                ..config
+            },
+        )
+        .with_api_base(&api_base);
+        let error = adapter.send_message("oc_1", "hi").await.unwrap_err();
+        assert!(error.contains("10014"), "{}", error);
+    }
 }
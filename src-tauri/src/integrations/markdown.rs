@@ -0,0 +1,39 @@
+//! Markdown Rendering
+//!
+//! Agent replies are markdown; channels that show rich text take HTML. Raw
+//! HTML in a reply is escaped rather than passed through.
+
+use pulldown_cmark::{html, Event, Options, Parser};
+
+/// Render markdown to HTML
+pub fn to_html(markdown: &str) -> String {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let parser = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        other => other,
+    });
+    let mut output = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut output, parser);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_html() {
+        assert_eq!(
+            to_html("**Done**: fixed `login`"),
+            "<p><strong>Done</strong>: fixed <code>login</code></p>\n"
+        );
+        assert!(
+            to_html("```rust\nfn main() {}\n```").contains("<pre><code class=\"language-rust\">")
+        );
+        assert_eq!(
+            to_html("<script>alert(1)</script>"),
+            "&lt;script&gt;alert(1)&lt;/script&gt;"
+        );
+    }
+}
@@ -0,0 +1,560 @@
+//! Matrix Integration Adapter
+//!
+//! Speaks the Matrix client-server API as a bot account with an access
+//! token. Each project can have a room of its own, and messages are sent as
+//! markdown with an HTML rendering. The adapter cannot encrypt, so in
+//! end-to-end encrypted rooms it posts unencrypted notices when allowed to
+//! and refuses otherwise.
+
+use crate::integrations::markdown;
+use crate::integrations::types::*;
+use reqwest::{Client, StatusCode, Url};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+const HTML_FORMAT: &str = "org.matrix.custom.html";
+
+/// How long a typing notification lasts unless renewed
+const TYPING_TIMEOUT_MS: u64 = 10_000;
+
+/// Matrix adapter configuration
+#[derive(Debug, Clone)]
+pub struct MatrixConfig {
+    /// Homeserver base URL, e.g. `https://matrix.org`
+    pub homeserver_url: String,
+    pub access_token: String,
+    /// Room ID for each project ID
+    pub project_rooms: HashMap<String, String>,
+    /// Post unencrypted notices in encrypted rooms instead of refusing
+    pub encrypted_room_fallback: bool,
+}
+
+/// Matrix integration adapter
+pub struct MatrixAdapter {
+    id: IntegrationId,
+    config: MatrixConfig,
+    client: Client,
+    connected: RwLock<bool>,
+    /// The bot's user ID, learned on start; its own messages are skipped
+    user_id: RwLock<Option<String>>,
+    /// Whether each room sent to so far is encrypted
+    encrypted_rooms: RwLock<HashMap<String, bool>>,
+    /// Sync token of the last poll
+    since: RwLock<Option<String>>,
+}
+
+impl MatrixAdapter {
+    pub fn new(id: impl Into<IntegrationId>, config: MatrixConfig) -> Self {
+        Self {
+            id: id.into(),
+            config,
+            client: Client::new(),
+            connected: RwLock::new(false),
+            user_id: RwLock::new(None),
+            encrypted_rooms: RwLock::new(HashMap::new()),
+            since: RwLock::new(None),
+        }
+    }
+
+    /// Room of a project
+    pub fn room_for_project(&self, project_id: &str) -> Option<&str> {
+        self.config
+            .project_rooms
+            .get(project_id)
+            .map(String::as_str)
+    }
+
+    /// Project a room belongs to
+    pub fn project_for_room(&self, room_id: &str) -> Option<&str> {
+        self.config
+            .project_rooms
+            .iter()
+            .find(|(_, room)| room.as_str() == room_id)
+            .map(|(project_id, _)| project_id.as_str())
+    }
+
+    /// Room ID of a recipient, given either as a room ID or a project ID
+    fn resolve_room(&self, recipient: &str) -> Result<String, String> {
+        if recipient.starts_with('!') {
+            return Ok(recipient.to_string());
+        }
+        self.room_for_project(recipient)
+            .map(str::to_string)
+            .ok_or_else(|| format!("No Matrix room for project '{}'", recipient))
+    }
+
+    /// Fetch messages sent to the bot's rooms since the last poll, waiting up
+    /// to `timeout_ms` for one. The first poll only marks where to start, so
+    /// history is not replayed.
+    pub async fn poll_messages(&self, timeout_ms: u64) -> Result<Vec<IncomingMessage>, String> {
+        let mut url = self.api_url(&["sync"])?;
+        let filter = json!({
+            "presence": { "not_types": ["*"] },
+            "room": { "timeline": { "types": ["m.room.message"] } },
+        });
+        let since = self.since.read().await.clone();
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("filter", &filter.to_string());
+            match &since {
+                Some(since) => {
+                    query.append_pair("since", since);
+                    query.append_pair("timeout", &timeout_ms.to_string());
+                }
+                None => {
+                    query.append_pair("timeout", "0");
+                }
+            }
+        }
+
+        let response = self
+            .client
+            .get(url)
+            .bearer_auth(&self.config.access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Matrix sync failed: {}", e))?;
+        let sync: SyncResponse = read_response(response).await?;
+        *self.since.write().await = Some(sync.next_batch.clone());
+        if since.is_none() {
+            return Ok(Vec::new());
+        }
+
+        let user_id = self.user_id.read().await.clone();
+        Ok(parse_sync(&self.id, user_id.as_deref(), sync))
+    }
+
+    fn api_url(&self, segments: &[&str]) -> Result<Url, String> {
+        let mut url = Url::parse(&self.config.homeserver_url)
+            .map_err(|e| format!("Invalid Matrix homeserver URL: {}", e))?;
+        url.path_segments_mut()
+            .map_err(|_| "Invalid Matrix homeserver URL".to_string())?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3"])
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn is_encrypted(&self, room_id: &str) -> Result<bool, String> {
+        if let Some(encrypted) = self.encrypted_rooms.read().await.get(room_id) {
+            return Ok(*encrypted);
+        }
+
+        let url = self.api_url(&["rooms", room_id, "state", "m.room.encryption"])?;
+        let response = self
+            .client
+            .get(url)
+            .bearer_auth(&self.config.access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to read Matrix room state: {}", e))?;
+        let encrypted = match response.status() {
+            status if status.is_success() => true,
+            StatusCode::NOT_FOUND => false,
+            status => {
+                let body = response.text().await.unwrap_or_default();
+                return Err(format!("Matrix API error ({}): {}", status, body));
+            }
+        };
+        self.encrypted_rooms
+            .write()
+            .await
+            .insert(room_id.to_string(), encrypted);
+        Ok(encrypted)
+    }
+
+    /// Message type to post in a room: text, or a notice in encrypted rooms
+    async fn message_type(&self, room_id: &str) -> Result<&'static str, String> {
+        if !self.is_encrypted(room_id).await? {
+            return Ok("m.text");
+        }
+        if self.config.encrypted_room_fallback {
+            Ok("m.notice")
+        } else {
+            Err(format!(
+                "Matrix room {} is end-to-end encrypted and unencrypted fallback is off",
+                room_id
+            ))
+        }
+    }
+
+    async fn send_event(&self, room_id: &str, content: &Value) -> Result<MessageId, String> {
+        let txn_id = uuid::Uuid::new_v4().to_string();
+        let url = self.api_url(&["rooms", room_id, "send", "m.room.message", &txn_id])?;
+        let response = self
+            .client
+            .put(url)
+            .bearer_auth(&self.config.access_token)
+            .json(content)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send Matrix message: {}", e))?;
+        let sent: SendResponse = read_response(response).await?;
+        Ok(sent.event_id)
+    }
+}
+
+async fn read_response<T: serde::de::DeserializeOwned>(
+    response: reqwest::Response,
+) -> Result<T, String> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("Matrix API error ({}): {}", status, body));
+    }
+    response
+        .json::<T>()
+        .await
+        .map_err(|e| format!("Failed to parse Matrix response: {}", e))
+}
+
+/// Content of a message with its markdown rendered as HTML
+fn message_content(msgtype: &str, text: &str) -> Value {
+    json!({
+        "msgtype": msgtype,
+        "body": text,
+        "format": HTML_FORMAT,
+        "formatted_body": markdown::to_html(text),
+    })
+}
+
+/// Content replacing an earlier message; clients without edit support show
+/// the `*`-prefixed fallback
+fn edit_content(msgtype: &str, event_id: &str, text: &str) -> Value {
+    let new_content = message_content(msgtype, text);
+    json!({
+        "msgtype": msgtype,
+        "body": format!("* {}", text),
+        "format": HTML_FORMAT,
+        "formatted_body": format!("* {}", new_content["formatted_body"].as_str().unwrap_or_default()),
+        "m.new_content": new_content,
+        "m.relates_to": { "rel_type": "m.replace", "event_id": event_id },
+    })
+}
+
+fn parse_sync(
+    integration_id: &IntegrationId,
+    own_user_id: Option<&str>,
+    sync: SyncResponse,
+) -> Vec<IncomingMessage> {
+    let mut messages = Vec::new();
+    for (room_id, room) in sync.rooms.join {
+        for event in room.timeline.events {
+            if event.kind != "m.room.message" || Some(event.sender.as_str()) == own_user_id {
+                continue;
+            }
+            let content = &event.content;
+            let relates_to = &content["m.relates_to"];
+            // Edits repeat a message already delivered
+            if relates_to["rel_type"] == "m.replace" {
+                continue;
+            }
+            if !matches!(
+                content["msgtype"].as_str(),
+                Some("m.text" | "m.notice" | "m.emote")
+            ) {
+                continue;
+            }
+            let Some(body) = content["body"].as_str() else {
+                continue;
+            };
+            let reply_to = relates_to["m.in_reply_to"]["event_id"]
+                .as_str()
+                .map(str::to_string);
+            let body = if reply_to.is_some() {
+                strip_reply_fallback(body)
+            } else {
+                body
+            };
+
+            messages.push(IncomingMessage {
+                integration_id: integration_id.clone(),
+                channel_type: ChannelType::Matrix,
+                sender_id: event.sender,
+                sender_name: None,
+                chat_id: room_id.clone(),
+                message_id: event.event_id,
+                content: body.to_string(),
+                timestamp: event.origin_server_ts,
+                reply_to,
+                media: Vec::new(),
+            });
+        }
+    }
+    messages
+}
+
+/// Drop the quote of the replied-to message that older clients put in front
+/// of a reply
+fn strip_reply_fallback(body: &str) -> &str {
+    let mut rest = body;
+    while rest.starts_with('>') {
+        rest = rest.split_once('\n').map_or("", |(_, tail)| tail);
+    }
+    rest.strip_prefix('\n').unwrap_or(rest)
+}
+
+#[async_trait::async_trait]
+impl IntegrationAdapter for MatrixAdapter {
+    fn id(&self) -> &IntegrationId {
+        &self.id
+    }
+
+    fn channel_type(&self) -> ChannelType {
+        ChannelType::Matrix
+    }
+
+    async fn start(&self) -> Result<(), String> {
+        if self.config.homeserver_url.is_empty() || self.config.access_token.is_empty() {
+            return Err("Matrix needs a homeserver URL and access token".to_string());
+        }
+
+        let url = self.api_url(&["account", "whoami"])?;
+        let response = self
+            .client
+            .get(url)
+            .bearer_auth(&self.config.access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach Matrix homeserver: {}", e))?;
+        let whoami: WhoamiResponse = read_response(response).await?;
+        *self.user_id.write().await = Some(whoami.user_id);
+
+        let mut connected = self.connected.write().await;
+        *connected = true;
+        Ok(())
+    }
+
+    async fn stop(&self) -> Result<(), String> {
+        let mut connected = self.connected.write().await;
+        *connected = false;
+        Ok(())
+    }
+
+    async fn send_message(&self, recipient: &str, content: &str) -> Result<MessageId, String> {
+        let room_id = self.resolve_room(recipient)?;
+        let msgtype = self.message_type(&room_id).await?;
+        self.send_event(&room_id, &message_content(msgtype, content))
+            .await
+    }
+
+    async fn edit_message(
+        &self,
+        recipient: &str,
+        message_id: &str,
+        new_content: &str,
+    ) -> Result<(), String> {
+        let room_id = self.resolve_room(recipient)?;
+        let msgtype = self.message_type(&room_id).await?;
+        self.send_event(&room_id, &edit_content(msgtype, message_id, new_content))
+            .await
+            .map(|_| ())
+    }
+
+    async fn send_typing(&self, recipient: &str) -> Result<(), String> {
+        let room_id = self.resolve_room(recipient)?;
+        let user_id = self
+            .user_id
+            .read()
+            .await
+            .clone()
+            .ok_or_else(|| "Matrix adapter is not started".to_string())?;
+        let url = self.api_url(&["rooms", &room_id, "typing", &user_id])?;
+        let response = self
+            .client
+            .put(url)
+            .bearer_auth(&self.config.access_token)
+            .json(&json!({ "typing": true, "timeout": TYPING_TIMEOUT_MS }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send Matrix typing notification: {}", e))?;
+        read_response::<Value>(response).await.map(|_| ())
+    }
+
+    async fn is_connected(&self) -> bool {
+        *self.connected.read().await
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WhoamiResponse {
+    user_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SendResponse {
+    event_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SyncResponse {
+    next_batch: String,
+    #[serde(default)]
+    rooms: SyncRooms,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SyncRooms {
+    #[serde(default)]
+    join: HashMap<String, JoinedRoom>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JoinedRoom {
+    #[serde(default)]
+    timeline: Timeline,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Timeline {
+    #[serde(default)]
+    events: Vec<RoomEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RoomEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    event_id: String,
+    sender: String,
+    origin_server_ts: i64,
+    #[serde(default)]
+    content: Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> MatrixConfig {
+        MatrixConfig {
+            homeserver_url: "https://matrix.example.org".to_string(),
+            access_token: "test_token".to_string(),
+            project_rooms: HashMap::from([(
+                "project-1".to_string(),
+                "!abc:example.org".to_string(),
+            )]),
+            encrypted_room_fallback: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_matrix_adapter_creation() {
+        let adapter = MatrixAdapter::new("matrix-1", config());
+        assert_eq!(adapter.id(), "matrix-1");
+        assert_eq!(adapter.channel_type(), ChannelType::Matrix);
+        assert!(!adapter.is_connected().await);
+
+        let unconfigured = MatrixAdapter::new(
+            "matrix-2",
+            MatrixConfig {
+                access_token: String::new(),
+                ..config()
+            },
+        );
+        assert!(unconfigured.start().await.is_err());
+    }
+
+    #[test]
+    fn test_project_rooms() {
+        let adapter = MatrixAdapter::new("matrix-1", config());
+        assert_eq!(
+            adapter.room_for_project("project-1"),
+            Some("!abc:example.org")
+        );
+        assert_eq!(
+            adapter.project_for_room("!abc:example.org"),
+            Some("project-1")
+        );
+        assert_eq!(
+            adapter.resolve_room("project-1").unwrap(),
+            "!abc:example.org"
+        );
+        assert_eq!(
+            adapter.resolve_room("!other:example.org").unwrap(),
+            "!other:example.org"
+        );
+        assert!(adapter.resolve_room("project-2").is_err());
+
+        // Room IDs are escaped as path segments
+        assert_eq!(
+            adapter
+                .api_url(&["rooms", "!abc:example.org", "state", "m.room.encryption"])
+                .unwrap()
+                .as_str(),
+            "https://matrix.example.org/_matrix/client/v3/rooms/!abc:example.org/state/m.room.encryption"
+        );
+    }
+
+    #[test]
+    fn test_message_content() {
+        let content = message_content("m.notice", "**Done**");
+        assert_eq!(content["msgtype"], "m.notice");
+        assert_eq!(content["body"], "**Done**");
+        assert_eq!(content["format"], HTML_FORMAT);
+        assert_eq!(content["formatted_body"], "<p><strong>Done</strong></p>\n");
+
+        let edit = edit_content("m.text", "$event", "Step 2 of 3");
+        assert_eq!(edit["body"], "* Step 2 of 3");
+        assert_eq!(edit["m.new_content"]["body"], "Step 2 of 3");
+        assert_eq!(edit["m.relates_to"]["rel_type"], "m.replace");
+        assert_eq!(edit["m.relates_to"]["event_id"], "$event");
+    }
+
+    #[test]
+    fn test_parse_sync() {
+        let sync: SyncResponse = serde_json::from_value(json!({
+            "next_batch": "s2",
+            "rooms": { "join": { "!abc:example.org": { "timeline": { "events": [
+                {
+                    "type": "m.room.message",
+                    "event_id": "$1",
+                    "sender": "@ada:example.org",
+                    "origin_server_ts": 1700000000000i64,
+                    "content": { "msgtype": "m.text", "body": "Run the tests" }
+                },
+                {
+                    "type": "m.room.message",
+                    "event_id": "$2",
+                    "sender": "@ada:example.org",
+                    "origin_server_ts": 1700000001000i64,
+                    "content": {
+                        "msgtype": "m.text",
+                        "body": "> <@bot:example.org> Tests failed\n\nShow me the log",
+                        "m.relates_to": { "m.in_reply_to": { "event_id": "$0" } }
+                    }
+                },
+                {
+                    "type": "m.room.message",
+                    "event_id": "$3",
+                    "sender": "@ada:example.org",
+                    "origin_server_ts": 1700000002000i64,
+                    "content": {
+                        "msgtype": "m.text",
+                        "body": "* Show me the full log",
+                        "m.relates_to": { "rel_type": "m.replace", "event_id": "$2" }
+                    }
+                },
+                {
+                    "type": "m.room.message",
+                    "event_id": "$4",
+                    "sender": "@bot:example.org",
+                    "origin_server_ts": 1700000003000i64,
+                    "content": { "msgtype": "m.notice", "body": "Working on it" }
+                }
+            ] } } } }
+        }))
+        .unwrap();
+
+        let messages = parse_sync(&"matrix-1".to_string(), Some("@bot:example.org"), sync);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].chat_id, "!abc:example.org");
+        assert_eq!(messages[0].sender_id, "@ada:example.org");
+        assert_eq!(messages[0].content, "Run the tests");
+        assert_eq!(messages[0].timestamp, 1_700_000_000_000);
+        assert_eq!(messages[1].content, "Show me the log");
+        assert_eq!(messages[1].reply_to.as_deref(), Some("$0"));
+    }
+}
@@ -1,15 +1,37 @@
 //! Integration Layer
 //!
-//! IM adapters for Telegram, Feishu, and future channels (Slack, Discord, WhatsApp).
+//! IM adapters for Telegram, Feishu, WhatsApp, Matrix, email, and future channels (Slack, Discord).
 //! Wraps existing gateway implementations for cloud backend integration.
 
+pub mod access;
+pub mod access_commands;
+pub mod commands;
+pub mod email;
 pub mod feishu;
+pub mod markdown;
+pub mod matrix;
+pub mod outbound;
+pub mod progress;
+pub mod router;
 pub mod telegram;
+pub mod threads;
 pub mod types;
-
-pub use feishu::{FeishuAdapter, FeishuConfig};
+pub mod verification;
+pub mod whatsapp;
+
+pub use access::{AccessControl, AccessList, InboundAccess, IntegrationPairing};
+pub use commands::ChatCommand;
+pub use email::{EmailAdapter, EmailConfig, ImapConfig};
+pub use feishu::{FeishuAdapter, FeishuConfig, FeishuWebhook};
+pub use matrix::{MatrixAdapter, MatrixConfig};
+pub use outbound::{OutboundQueue, QueuedAdapter, RateLimit, SendOutcome};
+pub use progress::{ProgressReporter, TaskProgress};
+pub use router::{ChatRouter, ChatRouting};
 pub use telegram::{TelegramAdapter, TelegramConfig};
+pub use threads::{ThreadRoute, ThreadRouter};
 pub use types::*;
+pub use verification::ReplayGuard;
+pub use whatsapp::{WhatsAppAdapter, WhatsAppConfig, WhatsAppProvider};
 
 /// Integration factory for creating adapters
 pub struct IntegrationFactory;
@@ -22,6 +44,7 @@ impl IntegrationFactory {
             TelegramConfig {
                 bot_token,
                 webhook_url: None,
+                secret_token: None,
             },
         )
     }
@@ -38,6 +61,59 @@ impl IntegrationFactory {
                 app_id,
                 app_secret,
                 webhook_url: None,
+                verification_token: None,
+                encrypt_key: None,
+            },
+        )
+    }
+
+    /// Create a WhatsApp adapter
+    pub fn create_whatsapp(
+        id: impl Into<IntegrationId>,
+        provider: WhatsAppProvider,
+    ) -> WhatsAppAdapter {
+        WhatsAppAdapter::new(
+            id,
+            WhatsAppConfig {
+                provider,
+                webhook_url: None,
+            },
+        )
+    }
+
+    /// Create a Matrix adapter with no project rooms mapped yet
+    pub fn create_matrix(
+        id: impl Into<IntegrationId>,
+        homeserver_url: String,
+        access_token: String,
+    ) -> MatrixAdapter {
+        MatrixAdapter::new(
+            id,
+            MatrixConfig {
+                homeserver_url,
+                access_token,
+                project_rooms: std::collections::HashMap::new(),
+                encrypted_room_fallback: true,
+            },
+        )
+    }
+
+    /// Create an email adapter that sends over STARTTLS and polls no inbox
+    pub fn create_email(
+        id: impl Into<IntegrationId>,
+        smtp_host: String,
+        username: String,
+        password: String,
+        from_address: String,
+    ) -> EmailAdapter {
+        EmailAdapter::new(
+            id,
+            EmailConfig {
+                smtp_host,
+                username,
+                password,
+                from_address,
+                ..EmailConfig::default()
             },
         )
     }
@@ -74,6 +150,33 @@ mod tests {
         let feishu =
             IntegrationFactory::create_feishu("fs-1", "app_id".to_string(), "secret".to_string());
         assert_eq!(feishu.id(), "fs-1");
+
+        let whatsapp = IntegrationFactory::create_whatsapp(
+            "wa-1",
+            WhatsAppProvider::Twilio {
+                account_sid: "AC123".to_string(),
+                auth_token: "token".to_string(),
+                from_number: "+14155238886".to_string(),
+            },
+        );
+        assert_eq!(whatsapp.id(), "wa-1");
+        assert_eq!(whatsapp.channel_type(), ChannelType::WhatsApp);
+
+        let matrix = IntegrationFactory::create_matrix(
+            "mx-1",
+            "https://matrix.org".to_string(),
+            "token".to_string(),
+        );
+        assert_eq!(matrix.id(), "mx-1");
+
+        let email = IntegrationFactory::create_email(
+            "mail-1",
+            "smtp.example.com".to_string(),
+            "bot@example.com".to_string(),
+            "secret".to_string(),
+            "bot@example.com".to_string(),
+        );
+        assert_eq!(email.id(), "mail-1");
     }
 
     #[test]
@@ -0,0 +1,636 @@
+//! Outbound Message Queue
+//!
+//! Messages to an integration are queued in the outbox and sent in order, no
+//! faster than the channel accepts them overall and per recipient. Failed
+//! sends are retried with backoff. An edit of a message that has an edit waiting replaces it, so a
+//! burst of streaming updates sends its latest state instead of being
+//! dropped by the channel. [`QueuedAdapter`] lets code written against
+//! adapters send through the queue.
+
+use crate::integrations::types::*;
+use crate::storage::{OutboxRepository, QueuedMessage};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, Mutex, Notify};
+use tokio::time::Instant;
+
+/// Waits before each retry of a failed send
+const RETRY_DELAYS_MS: [u64; 4] = [1_000, 5_000, 30_000, 120_000];
+
+/// Messages taken from the outbox at a time
+const BATCH_SIZE: usize = 50;
+
+/// Longest the worker sleeps before checking the outbox again
+const IDLE_POLL: Duration = Duration::from_secs(5);
+
+/// Time a waiting send allows beyond its retries for the message to go out
+const SEND_WAIT_MARGIN: Duration = Duration::from_secs(60);
+
+/// How many messages a channel accepts in a window of time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub messages: usize,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    /// Limit of a channel's bot API
+    pub fn for_channel(channel: ChannelType) -> Self {
+        let (messages, per_secs) = match channel {
+            ChannelType::Telegram => (30, 1),
+            ChannelType::Feishu => (50, 1),
+            ChannelType::Slack => (1, 1),
+            ChannelType::Discord => (5, 5),
+            ChannelType::WhatsApp => (80, 1),
+            ChannelType::Matrix => (10, 1),
+            ChannelType::Email => (1, 1),
+        };
+        Self {
+            messages,
+            per: Duration::from_secs(per_secs),
+        }
+    }
+
+    /// Limit of a channel's bot API for messages to one chat, if it has one
+    pub fn per_recipient(channel: ChannelType) -> Option<Self> {
+        match channel {
+            ChannelType::Telegram => Some(Self {
+                messages: 1,
+                per: Duration::from_secs(1),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Sliding window of the times recent messages went out
+struct RateLimiter {
+    limit: RateLimit,
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Forget messages that left the window; returns true when none are left
+    fn prune(&mut self, now: Instant) -> bool {
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= self.limit.per)
+        {
+            self.sent.pop_front();
+        }
+        self.sent.is_empty()
+    }
+
+    /// Time to wait before another message may go out
+    fn delay(&mut self, now: Instant) -> Duration {
+        self.prune(now);
+        match self.sent.front() {
+            Some(oldest) if self.sent.len() >= self.limit.messages => {
+                (*oldest + self.limit.per).saturating_duration_since(now)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.sent.push_back(now);
+    }
+}
+
+/// What became of a queued message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendOutcome {
+    /// ID the message was queued under
+    pub queued_id: String,
+    /// ID the channel gave the message, or the edited message's; the last
+    /// error when the message was dropped after its retries
+    pub result: Result<MessageId, String>,
+}
+
+/// Outbound queue of one integration
+pub struct OutboundQueue {
+    adapter: Arc<dyn IntegrationAdapter>,
+    outbox: OutboxRepository,
+    limiter: Mutex<RateLimiter>,
+    recipient_limit: Option<RateLimit>,
+    recipient_limiters: Mutex<HashMap<String, RateLimiter>>,
+    retry_delays: Vec<Duration>,
+    wake: Notify,
+    sent_tx: broadcast::Sender<SendOutcome>,
+}
+
+impl OutboundQueue {
+    pub fn new(adapter: Arc<dyn IntegrationAdapter>, outbox: OutboxRepository) -> Self {
+        let (sent_tx, _) = broadcast::channel(256);
+        Self {
+            limiter: Mutex::new(RateLimiter::new(RateLimit::for_channel(
+                adapter.channel_type(),
+            ))),
+            recipient_limit: RateLimit::per_recipient(adapter.channel_type()),
+            recipient_limiters: Mutex::new(HashMap::new()),
+            adapter,
+            outbox,
+            retry_delays: RETRY_DELAYS_MS
+                .iter()
+                .map(|ms| Duration::from_millis(*ms))
+                .collect(),
+            wake: Notify::new(),
+            sent_tx,
+        }
+    }
+
+    /// Send no faster than `limit` instead of the channel's default
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.limiter = Mutex::new(RateLimiter::new(limit));
+        self
+    }
+
+    /// Send to each recipient no faster than `limit` instead of the
+    /// channel's default; `None` sends as fast as the overall limit allows
+    pub fn with_recipient_rate_limit(mut self, limit: Option<RateLimit>) -> Self {
+        self.recipient_limit = limit;
+        self
+    }
+
+    /// Wait `delays` before the retries of a failed send; its length is the
+    /// number of retries
+    pub fn with_retry_delays(mut self, delays: Vec<Duration>) -> Self {
+        self.retry_delays = delays;
+        self
+    }
+
+    /// Queue a message. Returns the ID it is queued under.
+    pub async fn send(&self, recipient: &str, content: &str) -> Result<String, String> {
+        self.enqueue(recipient, content, None).await
+    }
+
+    /// Queue an edit of a sent message, replacing an edit of it still waiting
+    pub async fn edit(
+        &self,
+        recipient: &str,
+        message_id: &str,
+        content: &str,
+    ) -> Result<String, String> {
+        self.enqueue(recipient, content, Some(message_id.to_string()))
+            .await
+    }
+
+    /// Queue a message and wait until it went out, returning the ID the
+    /// channel gave it, or until it was dropped after its retries
+    pub async fn send_and_wait(&self, recipient: &str, content: &str) -> Result<MessageId, String> {
+        // Subscribed before queueing, so the message cannot go out unseen
+        let mut sent = self.subscribe();
+        let queued_id = self.send(recipient, content).await?;
+        let wait = self.retry_delays.iter().sum::<Duration>() + SEND_WAIT_MARGIN;
+        tokio::time::timeout(wait, async {
+            loop {
+                match sent.recv().await {
+                    Ok(outcome) if outcome.queued_id == queued_id => return outcome.result,
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return Err("Outbound queue closed".to_string()),
+                }
+            }
+        })
+        .await
+        .map_err(|_| format!("Message to {} was not sent", recipient))?
+    }
+
+    /// Receive what became of queued messages: the IDs the channel gave those
+    /// that went out and the errors of those dropped
+    pub fn subscribe(&self) -> broadcast::Receiver<SendOutcome> {
+        self.sent_tx.subscribe()
+    }
+
+    /// Send queued messages until the process exits, picking up those left
+    /// queued by a previous run
+    pub fn spawn(self: Arc<Self>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                let held = match self.process_due().await {
+                    Ok(held) => held,
+                    Err(e) => {
+                        log::warn!(
+                            "Outbound queue of integration {} failed: {}",
+                            self.adapter.id(),
+                            e
+                        );
+                        None
+                    }
+                };
+                let wait = match self.outbox.next_attempt_at(self.adapter.id()).await {
+                    Ok(Some(next)) => {
+                        Duration::from_millis(next.saturating_sub(now_ms()).max(0) as u64)
+                            .min(IDLE_POLL)
+                    }
+                    _ => IDLE_POLL,
+                };
+                let wait = held.map_or(wait, |held| wait.min(held));
+                tokio::select! {
+                    _ = self.wake.notified() => {}
+                    _ = tokio::time::sleep(wait) => {}
+                }
+            }
+        })
+    }
+
+    async fn enqueue(
+        &self,
+        recipient: &str,
+        content: &str,
+        edit_message_id: Option<String>,
+    ) -> Result<String, String> {
+        let now = now_ms();
+        let id = self
+            .outbox
+            .enqueue(&QueuedMessage {
+                id: format!("out_{}", uuid::Uuid::new_v4().simple()),
+                integration_id: self.adapter.id().clone(),
+                recipient: recipient.to_string(),
+                content: content.to_string(),
+                edit_message_id,
+                attempts: 0,
+                next_attempt_at: now,
+                last_error: None,
+                created_at: now,
+            })
+            .await?;
+        self.wake.notify_one();
+        Ok(id)
+    }
+
+    /// Send the messages that are due, in order. Returns how long until a
+    /// recipient held back by its rate limit may be sent to again.
+    async fn process_due(&self) -> Result<Option<Duration>, String> {
+        let due = self
+            .outbox
+            .due(self.adapter.id(), now_ms(), BATCH_SIZE)
+            .await?;
+        // Once a send to a recipient fails or has to wait, its later
+        // messages wait for it while other recipients go ahead
+        let mut held_recipients = HashSet::new();
+        let mut held_for: Option<Duration> = None;
+        let mut recipient_limiters = self.recipient_limiters.lock().await;
+        let now = Instant::now();
+        recipient_limiters.retain(|_, limiter| !limiter.prune(now));
+
+        for mut message in due {
+            if held_recipients.contains(&message.recipient) {
+                continue;
+            }
+            if let Some(limit) = self.recipient_limit {
+                let delay = recipient_limiters
+                    .entry(message.recipient.clone())
+                    .or_insert_with(|| RateLimiter::new(limit))
+                    .delay(Instant::now());
+                if !delay.is_zero() {
+                    held_for = Some(held_for.map_or(delay, |held| held.min(delay)));
+                    held_recipients.insert(message.recipient);
+                    continue;
+                }
+            }
+            self.wait_for_rate_limit().await;
+            if let Some(limiter) = recipient_limiters.get_mut(&message.recipient) {
+                limiter.record(Instant::now());
+            }
+
+            let result = match &message.edit_message_id {
+                Some(message_id) => self
+                    .adapter
+                    .edit_message(&message.recipient, message_id, &message.content)
+                    .await
+                    .map(|_| message_id.clone()),
+                None => {
+                    self.adapter
+                        .send_message(&message.recipient, &message.content)
+                        .await
+                }
+            };
+            match result {
+                Ok(message_id) => {
+                    self.outbox.complete(&message).await?;
+                    let _ = self.sent_tx.send(SendOutcome {
+                        queued_id: message.id,
+                        result: Ok(message_id),
+                    });
+                }
+                Err(e) if (message.attempts as usize) < self.retry_delays.len() => {
+                    let delay = self.retry_delays[message.attempts as usize];
+                    message.attempts += 1;
+                    message.next_attempt_at = now_ms() + delay.as_millis() as i64;
+                    message.last_error = Some(e);
+                    self.outbox.record_failure(&message).await?;
+                    held_recipients.insert(message.recipient);
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Dropped message to {} through integration {} after {} attempts: {}",
+                        message.recipient,
+                        message.integration_id,
+                        message.attempts + 1,
+                        e
+                    );
+                    self.outbox.delete(&message.id).await?;
+                    let _ = self.sent_tx.send(SendOutcome {
+                        queued_id: message.id,
+                        result: Err(format!(
+                            "Message to {} was dropped after {} attempts: {}",
+                            message.recipient,
+                            message.attempts + 1,
+                            e
+                        )),
+                    });
+                }
+            }
+        }
+        Ok(held_for)
+    }
+
+    async fn wait_for_rate_limit(&self) {
+        let mut limiter = self.limiter.lock().await;
+        let delay = limiter.delay(Instant::now());
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        limiter.record(Instant::now());
+    }
+}
+
+/// Adapter sending through an outbound queue. A send returns once the
+/// message went out, with its ID, and an edit once it is queued; typing
+/// actions go straight to the queue's adapter.
+pub struct QueuedAdapter {
+    queue: Arc<OutboundQueue>,
+}
+
+impl QueuedAdapter {
+    pub fn new(queue: Arc<OutboundQueue>) -> Self {
+        Self { queue }
+    }
+}
+
+#[async_trait::async_trait]
+impl IntegrationAdapter for QueuedAdapter {
+    fn id(&self) -> &IntegrationId {
+        self.queue.adapter.id()
+    }
+
+    fn channel_type(&self) -> ChannelType {
+        self.queue.adapter.channel_type()
+    }
+
+    async fn start(&self) -> Result<(), String> {
+        self.queue.adapter.start().await
+    }
+
+    async fn stop(&self) -> Result<(), String> {
+        self.queue.adapter.stop().await
+    }
+
+    async fn send_message(&self, recipient: &str, content: &str) -> Result<MessageId, String> {
+        self.queue.send_and_wait(recipient, content).await
+    }
+
+    async fn edit_message(
+        &self,
+        recipient: &str,
+        message_id: &str,
+        new_content: &str,
+    ) -> Result<(), String> {
+        self.queue
+            .edit(recipient, message_id, new_content)
+            .await
+            .map(|_| ())
+    }
+
+    async fn send_typing(&self, recipient: &str) -> Result<(), String> {
+        self.queue.adapter.send_typing(recipient).await
+    }
+
+    async fn is_connected(&self) -> bool {
+        self.queue.adapter.is_connected().await
+    }
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::storage::migrations::{chat_history_migrations, MigrationRunner};
+    use std::sync::Mutex as StdMutex;
+    use tempfile::TempDir;
+
+    /// Adapter that records what it is asked to do and fails the first sends
+    struct RecordingAdapter {
+        id: IntegrationId,
+        failures: StdMutex<usize>,
+        calls: StdMutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl IntegrationAdapter for RecordingAdapter {
+        fn id(&self) -> &IntegrationId {
+            &self.id
+        }
+
+        fn channel_type(&self) -> ChannelType {
+            ChannelType::Telegram
+        }
+
+        async fn start(&self) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn stop(&self) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn send_message(&self, recipient: &str, content: &str) -> Result<MessageId, String> {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err("429 Too Many Requests".to_string());
+            }
+            let mut calls = self.calls.lock().unwrap();
+            calls.push(format!("send {} {}", recipient, content));
+            Ok(format!("msg-{}", calls.len()))
+        }
+
+        async fn edit_message(
+            &self,
+            recipient: &str,
+            message_id: &str,
+            new_content: &str,
+        ) -> Result<(), String> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("edit {} {} {}", recipient, message_id, new_content));
+            Ok(())
+        }
+
+        async fn is_connected(&self) -> bool {
+            true
+        }
+    }
+
+    async fn create_queue(failures: usize) -> (Arc<RecordingAdapter>, OutboundQueue, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect().await.unwrap();
+        let migrations = chat_history_migrations();
+        let runner = MigrationRunner::new(&db, &migrations);
+        runner.init().await.unwrap();
+        runner.migrate().await.unwrap();
+
+        let adapter = Arc::new(RecordingAdapter {
+            id: "telegram".to_string(),
+            failures: StdMutex::new(failures),
+            calls: StdMutex::new(Vec::new()),
+        });
+        let queue = OutboundQueue::new(adapter.clone(), OutboxRepository::new(db))
+            .with_recipient_rate_limit(None)
+            .with_retry_delays(vec![Duration::ZERO]);
+        (adapter, queue, temp_dir)
+    }
+
+    #[tokio::test]
+    async fn test_edits_are_deduplicated() {
+        let (adapter, queue, _temp_dir) = create_queue(0).await;
+        let mut sent = queue.subscribe();
+
+        let first = queue.send("chat-1", "Working...").await.unwrap();
+        queue.edit("chat-1", "msg-0", "Step 1").await.unwrap();
+        queue.edit("chat-1", "msg-0", "Step 2").await.unwrap();
+        queue.process_due().await.unwrap();
+
+        assert_eq!(
+            *adapter.calls.lock().unwrap(),
+            ["send chat-1 Working...", "edit chat-1 msg-0 Step 2"]
+        );
+        assert_eq!(
+            sent.recv().await.unwrap(),
+            SendOutcome {
+                queued_id: first,
+                result: Ok("msg-1".to_string()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_sends_are_retried_in_order() {
+        let (adapter, queue, _temp_dir) = create_queue(1).await;
+
+        queue.send("chat-1", "first").await.unwrap();
+        queue.send("chat-1", "second").await.unwrap();
+        queue.process_due().await.unwrap();
+        assert!(adapter.calls.lock().unwrap().is_empty());
+
+        queue.process_due().await.unwrap();
+        assert_eq!(
+            *adapter.calls.lock().unwrap(),
+            ["send chat-1 first", "send chat-1 second"]
+        );
+
+        // Sends that fail past their retries are dropped
+        *adapter.failures.lock().unwrap() = 2;
+        queue.send("chat-1", "third").await.unwrap();
+        queue.process_due().await.unwrap();
+        queue.process_due().await.unwrap();
+        queue.process_due().await.unwrap();
+        assert_eq!(adapter.calls.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_queued_adapter_waits_for_sends() {
+        let (adapter, queue, _temp_dir) = create_queue(1).await;
+        let queue = Arc::new(queue);
+        let worker = queue.clone().spawn();
+        let queued = QueuedAdapter::new(queue);
+
+        // The first attempt fails and is retried
+        assert_eq!(
+            queued.send_message("chat-1", "Working...").await.unwrap(),
+            "msg-1"
+        );
+        queued
+            .edit_message("chat-1", "msg-1", "Done")
+            .await
+            .unwrap();
+        worker.abort();
+        assert_eq!(adapter.calls.lock().unwrap()[0], "send chat-1 Working...");
+    }
+
+    #[tokio::test]
+    async fn test_send_and_wait_fails_once_retries_run_out() {
+        let (adapter, queue, _temp_dir) = create_queue(2).await;
+        let queue = Arc::new(queue);
+        let worker = queue.clone().spawn();
+
+        // Returns with the failure instead of waiting out the send timeout
+        let result = tokio::time::timeout(
+            Duration::from_secs(10),
+            queue.send_and_wait("chat-1", "Working..."),
+        )
+        .await
+        .unwrap();
+        worker.abort();
+        assert!(result.unwrap_err().contains("429 Too Many Requests"));
+        assert!(adapter.calls.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_recipient_rate_limit_holds_back_only_that_recipient() {
+        let (adapter, queue, _temp_dir) = create_queue(0).await;
+        let queue = queue.with_recipient_rate_limit(Some(RateLimit {
+            messages: 1,
+            per: Duration::from_secs(60),
+        }));
+
+        queue.send("chat-1", "first").await.unwrap();
+        queue.send("chat-1", "second").await.unwrap();
+        queue.send("chat-2", "other").await.unwrap();
+        let held = queue.process_due().await.unwrap().unwrap();
+
+        assert_eq!(
+            *adapter.calls.lock().unwrap(),
+            ["send chat-1 first", "send chat-2 other"]
+        );
+        assert!(held > Duration::ZERO && held <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::new(RateLimit {
+            messages: 2,
+            per: Duration::from_secs(1),
+        });
+        let start = Instant::now();
+
+        assert_eq!(limiter.delay(start), Duration::ZERO);
+        limiter.record(start);
+        limiter.record(start + Duration::from_millis(200));
+        assert_eq!(
+            limiter.delay(start + Duration::from_millis(500)),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            limiter.delay(start + Duration::from_millis(1000)),
+            Duration::ZERO
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! Task Progress in IM Channels
+//!
+//! While a task runs, keeps the chat's typing indicator on and edits a single
+//! progress message with the iteration and the tool being run, instead of
+//! leaving the chat silent or posting a message for every step.
+
+use crate::core::event_log::{event_ids, LoggedEvent};
+use crate::core::types::{RuntimeEvent, RuntimeTaskState};
+use crate::integrations::types::*;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{interval, interval_at, Instant, MissedTickBehavior};
+
+/// Least time between edits of the progress message
+const EDIT_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Time between typing actions; Telegram shows one for five seconds
+const TYPING_INTERVAL: Duration = Duration::from_secs(4);
+
+/// What a running task has done so far
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskProgress {
+    pub iteration: u32,
+    pub tool_calls: u32,
+    /// Tool being run, if any
+    pub current_tool: Option<String>,
+    pub state: Option<RuntimeTaskState>,
+}
+
+impl TaskProgress {
+    /// Apply an event of the task
+    pub fn apply(&mut self, event: &RuntimeEvent) {
+        match event {
+            RuntimeEvent::IterationStarted { iteration, .. } => {
+                self.iteration = *iteration;
+                self.current_tool = None;
+            }
+            RuntimeEvent::ToolCallStarted { request, .. } => {
+                self.tool_calls += 1;
+                self.current_tool = Some(request.name.clone());
+            }
+            RuntimeEvent::ToolCallCompleted { .. } => self.current_tool = None,
+            RuntimeEvent::TaskStateChanged { state, .. } => self.state = Some(*state),
+            _ => {}
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state.is_some_and(|state| state.is_terminal())
+    }
+
+    /// Text of the progress message
+    pub fn render(&self) -> String {
+        let counts = format!(
+            "{}, {}",
+            count(self.iteration, "iteration"),
+            count(self.tool_calls, "tool call")
+        );
+        match self.state {
+            Some(RuntimeTaskState::Completed) => format!("Done ({})", counts),
+            Some(RuntimeTaskState::Failed) => format!("Failed ({})", counts),
+            Some(RuntimeTaskState::Cancelled) => format!("Cancelled ({})", counts),
+            Some(RuntimeTaskState::WaitingForUser) => format!("Waiting for you ({})", counts),
+            _ if self.iteration == 0 => "Working...".to_string(),
+            _ => match &self.current_tool {
+                Some(tool) => format!("Working: iteration {}, running {}", self.iteration, tool),
+                None => format!("Working: iteration {}", self.iteration),
+            },
+        }
+    }
+}
+
+fn count(n: u32, noun: &str) -> String {
+    if n == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", n, noun)
+    }
+}
+
+/// Reports the progress of tasks to a channel
+pub struct ProgressReporter {
+    adapter: Arc<dyn IntegrationAdapter>,
+    edit_interval: Duration,
+    typing_interval: Duration,
+}
+
+impl ProgressReporter {
+    pub fn new(adapter: Arc<dyn IntegrationAdapter>) -> Self {
+        Self {
+            adapter,
+            edit_interval: EDIT_INTERVAL,
+            typing_interval: TYPING_INTERVAL,
+        }
+    }
+
+    /// Edit the progress message at most once per `interval`
+    pub fn with_edit_interval(mut self, interval: Duration) -> Self {
+        self.edit_interval = interval;
+        self
+    }
+
+    /// Renew the typing indicator every `interval`
+    pub fn with_typing_interval(mut self, interval: Duration) -> Self {
+        self.typing_interval = interval;
+        self
+    }
+
+    /// Post a progress message to `recipient` and keep it up to date until
+    /// the task finishes or the runtime's events close. Returns the ID of the
+    /// progress message.
+    pub async fn run(
+        &self,
+        recipient: &str,
+        task_id: &str,
+        mut events: broadcast::Receiver<LoggedEvent>,
+    ) -> Result<MessageId, String> {
+        let mut progress = TaskProgress::default();
+        let mut shown = progress.render();
+        let message_id = self.adapter.send_message(recipient, &shown).await?;
+
+        let mut typing = interval(self.typing_interval);
+        typing.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut edits = interval_at(Instant::now() + self.edit_interval, self.edit_interval);
+        edits.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        while !progress.is_finished() {
+            tokio::select! {
+                // Apply what happened before showing it
+                biased;
+                event = events.recv() => match event {
+                    Ok(logged) => {
+                        if event_ids(&logged.event).0 == Some(task_id) {
+                            progress.apply(&logged.event);
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                },
+                _ = typing.tick() => {
+                    if progress.state != Some(RuntimeTaskState::WaitingForUser) {
+                        if let Err(e) = self.adapter.send_typing(recipient).await {
+                            log::debug!("Failed to send typing action to {}: {}", recipient, e);
+                        }
+                    }
+                }
+                _ = edits.tick() => {
+                    let text = progress.render();
+                    if text != shown {
+                        match self.adapter.edit_message(recipient, &message_id, &text).await {
+                            Ok(()) => shown = text,
+                            Err(e) => log::warn!("Failed to update progress message: {}", e),
+                        }
+                    }
+                }
+            }
+        }
+
+        let text = progress.render();
+        if text != shown {
+            self.adapter
+                .edit_message(recipient, &message_id, &text)
+                .await?;
+        }
+        Ok(message_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::ToolRequest;
+    use std::sync::Mutex;
+
+    /// Adapter that records what it is asked to do
+    struct RecordingAdapter {
+        id: IntegrationId,
+        calls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl IntegrationAdapter for RecordingAdapter {
+        fn id(&self) -> &IntegrationId {
+            &self.id
+        }
+
+        fn channel_type(&self) -> ChannelType {
+            ChannelType::Telegram
+        }
+
+        async fn start(&self) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn stop(&self) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn send_message(&self, recipient: &str, content: &str) -> Result<MessageId, String> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("send {} {}", recipient, content));
+            Ok("msg-1".to_string())
+        }
+
+        async fn edit_message(
+            &self,
+            recipient: &str,
+            message_id: &str,
+            new_content: &str,
+        ) -> Result<(), String> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("edit {} {} {}", recipient, message_id, new_content));
+            Ok(())
+        }
+
+        async fn send_typing(&self, recipient: &str) -> Result<(), String> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("typing {}", recipient));
+            Ok(())
+        }
+
+        async fn is_connected(&self) -> bool {
+            true
+        }
+    }
+
+    fn logged(event: RuntimeEvent) -> LoggedEvent {
+        LoggedEvent {
+            session_id: Some("session-1".to_string()),
+            sequence: None,
+            event,
+        }
+    }
+
+    fn tool_started(task_id: &str, name: &str) -> RuntimeEvent {
+        RuntimeEvent::ToolCallStarted {
+            task_id: task_id.to_string(),
+            request: ToolRequest {
+                tool_call_id: format!("call-{}", name),
+                name: name.to_string(),
+                input: serde_json::json!({}),
+            },
+        }
+    }
+
+    #[test]
+    fn test_task_progress() {
+        let mut progress = TaskProgress::default();
+        assert_eq!(progress.render(), "Working...");
+
+        progress.apply(&RuntimeEvent::IterationStarted {
+            task_id: "task-1".to_string(),
+            session_id: "session-1".to_string(),
+            iteration: 2,
+        });
+        progress.apply(&tool_started("task-1", "read_file"));
+        assert_eq!(progress.render(), "Working: iteration 2, running read_file");
+
+        progress.apply(&RuntimeEvent::TaskStateChanged {
+            task_id: "task-1".to_string(),
+            state: RuntimeTaskState::Completed,
+            previous_state: RuntimeTaskState::Running,
+        });
+        assert!(progress.is_finished());
+        assert_eq!(progress.render(), "Done (2 iterations, 1 tool call)");
+    }
+
+    #[tokio::test]
+    async fn test_progress_reporter() {
+        let adapter = Arc::new(RecordingAdapter {
+            id: "telegram".to_string(),
+            calls: Mutex::new(Vec::new()),
+        });
+        let reporter = ProgressReporter::new(adapter.clone())
+            .with_edit_interval(Duration::from_millis(20))
+            .with_typing_interval(Duration::from_secs(3600));
+        let (tx, rx) = broadcast::channel(16);
+
+        let events = async {
+            tx.send(logged(RuntimeEvent::IterationStarted {
+                task_id: "task-1".to_string(),
+                session_id: "session-1".to_string(),
+                iteration: 1,
+            }))
+            .unwrap();
+            // Other tasks' events are ignored
+            tx.send(logged(tool_started("task-2", "write_file")))
+                .unwrap();
+            tx.send(logged(tool_started("task-1", "read_file")))
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            tx.send(logged(RuntimeEvent::TaskStateChanged {
+                task_id: "task-1".to_string(),
+                state: RuntimeTaskState::Completed,
+                previous_state: RuntimeTaskState::Running,
+            }))
+            .unwrap();
+        };
+        let (message_id, ()) = tokio::join!(reporter.run("chat-1", "task-1", rx), events);
+
+        assert_eq!(message_id.unwrap(), "msg-1");
+        let calls = adapter.calls.lock().unwrap();
+        let edits: Vec<&String> = calls.iter().filter(|c| c.starts_with("edit")).collect();
+        assert_eq!(
+            edits,
+            [
+                "edit chat-1 msg-1 Working: iteration 1, running read_file",
+                "edit chat-1 msg-1 Done (1 iteration, 1 tool call)",
+            ]
+        );
+        assert_eq!(calls[0], "send chat-1 Working...");
+        assert_eq!(calls.iter().filter(|c| *c == "typing chat-1").count(), 1);
+    }
+}
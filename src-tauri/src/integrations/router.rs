@@ -0,0 +1,702 @@
+//! Chat Routing
+//!
+//! Carries messages from IM chats to the runtime and the agent's answers
+//! back. A message is first checked against its integration's access list,
+//! so nothing from a sender who may not use the agent reaches the runtime.
+//! A message that is let in is either a chat command, answered right away,
+//! or a prompt, which starts a task; the chat is answered with the task's
+//! last reply once it finishes, and asked to approve tool calls meanwhile.
+//! While the task runs, a progress message is kept up to date.
+//! A prompt replying to a message of a session continues that session.
+//! Everything sent to a chat goes through the integration's outbound queue.
+
+use crate::core::event_log::LoggedEvent;
+use crate::core::types::{RuntimeEvent, RuntimeTaskState, TaskInput};
+use crate::core::CoreRuntime;
+use crate::integrations::access::{AccessControl, InboundAccess};
+use crate::integrations::commands::{self, ChatCommand};
+use crate::integrations::outbound::{OutboundQueue, QueuedAdapter};
+use crate::integrations::progress::ProgressReporter;
+use crate::integrations::threads::{ThreadRoute, ThreadRouter};
+use crate::integrations::types::*;
+use crate::storage::{MessageContent, MessageRole, OutboxRepository, PendingApproval, Storage};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+
+/// Longest message sent at once; longer replies are split. Telegram takes
+/// 4096 characters.
+const MAX_MESSAGE_CHARS: usize = 4000;
+
+const PAIRED_REPLY: &str = "Paired. Messages you send here now go to the agent.";
+
+/// Routers of the integrations, and the access lists they share
+#[derive(Clone)]
+pub struct ChatRouting {
+    runtime: CoreRuntime,
+    access: AccessControl,
+    threads: ThreadRouter,
+    outbox: OutboxRepository,
+    /// Router of each integration, with the credentials its adapter uses
+    routers: Arc<Mutex<HashMap<IntegrationId, (String, Arc<ChatRouter>)>>>,
+}
+
+impl ChatRouting {
+    pub fn new(runtime: CoreRuntime, storage: &Storage) -> Self {
+        Self {
+            runtime,
+            access: AccessControl::new(storage.settings.clone()),
+            threads: ThreadRouter::new(storage.threads.clone()),
+            outbox: storage.outbox.clone(),
+            routers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Access lists of the integrations, and the pairing codes adding to them
+    pub fn access(&self) -> &AccessControl {
+        &self.access
+    }
+
+    /// Router of an integration. One is created with the adapter `connect`
+    /// returns when there is none, or when the integration's credentials
+    /// changed since; the router it replaces stops sending.
+    pub fn router(
+        &self,
+        integration_id: &str,
+        credentials: &str,
+        connect: impl FnOnce() -> Arc<dyn IntegrationAdapter>,
+    ) -> Arc<ChatRouter> {
+        let mut routers = self.routers.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((attached, router)) = routers.get(integration_id) {
+            if attached == credentials {
+                return router.clone();
+            }
+        }
+        let queue = Arc::new(OutboundQueue::new(connect(), self.outbox.clone()));
+        let router = Arc::new(ChatRouter {
+            runtime: self.runtime.clone(),
+            access: self.access.clone(),
+            threads: self.threads.clone(),
+            sender: queue.clone().spawn(),
+            adapter: Arc::new(QueuedAdapter::new(queue)),
+            chats: Mutex::new(HashMap::new()),
+        });
+        if let Some((_, replaced)) = routers.insert(
+            integration_id.to_string(),
+            (credentials.to_string(), router.clone()),
+        ) {
+            // Only one router sends an integration's queued messages
+            replaced.sender.abort();
+        }
+        router
+    }
+}
+
+/// Last task a chat started
+#[derive(Debug, Clone)]
+struct ChatTask {
+    session_id: String,
+    task_id: String,
+}
+
+/// Routes the messages of one integration
+pub struct ChatRouter {
+    runtime: CoreRuntime,
+    access: AccessControl,
+    threads: ThreadRouter,
+    /// Sends through the outbound queue
+    adapter: Arc<dyn IntegrationAdapter>,
+    /// Worker sending the queued messages
+    sender: JoinHandle<()>,
+    /// Last task of each chat, by chat ID, which commands act on
+    chats: Mutex<HashMap<String, ChatTask>>,
+}
+
+impl Drop for ChatRouter {
+    fn drop(&mut self) {
+        self.sender.abort();
+    }
+}
+
+impl ChatRouter {
+    /// Whether a message may be passed on to the agent. Senders redeeming a
+    /// pairing code are told they are paired; other messages not on the
+    /// access list are dropped without an answer.
+    pub async fn admit(&self, message: &IncomingMessage) -> bool {
+        match self.access.check(message).await {
+            Ok(InboundAccess::Allowed) => true,
+            Ok(InboundAccess::Paired) => {
+                self.reply(&message.chat_id, PAIRED_REPLY).await;
+                false
+            }
+            Ok(InboundAccess::Denied) => false,
+            Err(e) => {
+                log::warn!(
+                    "Dropped message from {} through integration {}: {}",
+                    message.sender_id,
+                    message.integration_id,
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    /// Handle a message if it is admitted, logging what fails
+    pub async fn receive(&self, message: IncomingMessage) {
+        if !self.admit(&message).await {
+            return;
+        }
+        let (chat_id, message_id) = (message.chat_id.clone(), message.message_id.clone());
+        if let Err(e) = self.handle(message).await {
+            log::warn!(
+                "Failed to handle message {} in chat {} of integration {}: {}",
+                message_id,
+                chat_id,
+                self.adapter.id(),
+                e
+            );
+        }
+    }
+
+    /// Run an admitted message's command, or start a task with it and answer
+    /// the chat with its reply once it finishes
+    pub async fn handle(&self, message: IncomingMessage) -> Result<(), String> {
+        let prompt = match ChatCommand::from_message(&message) {
+            None => message.content.clone(),
+            Some(ChatCommand::New {
+                prompt: Some(prompt),
+            }) => prompt,
+            Some(command) => {
+                let reply = self.run_command(&message, command).await;
+                self.reply(&message.chat_id, &reply).await;
+                return Ok(());
+            }
+        };
+
+        let session_id = match self.threads.route(&message).await? {
+            ThreadRoute::Continue(session_id) => session_id,
+            ThreadRoute::New => String::new(),
+        };
+
+        // Subscribed before the task starts, so none of its events are missed
+        let mut events = self.runtime.subscribe_events();
+        let progress_events = self.runtime.subscribe_events();
+        let handle = self
+            .runtime
+            .start_task(TaskInput {
+                session_id,
+                agent_id: None,
+                project_id: None,
+                initial_message: prompt,
+                settings: None,
+                workspace: None,
+                priority: 0,
+                isolate: false,
+                read_only_tools: false,
+            })
+            .await?;
+        self.chats.lock().unwrap_or_else(|e| e.into_inner()).insert(
+            message.chat_id.clone(),
+            ChatTask {
+                session_id: handle.session_id.clone(),
+                task_id: handle.task_id.clone(),
+            },
+        );
+        if let Err(e) = self
+            .threads
+            .link_incoming(&message, &handle.session_id)
+            .await
+        {
+            log::warn!("Failed to link message {}: {}", message.message_id, e);
+        }
+
+        let reporter = ProgressReporter::new(self.adapter.clone());
+        let (progress, reply) = tokio::join!(
+            reporter.run(&message.chat_id, &handle.task_id, progress_events),
+            self.task_reply(&message, &mut events, &handle.task_id, &handle.session_id)
+        );
+        match progress {
+            Ok(progress_id) => {
+                self.link_sent(&message.chat_id, &[progress_id], &handle.session_id)
+                    .await
+            }
+            Err(e) => log::warn!("Failed to report progress to {}: {}", message.chat_id, e),
+        }
+
+        let sent = self.reply(&message.chat_id, &reply?).await;
+        self.link_sent(&message.chat_id, &sent, &handle.session_id)
+            .await;
+        Ok(())
+    }
+
+    /// Answer to a command
+    async fn run_command(&self, message: &IncomingMessage, command: ChatCommand) -> String {
+        let prefix = commands::command_prefix(message.channel_type);
+        let chat = self
+            .chats
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&message.chat_id)
+            .cloned();
+        match command {
+            ChatCommand::New { .. } => "Send a message to start a new session.".to_string(),
+            ChatCommand::Status => match chat {
+                Some(chat) => self.status(&chat).await,
+                None => "No task has run in this chat yet.".to_string(),
+            },
+            ChatCommand::Cancel => {
+                let Some(chat) = chat else {
+                    return "No task is running.".to_string();
+                };
+                if self.runtime.get_task(&chat.task_id).await.is_none() {
+                    return "No task is running.".to_string();
+                }
+                match self.runtime.cancel_task(&chat.task_id).await {
+                    Ok(()) => "Cancelling the task.".to_string(),
+                    Err(e) => format!("Could not cancel the task: {}", e),
+                }
+            }
+            ChatCommand::Approve { tool_call_id } => {
+                self.decide(chat, tool_call_id, true, prefix).await
+            }
+            ChatCommand::Deny { tool_call_id } => {
+                self.decide(chat, tool_call_id, false, prefix).await
+            }
+            ChatCommand::Pair { .. } => "This chat already has access to the agent.".to_string(),
+            ChatCommand::Help => commands::help(message.channel_type),
+            ChatCommand::Unknown(name) => format!(
+                "Unknown command {}{}. Send {}help for the commands.",
+                prefix, name, prefix
+            ),
+        }
+    }
+
+    /// State of a chat's last task, and the tool calls it waits on
+    async fn status(&self, chat: &ChatTask) -> String {
+        let Some(handle) = self.runtime.get_task(&chat.task_id).await else {
+            return format!(
+                "No task is running. The last one was in session {}.",
+                chat.session_id
+            );
+        };
+        let state = format!("{:?}", *handle.state.read().await).to_lowercase();
+        let mut status = format!(
+            "Task {} in session {} is {}.",
+            chat.task_id, chat.session_id, state
+        );
+        if let Ok(pending) = self
+            .runtime
+            .list_pending_approvals(Some(&chat.session_id))
+            .await
+        {
+            for approval in pending {
+                status.push_str(&format!(
+                    "\nWaiting for approval: {} ({})",
+                    approval.tool_call.name, approval.tool_call_id
+                ));
+            }
+        }
+        status
+    }
+
+    /// Approve or deny a tool call the chat's session waits on; without an
+    /// ID, the only one it waits on
+    async fn decide(
+        &self,
+        chat: Option<ChatTask>,
+        tool_call_id: Option<String>,
+        approve: bool,
+        prefix: char,
+    ) -> String {
+        let pending = match &chat {
+            Some(chat) => {
+                match self
+                    .runtime
+                    .list_pending_approvals(Some(&chat.session_id))
+                    .await
+                {
+                    Ok(pending) => pending,
+                    Err(e) => return format!("Could not list the pending tool calls: {}", e),
+                }
+            }
+            None => Vec::new(),
+        };
+        let approval = match pick_approval(&pending, tool_call_id.as_deref()) {
+            Ok(approval) => approval,
+            Err(e) => return e,
+        };
+
+        let (result, done) = if approve {
+            (
+                self.runtime.approve_tool_call(&approval.tool_call_id).await,
+                "Approved",
+            )
+        } else {
+            (
+                self.runtime
+                    .deny_tool_call(&approval.tool_call_id, None)
+                    .await,
+                "Denied",
+            )
+        };
+        match result {
+            Ok(_) => format!("{} {}.", done, approval.tool_call.name),
+            Err(e) => format!(
+                "Could not decide on {}: {}. Send {}status to see what is pending.",
+                approval.tool_call_id, e, prefix
+            ),
+        }
+    }
+
+    /// Wait for a task to finish, returning what to answer the chat with:
+    /// the agent's last reply, or why there is none. The chat is asked to
+    /// approve each tool call the task waits on.
+    async fn task_reply(
+        &self,
+        incoming: &IncomingMessage,
+        events: &mut broadcast::Receiver<LoggedEvent>,
+        task_id: &str,
+        session_id: &str,
+    ) -> Result<String, String> {
+        let prefix = commands::command_prefix(incoming.channel_type);
+        let mut reply: Option<String> = None;
+        let mut error: Option<String> = None;
+        loop {
+            let logged = match events.recv().await {
+                Ok(logged) => logged,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return Err("Runtime events closed".to_string()),
+            };
+            match logged.event {
+                RuntimeEvent::MessageCreated {
+                    session_id: message_session,
+                    message,
+                } if message_session == session_id && message.role == MessageRole::Assistant => {
+                    if let MessageContent::Text { text } = message.content {
+                        if !text.trim().is_empty() {
+                            reply = Some(text);
+                        }
+                    }
+                }
+                RuntimeEvent::ToolCallRequested {
+                    task_id: id,
+                    request,
+                } if id == task_id => {
+                    let ask = format!(
+                        "The agent wants to run {} ({}). Send {}approve or {}deny.",
+                        request.name, request.tool_call_id, prefix, prefix
+                    );
+                    let sent = self.reply(&incoming.chat_id, &ask).await;
+                    self.link_sent(&incoming.chat_id, &sent, session_id).await;
+                }
+                RuntimeEvent::Error {
+                    task_id: Some(id),
+                    message,
+                    ..
+                } if id == task_id => error = Some(message),
+                RuntimeEvent::TaskStateChanged {
+                    task_id: id, state, ..
+                } if id == task_id => match state {
+                    RuntimeTaskState::Completed => {
+                        return Ok(reply.unwrap_or_else(|| "Done.".to_string()))
+                    }
+                    RuntimeTaskState::Failed => {
+                        return Ok(match error {
+                            Some(error) => format!("The task failed: {}", error),
+                            None => "The task failed.".to_string(),
+                        })
+                    }
+                    RuntimeTaskState::Cancelled => return Ok("The task was cancelled.".to_string()),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Send a reply, split into messages the channel accepts, returning the
+    /// IDs of the messages sent
+    async fn reply(&self, chat_id: &str, text: &str) -> Vec<MessageId> {
+        let mut sent = Vec::new();
+        for part in split_message(text, MAX_MESSAGE_CHARS) {
+            match self.adapter.send_message(chat_id, &part).await {
+                Ok(message_id) => sent.push(message_id),
+                Err(e) => {
+                    log::warn!(
+                        "Failed to reply to {} through integration {}: {}",
+                        chat_id,
+                        self.adapter.id(),
+                        e
+                    );
+                    break;
+                }
+            }
+        }
+        sent
+    }
+
+    /// Link messages sent to a chat to their session, so replying to them
+    /// continues it
+    async fn link_sent(&self, chat_id: &str, message_ids: &[MessageId], session_id: &str) {
+        for message_id in message_ids {
+            if let Err(e) = self
+                .threads
+                .link_sent(self.adapter.id(), chat_id, message_id, session_id)
+                .await
+            {
+                log::warn!("Failed to link message {}: {}", message_id, e);
+            }
+        }
+    }
+}
+
+/// Tool call of `pending` to decide on: the one named, or the only one
+fn pick_approval<'a>(
+    pending: &'a [PendingApproval],
+    tool_call_id: Option<&str>,
+) -> Result<&'a PendingApproval, String> {
+    match tool_call_id {
+        Some(id) => pending
+            .iter()
+            .find(|approval| approval.tool_call_id == id)
+            .ok_or_else(|| format!("No tool call {} is waiting for approval.", id)),
+        None => match pending {
+            [] => Err("No tool call is waiting for approval.".to_string()),
+            [approval] => Ok(approval),
+            _ => Err(format!(
+                "Several tool calls are waiting for approval; name one of: {}",
+                pending
+                    .iter()
+                    .map(|approval| approval.tool_call_id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        },
+    }
+}
+
+/// Split text into parts of at most `max_chars` characters, at line breaks
+/// where there are any
+fn split_message(text: &str, max_chars: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while rest.chars().count() > max_chars {
+        let limit = rest
+            .char_indices()
+            .nth(max_chars)
+            .map_or(rest.len(), |(index, _)| index);
+        let split = match rest[..limit].rfind('\n') {
+            Some(newline) if newline > 0 => newline,
+            _ => limit,
+        };
+        parts.push(rest[..split].to_string());
+        rest = rest[split..].strip_prefix('\n').unwrap_or(&rest[split..]);
+    }
+    parts.push(rest.to_string());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::LlmClient;
+    use crate::llm::types::{StreamEvent, StreamTextRequest};
+    use crate::server::config::ServerConfig;
+    use crate::server::state::ServerStateFactory;
+    use tempfile::TempDir;
+
+    /// LLM client that answers every request with a fixed text response
+    struct FixedResponseLlm;
+
+    #[async_trait::async_trait]
+    impl LlmClient for FixedResponseLlm {
+        async fn stream(
+            &self,
+            _request: StreamTextRequest,
+            on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            on_event(StreamEvent::TextDelta {
+                text: "Hello from the agent".to_string(),
+            });
+            on_event(StreamEvent::Done {
+                finish_reason: Some("stop".to_string()),
+            });
+            Ok(())
+        }
+    }
+
+    /// Adapter that records the messages it sends
+    struct RecordingAdapter {
+        id: IntegrationId,
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl IntegrationAdapter for RecordingAdapter {
+        fn id(&self) -> &IntegrationId {
+            &self.id
+        }
+
+        fn channel_type(&self) -> ChannelType {
+            ChannelType::Telegram
+        }
+
+        async fn start(&self) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn stop(&self) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn send_message(&self, recipient: &str, content: &str) -> Result<MessageId, String> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push(format!("{}: {}", recipient, content));
+            Ok("msg-1".to_string())
+        }
+
+        async fn edit_message(
+            &self,
+            _recipient: &str,
+            _message_id: &str,
+            _new_content: &str,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+
+        async fn is_connected(&self) -> bool {
+            true
+        }
+    }
+
+    fn incoming(sender_id: &str, content: &str) -> IncomingMessage {
+        IncomingMessage {
+            integration_id: "telegram".to_string(),
+            channel_type: ChannelType::Telegram,
+            sender_id: sender_id.to_string(),
+            sender_name: None,
+            chat_id: format!("chat-{}", sender_id),
+            message_id: "1".to_string(),
+            content: content.to_string(),
+            timestamp: 0,
+            reply_to: None,
+            media: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admit_and_answer() {
+        let temp_dir = TempDir::new().unwrap();
+        let config =
+            ServerConfig::new(temp_dir.path().to_path_buf(), temp_dir.path().to_path_buf());
+        let (event_tx, _event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let state = ServerStateFactory::create(config, Arc::new(FixedResponseLlm), event_tx)
+            .await
+            .unwrap();
+        let adapter = Arc::new(RecordingAdapter {
+            id: "telegram".to_string(),
+            sent: Mutex::new(Vec::new()),
+        });
+        let router = state
+            .routing
+            .router("telegram", "token", || adapter.clone());
+        // The same credentials get the same router
+        assert!(Arc::ptr_eq(
+            &router,
+            &state
+                .routing
+                .router("telegram", "token", || adapter.clone())
+        ));
+
+        // Strangers are dropped until they pair
+        let prompt = incoming("1001", "say hello");
+        assert!(!router.admit(&prompt).await);
+        let pairing = state.routing.access().create_pairing("telegram");
+        assert!(
+            !router
+                .admit(&incoming("1001", &format!("/pair {}", pairing.code)))
+                .await
+        );
+        assert!(router.admit(&prompt).await);
+
+        router.handle(prompt).await.unwrap();
+        // Replying to the answer continues its session
+        let session_id = state
+            .storage
+            .threads
+            .session_for("telegram", "chat-1001", "msg-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            state
+                .storage
+                .threads
+                .session_for("telegram", "chat-1001", "1")
+                .await
+                .unwrap(),
+            Some(session_id)
+        );
+        assert_eq!(
+            *adapter.sent.lock().unwrap(),
+            vec![
+                format!("chat-1001: {}", PAIRED_REPLY),
+                "chat-1001: Working...".to_string(),
+                "chat-1001: Hello from the agent".to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chat_commands() {
+        let temp_dir = TempDir::new().unwrap();
+        let config =
+            ServerConfig::new(temp_dir.path().to_path_buf(), temp_dir.path().to_path_buf());
+        let (event_tx, _event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let state = ServerStateFactory::create(config, Arc::new(FixedResponseLlm), event_tx)
+            .await
+            .unwrap();
+        let adapter = Arc::new(RecordingAdapter {
+            id: "telegram".to_string(),
+            sent: Mutex::new(Vec::new()),
+        });
+        let router = state
+            .routing
+            .router("telegram", "token", || adapter.clone());
+
+        for command in [
+            "/status",
+            "/approve",
+            "/pair 123456",
+            "/frobnicate",
+            "/help",
+        ] {
+            router.handle(incoming("1001", command)).await.unwrap();
+        }
+        assert_eq!(
+            *adapter.sent.lock().unwrap(),
+            vec![
+                "chat-1001: No task has run in this chat yet.".to_string(),
+                "chat-1001: No tool call is waiting for approval.".to_string(),
+                "chat-1001: This chat already has access to the agent.".to_string(),
+                "chat-1001: Unknown command /frobnicate. Send /help for the commands.".to_string(),
+                format!("chat-1001: {}", commands::help(ChannelType::Telegram)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_message() {
+        assert_eq!(split_message("short", 10), vec!["short"]);
+        assert_eq!(
+            split_message("line one\nline two", 12),
+            vec!["line one", "line two"]
+        );
+        assert_eq!(split_message("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(split_message("äöüäöü", 4), vec!["äöüä", "öü"]);
+    }
+}
@@ -1,23 +1,42 @@
 //! Telegram Integration Adapter
 //!
-//! Wraps existing telegram_gateway.rs for cloud backend integration.
+//! Wraps existing telegram_gateway.rs for cloud backend integration, and
+//! answers chats through the Bot API.
 
 use crate::integrations::types::*;
-use std::sync::Arc;
+use crate::integrations::verification::{verify_telegram_secret, ReplayGuard};
+use axum::http::HeaderMap;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
 use tokio::sync::RwLock;
 
+/// Bot API server; a local Bot API server can stand in for it
+const TELEGRAM_API_BASE: &str = "https://api.telegram.org";
+
+/// How long update IDs are remembered. Telegram keeps redelivering an
+/// update it got no answer to for up to 24 hours.
+const UPDATE_RETRY_WINDOW_SECS: i64 = 24 * 60 * 60;
+
 /// Telegram adapter configuration
 #[derive(Debug, Clone)]
 pub struct TelegramConfig {
     pub bot_token: String,
     pub webhook_url: Option<String>,
+    /// Secret given to `setWebhook`, which Telegram sends with each update;
+    /// webhooks are refused without one
+    pub secret_token: Option<String>,
 }
 
 /// Telegram integration adapter
 pub struct TelegramAdapter {
     id: IntegrationId,
     config: TelegramConfig,
+    client: Client,
+    api_base: String,
     connected: RwLock<bool>,
+    replay_guard: ReplayGuard,
 }
 
 impl TelegramAdapter {
@@ -25,10 +44,19 @@ impl TelegramAdapter {
         Self {
             id: id.into(),
             config,
+            client: Client::new(),
+            api_base: TELEGRAM_API_BASE.to_string(),
             connected: RwLock::new(false),
+            replay_guard: ReplayGuard::default().remembering(UPDATE_RETRY_WINDOW_SECS),
         }
     }
 
+    /// Send Bot API calls to another server, such as a local Bot API server
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into();
+        self
+    }
+
     /// Create adapter from existing gateway state
     pub fn from_gateway(id: impl Into<IntegrationId>) -> Self {
         Self {
@@ -36,12 +64,146 @@ impl TelegramAdapter {
             config: TelegramConfig {
                 bot_token: String::new(),
                 webhook_url: None,
+                secret_token: None,
             },
+            client: Client::new(),
+            api_base: TELEGRAM_API_BASE.to_string(),
             connected: RwLock::new(false),
+            replay_guard: ReplayGuard::default().remembering(UPDATE_RETRY_WINDOW_SECS),
+        }
+    }
+
+    /// Verify a webhook delivery and parse the message it carries. Updates
+    /// already received are refused; other updates yield no messages. The
+    /// message date is not signed, so only the update ID is checked.
+    pub fn receive_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &str,
+    ) -> Result<Vec<IncomingMessage>, String> {
+        let secret_token = self
+            .config
+            .secret_token
+            .as_deref()
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| "Telegram webhooks need a secret token".to_string())?;
+        verify_telegram_secret(headers, secret_token)?;
+
+        let update: TelegramUpdate =
+            serde_json::from_str(body).map_err(|e| format!("Invalid Telegram update: {}", e))?;
+        self.replay_guard
+            .check_once(&update.update_id.to_string())?;
+        let Some(message) = update.message else {
+            return Ok(Vec::new());
+        };
+        let Some(content) = message.text.or(message.caption) else {
+            return Ok(Vec::new());
+        };
+
+        let sender = message.from;
+        Ok(vec![IncomingMessage {
+            integration_id: self.id.clone(),
+            channel_type: ChannelType::Telegram,
+            sender_id: sender
+                .as_ref()
+                .map(|user| user.id.to_string())
+                .unwrap_or_default(),
+            sender_name: sender.map(|user| match user.username {
+                Some(username) => username,
+                None => user.first_name,
+            }),
+            chat_id: message.chat.id.to_string(),
+            message_id: message.message_id.to_string(),
+            content,
+            timestamp: message.date * 1000,
+            reply_to: message
+                .reply_to_message
+                .map(|reply| reply.message_id.to_string()),
+            media: Vec::new(),
+        }])
+    }
+
+    /// Call a Bot API method, returning its result
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: &Value) -> Result<T, String> {
+        if self.config.bot_token.is_empty() {
+            return Err("Telegram bot token is not configured".to_string());
         }
+        let url = format!(
+            "{}/bot{}/{}",
+            self.api_base.trim_end_matches('/'),
+            self.config.bot_token,
+            method
+        );
+        // The URL holds the bot token; keep it out of errors
+        let response = self
+            .client
+            .post(url)
+            .json(params)
+            .send()
+            .await
+            .map_err(|e| format!("Telegram {} failed: {}", method, e.without_url()))?;
+        let payload: BotApiResponse<T> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse {} response: {}", method, e.without_url()))?;
+        if !payload.ok {
+            return Err(payload
+                .description
+                .unwrap_or_else(|| format!("Telegram {} returned ok=false", method)));
+        }
+        payload
+            .result
+            .ok_or_else(|| format!("Telegram {} returned no result", method))
     }
 }
 
+#[derive(Deserialize)]
+struct BotApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SentMessage {
+    message_id: i64,
+}
+
+#[derive(Deserialize)]
+struct TelegramUpdate {
+    update_id: i64,
+    message: Option<TelegramMessage>,
+}
+
+#[derive(Deserialize)]
+struct TelegramMessage {
+    message_id: i64,
+    from: Option<TelegramUser>,
+    chat: TelegramChat,
+    /// Seconds
+    date: i64,
+    text: Option<String>,
+    caption: Option<String>,
+    reply_to_message: Option<TelegramReply>,
+}
+
+#[derive(Deserialize)]
+struct TelegramUser {
+    id: i64,
+    first_name: String,
+    username: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TelegramChat {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct TelegramReply {
+    message_id: i64,
+}
+
 #[async_trait::async_trait]
 impl IntegrationAdapter for TelegramAdapter {
     fn id(&self) -> &IntegrationId {
@@ -70,22 +232,50 @@ impl IntegrationAdapter for TelegramAdapter {
     }
 
     async fn send_message(&self, recipient: &str, content: &str) -> Result<MessageId, String> {
-        // Use existing telegram_gateway functionality
-        // This is a placeholder - real implementation would call telegram_gateway
-
-        let message_id = format!("tg_msg_{}", uuid::Uuid::new_v4());
-        Ok(message_id)
+        let sent: SentMessage = self
+            .call(
+                "sendMessage",
+                &json!({
+                    "chat_id": recipient,
+                    "text": content,
+                    "disable_web_page_preview": true,
+                }),
+            )
+            .await?;
+        Ok(sent.message_id.to_string())
     }
 
     async fn edit_message(
         &self,
-        _recipient: &str,
+        recipient: &str,
         message_id: &str,
         new_content: &str,
     ) -> Result<(), String> {
-        // Edit message via existing telegram_gateway
-        // Placeholder implementation
-        let _ = (message_id, new_content);
+        let message_id = message_id
+            .parse::<i64>()
+            .map_err(|_| format!("Invalid Telegram message ID '{}'", message_id))?;
+        // The edited message, or `true` for inline messages
+        let _: Value = self
+            .call(
+                "editMessageText",
+                &json!({
+                    "chat_id": recipient,
+                    "message_id": message_id,
+                    "text": new_content,
+                    "disable_web_page_preview": true,
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn send_typing(&self, recipient: &str) -> Result<(), String> {
+        let _: bool = self
+            .call(
+                "sendChatAction",
+                &json!({ "chat_id": recipient, "action": "typing" }),
+            )
+            .await?;
         Ok(())
     }
 
@@ -97,12 +287,16 @@ impl IntegrationAdapter for TelegramAdapter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axum::extract::{Path, State};
+    use axum::Json;
+    use std::sync::{Arc, Mutex};
 
     #[tokio::test]
     async fn test_telegram_adapter_creation() {
         let config = TelegramConfig {
             bot_token: "test_token".to_string(),
             webhook_url: None,
+            secret_token: None,
         };
 
         let adapter = TelegramAdapter::new("telegram-1", config);
@@ -116,6 +310,7 @@ mod tests {
         let config = TelegramConfig {
             bot_token: "test_token".to_string(),
             webhook_url: None,
+            secret_token: None,
         };
 
         let adapter = TelegramAdapter::new("telegram-1", config);
@@ -126,4 +321,125 @@ mod tests {
         adapter.stop().await.expect("Failed to stop");
         assert!(!adapter.is_connected().await);
     }
+
+    fn update(update_id: i64, date: i64) -> String {
+        serde_json::json!({
+            "update_id": update_id,
+            "message": {
+                "message_id": 7,
+                "from": {"id": 1001, "first_name": "Ada", "username": "ada"},
+                "chat": {"id": -500},
+                "date": date,
+                "text": "run the tests",
+                "reply_to_message": {"message_id": 6}
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_receive_webhook() {
+        let config = TelegramConfig {
+            bot_token: "test_token".to_string(),
+            webhook_url: None,
+            secret_token: Some("s3cret".to_string()),
+        };
+        let adapter = TelegramAdapter::new("telegram-1", config);
+        let now = chrono::Utc::now().timestamp();
+
+        let mut headers = HeaderMap::new();
+        assert!(adapter.receive_webhook(&headers, &update(42, now)).is_err());
+        headers.insert("x-telegram-bot-api-secret-token", "s3cret".parse().unwrap());
+        let messages = adapter.receive_webhook(&headers, &update(42, now)).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].sender_id, "1001");
+        assert_eq!(messages[0].sender_name.as_deref(), Some("ada"));
+        assert_eq!(messages[0].chat_id, "-500");
+        assert_eq!(messages[0].content, "run the tests");
+        assert_eq!(messages[0].reply_to.as_deref(), Some("6"));
+
+        // Replayed, then redelivered late after the webhook was down
+        assert!(adapter.receive_webhook(&headers, &update(42, now)).is_err());
+        assert!(adapter
+            .receive_webhook(&headers, &update(43, now - 3600))
+            .is_ok());
+        assert!(adapter
+            .receive_webhook(&headers, &update(43, now - 3600))
+            .is_err());
+    }
+
+    type Calls = Arc<Mutex<Vec<(String, Value)>>>;
+
+    async fn bot_api(
+        State(calls): State<Calls>,
+        Path((token, method)): Path<(String, String)>,
+        Json(params): Json<Value>,
+    ) -> Json<Value> {
+        if token != "bottest_token" {
+            return Json(json!({"ok": false, "description": "Unauthorized"}));
+        }
+        calls.lock().unwrap().push((method.clone(), params));
+        Json(match method.as_str() {
+            "sendMessage" | "editMessageText" => json!({"ok": true, "result": {"message_id": 42}}),
+            "sendChatAction" => json!({"ok": true, "result": true}),
+            _ => json!({"ok": false, "description": "Not Found"}),
+        })
+    }
+
+    /// A Bot API server recording the calls it gets
+    async fn mock_bot_api() -> (String, Calls) {
+        let calls = Calls::default();
+        let app = axum::Router::new()
+            .route("/:token/:method", axum::routing::post(bot_api))
+            .with_state(calls.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, calls)
+    }
+
+    #[tokio::test]
+    async fn test_send_edit_and_typing() {
+        let (api_base, calls) = mock_bot_api().await;
+        let config = TelegramConfig {
+            bot_token: "test_token".to_string(),
+            webhook_url: None,
+            secret_token: None,
+        };
+        let adapter = TelegramAdapter::new("telegram-1", config.clone()).with_api_base(&api_base);
+
+        let message_id = adapter.send_message("-500", "Working...").await.unwrap();
+        assert_eq!(message_id, "42");
+        adapter
+            .edit_message("-500", &message_id, "Done")
+            .await
+            .unwrap();
+        adapter.send_typing("-500").await.unwrap();
+        assert!(adapter.edit_message("-500", "tg_1", "Done").await.is_err());
+
+        let calls = calls.lock().unwrap().clone();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].0, "sendMessage");
+        assert_eq!(calls[0].1["chat_id"], "-500");
+        assert_eq!(calls[0].1["text"], "Working...");
+        assert_eq!(calls[1].0, "editMessageText");
+        assert_eq!(calls[1].1["message_id"], 42);
+        assert_eq!(calls[1].1["text"], "Done");
+        assert_eq!(calls[2].0, "sendChatAction");
+        assert_eq!(calls[2].1["action"], "typing");
+
+        // API errors are reported with their description
+        let adapter = TelegramAdapter::new(
+            "telegram-1",
+            TelegramConfig {
+                bot_token: "wrong".to_string(),
+                ..config
+            },
+        )
+        .with_api_base(&api_base);
+        assert_eq!(
+            adapter.send_message("-500", "hi").await.unwrap_err(),
+            "Unauthorized"
+        );
+    }
 }
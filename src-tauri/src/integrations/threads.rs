@@ -0,0 +1,148 @@
+//! Threads to Sessions
+//!
+//! A reply to a message continues the session that message belongs to; a
+//! message that replies to nothing, or to a message of no known session,
+//! starts a new one. Incoming messages and the replies sent to them are
+//! linked to their session, so a reply to either continues it.
+
+use crate::integrations::types::*;
+use crate::storage::ThreadsRepository;
+
+/// Session an incoming message goes to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThreadRoute {
+    /// Continue the session of the message replied to
+    Continue(String),
+    /// Start a new session
+    New,
+}
+
+/// Routes incoming messages to sessions by their reply chains
+#[derive(Clone)]
+pub struct ThreadRouter {
+    threads: ThreadsRepository,
+}
+
+impl ThreadRouter {
+    pub fn new(threads: ThreadsRepository) -> Self {
+        Self { threads }
+    }
+
+    /// Session an incoming message goes to
+    pub async fn route(&self, message: &IncomingMessage) -> Result<ThreadRoute, String> {
+        let Some(reply_to) = &message.reply_to else {
+            return Ok(ThreadRoute::New);
+        };
+        Ok(self
+            .threads
+            .session_for(&message.integration_id, &message.chat_id, reply_to)
+            .await?
+            .map_or(ThreadRoute::New, ThreadRoute::Continue))
+    }
+
+    /// Link an incoming message to the session it went to
+    pub async fn link_incoming(
+        &self,
+        message: &IncomingMessage,
+        session_id: &str,
+    ) -> Result<(), String> {
+        self.threads
+            .link(
+                &message.integration_id,
+                &message.chat_id,
+                &message.message_id,
+                session_id,
+            )
+            .await
+    }
+
+    /// Link a message sent to a chat to the session it came from
+    pub async fn link_sent(
+        &self,
+        integration_id: &str,
+        chat_id: &str,
+        message_id: &str,
+        session_id: &str,
+    ) -> Result<(), String> {
+        self.threads
+            .link(integration_id, chat_id, message_id, session_id)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::storage::migrations::{chat_history_migrations, MigrationRunner};
+    use crate::storage::{ChatHistoryRepository, Session, SessionStatus};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn incoming(message_id: &str, reply_to: Option<&str>) -> IncomingMessage {
+        IncomingMessage {
+            integration_id: "telegram".to_string(),
+            channel_type: ChannelType::Telegram,
+            sender_id: "1001".to_string(),
+            sender_name: None,
+            chat_id: "chat-1".to_string(),
+            message_id: message_id.to_string(),
+            content: "hello".to_string(),
+            timestamp: 0,
+            reply_to: reply_to.map(str::to_string),
+            media: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_thread_routes() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect().await.unwrap();
+        let migrations = chat_history_migrations();
+        let runner = MigrationRunner::new(&db, &migrations);
+        runner.init().await.unwrap();
+        runner.migrate().await.unwrap();
+        ChatHistoryRepository::new(db.clone())
+            .create_session(&Session {
+                id: "session-1".to_string(),
+                project_id: None,
+                title: None,
+                summary: None,
+                status: SessionStatus::Created,
+                created_at: 0,
+                updated_at: 0,
+                last_event_id: None,
+                metadata: None,
+                starred: false,
+                archived_at: None,
+                user_id: None,
+            })
+            .await
+            .unwrap();
+        let router = ThreadRouter::new(ThreadsRepository::new(db));
+
+        let first = incoming("1", None);
+        assert_eq!(router.route(&first).await.unwrap(), ThreadRoute::New);
+        router.link_incoming(&first, "session-1").await.unwrap();
+        router
+            .link_sent("telegram", "chat-1", "2", "session-1")
+            .await
+            .unwrap();
+
+        // Replies to the question or to the answer continue the session
+        let continued = ThreadRoute::Continue("session-1".to_string());
+        let reply = incoming("3", Some("2"));
+        assert_eq!(router.route(&reply).await.unwrap(), continued);
+        router.link_incoming(&reply, "session-1").await.unwrap();
+        assert_eq!(
+            router.route(&incoming("4", Some("3"))).await.unwrap(),
+            continued
+        );
+        assert_eq!(
+            router.route(&incoming("5", Some("99"))).await.unwrap(),
+            ThreadRoute::New
+        );
+    }
+}
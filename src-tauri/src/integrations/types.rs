@@ -16,6 +16,8 @@ pub enum ChannelType {
     Slack,
     Discord,
     WhatsApp,
+    Matrix,
+    Email,
 }
 
 impl ChannelType {
@@ -26,6 +28,8 @@ impl ChannelType {
             ChannelType::Slack => "slack",
             ChannelType::Discord => "discord",
             ChannelType::WhatsApp => "whatsapp",
+            ChannelType::Matrix => "matrix",
+            ChannelType::Email => "email",
         }
     }
 }
@@ -56,6 +60,12 @@ pub trait IntegrationAdapter: Send + Sync {
         new_content: &str,
     ) -> Result<(), String>;
 
+    /// Show the recipient that a reply is being worked on, on channels with a
+    /// typing indicator
+    async fn send_typing(&self, _recipient: &str) -> Result<(), String> {
+        Ok(())
+    }
+
     /// Check if the integration is connected
     async fn is_connected(&self) -> bool;
 }
@@ -76,6 +86,19 @@ pub struct IncomingMessage {
     pub content: String,
     pub timestamp: i64,
     pub reply_to: Option<MessageId>,
+    #[serde(default)]
+    pub media: Vec<IncomingMedia>,
+}
+
+/// Media attached to an incoming message, downloaded on demand through the
+/// adapter that received it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncomingMedia {
+    pub media_id: String,
+    pub mime_type: Option<String>,
+    pub filename: Option<String>,
+    pub caption: Option<String>,
 }
 
 /// Outgoing message to an integration
@@ -0,0 +1,247 @@
+//! Inbound Webhook Verification
+//!
+//! Checks that a webhook delivery comes from the channel it claims to, and
+//! refuses deliveries that are stale or were already received.
+
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use axum::http::HeaderMap;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::security::api_keys::constant_time_eq;
+
+/// Oldest delivery accepted, in seconds; also how far ahead of the local
+/// clock a delivery may be dated
+pub const MAX_WEBHOOK_AGE_SECS: i64 = 300;
+
+const TELEGRAM_SECRET_HEADER: &str = "x-telegram-bot-api-secret-token";
+const FEISHU_TIMESTAMP_HEADER: &str = "x-lark-request-timestamp";
+const FEISHU_NONCE_HEADER: &str = "x-lark-request-nonce";
+const FEISHU_SIGNATURE_HEADER: &str = "x-lark-signature";
+
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+/// Remembers the deliveries received within the accepted age, so a replayed
+/// delivery is refused
+pub struct ReplayGuard {
+    max_age_secs: i64,
+    /// How long a delivery is remembered, at least the accepted age
+    remember_secs: i64,
+    /// Delivery ID to the time it was dated, in seconds
+    seen: Mutex<HashMap<String, i64>>,
+}
+
+impl Default for ReplayGuard {
+    fn default() -> Self {
+        Self::new(MAX_WEBHOOK_AGE_SECS)
+    }
+}
+
+impl ReplayGuard {
+    pub fn new(max_age_secs: i64) -> Self {
+        Self {
+            max_age_secs,
+            remember_secs: max_age_secs,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Remember deliveries for `secs`, for channels that send a delivery
+    /// again later than the accepted age
+    pub fn remembering(mut self, secs: i64) -> Self {
+        self.remember_secs = secs.max(self.max_age_secs);
+        self
+    }
+
+    /// Accept a delivery dated `timestamp` (seconds) once, if it is not stale
+    pub fn check(&self, delivery_id: &str, timestamp: i64) -> Result<(), String> {
+        self.check_at(delivery_id, timestamp, chrono::Utc::now().timestamp())
+    }
+
+    /// Accept a delivery once, whatever it is dated; for deliveries whose
+    /// date is not signed and so may not be trusted
+    pub fn check_once(&self, delivery_id: &str) -> Result<(), String> {
+        let now = chrono::Utc::now().timestamp();
+        self.remember(delivery_id, now, now)
+    }
+
+    fn check_at(&self, delivery_id: &str, timestamp: i64, now: i64) -> Result<(), String> {
+        if (now - timestamp).abs() > self.max_age_secs {
+            return Err(format!(
+                "Webhook delivery {} is dated {}s from now",
+                delivery_id,
+                timestamp - now
+            ));
+        }
+
+        self.remember(delivery_id, timestamp, now)
+    }
+
+    fn remember(&self, delivery_id: &str, timestamp: i64, now: i64) -> Result<(), String> {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, dated| now - *dated <= self.remember_secs);
+        if seen.insert(delivery_id.to_string(), timestamp).is_some() {
+            return Err(format!(
+                "Webhook delivery {} was already received",
+                delivery_id
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Check the secret token Telegram echoes from `setWebhook`
+pub fn verify_telegram_secret(headers: &HeaderMap, secret_token: &str) -> Result<(), String> {
+    let received = header(headers, TELEGRAM_SECRET_HEADER)
+        .ok_or_else(|| "Telegram webhook has no secret token".to_string())?;
+    if !constant_time_eq(received.as_bytes(), secret_token.as_bytes()) {
+        return Err("Telegram webhook secret token does not match".to_string());
+    }
+    Ok(())
+}
+
+/// Check the signature of a Feishu event delivery, the SHA-256 of its
+/// timestamp, nonce, encrypt key and body. Returns the timestamp (seconds)
+/// and nonce it was signed with.
+pub fn verify_feishu_signature(
+    headers: &HeaderMap,
+    encrypt_key: &str,
+    body: &str,
+) -> Result<(i64, String), String> {
+    let (Some(timestamp), Some(nonce), Some(signature)) = (
+        header(headers, FEISHU_TIMESTAMP_HEADER),
+        header(headers, FEISHU_NONCE_HEADER),
+        header(headers, FEISHU_SIGNATURE_HEADER),
+    ) else {
+        return Err("Feishu webhook is not signed".to_string());
+    };
+
+    let expected = feishu_signature(timestamp, nonce, encrypt_key, body);
+    if !constant_time_eq(signature.as_bytes(), expected.as_bytes()) {
+        return Err("Feishu webhook signature does not match".to_string());
+    }
+    let timestamp = timestamp
+        .parse::<i64>()
+        .map_err(|_| format!("Invalid Feishu webhook timestamp: {}", timestamp))?;
+    Ok((timestamp, nonce.to_string()))
+}
+
+/// Decrypt the `encrypt` field of a Feishu delivery: AES-256-CBC keyed with
+/// the SHA-256 of the encrypt key, with the IV in front of the ciphertext
+pub fn decrypt_feishu_event(encrypt_key: &str, encrypted: &str) -> Result<String, String> {
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(encrypted)
+        .map_err(|e| format!("Invalid Feishu encrypted event: {}", e))?;
+    if data.len() <= 16 || data.len() % 16 != 0 {
+        return Err("Invalid Feishu encrypted event: bad length".to_string());
+    }
+
+    let key = Sha256::digest(encrypt_key.as_bytes());
+    let (iv, ciphertext) = data.split_at(16);
+    let mut buf = ciphertext.to_vec();
+    let plaintext = Aes256CbcDec::new(key.as_slice().into(), iv.into())
+        .decrypt_padded_mut::<Pkcs7>(&mut buf)
+        .map_err(|_| "Feishu event does not decrypt with the encrypt key".to_string())?;
+    String::from_utf8(plaintext.to_vec())
+        .map_err(|_| "Feishu event does not decrypt with the encrypt key".to_string())
+}
+
+/// Signature of a Feishu event delivery
+pub(crate) fn feishu_signature(
+    timestamp: &str,
+    nonce: &str,
+    encrypt_key: &str,
+    body: &str,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(timestamp.as_bytes());
+    hasher.update(nonce.as_bytes());
+    hasher.update(encrypt_key.as_bytes());
+    hasher.update(body.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::cipher::BlockEncryptMut;
+
+    type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+
+    /// Encrypt as Feishu does
+    fn encrypt_feishu_event(encrypt_key: &str, plaintext: &str) -> String {
+        let key = Sha256::digest(encrypt_key.as_bytes());
+        let iv = [7u8; 16];
+        let mut buf = plaintext.as_bytes().to_vec();
+        buf.resize(plaintext.len() + 16, 0);
+        let ciphertext = Aes256CbcEnc::new(key.as_slice().into(), (&iv).into())
+            .encrypt_padded_mut::<Pkcs7>(&mut buf, plaintext.len())
+            .unwrap();
+        base64::engine::general_purpose::STANDARD.encode([iv.as_slice(), ciphertext].concat())
+    }
+
+    #[test]
+    fn test_replay_guard() {
+        let guard = ReplayGuard::new(300);
+
+        assert!(guard.check_at("evt-1", 1_000, 1_100).is_ok());
+        assert!(guard.check_at("evt-1", 1_000, 1_100).is_err());
+        assert!(guard.check_at("evt-2", 1_000, 1_400).is_err());
+        assert!(guard.check_at("evt-3", 2_000, 1_400).is_err());
+        // Forgotten once stale, when it would be refused as stale anyway
+        assert!(guard.check_at("evt-4", 1_400, 1_400).is_ok());
+        assert!(!guard
+            .seen
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key("evt-1"));
+
+        // Remembered past the accepted age when asked to
+        let guard = ReplayGuard::new(300).remembering(3_600);
+        assert!(guard.check_at("evt-1", 1_000, 1_000).is_ok());
+        assert!(guard.remember("evt-1", 3_000, 3_000).is_err());
+        assert!(guard.remember("evt-1", 7_000, 7_000).is_ok());
+    }
+
+    #[test]
+    fn test_telegram_secret() {
+        let mut headers = HeaderMap::new();
+        assert!(verify_telegram_secret(&headers, "s3cret").is_err());
+        headers.insert(TELEGRAM_SECRET_HEADER, "guess".parse().unwrap());
+        assert!(verify_telegram_secret(&headers, "s3cret").is_err());
+        headers.insert(TELEGRAM_SECRET_HEADER, "s3cret".parse().unwrap());
+        assert!(verify_telegram_secret(&headers, "s3cret").is_ok());
+    }
+
+    #[test]
+    fn test_feishu_signature_and_decryption() {
+        let body = r#"{"encrypt":"..."}"#;
+        let mut headers = HeaderMap::new();
+        headers.insert(FEISHU_TIMESTAMP_HEADER, "1700000000".parse().unwrap());
+        headers.insert(FEISHU_NONCE_HEADER, "n0nce".parse().unwrap());
+        assert!(verify_feishu_signature(&headers, "key", body).is_err());
+
+        let signature = feishu_signature("1700000000", "n0nce", "key", body);
+        headers.insert(FEISHU_SIGNATURE_HEADER, signature.parse().unwrap());
+        assert_eq!(
+            verify_feishu_signature(&headers, "key", body).unwrap(),
+            (1_700_000_000, "n0nce".to_string())
+        );
+        assert!(verify_feishu_signature(&headers, "other", body).is_err());
+        assert!(verify_feishu_signature(&headers, "key", "{}").is_err());
+
+        let encrypted = encrypt_feishu_event("key", r#"{"challenge":"abc"}"#);
+        assert_eq!(
+            decrypt_feishu_event("key", &encrypted).unwrap(),
+            r#"{"challenge":"abc"}"#
+        );
+        assert!(decrypt_feishu_event("other", &encrypted).is_err());
+        assert!(decrypt_feishu_event("key", "bm90IGVuY3J5cHRlZA==").is_err());
+    }
+}
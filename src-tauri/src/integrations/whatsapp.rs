@@ -0,0 +1,873 @@
+//! WhatsApp Integration Adapter
+//!
+//! Talks to WhatsApp through either the Meta Cloud API or Twilio. Messages
+//! arrive by webhook and are parsed here; replies go out as text or media,
+//! and notifications to users who have not written in the last 24 hours as
+//! approved templates, the only messages WhatsApp delivers to them.
+
+use crate::integrations::types::*;
+use bytes::Bytes;
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+const CLOUD_API_BASE_URL: &str = "https://graph.facebook.com";
+const DEFAULT_CLOUD_API_VERSION: &str = "v21.0";
+const TWILIO_API_BASE_URL: &str = "https://api.twilio.com/2010-04-01";
+const MAX_WHATSAPP_MEDIA_BYTES: u64 = 20 * 1024 * 1024;
+
+/// The API messages are sent through
+#[derive(Debug, Clone)]
+pub enum WhatsAppProvider {
+    /// Meta's WhatsApp Business Cloud API
+    Cloud {
+        phone_number_id: String,
+        access_token: String,
+        /// Token Meta echoes back when subscribing the webhook
+        verify_token: String,
+        /// Graph API version, `v21.0` when unset
+        api_version: Option<String>,
+    },
+    /// Twilio's WhatsApp sender
+    Twilio {
+        account_sid: String,
+        auth_token: String,
+        /// Sender number in E.164 form, e.g. `+14155238886`
+        from_number: String,
+    },
+}
+
+/// WhatsApp adapter configuration
+#[derive(Debug, Clone)]
+pub struct WhatsAppConfig {
+    pub provider: WhatsAppProvider,
+    pub webhook_url: Option<String>,
+}
+
+/// An approved message template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WhatsAppTemplate {
+    /// Template name on the Cloud API, content SID on Twilio
+    pub name: String,
+    /// Language code such as `en_US`; Twilio templates carry their own
+    pub language: String,
+    /// Values of the body placeholders `{{1}}`, `{{2}}`, ...
+    #[serde(default)]
+    pub parameters: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WhatsAppMediaKind {
+    Image,
+    Audio,
+    Video,
+    Document,
+}
+
+impl WhatsAppMediaKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WhatsAppMediaKind::Image => "image",
+            WhatsAppMediaKind::Audio => "audio",
+            WhatsAppMediaKind::Video => "video",
+            WhatsAppMediaKind::Document => "document",
+        }
+    }
+}
+
+/// Media to send, fetched by WhatsApp from a publicly reachable URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WhatsAppMedia {
+    pub kind: WhatsAppMediaKind,
+    pub url: String,
+    pub caption: Option<String>,
+    pub filename: Option<String>,
+}
+
+/// WhatsApp integration adapter
+pub struct WhatsAppAdapter {
+    id: IntegrationId,
+    config: WhatsAppConfig,
+    client: Client,
+    connected: RwLock<bool>,
+}
+
+/// What a message carries, which the Cloud API nests under its type and
+/// Twilio spreads over form fields
+enum Outbound<'a> {
+    Text(&'a str),
+    Template(&'a WhatsAppTemplate),
+    Media(&'a WhatsAppMedia),
+}
+
+impl WhatsAppAdapter {
+    pub fn new(id: impl Into<IntegrationId>, config: WhatsAppConfig) -> Self {
+        Self {
+            id: id.into(),
+            config,
+            client: Client::new(),
+            connected: RwLock::new(false),
+        }
+    }
+
+    /// Answer Meta's webhook subscription request with its challenge
+    pub fn verify_subscription(
+        &self,
+        mode: &str,
+        verify_token: &str,
+        challenge: &str,
+    ) -> Result<String, String> {
+        match &self.config.provider {
+            WhatsAppProvider::Cloud {
+                verify_token: expected,
+                ..
+            } => {
+                if mode == "subscribe" && !expected.is_empty() && verify_token == expected {
+                    Ok(challenge.to_string())
+                } else {
+                    Err("WhatsApp webhook verify token does not match".to_string())
+                }
+            }
+            WhatsAppProvider::Twilio { .. } => {
+                Err("Twilio webhooks have no subscription request".to_string())
+            }
+        }
+    }
+
+    /// Parse a webhook delivery into the messages it carries: JSON from the
+    /// Cloud API, a form from Twilio. Delivery receipts yield no messages.
+    pub fn receive_webhook(&self, body: &str) -> Result<Vec<IncomingMessage>, String> {
+        match &self.config.provider {
+            WhatsAppProvider::Cloud { .. } => parse_cloud_webhook(&self.id, body),
+            WhatsAppProvider::Twilio { .. } => parse_twilio_webhook(&self.id, body),
+        }
+    }
+
+    /// Send an approved template, for notifications outside the 24-hour window
+    pub async fn send_template(
+        &self,
+        recipient: &str,
+        template: &WhatsAppTemplate,
+    ) -> Result<MessageId, String> {
+        self.send(recipient, Outbound::Template(template)).await
+    }
+
+    /// Send an image, audio clip, video or document
+    pub async fn send_media(
+        &self,
+        recipient: &str,
+        media: &WhatsAppMedia,
+    ) -> Result<MessageId, String> {
+        self.send(recipient, Outbound::Media(media)).await
+    }
+
+    /// Download the media of an incoming message
+    pub async fn download_media(&self, media: &IncomingMedia) -> Result<Bytes, String> {
+        let request = match &self.config.provider {
+            WhatsAppProvider::Cloud {
+                access_token,
+                api_version,
+                ..
+            } => {
+                let url = format!(
+                    "{}/{}/{}",
+                    CLOUD_API_BASE_URL,
+                    cloud_api_version(api_version),
+                    media.media_id
+                );
+                let response = self
+                    .client
+                    .get(&url)
+                    .bearer_auth(access_token)
+                    .send()
+                    .await
+                    .map_err(|e| format!("Failed to look up WhatsApp media: {}", e))?;
+                let info: CloudMediaInfo = read_response(response).await?;
+                if info.file_size.unwrap_or(0) > MAX_WHATSAPP_MEDIA_BYTES {
+                    return Err(media_too_large());
+                }
+                self.client.get(&info.url).bearer_auth(access_token)
+            }
+            WhatsAppProvider::Twilio {
+                account_sid,
+                auth_token,
+                ..
+            } => {
+                // The URL came in a webhook; only Twilio gets the credentials
+                if !media.media_id.starts_with(TWILIO_API_BASE_URL) {
+                    return Err(format!("Not a Twilio media URL: {}", media.media_id));
+                }
+                self.client
+                    .get(&media.media_id)
+                    .basic_auth(account_sid, Some(auth_token))
+            }
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to download WhatsApp media: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Failed to download WhatsApp media: {}",
+                response.status()
+            ));
+        }
+        if response.content_length().unwrap_or(0) > MAX_WHATSAPP_MEDIA_BYTES {
+            return Err(media_too_large());
+        }
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to download WhatsApp media: {}", e))?;
+        if bytes.len() as u64 > MAX_WHATSAPP_MEDIA_BYTES {
+            return Err(media_too_large());
+        }
+        Ok(bytes)
+    }
+
+    async fn send(&self, recipient: &str, outbound: Outbound<'_>) -> Result<MessageId, String> {
+        match &self.config.provider {
+            WhatsAppProvider::Cloud {
+                phone_number_id,
+                access_token,
+                api_version,
+                ..
+            } => {
+                let url = format!(
+                    "{}/{}/{}/messages",
+                    CLOUD_API_BASE_URL,
+                    cloud_api_version(api_version),
+                    phone_number_id
+                );
+                let response = self
+                    .client
+                    .post(&url)
+                    .bearer_auth(access_token)
+                    .json(&cloud_payload(recipient, &outbound))
+                    .send()
+                    .await
+                    .map_err(|e| format!("Failed to send WhatsApp message: {}", e))?;
+                let sent: CloudSendResponse = read_response(response).await?;
+                sent.messages
+                    .into_iter()
+                    .next()
+                    .map(|message| message.id)
+                    .ok_or_else(|| "WhatsApp response has no message ID".to_string())
+            }
+            WhatsAppProvider::Twilio {
+                account_sid,
+                auth_token,
+                from_number,
+            } => {
+                let url = format!(
+                    "{}/Accounts/{}/Messages.json",
+                    TWILIO_API_BASE_URL, account_sid
+                );
+                let response = self
+                    .client
+                    .post(&url)
+                    .basic_auth(account_sid, Some(auth_token))
+                    .form(&twilio_form(from_number, recipient, &outbound))
+                    .send()
+                    .await
+                    .map_err(|e| format!("Failed to send WhatsApp message: {}", e))?;
+                let sent: TwilioSendResponse = read_response(response).await?;
+                Ok(sent.sid)
+            }
+        }
+    }
+}
+
+fn cloud_api_version(api_version: &Option<String>) -> &str {
+    api_version.as_deref().unwrap_or(DEFAULT_CLOUD_API_VERSION)
+}
+
+fn media_too_large() -> String {
+    format!("WhatsApp media exceeds {} bytes", MAX_WHATSAPP_MEDIA_BYTES)
+}
+
+async fn read_response<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, String> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("WhatsApp API error ({}): {}", status, body));
+    }
+    response
+        .json::<T>()
+        .await
+        .map_err(|e| format!("Failed to parse WhatsApp response: {}", e))
+}
+
+fn cloud_payload(recipient: &str, outbound: &Outbound) -> Value {
+    let to = recipient
+        .trim_start_matches("whatsapp:")
+        .trim_start_matches('+');
+    let mut payload = json!({
+        "messaging_product": "whatsapp",
+        "recipient_type": "individual",
+        "to": to,
+    });
+    match outbound {
+        Outbound::Text(text) => {
+            payload["type"] = json!("text");
+            payload["text"] = json!({ "body": text, "preview_url": false });
+        }
+        Outbound::Template(template) => {
+            let mut body = json!({
+                "name": template.name,
+                "language": { "code": template.language },
+            });
+            if !template.parameters.is_empty() {
+                let parameters: Vec<Value> = template
+                    .parameters
+                    .iter()
+                    .map(|value| json!({ "type": "text", "text": value }))
+                    .collect();
+                body["components"] = json!([{ "type": "body", "parameters": parameters }]);
+            }
+            payload["type"] = json!("template");
+            payload["template"] = body;
+        }
+        Outbound::Media(media) => {
+            let mut body = json!({ "link": media.url });
+            // Audio takes neither a caption nor a filename
+            if let (Some(caption), false) = (&media.caption, media.kind == WhatsAppMediaKind::Audio)
+            {
+                body["caption"] = json!(caption);
+            }
+            if let (Some(filename), WhatsAppMediaKind::Document) = (&media.filename, media.kind) {
+                body["filename"] = json!(filename);
+            }
+            payload["type"] = json!(media.kind.as_str());
+            payload[media.kind.as_str()] = body;
+        }
+    }
+    payload
+}
+
+fn twilio_form(
+    from_number: &str,
+    recipient: &str,
+    outbound: &Outbound,
+) -> Vec<(&'static str, String)> {
+    let mut form = vec![
+        ("From", twilio_address(from_number)),
+        ("To", twilio_address(recipient)),
+    ];
+    match outbound {
+        Outbound::Text(text) => form.push(("Body", text.to_string())),
+        Outbound::Template(template) => {
+            form.push(("ContentSid", template.name.clone()));
+            if !template.parameters.is_empty() {
+                let variables: serde_json::Map<String, Value> = template
+                    .parameters
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| ((index + 1).to_string(), json!(value)))
+                    .collect();
+                form.push(("ContentVariables", Value::Object(variables).to_string()));
+            }
+        }
+        Outbound::Media(media) => {
+            form.push(("MediaUrl", media.url.clone()));
+            if let Some(caption) = &media.caption {
+                form.push(("Body", caption.clone()));
+            }
+        }
+    }
+    form
+}
+
+/// Twilio addresses WhatsApp numbers as `whatsapp:+<E.164>`
+fn twilio_address(number: &str) -> String {
+    let number = number.trim_start_matches("whatsapp:");
+    if number.starts_with('+') {
+        format!("whatsapp:{}", number)
+    } else {
+        format!("whatsapp:+{}", number)
+    }
+}
+
+fn parse_cloud_webhook(
+    integration_id: &IntegrationId,
+    body: &str,
+) -> Result<Vec<IncomingMessage>, String> {
+    let webhook: CloudWebhook =
+        serde_json::from_str(body).map_err(|e| format!("Invalid WhatsApp webhook: {}", e))?;
+
+    let mut messages = Vec::new();
+    for value in webhook
+        .entry
+        .into_iter()
+        .flat_map(|entry| entry.changes)
+        .map(|change| change.value)
+    {
+        for message in value.messages {
+            let sender_name = value
+                .contacts
+                .iter()
+                .find(|contact| contact.wa_id == message.from)
+                .and_then(|contact| contact.profile.as_ref())
+                .map(|profile| profile.name.clone());
+            let media = message.media().map(|media| IncomingMedia {
+                media_id: media.id.clone(),
+                mime_type: media.mime_type.clone(),
+                filename: media.filename.clone(),
+                caption: media.caption.clone(),
+            });
+            let content = message
+                .text
+                .as_ref()
+                .map(|text| text.body.clone())
+                .or_else(|| message.button.as_ref().map(|button| button.text.clone()))
+                .or_else(|| media.as_ref().and_then(|media| media.caption.clone()))
+                .unwrap_or_default();
+            // Reactions, locations and the like carry nothing to act on
+            if content.is_empty() && media.is_none() {
+                continue;
+            }
+
+            messages.push(IncomingMessage {
+                integration_id: integration_id.clone(),
+                channel_type: ChannelType::WhatsApp,
+                sender_id: message.from.clone(),
+                sender_name,
+                chat_id: message.from,
+                message_id: message.id,
+                content,
+                timestamp: message.timestamp.parse::<i64>().unwrap_or_default() * 1000,
+                reply_to: message.context.map(|context| context.id),
+                media: media.into_iter().collect(),
+            });
+        }
+    }
+    Ok(messages)
+}
+
+fn parse_twilio_webhook(
+    integration_id: &IntegrationId,
+    body: &str,
+) -> Result<Vec<IncomingMessage>, String> {
+    let fields: HashMap<String, String> = url::form_urlencoded::parse(body.as_bytes())
+        .into_owned()
+        .collect();
+    let message_id = fields
+        .get("MessageSid")
+        .ok_or_else(|| "Invalid Twilio webhook: no MessageSid".to_string())?;
+    let sender = fields
+        .get("From")
+        .map(|from| from.trim_start_matches("whatsapp:").to_string())
+        .ok_or_else(|| "Invalid Twilio webhook: no From".to_string())?;
+
+    let media_count = fields
+        .get("NumMedia")
+        .and_then(|count| count.parse::<usize>().ok())
+        .unwrap_or(0);
+    let media: Vec<IncomingMedia> = (0..media_count)
+        .filter_map(|index| {
+            Some(IncomingMedia {
+                media_id: fields.get(&format!("MediaUrl{}", index))?.clone(),
+                mime_type: fields.get(&format!("MediaContentType{}", index)).cloned(),
+                filename: None,
+                caption: None,
+            })
+        })
+        .collect();
+    let content = fields.get("Body").cloned().unwrap_or_default();
+    // Status callbacks share the endpoint but carry no body or media
+    if content.is_empty() && media.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(vec![IncomingMessage {
+        integration_id: integration_id.clone(),
+        channel_type: ChannelType::WhatsApp,
+        sender_id: sender.clone(),
+        sender_name: fields.get("ProfileName").cloned(),
+        chat_id: sender,
+        message_id: message_id.clone(),
+        content,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        reply_to: fields.get("OriginalRepliedMessageSid").cloned(),
+        media,
+    }])
+}
+
+#[async_trait::async_trait]
+impl IntegrationAdapter for WhatsAppAdapter {
+    fn id(&self) -> &IntegrationId {
+        &self.id
+    }
+
+    fn channel_type(&self) -> ChannelType {
+        ChannelType::WhatsApp
+    }
+
+    async fn start(&self) -> Result<(), String> {
+        // Messages arrive by webhook, so there is nothing to connect to
+        match &self.config.provider {
+            WhatsAppProvider::Cloud {
+                phone_number_id,
+                access_token,
+                ..
+            } if phone_number_id.is_empty() || access_token.is_empty() => {
+                return Err(
+                    "WhatsApp Cloud API needs a phone number ID and access token".to_string(),
+                );
+            }
+            WhatsAppProvider::Twilio {
+                account_sid,
+                auth_token,
+                from_number,
+            } if account_sid.is_empty() || auth_token.is_empty() || from_number.is_empty() => {
+                return Err("Twilio needs an account SID, auth token and sender number".to_string());
+            }
+            _ => {}
+        }
+
+        let mut connected = self.connected.write().await;
+        *connected = true;
+        Ok(())
+    }
+
+    async fn stop(&self) -> Result<(), String> {
+        let mut connected = self.connected.write().await;
+        *connected = false;
+        Ok(())
+    }
+
+    async fn send_message(&self, recipient: &str, content: &str) -> Result<MessageId, String> {
+        self.send(recipient, Outbound::Text(content)).await
+    }
+
+    async fn edit_message(
+        &self,
+        _recipient: &str,
+        _message_id: &str,
+        _new_content: &str,
+    ) -> Result<(), String> {
+        Err("WhatsApp does not support editing sent messages".to_string())
+    }
+
+    async fn is_connected(&self) -> bool {
+        *self.connected.read().await
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudWebhook {
+    #[serde(default)]
+    entry: Vec<CloudEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudEntry {
+    #[serde(default)]
+    changes: Vec<CloudChange>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudChange {
+    value: CloudChangeValue,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudChangeValue {
+    #[serde(default)]
+    contacts: Vec<CloudContact>,
+    #[serde(default)]
+    messages: Vec<CloudMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudContact {
+    wa_id: String,
+    profile: Option<CloudProfile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudProfile {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudMessage {
+    from: String,
+    id: String,
+    timestamp: String,
+    text: Option<CloudText>,
+    button: Option<CloudButton>,
+    context: Option<CloudContext>,
+    image: Option<CloudMedia>,
+    audio: Option<CloudMedia>,
+    video: Option<CloudMedia>,
+    document: Option<CloudMedia>,
+    sticker: Option<CloudMedia>,
+}
+
+impl CloudMessage {
+    fn media(&self) -> Option<&CloudMedia> {
+        self.image
+            .as_ref()
+            .or(self.audio.as_ref())
+            .or(self.video.as_ref())
+            .or(self.document.as_ref())
+            .or(self.sticker.as_ref())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudText {
+    body: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudButton {
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudContext {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudMedia {
+    id: String,
+    mime_type: Option<String>,
+    caption: Option<String>,
+    filename: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudMediaInfo {
+    url: String,
+    file_size: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudSendResponse {
+    #[serde(default)]
+    messages: Vec<CloudSentMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudSentMessage {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TwilioSendResponse {
+    sid: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cloud_config() -> WhatsAppConfig {
+        WhatsAppConfig {
+            provider: WhatsAppProvider::Cloud {
+                phone_number_id: "1234567890".to_string(),
+                access_token: "test_token".to_string(),
+                verify_token: "verify-me".to_string(),
+                api_version: None,
+            },
+            webhook_url: None,
+        }
+    }
+
+    fn twilio_config() -> WhatsAppConfig {
+        WhatsAppConfig {
+            provider: WhatsAppProvider::Twilio {
+                account_sid: "AC123".to_string(),
+                auth_token: "test_token".to_string(),
+                from_number: "+14155238886".to_string(),
+            },
+            webhook_url: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_whatsapp_start_stop() {
+        let adapter = WhatsAppAdapter::new("whatsapp-1", cloud_config());
+        assert_eq!(adapter.id(), "whatsapp-1");
+        assert_eq!(adapter.channel_type(), ChannelType::WhatsApp);
+        assert!(!adapter.is_connected().await);
+
+        adapter.start().await.expect("Failed to start");
+        assert!(adapter.is_connected().await);
+        adapter.stop().await.expect("Failed to stop");
+        assert!(!adapter.is_connected().await);
+
+        let unconfigured = WhatsAppAdapter::new(
+            "whatsapp-2",
+            WhatsAppConfig {
+                provider: WhatsAppProvider::Twilio {
+                    account_sid: String::new(),
+                    auth_token: String::new(),
+                    from_number: String::new(),
+                },
+                webhook_url: None,
+            },
+        );
+        assert!(unconfigured.start().await.is_err());
+        assert!(!unconfigured.is_connected().await);
+    }
+
+    #[test]
+    fn test_verify_subscription() {
+        let adapter = WhatsAppAdapter::new("whatsapp-1", cloud_config());
+        assert_eq!(
+            adapter
+                .verify_subscription("subscribe", "verify-me", "challenge-42")
+                .unwrap(),
+            "challenge-42"
+        );
+        assert!(adapter
+            .verify_subscription("subscribe", "wrong", "challenge-42")
+            .is_err());
+        assert!(adapter
+            .verify_subscription("unsubscribe", "verify-me", "challenge-42")
+            .is_err());
+    }
+
+    #[test]
+    fn test_cloud_webhook() {
+        let adapter = WhatsAppAdapter::new("whatsapp-1", cloud_config());
+        let body = json!({
+            "object": "whatsapp_business_account",
+            "entry": [{
+                "id": "entry-1",
+                "changes": [{
+                    "field": "messages",
+                    "value": {
+                        "messaging_product": "whatsapp",
+                        "contacts": [{ "wa_id": "15551234567", "profile": { "name": "Ada" } }],
+                        "messages": [
+                            {
+                                "from": "15551234567",
+                                "id": "wamid.text",
+                                "timestamp": "1700000000",
+                                "type": "text",
+                                "text": { "body": "Run the tests" },
+                                "context": { "id": "wamid.previous" }
+                            },
+                            {
+                                "from": "15551234567",
+                                "id": "wamid.image",
+                                "timestamp": "1700000001",
+                                "type": "image",
+                                "image": { "id": "media-1", "mime_type": "image/jpeg", "caption": "This error" }
+                            },
+                            {
+                                "from": "15551234567",
+                                "id": "wamid.reaction",
+                                "timestamp": "1700000002",
+                                "type": "reaction",
+                                "reaction": { "message_id": "wamid.text", "emoji": "👍" }
+                            }
+                        ]
+                    }
+                }]
+            }]
+        });
+
+        let messages = adapter.receive_webhook(&body.to_string()).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].sender_id, "15551234567");
+        assert_eq!(messages[0].sender_name.as_deref(), Some("Ada"));
+        assert_eq!(messages[0].content, "Run the tests");
+        assert_eq!(messages[0].timestamp, 1_700_000_000_000);
+        assert_eq!(messages[0].reply_to.as_deref(), Some("wamid.previous"));
+        assert_eq!(messages[1].content, "This error");
+        assert_eq!(messages[1].media[0].media_id, "media-1");
+        assert_eq!(
+            messages[1].media[0].mime_type.as_deref(),
+            Some("image/jpeg")
+        );
+
+        // Delivery receipts carry no messages
+        let statuses = json!({
+            "entry": [{ "changes": [{ "value": { "statuses": [{ "id": "wamid.text", "status": "read" }] } }] }]
+        });
+        assert!(adapter
+            .receive_webhook(&statuses.to_string())
+            .unwrap()
+            .is_empty());
+        assert!(adapter.receive_webhook("not json").is_err());
+    }
+
+    #[test]
+    fn test_twilio_webhook() {
+        let adapter = WhatsAppAdapter::new("whatsapp-1", twilio_config());
+        let body = "MessageSid=SM123&From=whatsapp%3A%2B15551234567&To=whatsapp%3A%2B14155238886\
+            &Body=Deploy+it&ProfileName=Ada&NumMedia=1\
+            &MediaUrl0=https%3A%2F%2Fapi.twilio.com%2F2010-04-01%2FAccounts%2FAC123%2FMessages%2FSM123%2FMedia%2FME1\
+            &MediaContentType0=image%2Fpng";
+
+        let messages = adapter.receive_webhook(body).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message_id, "SM123");
+        assert_eq!(messages[0].sender_id, "+15551234567");
+        assert_eq!(messages[0].sender_name.as_deref(), Some("Ada"));
+        assert_eq!(messages[0].content, "Deploy it");
+        assert_eq!(messages[0].media.len(), 1);
+        assert_eq!(messages[0].media[0].mime_type.as_deref(), Some("image/png"));
+
+        let status = "MessageSid=SM123&From=whatsapp%3A%2B14155238886&MessageStatus=delivered";
+        assert!(adapter.receive_webhook(status).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_outbound_payloads() {
+        let template = WhatsAppTemplate {
+            name: "task_finished".to_string(),
+            language: "en_US".to_string(),
+            parameters: vec!["Fix login".to_string(), "3 files changed".to_string()],
+        };
+
+        let payload = cloud_payload("+15551234567", &Outbound::Template(&template));
+        assert_eq!(payload["to"], "15551234567");
+        assert_eq!(payload["type"], "template");
+        assert_eq!(payload["template"]["language"]["code"], "en_US");
+        assert_eq!(
+            payload["template"]["components"][0]["parameters"][1]["text"],
+            "3 files changed"
+        );
+
+        let media = WhatsAppMedia {
+            kind: WhatsAppMediaKind::Document,
+            url: "https://example.com/report.pdf".to_string(),
+            caption: Some("Report".to_string()),
+            filename: Some("report.pdf".to_string()),
+        };
+        let payload = cloud_payload("15551234567", &Outbound::Media(&media));
+        assert_eq!(payload["type"], "document");
+        assert_eq!(
+            payload["document"]["link"],
+            "https://example.com/report.pdf"
+        );
+        assert_eq!(payload["document"]["filename"], "report.pdf");
+
+        let form = twilio_form(
+            "+14155238886",
+            "15551234567",
+            &Outbound::Template(&template),
+        );
+        assert!(form.contains(&("From", "whatsapp:+14155238886".to_string())));
+        assert!(form.contains(&("To", "whatsapp:+15551234567".to_string())));
+        assert!(form.contains(&("ContentSid", "task_finished".to_string())));
+        assert!(form.contains(&(
+            "ContentVariables",
+            r#"{"1":"Fix login","2":"3 files changed"}"#.to_string()
+        )));
+    }
+}
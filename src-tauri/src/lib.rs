@@ -3,16 +3,19 @@ mod archive;
 mod background_tasks;
 mod code_navigation;
 mod constants;
+mod content_search;
 mod core;
 mod database;
 mod device_id;
 mod directory_tree;
 mod dock_menu;
 mod feishu_gateway;
+mod file_index;
 mod file_search;
 mod file_watcher;
 mod git;
 mod glob;
+mod headless;
 mod http_proxy;
 mod integrations;
 mod keep_awake;
@@ -21,6 +24,7 @@ mod list_files;
 mod llm;
 mod lsp;
 mod oauth_callback_server;
+mod path_guard;
 mod platform;
 mod script_executor;
 mod search;
@@ -31,11 +35,17 @@ mod s3_sync;
 mod shell_utils;
 mod storage;
 mod streaming;
+mod symbol_outline;
 mod telegram_gateway;
 mod terminal;
+mod text_file;
+mod walk_stream;
 mod walker;
 mod websocket;
 mod window_manager;
+mod workspace_replace;
+
+pub use headless::run_headless;
 
 use analytics::AnalyticsState;
 use archive::{
@@ -72,12 +82,6 @@ use window_manager::{create_window, WindowRegistry, WindowState};
 // before any code calls get_app_handle()
 static APP_HANDLE: OnceLock<tauri::AppHandle> = OnceLock::new();
 
-/// Server information for client discovery
-#[derive(Clone)]
-pub struct ServerInfo {
-    pub addr: std::net::SocketAddr,
-}
-
 /// Initialize the global app handle
 ///
 /// # Panics
@@ -131,6 +135,7 @@ fn start_file_watching(
         watcher.stop();
     }
 
+    file_watcher::load_config_in_background(&app_handle);
     let mut watcher = FileWatcher::new().map_err(|e| e.to_string())?;
     watcher
         .watch_directory(&path, app_handle, None)
@@ -365,6 +370,32 @@ fn update_window_project(
         .update_window_project(&label, project_id, root_path)
 }
 
+#[tauri::command]
+async fn configure_file_watcher(
+    config: file_watcher::FileWatcherConfig,
+    database: State<'_, Arc<Database>>,
+) -> Result<(), String> {
+    file_watcher::configure(&database, config).await
+}
+
+#[tauri::command]
+async fn get_file_watcher_config(
+    database: State<'_, Arc<Database>>,
+) -> Result<file_watcher::FileWatcherConfig, String> {
+    file_watcher::ensure_config_loaded(&database).await;
+    Ok(file_watcher::current_config())
+}
+
+#[tauri::command]
+fn file_watcher_pause() {
+    file_watcher::pause();
+}
+
+#[tauri::command]
+fn file_watcher_resume() -> bool {
+    file_watcher::resume()
+}
+
 #[tauri::command]
 fn start_window_file_watching(
     window_label: String,
@@ -377,6 +408,7 @@ fn start_window_file_watching(
         window_label,
         path
     );
+    file_watcher::load_config_in_background(&app_handle);
     let mut watcher = FileWatcher::new().map_err(|e| e.to_string())?;
     watcher
         .watch_directory(&path, app_handle, Some(window_label.clone()))
@@ -788,6 +820,27 @@ pub fn run() {
             // Set global app handle first (used by dock menu and other modules)
             set_app_handle(app.handle().clone());
 
+            // Log through a logger masking secrets, so logs can be shared
+            let (log_plugin, max_level, logger) = tauri_plugin_log::Builder::new()
+                .targets([
+                    tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::Stdout),
+                    tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::LogDir {
+                        file_name: None,
+                    }),
+                ])
+                .level(if cfg!(debug_assertions) {
+                    log::LevelFilter::Debug
+                } else {
+                    log::LevelFilter::Info
+                })
+                .max_file_size(100_000_000)
+                .rotation_strategy(tauri_plugin_log::RotationStrategy::KeepAll)
+                .timezone_strategy(tauri_plugin_log::TimezoneStrategy::UseLocal)
+                .split(app.handle())?;
+            log::set_boxed_logger(Box::new(llm::redaction::RedactingLogger::new(logger)))?;
+            log::set_max_level(max_level);
+            app.handle().plugin(log_plugin)?;
+
             if app
                 .try_state::<keep_awake::KeepAwakeStateWrapper>()
                 .is_none()
@@ -815,23 +868,32 @@ pub fn run() {
             let server_config = server::config::ServerConfig::new(app_data_dir.clone(), app_data_dir.clone());
             let (event_tx, _event_rx) = tokio::sync::mpsc::unbounded_channel::<core::types::RuntimeEvent>();
 
+            let server_llm: Arc<dyn core::LlmClient> = Arc::new(core::ProviderLlmClient::new(
+                llm::providers::provider_registry::ProviderRegistry::new(
+                    llm::providers::provider_configs::builtin_providers(),
+                ),
+                llm::auth::api_key_manager::ApiKeyManager::new(database.clone(), app_data_dir.clone()),
+            ));
+
             let server_handle = app.handle().clone();
             let server_config_clone = server_config.clone();
             tauri::async_runtime::spawn(async move {
-                match server::state::ServerStateFactory::create(server_config_clone, event_tx).await {
+                match server::state::ServerStateFactory::create(server_config_clone, server_llm, event_tx).await {
                     Ok(server_state) => {
-                        // Start server with the configured state
-                        let bind_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
-                        match tokio::net::TcpListener::bind(bind_addr).await {
-                            Ok(listener) => {
-                                let addr = listener.local_addr().unwrap_or(bind_addr);
-                                log::info!("Cloud backend server started on {}", addr);
-                                server_handle.manage(ServerInfo { addr });
-                            }
-                            Err(e) => {
-                                log::error!("Failed to bind server: {}", e);
-                            }
+                        server_handle.manage(server_state.runtime.clone());
+                        server_handle.manage(server_state.streaming.clone());
+                        server_handle.manage(server_state.routing.clone());
+
+                        // Start server per the bind settings in the settings store
+                        let manager = server::ServerManager::new(
+                            server_state.with_app(server_handle.clone()),
+                        );
+                        match manager.start().await {
+                            Ok(Some(addr)) => log::info!("Cloud backend server started on {}", addr),
+                            Ok(None) => {}
+                            Err(e) => log::error!("Failed to start server: {}", e),
                         }
+                        server_handle.manage(manager);
                     }
                     Err(e) => {
                         log::error!("Failed to create server state: {}", e);
@@ -918,6 +980,7 @@ pub fn run() {
             app.manage(code_nav_state);
             let lsp_state = lsp::LspState(tokio::sync::Mutex::new(lsp::LspRegistry::new()));
             app.manage(lsp_state);
+            lsp::spawn_idle_reaper(app.handle().clone());
 
             // Start analytics session
             let app_version = app.package_info().version.to_string();
@@ -963,24 +1026,6 @@ pub fn run() {
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_os::init())
         .plugin(tauri_plugin_notification::init())
-        .plugin(
-            tauri_plugin_log::Builder::new()
-                .targets([
-                    tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::Stdout),
-                    tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::LogDir {
-                        file_name: None,
-                    }),
-                ])
-                .level(if cfg!(debug_assertions) {
-                    log::LevelFilter::Debug
-                } else {
-                    log::LevelFilter::Info
-                })
-                .max_file_size(100_000_000)
-                .rotation_strategy(tauri_plugin_log::RotationStrategy::KeepAll)
-                .timezone_strategy(tauri_plugin_log::TimezoneStrategy::UseLocal)
-                .build(),
-        )
         .on_menu_event(|app, event| {
             dock_menu::handle_dock_menu_event(app, event);
         })
@@ -990,11 +1035,19 @@ pub fn run() {
             search_file_content,
             search_files_fast,
             list_files::list_project_files,
+            walk_stream::stream_list_files,
+            walk_stream::stream_glob,
+            walk_stream::cancel_walk_stream,
             directory_tree::build_directory_tree,
             directory_tree::load_directory_children,
             directory_tree::clear_directory_cache,
             directory_tree::invalidate_directory_path,
             glob::search_files_by_glob,
+            content_search::search_content,
+            workspace_replace::replace_in_workspace,
+            file_index::file_index_build,
+            file_index::file_index_search,
+            file_index::file_index_glob,
             create_project_window,
             get_all_project_windows,
             get_current_window_label,
@@ -1005,6 +1058,10 @@ pub fn run() {
             update_window_project,
             refresh_dock_menu,
             start_window_file_watching,
+            configure_file_watcher,
+            get_file_watcher_config,
+            file_watcher_pause,
+            file_watcher_resume,
             stop_window_file_watching,
             activate_app,
             database::db_connect,
@@ -1055,6 +1112,8 @@ pub fn run() {
             code_navigation::code_nav_delete_index,
             code_navigation::code_nav_get_indexed_files,
             code_navigation::summarize_code_content,
+            symbol_outline::get_file_outline,
+            symbol_outline::search_symbols,
             estimate_tokens,
             lint::run_lint,
             lint::check_lint_runtime,
@@ -1067,12 +1126,83 @@ pub fn run() {
             lsp::lsp_start_server,
             lsp::lsp_send_message,
             lsp::lsp_stop_server,
+            lsp::lsp_stop_all_for_root,
             lsp::lsp_list_servers,
+            lsp::lsp_incoming_calls,
+            lsp::lsp_outgoing_calls,
+            lsp::lsp_type_hierarchy,
             lsp::lsp_check_server_available,
             lsp::lsp_get_server_config,
+            lsp::lsp_list_custom_servers,
             lsp::lsp_get_server_status,
             lsp::lsp_download_server,
             oauth_callback_server::start_oauth_callback_server,
+            server::commands::get_server_bind_settings,
+            server::commands::set_server_bind_settings,
+            server::commands::get_server_web_ui_settings,
+            server::commands::set_server_web_ui_settings,
+            server::commands::get_server_address,
+            server::commands::restart_server,
+            core::commands::approve_tool_call,
+            core::commands::deny_tool_call,
+            core::commands::write_tool_input,
+            core::commands::list_pending_tool_approvals,
+            core::commands::continue_task,
+            core::commands::list_budget_pauses,
+            core::commands::list_interrupted_tasks,
+            core::commands::list_checkpoints,
+            core::commands::rollback_to_checkpoint,
+            core::commands::execute_plan,
+            core::commands::list_plans,
+            core::commands::get_session_todos,
+            core::commands::list_memories,
+            core::commands::create_memory,
+            core::commands::update_memory,
+            core::commands::delete_memory,
+            core::commands::list_projects,
+            core::commands::get_project,
+            core::commands::create_project,
+            core::commands::update_project,
+            core::commands::delete_project,
+            core::commands::list_workspace_agents,
+            core::commands::list_script_tools,
+            core::commands::list_hooks,
+            core::commands::set_hooks,
+            core::commands::list_webhooks,
+            core::commands::set_webhooks,
+            core::commands::list_webhook_deliveries,
+            core::commands::get_tool_retry_policy,
+            core::commands::set_tool_retry_policy,
+            core::commands::list_task_worktrees,
+            core::commands::merge_task_worktree,
+            core::commands::discard_task_worktree,
+            core::commands::get_runtime_stats,
+            core::commands::get_streaming_stats,
+            core::commands::create_api_key,
+            core::commands::list_api_keys,
+            core::commands::revoke_api_key,
+            core::commands::create_user,
+            core::commands::list_users,
+            core::commands::delete_user,
+            core::commands::list_session_events,
+            core::commands::rebuild_session_state,
+            core::commands::archive_session,
+            core::commands::restore_session,
+            core::commands::set_session_starred,
+            core::commands::search_sessions,
+            core::commands::get_retention_policy,
+            core::commands::set_retention_policy,
+            core::commands::apply_retention_policy,
+            core::commands::get_web_search_config,
+            core::commands::set_web_search_config,
+            core::commands::list_databases,
+            core::commands::set_databases,
+            core::commands::get_sandbox_policy,
+            core::commands::set_sandbox_policy,
+            core::commands::get_shell_policy,
+            core::commands::set_shell_policy,
+            core::commands::get_http_request_config,
+            core::commands::set_http_request_config,
             llm::commands::llm_stream_text,
             llm::commands::llm_list_available_models,
             llm::commands::llm_register_custom_provider,
@@ -1087,6 +1217,8 @@ pub fn run() {
             llm::commands::llm_generate_title,
             llm::commands::llm_compact_context,
             llm::auth::api_key_manager::llm_set_setting,
+            llm::auth::api_key_manager::llm_get_api_keys,
+            llm::auth::api_key_manager::llm_get_custom_provider_api_key,
             llm::auth::oauth::llm_openai_oauth_start,
             llm::auth::oauth::llm_openai_oauth_complete,
             llm::auth::oauth::llm_openai_oauth_refresh,
@@ -1130,6 +1262,9 @@ pub fn run() {
             feishu_gateway::feishu_is_running,
             feishu_gateway::feishu_send_message,
             feishu_gateway::feishu_edit_message,
+            integrations::access_commands::create_integration_pairing,
+            integrations::access_commands::get_integration_access,
+            integrations::access_commands::set_integration_access,
         ])
         .on_window_event(|window, event| {
             if let WindowEvent::CloseRequested { api, .. } = event {
@@ -82,7 +82,7 @@ impl ContextCompactionService {
     }
 
     /// Build the compaction prompt with the 8-section template
-    fn build_compaction_prompt(&self, conversation_history: &str) -> String {
+    pub fn build_compaction_prompt(&self, conversation_history: &str) -> String {
         format!(
             "Your task is to create a detailed summary of the conversation so far, paying close attention to the user's explicit requests and your previous actions.\n\
              This summary should be thorough in capturing technical details, code patterns, and architectural decisions that would be essential for continuing development work without losing context.\n\n\
@@ -169,6 +169,7 @@ mod tests {
                     image_output: false,
                     audio_input: false,
                     interleaved: false,
+                    tool_call: true,
                     providers: vec!["openai".to_string()],
                     provider_mappings: None,
                     pricing: Some(ModelPricing {
@@ -179,6 +179,7 @@ mod tests {
                     image_output: false,
                     audio_input: false,
                     interleaved: false,
+                    tool_call: true,
                     providers: vec![provider_id.to_string()],
                     provider_mappings: None,
                     pricing: Some(ModelPricing {
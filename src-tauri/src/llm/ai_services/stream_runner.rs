@@ -1,6 +1,7 @@
 use crate::llm::protocols::stream_parser::StreamParseState;
 use crate::llm::providers::provider::ProviderContext;
 use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::redaction::Redactor;
 use crate::llm::types::{StreamEvent, StreamTextRequest};
 use futures_util::StreamExt;
 use std::time::Duration;
@@ -20,7 +21,7 @@ impl StreamRunner {
 
     pub async fn stream<F>(
         &self,
-        request: StreamTextRequest,
+        mut request: StreamTextRequest,
         timeout: Duration,
         mut on_event: F,
     ) -> Result<(), String>
@@ -36,6 +37,14 @@ impl StreamRunner {
             .ok_or_else(|| format!("Provider not found: {}", provider_id))?;
         let provider_config = provider.config();
 
+        let redactions = Redactor::load(&self.api_keys)
+            .await
+            .redact_messages(&mut request.messages);
+        if !redactions.is_empty() {
+            log::warn!("Redacted secrets from request: {:?}", redactions);
+            on_event(StreamEvent::SecretsRedacted { redactions });
+        }
+
         let provider_ctx = ProviderContext {
             provider_config,
             api_key_manager: &self.api_keys,
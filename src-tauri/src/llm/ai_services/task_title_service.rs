@@ -3,7 +3,9 @@ use crate::llm::ai_services::stream_collector::StreamCollector;
 use crate::llm::ai_services::stream_runner::StreamRunner;
 use crate::llm::ai_services::types::{TitleGenerationRequest, TitleGenerationResult};
 use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::models::model_registry::ModelRegistry;
 use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::storage::ModelPhase;
 use std::time::Duration;
 
 pub struct TaskTitleService;
@@ -43,7 +45,11 @@ impl TaskTitleService {
             prompt.len()
         );
 
-        let preferred_model = request.model.clone();
+        let preferred_model = request.model.clone().or_else(|| {
+            request.settings.as_ref().and_then(|settings| {
+                ModelRegistry::resolve_phase_model(settings, ModelPhase::Title)
+            })
+        });
         let model_identifier = resolve_model_identifier(
             api_keys,
             registry,
@@ -173,6 +179,7 @@ mod tests {
                     image_output: false,
                     audio_input: false,
                     interleaved: false,
+                    tool_call: true,
                     providers: vec!["openai".to_string()],
                     provider_mappings: None,
                     pricing: Some(ModelPricing {
@@ -253,6 +260,7 @@ mod tests {
             user_input: "   ".to_string(),
             language: None,
             model: None,
+            settings: None,
         };
 
         let result = service.generate_title(request, &api_keys, &registry).await;
@@ -1,3 +1,4 @@
+use crate::storage::TaskSettings;
 use serde::{Deserialize, Serialize};
 
 // Completion Service Types
@@ -94,6 +95,10 @@ pub struct TitleGenerationRequest {
     pub user_input: String,
     pub language: Option<String>,
     pub model: Option<String>,
+    /// Settings of the task being titled; their title model is used when
+    /// `model` is not set
+    #[serde(default)]
+    pub settings: Option<TaskSettings>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,3 +1,5 @@
+use crate::llm::auth::keychain::{self, Keychain, KEYCHAIN_PLACEHOLDER};
+use crate::llm::redaction;
 use crate::llm::types::CustomProvidersConfiguration;
 use crate::llm::types::{AuthType, ModelsConfiguration, ProviderConfig};
 use reqwest::Client;
@@ -27,10 +29,30 @@ const GITHUB_COPILOT_PLUGIN_VERSION: &str = "copilot-chat/0.35.0";
 const GITHUB_COPILOT_INTEGRATION_ID: &str = "vscode-chat";
 const GITHUB_COPILOT_TOKEN_BUFFER_SECONDS: i64 = 60;
 
+/// Settings holding OAuth and sign-in tokens
+const TOKEN_SETTING_KEYS: [&str; 7] = [
+    "openai_oauth_access_token",
+    "openai_oauth_refresh_token",
+    "claude_oauth_access_token",
+    "claude_oauth_refresh_token",
+    GITHUB_COPILOT_ACCESS_TOKEN_KEY,
+    GITHUB_COPILOT_COPILOT_TOKEN_KEY,
+    "talkcody_auth_token",
+];
+
+/// Whether a setting holds an API key or a token
+pub fn is_credential_setting(key: &str) -> bool {
+    key.starts_with("api_key_")
+        || key.starts_with("custom_api_key_")
+        || TOKEN_SETTING_KEYS.contains(&key)
+}
+
 pub struct ApiKeyManager {
     db: Arc<Database>,
     app_data_dir: PathBuf,
     models_cache: RwLock<Option<ModelsCacheEntry>>,
+    /// Where credentials are kept; None keeps them in the settings table
+    keychain: Option<Arc<dyn Keychain>>,
 }
 
 struct ModelsCacheEntry {
@@ -45,6 +67,7 @@ impl Clone for ApiKeyManager {
             db: self.db.clone(),
             app_data_dir: self.app_data_dir.clone(),
             models_cache: RwLock::new(None),
+            keychain: self.keychain.clone(),
         }
     }
 }
@@ -55,6 +78,7 @@ impl ApiKeyManager {
             db,
             app_data_dir,
             models_cache: RwLock::new(None),
+            keychain: keychain::system_keychain(),
         }
     }
 
@@ -160,6 +184,112 @@ impl ApiKeyManager {
     }
 
     pub async fn get_setting(&self, key: &str) -> Result<Option<String>, String> {
+        match self.read_setting(key).await? {
+            Some(value) if is_credential_setting(key) => self.resolve_credential(key, value).await,
+            value => Ok(value),
+        }
+    }
+
+    pub async fn set_setting(&self, key: &str, value: &str) -> Result<(), String> {
+        match &self.keychain {
+            Some(keychain) if is_credential_setting(key) => {
+                if value == KEYCHAIN_PLACEHOLDER {
+                    // Already in the keychain
+                } else if keychain::is_secret_value(value) {
+                    if let Err(e) = self.store_in_keychain(keychain, key, value).await {
+                        log::warn!("{}; keeping {} in the settings table", e, key);
+                        self.write_setting(key, value).await?;
+                    }
+                } else {
+                    let name = key.to_string();
+                    if let Err(e) = keychain::run(keychain, move |k| k.delete(&name)).await {
+                        log::warn!("{}", e);
+                    }
+                    self.write_setting(key, value).await?;
+                }
+            }
+            _ => self.write_setting(key, value).await?,
+        }
+
+        // Logs and events mask the stored keys and tokens by value
+        if key == redaction::SETTINGS_KEY || is_credential_setting(key) {
+            redaction::refresh_shared(self).await;
+        }
+        Ok(())
+    }
+
+    /// A credential from its settings row: read from the keychain when the
+    /// row holds the placeholder, or moved there when it holds the value
+    async fn resolve_credential(&self, key: &str, value: String) -> Result<Option<String>, String> {
+        let Some(keychain) = &self.keychain else {
+            if value == KEYCHAIN_PLACEHOLDER {
+                log::warn!("{} is in a keychain that is not available", key);
+                return Ok(None);
+            }
+            return Ok(Some(value));
+        };
+        if value == KEYCHAIN_PLACEHOLDER {
+            let name = key.to_string();
+            return keychain::run(keychain, move |k| k.get(&name)).await;
+        }
+        if keychain::is_secret_value(&value) {
+            if let Err(e) = self.store_in_keychain(keychain, key, &value).await {
+                log::warn!("{}; keeping {} in the settings table", e, key);
+            }
+        }
+        Ok(Some(value))
+    }
+
+    /// Store a credential in the keychain, leaving the placeholder in its
+    /// settings row
+    async fn store_in_keychain(
+        &self,
+        keychain: &Arc<dyn Keychain>,
+        key: &str,
+        value: &str,
+    ) -> Result<(), String> {
+        let (name, secret) = (key.to_string(), value.to_string());
+        keychain::run(keychain, move |k| k.set(&name, &secret)).await?;
+        self.write_setting(key, KEYCHAIN_PLACEHOLDER).await
+    }
+
+    /// Move the credentials still in the settings table into the keychain,
+    /// and drop those cleared there from it; returns how many were moved
+    pub async fn migrate_credentials(&self) -> Result<usize, String> {
+        let Some(keychain) = &self.keychain else {
+            return Ok(0);
+        };
+        let rows = self
+            .db
+            .query("SELECT key, value FROM settings", vec![])
+            .await?;
+
+        let mut moved = 0;
+        for row in rows.rows {
+            let (Some(key), Some(value)) = (
+                row.get("key").and_then(|v| v.as_str()),
+                row.get("value").and_then(|v| v.as_str()),
+            ) else {
+                continue;
+            };
+            if !is_credential_setting(key) {
+                continue;
+            }
+            if value.trim().is_empty() {
+                let name = key.to_string();
+                keychain::run(keychain, move |k| k.delete(&name)).await?;
+            } else if keychain::is_secret_value(value) {
+                self.store_in_keychain(keychain, key, value).await?;
+                moved += 1;
+            }
+        }
+        if moved > 0 {
+            log::info!("Moved {} credentials into the keychain", moved);
+        }
+        Ok(moved)
+    }
+
+    async fn read_setting(&self, key: &str) -> Result<Option<String>, String> {
         let result = self
             .db
             .query(SETTINGS_SELECT, vec![Value::String(key.to_string())])
@@ -173,7 +303,7 @@ impl ApiKeyManager {
             .map(|v| v.to_string()))
     }
 
-    pub async fn set_setting(&self, key: &str, value: &str) -> Result<(), String> {
+    async fn write_setting(&self, key: &str, value: &str) -> Result<(), String> {
         let now = chrono::Utc::now().timestamp_millis();
         self.db
             .execute(
@@ -189,12 +319,21 @@ impl ApiKeyManager {
     }
 
     pub async fn load_api_keys(&self) -> Result<HashMap<String, String>, String> {
+        self.load_keys_with_prefix("api_key_").await
+    }
+
+    /// Custom provider API keys by provider, including those kept in the keychain
+    pub async fn load_custom_api_keys(&self) -> Result<HashMap<String, String>, String> {
+        self.load_keys_with_prefix("custom_api_key_").await
+    }
+
+    async fn load_keys_with_prefix(&self, prefix: &str) -> Result<HashMap<String, String>, String> {
         let mut api_keys = HashMap::new();
         let keys = self
             .db
             .query(
-                "SELECT key, value FROM settings WHERE key LIKE 'api_key_%'",
-                vec![],
+                "SELECT key, value FROM settings WHERE key LIKE $1",
+                vec![Value::String(format!("{}%", prefix))],
             )
             .await?;
 
@@ -202,9 +341,15 @@ impl ApiKeyManager {
             if let (Some(key), Some(value)) = (row.get("key"), row.get("value")) {
                 let key_str = key.as_str().unwrap_or_default();
                 let value_str = value.as_str().unwrap_or_default();
-                if let Some(provider_id) = key_str.strip_prefix("api_key_") {
-                    if !value_str.is_empty() {
-                        api_keys.insert(provider_id.to_string(), value_str.to_string());
+                if let Some(provider_id) = key_str.strip_prefix(prefix) {
+                    if value_str.is_empty() {
+                        continue;
+                    }
+                    let resolved = self
+                        .resolve_credential(key_str, value_str.to_string())
+                        .await?;
+                    if let Some(value) = resolved.filter(|value| !value.is_empty()) {
+                        api_keys.insert(provider_id.to_string(), value);
                     }
                 }
             }
@@ -452,6 +597,19 @@ impl ApiKeyManager {
         Ok(())
     }
 
+    /// Stored OAuth and sign-in tokens as they are, without refreshing any
+    pub async fn load_stored_tokens(&self) -> Result<Vec<String>, String> {
+        let mut tokens = Vec::new();
+        for key in TOKEN_SETTING_KEYS {
+            if let Some(token) = self.get_setting(key).await? {
+                if !token.trim().is_empty() {
+                    tokens.push(token);
+                }
+            }
+        }
+        Ok(tokens)
+    }
+
     pub async fn load_oauth_tokens(&self) -> Result<HashMap<String, String>, String> {
         let mut tokens = HashMap::new();
         if let Some(token) = self.get_setting("openai_oauth_access_token").await? {
@@ -517,6 +675,27 @@ pub async fn llm_set_setting(
     api_keys.set_setting(&key, &value).await
 }
 
+/// Provider API keys by provider, including those kept in the keychain
+#[tauri::command]
+pub async fn llm_get_api_keys(
+    state: State<'_, LlmState>,
+) -> Result<HashMap<String, String>, String> {
+    let api_keys = state.api_keys.lock().await;
+    api_keys.load_api_keys().await
+}
+
+/// API key of a custom provider, including one kept in the keychain
+#[tauri::command]
+pub async fn llm_get_custom_provider_api_key(
+    provider_id: String,
+    state: State<'_, LlmState>,
+) -> Result<Option<String>, String> {
+    let api_keys = state.api_keys.lock().await;
+    api_keys
+        .get_setting(&format!("custom_api_key_{}", provider_id))
+        .await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -637,6 +816,122 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn credentials_are_kept_in_the_keychain() {
+        let mut ctx = setup().await;
+        ctx.api_keys
+            .write_setting("api_key_openai", "sk-legacy")
+            .await
+            .expect("write legacy key");
+        ctx.api_keys
+            .write_setting("api_key_ollama", "enabled")
+            .await
+            .expect("write local provider");
+        let keychain = Arc::new(keychain::MemoryKeychain::default());
+        ctx.api_keys.keychain = Some(keychain.clone());
+
+        // Keys stored before the keychain are moved on startup
+        assert_eq!(ctx.api_keys.migrate_credentials().await, Ok(1));
+        assert_eq!(
+            keychain.get("api_key_openai").unwrap().as_deref(),
+            Some("sk-legacy")
+        );
+        assert_eq!(
+            ctx.api_keys
+                .read_setting("api_key_openai")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some(KEYCHAIN_PLACEHOLDER)
+        );
+        let api_keys = ctx.api_keys.load_api_keys().await.expect("load keys");
+        assert_eq!(
+            api_keys.get("openai").map(String::as_str),
+            Some("sk-legacy")
+        );
+        assert_eq!(api_keys.get("ollama").map(String::as_str), Some("enabled"));
+
+        // New tokens go to the keychain; clearing one removes it
+        ctx.api_keys
+            .set_setting("claude_oauth_access_token", "oauth-token")
+            .await
+            .expect("set token");
+        assert_eq!(
+            ctx.api_keys
+                .get_setting("claude_oauth_access_token")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("oauth-token")
+        );
+        ctx.api_keys
+            .set_setting("claude_oauth_access_token", "")
+            .await
+            .expect("clear token");
+        assert_eq!(keychain.get("claude_oauth_access_token").unwrap(), None);
+
+        // A key written straight to the settings table is moved on its next read
+        ctx.api_keys
+            .write_setting("api_key_openai", "sk-new")
+            .await
+            .expect("write key");
+        assert_eq!(
+            ctx.api_keys
+                .get_setting("api_key_openai")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("sk-new")
+        );
+        assert_eq!(
+            keychain.get("api_key_openai").unwrap().as_deref(),
+            Some("sk-new")
+        );
+    }
+
+    #[tokio::test]
+    async fn custom_provider_keys_are_kept_in_the_keychain_and_redacted() {
+        let mut ctx = setup().await;
+        ctx.api_keys
+            .write_setting("custom_api_key_my-llm", "my-llm-0123456789")
+            .await
+            .expect("write custom key");
+        let keychain = Arc::new(keychain::MemoryKeychain::default());
+        ctx.api_keys.keychain = Some(keychain.clone());
+
+        assert_eq!(ctx.api_keys.migrate_credentials().await, Ok(1));
+        assert_eq!(
+            keychain.get("custom_api_key_my-llm").unwrap().as_deref(),
+            Some("my-llm-0123456789")
+        );
+        assert_eq!(
+            ctx.api_keys
+                .read_setting("custom_api_key_my-llm")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some(KEYCHAIN_PLACEHOLDER)
+        );
+        let custom_keys = ctx
+            .api_keys
+            .load_custom_api_keys()
+            .await
+            .expect("load keys");
+        assert_eq!(
+            custom_keys.get("my-llm").map(String::as_str),
+            Some("my-llm-0123456789")
+        );
+
+        // Logs and events still mask the key once it is in the keychain
+        let redactor = redaction::Redactor::load_with_stored_secrets(&ctx.api_keys).await;
+        assert_eq!(
+            redactor
+                .redact_text("Calling with key my-llm-0123456789")
+                .as_deref(),
+            Some("Calling with key [REDACTED:stored_secret]")
+        );
+    }
+
     #[tokio::test]
     async fn get_credentials_rejects_missing_talkcody_jwt() {
         let ctx = setup().await;
@@ -0,0 +1,132 @@
+//! Keychain
+//!
+//! Provider API keys and OAuth tokens are kept in the platform keychain: the
+//! macOS Keychain, the Windows Credential Manager or the Linux Secret
+//! Service. Their settings rows hold a placeholder instead, so checks for
+//! whether a key is set still work. Values found in the settings table,
+//! stored before the keychain was used or written there by the frontend, are
+//! moved on their next read. Where there is no keychain, as on headless
+//! Linux without a Secret Service, credentials stay in the settings table.
+
+use std::sync::{Arc, OnceLock};
+
+/// Service the credentials are stored under
+const SERVICE: &str = "com.talkcody";
+
+/// Settings value standing for a credential kept in the keychain
+pub const KEYCHAIN_PLACEHOLDER: &str = "keychain:stored";
+
+/// Set to keep credentials in the settings table
+const DISABLE_ENV: &str = "TALKCODY_DISABLE_KEYCHAIN";
+
+/// Local providers store `enabled` in place of a key
+const LOCAL_PROVIDER_MARKER: &str = "enabled";
+
+/// Store of secret values by name. Calls may block.
+pub trait Keychain: Send + Sync {
+    fn get(&self, name: &str) -> Result<Option<String>, String>;
+    fn set(&self, name: &str, value: &str) -> Result<(), String>;
+    fn delete(&self, name: &str) -> Result<(), String>;
+}
+
+/// The platform keychain
+struct SystemKeychain;
+
+impl SystemKeychain {
+    fn entry(name: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(SERVICE, name)
+            .map_err(|e| format!("Failed to open keychain entry {}: {}", name, e))
+    }
+}
+
+impl Keychain for SystemKeychain {
+    fn get(&self, name: &str) -> Result<Option<String>, String> {
+        match Self::entry(name)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!("Failed to read {} from the keychain: {}", name, e)),
+        }
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<(), String> {
+        Self::entry(name)?
+            .set_password(value)
+            .map_err(|e| format!("Failed to store {} in the keychain: {}", name, e))
+    }
+
+    fn delete(&self, name: &str) -> Result<(), String> {
+        match Self::entry(name)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!(
+                "Failed to delete {} from the keychain: {}",
+                name, e
+            )),
+        }
+    }
+}
+
+/// The platform keychain, or None where it is unavailable or disabled.
+/// Probed once per process; tests never touch it.
+pub fn system_keychain() -> Option<Arc<dyn Keychain>> {
+    static KEYCHAIN: OnceLock<Option<Arc<dyn Keychain>>> = OnceLock::new();
+    KEYCHAIN
+        .get_or_init(|| {
+            if cfg!(test) || std::env::var_os(DISABLE_ENV).is_some() {
+                return None;
+            }
+            // Reading a missing entry fails without a keychain service to ask
+            match SystemKeychain.get("keychain_probe") {
+                Ok(_) => Some(Arc::new(SystemKeychain) as Arc<dyn Keychain>),
+                Err(e) => {
+                    log::warn!("{}; keeping credentials in the settings table", e);
+                    None
+                }
+            }
+        })
+        .clone()
+}
+
+/// Run a keychain call off the async runtime
+pub async fn run<T, F>(keychain: &Arc<dyn Keychain>, call: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&dyn Keychain) -> Result<T, String> + Send + 'static,
+{
+    let keychain = keychain.clone();
+    tokio::task::spawn_blocking(move || call(keychain.as_ref()))
+        .await
+        .map_err(|e| format!("Keychain task failed: {}", e))?
+}
+
+/// Whether a credential setting's value is a secret to keep in the keychain
+pub fn is_secret_value(value: &str) -> bool {
+    let value = value.trim();
+    !value.is_empty() && value != KEYCHAIN_PLACEHOLDER && value != LOCAL_PROVIDER_MARKER
+}
+
+/// Keychain held in memory, for tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryKeychain {
+    values: std::sync::Mutex<std::collections::HashMap<String, String>>,
+}
+
+#[cfg(test)]
+impl Keychain for MemoryKeychain {
+    fn get(&self, name: &str) -> Result<Option<String>, String> {
+        Ok(self.values.lock().unwrap().get(name).cloned())
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<(), String> {
+        self.values
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> Result<(), String> {
+        self.values.lock().unwrap().remove(name);
+        Ok(())
+    }
+}
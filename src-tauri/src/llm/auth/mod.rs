@@ -1,3 +1,4 @@
 pub mod api_key_manager;
+pub mod keychain;
 pub mod oauth;
 pub mod openai_usage;
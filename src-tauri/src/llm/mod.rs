@@ -4,6 +4,7 @@ pub mod commands;
 pub mod models;
 pub mod protocols;
 pub mod providers;
+pub mod redaction;
 pub mod streaming;
 pub mod testing;
 pub mod tracing;
@@ -1,6 +1,7 @@
 use crate::llm::auth::api_key_manager::ApiKeyManager;
 use crate::llm::providers::provider_registry::ProviderRegistry;
 use crate::llm::types::{AvailableModel, CustomProvidersConfiguration, ModelsConfiguration};
+use crate::storage::{ModelPhase, TaskSettings};
 use std::collections::HashMap;
 #[cfg(test)]
 use std::sync::Arc;
@@ -165,6 +166,34 @@ impl ModelRegistry {
         result
     }
 
+    /// Model a task uses for a phase: the phase's own model, then the
+    /// task's default model. Planning falls back to the coding model first.
+    /// `None` leaves the choice to the caller's fallback.
+    pub fn resolve_phase_model(settings: &TaskSettings, phase: ModelPhase) -> Option<String> {
+        let models = settings.models.as_ref();
+        let phase_model = models.and_then(|models| match phase {
+            ModelPhase::Planning => models
+                .get(ModelPhase::Planning)
+                .or(models.get(ModelPhase::Coding)),
+            phase => models.get(phase),
+        });
+        let legacy_key = match phase {
+            ModelPhase::Planning | ModelPhase::Coding => Some("model"),
+            ModelPhase::Summarization => Some("compactionModel"),
+            ModelPhase::Title => None,
+        };
+
+        phase_model
+            .or_else(|| {
+                legacy_key
+                    .and_then(|key| settings.extra.get(key))
+                    .and_then(|model| model.as_str())
+            })
+            .map(str::trim)
+            .filter(|model| !model.is_empty())
+            .map(str::to_string)
+    }
+
     pub fn resolve_provider_model_name(
         model_key: &str,
         provider_id: &str,
@@ -364,6 +393,7 @@ mod tests {
                 image_output: false,
                 audio_input: false,
                 interleaved: false,
+                tool_call: true,
                 providers: vec![
                     "openai".to_string(),
                     "ollama".to_string(),
@@ -423,6 +453,7 @@ mod tests {
             image_output: false,
             audio_input: false,
             interleaved: false,
+            tool_call: true,
             providers: vec!["custom".to_string()],
             provider_mappings: None,
             pricing: Some(ModelPricing {
@@ -452,6 +483,32 @@ mod tests {
         assert!(!loaded.models.contains_key("gpt-4o"));
     }
 
+    #[test]
+    fn resolve_phase_model_prefers_phase_then_default() {
+        let mut settings = TaskSettings::default();
+        settings
+            .extra
+            .insert("model".to_string(), serde_json::json!("gpt-4o"));
+        settings.models = Some(crate::storage::PhaseModels {
+            title: Some("gpt-4o-mini".to_string()),
+            coding: Some("claude-sonnet@anthropic".to_string()),
+            ..Default::default()
+        });
+
+        let resolve = |phase| ModelRegistry::resolve_phase_model(&settings, phase);
+        assert_eq!(resolve(ModelPhase::Title).as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(
+            resolve(ModelPhase::Planning).as_deref(),
+            Some("claude-sonnet@anthropic")
+        );
+        assert_eq!(resolve(ModelPhase::Summarization), None);
+
+        settings.models = None;
+        let resolve = |phase| ModelRegistry::resolve_phase_model(&settings, phase);
+        assert_eq!(resolve(ModelPhase::Coding).as_deref(), Some("gpt-4o"));
+        assert_eq!(resolve(ModelPhase::Title), None);
+    }
+
     #[test]
     fn resolve_provider_model_name_uses_mapping() {
         let config = build_models_config();
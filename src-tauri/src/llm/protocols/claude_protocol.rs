@@ -92,15 +92,18 @@ impl ClaudeProtocol {
                             text,
                             provider_options,
                         } => {
-                            let mut thinking = json!({ "type": "thinking", "text": text });
-                            if let Some(opts) = provider_options {
-                                if let Some(signature) =
-                                    opts.get("anthropic").and_then(|v| v.get("signature"))
-                                {
-                                    thinking["signature"] = signature.clone();
-                                }
+                            // Anthropic rejects thinking blocks it didn't sign
+                            if let Some(signature) = provider_options
+                                .as_ref()
+                                .and_then(|opts| opts.get("anthropic"))
+                                .and_then(|v| v.get("signature"))
+                            {
+                                mapped.push(json!({
+                                    "type": "thinking",
+                                    "thinking": text,
+                                    "signature": signature
+                                }));
                             }
-                            mapped.push(thinking);
                         }
                     }
                 }
@@ -297,7 +300,11 @@ impl LlmProtocol for ClaudeProtocol {
                             }
                         }
                         "thinking_delta" => {
-                            if let Some(text) = delta.get("text").and_then(|v| v.as_str()) {
+                            if let Some(text) = delta
+                                .get("thinking")
+                                .or_else(|| delta.get("text"))
+                                .and_then(|v| v.as_str())
+                            {
                                 let id = state
                                     .current_thinking_id
                                     .clone()
@@ -609,6 +616,39 @@ mod tests {
         assert_eq!(body.get("max_output_tokens"), Some(&json!(128)));
     }
 
+    #[test]
+    fn build_messages_sends_back_signed_thinking() {
+        let protocol = ClaudeProtocol;
+        let messages = vec![Message::Assistant {
+            content: MessageContent::Parts(vec![
+                ContentPart::Reasoning {
+                    text: "Check the readme".to_string(),
+                    provider_options: Some(json!({ "anthropic": { "signature": "sig-1" } })),
+                },
+                ContentPart::Reasoning {
+                    text: "unsigned".to_string(),
+                    provider_options: None,
+                },
+                ContentPart::ToolCall {
+                    tool_call_id: "call-1".to_string(),
+                    tool_name: "read_file".to_string(),
+                    input: json!({}),
+                    provider_metadata: None,
+                },
+            ]),
+            provider_options: None,
+        }];
+
+        let built = protocol.build_messages(&messages);
+        let content = built[0]["content"].as_array().expect("content");
+        assert_eq!(content.len(), 2);
+        assert_eq!(
+            content[0],
+            json!({ "type": "thinking", "thinking": "Check the readme", "signature": "sig-1" })
+        );
+        assert_eq!(content[1]["type"], "tool_use");
+    }
+
     #[test]
     fn parse_stream_emits_reasoning_signature_delta() {
         let protocol = ClaudeProtocol;
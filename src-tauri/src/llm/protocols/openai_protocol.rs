@@ -107,6 +107,7 @@ impl OpenAiProtocol {
         provider_options: Option<&Value>,
     ) -> Value {
         let mut content_value = Value::Null;
+        let mut reasoning_chunks: Vec<&str> = Vec::new();
 
         match content {
             MessageContent::Text(text) => {
@@ -129,8 +130,7 @@ impl OpenAiProtocol {
                         }
                         ContentPart::Reasoning { text, .. } => {
                             if !text.trim().is_empty() {
-                                text_chunks.push(text.clone());
-                                rich_parts.push(json!({ "type": "text", "text": text }));
+                                reasoning_chunks.push(text);
                             }
                         }
                         ContentPart::Image { image } => {
@@ -157,6 +157,10 @@ impl OpenAiProtocol {
             "role": "assistant",
             "content": content_value
         });
+        // Reasoning models expect their reasoning back beside the content
+        if !reasoning_chunks.is_empty() {
+            message["reasoning_content"] = json!(reasoning_chunks.join(""));
+        }
 
         if let MessageContent::Parts(parts) = content {
             let mut tool_calls: Vec<Value> = Vec::new();
@@ -696,6 +700,28 @@ mod tests {
     use serde_json::json;
     use std::collections::HashMap;
 
+    #[test]
+    fn build_assistant_message_sends_reasoning_as_reasoning_content() {
+        let protocol = OpenAiProtocol;
+        let content = MessageContent::Parts(vec![
+            ContentPart::Reasoning {
+                text: "Check the readme".to_string(),
+                provider_options: None,
+            },
+            ContentPart::ToolCall {
+                tool_call_id: "call-1".to_string(),
+                tool_name: "read_file".to_string(),
+                input: json!({}),
+                provider_metadata: None,
+            },
+        ]);
+
+        let message = protocol.build_assistant_message(&content, None);
+        assert_eq!(message["reasoning_content"], "Check the readme");
+        assert_eq!(message["content"], Value::Null);
+        assert_eq!(message["tool_calls"][0]["id"], "call-1");
+    }
+
     #[test]
     fn parse_stream_emits_reasoning_events_from_reasoning_content() {
         let protocol = OpenAiProtocol;
@@ -297,6 +297,36 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             extra_body: None,
             auth_type: AuthType::Bearer,
         },
+        ProviderConfig {
+            id: "brave".to_string(),
+            name: "Brave Web Search".to_string(),
+            protocol: ProtocolType::OpenAiCompatible,
+            base_url: "https://api.search.brave.com".to_string(),
+            api_key_name: "BRAVE_API_KEY".to_string(),
+            supports_oauth: false,
+            supports_coding_plan: false,
+            supports_international: false,
+            coding_plan_base_url: None,
+            international_base_url: None,
+            headers: None,
+            extra_body: None,
+            auth_type: AuthType::Bearer,
+        },
+        ProviderConfig {
+            id: "bing".to_string(),
+            name: "Bing Web Search".to_string(),
+            protocol: ProtocolType::OpenAiCompatible,
+            base_url: "https://api.bing.microsoft.com".to_string(),
+            api_key_name: "BING_API_KEY".to_string(),
+            supports_oauth: false,
+            supports_coding_plan: false,
+            supports_international: false,
+            coding_plan_base_url: None,
+            international_base_url: None,
+            headers: None,
+            extra_body: None,
+            auth_type: AuthType::Bearer,
+        },
         ProviderConfig {
             id: "elevenlabs".to_string(),
             name: "Eleven Labs Text-to-Speech".to_string(),
@@ -2,6 +2,7 @@ use crate::llm::auth::api_key_manager::ApiKeyManager;
 use crate::llm::protocols::stream_parser::StreamParseState;
 use crate::llm::providers::provider::ProviderContext;
 use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::redaction::Redactor;
 use crate::llm::testing::fixtures::FixtureInput;
 use crate::llm::testing::{Recorder, RecordingContext, TestConfig, TestMode};
 use crate::llm::tracing::types::{float_attr, int_attr};
@@ -32,7 +33,7 @@ impl StreamHandler {
     pub async fn stream_completion(
         &self,
         window: tauri::Window,
-        request: StreamTextRequest,
+        mut request: StreamTextRequest,
         request_id: String,
     ) -> Result<String, String> {
         // Use provided request_id if non-zero, otherwise generate one
@@ -69,6 +70,23 @@ impl StreamHandler {
             provider_config.protocol
         );
 
+        let redactions = Redactor::load(&self.api_keys)
+            .await
+            .redact_messages(&mut request.messages);
+        if !redactions.is_empty() {
+            log::warn!(
+                "[LLM Stream {}] Redacted secrets from request: {:?}",
+                request_id,
+                redactions
+            );
+            self.emit_stream_event(
+                &window,
+                &event_name,
+                &request_id,
+                &StreamEvent::SecretsRedacted { redactions },
+            );
+        }
+
         let provider_ctx = ProviderContext {
             provider_config,
             api_key_manager: &self.api_keys,
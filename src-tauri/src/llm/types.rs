@@ -55,6 +55,9 @@ pub struct ModelConfig {
     pub audio_input: bool,
     #[serde(default)]
     pub interleaved: bool,
+    /// Whether the model can call tools; assumed unless the config says otherwise
+    #[serde(default = "default_tool_call", rename = "toolCall")]
+    pub tool_call: bool,
     pub providers: Vec<String>,
     #[serde(rename = "providerMappings")]
     pub provider_mappings: Option<HashMap<String, String>>,
@@ -62,6 +65,10 @@ pub struct ModelConfig {
     pub context_length: Option<u32>,
 }
 
+fn default_tool_call() -> bool {
+    true
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelPricing {
     pub input: String,
@@ -254,6 +261,10 @@ pub enum StreamEvent {
     Raw {
         raw_value: String,
     },
+    /// Secrets were masked in the request before it was sent
+    SecretsRedacted {
+        redactions: Vec<crate::llm::redaction::Redaction>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
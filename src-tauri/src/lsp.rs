@@ -6,19 +6,23 @@
 //
 // LSP servers are automatically downloaded to ~/.talkcody/lsp-servers/
 
+use crate::platform::types::{LspPosition, LspRange};
 use flate2::read::GzDecoder;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, ChildStdin, ChildStdout, Command as TokioCommand};
-use tokio::sync::Mutex;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::process::{Child, ChildStderr, Command as TokioCommand};
+use tokio::sync::{oneshot, Mutex};
 use tokio::task::JoinHandle;
 
 /// Result of attempting to reserve a server creation slot
@@ -159,21 +163,96 @@ impl LspRegistry {
         self.server_index
             .contains_key(&(language.to_string(), root_path.to_string()))
     }
+
+    /// List server IDs whose root path is `root_path` or nested inside it
+    pub fn server_ids_for_root(&self, root_path: &str) -> Vec<String> {
+        let root = Path::new(root_path);
+        self.server_index
+            .iter()
+            .filter(|((_, server_root), _)| Path::new(server_root).starts_with(root))
+            .map(|(_, server_id)| server_id.clone())
+            .collect()
+    }
+
+    /// Describe the registered servers, ordered by ID
+    pub async fn describe(&self) -> Vec<LspServerDiagnostics> {
+        let mut servers = Vec::with_capacity(self.servers.len());
+        for server in self.servers.values() {
+            let server = server.lock().await;
+            servers.push(LspServerDiagnostics {
+                server_id: server.server_id.clone(),
+                language: server.language.clone(),
+                root_path: server.root_path.clone(),
+                initialized: server.is_initialized,
+                idle_secs: server.last_activity.elapsed().as_secs(),
+            });
+        }
+        servers.sort_by(|a, b| a.server_id.cmp(&b.server_id));
+        servers
+    }
 }
 
 /// Global LSP registry state
 pub struct LspState(pub Mutex<LspRegistry>);
 
+/// A registered LSP server, as shown on diagnostics pages
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LspServerDiagnostics {
+    pub server_id: String,
+    pub language: String,
+    pub root_path: String,
+    pub initialized: bool,
+    /// Seconds since the frontend last sent the server a message
+    pub idle_secs: u64,
+}
+
+/// Write half of an LSP transport
+pub type LspWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Read half of an LSP transport
+type LspReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// How the client talks to an LSP server
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum LspTransport {
+    /// Spawn the server and speak JSON-RPC over its stdin/stdout
+    #[default]
+    Stdio,
+    /// Connect to an already running server listening on host:port
+    Tcp { host: String, port: u16 },
+}
+
+/// An opened transport, ready to be attached to an `LspServer`
+struct LspConnection {
+    child: Option<Child>,
+    reader: LspReader,
+    writer: LspWriter,
+    stderr: Option<ChildStderr>,
+}
+
+/// Channel resolving a backend-issued request with its result or error message
+type PendingResponse = oneshot::Sender<Result<serde_json::Value, String>>;
+
 /// LSP server instance
 pub struct LspServer {
     pub server_id: String,
     pub language: String,
     pub root_path: String,
+    /// Server process (absent when connecting to an already running TCP server)
     pub child: Option<Child>,
-    pub stdin: Option<ChildStdin>,
+    /// Write half of the transport (process stdin or socket)
+    pub writer: Option<LspWriter>,
     pub stdout_task: Option<JoinHandle<()>>,
     pub stderr_task: Option<JoinHandle<()>>,
     pub is_initialized: bool,
+    /// File watchers registered via `client/registerCapability`, keyed by registration id
+    pub file_watchers: HashMap<String, Vec<FileSystemWatcher>>,
+    /// Last time the frontend sent a message to this server
+    pub last_activity: Instant,
+    /// Requests issued by the backend itself, awaiting a response
+    pending_requests: HashMap<String, PendingResponse>,
 }
 
 impl LspServer {
@@ -183,12 +262,20 @@ impl LspServer {
             language,
             root_path,
             child: None,
-            stdin: None,
+            writer: None,
             stdout_task: None,
             stderr_task: None,
             is_initialized: false,
+            file_watchers: HashMap::new(),
+            last_activity: Instant::now(),
+            pending_requests: HashMap::new(),
         }
     }
+
+    /// Check whether the server has received no messages for at least `timeout`
+    pub fn is_idle(&self, timeout: Duration) -> bool {
+        self.last_activity.elapsed() >= timeout
+    }
 }
 
 /// LSP server configuration
@@ -198,6 +285,33 @@ pub struct LspServerConfig {
     pub command: String,
     pub args: Vec<String>,
     pub extensions: Vec<String>,
+    #[serde(default)]
+    pub transport: LspTransport,
+}
+
+/// User-declared LSP server from ~/.talkcody/lsp-servers.json
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomLspServerConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Language IDs served by this server
+    pub languages: Vec<String>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub transport: LspTransport,
+}
+
+/// Contents of ~/.talkcody/lsp-servers.json
+#[derive(Debug, Default, Deserialize)]
+struct CustomLspServersFile {
+    /// Servers keyed by a user-chosen name
+    #[serde(default)]
+    servers: BTreeMap<String, CustomLspServerConfig>,
 }
 
 /// Response for starting an LSP server
@@ -248,6 +362,43 @@ pub struct LspServerStatus {
     pub download_url: Option<String>,
 }
 
+/// Event emitted when the backend stops a server on its own
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LspServerStoppedEvent {
+    pub server_id: String,
+    pub reason: String,
+}
+
+/// Kind of file event reported in `workspace/didChangeWatchedFiles`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileChangeType {
+    Created = 1,
+    Changed = 2,
+    Deleted = 3,
+}
+
+impl FileChangeType {
+    /// Bit used by `FileSystemWatcher.kind` to subscribe to this change type
+    fn watch_kind_bit(self) -> u8 {
+        match self {
+            FileChangeType::Created => 1,
+            FileChangeType::Changed => 2,
+            FileChangeType::Deleted => 4,
+        }
+    }
+}
+
+/// A file system watcher registered by an LSP server
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileSystemWatcher {
+    pub glob_pattern: String,
+    /// Base directory for relative patterns; `None` means the server root
+    pub base_path: Option<String>,
+    /// Bitmask of watched change kinds (1 = create, 2 = change, 4 = delete)
+    pub kind: u8,
+}
+
 // ============================================================================
 // LSP Server Directory Management
 // ============================================================================
@@ -269,6 +420,89 @@ fn ensure_lsp_servers_dir() -> Result<PathBuf, String> {
     Ok(lsp_dir)
 }
 
+/// Get the path of the user-defined LSP servers config (~/.talkcody/lsp-servers.json)
+fn get_custom_servers_config_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home.join(".talkcody").join("lsp-servers.json"))
+}
+
+/// Parse the user-defined LSP servers config
+fn parse_custom_servers(content: &str) -> Result<BTreeMap<String, CustomLspServerConfig>, String> {
+    let file: CustomLspServersFile = serde_json::from_str(content)
+        .map_err(|e| format!("Failed to parse lsp-servers.json: {}", e))?;
+    Ok(file.servers)
+}
+
+/// Parsed lsp-servers.json along with the file metadata it was read at
+struct CachedCustomServers {
+    modified: SystemTime,
+    len: u64,
+    servers: BTreeMap<String, CustomLspServerConfig>,
+}
+
+static CUSTOM_SERVERS_CACHE: OnceLock<std::sync::Mutex<Option<CachedCustomServers>>> =
+    OnceLock::new();
+
+/// Read user-defined LSP servers, reparsing only when the file's modified time
+/// or size changed. A missing file yields no servers.
+fn read_custom_servers() -> Result<BTreeMap<String, CustomLspServerConfig>, String> {
+    let path = get_custom_servers_config_path()?;
+    let cache = CUSTOM_SERVERS_CACHE.get_or_init(|| std::sync::Mutex::new(None));
+    read_custom_servers_from(&path, cache)
+}
+
+fn read_custom_servers_from(
+    path: &Path,
+    cache: &std::sync::Mutex<Option<CachedCustomServers>>,
+) -> Result<BTreeMap<String, CustomLspServerConfig>, String> {
+    let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+    let Ok(metadata) = std::fs::metadata(path) else {
+        *cache = None;
+        return Ok(BTreeMap::new());
+    };
+    let modified = metadata.modified().ok();
+    let len = metadata.len();
+    if let Some(cached) = cache.as_ref() {
+        if Some(cached.modified) == modified && cached.len == len {
+            return Ok(cached.servers.clone());
+        }
+    }
+
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let servers = parse_custom_servers(&content)?;
+    // Without a modified time there is nothing to tell a later edit apart
+    *cache = modified.map(|modified| CachedCustomServers {
+        modified,
+        len,
+        servers: servers.clone(),
+    });
+    Ok(servers)
+}
+
+/// Load user-defined LSP servers. A missing or invalid file yields no servers.
+fn load_custom_servers() -> BTreeMap<String, CustomLspServerConfig> {
+    read_custom_servers().unwrap_or_else(|e| {
+        log::warn!("Ignoring custom LSP servers: {}", e);
+        BTreeMap::new()
+    })
+}
+
+/// Find the user-defined server for a language, if any
+fn find_custom_server(language: &str) -> Option<CustomLspServerConfig> {
+    find_custom_server_in(&load_custom_servers(), language)
+}
+
+fn find_custom_server_in(
+    servers: &BTreeMap<String, CustomLspServerConfig>,
+    language: &str,
+) -> Option<CustomLspServerConfig> {
+    servers
+        .values()
+        .find(|server| server.languages.iter().any(|l| l == language))
+        .cloned()
+}
+
 /// Get the path to a specific LSP server binary
 fn get_lsp_server_path(server_name: &str) -> Result<PathBuf, String> {
     let lsp_dir = get_lsp_servers_dir()?;
@@ -285,8 +519,12 @@ fn get_lsp_server_path(server_name: &str) -> Result<PathBuf, String> {
 // rust-analyzer Download
 // ============================================================================
 
-/// Get the download URL for rust-analyzer based on current platform
-fn get_rust_analyzer_download_url() -> Option<String> {
+/// GitHub API endpoint describing the latest rust-analyzer release
+const RUST_ANALYZER_RELEASE_API: &str =
+    "https://api.github.com/repos/rust-lang/rust-analyzer/releases/latest";
+
+/// Get the release asset name for rust-analyzer based on current platform
+fn get_rust_analyzer_asset_name() -> Option<String> {
     let (os, arch) = get_platform_info();
 
     let suffix = match (os.as_str(), arch.as_str()) {
@@ -298,10 +536,197 @@ fn get_rust_analyzer_download_url() -> Option<String> {
         _ => return None,
     };
 
-    Some(format!(
-        "https://github.com/rust-lang/rust-analyzer/releases/latest/download/rust-analyzer-{}",
-        suffix
-    ))
+    Some(format!("rust-analyzer-{}", suffix))
+}
+
+/// Get the download URL for rust-analyzer based on current platform
+fn get_rust_analyzer_download_url() -> Option<String> {
+    get_rust_analyzer_asset_name().map(|asset| {
+        format!(
+            "https://github.com/rust-lang/rust-analyzer/releases/latest/download/{}",
+            asset
+        )
+    })
+}
+
+/// A release asset together with its published sha256 checksum
+#[derive(Debug, PartialEq)]
+struct ReleaseAsset {
+    name: String,
+    download_url: String,
+    sha256: String,
+}
+
+/// Find an asset and its published digest in a GitHub release API response
+fn parse_release_asset(
+    release: &serde_json::Value,
+    asset_name: &str,
+) -> Result<ReleaseAsset, String> {
+    let asset = release
+        .get("assets")
+        .and_then(|a| a.as_array())
+        .and_then(|assets| {
+            assets
+                .iter()
+                .find(|a| a.get("name").and_then(|n| n.as_str()) == Some(asset_name))
+        })
+        .ok_or_else(|| format!("Release asset not found: {}", asset_name))?;
+
+    let download_url = asset
+        .get("browser_download_url")
+        .and_then(|u| u.as_str())
+        .ok_or_else(|| format!("Release asset has no download URL: {}", asset_name))?;
+
+    // GitHub publishes digests as "sha256:<hex>"
+    let sha256 = asset
+        .get("digest")
+        .and_then(|d| d.as_str())
+        .and_then(|d| d.strip_prefix("sha256:"))
+        .filter(|d| d.len() == 64 && d.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| format!("No published sha256 checksum for {}", asset_name))?;
+
+    Ok(ReleaseAsset {
+        name: asset_name.to_string(),
+        download_url: download_url.to_string(),
+        sha256: sha256.to_lowercase(),
+    })
+}
+
+/// Look up the latest rust-analyzer asset for this platform and its checksum
+async fn fetch_rust_analyzer_asset(client: &Client) -> Result<ReleaseAsset, String> {
+    let asset_name =
+        get_rust_analyzer_asset_name().ok_or("rust-analyzer is not available for this platform")?;
+
+    let response = client
+        .get(RUST_ANALYZER_RELEASE_API)
+        .header(reqwest::header::USER_AGENT, "talkcody")
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch rust-analyzer release info: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to fetch rust-analyzer release info: HTTP {}",
+            response.status()
+        ));
+    }
+
+    let release: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse rust-analyzer release info: {}", e))?;
+
+    parse_release_asset(&release, &asset_name)
+}
+
+/// Compute the sha256 of a file as lowercase hex
+fn sha256_file(path: &Path) -> Result<String, String> {
+    use sha2::{Digest, Sha256};
+
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Move an invalid download or binary out of the way so it is never executed.
+/// Returns the quarantine location.
+fn quarantine_file(lsp_dir: &Path, path: &Path) -> Result<PathBuf, String> {
+    let quarantine_dir = lsp_dir.join(".quarantine");
+    std::fs::create_dir_all(&quarantine_dir)
+        .map_err(|e| format!("Failed to create quarantine directory: {}", e))?;
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let target = quarantine_dir.join(format!("{}.{}", file_name, timestamp));
+
+    std::fs::rename(path, &target)
+        .map_err(|e| format!("Failed to quarantine {}: {}", path.display(), e))?;
+    log::warn!("Quarantined invalid LSP download: {:?}", target);
+    Ok(target)
+}
+
+/// Download `url` into `part_path`, resuming from the existing partial file via
+/// an HTTP Range request when possible.
+async fn download_resumable(
+    app: &AppHandle,
+    client: &Client,
+    url: &str,
+    part_path: &Path,
+) -> Result<(), String> {
+    let existing = std::fs::metadata(part_path).map(|m| m.len()).unwrap_or(0);
+
+    let mut request = client.get(url);
+    if existing > 0 {
+        log::info!("Resuming download from byte {}", existing);
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+    }
+
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to download rust-analyzer: {}", e))?;
+
+    // The partial file already holds the complete archive
+    if existing > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        return Ok(());
+    }
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to download rust-analyzer: HTTP {}",
+            response.status()
+        ));
+    }
+
+    // Servers that ignore Range reply 200 with the full body: start over
+    let resumed = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut downloaded = if resumed { existing } else { 0 };
+    let total = response.content_length().map(|len| len + downloaded);
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part_path)
+        .map_err(|e| format!("Failed to open download file: {}", e))?;
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read download: {}", e))?
+    {
+        std::io::Write::write_all(&mut file, &chunk)
+            .map_err(|e| format!("Failed to write download: {}", e))?;
+        downloaded += chunk.len() as u64;
+
+        if let Some(total) = total.filter(|t| *t > 0) {
+            // Reserve the last part of the progress bar for extraction
+            let progress = (downloaded as f32 / total as f32) * 0.9;
+            emit_download_progress(app, "rust", "downloading", Some(progress), None);
+        }
+    }
+
+    std::io::Write::flush(&mut file).map_err(|e| format!("Failed to write download: {}", e))?;
+    Ok(())
 }
 
 /// Get platform info (os, arch)
@@ -327,13 +752,14 @@ fn get_platform_info() -> (String, String) {
     (os.to_string(), arch.to_string())
 }
 
-/// Download rust-analyzer to the local LSP servers directory
+/// Download rust-analyzer to the local LSP servers directory.
+///
+/// The archive is downloaded to `.downloads/` (resuming interrupted downloads),
+/// verified against the sha256 published with the release, and only then
+/// extracted. Invalid archives or binaries are moved to `.quarantine/`.
 async fn download_rust_analyzer(app: &AppHandle) -> Result<PathBuf, String> {
     let lsp_dir = ensure_lsp_servers_dir()?;
-    let download_url = get_rust_analyzer_download_url()
-        .ok_or("rust-analyzer is not available for this platform")?;
-
-    log::info!("Downloading rust-analyzer from: {}", download_url);
+    let client = Client::new();
 
     // Emit progress event
     emit_download_progress(
@@ -344,27 +770,41 @@ async fn download_rust_analyzer(app: &AppHandle) -> Result<PathBuf, String> {
         Some("Starting download..."),
     );
 
-    // Download the file
-    let client = Client::new();
-    let response = client
-        .get(&download_url)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to download rust-analyzer: {}", e))?;
+    let asset = fetch_rust_analyzer_asset(&client).await?;
+    log::info!(
+        "Downloading rust-analyzer from: {} (sha256 {})",
+        asset.download_url,
+        asset.sha256
+    );
 
-    if !response.status().is_success() {
+    // Key the partial file by digest so a new release never resumes an old download
+    let downloads_dir = lsp_dir.join(".downloads");
+    std::fs::create_dir_all(&downloads_dir)
+        .map_err(|e| format!("Failed to create downloads directory: {}", e))?;
+    let part_path = downloads_dir.join(format!("{}.{}.part", asset.name, &asset.sha256[..12]));
+
+    download_resumable(app, &client, &asset.download_url, &part_path).await?;
+
+    emit_download_progress(
+        app,
+        "rust",
+        "verifying",
+        Some(0.9),
+        Some("Verifying checksum..."),
+    );
+
+    let actual = sha256_file(&part_path)?;
+    if actual != asset.sha256 {
+        quarantine_file(&lsp_dir, &part_path)?;
         return Err(format!(
-            "Failed to download rust-analyzer: HTTP {}",
-            response.status()
+            "Checksum mismatch for {}: expected {}, got {}",
+            asset.name, asset.sha256, actual
         ));
     }
 
-    let bytes = response
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read download: {}", e))?;
+    emit_download_progress(app, "rust", "extracting", Some(0.95), Some("Extracting..."));
 
-    emit_download_progress(app, "rust", "extracting", Some(0.5), Some("Extracting..."));
+    let bytes = std::fs::read(&part_path).map_err(|e| format!("Failed to read download: {}", e))?;
 
     // Determine output path
     #[cfg(target_os = "windows")]
@@ -372,30 +812,41 @@ async fn download_rust_analyzer(app: &AppHandle) -> Result<PathBuf, String> {
     #[cfg(not(target_os = "windows"))]
     let output_path = lsp_dir.join("rust-analyzer");
 
+    // Extract next to the final location and rename once verified, so a failed
+    // extraction never leaves a partial executable behind
+    let staging_path = lsp_dir.join(".rust-analyzer.staging");
+
     // Track if we successfully wrote the binary
     let mut binary_written = false;
 
     // Extract based on file type
-    if download_url.ends_with(".gz") {
+    if asset.name.ends_with(".gz") {
         // Extract gzip
         let mut decoder = GzDecoder::new(&bytes[..]);
         let mut decompressed = Vec::new();
-        decoder
-            .read_to_end(&mut decompressed)
-            .map_err(|e| format!("Failed to decompress: {}", e))?;
+        if let Err(e) = decoder.read_to_end(&mut decompressed) {
+            quarantine_file(&lsp_dir, &part_path)?;
+            return Err(format!("Failed to decompress: {}", e));
+        }
 
         if decompressed.is_empty() {
+            quarantine_file(&lsp_dir, &part_path)?;
             return Err("Downloaded file is empty after decompression".to_string());
         }
 
-        std::fs::write(&output_path, &decompressed)
+        std::fs::write(&staging_path, &decompressed)
             .map_err(|e| format!("Failed to write rust-analyzer: {}", e))?;
         binary_written = true;
-    } else if download_url.ends_with(".zip") {
+    } else if asset.name.ends_with(".zip") {
         // For Windows - extract zip
         let cursor = std::io::Cursor::new(&bytes[..]);
-        let mut archive =
-            zip::ZipArchive::new(cursor).map_err(|e| format!("Failed to open zip: {}", e))?;
+        let mut archive = match zip::ZipArchive::new(cursor) {
+            Ok(archive) => archive,
+            Err(e) => {
+                quarantine_file(&lsp_dir, &part_path)?;
+                return Err(format!("Failed to open zip: {}", e));
+            }
+        };
 
         // Look for rust-analyzer executable in the zip
         for i in 0..archive.len() {
@@ -414,10 +865,11 @@ async fn download_rust_analyzer(app: &AppHandle) -> Result<PathBuf, String> {
                     .map_err(|e| format!("Failed to read file from zip: {}", e))?;
 
                 if contents.is_empty() {
+                    quarantine_file(&lsp_dir, &part_path)?;
                     return Err("Extracted binary is empty".to_string());
                 }
 
-                std::fs::write(&output_path, &contents)
+                std::fs::write(&staging_path, &contents)
                     .map_err(|e| format!("Failed to write rust-analyzer: {}", e))?;
                 binary_written = true;
                 log::info!("Extracted {} from zip", file_name);
@@ -428,14 +880,15 @@ async fn download_rust_analyzer(app: &AppHandle) -> Result<PathBuf, String> {
 
     // Verify the binary was actually written
     if !binary_written {
+        quarantine_file(&lsp_dir, &part_path)?;
         return Err("Failed to extract rust-analyzer binary from archive".to_string());
     }
 
     // Verify the file exists and has non-zero size
-    let metadata = std::fs::metadata(&output_path)
+    let metadata = std::fs::metadata(&staging_path)
         .map_err(|e| format!("Failed to verify downloaded binary: {}", e))?;
     if metadata.len() == 0 {
-        std::fs::remove_file(&output_path).ok();
+        quarantine_file(&lsp_dir, &staging_path)?;
         return Err("Downloaded binary is empty".to_string());
     }
 
@@ -445,10 +898,14 @@ async fn download_rust_analyzer(app: &AppHandle) -> Result<PathBuf, String> {
         use std::os::unix::fs::PermissionsExt;
         let mut perms = metadata.permissions();
         perms.set_mode(0o755);
-        std::fs::set_permissions(&output_path, perms)
+        std::fs::set_permissions(&staging_path, perms)
             .map_err(|e| format!("Failed to set executable permission: {}", e))?;
     }
 
+    std::fs::rename(&staging_path, &output_path)
+        .map_err(|e| format!("Failed to install rust-analyzer: {}", e))?;
+    std::fs::remove_file(&part_path).ok();
+
     emit_download_progress(
         app,
         "rust",
@@ -671,6 +1128,11 @@ fn emit_download_progress(
 /// Get the command for a language server
 /// Returns (command, args) or None if not available
 fn get_lsp_command(language: &str) -> Option<(String, Vec<String>)> {
+    // User-defined servers take precedence over the built-in ones
+    if let Some(custom) = find_custom_server(language) {
+        return Some((custom.command, custom.args));
+    }
+
     match language {
         "typescript" | "javascript" | "typescriptreact" | "javascriptreact" => {
             get_typescript_server_command()
@@ -758,8 +1220,11 @@ fn parse_content_length(headers: &str) -> Option<usize> {
     None
 }
 
-/// Read a single LSP message from stdout
-async fn read_lsp_message(reader: &mut BufReader<ChildStdout>) -> Result<String, String> {
+/// Read a single LSP message from the server transport
+async fn read_lsp_message<R>(reader: &mut R) -> Result<String, String>
+where
+    R: AsyncBufRead + Unpin,
+{
     // Read headers until empty line
     let mut headers = String::new();
     loop {
@@ -790,18 +1255,21 @@ async fn read_lsp_message(reader: &mut BufReader<ChildStdout>) -> Result<String,
     String::from_utf8(content).map_err(|e| format!("Invalid UTF-8: {}", e))
 }
 
-/// Write an LSP message to stdin
-async fn write_lsp_message(stdin: &mut ChildStdin, message: &str) -> Result<(), String> {
+/// Write an LSP message to the server transport
+async fn write_lsp_message<W>(writer: &mut W, message: &str) -> Result<(), String>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
     let header = format!("Content-Length: {}\r\n\r\n", message.len());
-    stdin
+    writer
         .write_all(header.as_bytes())
         .await
         .map_err(|e| format!("Failed to write header: {}", e))?;
-    stdin
+    writer
         .write_all(message.as_bytes())
         .await
         .map_err(|e| format!("Failed to write message: {}", e))?;
-    stdin
+    writer
         .flush()
         .await
         .map_err(|e| format!("Failed to flush: {}", e))?;
@@ -834,138 +1302,480 @@ fn validate_root_path(root_path: &str) -> Result<PathBuf, String> {
     Ok(canonical)
 }
 
-/// Start an LSP server for a specific language
-#[tauri::command]
-pub async fn lsp_start_server(
-    app: AppHandle,
-    state: tauri::State<'_, LspState>,
-    language: String,
-    root_path: String,
-) -> Result<LspStartResponse, String> {
-    log::info!(
-        "Starting LSP server for language: {} in {}",
-        language,
-        root_path
-    );
+// ============================================================================
+// Watched Files Bridge
+// ============================================================================
 
-    // Validate root_path
-    let validated_root = validate_root_path(&root_path)?;
-    let root_path_str = validated_root.to_string_lossy().to_string();
+const REGISTER_CAPABILITY_METHOD: &str = "client/registerCapability";
+const UNREGISTER_CAPABILITY_METHOD: &str = "client/unregisterCapability";
+const DID_CHANGE_WATCHED_FILES_METHOD: &str = "workspace/didChangeWatchedFiles";
+
+/// Convert a `file://` URI into a local path string
+fn file_uri_to_path(uri: &str) -> Option<String> {
+    url::Url::parse(uri)
+        .ok()?
+        .to_file_path()
+        .ok()
+        .map(|p| p.to_string_lossy().to_string())
+}
 
-    // Atomically check for existing server and reserve creation slot
-    // This prevents TOCTOU race conditions
-    {
-        let mut registry = state.0.lock().await;
-        match registry.try_reserve_creation(&language, &root_path_str) {
-            CreationReservation::ExistingServer(existing_id) => {
-                log::info!("Reusing existing LSP server: {}", existing_id);
-                return Ok(LspStartResponse {
-                    server_id: existing_id,
-                    success: true,
-                    error: None,
-                });
+/// Parse the watchers of a `workspace/didChangeWatchedFiles` registration
+fn parse_file_watchers(register_options: &serde_json::Value) -> Vec<FileSystemWatcher> {
+    let Some(watchers) = register_options.get("watchers").and_then(|w| w.as_array()) else {
+        return Vec::new();
+    };
+
+    watchers
+        .iter()
+        .filter_map(|watcher| {
+            let kind = watcher
+                .get("kind")
+                .and_then(|k| k.as_u64())
+                .map(|k| k as u8)
+                .unwrap_or(7);
+            match watcher.get("globPattern")? {
+                serde_json::Value::String(pattern) => Some(FileSystemWatcher {
+                    glob_pattern: pattern.clone(),
+                    base_path: None,
+                    kind,
+                }),
+                // RelativePattern: { baseUri: WorkspaceFolder | URI, pattern }
+                relative => {
+                    let pattern = relative.get("pattern")?.as_str()?.to_string();
+                    let base_uri = relative.get("baseUri")?;
+                    let base_uri = base_uri
+                        .as_str()
+                        .or_else(|| base_uri.get("uri").and_then(|u| u.as_str()))?;
+                    Some(FileSystemWatcher {
+                        glob_pattern: pattern,
+                        base_path: Some(file_uri_to_path(base_uri)?),
+                        kind,
+                    })
+                }
             }
-            CreationReservation::AlreadyCreating => {
-                log::info!(
-                    "LSP server for {} in {} is already being created by another request",
-                    language,
-                    root_path_str
+        })
+        .collect()
+}
+
+/// Apply a server-to-client capability (un)registration request to the server's
+/// watcher table. Returns the request id when the message was handled here so the
+/// caller can acknowledge it.
+fn apply_capability_request(
+    server: &mut LspServer,
+    message: &serde_json::Value,
+) -> Option<serde_json::Value> {
+    let method = message.get("method")?.as_str()?;
+    let id = message.get("id")?.clone();
+    let params = message.get("params")?;
+
+    match method {
+        REGISTER_CAPABILITY_METHOD => {
+            let registrations = params.get("registrations")?.as_array()?;
+            let mut handled = false;
+            for registration in registrations {
+                if registration.get("method").and_then(|m| m.as_str())
+                    != Some(DID_CHANGE_WATCHED_FILES_METHOD)
+                {
+                    continue;
+                }
+                let Some(reg_id) = registration.get("id").and_then(|i| i.as_str()) else {
+                    continue;
+                };
+                let watchers = registration
+                    .get("registerOptions")
+                    .map(parse_file_watchers)
+                    .unwrap_or_default();
+                log::debug!(
+                    "LSP server {} registered {} file watchers ({})",
+                    server.server_id,
+                    watchers.len(),
+                    reg_id
                 );
-                return Err(format!(
-                    "LSP server for {} is already being created. Please wait and retry.",
-                    language
-                ));
+                server.file_watchers.insert(reg_id.to_string(), watchers);
+                handled = true;
             }
-            CreationReservation::Reserved => {
-                log::info!(
-                    "Reserved creation slot for {} in {}",
-                    language,
-                    root_path_str
-                );
-                // Continue with creation
+            handled.then_some(id)
+        }
+        UNREGISTER_CAPABILITY_METHOD => {
+            // The spec spells this field "unregisterations"; accept both spellings
+            let unregistrations = params
+                .get("unregisterations")
+                .or_else(|| params.get("unregistrations"))?
+                .as_array()?;
+            let mut handled = false;
+            for unregistration in unregistrations {
+                if unregistration.get("method").and_then(|m| m.as_str())
+                    != Some(DID_CHANGE_WATCHED_FILES_METHOD)
+                {
+                    continue;
+                }
+                if let Some(reg_id) = unregistration.get("id").and_then(|i| i.as_str()) {
+                    server.file_watchers.remove(reg_id);
+                    handled = true;
+                }
             }
+            handled.then_some(id)
         }
+        _ => None,
     }
+}
 
-    // Get the command for this language
-    let (command, args) = match get_lsp_command(&language) {
-        Some(cmd) => cmd,
-        None => {
-            // Cancel the reservation before returning error
-            let mut registry = state.0.lock().await;
-            registry.cancel_creation(&language, &root_path_str);
+/// Expand `{a,b}` alternatives in a glob pattern (the workspace glob matcher
+/// does not support braces)
+fn expand_glob_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let Some(close) = pattern[open..].find('}').map(|i| i + open) else {
+        return vec![pattern.to_string()];
+    };
 
-            // Check if we can auto-download
-            let status = get_server_status(&language);
-            if status.can_download {
-                return Err(format!(
-                    "LSP server for {} is not installed. Use lsp_download_server to install it.",
-                    language
-                ));
-            } else {
-                return Err(format!(
-                    "No LSP server available for language: {}. Please install it manually.",
-                    language
-                ));
+    let prefix = &pattern[..open];
+    let suffix = &pattern[close + 1..];
+    pattern[open + 1..close]
+        .split(',')
+        .flat_map(|alt| expand_glob_braces(&format!("{}{}{}", prefix, alt, suffix)))
+        .collect()
+}
+
+/// Check whether a watcher is interested in a change to `path`
+fn watcher_matches(
+    watcher: &FileSystemWatcher,
+    root_path: &str,
+    path: &Path,
+    change: FileChangeType,
+) -> bool {
+    if watcher.kind & change.watch_kind_bit() == 0 {
+        return false;
+    }
+
+    let path_str = path.to_string_lossy().replace('\\', "/");
+    let base = watcher
+        .base_path
+        .as_deref()
+        .unwrap_or(root_path)
+        .replace('\\', "/");
+    let pattern = watcher.glob_pattern.replace('\\', "/");
+
+    // Absolute patterns are matched against the full path
+    let (text, pattern) = if Path::new(&pattern).is_absolute() {
+        (
+            path_str.trim_start_matches('/').to_string(),
+            pattern.trim_start_matches('/').to_string(),
+        )
+    } else {
+        match path_str.strip_prefix(base.trim_end_matches('/')) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                (rest.trim_start_matches('/').to_string(), pattern)
             }
+            _ => return false,
         }
     };
 
-    log::info!("Using LSP command: {} {:?}", command, args);
+    let glob = crate::glob::HighPerformanceGlob::new();
+    expand_glob_braces(&pattern)
+        .iter()
+        .any(|p| glob.glob_match(&text, p))
+}
 
-    // Generate server ID
-    let server_id = generate_server_id(&language);
+/// Build a `workspace/didChangeWatchedFiles` notification for the given changes
+fn build_did_change_watched_files(changes: &[(PathBuf, FileChangeType)]) -> Option<String> {
+    let events: Vec<serde_json::Value> = changes
+        .iter()
+        .filter_map(|(path, change)| {
+            let uri = url::Url::from_file_path(path).ok()?;
+            Some(serde_json::json!({ "uri": uri.to_string(), "type": *change as u8 }))
+        })
+        .collect();
+
+    if events.is_empty() {
+        return None;
+    }
+
+    Some(
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": DID_CHANGE_WATCHED_FILES_METHOD,
+            "params": { "changes": events },
+        })
+        .to_string(),
+    )
+}
 
-    // Spawn the LSP server process
-    let mut child = match TokioCommand::new(&command)
-        .args(&args)
-        .current_dir(&validated_root)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
+/// Handle LSP server requests that the backend answers itself.
+/// Capability registrations for watched files are tracked here so file watcher
+/// events can be forwarded without a round trip through the frontend.
+/// Returns true if the request was answered, so it must not reach the frontend.
+async fn handle_server_request(server_arc: &Arc<Mutex<LspServer>>, message: &str) -> bool {
+    if !message.contains(REGISTER_CAPABILITY_METHOD)
+        && !message.contains(UNREGISTER_CAPABILITY_METHOD)
     {
-        Ok(child) => child,
-        Err(e) => {
-            // Cancel the reservation before returning error
-            let mut registry = state.0.lock().await;
-            registry.cancel_creation(&language, &root_path_str);
-            return Err(format!("Failed to spawn LSP server '{}': {}", command, e));
-        }
+        return false;
+    }
+    let Ok(parsed) = serde_json::from_str::<serde_json::Value>(message) else {
+        return false;
     };
 
-    log::info!("LSP server started with PID: {:?}", child.id());
+    let mut server = server_arc.lock().await;
+    let Some(id) = apply_capability_request(&mut server, &parsed) else {
+        return false;
+    };
+    if let Some(writer) = server.writer.as_mut() {
+        let response = serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": null });
+        if let Err(e) = write_lsp_message(writer, &response.to_string()).await {
+            log::warn!("Failed to acknowledge capability registration: {}", e);
+        }
+    }
+    true
+}
 
-    // Take stdin and stdout
-    let stdin = match child.stdin.take() {
-        Some(stdin) => stdin,
-        None => {
-            let mut registry = state.0.lock().await;
-            registry.cancel_creation(&language, &root_path_str);
-            return Err("Failed to get stdin".to_string());
+/// Forward file system changes to every running LSP server whose registered
+/// watchers match. Called from the file watcher thread after debouncing.
+pub fn forward_watched_file_changes(app: &AppHandle, changes: Vec<(PathBuf, FileChangeType)>) {
+    if changes.is_empty() || app.try_state::<LspState>().is_none() {
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<LspState>();
+        let servers: Vec<Arc<Mutex<LspServer>>> = {
+            let registry = state.0.lock().await;
+            registry
+                .list()
+                .iter()
+                .filter_map(|id| registry.get(id))
+                .collect()
+        };
+
+        for server_arc in servers {
+            let mut server = server_arc.lock().await;
+            if server.file_watchers.is_empty() {
+                continue;
+            }
+
+            let matching: Vec<(PathBuf, FileChangeType)> =
+                changes
+                    .iter()
+                    .filter(|(path, change)| {
+                        server.file_watchers.values().flatten().any(|watcher| {
+                            watcher_matches(watcher, &server.root_path, path, *change)
+                        })
+                    })
+                    .cloned()
+                    .collect();
+
+            let Some(notification) = build_did_change_watched_files(&matching) else {
+                continue;
+            };
+            let server_id = server.server_id.clone();
+            if let Some(writer) = server.writer.as_mut() {
+                match write_lsp_message(writer, &notification).await {
+                    Ok(()) => log::debug!(
+                        "Forwarded {} watched file changes to {}",
+                        matching.len(),
+                        server_id
+                    ),
+                    Err(e) => log::warn!(
+                        "Failed to forward watched file changes to {}: {}",
+                        server_id,
+                        e
+                    ),
+                }
+            }
         }
-    };
-    let stdout = match child.stdout.take() {
-        Some(stdout) => stdout,
+    });
+}
+
+/// Number of attempts when connecting to a TCP server that is still starting up
+const TCP_CONNECT_ATTEMPTS: u32 = 5;
+/// Delay between TCP connection attempts
+const TCP_CONNECT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Spawn the server process and use its stdio pipes as the transport
+fn spawn_stdio_connection(language: &str, root: &Path) -> Result<LspConnection, String> {
+    // Get the command for this language
+    let (command, args) = match get_lsp_command(language) {
+        Some(cmd) => cmd,
         None => {
-            let mut registry = state.0.lock().await;
-            registry.cancel_creation(&language, &root_path_str);
-            return Err("Failed to get stdout".to_string());
+            // Check if we can auto-download
+            let status = get_server_status(language);
+            if status.can_download {
+                return Err(format!(
+                    "LSP server for {} is not installed. Use lsp_download_server to install it.",
+                    language
+                ));
+            } else {
+                return Err(format!(
+                    "No LSP server available for language: {}. Please install it manually.",
+                    language
+                ));
+            }
         }
     };
-    let stderr = match child.stderr.take() {
-        Some(stderr) => stderr,
-        None => {
+
+    log::info!("Using LSP command: {} {:?}", command, args);
+
+    let env = find_custom_server(language)
+        .map(|custom| custom.env)
+        .unwrap_or_default();
+
+    // Spawn the LSP server process
+    let mut child = TokioCommand::new(&command)
+        .args(&args)
+        .envs(env)
+        .current_dir(root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to spawn LSP server '{}': {}", command, e))?;
+
+    log::info!("LSP server started with PID: {:?}", child.id());
+
+    let stdin = child.stdin.take().ok_or("Failed to get stdin")?;
+    let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
+    let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
+
+    Ok(LspConnection {
+        child: Some(child),
+        reader: Box::new(stdout),
+        writer: Box::new(stdin),
+        stderr: Some(stderr),
+    })
+}
+
+/// Connect to a server listening on a TCP socket, retrying while it starts up
+async fn connect_tcp(host: &str, port: u16) -> Result<LspConnection, String> {
+    let mut last_error = String::new();
+    for attempt in 1..=TCP_CONNECT_ATTEMPTS {
+        match tokio::net::TcpStream::connect((host, port)).await {
+            Ok(stream) => {
+                log::info!("Connected to LSP server at {}:{}", host, port);
+                let _ = stream.set_nodelay(true);
+                let (read_half, write_half) = stream.into_split();
+                return Ok(LspConnection {
+                    child: None,
+                    reader: Box::new(read_half),
+                    writer: Box::new(write_half),
+                    stderr: None,
+                });
+            }
+            Err(e) => {
+                log::debug!(
+                    "LSP TCP connect attempt {}/{} to {}:{} failed: {}",
+                    attempt,
+                    TCP_CONNECT_ATTEMPTS,
+                    host,
+                    port,
+                    e
+                );
+                last_error = e.to_string();
+                tokio::time::sleep(TCP_CONNECT_RETRY_DELAY).await;
+            }
+        }
+    }
+
+    Err(format!(
+        "Failed to connect to LSP server at {}:{}: {}",
+        host, port, last_error
+    ))
+}
+
+/// Open the transport for a server
+async fn open_connection(
+    language: &str,
+    root: &Path,
+    transport: &LspTransport,
+) -> Result<LspConnection, String> {
+    match transport {
+        LspTransport::Stdio => spawn_stdio_connection(language, root),
+        LspTransport::Tcp { host, port } => connect_tcp(host, *port).await,
+    }
+}
+
+/// Start an LSP server for a specific language
+#[tauri::command]
+pub async fn lsp_start_server(
+    app: AppHandle,
+    state: tauri::State<'_, LspState>,
+    language: String,
+    root_path: String,
+    transport: Option<LspTransport>,
+) -> Result<LspStartResponse, String> {
+    log::info!(
+        "Starting LSP server for language: {} in {}",
+        language,
+        root_path
+    );
+
+    // Validate root_path
+    let validated_root = validate_root_path(&root_path)?;
+    let root_path_str = validated_root.to_string_lossy().to_string();
+
+    // Atomically check for existing server and reserve creation slot
+    // This prevents TOCTOU race conditions
+    {
+        let mut registry = state.0.lock().await;
+        match registry.try_reserve_creation(&language, &root_path_str) {
+            CreationReservation::ExistingServer(existing_id) => {
+                log::info!("Reusing existing LSP server: {}", existing_id);
+                return Ok(LspStartResponse {
+                    server_id: existing_id,
+                    success: true,
+                    error: None,
+                });
+            }
+            CreationReservation::AlreadyCreating => {
+                log::info!(
+                    "LSP server for {} in {} is already being created by another request",
+                    language,
+                    root_path_str
+                );
+                return Err(format!(
+                    "LSP server for {} is already being created. Please wait and retry.",
+                    language
+                ));
+            }
+            CreationReservation::Reserved => {
+                log::info!(
+                    "Reserved creation slot for {} in {}",
+                    language,
+                    root_path_str
+                );
+                // Continue with creation
+            }
+        }
+    }
+
+    let transport = transport
+        .or_else(|| find_custom_server(&language).map(|custom| custom.transport))
+        .unwrap_or_default();
+
+    // Generate server ID
+    let server_id = generate_server_id(&language);
+
+    // Open the transport (spawns the process for stdio)
+    let connection = match open_connection(&language, &validated_root, &transport).await {
+        Ok(connection) => connection,
+        Err(e) => {
+            // Cancel the reservation before returning error
             let mut registry = state.0.lock().await;
             registry.cancel_creation(&language, &root_path_str);
-            return Err("Failed to get stderr".to_string());
+            return Err(e);
         }
     };
+    let LspConnection {
+        child,
+        reader,
+        writer,
+        stderr,
+    } = connection;
 
     // Create server instance
     let mut server = LspServer::new(server_id.clone(), language.clone(), root_path_str.clone());
-    server.child = Some(child);
-    server.stdin = Some(stdin);
+    server.child = child;
+    server.writer = Some(writer);
 
     let server_arc = Arc::new(Mutex::new(server));
 
@@ -983,12 +1793,20 @@ pub async fn lsp_start_server(
     // Spawn stdout reader task
     let app_handle = app.clone();
     let server_id_clone = server_id.clone();
+    let reader_server = server_arc.clone();
     let stdout_task = tokio::spawn(async move {
-        let mut reader = BufReader::new(stdout);
+        let mut reader = BufReader::new(reader);
         loop {
             match read_lsp_message(&mut reader).await {
                 Ok(message) => {
                     log::debug!("LSP message received: {} bytes", message.len());
+                    // Responses to backend requests, and requests the backend
+                    // answered, are not meant for the frontend
+                    if handle_backend_response(&reader_server, &message).await
+                        || handle_server_request(&reader_server, &message).await
+                    {
+                        continue;
+                    }
                     let event = LspMessageEvent {
                         server_id: server_id_clone.clone(),
                         message,
@@ -1005,36 +1823,38 @@ pub async fn lsp_start_server(
         }
     });
 
-    // Spawn stderr reader task to avoid pipe backpressure
+    // Spawn stderr reader task to avoid pipe backpressure (stdio transport only)
     let server_id_stderr = server_id.clone();
-    let stderr_task = tokio::spawn(async move {
-        let mut reader = BufReader::new(stderr);
-        loop {
-            let mut line = String::new();
-            match reader.read_line(&mut line).await {
-                Ok(0) => {
-                    log::info!("LSP stderr reader ended for {}", server_id_stderr);
-                    break;
-                }
-                Ok(_) => {
-                    let trimmed = line.trim_end();
-                    if !trimmed.is_empty() {
-                        log::debug!("LSP stderr [{}]: {}", server_id_stderr, trimmed);
+    let stderr_task = stderr.map(|stderr| {
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(stderr);
+            loop {
+                let mut line = String::new();
+                match reader.read_line(&mut line).await {
+                    Ok(0) => {
+                        log::info!("LSP stderr reader ended for {}", server_id_stderr);
+                        break;
+                    }
+                    Ok(_) => {
+                        let trimmed = line.trim_end();
+                        if !trimmed.is_empty() {
+                            log::debug!("LSP stderr [{}]: {}", server_id_stderr, trimmed);
+                        }
+                    }
+                    Err(e) => {
+                        log::info!("LSP stderr reader error for {}: {}", server_id_stderr, e);
+                        break;
                     }
-                }
-                Err(e) => {
-                    log::info!("LSP stderr reader error for {}: {}", server_id_stderr, e);
-                    break;
                 }
             }
-        }
+        })
     });
 
     // Store the task handles
     {
         let mut server = server_arc.lock().await;
         server.stdout_task = Some(stdout_task);
-        server.stderr_task = Some(stderr_task);
+        server.stderr_task = stderr_task;
     }
 
     Ok(LspStartResponse {
@@ -1065,12 +1885,13 @@ pub async fn lsp_send_message(
     };
 
     let mut server = server_arc.lock().await;
-    let stdin = server
-        .stdin
+    server.last_activity = Instant::now();
+    let writer = server
+        .writer
         .as_mut()
-        .ok_or("LSP server stdin not available")?;
+        .ok_or("LSP server connection not available")?;
 
-    write_lsp_message(stdin, &message).await
+    write_lsp_message(writer, &message).await
 }
 
 /// Stop an LSP server
@@ -1088,6 +1909,114 @@ pub async fn lsp_stop_server(
             .ok_or_else(|| format!("LSP server not found: {}", server_id))?
     };
 
+    shutdown_server(&server_arc).await;
+
+    log::info!("LSP server stopped: {}", server_id);
+    Ok(())
+}
+
+/// Stop every LSP server running for a project root (called when a project is closed)
+#[tauri::command]
+pub async fn lsp_stop_all_for_root(
+    app: AppHandle,
+    state: tauri::State<'_, LspState>,
+    root_path: String,
+) -> Result<Vec<String>, String> {
+    // The project directory may already be gone, so fall back to the raw path
+    let root = validate_root_path(&root_path)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or(root_path);
+    log::info!("Stopping all LSP servers for root: {}", root);
+
+    let servers: Vec<(String, Arc<Mutex<LspServer>>)> = {
+        let mut registry = state.0.lock().await;
+        registry
+            .server_ids_for_root(&root)
+            .into_iter()
+            .filter_map(|id| registry.remove(&id).map(|server| (id, server)))
+            .collect()
+    };
+
+    let mut stopped = Vec::with_capacity(servers.len());
+    for (server_id, server_arc) in servers {
+        shutdown_server(&server_arc).await;
+        emit_server_stopped(&app, &server_id, "project-closed");
+        stopped.push(server_id);
+    }
+
+    log::info!("Stopped {} LSP servers for root: {}", stopped.len(), root);
+    Ok(stopped)
+}
+
+/// Default idle timeout after which servers without traffic are shut down
+const LSP_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// How often the idle reaper checks for idle servers
+const LSP_IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Spawn a background task that stops servers with no requests for `LSP_IDLE_TIMEOUT`
+pub fn spawn_idle_reaper(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(LSP_IDLE_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let Some(state) = app.try_state::<LspState>() else {
+                continue;
+            };
+            let servers: Vec<(String, Arc<Mutex<LspServer>>)> = {
+                let registry = state.0.lock().await;
+                registry
+                    .list()
+                    .into_iter()
+                    .filter_map(|id| registry.get(&id).map(|server| (id, server)))
+                    .collect()
+            };
+
+            for (server_id, server_arc) in servers {
+                if !server_arc.lock().await.is_idle(LSP_IDLE_TIMEOUT) {
+                    continue;
+                }
+
+                // Re-check holding both the registry and the server, so a
+                // request that reached the server since keeps it running and
+                // a server stopped or replaced meanwhile is left alone
+                let removed = {
+                    let mut registry = state.0.lock().await;
+                    let still_idle = match registry.get(&server_id) {
+                        Some(current) if Arc::ptr_eq(&current, &server_arc) => {
+                            current.lock().await.is_idle(LSP_IDLE_TIMEOUT)
+                        }
+                        _ => false,
+                    };
+                    if still_idle {
+                        registry.remove(&server_id)
+                    } else {
+                        None
+                    }
+                };
+                if let Some(server_arc) = removed {
+                    log::info!("Stopping idle LSP server: {}", server_id);
+                    shutdown_server(&server_arc).await;
+                    emit_server_stopped(&app, &server_id, "idle");
+                }
+            }
+        }
+    });
+}
+
+/// Notify the frontend that the backend stopped a server
+fn emit_server_stopped(app: &AppHandle, server_id: &str, reason: &str) {
+    let event = LspServerStoppedEvent {
+        server_id: server_id.to_string(),
+        reason: reason.to_string(),
+    };
+    if let Err(e) = app.emit("lsp-server-stopped", &event) {
+        log::error!("Failed to emit LSP server stopped event: {}", e);
+    }
+}
+
+/// Gracefully shut down a server process that has already been removed from the registry
+async fn shutdown_server(server_arc: &Arc<Mutex<LspServer>>) {
     let mut server = server_arc.lock().await;
 
     // Cancel stdout/stderr tasks
@@ -1098,28 +2027,302 @@ pub async fn lsp_stop_server(
         task.abort();
     }
 
-    // Kill the child process
+    // Send shutdown request first (graceful shutdown)
+    if let Some(writer) = server.writer.as_mut() {
+        let shutdown_request = r#"{"jsonrpc":"2.0","id":999999,"method":"shutdown","params":null}"#;
+        let _ = write_lsp_message(writer, shutdown_request).await;
+
+        // Wait a bit for graceful shutdown
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+        // Send exit notification
+        let exit_notification = r#"{"jsonrpc":"2.0","method":"exit","params":null}"#;
+        let _ = write_lsp_message(writer, exit_notification).await;
+        let _ = writer.shutdown().await;
+    }
+    server.writer = None;
+
+    // Force kill the child process if still running
     if let Some(mut child) = server.child.take() {
-        // Send shutdown request first (graceful shutdown)
-        if let Some(stdin) = server.stdin.as_mut() {
-            let shutdown_request =
-                r#"{"jsonrpc":"2.0","id":999999,"method":"shutdown","params":null}"#;
-            let _ = write_lsp_message(stdin, shutdown_request).await;
-
-            // Wait a bit for graceful shutdown
-            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-
-            // Send exit notification
-            let exit_notification = r#"{"jsonrpc":"2.0","method":"exit","params":null}"#;
-            let _ = write_lsp_message(stdin, exit_notification).await;
+        let _ = child.kill().await;
+    }
+}
+
+// ============================================================================
+// Call and Type Hierarchy
+// ============================================================================
+
+/// Prefix of request ids issued by the backend, distinct from the frontend's numeric ids
+const BACKEND_REQUEST_ID_PREFIX: &str = "talkcody-backend-";
+/// Timeout for requests issued by the backend
+const BACKEND_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+static BACKEND_REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Item returned by `textDocument/prepareCallHierarchy`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyItem {
+    pub name: String,
+    pub kind: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<u32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub uri: String,
+    pub range: LspRange,
+    pub selection_range: LspRange,
+    /// Opaque server data that must be sent back unchanged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+/// A caller of a call hierarchy item
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyIncomingCall {
+    pub from: CallHierarchyItem,
+    pub from_ranges: Vec<LspRange>,
+}
+
+/// A callee of a call hierarchy item
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyOutgoingCall {
+    pub to: CallHierarchyItem,
+    pub from_ranges: Vec<LspRange>,
+}
+
+/// Item returned by `textDocument/prepareTypeHierarchy`
+pub type TypeHierarchyItem = CallHierarchyItem;
+
+/// Which side of the type hierarchy to resolve
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TypeHierarchyDirection {
+    Supertypes,
+    Subtypes,
+}
+
+impl TypeHierarchyDirection {
+    fn method(self) -> &'static str {
+        match self {
+            TypeHierarchyDirection::Supertypes => "typeHierarchy/supertypes",
+            TypeHierarchyDirection::Subtypes => "typeHierarchy/subtypes",
         }
+    }
+}
 
-        // Force kill if still running
-        let _ = child.kill().await;
+/// Extract the result of a JSON-RPC response, mapping protocol errors to `Err`
+fn parse_response_result(response: &serde_json::Value) -> Result<serde_json::Value, String> {
+    if let Some(error) = response.get("error") {
+        let message = error
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("unknown error");
+        return Err(format!("LSP error: {}", message));
+    }
+    Ok(response
+        .get("result")
+        .cloned()
+        .unwrap_or(serde_json::Value::Null))
+}
+
+/// Route a response to a backend-issued request. Returns true if the message was consumed.
+async fn handle_backend_response(server_arc: &Arc<Mutex<LspServer>>, message: &str) -> bool {
+    if !message.contains(BACKEND_REQUEST_ID_PREFIX) {
+        return false;
     }
+    let Ok(parsed) = serde_json::from_str::<serde_json::Value>(message) else {
+        return false;
+    };
+    // Server-to-client requests carry a method; only responses are routed here
+    if parsed.get("method").is_some() {
+        return false;
+    }
+    let Some(id) = parsed.get("id").and_then(|id| id.as_str()) else {
+        return false;
+    };
 
-    log::info!("LSP server stopped: {}", server_id);
-    Ok(())
+    let sender = server_arc.lock().await.pending_requests.remove(id);
+    match sender {
+        Some(sender) => {
+            let _ = sender.send(parse_response_result(&parsed));
+            true
+        }
+        None => id.starts_with(BACKEND_REQUEST_ID_PREFIX),
+    }
+}
+
+/// Send a request from the backend and wait for its result
+async fn send_backend_request(
+    state: &LspState,
+    server_id: &str,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let server_arc = {
+        let registry = state.0.lock().await;
+        registry
+            .get(server_id)
+            .ok_or_else(|| format!("LSP server not found: {}", server_id))?
+    };
+
+    let id = format!(
+        "{}{}",
+        BACKEND_REQUEST_ID_PREFIX,
+        BACKEND_REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": params,
+    })
+    .to_string();
+
+    let (sender, receiver) = oneshot::channel();
+    {
+        // The reader task needs this lock to route the response, so registering
+        // the pending request after writing cannot miss it
+        let mut server = server_arc.lock().await;
+        server.last_activity = Instant::now();
+        let writer = server
+            .writer
+            .as_mut()
+            .ok_or("LSP server connection not available")?;
+        write_lsp_message(writer, &request).await?;
+        server.pending_requests.insert(id.clone(), sender);
+    }
+
+    match tokio::time::timeout(BACKEND_REQUEST_TIMEOUT, receiver).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => Err(format!("LSP server stopped before answering {}", method)),
+        Err(_) => {
+            server_arc.lock().await.pending_requests.remove(&id);
+            Err(format!("LSP request timed out: {}", method))
+        }
+    }
+}
+
+/// Build `TextDocumentPositionParams` for a file position
+fn text_document_position(
+    file_path: &str,
+    line: u32,
+    character: u32,
+) -> Result<serde_json::Value, String> {
+    let uri = url::Url::from_file_path(file_path)
+        .map_err(|_| format!("Invalid file path: {}", file_path))?;
+    Ok(serde_json::json!({
+        "textDocument": { "uri": uri.to_string() },
+        "position": { "line": line, "character": character },
+    }))
+}
+
+/// Deserialize a possibly-null list result
+fn parse_list_result<T: serde::de::DeserializeOwned>(
+    value: serde_json::Value,
+    method: &str,
+) -> Result<Vec<T>, String> {
+    serde_json::from_value::<Option<Vec<T>>>(value)
+        .map(|items| items.unwrap_or_default())
+        .map_err(|e| format!("Invalid {} response: {}", method, e))
+}
+
+/// Resolve the call hierarchy items at a position
+async fn prepare_call_hierarchy(
+    state: &LspState,
+    server_id: &str,
+    file_path: &str,
+    line: u32,
+    character: u32,
+) -> Result<Vec<CallHierarchyItem>, String> {
+    let method = "textDocument/prepareCallHierarchy";
+    let params = text_document_position(file_path, line, character)?;
+    let result = send_backend_request(state, server_id, method, params).await?;
+    parse_list_result(result, method)
+}
+
+/// Find all callers of the symbol at a position (0-based line/character)
+#[tauri::command]
+pub async fn lsp_incoming_calls(
+    state: tauri::State<'_, LspState>,
+    server_id: String,
+    file_path: String,
+    line: u32,
+    character: u32,
+) -> Result<Vec<CallHierarchyIncomingCall>, String> {
+    let method = "callHierarchy/incomingCalls";
+    let mut calls = Vec::new();
+    for item in prepare_call_hierarchy(&state, &server_id, &file_path, line, character).await? {
+        let result = send_backend_request(
+            &state,
+            &server_id,
+            method,
+            serde_json::json!({ "item": item }),
+        )
+        .await?;
+        calls.extend(parse_list_result::<CallHierarchyIncomingCall>(
+            result, method,
+        )?);
+    }
+    Ok(calls)
+}
+
+/// Find all functions called by the symbol at a position (0-based line/character)
+#[tauri::command]
+pub async fn lsp_outgoing_calls(
+    state: tauri::State<'_, LspState>,
+    server_id: String,
+    file_path: String,
+    line: u32,
+    character: u32,
+) -> Result<Vec<CallHierarchyOutgoingCall>, String> {
+    let method = "callHierarchy/outgoingCalls";
+    let mut calls = Vec::new();
+    for item in prepare_call_hierarchy(&state, &server_id, &file_path, line, character).await? {
+        let result = send_backend_request(
+            &state,
+            &server_id,
+            method,
+            serde_json::json!({ "item": item }),
+        )
+        .await?;
+        calls.extend(parse_list_result::<CallHierarchyOutgoingCall>(
+            result, method,
+        )?);
+    }
+    Ok(calls)
+}
+
+/// Find the supertypes or subtypes of the type at a position (0-based line/character)
+#[tauri::command]
+pub async fn lsp_type_hierarchy(
+    state: tauri::State<'_, LspState>,
+    server_id: String,
+    file_path: String,
+    line: u32,
+    character: u32,
+    direction: TypeHierarchyDirection,
+) -> Result<Vec<TypeHierarchyItem>, String> {
+    let prepare_method = "textDocument/prepareTypeHierarchy";
+    let params = text_document_position(&file_path, line, character)?;
+    let result = send_backend_request(&state, &server_id, prepare_method, params).await?;
+    let items: Vec<TypeHierarchyItem> = parse_list_result(result, prepare_method)?;
+
+    let method = direction.method();
+    let mut related = Vec::new();
+    for item in items {
+        let result = send_backend_request(
+            &state,
+            &server_id,
+            method,
+            serde_json::json!({ "item": item }),
+        )
+        .await?;
+        related.extend(parse_list_result::<TypeHierarchyItem>(result, method)?);
+    }
+    Ok(related)
 }
 
 /// List all active LSP servers
@@ -1250,6 +2453,15 @@ pub async fn lsp_download_server(app: AppHandle, language: String) -> Result<Str
 /// Get LSP server configuration for a language
 #[tauri::command]
 pub fn lsp_get_server_config(language: String) -> Result<Option<LspServerConfig>, String> {
+    if let Some(custom) = find_custom_server(&language) {
+        return Ok(Some(LspServerConfig {
+            command: custom.command,
+            args: custom.args,
+            extensions: custom.extensions,
+            transport: custom.transport,
+        }));
+    }
+
     let config = get_lsp_command(&language).map(|(command, args)| {
         let extensions = match language.as_str() {
             "typescript" | "javascript" | "typescriptreact" | "javascriptreact" => {
@@ -1278,12 +2490,264 @@ pub fn lsp_get_server_config(language: String) -> Result<Option<LspServerConfig>
             command,
             args,
             extensions,
+            transport: LspTransport::Stdio,
         }
     });
 
     Ok(config)
 }
 
+/// List user-defined LSP servers from ~/.talkcody/lsp-servers.json
+#[tauri::command]
+pub fn lsp_list_custom_servers() -> Result<BTreeMap<String, CustomLspServerConfig>, String> {
+    read_custom_servers()
+}
+
+// ============================================================================
+// Document Formatting
+// ============================================================================
+
+/// Timeout for a one-shot formatting session, including server startup
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Edit returned by `textDocument/formatting`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LspTextEdit {
+    pub range: LspRange,
+    pub new_text: String,
+}
+
+/// LSP language ID of a file, from user-defined servers first and then the
+/// built-in ones
+pub fn language_for_path(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let dotted = format!(".{}", extension);
+    let custom = load_custom_servers().into_values().find(|server| {
+        server
+            .extensions
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(&dotted))
+    });
+    if let Some(language) = custom.and_then(|server| server.languages.into_iter().next()) {
+        return Some(language);
+    }
+
+    let language = match extension.as_str() {
+        "rs" => "rust",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "py" => "python",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cpp" | "hpp" | "cc" | "hh" | "cxx" => "cpp",
+        "vue" => "vue",
+        _ => return None,
+    };
+    Some(language.to_string())
+}
+
+/// Format a document with its language server, started for this request
+/// only. Returns `None` when the server has nothing to change.
+pub async fn format_document(
+    language: &str,
+    root: &Path,
+    path: &Path,
+    content: &str,
+) -> Result<Option<String>, String> {
+    let mut connection = spawn_stdio_connection(language, root)?;
+    // An unread stderr pipe could fill up and stall the server
+    if let Some(mut stderr) = connection.stderr.take() {
+        tokio::spawn(async move {
+            let _ = tokio::io::copy(&mut stderr, &mut tokio::io::sink()).await;
+        });
+    }
+
+    let result = tokio::time::timeout(
+        FORMAT_TIMEOUT,
+        request_formatting(&mut connection, language, root, path, content),
+    )
+    .await
+    .unwrap_or_else(|_| Err(format!("LSP formatting of {} timed out", path.display())));
+
+    let exit = r#"{"jsonrpc":"2.0","method":"exit","params":null}"#;
+    let _ = write_lsp_message(&mut connection.writer, exit).await;
+    if let Some(mut child) = connection.child.take() {
+        let _ = child.kill().await;
+    }
+
+    let edits = result?;
+    if edits.is_empty() {
+        return Ok(None);
+    }
+    let formatted = apply_text_edits(content, &edits)?;
+    Ok((formatted != content).then_some(formatted))
+}
+
+async fn request_formatting(
+    connection: &mut LspConnection,
+    language: &str,
+    root: &Path,
+    path: &Path,
+    content: &str,
+) -> Result<Vec<LspTextEdit>, String> {
+    let root_uri = url::Url::from_directory_path(root)
+        .map_err(|_| format!("Invalid root path: {}", root.display()))?;
+    let uri = url::Url::from_file_path(path)
+        .map_err(|_| format!("Invalid file path: {}", path.display()))?;
+    let LspConnection { reader, writer, .. } = connection;
+    let mut reader = BufReader::new(reader);
+
+    let initialize = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "processId": std::process::id(),
+            "rootUri": root_uri.to_string(),
+            "workspaceFolders": [{ "uri": root_uri.to_string(), "name": "workspace" }],
+            "capabilities": {
+                "textDocument": { "formatting": { "dynamicRegistration": false } }
+            },
+        },
+    });
+    write_lsp_message(writer, &initialize.to_string()).await?;
+    let initialized = read_response(&mut reader, writer, 1).await?;
+    let provider = &initialized["capabilities"]["documentFormattingProvider"];
+    if provider.is_null() || provider == &serde_json::Value::Bool(false) {
+        return Err(format!(
+            "The {} language server does not format documents",
+            language
+        ));
+    }
+
+    let notifications = [
+        serde_json::json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": {
+                    "uri": uri.to_string(),
+                    "languageId": language,
+                    "version": 1,
+                    "text": content,
+                },
+            },
+        }),
+    ];
+    for notification in notifications {
+        write_lsp_message(writer, &notification.to_string()).await?;
+    }
+
+    let method = "textDocument/formatting";
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": method,
+        "params": {
+            "textDocument": { "uri": uri.to_string() },
+            "options": { "tabSize": 4, "insertSpaces": true },
+        },
+    });
+    write_lsp_message(writer, &request.to_string()).await?;
+    let result = read_response(&mut reader, writer, 2).await?;
+    parse_list_result(result, method)
+}
+
+/// Read messages until the response to request `id`, answering requests the
+/// server makes in the meantime
+async fn read_response<R>(
+    reader: &mut R,
+    writer: &mut LspWriter,
+    id: u64,
+) -> Result<serde_json::Value, String>
+where
+    R: AsyncBufRead + Unpin,
+{
+    loop {
+        let message = read_lsp_message(reader).await?;
+        let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&message) else {
+            continue;
+        };
+        let Some(method) = parsed.get("method").and_then(|m| m.as_str()) else {
+            if parsed.get("id").and_then(|v| v.as_u64()) == Some(id) {
+                return parse_response_result(&parsed);
+            }
+            continue;
+        };
+        let Some(request_id) = parsed.get("id") else {
+            continue;
+        };
+
+        // No settings are configured; every other request just succeeds
+        let result = match method {
+            "workspace/configuration" => {
+                let items = parsed["params"]["items"].as_array().map_or(0, Vec::len);
+                serde_json::Value::Array(vec![serde_json::Value::Null; items])
+            }
+            _ => serde_json::Value::Null,
+        };
+        let response = serde_json::json!({ "jsonrpc": "2.0", "id": request_id, "result": result });
+        write_lsp_message(writer, &response.to_string()).await?;
+    }
+}
+
+/// Byte offset of an LSP position, whose character counts UTF-16 code units
+fn position_offset(content: &str, line_starts: &[usize], line: u32, character: u32) -> usize {
+    let Some(&start) = line_starts.get(line as usize) else {
+        return content.len();
+    };
+    let end = line_starts
+        .get(line as usize + 1)
+        .copied()
+        .unwrap_or(content.len());
+    let mut units = 0;
+    for (index, c) in content[start..end].char_indices() {
+        if units >= character as usize || c == '\n' || c == '\r' {
+            return start + index;
+        }
+        units += c.len_utf16();
+    }
+    end
+}
+
+/// Apply text edits computed against `content`
+pub fn apply_text_edits(content: &str, edits: &[LspTextEdit]) -> Result<String, String> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let mut ranges: Vec<(usize, usize, &str)> = edits
+        .iter()
+        .map(|edit| {
+            let offset =
+                |p: LspPosition| position_offset(content, &line_starts, p.line, p.character);
+            (
+                offset(edit.range.start),
+                offset(edit.range.end),
+                edit.new_text.as_str(),
+            )
+        })
+        .collect();
+    // Edits at the same position keep their order
+    ranges.sort_by_key(|&(start, end, _)| (start, end));
+
+    let mut result = String::with_capacity(content.len());
+    let mut position = 0;
+    for (start, end, text) in ranges {
+        if start < position || end < start {
+            return Err("LSP server returned overlapping edits".to_string());
+        }
+        result.push_str(&content[position..start]);
+        result.push_str(text);
+        position = end;
+    }
+    result.push_str(&content[position..]);
+    Ok(result)
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -1454,6 +2918,320 @@ mod tests {
         assert!(registry.list().is_empty());
     }
 
+    #[test]
+    fn test_parse_response_result() {
+        let ok = serde_json::json!({ "jsonrpc": "2.0", "id": "x", "result": [1, 2] });
+        assert_eq!(
+            parse_response_result(&ok).unwrap(),
+            serde_json::json!([1, 2])
+        );
+
+        let null = serde_json::json!({ "jsonrpc": "2.0", "id": "x", "result": null });
+        assert!(parse_response_result(&null).unwrap().is_null());
+
+        let err = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": "x",
+            "error": { "code": -32601, "message": "Method not found" }
+        });
+        assert_eq!(
+            parse_response_result(&err).unwrap_err(),
+            "LSP error: Method not found"
+        );
+    }
+
+    #[test]
+    fn test_parse_incoming_calls() {
+        let result = serde_json::json!([{
+            "from": {
+                "name": "main",
+                "kind": 12,
+                "uri": "file:///project/src/main.rs",
+                "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 5, "character": 1 } },
+                "selectionRange": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 7 } },
+                "data": { "opaque": true }
+            },
+            "fromRanges": [{ "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 9 } }]
+        }]);
+
+        let calls: Vec<CallHierarchyIncomingCall> =
+            parse_list_result(result, "callHierarchy/incomingCalls").unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].from.name, "main");
+        assert_eq!(calls[0].from.selection_range.start.character, 3);
+        assert_eq!(calls[0].from_ranges[0].start.line, 2);
+
+        // Items are sent back to the server with their opaque data intact
+        let item = serde_json::to_value(&calls[0].from).unwrap();
+        assert_eq!(item["data"], serde_json::json!({ "opaque": true }));
+        assert!(item.get("tags").is_none());
+
+        let empty: Vec<CallHierarchyItem> =
+            parse_list_result(serde_json::Value::Null, "textDocument/prepareCallHierarchy")
+                .unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_backend_response_routes_pending_request() {
+        let server = Arc::new(Mutex::new(LspServer::new(
+            "server_1".to_string(),
+            "rust".to_string(),
+            "/project".to_string(),
+        )));
+        let (sender, receiver) = oneshot::channel();
+        server
+            .lock()
+            .await
+            .pending_requests
+            .insert("talkcody-backend-7".to_string(), sender);
+
+        // Frontend responses are left alone
+        assert!(
+            !handle_backend_response(&server, r#"{"jsonrpc":"2.0","id":7,"result":null}"#).await
+        );
+
+        let consumed = handle_backend_response(
+            &server,
+            r#"{"jsonrpc":"2.0","id":"talkcody-backend-7","result":{"ok":true}}"#,
+        )
+        .await;
+        assert!(consumed);
+        assert_eq!(
+            receiver.await.unwrap().unwrap(),
+            serde_json::json!({ "ok": true })
+        );
+        assert!(server.lock().await.pending_requests.is_empty());
+    }
+
+    #[test]
+    fn test_parse_release_asset() {
+        let digest = "a".repeat(64);
+        let release = serde_json::json!({
+            "assets": [
+                {
+                    "name": "rust-analyzer-x86_64-unknown-linux-gnu.gz",
+                    "browser_download_url": "https://example.com/ra.gz",
+                    "digest": format!("sha256:{}", digest)
+                },
+                {
+                    "name": "rust-analyzer-aarch64-apple-darwin.gz",
+                    "browser_download_url": "https://example.com/ra-mac.gz"
+                }
+            ]
+        });
+
+        let asset =
+            parse_release_asset(&release, "rust-analyzer-x86_64-unknown-linux-gnu.gz").unwrap();
+        assert_eq!(asset.download_url, "https://example.com/ra.gz");
+        assert_eq!(asset.sha256, digest);
+
+        // Assets without a published digest are rejected
+        let err =
+            parse_release_asset(&release, "rust-analyzer-aarch64-apple-darwin.gz").unwrap_err();
+        assert!(err.contains("checksum"));
+
+        assert!(parse_release_asset(&release, "missing").is_err());
+    }
+
+    #[test]
+    fn test_sha256_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("data");
+        std::fs::write(&path, b"hello").unwrap();
+
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn test_quarantine_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("rust-analyzer.part");
+        std::fs::write(&path, b"corrupt").unwrap();
+
+        let target = quarantine_file(temp_dir.path(), &path).unwrap();
+        assert!(!path.exists());
+        assert!(target.starts_with(temp_dir.path().join(".quarantine")));
+        assert_eq!(std::fs::read(&target).unwrap(), b"corrupt");
+    }
+
+    #[test]
+    fn test_parse_custom_servers() {
+        let content = r#"{
+            "servers": {
+                "zls": {
+                    "command": "zls",
+                    "languages": ["zig"],
+                    "extensions": [".zig"],
+                    "env": { "ZLS_LOG": "info" }
+                },
+                "jdtls": {
+                    "command": "jdtls",
+                    "args": ["--socket"],
+                    "languages": ["java"],
+                    "transport": { "type": "tcp", "host": "localhost", "port": 5036 }
+                }
+            }
+        }"#;
+
+        let servers = parse_custom_servers(content).unwrap();
+        assert_eq!(servers.len(), 2);
+
+        let zls = find_custom_server_in(&servers, "zig").unwrap();
+        assert_eq!(zls.command, "zls");
+        assert!(zls.args.is_empty());
+        assert_eq!(zls.extensions, vec![".zig".to_string()]);
+        assert_eq!(zls.env.get("ZLS_LOG").map(String::as_str), Some("info"));
+        assert_eq!(zls.transport, LspTransport::Stdio);
+
+        let jdtls = find_custom_server_in(&servers, "java").unwrap();
+        assert_eq!(
+            jdtls.transport,
+            LspTransport::Tcp {
+                host: "localhost".to_string(),
+                port: 5036
+            }
+        );
+
+        assert!(find_custom_server_in(&servers, "haskell").is_none());
+    }
+
+    #[test]
+    fn test_parse_custom_servers_invalid() {
+        assert!(parse_custom_servers("not json").is_err());
+        // Missing required "languages"
+        assert!(parse_custom_servers(r#"{"servers":{"x":{"command":"x"}}}"#).is_err());
+        assert!(parse_custom_servers("{}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_custom_servers_reloads_on_change() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("lsp-servers.json");
+        let cache = std::sync::Mutex::new(None);
+
+        assert!(read_custom_servers_from(&path, &cache).unwrap().is_empty());
+
+        std::fs::write(
+            &path,
+            r#"{"servers":{"zls":{"command":"zls","languages":["zig"]}}}"#,
+        )
+        .unwrap();
+        let servers = read_custom_servers_from(&path, &cache).unwrap();
+        assert_eq!(servers["zls"].command, "zls");
+        // Unchanged file is served from the cache
+        assert_eq!(read_custom_servers_from(&path, &cache).unwrap(), servers);
+
+        std::fs::write(
+            &path,
+            r#"{"servers":{"zls":{"command":"/opt/zls/bin/zls","languages":["zig"]}}}"#,
+        )
+        .unwrap();
+        let servers = read_custom_servers_from(&path, &cache).unwrap();
+        assert_eq!(servers["zls"].command, "/opt/zls/bin/zls");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_custom_servers_from(&path, &cache).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lsp_transport_serde() {
+        assert_eq!(LspTransport::default(), LspTransport::Stdio);
+
+        let tcp: LspTransport =
+            serde_json::from_str(r#"{"type":"tcp","host":"127.0.0.1","port":5007}"#).unwrap();
+        assert_eq!(
+            tcp,
+            LspTransport::Tcp {
+                host: "127.0.0.1".to_string(),
+                port: 5007
+            }
+        );
+
+        let stdio: LspTransport = serde_json::from_str(r#"{"type":"stdio"}"#).unwrap();
+        assert_eq!(stdio, LspTransport::Stdio);
+    }
+
+    #[tokio::test]
+    async fn test_lsp_message_framing_roundtrip() {
+        let (client, server) = tokio::io::duplex(1024);
+        let mut writer: LspWriter = Box::new(client);
+        let mut reader = BufReader::new(server);
+
+        let message = r#"{"jsonrpc":"2.0","id":1,"method":"initialize"}"#;
+        write_lsp_message(&mut writer, message).await.unwrap();
+        write_lsp_message(&mut writer, "{}").await.unwrap();
+
+        assert_eq!(read_lsp_message(&mut reader).await.unwrap(), message);
+        assert_eq!(read_lsp_message(&mut reader).await.unwrap(), "{}");
+
+        drop(writer);
+        assert!(read_lsp_message(&mut reader).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connect_tcp() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let accept = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            read_lsp_message(&mut reader).await.unwrap()
+        });
+
+        let mut connection = connect_tcp("127.0.0.1", port).await.unwrap();
+        assert!(connection.child.is_none());
+        write_lsp_message(&mut connection.writer, "{\"id\":1}")
+            .await
+            .unwrap();
+
+        assert_eq!(accept.await.unwrap(), "{\"id\":1}");
+    }
+
+    #[test]
+    fn test_server_ids_for_root() {
+        let mut registry = LspRegistry::new();
+        for (id, language, root) in [
+            ("a", "rust", "/work/project"),
+            ("b", "typescript", "/work/project/web"),
+            ("c", "rust", "/work/project-other"),
+        ] {
+            let server = Arc::new(Mutex::new(LspServer::new(
+                id.to_string(),
+                language.to_string(),
+                root.to_string(),
+            )));
+            registry.insert(
+                id.to_string(),
+                server,
+                language.to_string(),
+                root.to_string(),
+            );
+        }
+
+        let mut ids = registry.server_ids_for_root("/work/project");
+        ids.sort();
+        assert_eq!(ids, vec!["a".to_string(), "b".to_string()]);
+        assert!(registry.server_ids_for_root("/elsewhere").is_empty());
+    }
+
+    #[test]
+    fn test_lsp_server_is_idle() {
+        let mut server = LspServer::new(
+            "server_1".to_string(),
+            "rust".to_string(),
+            "/project".to_string(),
+        );
+        assert!(!server.is_idle(Duration::from_secs(60)));
+
+        server.last_activity = Instant::now() - Duration::from_secs(120);
+        assert!(server.is_idle(Duration::from_secs(60)));
+    }
+
     #[test]
     fn test_creation_reservation_basic() {
         let mut registry = LspRegistry::new();
@@ -1673,10 +3451,219 @@ mod tests {
         assert_eq!(server.language, "typescript");
         assert_eq!(server.root_path, "/home/user/project");
         assert!(server.child.is_none());
-        assert!(server.stdin.is_none());
+        assert!(server.writer.is_none());
         assert!(server.stdout_task.is_none());
         assert!(server.stderr_task.is_none());
         assert!(!server.is_initialized);
+        assert!(server.file_watchers.is_empty());
+    }
+
+    #[test]
+    fn test_apply_capability_request_registers_watchers() {
+        let mut server = LspServer::new(
+            "server_1".to_string(),
+            "rust".to_string(),
+            "/project".to_string(),
+        );
+
+        let register = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "client/registerCapability",
+            "params": {
+                "registrations": [{
+                    "id": "watch-1",
+                    "method": "workspace/didChangeWatchedFiles",
+                    "registerOptions": {
+                        "watchers": [
+                            { "globPattern": "**/*.rs" },
+                            {
+                                "globPattern": { "baseUri": "file:///project/crates", "pattern": "*/Cargo.toml" },
+                                "kind": 5
+                            }
+                        ]
+                    }
+                }]
+            }
+        });
+
+        let id = apply_capability_request(&mut server, &register);
+        assert_eq!(id, Some(serde_json::json!(3)));
+
+        let watchers = &server.file_watchers["watch-1"];
+        assert_eq!(watchers.len(), 2);
+        assert_eq!(watchers[0].kind, 7);
+        assert_eq!(watchers[0].base_path, None);
+        assert_eq!(watchers[1].kind, 5);
+        assert_eq!(watchers[1].base_path.as_deref(), Some("/project/crates"));
+
+        let unregister = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 4,
+            "method": "client/unregisterCapability",
+            "params": {
+                "unregisterations": [{ "id": "watch-1", "method": "workspace/didChangeWatchedFiles" }]
+            }
+        });
+        assert!(apply_capability_request(&mut server, &unregister).is_some());
+        assert!(server.file_watchers.is_empty());
+    }
+
+    #[test]
+    fn test_apply_capability_request_ignores_other_registrations() {
+        let mut server = LspServer::new(
+            "server_1".to_string(),
+            "rust".to_string(),
+            "/project".to_string(),
+        );
+
+        let register = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "client/registerCapability",
+            "params": {
+                "registrations": [{ "id": "fmt", "method": "textDocument/formatting" }]
+            }
+        });
+
+        assert!(apply_capability_request(&mut server, &register).is_none());
+        assert!(server.file_watchers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handle_server_request_consumes_answered_requests() {
+        let server = Arc::new(Mutex::new(LspServer::new(
+            "server_1".to_string(),
+            "rust".to_string(),
+            "/project".to_string(),
+        )));
+
+        let watch = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "client/registerCapability",
+            "params": {
+                "registrations": [{
+                    "id": "watch-1",
+                    "method": "workspace/didChangeWatchedFiles",
+                    "registerOptions": { "watchers": [{ "globPattern": "**/*.rs" }] }
+                }]
+            }
+        });
+        assert!(handle_server_request(&server, &watch.to_string()).await);
+
+        // Left for the frontend to answer
+        let format = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "client/registerCapability",
+            "params": {
+                "registrations": [{ "id": "fmt", "method": "textDocument/formatting" }]
+            }
+        });
+        assert!(!handle_server_request(&server, &format.to_string()).await);
+        let diagnostics = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": "file:///project/src/main.rs", "diagnostics": [] }
+        });
+        assert!(!handle_server_request(&server, &diagnostics.to_string()).await);
+    }
+
+    #[test]
+    fn test_expand_glob_braces() {
+        assert_eq!(expand_glob_braces("**/*.rs"), vec!["**/*.rs"]);
+        assert_eq!(
+            expand_glob_braces("**/*.{ts,tsx}"),
+            vec!["**/*.ts", "**/*.tsx"]
+        );
+        assert_eq!(
+            expand_glob_braces("{src,lib}/*.{c,h}"),
+            vec!["src/*.c", "src/*.h", "lib/*.c", "lib/*.h"]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_watcher_matches() {
+        let watcher = FileSystemWatcher {
+            glob_pattern: "**/*.{rs,toml}".to_string(),
+            base_path: None,
+            kind: 7,
+        };
+        let root = "/project";
+
+        assert!(watcher_matches(
+            &watcher,
+            root,
+            Path::new("/project/src/lib.rs"),
+            FileChangeType::Changed
+        ));
+        assert!(watcher_matches(
+            &watcher,
+            root,
+            Path::new("/project/Cargo.toml"),
+            FileChangeType::Created
+        ));
+        assert!(!watcher_matches(
+            &watcher,
+            root,
+            Path::new("/project/README.md"),
+            FileChangeType::Changed
+        ));
+        // Outside of the server root
+        assert!(!watcher_matches(
+            &watcher,
+            root,
+            Path::new("/project-other/src/lib.rs"),
+            FileChangeType::Changed
+        ));
+
+        // Only interested in deletions
+        let delete_only = FileSystemWatcher {
+            kind: 4,
+            ..watcher.clone()
+        };
+        assert!(!watcher_matches(
+            &delete_only,
+            root,
+            Path::new("/project/src/lib.rs"),
+            FileChangeType::Changed
+        ));
+        assert!(watcher_matches(
+            &delete_only,
+            root,
+            Path::new("/project/src/lib.rs"),
+            FileChangeType::Deleted
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_build_did_change_watched_files() {
+        let changes = vec![
+            (
+                PathBuf::from("/project/src/lib.rs"),
+                FileChangeType::Changed,
+            ),
+            (
+                PathBuf::from("/project/src/old.rs"),
+                FileChangeType::Deleted,
+            ),
+        ];
+
+        let message = build_did_change_watched_files(&changes).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&message).unwrap();
+
+        assert_eq!(parsed["method"], "workspace/didChangeWatchedFiles");
+        assert!(parsed.get("id").is_none());
+        let events = parsed["params"]["changes"].as_array().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["uri"], "file:///project/src/lib.rs");
+        assert_eq!(events[0]["type"], 2);
+        assert_eq!(events[1]["type"], 3);
+
+        assert!(build_did_change_watched_files(&[]).is_none());
     }
 
     #[test]
@@ -1696,4 +3683,40 @@ mod tests {
         let second = generate_server_id("vue");
         assert_ne!(first, second);
     }
+
+    #[test]
+    fn test_apply_text_edits() {
+        let edit = |start: (u32, u32), end: (u32, u32), new_text: &str| LspTextEdit {
+            range: LspRange {
+                start: LspPosition {
+                    line: start.0,
+                    character: start.1,
+                },
+                end: LspPosition {
+                    line: end.0,
+                    character: end.1,
+                },
+            },
+            new_text: new_text.to_string(),
+        };
+        let content = "fn main(){\n  let s = \"é😀\";x();\n}\n";
+
+        // Characters count UTF-16 code units: 😀 takes two
+        let edits = vec![
+            edit((1, 16), (1, 16), "\n    "),
+            edit((0, 9), (0, 9), " "),
+            edit((1, 0), (1, 2), "    "),
+        ];
+        assert_eq!(
+            apply_text_edits(content, &edits).unwrap(),
+            "fn main() {\n    let s = \"é😀\";\n    x();\n}\n"
+        );
+
+        let overlapping = vec![edit((0, 0), (0, 5), ""), edit((0, 3), (0, 4), "")];
+        assert!(apply_text_edits(content, &overlapping).is_err());
+        assert_eq!(
+            language_for_path(Path::new("src/App.tsx")).as_deref(),
+            Some("typescriptreact")
+        );
+    }
 }
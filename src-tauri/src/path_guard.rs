@@ -0,0 +1,163 @@
+//! Workspace Path Guard
+//!
+//! The one check of the paths file tools, platform calls and attachment
+//! storage touch on disk. A path is resolved against its root, `..` is
+//! applied lexically, and the deepest part of it that exists is
+//! canonicalized, so neither traversal nor a symlink leads out of the root.
+//! Containment itself is decided by `walker::validate_path_in_workspace`.
+
+use crate::walker::validate_path_in_workspace;
+use std::path::{Component, Path, PathBuf};
+
+/// Resolve a path, absolute or relative to `root`, that need not exist yet.
+/// The result lies under `root` as given, with the symlinks inside it
+/// resolved.
+pub fn resolve(root: &Path, path: &Path) -> Result<PathBuf, String> {
+    let canonical_root = root
+        .canonicalize()
+        .map_err(|e| format!("Invalid workspace root: {}", e))?;
+    let full_path = normalize(&canonical_root.join(path));
+
+    // A missing path is checked through the deepest part of it that exists.
+    // A dangling symlink exists but does not canonicalize, so it is refused.
+    let mut existing = full_path.as_path();
+    let mut missing = vec![];
+    while existing.symlink_metadata().is_err() {
+        let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+            return Err(outside(path, root));
+        };
+        missing.push(name);
+        existing = parent;
+    }
+    if !validate_path_in_workspace(existing, &canonical_root) {
+        return Err(outside(path, root));
+    }
+
+    let canonical = existing
+        .canonicalize()
+        .map_err(|e| format!("Invalid path: {}", e))?;
+    let relative = canonical
+        .strip_prefix(&canonical_root)
+        .map_err(|_| outside(path, root))?;
+    let mut resolved = root.to_path_buf();
+    resolved.extend(relative.components());
+    resolved.extend(missing.into_iter().rev());
+    Ok(resolved)
+}
+
+/// Resolve a workspace-relative path as tools take them; absolute paths and
+/// `..` are refused outright
+pub fn resolve_relative(root: &Path, path: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(path);
+    if path.is_empty()
+        || relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(outside(relative, root));
+    }
+    resolve(root, relative)
+}
+
+/// Resolve `.` and `..` without touching the filesystem
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn outside(path: &Path, root: &Path) -> String {
+    format!(
+        "Path '{}' is outside workspace root '{}'",
+        path.display(),
+        root.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolve_inside_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+
+        assert_eq!(
+            resolve(root, Path::new("src/main.rs")).unwrap(),
+            root.join("src/main.rs")
+        );
+        assert_eq!(
+            resolve(root, &root.join("src/./main.rs")).unwrap(),
+            root.join("src/main.rs")
+        );
+        // Files and directories that do not exist yet
+        assert_eq!(
+            resolve(root, Path::new("src/new/mod.rs")).unwrap(),
+            root.join("src/new/mod.rs")
+        );
+        assert_eq!(
+            resolve(root, Path::new("src/../docs/README.md")).unwrap(),
+            root.join("docs/README.md")
+        );
+        assert_eq!(resolve(root, Path::new(".")).unwrap(), root);
+
+        assert_eq!(
+            resolve_relative(root, "src/main.rs").unwrap(),
+            root.join("src/main.rs")
+        );
+        assert!(resolve_relative(root, "").is_err());
+        assert!(resolve_relative(root, "src/../main.rs").is_err());
+    }
+
+    #[test]
+    fn test_resolve_refuses_paths_leaving_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("workspace");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(temp_dir.path().join("secret.txt"), "secret").unwrap();
+
+        for path in ["../secret.txt", "../new.txt", "a/../../secret.txt"] {
+            let error = resolve(&root, Path::new(path)).unwrap_err();
+            assert!(error.contains("outside workspace"), "{}", error);
+        }
+        assert!(resolve(&root, &temp_dir.path().join("secret.txt")).is_err());
+        assert!(resolve_relative(&root, "/etc/passwd").is_err());
+        assert!(resolve(&temp_dir.path().join("missing"), Path::new("a.txt")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_refuses_symlinks_leaving_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("workspace");
+        let outside_dir = temp_dir.path().join("outside");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(&outside_dir).unwrap();
+        fs::write(outside_dir.join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(&outside_dir, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(root.join("src"), root.join("src_link")).unwrap();
+        std::os::unix::fs::symlink(outside_dir.join("gone"), root.join("dangling")).unwrap();
+
+        assert!(resolve_relative(&root, "link/secret.txt").is_err());
+        assert!(resolve_relative(&root, "link/new.txt").is_err());
+        assert!(resolve_relative(&root, "dangling").is_err());
+        // Symlinks that stay inside resolve to their target
+        assert_eq!(
+            resolve_relative(&root, "src_link/lib.rs").unwrap(),
+            root.join("src/lib.rs")
+        );
+    }
+}
@@ -3,7 +3,9 @@
 //! Provides safe filesystem operations with workspace validation.
 //! Wraps existing file system utilities from the codebase.
 
+use crate::path_guard;
 use crate::platform::types::*;
+use crate::text_file::{read_text_file, TextContent};
 use std::path::{Path, PathBuf};
 
 /// Filesystem operations provider
@@ -15,111 +17,45 @@ impl FileSystemPlatform {
         Self
     }
 
-    /// Validate that a path is within the workspace root
+    /// Validate that a path, which need not exist yet, is within the workspace root
     fn validate_path(&self, path: &Path, ctx: &PlatformContext) -> Result<PathBuf, String> {
-        let canonical_path = path
-            .canonicalize()
-            .map_err(|e| format!("Invalid path: {}", e))?;
-
-        let canonical_root = ctx
-            .workspace_root
-            .canonicalize()
-            .map_err(|e| format!("Invalid workspace root: {}", e))?;
-
-        if !canonical_path.starts_with(&canonical_root) {
-            return Err(format!(
-                "Path '{}' is outside workspace root '{}'",
-                canonical_path.display(),
-                canonical_root.display()
-            ));
-        }
-
-        Ok(canonical_path)
-    }
-
-    /// Validate that a path for writing is within the workspace root
-    /// For write operations, the file may not exist yet, so we validate the parent directory
-    fn validate_write_path(&self, path: &Path, ctx: &PlatformContext) -> Result<PathBuf, String> {
-        // Get absolute path (without requiring file to exist)
-        let absolute_path = if path.is_absolute() {
-            path.to_path_buf()
-        } else {
-            ctx.workspace_root.join(path)
-        };
-
-        // Get canonical workspace root
-        let canonical_root = ctx
-            .workspace_root
-            .canonicalize()
-            .map_err(|e| format!("Invalid workspace root: {}", e))?;
-
-        // For new files, validate that the parent directory is within workspace
-        let parent = absolute_path
-            .parent()
-            .ok_or_else(|| "Invalid path: no parent directory".to_string())?;
-
-        // Create parent if it doesn't exist for validation purposes
-        if !parent.exists() {
-            // Parent doesn't exist yet, check if it would be within workspace
-            let parent_absolute = if parent.is_absolute() {
-                parent.to_path_buf()
-            } else {
-                ctx.workspace_root.join(parent)
-            };
-
-            if !parent_absolute.starts_with(&canonical_root) {
-                return Err(format!(
-                    "Path '{}' is outside workspace root '{}'",
-                    absolute_path.display(),
-                    canonical_root.display()
-                ));
-            }
-        } else {
-            let canonical_parent = parent
-                .canonicalize()
-                .map_err(|e| format!("Invalid parent directory: {}", e))?;
-
-            if !canonical_parent.starts_with(&canonical_root) {
-                return Err(format!(
-                    "Path '{}' is outside workspace root '{}'",
-                    absolute_path.display(),
-                    canonical_root.display()
-                ));
-            }
-        }
-
-        Ok(absolute_path)
+        path_guard::resolve(&ctx.workspace_root, path)
     }
 
-    /// Read file contents
-    pub async fn read_file(&self, path: &str, ctx: &PlatformContext) -> PlatformResult<String> {
+    /// Read file contents as text. Binary and oversized files yield a
+    /// `SkippedFile` marker rather than an error or lossy content.
+    pub async fn read_text(
+        &self,
+        path: &str,
+        ctx: &PlatformContext,
+    ) -> PlatformResult<TextContent> {
         let path = Path::new(path);
 
         match self.validate_path(path, ctx) {
             Ok(validated_path) => {
-                // Check file size
-                match tokio::fs::metadata(&validated_path).await {
-                    Ok(metadata) => {
-                        if metadata.len() > ctx.max_file_size as u64 {
-                            return PlatformResult::error(format!(
-                                "File too large: {} bytes (max: {})",
-                                metadata.len(),
-                                ctx.max_file_size
-                            ));
-                        }
-
-                        match tokio::fs::read_to_string(&validated_path).await {
-                            Ok(content) => PlatformResult::success(content),
-                            Err(e) => PlatformResult::error(format!("Failed to read file: {}", e)),
-                        }
-                    }
-                    Err(e) => PlatformResult::error(format!("Failed to get file metadata: {}", e)),
+                let max_size = ctx.max_file_size as u64;
+                match tokio::task::spawn_blocking(move || read_text_file(&validated_path, max_size))
+                    .await
+                {
+                    Ok(Ok(content)) => PlatformResult::success(content),
+                    Ok(Err(e)) => PlatformResult::error(format!("Failed to read file: {}", e)),
+                    Err(e) => PlatformResult::error(format!("Failed to read file: {}", e)),
                 }
             }
             Err(e) => PlatformResult::error(e),
         }
     }
 
+    /// Read file contents
+    pub async fn read_file(&self, path: &str, ctx: &PlatformContext) -> PlatformResult<String> {
+        let result = self.read_text(path, ctx).await;
+        match result.data {
+            Some(TextContent::Text(content)) => PlatformResult::success(content),
+            Some(TextContent::Skipped(skipped)) => PlatformResult::error(skipped.message()),
+            None => PlatformResult::error(result.error.unwrap_or_default()),
+        }
+    }
+
     /// Write file contents
     pub async fn write_file(
         &self,
@@ -129,7 +65,7 @@ impl FileSystemPlatform {
     ) -> PlatformResult<()> {
         let path = Path::new(path);
 
-        match self.validate_write_path(path, ctx) {
+        match self.validate_path(path, ctx) {
             Ok(validated_path) => {
                 // Ensure parent directory exists
                 if let Some(parent) = validated_path.parent() {
@@ -274,6 +210,7 @@ impl Default for FileSystemPlatform {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::text_file::SkippedFile;
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -301,6 +238,35 @@ mod tests {
         assert_eq!(read_result.data, Some("Hello, World!".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_read_binary_and_large_files() {
+        let fs = FileSystemPlatform::new();
+        let temp_dir = TempDir::new().unwrap();
+
+        let ctx = PlatformContext {
+            workspace_root: temp_dir.path().to_path_buf(),
+            worktree_path: None,
+            max_file_size: 16,
+            shell_timeout_secs: 60,
+        };
+
+        let binary_file = temp_dir.path().join("image.bin");
+        tokio::fs::write(&binary_file, b"\x89PNG\x00\x00")
+            .await
+            .unwrap();
+        let result = fs.read_text(&binary_file.to_string_lossy(), &ctx).await;
+        assert!(matches!(
+            result.data,
+            Some(TextContent::Skipped(SkippedFile::Binary { .. }))
+        ));
+
+        let large_file = temp_dir.path().join("large.txt");
+        tokio::fs::write(&large_file, "x".repeat(32)).await.unwrap();
+        let result = fs.read_file(&large_file.to_string_lossy(), &ctx).await;
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("too large"));
+    }
+
     #[tokio::test]
     async fn test_path_validation_outside_workspace() {
         let fs = FileSystemPlatform::new();
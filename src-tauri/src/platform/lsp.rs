@@ -3,6 +3,7 @@
 //! Provides Language Server Protocol operations.
 //! Wraps existing LSP module from the codebase.
 
+use crate::path_guard;
 use crate::platform::types::*;
 use std::path::Path;
 
@@ -15,30 +16,13 @@ impl LspPlatform {
         Self
     }
 
-    /// Validate that path is within workspace
+    /// Check that a path is within the workspace
     fn validate_path(
         &self,
         path: &Path,
         ctx: &PlatformContext,
     ) -> Result<std::path::PathBuf, String> {
-        let canonical_path = path
-            .canonicalize()
-            .map_err(|e| format!("Invalid path: {}", e))?;
-
-        let canonical_root = ctx
-            .workspace_root
-            .canonicalize()
-            .map_err(|e| format!("Invalid workspace root: {}", e))?;
-
-        if !canonical_path.starts_with(&canonical_root) {
-            return Err(format!(
-                "Path '{}' is outside workspace root '{}'",
-                canonical_path.display(),
-                canonical_root.display()
-            ));
-        }
-
-        Ok(canonical_path)
+        path_guard::resolve(&ctx.workspace_root, path)
     }
 
     /// Go to definition
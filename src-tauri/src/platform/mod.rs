@@ -61,12 +61,25 @@ impl Platform {
                     .get("path")
                     .and_then(|v| v.as_str())
                     .ok_or("Missing 'path' parameter")?;
-                let result = self.filesystem.read_file(path, ctx).await;
-                Ok(serde_json::json!({
-                    "success": result.success,
-                    "content": result.data,
-                    "error": result.error
-                }))
+                let result = self.filesystem.read_text(path, ctx).await;
+                Ok(match result.data {
+                    Some(crate::text_file::TextContent::Skipped(skipped)) => serde_json::json!({
+                        "success": true,
+                        "content": null,
+                        "skipped": skipped,
+                        "error": null
+                    }),
+                    Some(crate::text_file::TextContent::Text(content)) => serde_json::json!({
+                        "success": true,
+                        "content": content,
+                        "error": null
+                    }),
+                    None => serde_json::json!({
+                        "success": false,
+                        "content": null,
+                        "error": result.error
+                    }),
+                })
             }
             "write_file" => {
                 let path = input
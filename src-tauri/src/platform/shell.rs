@@ -3,6 +3,7 @@
 //! Provides shell command execution with workspace validation and timeouts.
 //! Wraps existing shell utilities from the codebase.
 
+use crate::path_guard;
 use crate::platform::types::*;
 use std::path::Path;
 
@@ -17,25 +18,8 @@ impl ShellPlatform {
 
     /// Validate that working directory is within workspace
     fn validate_cwd(&self, cwd: &str, ctx: &PlatformContext) -> Result<String, String> {
-        let path = Path::new(cwd);
-        let canonical_path = path
-            .canonicalize()
-            .map_err(|e| format!("Invalid working directory: {}", e))?;
-
-        let canonical_root = ctx
-            .workspace_root
-            .canonicalize()
-            .map_err(|e| format!("Invalid workspace root: {}", e))?;
-
-        if !canonical_path.starts_with(&canonical_root) {
-            return Err(format!(
-                "Working directory '{}' is outside workspace root '{}'",
-                canonical_path.display(),
-                canonical_root.display()
-            ));
-        }
-
-        Ok(canonical_path.to_string_lossy().to_string())
+        path_guard::resolve(&ctx.workspace_root, Path::new(cwd))
+            .map(|path| path.to_string_lossy().to_string())
     }
 
     /// Execute a shell command
@@ -86,26 +70,13 @@ impl ShellPlatform {
         ctx: &PlatformContext,
     ) -> PlatformResult<ShellResult> {
         // Validate script path
-        let path = Path::new(script_path);
-        let canonical_path = match path.canonicalize() {
-            Ok(p) => p,
-            Err(e) => return PlatformResult::error(format!("Invalid script path: {}", e)),
-        };
-
-        let _ = match self.validate_cwd(
-            canonical_path
-                .parent()
-                .map(|p| p.to_string_lossy().to_string())
-                .as_deref()
-                .unwrap_or("/"),
-            ctx,
-        ) {
+        let script_path = match path_guard::resolve(&ctx.workspace_root, Path::new(script_path)) {
             Ok(p) => p,
             Err(e) => return PlatformResult::error(e),
         };
 
         // Build command
-        let command = format!("{} {}", script_path, args.join(" "));
+        let command = format!("{} {}", script_path.display(), args.join(" "));
         self.execute(&command, cwd, ctx).await
     }
 
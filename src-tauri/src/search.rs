@@ -1,4 +1,5 @@
 use crate::constants::{is_code_extension, is_code_filename};
+use crate::text_file::{skip_for_search, DEFAULT_MAX_SEARCH_FILE_SIZE};
 use crate::walker::{WalkerConfig, WorkspaceWalker};
 use grep::regex::{RegexMatcher, RegexMatcherBuilder};
 use grep::searcher::sinks::UTF8;
@@ -196,12 +197,16 @@ impl RipgrepSearch {
             WalkerConfig::for_content_search().with_additional_excludes(additional_excludes);
         let walker = WorkspaceWalker::new(root_path, config).build();
 
-        // Collect files in parallel batches
+        // Collect files in parallel batches, skipping oversized and binary files up front
         let files: Vec<_> = walker
             .filter_map(|entry| entry.ok())
             .filter(|entry| {
                 let path = entry.path();
-                path.is_file() && self.is_valid_file(path)
+                if !path.is_file() || !self.is_valid_file(path) {
+                    return false;
+                }
+                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                skip_for_search(path, size, DEFAULT_MAX_SEARCH_FILE_SIZE).is_none()
             })
             .collect();
 
@@ -0,0 +1,305 @@
+//! API Keys
+//!
+//! Keys the HTTP server accepts in the `x-api-key` header. A key reads
+//! `tck_<id>_<secret>`; only a salted SHA-256 hash of the secret is stored,
+//! and presented secrets are compared with it in constant time. The full key
+//! is returned once, when it is created.
+//!
+//! A key may belong to a user, a team member reaching the machine remotely;
+//! such keys only reach that user's sessions. Keys without a user are the
+//! owner's and reach every session.
+//!
+//! A key's scopes limit what it may do, so a dashboard can be given a key
+//! that only reads sessions.
+
+use crate::security::random_hex;
+use crate::storage::{ApiKey, ApiKeyScope, ApiKeysRepository, StoredApiKey, User};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Start of every API key
+pub const API_KEY_PREFIX: &str = "tck_";
+
+/// Least time between two updates of a key's last use, in seconds
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+/// A newly created key along with its only copy of the full key
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+/// Creates, lists, revokes and verifies the server's API keys, and manages
+/// the users they belong to
+#[derive(Clone)]
+pub struct ApiKeys {
+    repository: ApiKeysRepository,
+}
+
+impl ApiKeys {
+    pub fn new(repository: ApiKeysRepository) -> Self {
+        Self { repository }
+    }
+
+    /// Create a key with a name describing its client and the scopes it
+    /// grants, optionally for a user
+    pub async fn create(
+        &self,
+        name: &str,
+        user_id: Option<&str>,
+        scopes: &[ApiKeyScope],
+    ) -> Result<CreatedApiKey, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("API key name must not be empty".to_string());
+        }
+        if scopes.is_empty() {
+            return Err("API key must have at least one scope".to_string());
+        }
+        if let Some(user_id) = user_id {
+            if self.repository.get_user(user_id).await?.is_none() {
+                return Err(format!("User not found: {}", user_id));
+            }
+        }
+
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let secret = random_hex::<32>();
+        let salt = random_hex::<16>();
+        let api_key = ApiKey {
+            id: id.clone(),
+            name: name.to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+            last_used_at: None,
+            revoked_at: None,
+            user_id: user_id.map(str::to_string),
+            scopes: ApiKeyScope::all()
+                .into_iter()
+                .filter(|scope| scopes.contains(scope))
+                .collect(),
+        };
+        self.repository
+            .create_api_key(&StoredApiKey {
+                key: api_key.clone(),
+                key_hash: hash_secret(&salt, &secret),
+                salt,
+            })
+            .await?;
+
+        Ok(CreatedApiKey {
+            api_key,
+            key: format!("{}{}_{}", API_KEY_PREFIX, id, secret),
+        })
+    }
+
+    /// List keys, revoked ones included
+    pub async fn list(&self) -> Result<Vec<ApiKey>, String> {
+        self.repository.list_api_keys().await
+    }
+
+    /// Revoke a key along with the refresh tokens issued for it. Returns
+    /// `false` when it does not exist or was already revoked.
+    pub async fn revoke(&self, key_id: &str) -> Result<bool, String> {
+        let now = chrono::Utc::now().timestamp();
+        let revoked = self.repository.revoke_api_key(key_id, now).await?;
+        self.repository
+            .revoke_api_key_refresh_tokens(key_id, now)
+            .await?;
+        Ok(revoked)
+    }
+
+    /// Create a user
+    pub async fn create_user(&self, name: &str) -> Result<User, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("User name must not be empty".to_string());
+        }
+        if self
+            .repository
+            .list_users()
+            .await?
+            .iter()
+            .any(|user| user.name == name)
+        {
+            return Err(format!("User already exists: {}", name));
+        }
+
+        let user = User {
+            id: format!("usr_{}", uuid::Uuid::new_v4().simple()),
+            name: name.to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        self.repository.create_user(&user).await?;
+        Ok(user)
+    }
+
+    /// List users, oldest first
+    pub async fn list_users(&self) -> Result<Vec<User>, String> {
+        self.repository.list_users().await
+    }
+
+    /// Delete a user and revoke their keys; their sessions are kept. Returns
+    /// `false` when the user does not exist.
+    pub async fn delete_user(&self, user_id: &str) -> Result<bool, String> {
+        if !self.repository.delete_user(user_id).await? {
+            return Ok(false);
+        }
+        for key in self.list().await? {
+            if key.user_id.as_deref() == Some(user_id) && key.revoked_at.is_none() {
+                self.revoke(&key.id).await?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// The key a client presented, or None when it is malformed, unknown,
+    /// revoked or its secret does not match
+    pub async fn verify(&self, key: &str) -> Result<Option<ApiKey>, String> {
+        let Some((id, secret)) = parse_key(key) else {
+            return Ok(None);
+        };
+        let Some(stored) = self.repository.get_api_key(id).await? else {
+            return Ok(None);
+        };
+        let hash = hash_secret(&stored.salt, secret);
+        if stored.key.revoked_at.is_some()
+            || !constant_time_eq(hash.as_bytes(), stored.key_hash.as_bytes())
+        {
+            return Ok(None);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let mut api_key = stored.key;
+        if api_key
+            .last_used_at
+            .is_none_or(|used_at| now - used_at >= LAST_USED_RESOLUTION_SECS)
+        {
+            if let Err(e) = self.repository.touch_api_key(&api_key.id, now).await {
+                log::warn!("Failed to record use of API key {}: {}", api_key.id, e);
+            }
+            api_key.last_used_at = Some(now);
+        }
+        Ok(Some(api_key))
+    }
+}
+
+/// ID and secret of a key
+fn parse_key(key: &str) -> Option<(&str, &str)> {
+    let (id, secret) = key.trim().strip_prefix(API_KEY_PREFIX)?.split_once('_')?;
+    (!id.is_empty() && !secret.is_empty()).then_some((id, secret))
+}
+
+fn hash_secret(salt: &str, secret: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(secret.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Compare two byte strings in time independent of where they differ
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::storage::migrations::{settings_migrations, MigrationRunner};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_key_and_compare() {
+        assert_eq!(parse_key("tck_abc_def"), Some(("abc", "def")));
+        assert_eq!(parse_key("tck_abc"), None);
+        assert_eq!(parse_key("sk-abc_def"), None);
+        assert!(constant_time_eq(b"same", b"same"));
+        assert!(!constant_time_eq(b"same", b"sane"));
+        assert!(!constant_time_eq(b"same", b"sam"));
+        assert_ne!(
+            hash_secret("salt-1", "secret"),
+            hash_secret("salt-2", "secret")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_verify_and_revoke() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("settings.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect().await.unwrap();
+        let registry = settings_migrations();
+        MigrationRunner::new(&db, &registry)
+            .migrate()
+            .await
+            .unwrap();
+        let api_keys = ApiKeys::new(ApiKeysRepository::new(db));
+
+        let all = ApiKeyScope::all();
+        assert!(api_keys.create("  ", None, &all).await.is_err());
+        assert!(api_keys.create("Phone", None, &[]).await.is_err());
+        let created = api_keys.create("Phone", None, &all).await.unwrap();
+        assert!(created.key.starts_with(API_KEY_PREFIX));
+
+        let verified = api_keys.verify(&created.key).await.unwrap().unwrap();
+        assert_eq!(verified.id, created.api_key.id);
+        assert_eq!(verified.scopes, all);
+        assert!(verified.last_used_at.is_some());
+
+        let dashboard = api_keys
+            .create("Dashboard", None, &[ApiKeyScope::ReadSessions])
+            .await
+            .unwrap();
+        let verified = api_keys.verify(&dashboard.key).await.unwrap().unwrap();
+        assert_eq!(verified.scopes, vec![ApiKeyScope::ReadSessions]);
+        assert!(api_keys
+            .verify(&format!("{}x", created.key))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(api_keys.verify("not-a-key").await.unwrap().is_none());
+
+        assert!(api_keys.revoke(&created.api_key.id).await.unwrap());
+        assert!(!api_keys.revoke(&created.api_key.id).await.unwrap());
+        assert!(api_keys.verify(&created.key).await.unwrap().is_none());
+        let listed = api_keys.list().await.unwrap();
+        assert_eq!(listed.len(), 2);
+        assert!(listed[0].revoked_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_user_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("settings.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect().await.unwrap();
+        let registry = settings_migrations();
+        MigrationRunner::new(&db, &registry)
+            .migrate()
+            .await
+            .unwrap();
+        let api_keys = ApiKeys::new(ApiKeysRepository::new(db));
+
+        let user = api_keys.create_user("Alice").await.unwrap();
+        assert!(api_keys.create_user(" Alice ").await.is_err());
+        assert!(api_keys
+            .create("Laptop", Some("usr_unknown"), &ApiKeyScope::all())
+            .await
+            .is_err());
+        let created = api_keys
+            .create("Laptop", Some(&user.id), &ApiKeyScope::all())
+            .await
+            .unwrap();
+        let verified = api_keys.verify(&created.key).await.unwrap().unwrap();
+        assert_eq!(verified.user_id.as_deref(), Some(user.id.as_str()));
+
+        // Deleting a user revokes their keys
+        assert!(api_keys.delete_user(&user.id).await.unwrap());
+        assert!(!api_keys.delete_user(&user.id).await.unwrap());
+        assert!(api_keys.verify(&created.key).await.unwrap().is_none());
+        assert!(api_keys.list_users().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,305 @@
+//! JWT Authentication
+//!
+//! Remote clients sign in once with an API key and then authenticate with
+//! short-lived access tokens, renewing them with a refresh token instead of
+//! keeping the key. Tokens are HS256 JWTs signed with a secret kept in the
+//! settings. Every refresh rotates the refresh token; presenting a rotated
+//! token again revokes its whole family, since it was probably stolen.
+//!
+//! Revoking an API key revokes its refresh tokens at once, while its access
+//! tokens stay valid until they expire. Tokens carry the scopes of their key.
+
+use crate::security::api_keys::ApiKeys;
+use crate::security::{random_hex, AuthenticatedKey};
+use crate::storage::{ApiKey, ApiKeyScope, ApiKeysRepository, RefreshToken, SettingsRepository};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+/// Setting holding the secret tokens are signed with
+pub const JWT_SECRET_KEY: &str = "server_jwt_secret";
+
+/// Lifetime of access tokens, in seconds
+pub const ACCESS_TOKEN_TTL_SECS: i64 = 15 * 60;
+
+/// Lifetime of refresh tokens, in seconds
+pub const REFRESH_TOKEN_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// Kind of a token; each is only accepted where it belongs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TokenType {
+    Access,
+    Refresh,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    /// API key the tokens were issued for
+    sub: String,
+    jti: String,
+    iat: i64,
+    exp: i64,
+    typ: TokenType,
+    /// Family of a refresh token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fam: Option<String>,
+    /// User the API key belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    usr: Option<String>,
+    /// Scopes of the API key; tokens issued before scopes existed have all
+    #[serde(default = "ApiKeyScope::all")]
+    scp: Vec<ApiKeyScope>,
+}
+
+/// Tokens issued on sign-in or refresh
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    /// Seconds until the access token expires
+    pub expires_in: i64,
+    /// Seconds until the refresh token expires
+    pub refresh_expires_in: i64,
+}
+
+/// Issues, rotates and validates the HTTP server's tokens
+#[derive(Clone)]
+pub struct JwtAuth {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    api_keys: ApiKeys,
+    repository: ApiKeysRepository,
+}
+
+impl JwtAuth {
+    /// Load the signing secret, creating it on first use
+    pub async fn load(
+        settings: SettingsRepository,
+        api_keys: ApiKeys,
+        repository: ApiKeysRepository,
+    ) -> Result<Self, String> {
+        let secret = match settings
+            .get_setting_or_default::<Option<String>>(JWT_SECRET_KEY, None)
+            .await?
+        {
+            Some(secret) => secret,
+            None => {
+                let secret = random_hex::<32>();
+                settings
+                    .set_setting(JWT_SECRET_KEY, &serde_json::json!(secret))
+                    .await?;
+                secret
+            }
+        };
+
+        Ok(Self {
+            encoding_key: EncodingKey::from_secret(secret.as_bytes()),
+            decoding_key: DecodingKey::from_secret(secret.as_bytes()),
+            api_keys,
+            repository,
+        })
+    }
+
+    /// Sign in with an API key
+    pub async fn login(&self, api_key: &str) -> Result<AuthTokens, String> {
+        let key = self
+            .api_keys
+            .verify(api_key)
+            .await?
+            .ok_or_else(|| "Invalid API key".to_string())?;
+
+        let now = chrono::Utc::now().timestamp();
+        if let Err(e) = self.repository.delete_expired_refresh_tokens(now).await {
+            log::warn!("Failed to delete expired refresh tokens: {}", e);
+        }
+        let family_id = uuid::Uuid::new_v4().to_string();
+        self.issue(&key, &family_id).await
+    }
+
+    /// Exchange a refresh token for new tokens. The refresh token can not be
+    /// used again; reusing it revokes every token rotated from the same sign-in.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<AuthTokens, String> {
+        let (claims, token) = self.stored_refresh_token(refresh_token).await?;
+        let now = chrono::Utc::now().timestamp();
+        if !self.repository.revoke_refresh_token(&token.id, now).await? {
+            self.repository
+                .revoke_refresh_token_family(&token.family_id, now)
+                .await?;
+            return Err("Refresh token was already used; sign in again".to_string());
+        }
+
+        let Some(key) = self
+            .repository
+            .get_api_key(&claims.sub)
+            .await?
+            .map(|stored| stored.key)
+            .filter(|key| key.revoked_at.is_none())
+        else {
+            return Err("API key was revoked; sign in again".to_string());
+        };
+        self.issue(&key, &token.family_id).await
+    }
+
+    /// Sign out, revoking the refresh token and every token rotated with it
+    pub async fn logout(&self, refresh_token: &str) -> Result<(), String> {
+        let (_, token) = self.stored_refresh_token(refresh_token).await?;
+        self.repository
+            .revoke_refresh_token_family(&token.family_id, chrono::Utc::now().timestamp())
+            .await
+    }
+
+    /// API key an access token was issued for, or None when the token is
+    /// invalid or expired
+    pub fn verify_access_token(&self, token: &str) -> Option<AuthenticatedKey> {
+        self.decode(token)
+            .ok()
+            .filter(|claims| claims.typ == TokenType::Access)
+            .map(|claims| AuthenticatedKey {
+                key_id: claims.sub,
+                user_id: claims.usr,
+                scopes: claims.scp,
+            })
+    }
+
+    async fn issue(&self, key: &ApiKey, family_id: &str) -> Result<AuthTokens, String> {
+        let now = chrono::Utc::now().timestamp();
+        let refresh = RefreshToken {
+            id: uuid::Uuid::new_v4().to_string(),
+            family_id: family_id.to_string(),
+            api_key_id: key.id.clone(),
+            created_at: now,
+            expires_at: now + REFRESH_TOKEN_TTL_SECS,
+            revoked_at: None,
+        };
+        let access_token = self.encode(&Claims {
+            sub: key.id.clone(),
+            jti: uuid::Uuid::new_v4().to_string(),
+            iat: now,
+            exp: now + ACCESS_TOKEN_TTL_SECS,
+            typ: TokenType::Access,
+            fam: None,
+            usr: key.user_id.clone(),
+            scp: key.scopes.clone(),
+        })?;
+        let refresh_token = self.encode(&Claims {
+            sub: key.id.clone(),
+            jti: refresh.id.clone(),
+            iat: now,
+            exp: refresh.expires_at,
+            typ: TokenType::Refresh,
+            fam: Some(refresh.family_id.clone()),
+            usr: key.user_id.clone(),
+            scp: key.scopes.clone(),
+        })?;
+        self.repository.create_refresh_token(&refresh).await?;
+
+        Ok(AuthTokens {
+            access_token,
+            refresh_token,
+            token_type: "Bearer".to_string(),
+            expires_in: ACCESS_TOKEN_TTL_SECS,
+            refresh_expires_in: REFRESH_TOKEN_TTL_SECS,
+        })
+    }
+
+    /// Claims and record of a validly signed, unexpired refresh token
+    async fn stored_refresh_token(&self, token: &str) -> Result<(Claims, RefreshToken), String> {
+        let claims = self
+            .decode(token)
+            .ok()
+            .filter(|claims| claims.typ == TokenType::Refresh)
+            .ok_or_else(|| "Invalid or expired refresh token".to_string())?;
+        let stored = self
+            .repository
+            .get_refresh_token(&claims.jti)
+            .await?
+            .filter(|stored| stored.api_key_id == claims.sub)
+            .ok_or_else(|| "Invalid or expired refresh token".to_string())?;
+        Ok((claims, stored))
+    }
+
+    fn encode(&self, claims: &Claims) -> Result<String, String> {
+        encode(&Header::new(Algorithm::HS256), claims, &self.encoding_key)
+            .map_err(|e| format!("Failed to sign token: {}", e))
+    }
+
+    fn decode(&self, token: &str) -> Result<Claims, String> {
+        decode::<Claims>(
+            token,
+            &self.decoding_key,
+            &Validation::new(Algorithm::HS256),
+        )
+        .map(|data| data.claims)
+        .map_err(|e| format!("Invalid token: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::storage::migrations::{settings_migrations, MigrationRunner};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_login_refresh_rotation_and_reuse() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("settings.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect().await.unwrap();
+        let registry = settings_migrations();
+        MigrationRunner::new(&db, &registry)
+            .migrate()
+            .await
+            .unwrap();
+        let repository = ApiKeysRepository::new(db.clone());
+        let api_keys = ApiKeys::new(repository.clone());
+        let auth = JwtAuth::load(SettingsRepository::new(db), api_keys.clone(), repository)
+            .await
+            .unwrap();
+
+        let key = api_keys
+            .create("Phone", None, &ApiKeyScope::all())
+            .await
+            .unwrap();
+        assert!(auth.login("tck_unknown_secret").await.is_err());
+        let tokens = auth.login(&key.key).await.unwrap();
+        assert_eq!(
+            auth.verify_access_token(&tokens.access_token),
+            Some(AuthenticatedKey {
+                key_id: key.api_key.id.clone(),
+                user_id: None,
+                scopes: ApiKeyScope::all(),
+            })
+        );
+        // A refresh token is not an access token
+        assert_eq!(auth.verify_access_token(&tokens.refresh_token), None);
+        assert_eq!(auth.verify_access_token("not-a-token"), None);
+
+        // Refreshing rotates the refresh token; reusing the old one revokes
+        // the rotated one too
+        let rotated = auth.refresh(&tokens.refresh_token).await.unwrap();
+        assert!(auth.refresh(&tokens.refresh_token).await.is_err());
+        assert!(auth.refresh(&rotated.refresh_token).await.is_err());
+
+        // Tokens of a user's key carry the user and the key's scopes
+        let user = api_keys.create_user("Alice").await.unwrap();
+        let user_key = api_keys
+            .create("Laptop", Some(&user.id), &[ApiKeyScope::ReadSessions])
+            .await
+            .unwrap();
+        let tokens = auth.login(&user_key.key).await.unwrap();
+        let rotated = auth.refresh(&tokens.refresh_token).await.unwrap();
+        let verified = auth.verify_access_token(&rotated.access_token).unwrap();
+        assert_eq!(verified.user_id, Some(user.id));
+        assert_eq!(verified.scopes, vec![ApiKeyScope::ReadSessions]);
+
+        // Revoking the key revokes its refresh tokens
+        let tokens = auth.login(&key.key).await.unwrap();
+        api_keys.revoke(&key.api_key.id).await.unwrap();
+        assert!(auth.refresh(&tokens.refresh_token).await.is_err());
+    }
+}
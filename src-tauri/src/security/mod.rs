@@ -1,20 +1,92 @@
-use axum::extract::Request;
+pub mod api_keys;
+pub mod jwt;
+pub mod network;
+pub mod pairing;
+pub mod permissions;
+pub mod rate_limit;
+pub mod scope;
+
+use axum::extract::{Request, State};
+use axum::http::header::AUTHORIZATION;
 use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
+use rand::RngCore;
+
+use crate::server::state::ServerState;
+use crate::storage::ApiKeyScope;
 
 const API_KEY_HEADER: &str = "x-api-key";
 
-pub async fn api_key_middleware(req: Request, next: Next) -> Response {
-    let authorized = req
-        .headers()
-        .get(API_KEY_HEADER)
-        .and_then(|value| value.to_str().ok())
-        .map(|value| !value.trim().is_empty())
-        .unwrap_or(false);
+/// API key a request was authenticated with, added to the request's
+/// extensions by `auth_middleware`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedKey {
+    pub key_id: String,
+    /// User the key belongs to; `None` for keys of the machine's owner
+    pub user_id: Option<String>,
+    /// What the request may do
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+impl AuthenticatedKey {
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
 
-    if !authorized {
+/// Reject requests without a valid access token or API key. Access tokens are
+/// sent as `Authorization: Bearer <token>`, API keys in `x-api-key`.
+pub async fn auth_middleware(
+    State(state): State<ServerState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if let Some(token) = header(&req, AUTHORIZATION.as_str())
+        .and_then(|value| value.strip_prefix("Bearer ").map(str::to_string))
+    {
+        return match state.runtime().verify_access_token(token.trim()) {
+            Some(key) => {
+                req.extensions_mut().insert(key);
+                next.run(req).await
+            }
+            None => axum::http::StatusCode::UNAUTHORIZED.into_response(),
+        };
+    }
+
+    let Some(key) = header(&req, API_KEY_HEADER) else {
         return axum::http::StatusCode::UNAUTHORIZED.into_response();
+    };
+    match state.runtime().verify_api_key(&key).await {
+        Ok(Some(api_key)) => {
+            req.extensions_mut().insert(AuthenticatedKey {
+                key_id: api_key.id,
+                user_id: api_key.user_id,
+                scopes: api_key.scopes,
+            });
+            next.run(req).await
+        }
+        Ok(None) => axum::http::StatusCode::UNAUTHORIZED.into_response(),
+        Err(e) => {
+            log::error!("Failed to verify API key: {}", e);
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
     }
+}
+
+/// Trimmed value of a non-empty header. Owned, so the request is not borrowed
+/// while credentials are checked.
+fn header(req: &Request, name: &str) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
 
-    next.run(req).await
+/// Hex encoding of N random bytes, for secrets and salts
+pub(crate) fn random_hex<const N: usize>() -> String {
+    let mut bytes = [0u8; N];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
 }
@@ -0,0 +1,34 @@
+//! Network Access
+//!
+//! Which clients may reach the HTTP server at all, checked before rate
+//! limiting and authentication. Clients on this machine always may; when the
+//! server listens on a LAN address, others must come from one of the allowed
+//! networks of its bind settings. Clients of the local socket have no address
+//! and are local.
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::net::SocketAddr;
+
+use crate::server::state::ServerState;
+
+/// Refuse requests from addresses outside the allowed networks
+pub async fn ip_allowlist_middleware(
+    State(state): State<ServerState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let ip = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+    match ip {
+        Some(ip) if !state.config.bind.allows(ip) => {
+            log::debug!("Refused request from {} outside the allowed networks", ip);
+            StatusCode::FORBIDDEN.into_response()
+        }
+        _ => next.run(req).await,
+    }
+}
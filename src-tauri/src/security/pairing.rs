@@ -0,0 +1,144 @@
+//! Device Pairing
+//!
+//! One-time codes that let a companion client on the LAN sign in without
+//! copying an API key by hand. The owner creates a pairing, shown as a code
+//! and a QR code; the client redeems it once, before it expires, for an API
+//! key of its own. Codes are kept in memory only, so a restart voids them.
+
+use crate::security::api_keys::CreatedApiKey;
+use crate::security::jwt::AuthTokens;
+use rand::Rng;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Time a pairing code can be redeemed in
+pub const PAIRING_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Characters of a pairing code, without ones easily mistaken for another
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+const CODE_LENGTH: usize = 8;
+
+/// A pairing code waiting to be redeemed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pairing {
+    pub code: String,
+    /// User the paired device's key will belong to; `None` for the owner
+    pub user_id: Option<String>,
+    pub expires_at: i64,
+}
+
+/// A device that redeemed a pairing code: its key, for signing in again,
+/// and tokens it is signed in with
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairedDevice {
+    pub api_key: CreatedApiKey,
+    pub tokens: AuthTokens,
+}
+
+struct PendingPairing {
+    user_id: Option<String>,
+    expires: Instant,
+}
+
+/// Pairing codes created and not yet redeemed
+#[derive(Clone, Default)]
+pub struct Pairings {
+    pending: Arc<Mutex<HashMap<String, PendingPairing>>>,
+}
+
+impl Pairings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a code for a device of `user_id`, or of the owner
+    pub fn create(&self, user_id: Option<&str>) -> Pairing {
+        self.create_at(user_id, Instant::now())
+    }
+
+    /// Consume a code, returning the user its device's key belongs to
+    pub fn redeem(&self, code: &str) -> Result<Option<String>, String> {
+        self.redeem_at(code, Instant::now())
+    }
+
+    fn create_at(&self, user_id: Option<&str>, now: Instant) -> Pairing {
+        let code = generate_code();
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, pairing| pairing.expires > now);
+        pending.insert(
+            code.clone(),
+            PendingPairing {
+                user_id: user_id.map(str::to_string),
+                expires: now + PAIRING_TTL,
+            },
+        );
+
+        Pairing {
+            code,
+            user_id: user_id.map(str::to_string),
+            expires_at: chrono::Utc::now().timestamp() + PAIRING_TTL.as_secs() as i64,
+        }
+    }
+
+    fn redeem_at(&self, code: &str, now: Instant) -> Result<Option<String>, String> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        match pending.remove(&normalize_code(code)) {
+            Some(pairing) if pairing.expires > now => Ok(pairing.user_id),
+            _ => Err("Invalid or expired pairing code".to_string()),
+        }
+    }
+}
+
+/// A random pairing code
+pub(crate) fn generate_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..CODE_LENGTH)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// A code as typed, in the form it was created in. Codes are read off a
+/// screen; accept them in any case and grouped.
+pub(crate) fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pairing_codes_are_redeemed_once() {
+        let pairings = Pairings::new();
+        let start = Instant::now();
+
+        let pairing = pairings.create_at(Some("user-1"), start);
+        assert_eq!(pairing.code.len(), CODE_LENGTH);
+        assert_eq!(pairing.user_id.as_deref(), Some("user-1"));
+
+        let typed = format!("{}-{}", &pairing.code[..4], &pairing.code[4..]).to_lowercase();
+        assert_eq!(
+            pairings.redeem_at(&typed, start),
+            Ok(Some("user-1".to_string()))
+        );
+        assert!(pairings.redeem_at(&pairing.code, start).is_err());
+
+        // Codes of the owner carry no user; expired codes are refused
+        let owner = pairings.create_at(None, start);
+        assert_eq!(pairings.redeem_at(&owner.code, start), Ok(None));
+        let expired = pairings.create_at(None, start);
+        assert!(pairings
+            .redeem_at(&expired.code, start + PAIRING_TTL)
+            .is_err());
+        assert!(pairings.redeem_at("UNKNOWN1", start).is_err());
+    }
+}
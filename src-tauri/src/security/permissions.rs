@@ -0,0 +1,175 @@
+//! Scope Permissions
+//!
+//! Every route needs a scope of the key a request was made with: routes
+//! administering the server need `admin`, deciding on tool calls, running
+//! plans and rolling back or merging files need `execute-tools`, other reads need `read-sessions` and other
+//! writes `write-sessions`. Tasks started without `execute-tools` only get
+//! read-only tools, so a read-only or chat-only client can not change files.
+
+use axum::async_trait;
+use axum::extract::{FromRequestParts, MatchedPath, Request};
+use axum::http::request::Parts;
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::convert::Infallible;
+
+use crate::security::scope::ADMIN_ROUTES;
+use crate::security::AuthenticatedKey;
+use crate::storage::ApiKeyScope;
+
+/// Routes that decide on or run tool calls, or change files on disk
+const TOOL_ROUTES: [&str; 9] = [
+    "/v1/tool-calls/:id/approve",
+    "/v1/tool-calls/:id/deny",
+    "/v1/tool-calls/:id/input",
+    "/v1/sessions/:id/approvals/resolve",
+    "/v1/sessions/:id/actions",
+    "/v1/sessions/:id/plans/execute",
+    "/v1/sessions/:id/checkpoints/:checkpoint_id/rollback",
+    "/v1/tasks/:id/worktree/merge",
+    "/v1/tasks/:id/worktree",
+];
+
+/// Routes any authenticated key reaches
+const OPEN_ROUTES: [&str; 1] = ["/health"];
+
+/// Scopes of the key a request was made with
+#[derive(Debug, Clone, Default)]
+pub struct RequestScopes(pub Vec<ApiKeyScope>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestScopes {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(
+            parts
+                .extensions
+                .get::<AuthenticatedKey>()
+                .map(|key| key.scopes.clone())
+                .unwrap_or_default(),
+        ))
+    }
+}
+
+impl RequestScopes {
+    /// Whether tasks the request starts may run tools that need approval or
+    /// modify files
+    pub fn can_run_tools(&self) -> bool {
+        self.0.contains(&ApiKeyScope::ExecuteTools)
+    }
+}
+
+/// Scope a request to a route needs; None for routes open to any key
+fn required_scope(method: &Method, route: &str) -> Option<ApiKeyScope> {
+    if OPEN_ROUTES.contains(&route) {
+        return None;
+    }
+    if ADMIN_ROUTES.iter().any(|prefix| route.starts_with(prefix)) {
+        return Some(ApiKeyScope::Admin);
+    }
+    if method == Method::GET || method == Method::HEAD {
+        return Some(ApiKeyScope::ReadSessions);
+    }
+    if TOOL_ROUTES.contains(&route) {
+        return Some(ApiKeyScope::ExecuteTools);
+    }
+    Some(ApiKeyScope::WriteSessions)
+}
+
+/// Refuse requests whose key lacks the scope of the route; runs after
+/// authentication
+pub async fn permission_middleware(
+    route: Option<MatchedPath>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let route = route.as_ref().map(MatchedPath::as_str).unwrap_or_default();
+    let Some(scope) = required_scope(req.method(), route) else {
+        return next.run(req).await;
+    };
+    if req
+        .extensions()
+        .get::<AuthenticatedKey>()
+        .is_some_and(|key| key.has_scope(scope))
+    {
+        return next.run(req).await;
+    }
+    (
+        StatusCode::FORBIDDEN,
+        format!("API key lacks the {} scope", scope.as_str()),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_scope() {
+        assert_eq!(required_scope(&Method::GET, "/health"), None);
+        assert_eq!(
+            required_scope(&Method::GET, "/v1/sessions/:id/messages"),
+            Some(ApiKeyScope::ReadSessions)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/v1/ws"),
+            Some(ApiKeyScope::ReadSessions)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/v1/sessions/:id/messages"),
+            Some(ApiKeyScope::WriteSessions)
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, "/v1/sessions/:id"),
+            Some(ApiKeyScope::WriteSessions)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/v1/tool-calls/:id/approve"),
+            Some(ApiKeyScope::ExecuteTools)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/v1/sessions/:id/plans/execute"),
+            Some(ApiKeyScope::ExecuteTools)
+        );
+        // Reading admin routes needs admin too
+        assert_eq!(
+            required_scope(&Method::GET, "/v1/api-keys"),
+            Some(ApiKeyScope::Admin)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/v1/retention-policy/apply"),
+            Some(ApiKeyScope::Admin)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/v1/integrations/:id/pairings"),
+            Some(ApiKeyScope::Admin)
+        );
+    }
+
+    #[test]
+    fn test_routes_changing_files_need_execute_tools() {
+        assert_eq!(
+            required_scope(
+                &Method::POST,
+                "/v1/sessions/:id/checkpoints/:checkpoint_id/rollback"
+            ),
+            Some(ApiKeyScope::ExecuteTools)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/v1/tasks/:id/worktree/merge"),
+            Some(ApiKeyScope::ExecuteTools)
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, "/v1/tasks/:id/worktree"),
+            Some(ApiKeyScope::ExecuteTools)
+        );
+        // Listing them only reads
+        assert_eq!(
+            required_scope(&Method::GET, "/v1/sessions/:id/checkpoints"),
+            Some(ApiKeyScope::ReadSessions)
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Rate Limiting
+//!
+//! Sliding window limits on requests to the HTTP server, per client IP and
+//! per API key, so one client on the LAN can not starve the server. Requests
+//! over a limit are answered with 429 and a Retry-After header; violations
+//! are logged and counted per client.
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::RETRY_AFTER;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::security::AuthenticatedKey;
+use crate::server::state::ServerState;
+
+/// Setting holding the server's `RateLimitConfig`
+pub const RATE_LIMITS_KEY: &str = "server_rate_limits";
+
+/// Most clients whose recent requests are tracked before idle ones are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Request limits of the HTTP server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RateLimitConfig {
+    /// Requests per window from one IP address; 0 disables the limit
+    pub per_ip: u32,
+    /// Requests per window with one API key; 0 disables the limit
+    pub per_key: u32,
+    /// Length of the sliding window, in seconds
+    pub window_secs: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_ip: 600,
+            per_key: 300,
+            window_secs: 60,
+        }
+    }
+}
+
+/// Requests of one client rejected for exceeding a limit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitViolation {
+    /// `ip:<address>` or `key:<API key ID>`
+    pub client: String,
+    pub rejected: u64,
+    pub last_rejected_at: i64,
+}
+
+/// Sliding window rate limiter keyed by client
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    /// Times of each client's requests within the window
+    requests: Mutex<HashMap<String, VecDeque<Instant>>>,
+    violations: Mutex<HashMap<String, RateLimitViolation>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            requests: Mutex::new(HashMap::new()),
+            violations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> RateLimitConfig {
+        self.config
+    }
+
+    /// Count a request of a client against a limit. Returns how long until
+    /// the client may retry when it is over the limit; rejected requests are
+    /// not counted.
+    pub fn check(&self, client: &str, limit: u32) -> Result<(), Duration> {
+        self.check_at(client, limit, Instant::now())
+    }
+
+    fn check_at(&self, client: &str, limit: u32, now: Instant) -> Result<(), Duration> {
+        if limit == 0 {
+            return Ok(());
+        }
+        let window = Duration::from_secs(self.config.window_secs);
+        let mut requests = lock(&self.requests);
+        if requests.len() >= MAX_TRACKED_CLIENTS && !requests.contains_key(client) {
+            requests.retain(|_, times| {
+                times
+                    .back()
+                    .is_some_and(|last| now.duration_since(*last) < window)
+            });
+        }
+
+        let times = requests.entry(client.to_string()).or_default();
+        while times
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= window)
+        {
+            times.pop_front();
+        }
+        if let Some(first) = times.front().filter(|_| times.len() >= limit as usize) {
+            let retry_after = window.saturating_sub(now.duration_since(*first));
+            drop(requests);
+            self.record_violation(client);
+            return Err(retry_after);
+        }
+        times.push_back(now);
+        Ok(())
+    }
+
+    /// Clients that had requests rejected, most rejected first
+    pub fn violations(&self) -> Vec<RateLimitViolation> {
+        let mut violations: Vec<_> = lock(&self.violations).values().cloned().collect();
+        violations.sort_by_key(|violation| std::cmp::Reverse(violation.rejected));
+        violations
+    }
+
+    fn record_violation(&self, client: &str) {
+        let mut violations = lock(&self.violations);
+        let violation =
+            violations
+                .entry(client.to_string())
+                .or_insert_with(|| RateLimitViolation {
+                    client: client.to_string(),
+                    rejected: 0,
+                    last_rejected_at: 0,
+                });
+        // Logged once per client rather than for every rejected request
+        if violation.rejected == 0 {
+            log::warn!("Rate limit exceeded by {}", client);
+        }
+        violation.rejected += 1;
+        violation.last_rejected_at = chrono::Utc::now().timestamp();
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Limit the requests of each client IP address
+pub async fn ip_rate_limit_middleware(
+    State(state): State<ServerState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let limiter = state.rate_limiter();
+    let ip = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+    if let Some(ip) = ip {
+        if let Err(retry_after) = limiter.check(&format!("ip:{}", ip), limiter.config().per_ip) {
+            return too_many_requests(retry_after);
+        }
+    }
+    next.run(req).await
+}
+
+/// Limit the requests made with each API key; runs after authentication
+pub async fn key_rate_limit_middleware(
+    State(state): State<ServerState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let limiter = state.rate_limiter();
+    let key_id = req
+        .extensions()
+        .get::<AuthenticatedKey>()
+        .map(|key| key.key_id.clone());
+    if let Some(key_id) = key_id {
+        if let Err(retry_after) =
+            limiter.check(&format!("key:{}", key_id), limiter.config().per_key)
+        {
+            return too_many_requests(retry_after);
+        }
+    }
+    next.run(req).await
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, seconds.max(1).to_string())],
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sliding_window() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            per_ip: 2,
+            per_key: 0,
+            window_secs: 10,
+        });
+        let start = Instant::now();
+
+        assert!(limiter.check_at("ip:1", 2, start).is_ok());
+        assert!(limiter
+            .check_at("ip:1", 2, start + Duration::from_secs(4))
+            .is_ok());
+        assert_eq!(
+            limiter.check_at("ip:1", 2, start + Duration::from_secs(6)),
+            Err(Duration::from_secs(4))
+        );
+        // Other clients and disabled limits are not affected
+        assert!(limiter.check_at("ip:2", 2, start).is_ok());
+        assert!(limiter.check_at("key:a", 0, start).is_ok());
+
+        // The first request leaves the window, making room for one more
+        let later = start + Duration::from_secs(10);
+        assert!(limiter.check_at("ip:1", 2, later).is_ok());
+        assert!(limiter.check_at("ip:1", 2, later).is_err());
+
+        let violations = limiter.violations();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].client, "ip:1");
+        assert_eq!(violations[0].rejected, 2);
+    }
+}
@@ -0,0 +1,221 @@
+//! User Scoping
+//!
+//! Requests made with a key that belongs to a user only reach that user's
+//! sessions and projects: a route naming a project or session, or a message,
+//! task or tool call of a session, answers 404 unless it is the user's, and
+//! the routes that administer the server answer 403. Keys without a user are the machine
+//! owner's and reach everything, like the desktop app.
+
+use axum::async_trait;
+use axum::extract::{FromRequestParts, MatchedPath, RawPathParams, Request, State};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::convert::Infallible;
+
+use crate::security::AuthenticatedKey;
+use crate::server::state::ServerState;
+use crate::storage::SessionId;
+
+/// Routes administering the server, closed to keys of users
+pub(crate) const ADMIN_ROUTES: [&str; 9] = [
+    "/v1/admin",
+    "/v1/pairings",
+    "/v1/integrations",
+    "/v1/api-keys",
+    "/v1/users",
+    "/v1/stats",
+    "/v1/streaming",
+    "/v1/rate-limits",
+    "/v1/retention-policy",
+];
+
+/// User a request is scoped to; `None` when it reaches every user's sessions
+#[derive(Debug, Clone, Default)]
+pub struct RequestUser(pub Option<String>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestUser {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(
+            parts
+                .extensions
+                .get::<AuthenticatedKey>()
+                .and_then(|key| key.user_id.clone()),
+        ))
+    }
+}
+
+impl RequestUser {
+    /// Whether the request may reach a session
+    pub async fn can_access(&self, state: &ServerState, session_id: &str) -> Result<bool, String> {
+        let Some(user_id) = &self.0 else {
+            return Ok(true);
+        };
+        Ok(state
+            .storage()
+            .chat_history
+            .get_session(session_id)
+            .await?
+            .is_some_and(|session| session.user_id.as_ref() == Some(user_id)))
+    }
+
+    /// Whether the request may reach a project
+    pub async fn can_access_project(
+        &self,
+        state: &ServerState,
+        project_id: &str,
+    ) -> Result<bool, String> {
+        let Some(user_id) = &self.0 else {
+            return Ok(true);
+        };
+        Ok(state
+            .storage()
+            .projects
+            .get_project(project_id)
+            .await?
+            .is_some_and(|project| project.user_id.as_ref() == Some(user_id)))
+    }
+}
+
+/// Kind of the resource a route names by its first path parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resource {
+    Project,
+    Session,
+    Message,
+    Task,
+    ToolCall,
+}
+
+impl Resource {
+    /// Resource named by a route, such as `/v1/tasks/:id/worktree`
+    fn of_route(route: &str) -> Option<Self> {
+        let mut segments = route.strip_prefix("/v1/")?.split('/');
+        let kind = match segments.next()? {
+            "projects" => Resource::Project,
+            "sessions" => Resource::Session,
+            "messages" => Resource::Message,
+            "tasks" => Resource::Task,
+            "tool-calls" => Resource::ToolCall,
+            _ => return None,
+        };
+        segments
+            .next()
+            .is_some_and(|segment| segment.starts_with(':'))
+            .then_some(kind)
+    }
+
+    /// Whether a request may reach the resource with an ID
+    async fn can_access(
+        &self,
+        state: &ServerState,
+        user: &RequestUser,
+        id: &str,
+    ) -> Result<bool, String> {
+        if *self == Resource::Project {
+            return user.can_access_project(state, id).await;
+        }
+        match self.session(state, id).await? {
+            Some(session_id) => user.can_access(state, &session_id).await,
+            None => Ok(false),
+        }
+    }
+
+    /// Session a resource belongs to, if it exists
+    async fn session(&self, state: &ServerState, id: &str) -> Result<Option<SessionId>, String> {
+        let chat_history = &state.storage().chat_history;
+        match self {
+            Resource::Project => Ok(None),
+            Resource::Session => Ok(Some(id.to_string())),
+            Resource::Message => Ok(chat_history.get_message(id).await?.map(|m| m.session_id)),
+            Resource::Task => match state.runtime().get_task(id).await {
+                Some(handle) => Ok(Some(handle.session_id)),
+                None => Ok(chat_history
+                    .get_task_worktree(id)
+                    .await?
+                    .map(|worktree| worktree.session_id)),
+            },
+            Resource::ToolCall => match chat_history.get_pending_approval(id).await? {
+                Some(approval) => Ok(Some(approval.session_id)),
+                None => chat_history.get_tool_call_session(id).await,
+            },
+        }
+    }
+}
+
+/// Keep requests of users to their own sessions; runs after authentication
+pub async fn user_scope_middleware(
+    State(state): State<ServerState>,
+    user: RequestUser,
+    route: Option<MatchedPath>,
+    params: Option<RawPathParams>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if user.0.is_none() {
+        return next.run(req).await;
+    }
+    let route = route.as_ref().map(MatchedPath::as_str).unwrap_or_default();
+    if ADMIN_ROUTES.iter().any(|prefix| route.starts_with(prefix)) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let resource = Resource::of_route(route).zip(
+        params
+            .as_ref()
+            .and_then(|params| params.iter().next())
+            .map(|(_, value)| value.to_string()),
+    );
+    if let Some((resource, id)) = resource {
+        match resource.can_access(&state, &user, &id).await {
+            Ok(true) => {}
+            Ok(false) => return StatusCode::NOT_FOUND.into_response(),
+            Err(e) => {
+                log::error!("Failed to check access to {}: {}", route, e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resource_of_route() {
+        assert_eq!(
+            Resource::of_route("/v1/sessions/:id/messages"),
+            Some(Resource::Session)
+        );
+        assert_eq!(
+            Resource::of_route("/v1/sessions/:session_id/files/:file_id"),
+            Some(Resource::Session)
+        );
+        assert_eq!(
+            Resource::of_route("/v1/tasks/:id/worktree/merge"),
+            Some(Resource::Task)
+        );
+        assert_eq!(
+            Resource::of_route("/v1/tool-calls/:id/approve"),
+            Some(Resource::ToolCall)
+        );
+        assert_eq!(
+            Resource::of_route("/v1/messages/:id"),
+            Some(Resource::Message)
+        );
+        assert_eq!(
+            Resource::of_route("/v1/projects/:id"),
+            Some(Resource::Project)
+        );
+        assert_eq!(Resource::of_route("/v1/projects"), None);
+        assert_eq!(Resource::of_route("/v1/sessions"), None);
+        assert_eq!(Resource::of_route("/v1/sessions/search"), None);
+        assert_eq!(Resource::of_route("/v1/queue"), None);
+    }
+}
@@ -0,0 +1,55 @@
+//! Tauri commands for the embedded server's bind and web UI settings
+
+use std::net::SocketAddr;
+use tauri::{AppHandle, Manager};
+
+use crate::server::config::{BindSettings, WebUiSettings};
+use crate::server::ServerManager;
+
+fn manager(app: &AppHandle) -> Result<tauri::State<'_, ServerManager>, String> {
+    app.try_state::<ServerManager>()
+        .ok_or_else(|| "Cloud backend server is not ready".to_string())
+}
+
+/// Where the server listens, as stored in the settings store
+#[tauri::command]
+pub async fn get_server_bind_settings(app: AppHandle) -> Result<BindSettings, String> {
+    manager(&app)?.bind_settings().await
+}
+
+/// Store where the server listens; `restart_server` applies the settings
+#[tauri::command]
+pub async fn set_server_bind_settings(
+    app: AppHandle,
+    settings: BindSettings,
+) -> Result<(), String> {
+    manager(&app)?.set_bind_settings(&settings).await
+}
+
+/// Web UI the server serves, as stored in the settings store
+#[tauri::command]
+pub async fn get_server_web_ui_settings(app: AppHandle) -> Result<WebUiSettings, String> {
+    manager(&app)?.web_ui_settings().await
+}
+
+/// Store the web UI the server serves; `restart_server` applies the settings
+#[tauri::command]
+pub async fn set_server_web_ui_settings(
+    app: AppHandle,
+    settings: WebUiSettings,
+) -> Result<(), String> {
+    manager(&app)?.set_web_ui_settings(&settings).await
+}
+
+/// Address the server listens on, or None when it is not running
+#[tauri::command]
+pub async fn get_server_address(app: AppHandle) -> Result<Option<SocketAddr>, String> {
+    Ok(manager(&app)?.addr().await)
+}
+
+/// Listen again per the stored settings, returning the new address, or
+/// None when they disable the server
+#[tauri::command]
+pub async fn restart_server(app: AppHandle) -> Result<Option<SocketAddr>, String> {
+    manager(&app)?.restart().await
+}
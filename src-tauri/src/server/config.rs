@@ -1,10 +1,36 @@
+use crate::core::scheduler::DEFAULT_MAX_CONCURRENT_TASKS;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::PathBuf;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+/// Time a shutdown waits for running tasks to reach a checkpoint
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Settings key of the server's `BindSettings`
+pub const BIND_SETTINGS_KEY: &str = "server_bind";
+
+/// Settings key of the server's `WebUiSettings`
+pub const WEB_UI_SETTINGS_KEY: &str = "server_web_ui";
 
 #[derive(Clone, Debug)]
 pub struct ServerConfig {
     pub workspace_root: PathBuf,
     pub data_root: PathBuf,
     pub attachments_root: PathBuf,
+    /// Tasks that run at once; further tasks wait in the queue
+    pub max_concurrent_tasks: usize,
+    /// Time a shutdown waits for running tasks to reach a checkpoint
+    pub shutdown_timeout: Duration,
+    /// Where the server listens when the settings store has no bind settings
+    pub bind: BindSettings,
+    /// Web UI served when the settings store has no web UI settings
+    pub web_ui: WebUiSettings,
+    /// Unix socket, or named pipe on Windows, the server also listens on for
+    /// local CLI tools and editor plugins
+    pub local_socket: Option<PathBuf>,
 }
 
 impl ServerConfig {
@@ -14,6 +40,192 @@ impl ServerConfig {
             workspace_root,
             data_root,
             attachments_root,
+            max_concurrent_tasks: DEFAULT_MAX_CONCURRENT_TASKS,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            bind: BindSettings::default(),
+            web_ui: WebUiSettings::default(),
+            local_socket: None,
+        }
+    }
+}
+
+/// Port the server listens on
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum PortSelection {
+    /// Any free port, picked by the OS
+    Auto,
+    /// This port; binding fails when it is taken
+    Fixed { port: u16 },
+    /// The first free port from `start` to `end`, inclusive
+    Range { start: u16, end: u16 },
+}
+
+/// Where the server listens, kept in the settings store
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BindSettings {
+    /// Start the server with the app
+    pub enabled: bool,
+    /// Address to listen on. Any but a loopback address lets the LAN reach
+    /// the server, which is then advertised over mDNS for pairing.
+    pub address: IpAddr,
+    pub port: PortSelection,
+    /// CIDR ranges, such as `192.168.1.0/24`, of the clients on other
+    /// machines allowed to reach a LAN-facing server; with none, only this
+    /// machine reaches it
+    pub allowed_networks: Vec<IpNet>,
+}
+
+impl Default for BindSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: PortSelection::Auto,
+            allowed_networks: vec![],
+        }
+    }
+}
+
+impl BindSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.port {
+            PortSelection::Auto => Ok(()),
+            PortSelection::Fixed { port: 0 } => {
+                Err("Fixed port must not be 0; use automatic selection".to_string())
+            }
+            PortSelection::Fixed { .. } => Ok(()),
+            PortSelection::Range { start, end } if start == 0 || start > end => Err(format!(
+                "Invalid port range {}-{}: it must be ascending and start above 0",
+                start, end
+            )),
+            PortSelection::Range { .. } => Ok(()),
+        }
+    }
+
+    /// Whether clients on other machines can reach the server
+    pub fn lan_access(&self) -> bool {
+        !self.address.is_loopback()
+    }
+
+    /// Whether a client at `ip` may make requests: loopback clients always
+    /// may, others only from the allowed networks of a LAN-facing server
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        // IPv4 clients of a server listening on `::` arrive as mapped addresses
+        let ip = ip.to_canonical();
+        ip.is_loopback()
+            || (self.lan_access()
+                && self
+                    .allowed_networks
+                    .iter()
+                    .any(|network| network.contains(&ip)))
+    }
+
+    /// Bind a listener on the address and the first free port the settings
+    /// allow
+    pub async fn bind(&self) -> Result<TcpListener, String> {
+        self.validate()?;
+        let ports = match self.port {
+            PortSelection::Auto => 0..=0,
+            PortSelection::Fixed { port } => port..=port,
+            PortSelection::Range { start, end } => start..=end,
+        };
+
+        let mut last_error = None;
+        for port in ports {
+            match TcpListener::bind((self.address, port)).await {
+                Ok(listener) => return Ok(listener),
+                Err(e) => last_error = Some(e),
+            }
         }
+        Err(format!(
+            "Failed to bind server on {}: {}",
+            self.address,
+            last_error.map(|e| e.to_string()).unwrap_or_default()
+        ))
+    }
+}
+
+/// Web frontend the server serves to browsers, kept in the settings store
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WebUiSettings {
+    pub enabled: bool,
+    /// Directory of a built frontend, served instead of the bundled one
+    pub directory: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_settings() {
+        let settings: BindSettings = serde_json::from_value(serde_json::json!({
+            "port": { "mode": "fixed", "port": 0 }
+        }))
+        .unwrap();
+        assert!(settings.enabled);
+        assert!(!settings.lan_access());
+        assert!(settings.validate().is_err());
+
+        let taken = BindSettings::default().bind().await.unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let fixed = BindSettings {
+            port: PortSelection::Fixed { port },
+            ..BindSettings::default()
+        };
+        assert!(fixed.bind().await.is_err());
+
+        // A range skips the ports that are taken
+        let range = BindSettings {
+            port: PortSelection::Range {
+                start: port,
+                end: port.saturating_add(20),
+            },
+            ..BindSettings::default()
+        };
+        let listener = range.bind().await.unwrap();
+        assert_ne!(listener.local_addr().unwrap().port(), port);
+
+        let reversed = BindSettings {
+            port: PortSelection::Range {
+                start: 9000,
+                end: 8000,
+            },
+            ..BindSettings::default()
+        };
+        assert!(reversed.bind().await.is_err());
+    }
+
+    #[test]
+    fn test_allowed_networks() {
+        let loopback = BindSettings::default();
+        assert!(loopback.allows("127.0.0.1".parse().unwrap()));
+        assert!(loopback.allows("::1".parse().unwrap()));
+        assert!(!loopback.allows("192.168.1.20".parse().unwrap()));
+
+        let mut lan: BindSettings = serde_json::from_value(serde_json::json!({
+            "address": "::",
+            "allowedNetworks": ["192.168.1.0/24", "fd00::/8"]
+        }))
+        .unwrap();
+        assert!(lan.allows("192.168.1.20".parse().unwrap()));
+        assert!(lan.allows("::ffff:192.168.1.20".parse().unwrap()));
+        assert!(lan.allows("fd12::1".parse().unwrap()));
+        assert!(lan.allows("::ffff:127.0.0.1".parse().unwrap()));
+        assert!(!lan.allows("192.168.2.20".parse().unwrap()));
+        assert!(!lan.allows("10.0.0.5".parse().unwrap()));
+
+        // Without allowed networks only this machine reaches the server
+        lan.allowed_networks.clear();
+        assert!(!lan.allows("192.168.1.20".parse().unwrap()));
+        assert!(lan.allows("127.0.0.1".parse().unwrap()));
+
+        assert!(serde_json::from_value::<BindSettings>(serde_json::json!({
+            "allowedNetworks": ["192.168.1.0/33"]
+        }))
+        .is_err());
     }
 }
@@ -0,0 +1,109 @@
+//! LAN Discovery
+//!
+//! Advertises the server over mDNS/Bonjour so companion clients on the LAN
+//! find it without configuration, and builds the URI and QR code a client
+//! pairs with.
+
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use std::net::IpAddr;
+
+/// mDNS service type the server is advertised under
+pub const SERVICE_TYPE: &str = "_talkcody._tcp.local.";
+
+/// Scheme of pairing URIs, opened by the companion client
+const PAIRING_URI: &str = "talkcody://pair";
+
+/// The server's mDNS registration; unregistered by `stop`
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl Advertisement {
+    /// Advertise the server listening on `port` on every LAN interface
+    pub fn start(port: u16) -> Result<Self, String> {
+        let daemon =
+            ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS daemon: {}", e))?;
+        let hostname = tauri_plugin_os::hostname();
+        let instance = format!("TalkCody on {}", hostname);
+        let host = format!(
+            "{}.local.",
+            hostname.replace(|c: char| !c.is_ascii_alphanumeric(), "-")
+        );
+        let properties = [("version", env!("CARGO_PKG_VERSION")), ("api", "v1")];
+
+        let service = ServiceInfo::new(SERVICE_TYPE, &instance, &host, "", port, &properties[..])
+            .map_err(|e| format!("Invalid mDNS service: {}", e))?
+            .enable_addr_auto();
+        let fullname = service.get_fullname().to_string();
+        daemon
+            .register(service)
+            .map_err(|e| format!("Failed to register mDNS service: {}", e))?;
+
+        log::info!("Advertising {} on port {}", fullname, port);
+        Ok(Self { daemon, fullname })
+    }
+
+    /// Withdraw the advertisement and stop the daemon
+    pub fn stop(self) {
+        if let Err(e) = self.daemon.unregister(&self.fullname) {
+            log::warn!("Failed to unregister mDNS service: {}", e);
+        }
+        if let Err(e) = self.daemon.shutdown() {
+            log::warn!("Failed to stop mDNS daemon: {}", e);
+        }
+    }
+}
+
+/// IPv4 addresses of the machine's non-loopback interfaces
+pub fn lan_addresses() -> Vec<IpAddr> {
+    match if_addrs::get_if_addrs() {
+        Ok(interfaces) => interfaces
+            .into_iter()
+            .filter(|interface| !interface.is_loopback())
+            .map(|interface| interface.ip())
+            .filter(IpAddr::is_ipv4)
+            .collect(),
+        Err(e) => {
+            log::warn!("Failed to list network interfaces: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// URI a companion client pairs with, reaching `host:port` with `code`
+pub fn pairing_uri(host: IpAddr, port: u16, code: &str) -> String {
+    url::Url::parse_with_params(
+        PAIRING_URI,
+        &[
+            ("host", host.to_string()),
+            ("port", port.to_string()),
+            ("code", code.to_string()),
+        ],
+    )
+    .map(String::from)
+    .unwrap_or_default()
+}
+
+/// QR code of `data` as an SVG document
+pub fn qr_svg(data: &str) -> Result<String, String> {
+    let code =
+        QrCode::new(data.as_bytes()).map_err(|e| format!("Failed to encode QR code: {}", e))?;
+    Ok(code.render::<svg::Color>().min_dimensions(256, 256).build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pairing_uri() {
+        let uri = pairing_uri("192.168.1.20".parse().unwrap(), 4321, "ABCD2345");
+        assert_eq!(
+            uri,
+            "talkcody://pair?host=192.168.1.20&port=4321&code=ABCD2345"
+        );
+    }
+}
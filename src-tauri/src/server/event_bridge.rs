@@ -0,0 +1,50 @@
+//! Runtime Event Bridge
+//!
+//! Publishes the runtime's events to SSE clients: tokens, final messages,
+//! tool calls and results, usage, task status and errors of a session become
+//! its streaming events, numbered by the event buffer so a client resumes
+//! from the last one it received.
+
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+
+use crate::core::event_log::LoggedEvent;
+use crate::core::types::RuntimeEvent;
+use crate::streaming::{StreamingEvent, StreamingManager};
+
+/// Publish logged runtime events to stream clients until the runtime is gone
+pub fn spawn(
+    mut events: broadcast::Receiver<LoggedEvent>,
+    streaming: Arc<RwLock<StreamingManager>>,
+) {
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(logged) => publish(&streaming, logged).await,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("Event stream missed {} runtime events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+async fn publish(streaming: &RwLock<StreamingManager>, logged: LoggedEvent) {
+    let Some(session_id) = logged.session_id else {
+        return;
+    };
+    let streaming = streaming.read().await;
+    if let Some(event) = StreamingEvent::from_runtime_event(&logged.event, &session_id) {
+        if let Err(e) = streaming.publish(event).await {
+            log::warn!("Failed to publish event of session {}: {}", session_id, e);
+        }
+    }
+
+    // Nothing follows a task's end to release the events held back for it
+    if matches!(logged.event, RuntimeEvent::TaskCompleted { .. }) {
+        if let Err(e) = streaming.flush(&session_id).await {
+            log::warn!("Failed to flush events of session {}: {}", session_id, e);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Idempotency Keys
+//!
+//! Requests that create sessions or start tasks may carry an
+//! `Idempotency-Key` header. The first response to a key is stored along with
+//! a fingerprint of the request; retries with the same key and request are
+//! answered with it instead of running again, so a flaky mobile network does
+//! not create duplicate sessions or pay for duplicate LLM calls. Reusing a key
+//! for a different request is refused.
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::security::AuthenticatedKey;
+use crate::server::state::ServerState;
+use crate::storage::IdempotentResponse;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Header set on responses replayed for a retry
+const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Time a stored response is replayed for, in seconds
+const IDEMPOTENCY_TTL_SECS: i64 = 24 * 60 * 60;
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Largest request or response body buffered, axum's default body limit
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Keys of requests being handled, so a retry racing its original request is
+/// refused instead of running alongside it
+#[derive(Default)]
+pub struct IdempotencyKeys {
+    in_flight: Mutex<HashSet<(String, String)>>,
+}
+
+impl IdempotencyKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark a client's key as being handled until the claim is dropped;
+    /// `None` when it already is
+    fn claim(&self, scope: &str, key: &str) -> Option<Claim<'_>> {
+        let id = (scope.to_string(), key.to_string());
+        if !self.lock().insert(id.clone()) {
+            return None;
+        }
+        Some(Claim { keys: self, id })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashSet<(String, String)>> {
+        self.in_flight.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+struct Claim<'a> {
+    keys: &'a IdempotencyKeys,
+    id: (String, String),
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        self.keys.lock().remove(&self.id);
+    }
+}
+
+/// Replay the stored response to a request's idempotency key, or handle the
+/// request and store its response. Runs after authentication, so keys are
+/// scoped to the API key that sent them.
+pub async fn idempotency_middleware(
+    State(state): State<ServerState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = req
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+    else {
+        return next.run(req).await;
+    };
+    if key.len() > MAX_KEY_LENGTH {
+        return (StatusCode::BAD_REQUEST, "Idempotency key is too long").into_response();
+    }
+    let scope = req
+        .extensions()
+        .get::<AuthenticatedKey>()
+        .map(|key| key.key_id.clone())
+        .unwrap_or_default();
+
+    let (parts, body) = req.into_parts();
+    let Ok(body) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    let fingerprint = fingerprint(&parts.method, parts.uri.path(), &body);
+
+    let Some(_claim) = state.idempotency_keys().claim(&scope, &key) else {
+        return (
+            StatusCode::CONFLICT,
+            "A request with this idempotency key is in progress",
+        )
+            .into_response();
+    };
+
+    let repository = &state.storage().idempotency;
+    let now = chrono::Utc::now().timestamp();
+    match repository.get_response(&scope, &key).await {
+        Ok(Some(stored)) if stored.created_at > now - IDEMPOTENCY_TTL_SECS => {
+            if stored.fingerprint != fingerprint {
+                return (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "Idempotency key was used for a different request",
+                )
+                    .into_response();
+            }
+            return replay(stored);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Failed to look up idempotency key: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let (parts, body) = response.into_parts();
+    let Ok(body) = to_bytes(body, MAX_BODY_BYTES).await else {
+        log::error!(
+            "Response to idempotent request {} is too large to store",
+            key
+        );
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // Server errors may be transient; retries of them run again
+    if !parts.status.is_server_error() {
+        if let Ok(text) = std::str::from_utf8(&body) {
+            let stored = IdempotentResponse {
+                scope,
+                key,
+                fingerprint,
+                status: parts.status.as_u16(),
+                body: text.to_string(),
+                created_at: now,
+            };
+            if let Err(e) = repository.save_response(&stored).await {
+                log::error!("Failed to store idempotent response: {}", e);
+            }
+            if let Err(e) = repository
+                .delete_responses_before(now - IDEMPOTENCY_TTL_SECS)
+                .await
+            {
+                log::warn!("Failed to delete expired idempotent responses: {}", e);
+            }
+        }
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Hash of a request's method, path and body
+fn fingerprint(method: &Method, path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str().as_bytes());
+    hasher.update(b" ");
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+fn replay(stored: IdempotentResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    (
+        status,
+        [
+            (CONTENT_TYPE.as_str(), "application/json"),
+            (REPLAYED_HEADER, "true"),
+        ],
+        stored.body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idempotency_keys() {
+        let a = fingerprint(&Method::POST, "/v1/sessions", b"{}");
+        assert_eq!(a, fingerprint(&Method::POST, "/v1/sessions", b"{}"));
+        assert_ne!(a, fingerprint(&Method::POST, "/v1/tasks", b"{}"));
+        assert_ne!(a, fingerprint(&Method::POST, "/v1/sessions", b"{ }"));
+
+        let keys = IdempotencyKeys::new();
+        let claim = keys.claim("key-1", "retry-1");
+        assert!(claim.is_some());
+        assert!(keys.claim("key-1", "retry-1").is_none());
+        assert!(keys.claim("key-2", "retry-1").is_some());
+
+        // Dropping the claim frees the key for the next retry
+        drop(claim);
+        assert!(keys.claim("key-1", "retry-1").is_some());
+    }
+}
@@ -0,0 +1,182 @@
+//! Local Socket Listener
+//!
+//! Serves the API on a Unix domain socket, or a named pipe on Windows, next
+//! to the TCP port, so CLI tools and editor plugins on the machine reach the
+//! backend without a network port. Requests still need credentials.
+
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::core::cancellation::CancellationToken;
+
+/// Pause after a failed accept, so a persistent error does not spin
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+#[cfg(unix)]
+type LocalStream = tokio::net::UnixStream;
+#[cfg(windows)]
+type LocalStream = tokio::net::windows::named_pipe::NamedPipeServer;
+
+/// A bound Unix socket or named pipe
+pub struct LocalListener {
+    path: PathBuf,
+    #[cfg(unix)]
+    listener: tokio::net::UnixListener,
+    /// Pipe instance the next client connects to
+    #[cfg(windows)]
+    next: tokio::net::windows::named_pipe::NamedPipeServer,
+}
+
+impl LocalListener {
+    /// Listen on the Unix socket at `path`, replacing a stale one; only the
+    /// current user may connect
+    #[cfg(unix)]
+    pub fn bind(path: &Path) -> Result<Self, String> {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(format!("Socket already in use: {}", path.display()));
+        }
+        if path.exists() {
+            std::fs::remove_file(path)
+                .map_err(|e| format!("Failed to remove stale socket: {}", e))?;
+        }
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create socket directory: {}", e))?;
+
+        // Bind in a directory only the current user can enter, and move the
+        // socket into place once it is restricted, so nobody else can
+        // connect in between
+        let private_dir = parent.join(format!(".talkcody-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&private_dir)
+            .map_err(|e| format!("Failed to create socket directory: {}", e))?;
+        let private_path = private_dir.join("socket");
+        let bound = tokio::net::UnixListener::bind(&private_path)
+            .map_err(|e| format!("Failed to bind socket {}: {}", path.display(), e))
+            .and_then(|listener| {
+                std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(0o600))
+                    .map_err(|e| format!("Failed to restrict socket permissions: {}", e))?;
+                std::fs::rename(&private_path, path)
+                    .map_err(|e| format!("Failed to bind socket {}: {}", path.display(), e))?;
+                Ok(listener)
+            });
+        let _ = std::fs::remove_file(&private_path);
+        let _ = std::fs::remove_dir(&private_dir);
+        let listener = bound?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            listener,
+        })
+    }
+
+    /// Listen on the named pipe `path`, such as `\\.\pipe\talkcody`
+    #[cfg(windows)]
+    pub fn bind(path: &Path) -> Result<Self, String> {
+        let next = tokio::net::windows::named_pipe::ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(path)
+            .map_err(|e| format!("Failed to create pipe {}: {}", path.display(), e))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            next,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[cfg(unix)]
+    async fn accept(&mut self) -> std::io::Result<LocalStream> {
+        let (stream, _) = self.listener.accept().await?;
+        Ok(stream)
+    }
+
+    /// Wait for a client on the pending pipe instance, and create the one the
+    /// next client connects to
+    #[cfg(windows)]
+    async fn accept(&mut self) -> std::io::Result<LocalStream> {
+        self.next.connect().await?;
+        let next = tokio::net::windows::named_pipe::ServerOptions::new().create(&self.path)?;
+        Ok(std::mem::replace(&mut self.next, next))
+    }
+
+    /// Serve `app` until `shutdown` is cancelled, then wait for open
+    /// connections to finish their requests
+    pub async fn serve(mut self, app: Router, shutdown: CancellationToken) {
+        log::info!("Cloud backend server listening on {}", self.path.display());
+
+        let graceful = GracefulShutdown::new();
+        loop {
+            let stream = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                stream = self.accept() => stream,
+            };
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("Failed to accept local connection: {}", e);
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                    continue;
+                }
+            };
+
+            let connection = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(
+                    TokioIo::new(stream),
+                    TowerToHyperService::new(app.clone()),
+                )
+                .into_owned();
+            let connection = graceful.watch(connection);
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    log::debug!("Local connection closed with error: {}", e);
+                }
+            });
+        }
+
+        graceful.shutdown().await;
+    }
+}
+
+#[cfg(unix)]
+impl Drop for LocalListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[tokio::test]
+    async fn test_socket_is_private_once_bound() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("server.sock");
+
+        let listener = LocalListener::bind(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // The directory it was bound in is gone
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        assert!(tokio::net::UnixStream::connect(&path).await.is_ok());
+
+        drop(listener);
+        assert!(!path.exists());
+    }
+}
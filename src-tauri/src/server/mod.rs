@@ -1,51 +1,364 @@
+pub mod commands;
 pub mod config;
+pub mod discovery;
+pub mod event_bridge;
+pub mod idempotency;
+pub mod local_socket;
 pub mod routes;
 pub mod state;
 pub mod types;
+pub mod web_ui;
 
 use std::net::SocketAddr;
-use tokio::net::TcpListener;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 
+use crate::core::cancellation::CancellationToken;
 use crate::core::types::EventSender;
-use crate::security::api_key_middleware;
+use crate::core::LlmClient;
+use crate::security::auth_middleware;
+use crate::security::network::ip_allowlist_middleware;
+use crate::security::permissions::permission_middleware;
+use crate::security::rate_limit::{ip_rate_limit_middleware, key_rate_limit_middleware};
+use crate::security::scope::user_scope_middleware;
+use crate::server::config::{
+    BindSettings, WebUiSettings, BIND_SETTINGS_KEY, WEB_UI_SETTINGS_KEY,
+};
+use crate::server::discovery::Advertisement;
+use crate::server::local_socket::LocalListener;
 use crate::server::state::ServerStateFactory;
 
 pub use config::ServerConfig;
 pub use state::ServerState;
 
+/// A running listener of the server
 pub struct ServerHandle {
     pub addr: SocketAddr,
+    shutdown: CancellationToken,
+    /// Cancelled when the listener stops for a restart, which leaves the
+    /// runtime running
+    restarting: CancellationToken,
+    server: JoinHandle<()>,
 }
 
+impl ServerHandle {
+    /// Shut the server down gracefully and wait until it has stopped
+    pub async fn shutdown(self) {
+        self.shutdown.cancel();
+        self.join().await;
+    }
+
+    /// Stop listening, leaving the runtime and its tasks running
+    async fn stop_listening(self) {
+        self.restarting.cancel();
+        self.shutdown.cancel();
+        self.join().await;
+    }
+
+    async fn join(self) {
+        if let Err(e) = self.server.await {
+            log::error!("Cloud backend server task failed: {}", e);
+        }
+    }
+}
+
+/// The server, listening per the bind settings in the settings store and
+/// restarted when they change
+pub struct ServerManager {
+    state: ServerState,
+    handle: Mutex<Option<ServerHandle>>,
+}
+
+impl ServerManager {
+    pub fn new(state: ServerState) -> Self {
+        Self {
+            state,
+            handle: Mutex::new(None),
+        }
+    }
+
+    pub fn state(&self) -> &ServerState {
+        &self.state
+    }
+
+    /// Bind settings in the settings store, or the configured ones
+    pub async fn bind_settings(&self) -> Result<BindSettings, String> {
+        self.state
+            .storage()
+            .settings
+            .get_setting_or_default(BIND_SETTINGS_KEY, self.state.config.bind.clone())
+            .await
+    }
+
+    /// Validate and store bind settings; a restart applies them
+    pub async fn set_bind_settings(&self, settings: &BindSettings) -> Result<(), String> {
+        settings.validate()?;
+        let value = serde_json::to_value(settings)
+            .map_err(|e| format!("Failed to serialize bind settings: {}", e))?;
+        self.state
+            .storage()
+            .settings
+            .set_setting(BIND_SETTINGS_KEY, &value)
+            .await
+    }
+
+    /// Web UI settings in the settings store, or the configured ones
+    pub async fn web_ui_settings(&self) -> Result<WebUiSettings, String> {
+        self.state
+            .storage()
+            .settings
+            .get_setting_or_default(WEB_UI_SETTINGS_KEY, self.state.config.web_ui.clone())
+            .await
+    }
+
+    /// Validate and store web UI settings; a restart applies them
+    pub async fn set_web_ui_settings(&self, settings: &WebUiSettings) -> Result<(), String> {
+        if let Some(directory) = &settings.directory {
+            web_ui::validate_directory(directory)?;
+        }
+        let value = serde_json::to_value(settings)
+            .map_err(|e| format!("Failed to serialize web UI settings: {}", e))?;
+        self.state
+            .storage()
+            .settings
+            .set_setting(WEB_UI_SETTINGS_KEY, &value)
+            .await
+    }
+
+    /// Address the server listens on, if it is running
+    pub async fn addr(&self) -> Option<SocketAddr> {
+        self.handle.lock().await.as_ref().map(|handle| handle.addr)
+    }
+
+    /// Start listening per the stored bind settings, unless they disable the
+    /// server
+    pub async fn start(&self) -> Result<Option<SocketAddr>, String> {
+        self.restart().await
+    }
+
+    /// Stop listening and listen again per the stored bind settings; the
+    /// runtime and its tasks keep running. Returns the new address, or None
+    /// when the settings disable the server.
+    pub async fn restart(&self) -> Result<Option<SocketAddr>, String> {
+        let mut handle = self.handle.lock().await;
+        if let Some(running) = handle.take() {
+            running.stop_listening().await;
+        }
+
+        let bind = self.bind_settings().await?;
+        if !bind.enabled {
+            log::info!("Cloud backend server is disabled");
+            return Ok(None);
+        }
+        let web_ui = self.web_ui_settings().await?;
+        let running = serve(&self.state, &bind, &web_ui).await?;
+        let addr = running.addr;
+        *handle = Some(running);
+        Ok(Some(addr))
+    }
+
+    /// Shut the server down gracefully, draining the runtime
+    pub async fn shutdown(&self) {
+        match self.handle.lock().await.take() {
+            Some(running) => running.shutdown().await,
+            None => drain(&self.state).await,
+        }
+    }
+}
+
+/// Start the server; `app` is the desktop app embedding it, if any
 pub async fn start_server(
     config: ServerConfig,
+    llm: Arc<dyn LlmClient>,
     event_sender: EventSender,
-) -> Result<ServerHandle, String> {
+    app: Option<tauri::AppHandle>,
+) -> Result<ServerManager, String> {
     // Create server state with all dependencies
-    let state = ServerStateFactory::create(config, event_sender)
+    let mut state = ServerStateFactory::create(config, llm, event_sender)
         .await
         .map_err(|e| format!("Failed to create server state: {}", e))?;
+    if let Some(app) = app {
+        state = state.with_app(app);
+    }
 
-    // Build router with API key middleware
-    let app = routes::router(state).route_layer(axum::middleware::from_fn(api_key_middleware));
-
-    // Bind to any available port
-    let listener = TcpListener::bind(("127.0.0.1", 0))
-        .await
-        .map_err(|e| format!("Failed to bind server: {}", e))?;
+    let manager = ServerManager::new(state);
+    manager.start().await?;
+    Ok(manager)
+}
 
+/// Listen per `bind` and serve the API, and the web UI if enabled, until the
+/// returned handle, or a signal, stops it
+async fn serve(
+    state: &ServerState,
+    bind: &BindSettings,
+    web_ui: &WebUiSettings,
+) -> Result<ServerHandle, String> {
+    let listener = bind.bind().await?;
     let addr = listener
         .local_addr()
         .map_err(|e| format!("Failed to read server address: {}", e))?;
 
+    // Each listener has its own shutdown token, so a restart ends the streams
+    // of its clients
+    let mut state = state.clone().with_addr(addr);
+    state.shutdown = CancellationToken::new();
+    state.config.bind = bind.clone();
+    let local_listener = match &state.config.local_socket {
+        Some(path) => Some(LocalListener::bind(path)?),
+        None => None,
+    };
+
+    // Build router with auth middleware; signing in needs no credentials.
+    // Requests are rate limited per IP before authentication and per API key
+    // after it, need the scope of their route, and keys of users are kept to
+    // their own sessions.
+    let app = routes::router(state.clone())
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            user_scope_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn(permission_middleware))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            key_rate_limit_middleware,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth_middleware,
+        ))
+        .merge(routes::public_router(state.clone()))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            ip_rate_limit_middleware,
+        ));
+    // Static files need no credentials; a broken web UI leaves the API up
+    let app = match web_ui::with_web_ui(app.clone(), web_ui, state.app()) {
+        Ok(app) => app,
+        Err(e) => {
+            log::warn!("Failed to serve web UI: {}", e);
+            app
+        }
+    };
+    // Clients outside the allowed networks reach neither the API nor the UI
+    let app = app.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        ip_allowlist_middleware,
+    ));
+    if bind.lan_access() && bind.allowed_networks.is_empty() {
+        log::warn!(
+            "Server listens on {} but allows no networks; only this machine can reach it",
+            bind.address
+        );
+    }
+
     log::info!("Cloud backend server starting on {}", addr);
 
-    // Spawn server
-    tokio::spawn(async move {
-        if let Err(error) = axum::serve(listener, app).await {
-            log::error!("Cloud backend server error: {}", error);
+    // Companion clients find the server on the LAN; it serves local clients
+    // if advertising fails
+    let advertisement = if bind.lan_access() {
+        Advertisement::start(addr.port())
+            .map_err(|e| log::warn!("Failed to advertise server: {}", e))
+            .ok()
+    } else {
+        None
+    };
+
+    let shutdown = state.shutdown_token().clone();
+    let restarting = CancellationToken::new();
+    tokio::spawn(shutdown_on_signal(shutdown.clone()));
+
+    // Spawn server. On shutdown it stops accepting connections, streams end
+    // with a shutdown event, and in-flight requests finish before it drains.
+    let stopped_for_restart = restarting.clone();
+    let server = tokio::spawn(async move {
+        let local = async {
+            if let Some(local_listener) = local_listener {
+                local_listener
+                    .serve(app.clone(), state.shutdown_token().clone())
+                    .await;
+            }
+        };
+        // Connection info gives the rate limiter each client's address
+        let tcp_app = app.clone().into_make_service_with_connect_info::<SocketAddr>();
+        let signal = state.shutdown_token().clone();
+        let tcp = async {
+            if let Err(error) = axum::serve(listener, tcp_app)
+                .with_graceful_shutdown(async move { signal.cancelled().await })
+                .await
+            {
+                log::error!("Cloud backend server error: {}", error);
+            }
+        };
+        tokio::join!(tcp, local);
+        if let Some(advertisement) = advertisement {
+            advertisement.stop();
+        }
+        if stopped_for_restart.is_cancelled() {
+            log::info!("Cloud backend server stopped listening on {}", addr);
+        } else {
+            drain(&state).await;
         }
     });
 
-    Ok(ServerHandle { addr })
+    Ok(ServerHandle {
+        addr,
+        shutdown,
+        restarting,
+        server,
+    })
+}
+
+/// Cancel `shutdown` on Ctrl-C or, on Unix, SIGTERM
+async fn shutdown_on_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            log::warn!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                log::warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = shutdown.cancelled() => return,
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    log::info!("Shutdown signal received");
+    shutdown.cancel();
+}
+
+/// Let running tasks reach a checkpoint, then store the buffered stream
+/// events so clients can resume after a restart
+async fn drain(state: &ServerState) {
+    let running = state
+        .runtime()
+        .shutdown(state.config.shutdown_timeout)
+        .await;
+    if !running.is_empty() {
+        log::warn!(
+            "Shutting down with {} tasks still running: {}",
+            running.len(),
+            running.join(", ")
+        );
+    }
+
+    match state.streaming().read().await.shutdown().await {
+        Ok(stored) => log::info!("Stored {} buffered stream events", stored),
+        Err(e) => log::error!("Failed to store buffered stream events: {}", e),
+    }
+    log::info!("Cloud backend server stopped");
 }
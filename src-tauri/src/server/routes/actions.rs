@@ -1,7 +1,7 @@
 use axum::extract::{Path, State};
 use axum::Json;
 
-use crate::core::types::{TaskAction, ToolRequest};
+use crate::core::types::TaskAction;
 use crate::server::state::ServerState;
 use crate::server::types::*;
 
@@ -11,6 +11,11 @@ pub async fn create_action(
     Path(session_id): Path<String>,
     Json(payload): Json<CreateActionRequest>,
 ) -> Result<Json<CreateActionResponse>, Json<ErrorResponse>> {
+    // Approval decisions resume the task from its stored pending approval
+    if matches!(payload.action_type.as_str(), "approve" | "reject") {
+        return apply_approval_decision(&state, &session_id, payload).await;
+    }
+
     // Find the active task for this session
     let tasks = state.runtime().list_active_tasks().await;
     let task_handle = tasks.into_iter().find(|t| t.session_id == session_id);
@@ -27,27 +32,6 @@ pub async fn create_action(
 
     // Convert action type to TaskAction
     let action = match payload.action_type.as_str() {
-        "approve" => {
-            let tool_call_id = payload.tool_call_id.ok_or_else(|| {
-                Json(ErrorResponse::new(
-                    "BAD_REQUEST",
-                    "tool_call_id required for approve action",
-                ))
-            })?;
-            TaskAction::Approve { tool_call_id }
-        }
-        "reject" => {
-            let tool_call_id = payload.tool_call_id.ok_or_else(|| {
-                Json(ErrorResponse::new(
-                    "BAD_REQUEST",
-                    "tool_call_id required for reject action",
-                ))
-            })?;
-            TaskAction::Reject {
-                tool_call_id,
-                reason: payload.reason,
-            }
-        }
         "tool_result" => {
             let tool_call_id = payload.tool_call_id.ok_or_else(|| {
                 Json(ErrorResponse::new(
@@ -87,3 +71,55 @@ pub async fn create_action(
         ))),
     }
 }
+
+/// Approve or reject a pending tool call that belongs to the session
+async fn apply_approval_decision(
+    state: &ServerState,
+    session_id: &str,
+    payload: CreateActionRequest,
+) -> Result<Json<CreateActionResponse>, Json<ErrorResponse>> {
+    let tool_call_id = payload.tool_call_id.ok_or_else(|| {
+        Json(ErrorResponse::new(
+            "BAD_REQUEST",
+            format!("tool_call_id required for {} action", payload.action_type),
+        ))
+    })?;
+
+    let pending = state
+        .runtime()
+        .list_pending_approvals(Some(session_id))
+        .await
+        .map_err(|e| Json(ErrorResponse::new("INTERNAL_ERROR", e)))?;
+    if !pending
+        .iter()
+        .any(|approval| approval.tool_call_id == tool_call_id)
+    {
+        return Err(Json(ErrorResponse::new(
+            "NOT_FOUND",
+            format!(
+                "No pending approval for tool call '{}' in session '{}'",
+                tool_call_id, session_id
+            ),
+        )));
+    }
+
+    let result = if payload.action_type == "approve" {
+        state.runtime().approve_tool_call(&tool_call_id).await
+    } else {
+        state
+            .runtime()
+            .deny_tool_call(&tool_call_id, payload.reason)
+            .await
+    };
+
+    match result {
+        Ok(_) => Ok(Json(CreateActionResponse {
+            success: true,
+            message: "Action applied successfully".to_string(),
+        })),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to apply action: {}", e),
+        ))),
+    }
+}
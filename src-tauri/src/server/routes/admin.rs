@@ -0,0 +1,91 @@
+use axum::extract::{Query, State};
+use axum::Json;
+use tauri::Manager;
+
+use crate::feishu_gateway::{self, FeishuGatewayState};
+use crate::lsp::{LspServerDiagnostics, LspState};
+use crate::server::state::ServerState;
+use crate::server::types::*;
+use crate::streaming::SubscriberTransport;
+use crate::telegram_gateway::{self, TelegramGatewayState};
+
+/// Recent errors returned when the request sets no limit
+const DEFAULT_RECENT_ERRORS: usize = 20;
+
+/// Queue, LSP servers, IM gateways, stream subscribers and recent errors,
+/// for a local diagnostics page
+pub async fn get_diagnostics(
+    State(state): State<ServerState>,
+    Query(query): Query<DiagnosticsQuery>,
+) -> Json<DiagnosticsResponse> {
+    let runtime = state.runtime();
+    let mut active_tasks = Vec::new();
+    for handle in runtime.list_active_tasks().await {
+        let task_state = *handle.state.read().await;
+        active_tasks.push(ActiveTaskDiagnostics {
+            task_id: handle.task_id,
+            session_id: handle.session_id,
+            state: task_state,
+        });
+    }
+    active_tasks.sort_by(|a, b| a.task_id.cmp(&b.task_id));
+    let queue = QueueDiagnostics {
+        max_concurrent_tasks: runtime.get_runtime_stats(None).await.max_concurrent_tasks,
+        active_tasks,
+        queued_tasks: runtime.list_queued_tasks(),
+    };
+
+    let subscribers = state.streaming().read().await.get_stats().await.subscribers;
+    let count = |transport| {
+        subscribers
+            .iter()
+            .filter(|subscriber| subscriber.transport == transport)
+            .count()
+    };
+
+    Json(DiagnosticsResponse {
+        queue,
+        lsp_servers: lsp_servers(&state).await,
+        integrations: integrations(&state).await,
+        subscribers: SubscriberCounts {
+            sse: count(SubscriberTransport::Sse),
+            websocket: count(SubscriberTransport::WebSocket),
+        },
+        recent_errors: runtime.recent_errors(query.errors.unwrap_or(DEFAULT_RECENT_ERRORS)),
+    })
+}
+
+async fn lsp_servers(state: &ServerState) -> Vec<LspServerDiagnostics> {
+    let Some(lsp) = state.app().and_then(|app| app.try_state::<LspState>()) else {
+        return Vec::new();
+    };
+    let registry = lsp.0.lock().await;
+    registry.describe().await
+}
+
+async fn integrations(state: &ServerState) -> Vec<IntegrationDiagnostics> {
+    let Some(app) = state.app() else {
+        return Vec::new();
+    };
+
+    let mut integrations = Vec::new();
+    if let Some(telegram) = app.try_state::<TelegramGatewayState>() {
+        let status = telegram_gateway::gateway_status(&telegram).await;
+        integrations.push(IntegrationDiagnostics {
+            name: "telegram".to_string(),
+            running: status.running,
+            last_error: status.last_error,
+            last_error_at_ms: status.last_error_at_ms,
+        });
+    }
+    if let Some(feishu) = app.try_state::<FeishuGatewayState>() {
+        let status = feishu_gateway::gateway_status(&feishu).await;
+        integrations.push(IntegrationDiagnostics {
+            name: "feishu".to_string(),
+            running: status.running,
+            last_error: status.last_error,
+            last_error_at_ms: status.last_error_at_ms,
+        });
+    }
+    integrations
+}
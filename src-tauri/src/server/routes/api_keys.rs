@@ -0,0 +1,57 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::security::api_keys::CreatedApiKey;
+use crate::server::state::ServerState;
+use crate::server::types::*;
+use crate::storage::models::ApiKey;
+
+/// Create an API key; the full key is only returned in this response
+pub async fn create_api_key(
+    State(state): State<ServerState>,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Result<Json<CreatedApiKey>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .create_api_key(
+            &payload.name,
+            payload.user_id.as_deref(),
+            payload.scopes.as_deref(),
+        )
+        .await
+    {
+        Ok(created) => Ok(Json(created)),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
+
+/// List API keys, revoked ones included; secrets are never returned
+pub async fn list_api_keys(
+    State(state): State<ServerState>,
+) -> Result<Json<Vec<ApiKey>>, Json<ErrorResponse>> {
+    match state.runtime().list_api_keys().await {
+        Ok(keys) => Ok(Json(keys)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list API keys: {}", e),
+        ))),
+    }
+}
+
+/// Revoke an API key so requests presenting it are rejected
+pub async fn revoke_api_key(
+    State(state): State<ServerState>,
+    Path(key_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Json<ErrorResponse>> {
+    match state.runtime().revoke_api_key(&key_id).await {
+        Ok(true) => Ok(Json(serde_json::json!({ "success": true }))),
+        Ok(false) => Err(Json(ErrorResponse::new(
+            "NOT_FOUND",
+            format!("Active API key not found: {}", key_id),
+        ))),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to revoke API key: {}", e),
+        ))),
+    }
+}
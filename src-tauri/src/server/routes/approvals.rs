@@ -0,0 +1,163 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::server::state::ServerState;
+use crate::server::types::*;
+use crate::storage::models::{BudgetPause, PendingApproval, StreamState};
+
+/// List tool calls waiting for approval in a session
+pub async fn list_approvals(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<PendingApproval>>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .list_pending_approvals(Some(&session_id))
+        .await
+    {
+        Ok(approvals) => Ok(Json(approvals)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list pending approvals: {}", e),
+        ))),
+    }
+}
+
+/// Approve a pending tool call and resume its task
+pub async fn approve_tool_call(
+    State(state): State<ServerState>,
+    Path(tool_call_id): Path<String>,
+) -> Result<Json<ToolApprovalResponse>, Json<ErrorResponse>> {
+    match state.runtime().approve_tool_call(&tool_call_id).await {
+        Ok(handle) => Ok(Json(ToolApprovalResponse {
+            tool_call_id,
+            task_id: handle.task_id,
+            session_id: handle.session_id,
+            approved: true,
+        })),
+        Err(e) => Err(Json(ErrorResponse::new("NOT_FOUND", e))),
+    }
+}
+
+/// Deny a pending tool call and resume its task
+pub async fn deny_tool_call(
+    State(state): State<ServerState>,
+    Path(tool_call_id): Path<String>,
+    Json(payload): Json<DenyToolCallRequest>,
+) -> Result<Json<ToolApprovalResponse>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .deny_tool_call(&tool_call_id, payload.reason)
+        .await
+    {
+        Ok(handle) => Ok(Json(ToolApprovalResponse {
+            tool_call_id,
+            task_id: handle.task_id,
+            session_id: handle.session_id,
+            approved: false,
+        })),
+        Err(e) => Err(Json(ErrorResponse::new("NOT_FOUND", e))),
+    }
+}
+
+/// Approve or deny every tool call waiting for approval in a session, oldest
+/// first
+pub async fn resolve_approvals(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+    Json(payload): Json<ResolveApprovalsRequest>,
+) -> Result<Json<Vec<ToolApprovalResponse>>, Json<ErrorResponse>> {
+    let pending = match state
+        .runtime()
+        .list_pending_approvals(Some(&session_id))
+        .await
+    {
+        Ok(pending) => pending,
+        Err(e) => {
+            return Err(Json(ErrorResponse::new(
+                "INTERNAL_ERROR",
+                format!("Failed to list pending approvals: {}", e),
+            )))
+        }
+    };
+
+    let mut resolved = Vec::with_capacity(pending.len());
+    for approval in pending {
+        let result = if payload.approved {
+            state
+                .runtime()
+                .approve_tool_call(&approval.tool_call_id)
+                .await
+        } else {
+            state
+                .runtime()
+                .deny_tool_call(&approval.tool_call_id, payload.reason.clone())
+                .await
+        };
+        match result {
+            Ok(handle) => resolved.push(ToolApprovalResponse {
+                tool_call_id: approval.tool_call_id,
+                task_id: handle.task_id,
+                session_id: handle.session_id,
+                approved: payload.approved,
+            }),
+            Err(e) => {
+                return Err(Json(ErrorResponse::new(
+                    "INTERNAL_ERROR",
+                    format!(
+                        "Failed to resolve tool call '{}': {}",
+                        approval.tool_call_id, e
+                    ),
+                )))
+            }
+        }
+    }
+    Ok(Json(resolved))
+}
+
+/// Type into the terminal of a running shell tool call
+pub async fn write_tool_input(
+    State(state): State<ServerState>,
+    Path(tool_call_id): Path<String>,
+    Json(payload): Json<ToolInputRequest>,
+) -> Result<Json<serde_json::Value>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .write_tool_input(&tool_call_id, &payload.input)
+    {
+        Ok(()) => Ok(Json(serde_json::json!({ "success": true }))),
+        Err(e) => Err(Json(ErrorResponse::new("NOT_FOUND", e))),
+    }
+}
+
+/// List tasks in a session paused because they exceeded their budget
+pub async fn list_budget_pauses(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<BudgetPause>>, Json<ErrorResponse>> {
+    match state.runtime().list_budget_pauses(Some(&session_id)).await {
+        Ok(pauses) => Ok(Json(pauses)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list budget pauses: {}", e),
+        ))),
+    }
+}
+
+/// List tasks in a session interrupted by a restart, which can be continued
+pub async fn list_interrupted_tasks(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<StreamState>>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .list_interrupted_tasks(Some(&session_id))
+        .await
+    {
+        Ok(tasks) => Ok(Json(tasks)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list interrupted tasks: {}", e),
+        ))),
+    }
+}
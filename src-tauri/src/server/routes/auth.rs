@@ -0,0 +1,43 @@
+use axum::extract::State;
+use axum::Json;
+
+use crate::security::jwt::AuthTokens;
+use crate::server::state::ServerState;
+use crate::server::types::*;
+
+/// Sign in with an API key, receiving an access and a refresh token
+pub async fn login(
+    State(state): State<ServerState>,
+    Json(payload): Json<LoginRequest>,
+) -> Result<Json<AuthTokens>, Json<ErrorResponse>> {
+    match state.runtime().login(&payload.api_key).await {
+        Ok(tokens) => Ok(Json(tokens)),
+        Err(e) => Err(Json(ErrorResponse::new("UNAUTHORIZED", e))),
+    }
+}
+
+/// Exchange a refresh token for new tokens; the refresh token is rotated
+pub async fn refresh(
+    State(state): State<ServerState>,
+    Json(payload): Json<RefreshTokenRequest>,
+) -> Result<Json<AuthTokens>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .refresh_auth_tokens(&payload.refresh_token)
+        .await
+    {
+        Ok(tokens) => Ok(Json(tokens)),
+        Err(e) => Err(Json(ErrorResponse::new("UNAUTHORIZED", e))),
+    }
+}
+
+/// Sign out, revoking the refresh token and those rotated with it
+pub async fn logout(
+    State(state): State<ServerState>,
+    Json(payload): Json<RefreshTokenRequest>,
+) -> Result<Json<serde_json::Value>, Json<ErrorResponse>> {
+    match state.runtime().logout(&payload.refresh_token).await {
+        Ok(()) => Ok(Json(serde_json::json!({ "success": true }))),
+        Err(e) => Err(Json(ErrorResponse::new("UNAUTHORIZED", e))),
+    }
+}
@@ -0,0 +1,36 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::core::checkpoints::CheckpointRollback;
+use crate::server::state::ServerState;
+use crate::server::types::*;
+use crate::storage::models::Checkpoint;
+
+/// List file checkpoints of a session, oldest first
+pub async fn list_checkpoints(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<Checkpoint>>, Json<ErrorResponse>> {
+    match state.runtime().list_checkpoints(&session_id).await {
+        Ok(checkpoints) => Ok(Json(checkpoints)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list checkpoints: {}", e),
+        ))),
+    }
+}
+
+/// Undo a session's file changes back to the state before a checkpoint
+pub async fn rollback_to_checkpoint(
+    State(state): State<ServerState>,
+    Path((session_id, checkpoint_id)): Path<(String, String)>,
+) -> Result<Json<CheckpointRollback>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .rollback_to_checkpoint(&session_id, &checkpoint_id)
+        .await
+    {
+        Ok(rollback) => Ok(Json(rollback)),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
@@ -0,0 +1,40 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+
+use crate::core::event_log::RebuiltSession;
+use crate::server::state::ServerState;
+use crate::server::types::*;
+use crate::storage::models::RuntimeEventRecord;
+
+/// A session's logged runtime events, for resuming after the last one seen
+pub async fn list_session_events(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+    Query(query): Query<SessionEventLogQuery>,
+) -> Result<Json<Vec<RuntimeEventRecord>>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .list_session_events(&session_id, query.after, query.limit)
+        .await
+    {
+        Ok(events) => Ok(Json(events)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list session events: {}", e),
+        ))),
+    }
+}
+
+/// A session's messages, tool results and task states rebuilt from its event log
+pub async fn rebuild_session_state(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<RebuiltSession>, Json<ErrorResponse>> {
+    match state.runtime().rebuild_session_state(&session_id).await {
+        Ok(rebuilt) => Ok(Json(rebuilt)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to rebuild session state: {}", e),
+        ))),
+    }
+}
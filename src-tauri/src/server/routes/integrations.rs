@@ -0,0 +1,54 @@
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use serde_json::Value;
+
+use crate::integrations::IntegrationPairing;
+use crate::server::state::ServerState;
+use crate::{feishu_gateway, telegram_gateway};
+
+/// Create a one-time code a chat sends with `/pair <code>` to be allowed to
+/// use the agent through an integration
+pub async fn create_pairing(
+    State(state): State<ServerState>,
+    Path(integration_id): Path<String>,
+) -> Json<IntegrationPairing> {
+    Json(state.routing().access().create_pairing(&integration_id))
+}
+
+/// Receive a Telegram webhook delivery, verified by its secret token
+pub async fn telegram_webhook(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    body: String,
+) -> StatusCode {
+    let Some(app) = state.app() else {
+        return StatusCode::SERVICE_UNAVAILABLE;
+    };
+    match telegram_gateway::receive_webhook(app, &headers, &body).await {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            log::warn!("Refused Telegram webhook delivery: {}", e);
+            StatusCode::FORBIDDEN
+        }
+    }
+}
+
+/// Receive a Feishu webhook delivery, verified by its signature or
+/// verification token
+pub async fn feishu_webhook(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Json<Value>, StatusCode> {
+    let Some(app) = state.app() else {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    match feishu_gateway::receive_webhook(app, &headers, &body).await {
+        Ok(answer) => Ok(Json(answer)),
+        Err(e) => {
+            log::warn!("Refused Feishu webhook delivery: {}", e);
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
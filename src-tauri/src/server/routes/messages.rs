@@ -1,19 +1,23 @@
 use axum::extract::{Path, Query, State};
 use axum::Json;
 
+use crate::core::types::TaskInput;
+use crate::security::permissions::RequestScopes;
 use crate::server::state::ServerState;
 use crate::server::types::*;
-use crate::storage::models::{Message, MessageContent, MessageRole};
+use crate::storage::models::{ContextCompaction, Message, MessageContent, MessageRole};
 
-/// Create a new message in a session
+/// Create a new message in a session. A user message enqueues a task that
+/// answers it; other messages are only stored.
 pub async fn create_message(
     State(state): State<ServerState>,
     Path(session_id): Path<String>,
+    scopes: RequestScopes,
     Json(payload): Json<CreateMessageRequest>,
 ) -> Result<Json<CreateMessageResponse>, Json<ErrorResponse>> {
     // Verify session exists
-    match state.storage().chat_history.get_session(&session_id).await {
-        Ok(Some(_)) => {}
+    let session = match state.storage().chat_history.get_session(&session_id).await {
+        Ok(Some(session)) => session,
         Ok(None) => {
             return Err(Json(ErrorResponse::new(
                 "NOT_FOUND",
@@ -26,30 +30,70 @@ pub async fn create_message(
                 format!("Failed to get session: {}", e),
             )));
         }
-    }
+    };
 
     let now = chrono::Utc::now().timestamp();
-    let message_id = format!("msg_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+    let role = payload
+        .role
+        .as_deref()
+        .and_then(|r| r.parse().ok())
+        .unwrap_or(MessageRole::User);
+
+    if role == MessageRole::User {
+        let task_input = TaskInput {
+            session_id,
+            agent_id: payload.agent_id,
+            project_id: session.project_id,
+            initial_message: payload.content,
+            settings: payload.settings,
+            workspace: None,
+            priority: 0,
+            isolate: false,
+            read_only_tools: !scopes.can_run_tools(),
+        };
+
+        let validation = state.runtime().validate_task(&task_input).await;
+        if !validation.valid {
+            return Err(Json(
+                ErrorResponse::new(
+                    "BAD_REQUEST",
+                    format!("Invalid settings: {}", validation.errors.join(", ")),
+                )
+                .with_details(serde_json::json!(validation.details)),
+            ));
+        }
+
+        return match state.runtime().start_task(task_input).await {
+            Ok(handle) => Ok(Json(CreateMessageResponse {
+                message_id: None,
+                task_id: Some(handle.task_id.clone()),
+                created_at: now,
+            })),
+            Err(e) => Err(Json(ErrorResponse::new(
+                "INTERNAL_ERROR",
+                format!("Failed to start task: {}", e),
+            ))),
+        };
+    }
 
+    let message_id = format!("msg_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
     let message = Message {
         id: message_id.clone(),
         session_id: session_id.clone(),
-        role: payload
-            .role
-            .as_deref()
-            .and_then(|r| r.parse().ok())
-            .unwrap_or(MessageRole::User),
+        role,
         content: MessageContent::Text {
             text: payload.content,
         },
         created_at: now,
         tool_call_id: None,
         parent_id: None,
+        pinned: false,
     };
 
     match state.storage().chat_history.create_message(&message).await {
         Ok(_) => Ok(Json(CreateMessageResponse {
-            message_id,
+            message_id: Some(message_id),
+            task_id: None,
             created_at: now,
         })),
         Err(e) => Err(Json(ErrorResponse::new(
@@ -59,24 +103,142 @@ pub async fn create_message(
     }
 }
 
-/// Get messages for a session
+/// Get messages for a session. Pages back from `beforeId`, or forward from
+/// `since` when it is given.
 pub async fn get_messages(
     State(state): State<ServerState>,
     Path(session_id): Path<String>,
     Query(query): Query<ListMessagesQuery>,
 ) -> Result<Json<Vec<MessageResponse>>, Json<ErrorResponse>> {
+    let chat_history = &state.storage().chat_history;
+    let messages = match query.since {
+        Some(since) => {
+            chat_history
+                .get_messages_since(&session_id, since, query.limit, query.before_id.as_deref())
+                .await
+        }
+        None => {
+            chat_history
+                .get_messages(&session_id, query.limit, query.before_id.as_deref())
+                .await
+        }
+    };
+
+    match messages {
+        Ok(messages) => Ok(Json(
+            messages.into_iter().map(MessageResponse::from).collect(),
+        )),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to get messages: {}", e),
+        ))),
+    }
+}
+
+/// Get a message with the tool calls it made and their results
+pub async fn get_message(
+    State(state): State<ServerState>,
+    Path(message_id): Path<String>,
+) -> Result<Json<MessageDetailResponse>, Json<ErrorResponse>> {
+    let chat_history = &state.storage().chat_history;
+    let message = match chat_history.get_message(&message_id).await {
+        Ok(Some(message)) => message,
+        Ok(None) => {
+            return Err(Json(ErrorResponse::new(
+                "NOT_FOUND",
+                format!("Message '{}' not found", message_id),
+            )));
+        }
+        Err(e) => {
+            return Err(Json(ErrorResponse::new(
+                "INTERNAL_ERROR",
+                format!("Failed to get message: {}", e),
+            )));
+        }
+    };
+
+    let calls = match &message.content {
+        MessageContent::ToolCalls { calls } => calls.clone(),
+        _ => vec![],
+    };
+    let call_ids: Vec<String> = calls.iter().map(|call| call.id.clone()).collect();
+    let mut results = match chat_history
+        .get_tool_results(&message.session_id, &call_ids)
+        .await
+    {
+        Ok(results) => results,
+        Err(e) => {
+            return Err(Json(ErrorResponse::new(
+                "INTERNAL_ERROR",
+                format!("Failed to get tool results: {}", e),
+            )));
+        }
+    };
+
+    let tool_calls = calls
+        .into_iter()
+        .map(|call| {
+            let result = results
+                .iter()
+                .position(|result| result.tool_call_id.as_deref() == Some(call.id.as_str()))
+                .map(|index| MessageResponse::from(results.remove(index)));
+            ToolCallResponse {
+                id: call.id,
+                name: call.name,
+                input: call.input,
+                result,
+            }
+        })
+        .collect();
+
+    Ok(Json(MessageDetailResponse {
+        message: MessageResponse::from(message),
+        tool_calls,
+    }))
+}
+
+/// Pin or unpin a message; pinned messages survive context compaction
+pub async fn pin_message(
+    State(state): State<ServerState>,
+    Path(message_id): Path<String>,
+    Json(payload): Json<PinMessageRequest>,
+) -> Result<Json<PinMessageResponse>, Json<ErrorResponse>> {
     match state
         .storage()
         .chat_history
-        .get_messages(&session_id, query.limit, query.before_id.as_deref())
+        .set_message_pinned(&message_id, payload.pinned)
         .await
     {
-        Ok(messages) => Ok(Json(
-            messages.into_iter().map(MessageResponse::from).collect(),
-        )),
+        Ok(true) => Ok(Json(PinMessageResponse {
+            message_id,
+            pinned: payload.pinned,
+        })),
+        Ok(false) => Err(Json(ErrorResponse::new(
+            "NOT_FOUND",
+            format!("Message '{}' not found", message_id),
+        ))),
         Err(e) => Err(Json(ErrorResponse::new(
             "INTERNAL_ERROR",
-            format!("Failed to get messages: {}", e),
+            format!("Failed to pin message: {}", e),
+        ))),
+    }
+}
+
+/// List context compactions of a session, oldest first
+pub async fn list_compactions(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<ContextCompaction>>, Json<ErrorResponse>> {
+    match state
+        .storage()
+        .chat_history
+        .list_compactions(&session_id)
+        .await
+    {
+        Ok(compactions) => Ok(Json(compactions)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list compactions: {}", e),
         ))),
     }
 }
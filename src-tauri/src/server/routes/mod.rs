@@ -1,25 +1,109 @@
+use axum::middleware::from_fn_with_state;
 use axum::routing::{delete, get, patch, post};
 use axum::Router;
 
+use crate::server::idempotency::idempotency_middleware;
 use crate::server::state::ServerState;
 
 pub mod actions;
+pub mod admin;
+pub mod api_keys;
+pub mod auth;
+pub mod approvals;
+pub mod checkpoints;
+pub mod event_log;
 pub mod files;
 pub mod health;
+pub mod integrations;
 pub mod messages;
+pub mod pairing;
+pub mod plans;
+pub mod projects;
 pub mod sessions;
+pub mod stats;
 pub mod tasks;
+pub mod todos;
+pub mod users;
+pub mod worktrees;
+pub mod ws;
+
+/// Routes reachable without credentials, for signing in and for the
+/// webhooks of IM integrations
+pub fn public_router(state: ServerState) -> Router {
+    Router::new()
+        .route("/v1/auth/login", post(auth::login))
+        .route("/v1/auth/refresh", post(auth::refresh))
+        .route("/v1/auth/logout", post(auth::logout))
+        .route("/v1/auth/pair", post(pairing::pair_device))
+        // Webhooks of IM integrations, verified by the channel's signature
+        .route("/v1/integrations/telegram/webhook", post(integrations::telegram_webhook))
+        .route("/v1/integrations/feishu/webhook", post(integrations::feishu_webhook))
+        .with_state(state)
+}
 
 pub fn router(state: ServerState) -> Router {
     Router::new()
         // Health check
         .route("/health", get(health::health_check))
+        // Stats
+        .route("/v1/stats", get(stats::get_runtime_stats))
+        .route("/v1/streaming/stats", get(stats::get_streaming_stats))
+        .route("/v1/rate-limits", get(stats::get_rate_limits))
+        // Diagnostics
+        .route("/v1/admin/diagnostics", get(admin::get_diagnostics))
+        // Pairing of companion clients
+        .route("/v1/pairings", post(pairing::create_pairing))
+        // Access to the agent through IM integrations
+        .route("/v1/integrations/:id/pairings", post(integrations::create_pairing))
+        // API keys
+        .route("/v1/api-keys", post(api_keys::create_api_key))
+        .route("/v1/api-keys", get(api_keys::list_api_keys))
+        .route("/v1/api-keys/:id", delete(api_keys::revoke_api_key))
+        // Users
+        .route("/v1/users", post(users::create_user))
+        .route("/v1/users", get(users::list_users))
+        .route("/v1/users/:id", delete(users::delete_user))
+        // Projects
+        .route("/v1/projects", post(projects::create_project))
+        .route("/v1/projects", get(projects::list_projects))
+        .route("/v1/projects/:id", get(projects::get_project))
+        .route("/v1/projects/:id", patch(projects::update_project))
+        .route("/v1/projects/:id", delete(projects::delete_project))
         // Sessions
-        .route("/v1/sessions", post(sessions::create_session))
+        .route(
+            "/v1/sessions",
+            post(sessions::create_session)
+                .layer(from_fn_with_state(state.clone(), idempotency_middleware)),
+        )
         .route("/v1/sessions", get(sessions::list_sessions))
+        .route("/v1/sessions/search", get(sessions::search_sessions))
         .route("/v1/sessions/:id", get(sessions::get_session))
         .route("/v1/sessions/:id", delete(sessions::delete_session))
         .route("/v1/sessions/:id/events", get(sessions::session_events))
+        .route("/v1/ws", get(ws::ws_handler))
+        .route("/v1/sessions/:id/archive", post(sessions::archive_session))
+        .route("/v1/sessions/:id/restore", post(sessions::restore_session))
+        .route("/v1/sessions/:id/star", post(sessions::star_session))
+        .route(
+            "/v1/retention-policy",
+            get(sessions::get_retention_policy),
+        )
+        .route(
+            "/v1/retention-policy",
+            post(sessions::set_retention_policy),
+        )
+        .route(
+            "/v1/retention-policy/apply",
+            post(sessions::apply_retention_policy),
+        )
+        .route(
+            "/v1/sessions/:id/event-log",
+            get(event_log::list_session_events),
+        )
+        .route(
+            "/v1/sessions/:id/state",
+            get(event_log::rebuild_session_state),
+        )
         .route(
             "/v1/sessions/:id/settings",
             get(sessions::get_session_settings),
@@ -31,13 +115,83 @@ pub fn router(state: ServerState) -> Router {
         // Messages
         .route("/v1/sessions/:id/messages", post(messages::create_message))
         .route("/v1/sessions/:id/messages", get(messages::get_messages))
+        .route("/v1/messages/:id", get(messages::get_message))
+        .route("/v1/messages/:id/pin", post(messages::pin_message))
+        .route(
+            "/v1/sessions/:id/compactions",
+            get(messages::list_compactions),
+        )
         // Tasks
-        .route("/v1/tasks", post(tasks::create_task))
+        .route(
+            "/v1/tasks",
+            post(tasks::create_task)
+                .layer(from_fn_with_state(state.clone(), idempotency_middleware)),
+        )
         .route("/v1/tasks", get(tasks::list_tasks))
         .route("/v1/tasks/:id", get(tasks::get_task))
         .route("/v1/tasks/:id", patch(tasks::patch_task))
+        .route("/v1/tasks/:id/cancel", post(tasks::cancel_task))
+        .route(
+            "/v1/sessions/:id/tasks",
+            post(tasks::start_session_task)
+                .layer(from_fn_with_state(state.clone(), idempotency_middleware)),
+        )
+        .route("/v1/queue", get(tasks::list_queue))
         // Actions
         .route("/v1/sessions/:id/actions", post(actions::create_action))
+        // Tool approvals
+        .route("/v1/sessions/:id/approvals", get(approvals::list_approvals))
+        .route(
+            "/v1/sessions/:id/approvals/resolve",
+            post(approvals::resolve_approvals),
+        )
+        .route(
+            "/v1/tool-calls/:id/approve",
+            post(approvals::approve_tool_call),
+        )
+        .route("/v1/tool-calls/:id/deny", post(approvals::deny_tool_call))
+        .route(
+            "/v1/tool-calls/:id/input",
+            post(approvals::write_tool_input),
+        )
+        .route(
+            "/v1/sessions/:id/budget-pauses",
+            get(approvals::list_budget_pauses),
+        )
+        .route(
+            "/v1/sessions/:id/interrupted-tasks",
+            get(approvals::list_interrupted_tasks),
+        )
+        // Checkpoints
+        .route(
+            "/v1/sessions/:id/checkpoints",
+            get(checkpoints::list_checkpoints),
+        )
+        .route(
+            "/v1/sessions/:id/checkpoints/:checkpoint_id/rollback",
+            post(checkpoints::rollback_to_checkpoint),
+        )
+        // Plans
+        .route("/v1/sessions/:id/plans", get(plans::list_plans))
+        .route(
+            "/v1/sessions/:id/plans/execute",
+            post(plans::execute_plan),
+        )
+        // Todos
+        .route("/v1/sessions/:id/todos", get(todos::get_todos))
+        // Worktrees
+        .route(
+            "/v1/sessions/:id/worktrees",
+            get(worktrees::list_task_worktrees),
+        )
+        .route(
+            "/v1/tasks/:id/worktree/merge",
+            post(worktrees::merge_task_worktree),
+        )
+        .route(
+            "/v1/tasks/:id/worktree",
+            delete(worktrees::discard_task_worktree),
+        )
         // Files
         .route("/v1/sessions/:id/files", post(files::upload_file))
         .route("/v1/sessions/:id/files", get(files::list_files))
@@ -0,0 +1,66 @@
+use axum::extract::State;
+use axum::Json;
+
+use crate::security::pairing::PairedDevice;
+use crate::server::discovery;
+use crate::server::state::ServerState;
+use crate::server::types::*;
+
+/// Create a one-time pairing code, with the URI and QR code a companion client
+/// on the LAN scans to pair
+pub async fn create_pairing(
+    State(state): State<ServerState>,
+    Json(payload): Json<CreatePairingRequest>,
+) -> Result<Json<PairingResponse>, Json<ErrorResponse>> {
+    let Some(addr) = state.addr.filter(|_| state.config.bind.lan_access()) else {
+        return Err(Json(ErrorResponse::new(
+            "BAD_REQUEST",
+            "LAN access is disabled",
+        )));
+    };
+    // A server on every interface is reached at any of the machine's addresses
+    let hosts = if addr.ip().is_unspecified() {
+        discovery::lan_addresses()
+    } else {
+        vec![addr.ip()]
+    };
+    let Some(host) = hosts.first().copied() else {
+        return Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            "No LAN address to pair over",
+        )));
+    };
+
+    let pairing = state
+        .runtime()
+        .create_pairing(payload.user_id.as_deref())
+        .await
+        .map_err(|e| Json(ErrorResponse::new("BAD_REQUEST", e)))?;
+    let uri = discovery::pairing_uri(host, addr.port(), &pairing.code);
+    let qr_svg =
+        discovery::qr_svg(&uri).map_err(|e| Json(ErrorResponse::new("INTERNAL_ERROR", e)))?;
+
+    Ok(Json(PairingResponse {
+        pairing,
+        hosts,
+        port: addr.port(),
+        uri,
+        qr_svg,
+    }))
+}
+
+/// Redeem a pairing code for an API key of the device and tokens signed in
+/// with it
+pub async fn pair_device(
+    State(state): State<ServerState>,
+    Json(payload): Json<PairDeviceRequest>,
+) -> Result<Json<PairedDevice>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .pair_device(&payload.code, &payload.device_name)
+        .await
+    {
+        Ok(device) => Ok(Json(device)),
+        Err(e) => Err(Json(ErrorResponse::new("UNAUTHORIZED", e))),
+    }
+}
@@ -0,0 +1,36 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::server::state::ServerState;
+use crate::server::types::*;
+use crate::storage::models::Plan;
+
+/// List plans submitted in a session, oldest first
+pub async fn list_plans(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<Plan>>, Json<ErrorResponse>> {
+    match state.runtime().list_plans(&session_id).await {
+        Ok(plans) => Ok(Json(plans)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list plans: {}", e),
+        ))),
+    }
+}
+
+/// Start a task that executes the session's submitted plan with every tool
+pub async fn execute_plan(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<CreateTaskResponse>, Json<ErrorResponse>> {
+    match state.runtime().execute_plan(&session_id).await {
+        Ok(handle) => Ok(Json(CreateTaskResponse {
+            task_id: handle.task_id,
+            session_id: handle.session_id,
+            state: "pending".to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+        })),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
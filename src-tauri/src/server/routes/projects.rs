@@ -0,0 +1,107 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::security::scope::RequestUser;
+use crate::server::state::ServerState;
+use crate::server::types::*;
+use crate::storage::models::Project;
+use crate::storage::ProjectUpdates;
+
+/// Create a project in an existing workspace directory
+pub async fn create_project(
+    State(state): State<ServerState>,
+    user: RequestUser,
+    Json(payload): Json<CreateProjectRequest>,
+) -> Result<Json<Project>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .create_project(
+            &payload.name,
+            &payload.workspace_path,
+            payload.default_settings,
+            user.0,
+        )
+        .await
+    {
+        Ok(project) => Ok(Json(project)),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
+
+/// List the projects a request reaches, by name
+pub async fn list_projects(
+    State(state): State<ServerState>,
+    user: RequestUser,
+) -> Result<Json<Vec<Project>>, Json<ErrorResponse>> {
+    match state.runtime().list_projects(user.0.as_deref()).await {
+        Ok(projects) => Ok(Json(projects)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list projects: {}", e),
+        ))),
+    }
+}
+
+pub async fn get_project(
+    State(state): State<ServerState>,
+    Path(project_id): Path<String>,
+) -> Result<Json<Project>, Json<ErrorResponse>> {
+    find_project(&state, &project_id).await.map(Json)
+}
+
+/// Edit the name, workspace or default settings of a project
+pub async fn update_project(
+    State(state): State<ServerState>,
+    Path(project_id): Path<String>,
+    Json(payload): Json<UpdateProjectRequest>,
+) -> Result<Json<Project>, Json<ErrorResponse>> {
+    find_project(&state, &project_id).await?;
+
+    let default_settings = if payload.clear_default_settings {
+        Some(None)
+    } else {
+        payload.default_settings.map(Some)
+    };
+    let updates = ProjectUpdates {
+        name: payload.name,
+        workspace_path: payload.workspace_path,
+        default_settings,
+    };
+    match state.runtime().update_project(&project_id, updates).await {
+        Ok(project) => Ok(Json(project)),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
+
+/// Delete a project; its sessions are kept
+pub async fn delete_project(
+    State(state): State<ServerState>,
+    Path(project_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Json<ErrorResponse>> {
+    find_project(&state, &project_id).await?;
+
+    match state.runtime().delete_project(&project_id).await {
+        Ok(()) => Ok(Json(serde_json::json!({ "success": true }))),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to delete project: {}", e),
+        ))),
+    }
+}
+
+async fn find_project(
+    state: &ServerState,
+    project_id: &str,
+) -> Result<Project, Json<ErrorResponse>> {
+    match state.runtime().get_project(project_id).await {
+        Ok(Some(project)) => Ok(project),
+        Ok(None) => Err(Json(ErrorResponse::new(
+            "NOT_FOUND",
+            format!("Project not found: {}", project_id),
+        ))),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to get project: {}", e),
+        ))),
+    }
+}
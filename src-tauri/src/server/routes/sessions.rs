@@ -1,21 +1,37 @@
+use axum::body::Body;
 use axum::extract::{Path, Query, State};
+use axum::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, VARY};
+use axum::http::{HeaderMap, HeaderValue};
 use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::convert::Infallible;
-use std::time::Duration;
-use tokio_stream::wrappers::IntervalStream;
+use std::time::Instant;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
 use tokio_stream::StreamExt;
 
+use crate::core::retention::{RetentionPolicy, RetentionReport};
+use crate::security::scope::RequestUser;
 use crate::server::state::ServerState;
 use crate::server::types::*;
-use crate::storage::models::{Session, SessionStatus, TaskSettings};
+use crate::storage::models::{SearchHit, Session, SessionStatus, TaskSettings};
+use crate::storage::pagination::{Page, PageRequest};
+use crate::streaming::{
+    ConsumerLag, DeltaEncoder, EventCategory, StreamCompressor, StreamEncoding, StreamingEvent,
+    SubscriberTransport,
+};
 
 /// Create a new session
 pub async fn create_session(
     State(state): State<ServerState>,
+    user: RequestUser,
     Json(payload): Json<CreateSessionRequest>,
 ) -> Result<Json<CreateSessionResponse>, Json<ErrorResponse>> {
+    if let Some(project_id) = &payload.project_id {
+        check_project_access(&state, &user, project_id).await?;
+    }
     let now = chrono::Utc::now().timestamp();
     let session_id = format!("sess_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
 
@@ -23,11 +39,15 @@ pub async fn create_session(
         id: session_id.clone(),
         project_id: payload.project_id,
         title: payload.title,
+        summary: None,
         status: SessionStatus::Created,
         created_at: now,
         updated_at: now,
         last_event_id: None,
         metadata: None,
+        starred: false,
+        archived_at: None,
+        user_id: user.0,
     };
 
     match state.storage().chat_history.create_session(&session).await {
@@ -53,6 +73,25 @@ pub async fn create_session(
     }
 }
 
+/// Refuse a project the request's user may not reach
+pub(crate) async fn check_project_access(
+    state: &ServerState,
+    user: &RequestUser,
+    project_id: &str,
+) -> Result<(), Json<ErrorResponse>> {
+    match user.can_access_project(state, project_id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Json(ErrorResponse::new(
+            "FORBIDDEN",
+            format!("Project '{}' is not accessible", project_id),
+        ))),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to get project: {}", e),
+        ))),
+    }
+}
+
 /// Get session by ID
 pub async fn get_session(
     State(state): State<ServerState>,
@@ -71,27 +110,36 @@ pub async fn get_session(
     }
 }
 
-/// List sessions with optional filters
+/// List a page of sessions with optional filters
 pub async fn list_sessions(
     State(state): State<ServerState>,
+    user: RequestUser,
     Query(query): Query<ListSessionsQuery>,
-) -> Result<Json<Vec<SessionResponse>>, Json<ErrorResponse>> {
+) -> Result<Json<Page<SessionResponse>>, Json<ErrorResponse>> {
     let status = query.status.and_then(|s| s.parse().ok());
+    let page = PageRequest {
+        limit: query.limit,
+        cursor: query.cursor,
+        order: query.order,
+    };
+    if let Err(e) = page.cursor_for(query.sort.column()) {
+        return Err(Json(ErrorResponse::new("BAD_REQUEST", e)));
+    }
 
     match state
         .storage()
         .chat_history
         .list_sessions(
             query.project_id.as_deref(),
+            user.0.as_deref(),
             status,
-            query.limit,
-            query.offset,
+            query.archived,
+            query.sort,
+            &page,
         )
         .await
     {
-        Ok(sessions) => Ok(Json(
-            sessions.into_iter().map(SessionResponse::from).collect(),
-        )),
+        Ok(sessions) => Ok(Json(sessions.map(SessionResponse::from))),
         Err(e) => Err(Json(ErrorResponse::new(
             "INTERNAL_ERROR",
             format!("Failed to list sessions: {}", e),
@@ -125,6 +173,112 @@ pub async fn delete_session(
     }
 }
 
+/// Archive a session, hiding it from default session lists
+pub async fn archive_session(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Json<ErrorResponse>> {
+    match state.runtime().archive_session(&session_id).await {
+        Ok(()) => Ok(Json(serde_json::json!({ "success": true }))),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
+
+/// Restore an archived session
+pub async fn restore_session(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Json<ErrorResponse>> {
+    match state.runtime().restore_session(&session_id).await {
+        Ok(()) => Ok(Json(serde_json::json!({ "success": true }))),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
+
+/// Star or unstar a session
+pub async fn star_session(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+    Json(payload): Json<StarSessionRequest>,
+) -> Result<Json<serde_json::Value>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .set_session_starred(&session_id, payload.starred)
+        .await
+    {
+        Ok(()) => Ok(Json(serde_json::json!({ "success": true }))),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
+
+/// Search session titles and message text, best matches first
+pub async fn search_sessions(
+    State(state): State<ServerState>,
+    user: RequestUser,
+    Query(query): Query<SearchSessionsQuery>,
+) -> Result<Json<Vec<SearchHit>>, Json<ErrorResponse>> {
+    if query.q.trim().is_empty() {
+        return Err(Json(ErrorResponse::new(
+            "BAD_REQUEST",
+            "Search query must not be empty",
+        )));
+    }
+
+    match state
+        .runtime()
+        .search_sessions(
+            &query.q,
+            query.project_id.as_deref(),
+            user.0.as_deref(),
+            query.limit,
+        )
+        .await
+    {
+        Ok(hits) => Ok(Json(hits)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to search sessions: {}", e),
+        ))),
+    }
+}
+
+/// Get the session retention policy
+pub async fn get_retention_policy(
+    State(state): State<ServerState>,
+) -> Result<Json<RetentionPolicy>, Json<ErrorResponse>> {
+    match state.runtime().retention_policy().await {
+        Ok(policy) => Ok(Json(policy)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to get retention policy: {}", e),
+        ))),
+    }
+}
+
+/// Replace the session retention policy
+pub async fn set_retention_policy(
+    State(state): State<ServerState>,
+    Json(payload): Json<RetentionPolicy>,
+) -> Result<Json<RetentionPolicy>, Json<ErrorResponse>> {
+    match state.runtime().set_retention_policy(payload.clone()).await {
+        Ok(()) => Ok(Json(payload)),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
+
+/// Apply the session retention policy right away
+pub async fn apply_retention_policy(
+    State(state): State<ServerState>,
+) -> Result<Json<RetentionReport>, Json<ErrorResponse>> {
+    match state.runtime().apply_retention_policy().await {
+        Ok(report) => Ok(Json(report)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to apply retention policy: {}", e),
+        ))),
+    }
+}
+
 /// Get session settings
 pub async fn get_session_settings(
     State(state): State<ServerState>,
@@ -165,34 +319,287 @@ pub async fn update_session_settings(
     }
 }
 
-/// SSE endpoint for session events
+/// SSE endpoint for session events. A client reconnecting with
+/// `Last-Event-ID` first receives the events it missed, then live events.
 pub async fn session_events(
     Path(session_id): Path<String>,
+    Query(query): Query<StreamQuery>,
     State(state): State<ServerState>,
-) -> Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>> {
-    // Get last event ID for resume (from header)
-    // For now, start from current time
-
-    let interval = tokio::time::interval(Duration::from_secs(15));
-    let stream = IntervalStream::new(interval).map(move |_| {
-        // In a full implementation, this would:
-        // 1. Query the events table for new events since last_event_id
-        // 2. Convert events to SSE format
-        // 3. Return them to the client
-
-        // Placeholder: send heartbeat
-        let event = Event::default().event("status").data(
-            serde_json::json!({
-                "type": "status",
-                "data": {
-                    "message": "heartbeat",
-                    "sessionId": session_id
+    headers: HeaderMap,
+) -> Response {
+    // Events outside the requested categories are left out
+    let categories = match query.events.as_deref().map(EventCategory::parse_list) {
+        Some(Ok(categories)) => Some(categories),
+        Some(Err(e)) => return Json(ErrorResponse::new("BAD_REQUEST", e)).into_response(),
+        None => None,
+    };
+    let wanted = move |event: &StreamingEvent| {
+        categories.as_ref().is_none_or(|categories| {
+            event
+                .category()
+                .is_none_or(|category| categories.contains(&category))
+        })
+    };
+
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty());
+
+    let streaming = state.streaming();
+    let streaming = streaming.read().await;
+    // Subscribe before replaying so no event falls between the two
+    let live = streaming.buffer.subscribe();
+    // Listed in the streaming stats until the client disconnects
+    let subscriber = streaming
+        .throttler
+        .metrics()
+        .register(SubscriberTransport::Sse, vec![session_id.clone()]);
+    let missed = match last_event_id {
+        Some(id) => streaming
+            .buffer
+            .get_events(&session_id, Some(id), None)
+            .await
+            .map(|events| events.into_iter().filter(&wanted).collect())
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to replay events of session {}: {}", session_id, e);
+                Vec::new()
+            }),
+        None => Vec::new(),
+    };
+    drop(streaming);
+
+    // Live events already sent in the replay are skipped
+    let replayed_up_to = missed
+        .last()
+        .map(|event| event.event_id().as_str())
+        .or(last_event_id)
+        .and_then(|id| id.parse::<u64>().ok());
+    let accepts = move |event: &StreamingEvent| {
+        event.session_id() == Some(&session_id)
+            && wanted(event)
+            && event
+                .event_id()
+                .parse::<u64>()
+                .is_ok_and(|id| replayed_up_to.is_none_or(|last| id > last))
+    };
+    let streaming = state.streaming();
+    let shutdown = state.shutdown_token().clone();
+    let closing = shutdown.clone();
+    let live = futures_util::stream::unfold(
+        (live, VecDeque::new(), Instant::now(), subscriber),
+        move |(mut live, mut pending, yielded_at, subscriber)| {
+            let streaming = streaming.clone();
+            let accepts = accepts.clone();
+            let shutdown = shutdown.clone();
+            async move {
+                // Time the client took to write the previous event before asking for more
+                let write_latency = yielded_at.elapsed();
+                while pending.is_empty() {
+                    // A client that fell behind the channel is disconnected and
+                    // resumes from its last event
+                    let received = tokio::select! {
+                        received = live.recv() => received,
+                        // Ends the stream; the shutdown event follows
+                        _ = shutdown.cancelled() => return None,
+                    };
+                    let first = match received {
+                        Ok(event) => event,
+                        Err(RecvError::Lagged(skipped)) => {
+                            subscriber.record_dropped(skipped);
+                            return None;
+                        }
+                        Err(RecvError::Closed) => return None,
+                    };
+                    let mut batch = vec![first];
+                    let lag = ConsumerLag {
+                        queued: live.len(),
+                        write_latency,
+                    };
+                    subscriber.record_lag(&lag);
+                    let streaming = streaming.read().await;
+                    let throttler = &streaming.throttler;
+                    if throttler.is_lagging(&lag) {
+                        while batch.len() < throttler.max_coalesced_events() {
+                            match live.try_recv() {
+                                Ok(event) => batch.push(event),
+                                Err(TryRecvError::Empty) => break,
+                                Err(TryRecvError::Lagged(skipped)) => {
+                                    subscriber.record_dropped(skipped);
+                                    return None;
+                                }
+                                Err(TryRecvError::Closed) => return None,
+                            }
+                        }
+                        batch.retain(&accepts);
+                        pending.extend(throttler.coalesce(batch));
+                    } else {
+                        batch.retain(&accepts);
+                        pending.extend(batch);
+                    }
                 }
-            })
-            .to_string(),
-        );
-        Ok(event)
+                let event = pending.pop_front()?;
+                Some((event, (live, pending, Instant::now(), subscriber)))
+            }
+        },
+    );
+
+    let mut deltas = query.delta.then(DeltaEncoder::new);
+    let stream = tokio_stream::iter(missed)
+        .chain(live)
+        .map(move |event| sse_event(&event, deltas.as_mut()))
+        .chain(
+            futures_util::stream::once(async move { closing.is_cancelled() })
+                .filter_map(|closing| closing.then(shutdown_event)),
+        )
+        .map(Ok::<_, Infallible>);
+    let response = Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response();
+
+    match headers
+        .get(ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .and_then(StreamEncoding::negotiate)
+    {
+        Some(encoding) => compress_response(response, encoding),
+        None => response,
+    }
+}
+
+/// SSE event of a streaming event. When the client accepts deltas and the
+/// data repeats most of the previous data of its event type, `data` is
+/// replaced by a `delta` over the compact JSON text of that earlier data.
+fn sse_event(event: &StreamingEvent, deltas: Option<&mut DeltaEncoder>) -> Event {
+    let event_id = event.event_id();
+    let mut payload = serde_json::to_value(event).unwrap_or_default();
+    if let (Some(deltas), Some(fields)) = (deltas, payload.as_object_mut()) {
+        let data = fields
+            .get("data")
+            .map(|data| data.to_string())
+            .unwrap_or_default();
+        if let Some(delta) = deltas.encode(event.sse_event_name(), event_id, &data) {
+            fields.remove("data");
+            fields.insert(
+                "delta".to_string(),
+                serde_json::to_value(delta).unwrap_or_default(),
+            );
+        }
+    }
+    Event::default()
+        .id(event_id.as_str())
+        .event(event.sse_event_name())
+        .data(payload.to_string())
+}
+
+/// Last SSE event of a stream closed by a server shutdown. It has no ID, so
+/// a client resuming after the restart sends the ID of the event before it.
+fn shutdown_event() -> Event {
+    Event::default()
+        .event("server.shutdown")
+        .data(serde_json::json!({ "type": "server.shutdown" }).to_string())
+}
+
+/// Compress a streamed response chunk by chunk
+fn compress_response(response: Response, encoding: StreamEncoding) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let mut compressor = StreamCompressor::new(encoding);
+    let body = body.into_data_stream().map(move |chunk| {
+        chunk
+            .map_err(|e| e.to_string())
+            .and_then(|chunk| compressor.compress(&chunk))
     });
 
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    parts.headers.insert(
+        CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.as_str()),
+    );
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("accept-encoding"));
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::LlmClient;
+    use crate::llm::types::{StreamEvent, StreamTextRequest};
+    use crate::server::config::ServerConfig;
+    use crate::server::state::ServerStateFactory;
+    use crate::storage::models::Project;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    /// LLM client that is never asked for a completion
+    struct UnusedLlm;
+
+    #[async_trait::async_trait]
+    impl LlmClient for UnusedLlm {
+        async fn stream(
+            &self,
+            _request: StreamTextRequest,
+            _on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            Err("Not used".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_session_in_project_of_another_user() {
+        let temp_dir = TempDir::new().unwrap();
+        let config =
+            ServerConfig::new(temp_dir.path().to_path_buf(), temp_dir.path().to_path_buf());
+        let (event_tx, _event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let state = ServerStateFactory::create(config, Arc::new(UnusedLlm), event_tx)
+            .await
+            .unwrap();
+        state
+            .storage()
+            .projects
+            .create_project(&Project {
+                id: "project-1".to_string(),
+                name: "Project".to_string(),
+                workspace_path: temp_dir.path().to_string_lossy().to_string(),
+                default_settings: None,
+                user_id: Some("user-1".to_string()),
+                created_at: 0,
+                updated_at: 0,
+            })
+            .await
+            .unwrap();
+        let request = || {
+            Json(CreateSessionRequest {
+                project_id: Some("project-1".to_string()),
+                title: None,
+                settings: None,
+            })
+        };
+
+        let refused = create_session(
+            State(state.clone()),
+            RequestUser(Some("user-2".to_string())),
+            request(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(refused.0.error, "FORBIDDEN");
+
+        let created = create_session(
+            State(state.clone()),
+            RequestUser(Some("user-1".to_string())),
+            request(),
+        )
+        .await
+        .unwrap();
+        let session = state
+            .storage()
+            .chat_history
+            .get_session(&created.0.session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.project_id.as_deref(), Some("project-1"));
+    }
 }
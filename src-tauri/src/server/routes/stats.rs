@@ -0,0 +1,35 @@
+use axum::extract::{Query, State};
+use axum::Json;
+
+use crate::core::metrics::RuntimeStats;
+use crate::server::state::ServerState;
+use crate::server::types::*;
+use crate::streaming::StreamingStats;
+
+/// Counts and timings of tracked tasks with the current load of the task queue
+pub async fn get_runtime_stats(
+    State(state): State<ServerState>,
+    Query(query): Query<RuntimeStatsQuery>,
+) -> Json<RuntimeStats> {
+    Json(
+        state
+            .runtime()
+            .get_runtime_stats(query.session_id.as_deref())
+            .await,
+    )
+}
+
+/// Buffer stats, dropped and coalesced events, and the lag of each stream
+/// subscriber, for diagnosing clients that stopped updating
+pub async fn get_streaming_stats(State(state): State<ServerState>) -> Json<StreamingStats> {
+    Json(state.streaming().read().await.get_stats().await)
+}
+
+/// Rate limits of the server and the clients that exceeded them
+pub async fn get_rate_limits(State(state): State<ServerState>) -> Json<RateLimitsResponse> {
+    let limiter = state.rate_limiter();
+    Json(RateLimitsResponse {
+        limits: limiter.config(),
+        violations: limiter.violations(),
+    })
+}
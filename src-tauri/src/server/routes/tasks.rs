@@ -1,7 +1,11 @@
 use axum::extract::{Path, State};
 use axum::Json;
 
+use crate::core::scheduler::QueuedTask;
 use crate::core::types::TaskInput;
+use crate::security::permissions::RequestScopes;
+use crate::security::scope::RequestUser;
+use crate::server::routes::sessions::check_project_access;
 use crate::server::state::ServerState;
 use crate::server::types::*;
 use crate::storage::models::WorkspaceInfo;
@@ -10,38 +14,57 @@ use crate::storage::models::{SessionStatus, TaskSettings};
 /// Create a new task (starts agent execution)
 pub async fn create_task(
     State(state): State<ServerState>,
+    user: RequestUser,
+    scopes: RequestScopes,
     Json(payload): Json<CreateTaskRequest>,
 ) -> Result<Json<CreateTaskResponse>, Json<ErrorResponse>> {
+    if let Some(project_id) = &payload.project_id {
+        check_project_access(&state, &user, project_id).await?;
+    }
     // Create or use existing session
     let session_id = match payload.session_id {
-        Some(id) => id,
+        Some(id) => match user.can_access(&state, &id).await {
+            Ok(true) => id,
+            Ok(false) => {
+                return Err(Json(ErrorResponse::new(
+                    "NOT_FOUND",
+                    format!("Session '{}' not found", id),
+                )))
+            }
+            Err(e) => {
+                return Err(Json(ErrorResponse::new(
+                    "INTERNAL_ERROR",
+                    format!("Failed to get session: {}", e),
+                )))
+            }
+        },
         None => {
             // Create new session
+            let session_id = format!("sess_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
             match state
                 .storage()
                 .chat_history
                 .create_session(&crate::storage::models::Session {
-                    id: format!("sess_{}", uuid::Uuid::new_v4().to_string().replace("-", "")),
+                    id: session_id.clone(),
                     project_id: payload.project_id.clone(),
                     title: Some("New Task".to_string()),
+                    summary: None,
                     status: SessionStatus::Running,
                     created_at: chrono::Utc::now().timestamp(),
                     updated_at: chrono::Utc::now().timestamp(),
                     last_event_id: None,
                     metadata: None,
+                    starred: false,
+                    archived_at: None,
+                    user_id: user.0.clone(),
                 })
                 .await
             {
-                Ok(_) => {
-                    return Err(Json(ErrorResponse::new(
-                        "INTERNAL_ERROR",
-                        "Failed to create session",
-                    )))
-                }
-                Err(_) => {
+                Ok(()) => session_id,
+                Err(e) => {
                     return Err(Json(ErrorResponse::new(
                         "INTERNAL_ERROR",
-                        "Failed to create session",
+                        format!("Failed to create session: {}", e),
                     )))
                 }
             }
@@ -63,8 +86,74 @@ pub async fn create_task(
         initial_message: payload.initial_message,
         settings: payload.settings,
         workspace,
+        priority: payload.priority.unwrap_or_default(),
+        isolate: payload.isolate.unwrap_or_default(),
+        read_only_tools: !scopes.can_run_tools(),
+    };
+    start_task(&state, task_input).await
+}
+
+/// Start a task in an existing session, in the session's project
+pub async fn start_session_task(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+    scopes: RequestScopes,
+    Json(payload): Json<StartTaskRequest>,
+) -> Result<Json<CreateTaskResponse>, Json<ErrorResponse>> {
+    let session = match state.storage().chat_history.get_session(&session_id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => {
+            return Err(Json(ErrorResponse::new(
+                "NOT_FOUND",
+                format!("Session '{}' not found", session_id),
+            )))
+        }
+        Err(e) => {
+            return Err(Json(ErrorResponse::new(
+                "INTERNAL_ERROR",
+                format!("Failed to get session: {}", e),
+            )))
+        }
     };
 
+    let task_input = TaskInput {
+        session_id,
+        agent_id: payload.agent_id,
+        project_id: session.project_id,
+        initial_message: payload.initial_message,
+        settings: payload.settings,
+        workspace: payload.workspace.map(|w| WorkspaceInfo {
+            root_path: w.root_path,
+            worktree_path: w.worktree_path,
+            repository_url: w.repository_url,
+            branch: w.branch,
+        }),
+        priority: payload.priority.unwrap_or_default(),
+        isolate: payload.isolate.unwrap_or_default(),
+        read_only_tools: !scopes.can_run_tools(),
+    };
+    start_task(&state, task_input).await
+}
+
+/// Validate a task's settings and start it
+async fn start_task(
+    state: &ServerState,
+    task_input: TaskInput,
+) -> Result<Json<CreateTaskResponse>, Json<ErrorResponse>> {
+    let session_id = task_input.session_id.clone();
+
+    // Reject settings the selected model cannot run with
+    let validation = state.runtime().validate_task(&task_input).await;
+    if !validation.valid {
+        return Err(Json(
+            ErrorResponse::new(
+                "BAD_REQUEST",
+                format!("Invalid settings: {}", validation.errors.join(", ")),
+            )
+            .with_details(serde_json::json!(validation.details)),
+        ));
+    }
+
     // Start the task
     match state.runtime().start_task(task_input).await {
         Ok(handle) => Ok(Json(CreateTaskResponse {
@@ -106,13 +195,13 @@ pub async fn get_task(
     }
 }
 
-/// Patch/update task (e.g., cancel, update settings)
+/// Patch/update task (e.g., cancel, continue, reorder in the queue)
 pub async fn patch_task(
     State(state): State<ServerState>,
     Path(task_id): Path<String>,
     Json(payload): Json<PatchTaskRequest>,
 ) -> Result<Json<TaskResponse>, Json<ErrorResponse>> {
-    // Handle cancel action
+    // Handle cancel and continue actions
     if let Some(action) = payload.action {
         if action == "cancel" {
             match state.runtime().cancel_task(&task_id).await {
@@ -124,6 +213,25 @@ pub async fn patch_task(
                     )));
                 }
             }
+        } else if action == "continue" {
+            if let Err(e) = state.runtime().continue_task(&task_id).await {
+                return Err(Json(ErrorResponse::new(
+                    "BAD_REQUEST",
+                    format!("Failed to continue task: {}", e),
+                )));
+            }
+        }
+    }
+
+    // Reorder a queued task
+    if let Some(priority) = payload.priority {
+        if let Err(e) = state.runtime().set_task_priority(&task_id, priority) {
+            return Err(Json(ErrorResponse::new("BAD_REQUEST", e)));
+        }
+    }
+    if let Some(position) = payload.position {
+        if let Err(e) = state.runtime().move_queued_task(&task_id, position) {
+            return Err(Json(ErrorResponse::new("BAD_REQUEST", e)));
         }
     }
 
@@ -149,11 +257,52 @@ pub async fn patch_task(
     }
 }
 
+/// Cancel a queued or running task
+pub async fn cancel_task(
+    State(state): State<ServerState>,
+    Path(task_id): Path<String>,
+) -> Result<Json<TaskResponse>, Json<ErrorResponse>> {
+    let Some(handle) = state.runtime().get_task(&task_id).await else {
+        return Err(Json(ErrorResponse::new(
+            "NOT_FOUND",
+            format!("Task '{}' not found", task_id),
+        )));
+    };
+    if let Err(e) = state.runtime().cancel_task(&task_id).await {
+        return Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to cancel task: {}", e),
+        )));
+    }
+
+    let state_guard = handle.state.read().await;
+    Ok(Json(TaskResponse {
+        id: handle.task_id.clone(),
+        session_id: handle.session_id.clone(),
+        agent_id: None,
+        state: format!("{:?}", *state_guard).to_lowercase(),
+        created_at: chrono::Utc::now().timestamp(),
+        started_at: None,
+        completed_at: None,
+        error_message: None,
+    }))
+}
+
 /// List active tasks
 pub async fn list_tasks(
     State(state): State<ServerState>,
+    user: RequestUser,
 ) -> Result<Json<Vec<TaskResponse>>, Json<ErrorResponse>> {
-    let tasks = state.runtime().list_active_tasks().await;
+    let mut tasks = vec![];
+    for handle in state.runtime().list_active_tasks().await {
+        if user
+            .can_access(&state, &handle.session_id)
+            .await
+            .unwrap_or(false)
+        {
+            tasks.push(handle);
+        }
+    }
 
     let responses: Vec<TaskResponse> = tasks
         .into_iter()
@@ -176,3 +325,28 @@ pub async fn list_tasks(
 
     Ok(Json(responses))
 }
+
+/// List queued tasks in start order
+pub async fn list_queue(
+    State(state): State<ServerState>,
+    user: RequestUser,
+) -> Result<Json<Vec<QueuedTask>>, Json<ErrorResponse>> {
+    let tasks = state.runtime().list_queued_tasks();
+    if user.0.is_none() {
+        return Ok(Json(tasks));
+    }
+    let mut queued = vec![];
+    for task in tasks {
+        let Some(handle) = state.runtime().get_task(&task.task_id).await else {
+            continue;
+        };
+        if user
+            .can_access(&state, &handle.session_id)
+            .await
+            .unwrap_or(false)
+        {
+            queued.push(task);
+        }
+    }
+    Ok(Json(queued))
+}
@@ -0,0 +1,28 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::server::state::ServerState;
+use crate::server::types::*;
+
+/// Todo list the agent keeps for a session, with its progress
+pub async fn get_todos(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<TodosResponse>, Json<ErrorResponse>> {
+    match state.runtime().get_todos(&session_id).await {
+        Ok(list) => {
+            let (completed, total) = list.progress();
+            Ok(Json(TodosResponse {
+                session_id: list.session_id,
+                todos: list.items,
+                completed,
+                total,
+                updated_at: list.updated_at,
+            }))
+        }
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to get todos: {}", e),
+        ))),
+    }
+}
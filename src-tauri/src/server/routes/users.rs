@@ -0,0 +1,48 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::server::state::ServerState;
+use crate::server::types::*;
+use crate::storage::models::User;
+
+/// Create a user; give them access with an API key created for them
+pub async fn create_user(
+    State(state): State<ServerState>,
+    Json(payload): Json<CreateUserRequest>,
+) -> Result<Json<User>, Json<ErrorResponse>> {
+    match state.runtime().create_user(&payload.name).await {
+        Ok(user) => Ok(Json(user)),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
+
+/// List users
+pub async fn list_users(
+    State(state): State<ServerState>,
+) -> Result<Json<Vec<User>>, Json<ErrorResponse>> {
+    match state.runtime().list_users().await {
+        Ok(users) => Ok(Json(users)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list users: {}", e),
+        ))),
+    }
+}
+
+/// Delete a user and revoke their API keys; their sessions are kept
+pub async fn delete_user(
+    State(state): State<ServerState>,
+    Path(user_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Json<ErrorResponse>> {
+    match state.runtime().delete_user(&user_id).await {
+        Ok(true) => Ok(Json(serde_json::json!({ "success": true }))),
+        Ok(false) => Err(Json(ErrorResponse::new(
+            "NOT_FOUND",
+            format!("User not found: {}", user_id),
+        ))),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to delete user: {}", e),
+        ))),
+    }
+}
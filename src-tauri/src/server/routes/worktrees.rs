@@ -0,0 +1,48 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use crate::git::worktree::MergeResult;
+use crate::server::state::ServerState;
+use crate::server::types::*;
+use crate::storage::models::TaskWorktree;
+
+/// List the worktrees of a session's isolated tasks awaiting merge or discard
+pub async fn list_task_worktrees(
+    State(state): State<ServerState>,
+    Path(session_id): Path<String>,
+) -> Result<Json<Vec<TaskWorktree>>, Json<ErrorResponse>> {
+    match state.runtime().list_task_worktrees(&session_id).await {
+        Ok(worktrees) => Ok(Json(worktrees)),
+        Err(e) => Err(Json(ErrorResponse::new(
+            "INTERNAL_ERROR",
+            format!("Failed to list task worktrees: {}", e),
+        ))),
+    }
+}
+
+/// Merge a finished isolated task's branch into the main branch
+pub async fn merge_task_worktree(
+    State(state): State<ServerState>,
+    Path(task_id): Path<String>,
+    Json(payload): Json<MergeTaskWorktreeRequest>,
+) -> Result<Json<MergeResult>, Json<ErrorResponse>> {
+    match state
+        .runtime()
+        .merge_task_worktree(&task_id, payload.commit_message)
+        .await
+    {
+        Ok(result) => Ok(Json(result)),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
+
+/// Remove a finished isolated task's worktree and branch, dropping its changes
+pub async fn discard_task_worktree(
+    State(state): State<ServerState>,
+    Path(task_id): Path<String>,
+) -> Result<Json<serde_json::Value>, Json<ErrorResponse>> {
+    match state.runtime().discard_task_worktree(&task_id).await {
+        Ok(()) => Ok(Json(serde_json::json!({ "success": true }))),
+        Err(e) => Err(Json(ErrorResponse::new("BAD_REQUEST", e))),
+    }
+}
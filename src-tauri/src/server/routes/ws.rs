@@ -1,66 +1,361 @@
 //! WebSocket route for bidirectional communication
 //!
-//! Provides real-time updates and remote channel edits
+//! Provides real-time updates and remote channel edits. A connection may
+//! subscribe to several sessions; every runtime event of a subscribed session,
+//! or of the categories named in the subscription, is forwarded tagged with
+//! its session and event log sequence. Clients on
+//! slow links may pass `?compression=gzip` (or `deflate`) to receive binary
+//! frames holding the compressed JSON messages, and `?delta=true` to receive
+//! large events as deltas of the previous event of their type. With
+//! `?format=msgpack` messages in both directions are MessagePack binary
+//! frames, which are cheaper than JSON for large events such as file diffs
+//! and images.
+//!
+//! Slow clients may pass `?ackWindow=N` and acknowledge the sequence of each
+//! event they handled. At most N event frames are then unacknowledged; later
+//! events wait on the server and go out as one bundle once acks make room.
 
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
-use axum::extract::State;
+use axum::extract::{Query, State};
 use axum::response::IntoResponse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
 
+use crate::core::event_log::LoggedEvent;
+use crate::core::types::RuntimeEvent;
+use crate::security::scope::RequestUser;
 use crate::server::state::ServerState;
-use crate::server::types::{WebSocketMessage, WebSocketResponse};
+use crate::server::types::{StreamQuery, WebSocketMessage, WebSocketResponse};
+use crate::storage::models::SessionId;
+use crate::streaming::compression::compress_message;
+use crate::streaming::{
+    ConsumerLag, DeltaEncoder, EventCategory, StreamEncoding, SubscriberHandle, SubscriberTransport,
+};
 
 /// WebSocket handler
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
+    user: RequestUser,
+    Query(query): Query<StreamQuery>,
     State(state): State<ServerState>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    ws.on_upgrade(move |socket| handle_socket(socket, state, user, query))
 }
 
-/// Handle WebSocket connection
-async fn handle_socket(mut socket: WebSocket, _state: ServerState) {
-    // In a full implementation, this would:
-    // 1. Authenticate the connection
-    // 2. Maintain a map of session subscriptions
-    // 3. Forward runtime events to subscribed clients
-    // 4. Handle incoming messages (subscribe, unsubscribe, actions)
-
-    // Placeholder: echo back ping messages
-    while let Some(Ok(msg)) = socket.recv().await {
-        match msg {
-            Message::Text(text) => {
-                // Parse and handle message
-                match serde_json::from_str::<WebSocketMessage>(&text) {
-                    Ok(WebSocketMessage::Ping) => {
-                        let response = WebSocketResponse::Pong;
-                        let _ = socket
-                            .send(Message::Text(serde_json::to_string(&response).unwrap()))
-                            .await;
-                    }
-                    Ok(WebSocketMessage::Subscribe { session_id }) => {
-                        let response = WebSocketResponse::Subscribed { session_id };
-                        let _ = socket
-                            .send(Message::Text(serde_json::to_string(&response).unwrap()))
-                            .await;
-                    }
-                    Ok(WebSocketMessage::Unsubscribe { session_id }) => {
-                        let response = WebSocketResponse::Unsubscribed { session_id };
-                        let _ = socket
-                            .send(Message::Text(serde_json::to_string(&response).unwrap()))
-                            .await;
+/// Serialization of WebSocket messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameFormat {
+    /// JSON text frames
+    Json,
+    /// MessagePack binary frames, with named fields like the JSON messages
+    MessagePack,
+}
+
+impl FrameFormat {
+    fn parse(name: Option<&str>) -> Self {
+        match name.map(|name| name.trim().to_ascii_lowercase()).as_deref() {
+            Some("msgpack") | Some("messagepack") => Self::MessagePack,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// Events held for a connection in ack mode before some are dropped
+const MAX_ACK_BACKLOG: usize = 1000;
+
+/// Flow control of a connection in ack mode
+struct FlowControl {
+    /// Most unacknowledged event frames
+    window: usize,
+    /// Sequences of sent frames not yet acknowledged
+    in_flight: VecDeque<i64>,
+    /// Events waiting for room in the window
+    backlog: VecDeque<(i64, WebSocketResponse)>,
+    /// Events dropped from a full backlog
+    dropped: usize,
+}
+
+impl FlowControl {
+    fn new(window: usize) -> Self {
+        Self {
+            window,
+            in_flight: VecDeque::new(),
+            backlog: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.in_flight.len() < self.window
+    }
+}
+
+/// State of one WebSocket connection
+struct Connection {
+    socket: WebSocket,
+    state: ServerState,
+    /// User whose sessions the connection may subscribe to
+    user: RequestUser,
+    format: FrameFormat,
+    encoding: Option<StreamEncoding>,
+    deltas: Option<DeltaEncoder>,
+    /// Sessions whose events are forwarded, with the categories wanted
+    subscriptions: HashMap<SessionId, Option<HashSet<EventCategory>>>,
+    flow: Option<FlowControl>,
+    /// Entry of the connection in the streaming stats
+    subscriber: SubscriberHandle,
+}
+
+impl Connection {
+    /// Send a response in the negotiated format, compressed into a binary
+    /// frame when negotiated
+    async fn send(&mut self, response: &WebSocketResponse) -> Result<(), String> {
+        let payload = match self.format {
+            FrameFormat::Json => serde_json::to_vec(response).map_err(|e| e.to_string()),
+            FrameFormat::MessagePack => {
+                rmp_serde::to_vec_named(response).map_err(|e| e.to_string())
+            }
+        }
+        .map_err(|e| format!("Failed to serialize response: {}", e))?;
+        let message = match (self.encoding, self.format) {
+            (Some(encoding), _) => Message::Binary(compress_message(encoding, &payload)?),
+            (None, FrameFormat::MessagePack) => Message::Binary(payload),
+            (None, FrameFormat::Json) => Message::Text(
+                String::from_utf8(payload).map_err(|e| format!("Invalid response: {}", e))?,
+            ),
+        };
+        self.socket
+            .send(message)
+            .await
+            .map_err(|e| format!("Failed to send response: {}", e))
+    }
+
+    /// Answer a client message
+    async fn handle_message(&mut self, message: Message) -> Result<(), String> {
+        let parsed = match (self.format, message) {
+            (_, Message::Text(text)) => serde_json::from_str::<WebSocketMessage>(&text).ok(),
+            (FrameFormat::MessagePack, Message::Binary(bytes)) => {
+                rmp_serde::from_slice(&bytes).ok()
+            }
+            _ => return Ok(()),
+        };
+        let response = match parsed {
+            Some(WebSocketMessage::Ping) => WebSocketResponse::Pong,
+            Some(WebSocketMessage::Subscribe { session_id, events }) => {
+                let session = match self.user.can_access(&self.state, &session_id).await {
+                    Ok(true) => {
+                        self.state
+                            .storage()
+                            .chat_history
+                            .get_session(&session_id)
+                            .await
                     }
-                    Err(_) => {
-                        let response = WebSocketResponse::Error {
-                            message: "Invalid message format".to_string(),
-                        };
-                        let _ = socket
-                            .send(Message::Text(serde_json::to_string(&response).unwrap()))
-                            .await;
+                    Ok(false) => Ok(None),
+                    Err(e) => Err(e),
+                };
+                match session {
+                    Ok(Some(_)) => {
+                        self.subscriptions.insert(session_id.clone(), events);
+                        self.subscriber
+                            .set_sessions(self.subscriptions.keys().cloned().collect());
+                        WebSocketResponse::Subscribed { session_id }
                     }
+                    Ok(None) => WebSocketResponse::Error {
+                        message: format!("Session not found: {}", session_id),
+                    },
+                    Err(e) => WebSocketResponse::Error { message: e },
                 }
             }
-            Message::Close(_) => break,
-            _ => {}
+            Some(WebSocketMessage::Unsubscribe { session_id }) => {
+                self.subscriptions.remove(&session_id);
+                self.subscriber
+                    .set_sessions(self.subscriptions.keys().cloned().collect());
+                WebSocketResponse::Unsubscribed { session_id }
+            }
+            Some(WebSocketMessage::Ack { sequence }) => return self.acknowledge(sequence).await,
+            None => WebSocketResponse::Error {
+                message: "Invalid message format".to_string(),
+            },
+        };
+        self.send(&response).await
+    }
+
+    /// Forward an event when its session is subscribed
+    async fn forward(&mut self, logged: LoggedEvent) -> Result<(), String> {
+        let Some(session_id) = logged.session_id.filter(|session_id| {
+            self.subscriptions
+                .get(session_id)
+                .is_some_and(|categories| {
+                    categories.as_ref().is_none_or(|categories| {
+                        category(&logged.event)
+                            .is_none_or(|category| categories.contains(&category))
+                    })
+                })
+        }) else {
+            return Ok(());
+        };
+
+        let delta = self.deltas.as_mut().and_then(|deltas| {
+            let payload = serde_json::to_value(&logged.event).ok()?;
+            let event_type = payload["type"].as_str()?.to_string();
+            let base_id = logged.sequence.map(|s| s.to_string()).unwrap_or_default();
+            deltas.encode(&event_type, &base_id, &payload.to_string())
+        });
+        let response = WebSocketResponse::RuntimeEvent {
+            session_id,
+            sequence: logged.sequence,
+            event: delta.is_none().then_some(logged.event),
+            delta,
+        };
+
+        // Events that were not logged have no sequence to acknowledge
+        let (Some(flow), Some(sequence)) = (self.flow.as_mut(), logged.sequence) else {
+            return self.send(&response).await;
+        };
+        if flow.is_open() && flow.backlog.is_empty() {
+            flow.in_flight.push_back(sequence);
+            return self.send(&response).await;
+        }
+        if flow.backlog.len() >= MAX_ACK_BACKLOG {
+            flow.backlog.pop_front();
+            flow.dropped += 1;
+            self.subscriber.record_dropped(1);
+        }
+        flow.backlog.push_back((sequence, response));
+        Ok(())
+    }
+
+    /// Handle a client's ack of every event up to a sequence, sending the
+    /// events that waited for room in the window as one bundle
+    async fn acknowledge(&mut self, sequence: i64) -> Result<(), String> {
+        let Some(flow) = self.flow.as_mut() else {
+            return Ok(());
+        };
+        flow.in_flight.retain(|sent| *sent > sequence);
+        if !flow.is_open() || flow.backlog.is_empty() {
+            return Ok(());
+        }
+
+        let dropped = std::mem::take(&mut flow.dropped);
+        let (sequences, events): (Vec<i64>, Vec<WebSocketResponse>) =
+            flow.backlog.drain(..).unzip();
+        let last = sequences.last().copied();
+        flow.in_flight.extend(last);
+
+        if dropped > 0 {
+            let response = WebSocketResponse::Error {
+                message: format!("Missed {} events; reload them from the event log", dropped),
+            };
+            self.send(&response).await?;
+        }
+        self.send(&WebSocketResponse::Bundle {
+            sequence: last,
+            events,
+        })
+        .await
+    }
+
+    /// Record how far behind the client is, counting events waiting for acks
+    fn record_lag(&self, queued: usize, write_latency: Duration) {
+        let backlog = self.flow.as_ref().map_or(0, |flow| flow.backlog.len());
+        self.subscriber.record_lag(&ConsumerLag {
+            queued: queued + backlog,
+            write_latency,
+        });
+    }
+}
+
+/// Category subscribers filter a runtime event by; events that need the
+/// user, such as plans and budget pauses, belong to none
+fn category(event: &RuntimeEvent) -> Option<EventCategory> {
+    match event {
+        RuntimeEvent::Token { .. } | RuntimeEvent::Reasoning { .. } => Some(EventCategory::Tokens),
+        RuntimeEvent::ToolCallStarted { .. }
+        | RuntimeEvent::ToolCallRequested { .. }
+        | RuntimeEvent::ToolOutput { .. }
+        | RuntimeEvent::ShellCommandDecided { .. }
+        | RuntimeEvent::ToolCallCompleted { .. } => Some(EventCategory::Tools),
+        RuntimeEvent::TaskStateChanged { .. }
+        | RuntimeEvent::TaskQueuePositionChanged { .. }
+        | RuntimeEvent::IterationStarted { .. }
+        | RuntimeEvent::ContextCompacted { .. }
+        | RuntimeEvent::TodosUpdated { .. }
+        | RuntimeEvent::WorktreeReady { .. }
+        | RuntimeEvent::SecretsRedacted { .. }
+        | RuntimeEvent::SessionUpdated { .. } => Some(EventCategory::Status),
+        RuntimeEvent::Usage { .. } => Some(EventCategory::Usage),
+        RuntimeEvent::MessageCreated { .. }
+        | RuntimeEvent::BudgetExceeded { .. }
+        | RuntimeEvent::PlanReady { .. }
+        | RuntimeEvent::Error { .. }
+        | RuntimeEvent::TaskCompleted { .. } => None,
+    }
+}
+
+/// Handle WebSocket connection
+async fn handle_socket(
+    socket: WebSocket,
+    state: ServerState,
+    user: RequestUser,
+    query: StreamQuery,
+) {
+    let mut events = state.runtime().subscribe_events();
+    let subscriber = state
+        .streaming()
+        .read()
+        .await
+        .throttler
+        .metrics()
+        .register(SubscriberTransport::WebSocket, Vec::new());
+    let mut connection = Connection {
+        socket,
+        state,
+        user,
+        format: FrameFormat::parse(query.format.as_deref()),
+        encoding: query.compression.as_deref().and_then(StreamEncoding::parse),
+        deltas: query.delta.then(DeltaEncoder::new),
+        subscriptions: HashMap::new(),
+        flow: query
+            .ack_window
+            .filter(|window| *window > 0)
+            .map(FlowControl::new),
+        subscriber,
+    };
+
+    let shutdown = connection.state.shutdown_token().clone();
+    loop {
+        let result = tokio::select! {
+            _ = shutdown.cancelled() => {
+                let _ = connection.send(&WebSocketResponse::Shutdown).await;
+                let _ = connection.socket.send(Message::Close(None)).await;
+                break;
+            }
+            message = connection.socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(message)) => connection.handle_message(message).await,
+            },
+            event = events.recv() => match event {
+                Ok(logged) => {
+                    let started = Instant::now();
+                    let result = connection.forward(logged).await;
+                    connection.record_lag(events.len(), started.elapsed());
+                    result
+                }
+                // Missed events can be read back from the session's event log
+                Err(RecvError::Lagged(skipped)) => {
+                    connection.subscriber.record_dropped(skipped);
+                    let response = WebSocketResponse::Error {
+                        message: format!("Missed {} events; reload them from the event log", skipped),
+                    };
+                    connection.send(&response).await
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+        if let Err(e) = result {
+            log::debug!("Closing WebSocket connection: {}", e);
+            break;
         }
     }
 }
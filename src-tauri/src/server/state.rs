@@ -1,18 +1,40 @@
+use crate::core::cancellation::CancellationToken;
 use crate::core::CoreRuntime;
+use crate::integrations::router::ChatRouting;
 use crate::platform::Platform;
+use crate::security::rate_limit::{RateLimitConfig, RateLimiter, RATE_LIMITS_KEY};
+use crate::server::idempotency::IdempotencyKeys;
 use crate::storage::Storage;
-use crate::streaming::StreamingManager;
+use crate::streaming::{StreamingManager, ThrottleConfig, ThrottlePolicies, THROTTLE_POLICIES_KEY};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
+use tauri::AppHandle;
 use tokio::sync::RwLock;
 
+/// How often the server applies the session retention policy
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 /// Server state shared across all request handlers
 #[derive(Clone)]
 pub struct ServerState {
     pub config: super::config::ServerConfig,
     pub runtime: CoreRuntime,
     pub storage: Storage,
+    /// Routes messages from IM chats to the runtime
+    pub routing: ChatRouting,
     pub platform: Platform,
     pub streaming: Arc<RwLock<StreamingManager>>,
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Idempotency keys of requests being handled
+    pub idempotency_keys: Arc<IdempotencyKeys>,
+    /// Cancelled when the server starts shutting down
+    pub shutdown: CancellationToken,
+    /// Desktop app embedding the server, whose LSP servers and gateways
+    /// diagnostics report; None for a standalone server
+    pub app: Option<AppHandle>,
+    /// Address the server listens on, once bound
+    pub addr: Option<SocketAddr>,
 }
 
 impl ServerState {
@@ -20,19 +42,44 @@ impl ServerState {
         config: super::config::ServerConfig,
         runtime: CoreRuntime,
         storage: Storage,
+        throttle: ThrottleConfig,
+        rate_limits: RateLimitConfig,
     ) -> Self {
         let platform = Platform::new();
-        let streaming = Arc::new(RwLock::new(StreamingManager::new()));
+        let routing = ChatRouting::new(runtime.clone(), &storage);
+        let streaming = Arc::new(RwLock::new(
+            StreamingManager::new()
+                .with_storage(Arc::new(storage.clone()))
+                .with_throttle_config(throttle),
+        ));
 
         Self {
             config,
             runtime,
             storage,
+            routing,
             platform,
             streaming,
+            rate_limiter: Arc::new(RateLimiter::new(rate_limits)),
+            idempotency_keys: Arc::new(IdempotencyKeys::new()),
+            shutdown: CancellationToken::new(),
+            app: None,
+            addr: None,
         }
     }
 
+    /// Report the LSP servers and gateways of the app embedding the server
+    pub fn with_app(mut self, app: AppHandle) -> Self {
+        self.app = Some(app);
+        self
+    }
+
+    /// Record the address the server listens on, for pairing clients
+    pub fn with_addr(mut self, addr: SocketAddr) -> Self {
+        self.addr = Some(addr);
+        self
+    }
+
     /// Get the runtime reference
     pub fn runtime(&self) -> &CoreRuntime {
         &self.runtime
@@ -43,6 +90,11 @@ impl ServerState {
         &self.storage
     }
 
+    /// Get the routing of IM chats
+    pub fn routing(&self) -> &ChatRouting {
+        &self.routing
+    }
+
     /// Get the platform reference
     pub fn platform(&self) -> &Platform {
         &self.platform
@@ -52,6 +104,26 @@ impl ServerState {
     pub fn streaming(&self) -> Arc<RwLock<StreamingManager>> {
         self.streaming.clone()
     }
+
+    /// Get the request rate limiter
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// Get the idempotency keys of requests being handled
+    pub fn idempotency_keys(&self) -> &IdempotencyKeys {
+        &self.idempotency_keys
+    }
+
+    /// Get the app embedding the server
+    pub fn app(&self) -> Option<&AppHandle> {
+        self.app.as_ref()
+    }
+
+    /// Get the token cancelled when the server starts shutting down
+    pub fn shutdown_token(&self) -> &CancellationToken {
+        &self.shutdown
+    }
 }
 
 /// Factory for creating server state with all dependencies
@@ -61,6 +133,7 @@ impl ServerStateFactory {
     /// Create server state with the given configuration
     pub async fn create(
         config: super::config::ServerConfig,
+        llm: Arc<dyn crate::core::LlmClient>,
         event_sender: crate::core::types::EventSender,
     ) -> Result<ServerState, String> {
         // Create storage
@@ -68,8 +141,35 @@ impl ServerStateFactory {
             Storage::new(config.data_root.clone(), config.attachments_root.clone()).await?;
 
         // Create runtime
-        let runtime = CoreRuntime::new(storage.clone(), event_sender).await?;
+        let runtime = CoreRuntime::new(storage.clone(), llm, event_sender).await?;
+        runtime.set_max_concurrent_tasks(config.max_concurrent_tasks);
+        runtime.spawn_maintenance(RETENTION_INTERVAL);
+
+        let policies = storage
+            .settings
+            .get_setting_or_default(THROTTLE_POLICIES_KEY, ThrottlePolicies::default())
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to load stream throttle policies: {}", e);
+                ThrottlePolicies::default()
+            });
+        let throttle = ThrottleConfig {
+            policies,
+            ..ThrottleConfig::default()
+        };
+
+        let rate_limits = storage
+            .settings
+            .get_setting_or_default(RATE_LIMITS_KEY, RateLimitConfig::default())
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to load server rate limits: {}", e);
+                RateLimitConfig::default()
+            });
 
-        Ok(ServerState::new(config, runtime, storage))
+        // SSE clients stream the runtime's events
+        let state = ServerState::new(config, runtime, storage, throttle, rate_limits);
+        super::event_bridge::spawn(state.runtime.subscribe_events(), state.streaming());
+        Ok(state)
     }
 }
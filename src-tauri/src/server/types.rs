@@ -2,10 +2,18 @@
 //!
 //! Request and response types for the REST API
 
-use crate::core::types::RuntimeTaskState;
+use crate::core::metrics::RecentError;
+use crate::core::scheduler::QueuedTask;
+use crate::core::types::{RuntimeEvent, RuntimeTaskState};
+use crate::lsp::LspServerDiagnostics;
+use crate::security::pairing::Pairing;
+use crate::security::rate_limit::{RateLimitConfig, RateLimitViolation};
 use crate::storage::models::*;
+use crate::storage::pagination::SortOrder;
+use crate::streaming::compression::PayloadDelta;
+use crate::streaming::EventCategory;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 // ============== Session Types ==============
 
@@ -30,11 +38,15 @@ pub struct SessionResponse {
     pub id: SessionId,
     pub project_id: Option<String>,
     pub title: Option<String>,
+    pub summary: Option<String>,
     pub status: SessionStatus,
     pub created_at: i64,
     pub updated_at: i64,
     pub last_event_id: Option<String>,
     pub metadata: Option<serde_json::Value>,
+    pub starred: bool,
+    pub archived_at: Option<i64>,
+    pub user_id: Option<String>,
 }
 
 impl From<Session> for SessionResponse {
@@ -43,11 +55,15 @@ impl From<Session> for SessionResponse {
             id: session.id,
             project_id: session.project_id,
             title: session.title,
+            summary: session.summary,
             status: session.status,
             created_at: session.created_at,
             updated_at: session.updated_at,
             last_event_id: session.last_event_id,
             metadata: session.metadata,
+            starred: session.starred,
+            archived_at: session.archived_at,
+            user_id: session.user_id,
         }
     }
 }
@@ -57,8 +73,30 @@ impl From<Session> for SessionResponse {
 pub struct ListSessionsQuery {
     pub project_id: Option<String>,
     pub status: Option<String>,
+    /// List archived sessions instead of active ones
+    #[serde(default)]
+    pub archived: bool,
     pub limit: Option<usize>,
-    pub offset: Option<usize>,
+    /// `nextCursor` of the previous page
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub sort: SessionSort,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSessionsQuery {
+    pub q: String,
+    pub project_id: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StarSessionRequest {
+    pub starred: bool,
 }
 
 // ============== Message Types ==============
@@ -68,12 +106,21 @@ pub struct ListSessionsQuery {
 pub struct CreateMessageRequest {
     pub content: String,
     pub role: Option<String>, // Defaults to "user"
+    /// Agent and settings of the task a user message starts
+    pub agent_id: Option<AgentId>,
+    pub settings: Option<TaskSettings>,
 }
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateMessageResponse {
-    pub message_id: MessageId,
+    /// Set for messages stored right away; a user message is stored by its
+    /// task once the task starts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<MessageId>,
+    /// Task enqueued for a user message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
     pub created_at: i64,
 }
 
@@ -87,6 +134,7 @@ pub struct MessageResponse {
     pub created_at: i64,
     pub tool_call_id: Option<String>,
     pub parent_id: Option<String>,
+    pub pinned: bool,
 }
 
 impl From<Message> for MessageResponse {
@@ -99,6 +147,7 @@ impl From<Message> for MessageResponse {
             created_at: message.created_at,
             tool_call_id: message.tool_call_id,
             parent_id: message.parent_id,
+            pinned: message.pinned,
         }
     }
 }
@@ -108,6 +157,27 @@ impl From<Message> for MessageResponse {
 pub struct ListMessagesQuery {
     pub limit: Option<usize>,
     pub before_id: Option<String>,
+    /// Only messages created at or after this time, oldest first
+    pub since: Option<i64>,
+}
+
+/// A message along with the tool calls it made and their results
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageDetailResponse {
+    #[serde(flatten)]
+    pub message: MessageResponse,
+    pub tool_calls: Vec<ToolCallResponse>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallResponse {
+    pub id: ToolCallId,
+    pub name: String,
+    pub input: serde_json::Value,
+    /// Tool result message; None while the call is pending
+    pub result: Option<MessageResponse>,
 }
 
 // ============== Task Types ==============
@@ -121,6 +191,22 @@ pub struct CreateTaskRequest {
     pub initial_message: String,
     pub settings: Option<TaskSettings>,
     pub workspace: Option<WorkspaceInfoRequest>,
+    pub priority: Option<i32>,
+    /// Run the task in a git worktree on its own branch
+    pub isolate: Option<bool>,
+}
+
+/// Task started in the session named by the route
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartTaskRequest {
+    pub agent_id: Option<AgentId>,
+    pub initial_message: String,
+    pub settings: Option<TaskSettings>,
+    pub workspace: Option<WorkspaceInfoRequest>,
+    pub priority: Option<i32>,
+    /// Run the task in a git worktree on its own branch
+    pub isolate: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -158,7 +244,11 @@ pub struct TaskResponse {
 #[serde(rename_all = "camelCase")]
 pub struct PatchTaskRequest {
     pub settings: Option<TaskSettings>,
-    pub action: Option<String>, // "cancel"
+    pub action: Option<String>, // "cancel", "continue"
+    /// New queue priority of a queued task
+    pub priority: Option<i32>,
+    /// New zero-based queue position of a queued task
+    pub position: Option<usize>,
 }
 
 // ============== Action Types ==============
@@ -179,6 +269,246 @@ pub struct CreateActionResponse {
     pub message: String,
 }
 
+// ============== Approval Types ==============
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DenyToolCallRequest {
+    pub reason: Option<String>,
+}
+
+/// Decision applied to every tool call waiting for approval in a session
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolveApprovalsRequest {
+    pub approved: bool,
+    /// Reason shown to the model for denied calls
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolInputRequest {
+    pub input: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolApprovalResponse {
+    pub tool_call_id: ToolCallId,
+    pub task_id: String,
+    pub session_id: SessionId,
+    pub approved: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinMessageRequest {
+    pub pinned: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinMessageResponse {
+    pub message_id: MessageId,
+    pub pinned: bool,
+}
+
+// ============== Worktree Types ==============
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeTaskWorktreeRequest {
+    /// Message for committing changes left uncommitted in the worktree
+    pub commit_message: Option<String>,
+}
+
+// ============== Todo Types ==============
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TodosResponse {
+    pub session_id: SessionId,
+    pub todos: Vec<TodoItem>,
+    /// Items completed
+    pub completed: usize,
+    /// Items not cancelled
+    pub total: usize,
+    /// Zero when the agent has not written a list
+    pub updated_at: i64,
+}
+
+// ============== API Key Types ==============
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyRequest {
+    /// Describes the client the key is for
+    pub name: String,
+    /// User the key belongs to; the key reaches every session when absent
+    pub user_id: Option<String>,
+    /// Scopes the key grants; every scope when absent
+    pub scopes: Option<Vec<ApiKeyScope>>,
+}
+
+// ============== User Types ==============
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateUserRequest {
+    pub name: String,
+}
+
+// ============== Project Types ==============
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateProjectRequest {
+    pub name: String,
+    /// Existing directory tasks of the project run in
+    pub workspace_path: String,
+    pub default_settings: Option<TaskSettings>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateProjectRequest {
+    pub name: Option<String>,
+    pub workspace_path: Option<String>,
+    pub default_settings: Option<TaskSettings>,
+    /// Clear the default settings
+    #[serde(default)]
+    pub clear_default_settings: bool,
+}
+
+// ============== Diagnostics Types ==============
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsQuery {
+    /// Most recent errors to return
+    pub errors: Option<usize>,
+}
+
+/// State of the runtime and the app embedding the server, for a local
+/// diagnostics page
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsResponse {
+    pub queue: QueueDiagnostics,
+    /// LSP servers of the app; empty for a standalone server
+    pub lsp_servers: Vec<LspServerDiagnostics>,
+    /// IM gateways of the app; empty for a standalone server
+    pub integrations: Vec<IntegrationDiagnostics>,
+    pub subscribers: SubscriberCounts,
+    /// Latest LLM, tool and task errors, newest first
+    pub recent_errors: Vec<RecentError>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueDiagnostics {
+    pub max_concurrent_tasks: usize,
+    /// Tasks running, waiting for the user or queued
+    pub active_tasks: Vec<ActiveTaskDiagnostics>,
+    /// Queued tasks in start order
+    pub queued_tasks: Vec<QueuedTask>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveTaskDiagnostics {
+    pub task_id: String,
+    pub session_id: SessionId,
+    pub state: RuntimeTaskState,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrationDiagnostics {
+    pub name: String,
+    pub running: bool,
+    pub last_error: Option<String>,
+    pub last_error_at_ms: Option<i64>,
+}
+
+/// Connected stream subscribers by transport
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriberCounts {
+    pub sse: usize,
+    pub websocket: usize,
+}
+
+// ============== Auth Types ==============
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginRequest {
+    pub api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
+// ============== Pairing Types ==============
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePairingRequest {
+    /// User the paired device's key will belong to; the owner when unset
+    pub user_id: Option<String>,
+}
+
+/// A pairing code with the addresses a companion client reaches the server
+/// at, and a QR code of the first of them
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingResponse {
+    #[serde(flatten)]
+    pub pairing: Pairing,
+    pub hosts: Vec<std::net::IpAddr>,
+    pub port: u16,
+    pub uri: String,
+    pub qr_svg: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairDeviceRequest {
+    pub code: String,
+    pub device_name: String,
+}
+
+// ============== Stats Types ==============
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeStatsQuery {
+    /// Only report the tasks of this session
+    pub session_id: Option<SessionId>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitsResponse {
+    pub limits: RateLimitConfig,
+    pub violations: Vec<RateLimitViolation>,
+}
+
+// ============== Event Log Types ==============
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionEventLogQuery {
+    /// Only return events after this sequence
+    pub after: Option<i64>,
+    pub limit: Option<usize>,
+}
+
 // ============== File Types ==============
 
 #[derive(Debug, Serialize)]
@@ -283,17 +613,44 @@ pub struct ErrorEventData {
     pub task_id: Option<String>,
 }
 
+/// Options of the SSE and WebSocket streams
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamQuery {
+    /// Send large payloads as deltas of the previous payload of their type
+    #[serde(default)]
+    pub delta: bool,
+    /// Compression of WebSocket frames (`gzip` or `deflate`), as browsers
+    /// cannot set Accept-Encoding on WebSocket requests
+    pub compression: Option<String>,
+    /// Serialization of WebSocket messages: `json` (default) or `msgpack`
+    pub format: Option<String>,
+    /// Most WebSocket events sent before the client acknowledges them
+    pub ack_window: Option<usize>,
+    /// Comma-separated event categories of an SSE stream, such as
+    /// `tools,status`; all events are sent when absent
+    pub events: Option<String>,
+}
+
 // ============== WebSocket Types ==============
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "action", rename_all = "camelCase")]
 pub enum WebSocketMessage {
+    /// Subscribe to a session's events, optionally only to some categories
     #[serde(rename = "subscribe")]
-    Subscribe { session_id: SessionId },
+    Subscribe {
+        session_id: SessionId,
+        #[serde(default)]
+        events: Option<HashSet<EventCategory>>,
+    },
     #[serde(rename = "unsubscribe")]
     Unsubscribe { session_id: SessionId },
     #[serde(rename = "ping")]
     Ping,
+    /// Confirm every event up to a sequence, in ack mode
+    #[serde(rename = "ack")]
+    Ack { sequence: i64 },
 }
 
 #[derive(Debug, Serialize)]
@@ -305,10 +662,32 @@ pub enum WebSocketResponse {
     Unsubscribed { session_id: SessionId },
     #[serde(rename = "event")]
     Event { event: SseEvent },
+    /// A runtime event of a subscribed session, or its delta against the
+    /// previous event of its type when the client accepts deltas
+    #[serde(rename = "runtimeEvent")]
+    RuntimeEvent {
+        session_id: SessionId,
+        /// Sequence in the session's event log
+        sequence: Option<i64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        event: Option<RuntimeEvent>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        delta: Option<PayloadDelta>,
+    },
+    /// Runtime events that waited for acks, acknowledged by the sequence of
+    /// the last one
+    #[serde(rename = "bundle")]
+    Bundle {
+        sequence: Option<i64>,
+        events: Vec<WebSocketResponse>,
+    },
     #[serde(rename = "pong")]
     Pong,
     #[serde(rename = "error")]
     Error { message: String },
+    /// Last message before the server closes the connection to shut down
+    #[serde(rename = "shutdown")]
+    Shutdown,
 }
 
 // ============== Error Response ==============
@@ -0,0 +1,105 @@
+//! Web UI
+//!
+//! Serves a web frontend from the server, so a browser on another device can
+//! use the app without the desktop shell: the frontend bundled into the app,
+//! or a built one in a directory. Paths matching no file get `index.html`,
+//! leaving routing to the single-page app; API paths are never rewritten.
+
+use axum::extract::Request;
+use axum::http::header::{CONTENT_SECURITY_POLICY, CONTENT_TYPE};
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use std::path::Path;
+use tauri::AppHandle;
+use tower_http::services::{ServeDir, ServeFile};
+
+use crate::server::config::WebUiSettings;
+
+const INDEX: &str = "index.html";
+
+/// Route prefixes of the API, which answer 404 rather than `index.html`
+const API_PREFIXES: [&str; 2] = ["/v1/", "/health"];
+
+/// Check that `directory` holds a built frontend
+pub fn validate_directory(directory: &Path) -> Result<(), String> {
+    if directory.join(INDEX).is_file() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Web UI directory has no {}: {}",
+            INDEX,
+            directory.display()
+        ))
+    }
+}
+
+/// Serve the web UI per `settings` for requests no route matches. The
+/// bundled frontend needs the desktop app.
+pub fn with_web_ui(
+    router: Router,
+    settings: &WebUiSettings,
+    app: Option<&AppHandle>,
+) -> Result<Router, String> {
+    if !settings.enabled {
+        return Ok(router);
+    }
+
+    if let Some(directory) = &settings.directory {
+        validate_directory(directory)?;
+        let files = ServeDir::new(directory)
+            .append_index_html_on_directories(true)
+            .fallback(ServeFile::new(directory.join(INDEX)));
+        return Ok(router.fallback(move |req: Request| {
+            let mut files = files.clone();
+            async move {
+                if is_api_path(req.uri().path()) {
+                    return StatusCode::NOT_FOUND.into_response();
+                }
+                match files.try_call(req).await {
+                    Ok(response) => response.into_response(),
+                    Err(e) => {
+                        log::error!("Failed to serve web UI file: {}", e);
+                        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                    }
+                }
+            }
+        }));
+    }
+
+    let Some(app) = app.cloned() else {
+        return Err("The bundled web UI needs the desktop app; set a directory".to_string());
+    };
+    Ok(router.fallback(move |req: Request| {
+        let app = app.clone();
+        async move { bundled_asset(&app, req.method(), req.uri().path()) }
+    }))
+}
+
+/// A file of the frontend bundled into the app. Tauri's resolver answers
+/// unknown paths with `index.html` itself.
+fn bundled_asset(app: &AppHandle, method: &Method, path: &str) -> Response {
+    if is_api_path(path) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if method != Method::GET && method != Method::HEAD {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+
+    let Some(asset) = app.asset_resolver().get(path.to_string()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let mut response = (
+        [(CONTENT_TYPE, asset.mime_type().to_string())],
+        asset.bytes().to_vec(),
+    )
+        .into_response();
+    if let Some(csp) = asset.csp_header().and_then(|csp| csp.parse().ok()) {
+        response.headers_mut().insert(CONTENT_SECURITY_POLICY, csp);
+    }
+    response
+}
+
+fn is_api_path(path: &str) -> bool {
+    path == "/v1" || API_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
@@ -413,6 +413,12 @@ mod tests {
                 auto_approve_edits: Some(true),
                 auto_approve_plan: Some(false),
                 auto_code_review: None,
+                plan_mode: None,
+                read_only_tools: None,
+                tool_capabilities: None,
+                agent: None,
+                models: None,
+                budget: None,
                 extra: Default::default(),
             },
             created_at: chrono::Utc::now().timestamp(),
@@ -0,0 +1,331 @@
+//! API Keys Repository
+//! Handles CRUD operations for the HTTP server's API keys, the refresh
+//! tokens issued for them and the users they belong to in settings.db
+
+use crate::database::Database;
+use crate::storage::models::{ApiKey, ApiKeyScope, User};
+use std::sync::Arc;
+
+/// An API key with the salted hash of its secret
+#[derive(Debug, Clone)]
+pub struct StoredApiKey {
+    pub key: ApiKey,
+    pub salt: String,
+    pub key_hash: String,
+}
+
+/// A refresh token issued for an API key. Tokens rotated from one another
+/// share a family, so a reused token revokes all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshToken {
+    pub id: String,
+    pub family_id: String,
+    pub api_key_id: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub revoked_at: Option<i64>,
+}
+
+/// Repository for API key operations
+#[derive(Clone)]
+pub struct ApiKeysRepository {
+    db: Arc<Database>,
+}
+
+impl ApiKeysRepository {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Create a new API key
+    pub async fn create_api_key(&self, stored: &StoredApiKey) -> Result<(), String> {
+        let sql = r#"
+            INSERT INTO api_keys (id, name, salt, key_hash, created_at, last_used_at, revoked_at, user_id, scopes)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#;
+        let scopes = serde_json::to_string(&stored.key.scopes)
+            .map_err(|e| format!("Failed to serialize API key scopes: {}", e))?;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(stored.key.id),
+                    serde_json::json!(stored.key.name),
+                    serde_json::json!(stored.salt),
+                    serde_json::json!(stored.key_hash),
+                    serde_json::json!(stored.key.created_at),
+                    serde_json::json!(stored.key.last_used_at),
+                    serde_json::json!(stored.key.revoked_at),
+                    serde_json::json!(stored.key.user_id),
+                    serde_json::json!(scopes),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get an API key with its hash by ID
+    pub async fn get_api_key(&self, key_id: &str) -> Result<Option<StoredApiKey>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM api_keys WHERE id = ?",
+                vec![serde_json::json!(key_id)],
+            )
+            .await?;
+
+        Ok(result.rows.first().map(|row| StoredApiKey {
+            key: row_to_api_key(row),
+            salt: string_field(row, "salt"),
+            key_hash: string_field(row, "key_hash"),
+        }))
+    }
+
+    /// List API keys, revoked ones included, oldest first
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM api_keys ORDER BY created_at ASC, rowid ASC",
+                vec![],
+            )
+            .await?;
+
+        Ok(result.rows.iter().map(row_to_api_key).collect())
+    }
+
+    /// Revoke an API key. Returns `false` when it does not exist or was
+    /// already revoked.
+    pub async fn revoke_api_key(&self, key_id: &str, revoked_at: i64) -> Result<bool, String> {
+        let result = self
+            .db
+            .execute(
+                "UPDATE api_keys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL",
+                vec![serde_json::json!(revoked_at), serde_json::json!(key_id)],
+            )
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Record when an API key was last used
+    pub async fn touch_api_key(&self, key_id: &str, used_at: i64) -> Result<(), String> {
+        self.db
+            .execute(
+                "UPDATE api_keys SET last_used_at = ? WHERE id = ?",
+                vec![serde_json::json!(used_at), serde_json::json!(key_id)],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    // ============== Refresh Tokens ==============
+
+    /// Record an issued refresh token
+    pub async fn create_refresh_token(&self, token: &RefreshToken) -> Result<(), String> {
+        let sql = r#"
+            INSERT INTO refresh_tokens (id, family_id, api_key_id, created_at, expires_at, revoked_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+        "#;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(token.id),
+                    serde_json::json!(token.family_id),
+                    serde_json::json!(token.api_key_id),
+                    serde_json::json!(token.created_at),
+                    serde_json::json!(token.expires_at),
+                    serde_json::json!(token.revoked_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get a refresh token by ID
+    pub async fn get_refresh_token(&self, token_id: &str) -> Result<Option<RefreshToken>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM refresh_tokens WHERE id = ?",
+                vec![serde_json::json!(token_id)],
+            )
+            .await?;
+
+        Ok(result.rows.first().map(row_to_refresh_token))
+    }
+
+    /// Revoke a refresh token. Returns `false` when it was already revoked,
+    /// so only one of two concurrent uses of a token succeeds.
+    pub async fn revoke_refresh_token(
+        &self,
+        token_id: &str,
+        revoked_at: i64,
+    ) -> Result<bool, String> {
+        let result = self
+            .db
+            .execute(
+                "UPDATE refresh_tokens SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL",
+                vec![serde_json::json!(revoked_at), serde_json::json!(token_id)],
+            )
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Revoke every refresh token of a family
+    pub async fn revoke_refresh_token_family(
+        &self,
+        family_id: &str,
+        revoked_at: i64,
+    ) -> Result<(), String> {
+        self.db
+            .execute(
+                "UPDATE refresh_tokens SET revoked_at = ? WHERE family_id = ? AND revoked_at IS NULL",
+                vec![serde_json::json!(revoked_at), serde_json::json!(family_id)],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Revoke every refresh token issued for an API key
+    pub async fn revoke_api_key_refresh_tokens(
+        &self,
+        api_key_id: &str,
+        revoked_at: i64,
+    ) -> Result<(), String> {
+        self.db
+            .execute(
+                "UPDATE refresh_tokens SET revoked_at = ? WHERE api_key_id = ? AND revoked_at IS NULL",
+                vec![serde_json::json!(revoked_at), serde_json::json!(api_key_id)],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete refresh tokens that expired before a time
+    pub async fn delete_expired_refresh_tokens(&self, before: i64) -> Result<u64, String> {
+        let result = self
+            .db
+            .execute(
+                "DELETE FROM refresh_tokens WHERE expires_at < ?",
+                vec![serde_json::json!(before)],
+            )
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+
+    // ============== Users ==============
+
+    /// Create a user
+    pub async fn create_user(&self, user: &User) -> Result<(), String> {
+        self.db
+            .execute(
+                "INSERT INTO users (id, name, created_at) VALUES (?, ?, ?)",
+                vec![
+                    serde_json::json!(user.id),
+                    serde_json::json!(user.name),
+                    serde_json::json!(user.created_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get a user by ID
+    pub async fn get_user(&self, user_id: &str) -> Result<Option<User>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM users WHERE id = ?",
+                vec![serde_json::json!(user_id)],
+            )
+            .await?;
+
+        Ok(result.rows.first().map(row_to_user))
+    }
+
+    /// List users, oldest first
+    pub async fn list_users(&self) -> Result<Vec<User>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM users ORDER BY created_at ASC, rowid ASC",
+                vec![],
+            )
+            .await?;
+
+        Ok(result.rows.iter().map(row_to_user).collect())
+    }
+
+    /// Delete a user. Returns `false` when it does not exist.
+    pub async fn delete_user(&self, user_id: &str) -> Result<bool, String> {
+        let result = self
+            .db
+            .execute(
+                "DELETE FROM users WHERE id = ?",
+                vec![serde_json::json!(user_id)],
+            )
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+}
+
+// ============== Row Conversions ==============
+
+fn string_field(row: &serde_json::Value, field: &str) -> String {
+    row.get(field)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+fn row_to_api_key(row: &serde_json::Value) -> ApiKey {
+    ApiKey {
+        id: string_field(row, "id"),
+        name: string_field(row, "name"),
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+        last_used_at: row.get("last_used_at").and_then(|v| v.as_i64()),
+        revoked_at: row.get("revoked_at").and_then(|v| v.as_i64()),
+        user_id: row
+            .get("user_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        // Keys created before scopes existed keep every scope
+        scopes: row
+            .get("scopes")
+            .and_then(|v| v.as_str())
+            .and_then(|s| serde_json::from_str(s).ok())
+            .unwrap_or_else(ApiKeyScope::all),
+    }
+}
+
+fn row_to_user(row: &serde_json::Value) -> User {
+    User {
+        id: string_field(row, "id"),
+        name: string_field(row, "name"),
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+    }
+}
+
+fn row_to_refresh_token(row: &serde_json::Value) -> RefreshToken {
+    RefreshToken {
+        id: string_field(row, "id"),
+        family_id: string_field(row, "family_id"),
+        api_key_id: string_field(row, "api_key_id"),
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+        expires_at: row.get("expires_at").and_then(|v| v.as_i64()).unwrap_or(0),
+        revoked_at: row.get("revoked_at").and_then(|v| v.as_i64()),
+    }
+}
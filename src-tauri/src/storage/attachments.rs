@@ -3,6 +3,7 @@
 //! Also manages file system operations for attachment storage
 
 use crate::database::Database;
+use crate::path_guard;
 use crate::storage::models::{Attachment, AttachmentId, AttachmentOrigin, SessionId};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -20,10 +21,20 @@ impl AttachmentsRepository {
     }
 
     /// Get the storage path for an attachment
-    fn attachment_path(&self, attachment_id: &str) -> PathBuf {
+    fn attachment_path(&self, attachment_id: &str) -> Result<PathBuf, String> {
+        std::fs::create_dir_all(&self.storage_root)
+            .map_err(|e| format!("Failed to create attachment directory: {}", e))?;
         // Use first 2 chars of ID as subdirectory to avoid too many files in one dir
         let prefix = &attachment_id[..2.min(attachment_id.len())];
-        self.storage_root.join(prefix).join(attachment_id)
+        path_guard::resolve(&self.storage_root, &Path::new(prefix).join(attachment_id))
+            .map_err(|e| format!("Invalid attachment ID {}: {}", attachment_id, e))
+    }
+
+    /// Path of a stored attachment's file, refusing paths outside the storage
+    /// root
+    fn stored_path(&self, attachment: &Attachment) -> Result<PathBuf, String> {
+        path_guard::resolve(&self.storage_root, Path::new(&attachment.path))
+            .map_err(|e| format!("Invalid path of attachment {}: {}", attachment.id, e))
     }
 
     /// Create attachment metadata record and store file
@@ -33,7 +44,7 @@ impl AttachmentsRepository {
         data: &[u8],
     ) -> Result<(), String> {
         // Ensure storage directory exists
-        let file_path = self.attachment_path(&attachment.id);
+        let file_path = self.attachment_path(&attachment.id)?;
         if let Some(parent) = file_path.parent() {
             std::fs::create_dir_all(parent)
                 .map_err(|e| format!("Failed to create attachment directory: {}", e))?;
@@ -94,7 +105,7 @@ impl AttachmentsRepository {
             None => return Ok(None),
         };
 
-        let data = std::fs::read(&attachment.path)
+        let data = std::fs::read(self.stored_path(&attachment)?)
             .map_err(|e| format!("Failed to read attachment file: {}", e))?;
 
         Ok(Some(data))
@@ -125,12 +136,14 @@ impl AttachmentsRepository {
     pub async fn delete_attachment(&self, attachment_id: &str) -> Result<(), String> {
         // Get attachment info first
         if let Some(attachment) = self.get_attachment(attachment_id).await? {
-            // Delete file
-            let _ = std::fs::remove_file(&attachment.path);
-
-            // Delete parent directory if empty
-            if let Some(parent) = Path::new(&attachment.path).parent() {
-                let _ = std::fs::remove_dir(parent);
+            if let Ok(path) = self.stored_path(&attachment) {
+                // Delete file
+                let _ = std::fs::remove_file(&path);
+
+                // Delete parent directory if empty
+                if let Some(parent) = path.parent() {
+                    let _ = std::fs::remove_dir(parent);
+                }
             }
         }
 
@@ -151,9 +164,12 @@ impl AttachmentsRepository {
         let attachments = self.list_attachments(session_id, None).await?;
 
         // Delete files
-        for attachment in attachments {
-            let _ = std::fs::remove_file(&attachment.path);
-            if let Some(parent) = Path::new(&attachment.path).parent() {
+        for path in attachments
+            .iter()
+            .filter_map(|attachment| self.stored_path(attachment).ok())
+        {
+            let _ = std::fs::remove_file(&path);
+            if let Some(parent) = path.parent() {
                 let _ = std::fs::remove_dir(parent);
             }
         }
@@ -3,6 +3,7 @@
 
 use crate::database::Database;
 use crate::storage::models::*;
+use crate::storage::pagination::{self, Page, PageRequest};
 use std::sync::Arc;
 
 /// Repository for chat history operations
@@ -21,8 +22,8 @@ impl ChatHistoryRepository {
     /// Create a new session
     pub async fn create_session(&self, session: &Session) -> Result<(), String> {
         let sql = r#"
-            INSERT INTO sessions (id, project_id, title, status, created_at, updated_at, last_event_id, metadata)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO sessions (id, project_id, title, summary, status, created_at, updated_at, last_event_id, metadata, starred, archived_at, user_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         "#;
 
         self.db
@@ -32,11 +33,15 @@ impl ChatHistoryRepository {
                     serde_json::json!(session.id),
                     serde_json::json!(session.project_id),
                     serde_json::json!(session.title),
+                    serde_json::json!(session.summary),
                     serde_json::json!(session.status.as_str()),
                     serde_json::json!(session.created_at),
                     serde_json::json!(session.updated_at),
                     serde_json::json!(session.last_event_id),
                     serde_json::json!(session.metadata.as_ref().map(|m| m.to_string())),
+                    serde_json::json!(session.starred as i64),
+                    serde_json::json!(session.archived_at),
+                    serde_json::json!(session.user_id),
                 ],
             )
             .await?;
@@ -110,15 +115,44 @@ impl ChatHistoryRepository {
         Ok(())
     }
 
-    /// List sessions with optional filters
+    /// Update session summary
+    pub async fn update_session_summary(
+        &self,
+        session_id: &str,
+        summary: &str,
+    ) -> Result<(), String> {
+        let updated_at = chrono::Utc::now().timestamp();
+
+        self.db
+            .execute(
+                "UPDATE sessions SET summary = ?, updated_at = ? WHERE id = ?",
+                vec![
+                    serde_json::json!(summary),
+                    serde_json::json!(updated_at),
+                    serde_json::json!(session_id),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// List a page of sessions with optional filters. Archived sessions are
+    /// listed only when `archived` is set, and then exclusively.
     pub async fn list_sessions(
         &self,
         project_id: Option<&str>,
+        user_id: Option<&str>,
         status: Option<SessionStatus>,
-        limit: Option<usize>,
-        offset: Option<usize>,
-    ) -> Result<Vec<Session>, String> {
-        let mut sql = "SELECT * FROM sessions WHERE 1=1".to_string();
+        archived: bool,
+        sort: SessionSort,
+        page: &PageRequest,
+    ) -> Result<Page<Session>, String> {
+        let mut sql = if archived {
+            "SELECT * FROM sessions WHERE archived_at IS NOT NULL".to_string()
+        } else {
+            "SELECT * FROM sessions WHERE archived_at IS NULL".to_string()
+        };
         let mut params: Vec<serde_json::Value> = vec![];
 
         if let Some(pid) = project_id {
@@ -126,26 +160,175 @@ impl ChatHistoryRepository {
             params.push(serde_json::json!(pid));
         }
 
+        if let Some(uid) = user_id {
+            sql.push_str(" AND user_id = ?");
+            params.push(serde_json::json!(uid));
+        }
+
         if let Some(s) = status {
             sql.push_str(" AND status = ?");
             params.push(serde_json::json!(s.as_str()));
         }
 
-        sql.push_str(" ORDER BY updated_at DESC");
+        let total = self
+            .db
+            .query(
+                &sql.replacen("SELECT *", "SELECT COUNT(*) AS total", 1),
+                params.clone(),
+            )
+            .await?
+            .rows
+            .first()
+            .and_then(|row| row.get("total"))
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0);
 
-        if let Some(limit) = limit {
-            sql.push_str(&format!(" LIMIT {}", limit));
-        }
+        let column = sort.column();
+        pagination::push_page_clause(&mut sql, &mut params, column, page)?;
+        let result = self.db.query(&sql, params).await?;
+        let sessions = result.rows.iter().map(row_to_session).collect();
+
+        Ok(pagination::into_page(
+            sessions,
+            column,
+            page,
+            total,
+            |session| match sort {
+                SessionSort::UpdatedAt => (session.updated_at, session.id.clone()),
+                SessionSort::CreatedAt => (session.created_at, session.id.clone()),
+            },
+        ))
+    }
 
-        if let Some(offset) = offset {
-            sql.push_str(&format!(" OFFSET {}", offset));
+    /// Search session titles and message text, best matches first. Every
+    /// word of the query must match; the last may be the start of a word.
+    pub async fn search_sessions(
+        &self,
+        query: &str,
+        project_id: Option<&str>,
+        user_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, String> {
+        let Some(fts_query) = fts_query(query) else {
+            return Ok(vec![]);
+        };
+        let mut filter = String::new();
+        if project_id.is_some() {
+            filter.push_str(" AND s.project_id = ?");
+        }
+        if user_id.is_some() {
+            filter.push_str(" AND s.user_id = ?");
+        }
+        let sql = format!(
+            r#"
+            SELECT s.id AS session_id, s.title AS session_title, 'title' AS kind,
+                NULL AS message_id,
+                snippet(sessions_fts, 0, char({start}), char({end}), '…', {tokens}) AS snippet,
+                bm25(sessions_fts) AS rank, s.created_at AS created_at
+            FROM sessions_fts JOIN sessions s ON s.rowid = sessions_fts.rowid
+            WHERE sessions_fts MATCH ?{filter}
+            UNION ALL
+            SELECT s.id, s.title, 'message', m.id,
+                snippet(messages_fts, 0, char({start}), char({end}), '…', {tokens}),
+                bm25(messages_fts), m.created_at
+            FROM messages_fts
+            JOIN messages m ON m.rowid = messages_fts.rowid
+            JOIN sessions s ON s.id = m.session_id
+            WHERE messages_fts MATCH ?{filter}
+            ORDER BY rank ASC, created_at DESC
+            LIMIT {limit}
+            "#,
+            start = u32::from(HIGHLIGHT_START),
+            end = u32::from(HIGHLIGHT_END),
+            tokens = SNIPPET_TOKENS,
+            filter = filter,
+            limit = limit,
+        );
+
+        let mut params = vec![];
+        for _ in 0..2 {
+            params.push(serde_json::json!(fts_query));
+            if let Some(project_id) = project_id {
+                params.push(serde_json::json!(project_id));
+            }
+            if let Some(user_id) = user_id {
+                params.push(serde_json::json!(user_id));
+            }
         }
 
         let result = self.db.query(&sql, params).await?;
+        Ok(result.rows.iter().map(row_to_search_hit).collect())
+    }
+
+    /// List sessions, archived or not, last updated before `before`
+    pub async fn list_sessions_idle_since(&self, before: i64) -> Result<Vec<Session>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM sessions WHERE updated_at < ? ORDER BY updated_at ASC",
+                vec![serde_json::json!(before)],
+            )
+            .await?;
 
         Ok(result.rows.iter().map(row_to_session).collect())
     }
 
+    /// Star or unstar a session. Returns false if the session doesn't exist.
+    pub async fn set_session_starred(
+        &self,
+        session_id: &str,
+        starred: bool,
+    ) -> Result<bool, String> {
+        let result = self
+            .db
+            .execute(
+                "UPDATE sessions SET starred = ? WHERE id = ?",
+                vec![
+                    serde_json::json!(starred as i64),
+                    serde_json::json!(session_id),
+                ],
+            )
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Archive a session, or restore it with `None`. Archiving keeps
+    /// `updated_at` so the session keeps ageing towards deletion; restoring
+    /// bumps it. Returns false if the session doesn't exist.
+    pub async fn set_session_archived(
+        &self,
+        session_id: &str,
+        archived_at: Option<i64>,
+    ) -> Result<bool, String> {
+        let result = match archived_at {
+            Some(archived_at) => {
+                self.db
+                    .execute(
+                        "UPDATE sessions SET archived_at = ? WHERE id = ?",
+                        vec![
+                            serde_json::json!(archived_at),
+                            serde_json::json!(session_id),
+                        ],
+                    )
+                    .await?
+            }
+            None => {
+                self.db
+                    .execute(
+                        "UPDATE sessions SET archived_at = NULL, updated_at = ? WHERE id = ?",
+                        vec![
+                            serde_json::json!(chrono::Utc::now().timestamp()),
+                            serde_json::json!(session_id),
+                        ],
+                    )
+                    .await?
+            }
+        };
+
+        Ok(result.rows_affected > 0)
+    }
+
     /// Delete a session and all related data
     pub async fn delete_session(&self, session_id: &str) -> Result<(), String> {
         self.db
@@ -162,8 +345,8 @@ impl ChatHistoryRepository {
     /// Create a new message
     pub async fn create_message(&self, message: &Message) -> Result<(), String> {
         let sql = r#"
-            INSERT INTO messages (id, session_id, role, content, created_at, tool_call_id, parent_id)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO messages (id, session_id, role, content, created_at, tool_call_id, parent_id, pinned)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
         "#;
 
         self.db
@@ -177,6 +360,7 @@ impl ChatHistoryRepository {
                     serde_json::json!(message.created_at),
                     serde_json::json!(message.tool_call_id),
                     serde_json::json!(message.parent_id),
+                    serde_json::json!(message.pinned),
                 ],
             )
             .await?;
@@ -196,12 +380,96 @@ impl ChatHistoryRepository {
         Ok(())
     }
 
+    /// Get a message by ID
+    pub async fn get_message(&self, message_id: &str) -> Result<Option<Message>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM messages WHERE id = ?",
+                vec![serde_json::json!(message_id)],
+            )
+            .await?;
+
+        result.rows.first().map(row_to_message).transpose()
+    }
+
     /// Get messages for a session
     pub async fn get_messages(
         &self,
         session_id: &str,
         limit: Option<usize>,
         before_id: Option<&str>,
+    ) -> Result<Vec<Message>, String> {
+        self.select_messages(session_id, limit, before_id, None)
+            .await
+    }
+
+    /// Get messages of a session created at or after a time, oldest first.
+    /// The limit keeps the oldest messages, so clients page forward by
+    /// passing the time of the last message they received.
+    pub async fn get_messages_since(
+        &self,
+        session_id: &str,
+        since: i64,
+        limit: Option<usize>,
+        before_id: Option<&str>,
+    ) -> Result<Vec<Message>, String> {
+        self.select_messages(session_id, limit, before_id, Some(since))
+            .await
+    }
+
+    /// Tool result messages of a session answering the given tool calls
+    pub async fn get_tool_results(
+        &self,
+        session_id: &str,
+        tool_call_ids: &[String],
+    ) -> Result<Vec<Message>, String> {
+        if tool_call_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let placeholders = vec!["?"; tool_call_ids.len()].join(", ");
+        let sql = format!(
+            "SELECT * FROM messages WHERE session_id = ? AND tool_call_id IN ({}) ORDER BY created_at ASC, rowid ASC",
+            placeholders
+        );
+        let mut params = vec![serde_json::json!(session_id)];
+        params.extend(tool_call_ids.iter().map(|id| serde_json::json!(id)));
+
+        let result = self.db.query(&sql, params).await?;
+        result.rows.iter().map(row_to_message).collect()
+    }
+
+    /// Session of the assistant message that made a tool call
+    pub async fn get_tool_call_session(
+        &self,
+        tool_call_id: &str,
+    ) -> Result<Option<SessionId>, String> {
+        let sql = r#"
+            SELECT m.session_id FROM messages m, json_each(m.content, '$.calls') AS call
+            WHERE json_extract(m.content, '$.type') = 'tool_calls'
+                AND json_extract(call.value, '$.id') = ?
+            LIMIT 1
+        "#;
+        let result = self
+            .db
+            .query(sql, vec![serde_json::json!(tool_call_id)])
+            .await?;
+
+        Ok(result
+            .rows
+            .first()
+            .and_then(|row| row.get("session_id"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()))
+    }
+
+    async fn select_messages(
+        &self,
+        session_id: &str,
+        limit: Option<usize>,
+        before_id: Option<&str>,
+        since: Option<i64>,
     ) -> Result<Vec<Message>, String> {
         let mut sql = "SELECT * FROM messages WHERE session_id = ?".to_string();
         let mut params: Vec<serde_json::Value> = vec![serde_json::json!(session_id)];
@@ -224,7 +492,19 @@ impl ChatHistoryRepository {
             }
         }
 
-        sql.push_str(" ORDER BY created_at DESC");
+        if let Some(since) = since {
+            sql.push_str(" AND created_at >= ?");
+            params.push(serde_json::json!(since));
+        }
+
+        // Messages created within the same second keep their insertion order.
+        // Without a start time the limit keeps the newest messages.
+        let newest_first = since.is_none();
+        if newest_first {
+            sql.push_str(" ORDER BY created_at DESC, rowid DESC");
+        } else {
+            sql.push_str(" ORDER BY created_at ASC, rowid ASC");
+        }
 
         if let Some(limit) = limit {
             sql.push_str(&format!(" LIMIT {}", limit));
@@ -239,10 +519,25 @@ impl ChatHistoryRepository {
             .collect::<Result<Vec<_>, _>>()?;
 
         // Reverse to get chronological order
-        messages.reverse();
+        if newest_first {
+            messages.reverse();
+        }
         Ok(messages)
     }
 
+    /// Pin or unpin a message. Returns false when the message does not exist
+    pub async fn set_message_pinned(&self, message_id: &str, pinned: bool) -> Result<bool, String> {
+        let result = self
+            .db
+            .execute(
+                "UPDATE messages SET pinned = ? WHERE id = ?",
+                vec![serde_json::json!(pinned), serde_json::json!(message_id)],
+            )
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
     /// Delete all messages for a session
     pub async fn delete_messages(&self, session_id: &str) -> Result<(), String> {
         self.db
@@ -279,7 +574,8 @@ impl ChatHistoryRepository {
         Ok(())
     }
 
-    /// Get events for a session, optionally after a specific event ID (for resume)
+    /// Get events for a session, optionally after a specific event ID (for resume).
+    /// Event IDs are increasing numbers assigned by the streaming `EventBuffer`.
     pub async fn get_events(
         &self,
         session_id: &str,
@@ -290,24 +586,14 @@ impl ChatHistoryRepository {
         let mut params: Vec<serde_json::Value> = vec![serde_json::json!(session_id)];
 
         if let Some(after_id) = after_event_id {
-            // Get created_at of the after event
-            let after_result = self
-                .db
-                .query(
-                    "SELECT created_at FROM events WHERE id = ?",
-                    vec![serde_json::json!(after_id)],
-                )
-                .await?;
-
-            if let Some(row) = after_result.rows.first() {
-                if let Some(created_at) = row.get("created_at").and_then(|v| v.as_i64()) {
-                    sql.push_str(" AND created_at > ?");
-                    params.push(serde_json::json!(created_at));
-                }
-            }
+            let after_id: i64 = after_id
+                .parse()
+                .map_err(|_| format!("Invalid event ID '{}'", after_id))?;
+            sql.push_str(" AND CAST(id AS INTEGER) > ?");
+            params.push(serde_json::json!(after_id));
         }
 
-        sql.push_str(" ORDER BY created_at ASC");
+        sql.push_str(" ORDER BY CAST(id AS INTEGER) ASC");
 
         if let Some(limit) = limit {
             sql.push_str(&format!(" LIMIT {}", limit));
@@ -341,109 +627,1053 @@ impl ChatHistoryRepository {
 
         Ok(result.rows_affected)
     }
-}
 
-// ============== Row Conversions ==============
+    // ============== Context Compaction Operations ==============
 
-fn row_to_session(row: &serde_json::Value) -> Session {
-    Session {
-        id: row
-            .get("id")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string(),
-        project_id: row
-            .get("project_id")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        title: row
-            .get("title")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        status: row
-            .get("status")
-            .and_then(|v| v.as_str())
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(SessionStatus::Created),
-        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
-        updated_at: row.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0),
-        last_event_id: row
-            .get("last_event_id")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        metadata: row
-            .get("metadata")
-            .and_then(|v| v.as_str())
-            .and_then(|s| serde_json::from_str(s).ok()),
+    /// Record a context compaction
+    pub async fn create_compaction(&self, compaction: &ContextCompaction) -> Result<(), String> {
+        self.db
+            .execute(
+                r#"
+                INSERT INTO context_compactions (id, session_id, summary, first_kept_message_id,
+                    compacted_count, tokens_before, tokens_after, created_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+                vec![
+                    serde_json::json!(compaction.id),
+                    serde_json::json!(compaction.session_id),
+                    serde_json::json!(compaction.summary),
+                    serde_json::json!(compaction.first_kept_message_id),
+                    serde_json::json!(compaction.compacted_count),
+                    serde_json::json!(compaction.tokens_before),
+                    serde_json::json!(compaction.tokens_after),
+                    serde_json::json!(compaction.created_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
     }
-}
 
-fn row_to_message(row: &serde_json::Value) -> Result<Message, String> {
-    let content_str = row
-        .get("content")
-        .and_then(|v| v.as_str())
-        .ok_or("Missing content field")?;
+    /// List compactions of a session, oldest first
+    pub async fn list_compactions(
+        &self,
+        session_id: &str,
+    ) -> Result<Vec<ContextCompaction>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM context_compactions WHERE session_id = ? ORDER BY created_at ASC, rowid ASC",
+                vec![serde_json::json!(session_id)],
+            )
+            .await?;
 
-    let content: MessageContent = serde_json::from_str(content_str)
-        .map_err(|e| format!("Failed to parse message content: {}", e))?;
+        Ok(result.rows.iter().map(row_to_compaction).collect())
+    }
 
-    Ok(Message {
-        id: row
-            .get("id")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string(),
-        session_id: row
-            .get("session_id")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string(),
-        role: row
-            .get("role")
-            .and_then(|v| v.as_str())
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(MessageRole::User),
-        content,
-        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
-        tool_call_id: row
-            .get("tool_call_id")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-        parent_id: row
-            .get("parent_id")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string()),
-    })
-}
+    /// Get the compaction that currently applies to a session
+    pub async fn get_latest_compaction(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<ContextCompaction>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM context_compactions WHERE session_id = ? ORDER BY created_at DESC, rowid DESC LIMIT 1",
+                vec![serde_json::json!(session_id)],
+            )
+            .await?;
 
-fn row_to_event(row: &serde_json::Value) -> Result<SessionEvent, String> {
-    let payload_str = row
-        .get("payload")
-        .and_then(|v| v.as_str())
-        .ok_or("Missing payload field")?;
+        Ok(result.rows.first().map(row_to_compaction))
+    }
 
-    let payload: serde_json::Value = serde_json::from_str(payload_str)
-        .map_err(|e| format!("Failed to parse event payload: {}", e))?;
+    // ============== Checkpoint Operations ==============
 
-    Ok(SessionEvent {
-        id: row
-            .get("id")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string(),
-        session_id: row
-            .get("session_id")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string(),
-        event_type: row
-            .get("event_type")
-            .and_then(|v| v.as_str())
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(EventType::Status),
-        payload,
-        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
-    })
+    /// Persist a file checkpoint
+    pub async fn create_checkpoint(&self, checkpoint: &Checkpoint) -> Result<(), String> {
+        let payload = serde_json::to_string(checkpoint)
+            .map_err(|e| format!("Failed to serialize checkpoint: {}", e))?;
+
+        self.db
+            .execute(
+                r#"
+                INSERT INTO checkpoints (id, session_id, task_id, tool_call_id, payload, created_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#,
+                vec![
+                    serde_json::json!(checkpoint.id),
+                    serde_json::json!(checkpoint.session_id),
+                    serde_json::json!(checkpoint.task_id),
+                    serde_json::json!(checkpoint.tool_call_id),
+                    serde_json::json!(payload),
+                    serde_json::json!(checkpoint.created_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// List checkpoints of a session, oldest first
+    pub async fn list_checkpoints(&self, session_id: &str) -> Result<Vec<Checkpoint>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT payload FROM checkpoints WHERE session_id = ? ORDER BY created_at ASC, rowid ASC",
+                vec![serde_json::json!(session_id)],
+            )
+            .await?;
+
+        result
+            .rows
+            .iter()
+            .map(row_to_checkpoint)
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Delete a checkpoint
+    pub async fn delete_checkpoint(&self, checkpoint_id: &str) -> Result<(), String> {
+        self.db
+            .execute(
+                "DELETE FROM checkpoints WHERE id = ?",
+                vec![serde_json::json!(checkpoint_id)],
+            )
+            .await?;
+        Ok(())
+    }
+
+    // ============== Pending Approval Operations ==============
+
+    /// Persist a tool call that is waiting for user approval
+    pub async fn create_pending_approval(&self, approval: &PendingApproval) -> Result<(), String> {
+        let payload = serde_json::to_string(approval)
+            .map_err(|e| format!("Failed to serialize pending approval: {}", e))?;
+
+        self.db
+            .execute(
+                r#"
+                INSERT INTO pending_approvals (tool_call_id, task_id, session_id, payload, created_at)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+                vec![
+                    serde_json::json!(approval.tool_call_id),
+                    serde_json::json!(approval.task_id),
+                    serde_json::json!(approval.session_id),
+                    serde_json::json!(payload),
+                    serde_json::json!(approval.created_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get a pending approval by tool call ID
+    pub async fn get_pending_approval(
+        &self,
+        tool_call_id: &str,
+    ) -> Result<Option<PendingApproval>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT payload FROM pending_approvals WHERE tool_call_id = ?",
+                vec![serde_json::json!(tool_call_id)],
+            )
+            .await?;
+
+        result.rows.first().map(row_to_pending_approval).transpose()
+    }
+
+    /// List pending approvals, optionally for a single session, oldest first
+    pub async fn list_pending_approvals(
+        &self,
+        session_id: Option<&str>,
+    ) -> Result<Vec<PendingApproval>, String> {
+        let mut sql = "SELECT payload FROM pending_approvals".to_string();
+        let mut params: Vec<serde_json::Value> = vec![];
+
+        if let Some(sid) = session_id {
+            sql.push_str(" WHERE session_id = ?");
+            params.push(serde_json::json!(sid));
+        }
+
+        sql.push_str(" ORDER BY created_at ASC");
+
+        let result = self.db.query(&sql, params).await?;
+
+        result
+            .rows
+            .iter()
+            .map(row_to_pending_approval)
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Remove and return a pending approval. Returns `None` when it does not exist
+    /// or was already taken, so a decision is only ever applied once.
+    pub async fn take_pending_approval(
+        &self,
+        tool_call_id: &str,
+    ) -> Result<Option<PendingApproval>, String> {
+        let Some(approval) = self.get_pending_approval(tool_call_id).await? else {
+            return Ok(None);
+        };
+
+        let result = self
+            .db
+            .execute(
+                "DELETE FROM pending_approvals WHERE tool_call_id = ?",
+                vec![serde_json::json!(tool_call_id)],
+            )
+            .await?;
+
+        Ok((result.rows_affected > 0).then_some(approval))
+    }
+
+    /// Delete all pending approvals of a task
+    pub async fn delete_pending_approvals_for_task(&self, task_id: &str) -> Result<u64, String> {
+        let result = self
+            .db
+            .execute(
+                "DELETE FROM pending_approvals WHERE task_id = ?",
+                vec![serde_json::json!(task_id)],
+            )
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+
+    // ============== Budget Pause Operations ==============
+
+    /// Persist a task paused for exceeding its budget
+    pub async fn create_budget_pause(&self, pause: &BudgetPause) -> Result<(), String> {
+        let payload = serde_json::to_string(pause)
+            .map_err(|e| format!("Failed to serialize budget pause: {}", e))?;
+
+        self.db
+            .execute(
+                r#"
+                INSERT INTO budget_pauses (task_id, session_id, payload, created_at)
+                VALUES (?, ?, ?, ?)
+                "#,
+                vec![
+                    serde_json::json!(pause.task_id),
+                    serde_json::json!(pause.session_id),
+                    serde_json::json!(payload),
+                    serde_json::json!(pause.created_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// List budget pauses, optionally for a single session, oldest first
+    pub async fn list_budget_pauses(
+        &self,
+        session_id: Option<&str>,
+    ) -> Result<Vec<BudgetPause>, String> {
+        let mut sql = "SELECT payload FROM budget_pauses".to_string();
+        let mut params: Vec<serde_json::Value> = vec![];
+
+        if let Some(sid) = session_id {
+            sql.push_str(" WHERE session_id = ?");
+            params.push(serde_json::json!(sid));
+        }
+
+        sql.push_str(" ORDER BY created_at ASC");
+
+        let result = self.db.query(&sql, params).await?;
+
+        result
+            .rows
+            .iter()
+            .map(row_to_budget_pause)
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Remove and return the budget pause of a task, so a decision is only applied once
+    pub async fn take_budget_pause(&self, task_id: &str) -> Result<Option<BudgetPause>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT payload FROM budget_pauses WHERE task_id = ?",
+                vec![serde_json::json!(task_id)],
+            )
+            .await?;
+        let Some(pause) = result.rows.first().map(row_to_budget_pause).transpose()? else {
+            return Ok(None);
+        };
+
+        let result = self
+            .db
+            .execute(
+                "DELETE FROM budget_pauses WHERE task_id = ?",
+                vec![serde_json::json!(task_id)],
+            )
+            .await?;
+
+        Ok((result.rows_affected > 0).then_some(pause))
+    }
+
+    // ============== Stream State Operations ==============
+
+    /// Insert or replace the stream state of a task
+    pub async fn save_stream_state(&self, state: &StreamState) -> Result<(), String> {
+        let payload = serde_json::to_string(state)
+            .map_err(|e| format!("Failed to serialize stream state: {}", e))?;
+
+        self.db
+            .execute(
+                r#"
+                INSERT INTO stream_states (task_id, session_id, payload, updated_at)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT(task_id) DO UPDATE SET
+                    payload = excluded.payload,
+                    updated_at = excluded.updated_at
+                "#,
+                vec![
+                    serde_json::json!(state.task_id),
+                    serde_json::json!(state.session_id),
+                    serde_json::json!(payload),
+                    serde_json::json!(state.updated_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// List stream states, optionally for a single session, oldest first
+    pub async fn list_stream_states(
+        &self,
+        session_id: Option<&str>,
+    ) -> Result<Vec<StreamState>, String> {
+        let mut sql = "SELECT payload FROM stream_states".to_string();
+        let mut params: Vec<serde_json::Value> = vec![];
+
+        if let Some(sid) = session_id {
+            sql.push_str(" WHERE session_id = ?");
+            params.push(serde_json::json!(sid));
+        }
+
+        sql.push_str(" ORDER BY updated_at ASC");
+
+        let result = self.db.query(&sql, params).await?;
+
+        result
+            .rows
+            .iter()
+            .map(row_to_stream_state)
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Remove and return the stream state of a task
+    pub async fn take_stream_state(&self, task_id: &str) -> Result<Option<StreamState>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT payload FROM stream_states WHERE task_id = ?",
+                vec![serde_json::json!(task_id)],
+            )
+            .await?;
+        let Some(state) = result.rows.first().map(row_to_stream_state).transpose()? else {
+            return Ok(None);
+        };
+
+        let result = self
+            .db
+            .execute(
+                "DELETE FROM stream_states WHERE task_id = ?",
+                vec![serde_json::json!(task_id)],
+            )
+            .await?;
+
+        Ok((result.rows_affected > 0).then_some(state))
+    }
+
+    // ============== Plan Operations ==============
+
+    /// Persist a plan submitted in plan mode
+    pub async fn create_plan(&self, plan: &Plan) -> Result<(), String> {
+        let payload =
+            serde_json::to_string(plan).map_err(|e| format!("Failed to serialize plan: {}", e))?;
+
+        self.db
+            .execute(
+                r#"
+                INSERT INTO plans (id, session_id, task_id, payload, created_at, executed_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#,
+                vec![
+                    serde_json::json!(plan.id),
+                    serde_json::json!(plan.session_id),
+                    serde_json::json!(plan.task_id),
+                    serde_json::json!(payload),
+                    serde_json::json!(plan.created_at),
+                    serde_json::json!(plan.executed_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// List plans of a session, oldest first
+    pub async fn list_plans(&self, session_id: &str) -> Result<Vec<Plan>, String> {
+        let result = self
+            .db
+            .query(
+                r#"
+                SELECT payload, executed_at FROM plans
+                WHERE session_id = ?
+                ORDER BY created_at ASC, rowid ASC
+                "#,
+                vec![serde_json::json!(session_id)],
+            )
+            .await?;
+
+        result
+            .rows
+            .iter()
+            .map(row_to_plan)
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Latest plan of a session that has not been executed yet
+    pub async fn get_pending_plan(&self, session_id: &str) -> Result<Option<Plan>, String> {
+        let result = self
+            .db
+            .query(
+                r#"
+                SELECT payload, executed_at FROM plans
+                WHERE session_id = ? AND executed_at IS NULL
+                ORDER BY created_at DESC, rowid DESC
+                LIMIT 1
+                "#,
+                vec![serde_json::json!(session_id)],
+            )
+            .await?;
+
+        result.rows.first().map(row_to_plan).transpose()
+    }
+
+    /// Mark a plan as executed. Returns `false` when it does not exist or was
+    /// already executed, so a plan only starts one task.
+    pub async fn mark_plan_executed(
+        &self,
+        plan_id: &str,
+        executed_at: i64,
+    ) -> Result<bool, String> {
+        let result = self
+            .db
+            .execute(
+                "UPDATE plans SET executed_at = ? WHERE id = ? AND executed_at IS NULL",
+                vec![serde_json::json!(executed_at), serde_json::json!(plan_id)],
+            )
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    // ============== Todo Operations ==============
+
+    /// Insert or replace the todo list of a session
+    pub async fn save_todos(&self, todos: &TodoList) -> Result<(), String> {
+        let payload = serde_json::to_string(todos)
+            .map_err(|e| format!("Failed to serialize todos: {}", e))?;
+
+        self.db
+            .execute(
+                r#"
+                INSERT INTO todos (session_id, payload, updated_at)
+                VALUES (?, ?, ?)
+                ON CONFLICT(session_id) DO UPDATE SET
+                    payload = excluded.payload,
+                    updated_at = excluded.updated_at
+                "#,
+                vec![
+                    serde_json::json!(todos.session_id),
+                    serde_json::json!(payload),
+                    serde_json::json!(todos.updated_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Todo list of a session, if the agent has written one
+    pub async fn get_todos(&self, session_id: &str) -> Result<Option<TodoList>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT payload FROM todos WHERE session_id = ?",
+                vec![serde_json::json!(session_id)],
+            )
+            .await?;
+
+        result.rows.first().map(row_to_todos).transpose()
+    }
+
+    // ============== Task Worktree Operations ==============
+
+    /// Persist the worktree of an isolated task
+    pub async fn create_task_worktree(&self, worktree: &TaskWorktree) -> Result<(), String> {
+        let payload = serde_json::to_string(worktree)
+            .map_err(|e| format!("Failed to serialize task worktree: {}", e))?;
+
+        self.db
+            .execute(
+                r#"
+                INSERT INTO task_worktrees (task_id, session_id, payload, created_at)
+                VALUES (?, ?, ?, ?)
+                "#,
+                vec![
+                    serde_json::json!(worktree.task_id),
+                    serde_json::json!(worktree.session_id),
+                    serde_json::json!(payload),
+                    serde_json::json!(worktree.created_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the worktree of a task
+    pub async fn get_task_worktree(&self, task_id: &str) -> Result<Option<TaskWorktree>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT payload FROM task_worktrees WHERE task_id = ?",
+                vec![serde_json::json!(task_id)],
+            )
+            .await?;
+
+        result.rows.first().map(row_to_task_worktree).transpose()
+    }
+
+    /// List the worktrees of a session's tasks, oldest first
+    pub async fn list_task_worktrees(&self, session_id: &str) -> Result<Vec<TaskWorktree>, String> {
+        let result = self
+            .db
+            .query(
+                r#"
+                SELECT payload FROM task_worktrees
+                WHERE session_id = ?
+                ORDER BY created_at ASC, rowid ASC
+                "#,
+                vec![serde_json::json!(session_id)],
+            )
+            .await?;
+
+        result
+            .rows
+            .iter()
+            .map(row_to_task_worktree)
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    /// Delete the worktree record of a task
+    pub async fn delete_task_worktree(&self, task_id: &str) -> Result<(), String> {
+        self.db
+            .execute(
+                "DELETE FROM task_worktrees WHERE task_id = ?",
+                vec![serde_json::json!(task_id)],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    // ============== Runtime Event Log Operations ==============
+
+    /// Append an event to a session's event log, returning its sequence
+    pub async fn append_runtime_event(
+        &self,
+        session_id: &str,
+        task_id: Option<&str>,
+        event_type: &str,
+        event: &serde_json::Value,
+    ) -> Result<i64, String> {
+        let result = self
+            .db
+            .query(
+                r#"
+                INSERT INTO runtime_events (session_id, task_id, event_type, payload, created_at)
+                VALUES (?, ?, ?, ?, ?)
+                RETURNING sequence
+                "#,
+                vec![
+                    serde_json::json!(session_id),
+                    serde_json::json!(task_id),
+                    serde_json::json!(event_type),
+                    serde_json::json!(event.to_string()),
+                    serde_json::json!(chrono::Utc::now().timestamp()),
+                ],
+            )
+            .await?;
+
+        result
+            .rows
+            .first()
+            .and_then(|row| row.get("sequence"))
+            .and_then(|v| v.as_i64())
+            .ok_or_else(|| "Failed to append runtime event".to_string())
+    }
+
+    /// List a session's logged events in order, optionally after a sequence
+    pub async fn list_runtime_events(
+        &self,
+        session_id: &str,
+        after_sequence: Option<i64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<RuntimeEventRecord>, String> {
+        let mut sql =
+            "SELECT * FROM runtime_events WHERE session_id = ? AND sequence > ? ORDER BY sequence ASC"
+                .to_string();
+        if let Some(limit) = limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let result = self
+            .db
+            .query(
+                &sql,
+                vec![
+                    serde_json::json!(session_id),
+                    serde_json::json!(after_sequence.unwrap_or(0)),
+                ],
+            )
+            .await?;
+
+        result
+            .rows
+            .iter()
+            .map(row_to_runtime_event)
+            .collect::<Result<Vec<_>, _>>()
+    }
+
+    // ============== Webhook Delivery Operations ==============
+
+    /// Record a webhook delivery
+    pub async fn create_webhook_delivery(&self, delivery: &WebhookDelivery) -> Result<(), String> {
+        let sql = r#"
+            INSERT INTO webhook_deliveries (id, webhook_id, project_id, session_id, task_id, event, payload, status, attempts, response_status, error, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(delivery.id),
+                    serde_json::json!(delivery.webhook_id),
+                    serde_json::json!(delivery.project_id),
+                    serde_json::json!(delivery.session_id),
+                    serde_json::json!(delivery.task_id),
+                    serde_json::json!(delivery.event),
+                    serde_json::json!(delivery.payload.to_string()),
+                    serde_json::json!(delivery.status.as_str()),
+                    serde_json::json!(delivery.attempts),
+                    serde_json::json!(delivery.response_status),
+                    serde_json::json!(delivery.error),
+                    serde_json::json!(delivery.created_at),
+                    serde_json::json!(delivery.updated_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Record the result of a delivery attempt
+    pub async fn update_webhook_delivery(&self, delivery: &WebhookDelivery) -> Result<(), String> {
+        let sql = r#"
+            UPDATE webhook_deliveries
+            SET status = ?, attempts = ?, response_status = ?, error = ?, updated_at = ?
+            WHERE id = ?
+        "#;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(delivery.status.as_str()),
+                    serde_json::json!(delivery.attempts),
+                    serde_json::json!(delivery.response_status),
+                    serde_json::json!(delivery.error),
+                    serde_json::json!(delivery.updated_at),
+                    serde_json::json!(delivery.id),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// List a project's webhook deliveries, newest first, optionally only
+    /// those of one webhook
+    pub async fn list_webhook_deliveries(
+        &self,
+        project_id: &str,
+        webhook_id: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<WebhookDelivery>, String> {
+        let mut sql = "SELECT * FROM webhook_deliveries WHERE project_id = ?".to_string();
+        let mut params = vec![serde_json::json!(project_id)];
+        if let Some(webhook_id) = webhook_id {
+            sql.push_str(" AND webhook_id = ?");
+            params.push(serde_json::json!(webhook_id));
+        }
+        sql.push_str(" ORDER BY created_at DESC, rowid DESC");
+        if let Some(limit) = limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let result = self.db.query(&sql, params).await?;
+        result.rows.iter().map(row_to_webhook_delivery).collect()
+    }
+}
+
+// ============== Full-Text Search ==============
+
+/// Marks the start of a match in FTS snippets
+const HIGHLIGHT_START: char = '\u{E000}';
+
+/// Marks the end of a match in FTS snippets
+const HIGHLIGHT_END: char = '\u{E001}';
+
+/// Most words of a search snippet
+const SNIPPET_TOKENS: usize = 16;
+
+/// FTS5 query matching every word of a search, the last one as a prefix.
+/// Words are quoted, so FTS syntax in the search is matched literally.
+fn fts_query(search: &str) -> Option<String> {
+    let mut words: Vec<String> = search
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+    words.last_mut()?.push('*');
+    Some(words.join(" "))
+}
+
+/// Snippet without its highlight markers, and the ranges they enclosed
+fn parse_snippet(marked: &str) -> (String, Vec<HighlightRange>) {
+    let mut snippet = String::with_capacity(marked.len());
+    let mut highlights = Vec::new();
+    let mut start = None;
+    let mut position = 0;
+    for c in marked.chars() {
+        match c {
+            HIGHLIGHT_START => start = Some(position),
+            HIGHLIGHT_END => {
+                if let Some(start) = start.take() {
+                    highlights.push(HighlightRange {
+                        start,
+                        end: position,
+                    });
+                }
+            }
+            _ => {
+                snippet.push(c);
+                position += 1;
+            }
+        }
+    }
+    (snippet, highlights)
+}
+
+// ============== Row Conversions ==============
+
+fn row_to_search_hit(row: &serde_json::Value) -> SearchHit {
+    let (snippet, highlights) =
+        parse_snippet(row.get("snippet").and_then(|v| v.as_str()).unwrap_or(""));
+    SearchHit {
+        session_id: row
+            .get("session_id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        session_title: row
+            .get("session_title")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        kind: match row.get("kind").and_then(|v| v.as_str()) {
+            Some("title") => SearchHitKind::Title,
+            _ => SearchHitKind::Message,
+        },
+        message_id: row
+            .get("message_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        snippet,
+        highlights,
+        rank: row.get("rank").and_then(|v| v.as_f64()).unwrap_or(0.0),
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+    }
+}
+
+fn row_to_session(row: &serde_json::Value) -> Session {
+    Session {
+        id: row
+            .get("id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        project_id: row
+            .get("project_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        title: row
+            .get("title")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        summary: row
+            .get("summary")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        status: row
+            .get("status")
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(SessionStatus::Created),
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+        updated_at: row.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0),
+        last_event_id: row
+            .get("last_event_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        metadata: row
+            .get("metadata")
+            .and_then(|v| v.as_str())
+            .and_then(|s| serde_json::from_str(s).ok()),
+        starred: row.get("starred").and_then(|v| v.as_i64()).unwrap_or(0) != 0,
+        archived_at: row.get("archived_at").and_then(|v| v.as_i64()),
+        user_id: row
+            .get("user_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+    }
+}
+
+fn row_to_message(row: &serde_json::Value) -> Result<Message, String> {
+    let content_str = row
+        .get("content")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing content field")?;
+
+    let content: MessageContent = serde_json::from_str(content_str)
+        .map_err(|e| format!("Failed to parse message content: {}", e))?;
+
+    Ok(Message {
+        id: row
+            .get("id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        session_id: row
+            .get("session_id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        role: row
+            .get("role")
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(MessageRole::User),
+        content,
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+        tool_call_id: row
+            .get("tool_call_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        parent_id: row
+            .get("parent_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        pinned: row.get("pinned").and_then(|v| v.as_i64()).unwrap_or(0) != 0,
+    })
+}
+
+fn row_to_event(row: &serde_json::Value) -> Result<SessionEvent, String> {
+    let payload_str = row
+        .get("payload")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing payload field")?;
+
+    let payload: serde_json::Value = serde_json::from_str(payload_str)
+        .map_err(|e| format!("Failed to parse event payload: {}", e))?;
+
+    Ok(SessionEvent {
+        id: row
+            .get("id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        session_id: row
+            .get("session_id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        event_type: row
+            .get("event_type")
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(EventType::Status),
+        payload,
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+    })
+}
+
+fn row_to_compaction(row: &serde_json::Value) -> ContextCompaction {
+    let text = |key: &str| {
+        row.get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    let number = |key: &str| row.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
+
+    ContextCompaction {
+        id: text("id"),
+        session_id: text("session_id"),
+        summary: text("summary"),
+        first_kept_message_id: text("first_kept_message_id"),
+        compacted_count: number("compacted_count") as usize,
+        tokens_before: number("tokens_before") as u32,
+        tokens_after: number("tokens_after") as u32,
+        created_at: number("created_at"),
+    }
+}
+
+fn row_to_checkpoint(row: &serde_json::Value) -> Result<Checkpoint, String> {
+    let payload = row
+        .get("payload")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing payload field")?;
+
+    serde_json::from_str(payload).map_err(|e| format!("Failed to parse checkpoint: {}", e))
+}
+
+fn row_to_budget_pause(row: &serde_json::Value) -> Result<BudgetPause, String> {
+    let payload = row
+        .get("payload")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing payload field")?;
+
+    serde_json::from_str(payload).map_err(|e| format!("Failed to parse budget pause: {}", e))
+}
+
+fn row_to_stream_state(row: &serde_json::Value) -> Result<StreamState, String> {
+    let payload = row
+        .get("payload")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing payload field")?;
+
+    serde_json::from_str(payload).map_err(|e| format!("Failed to parse stream state: {}", e))
+}
+
+fn row_to_plan(row: &serde_json::Value) -> Result<Plan, String> {
+    let payload = row
+        .get("payload")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing payload field")?;
+    let mut plan: Plan =
+        serde_json::from_str(payload).map_err(|e| format!("Failed to parse plan: {}", e))?;
+    // The column is updated in place; the payload keeps the value at creation
+    plan.executed_at = row.get("executed_at").and_then(|v| v.as_i64());
+
+    Ok(plan)
+}
+
+fn row_to_todos(row: &serde_json::Value) -> Result<TodoList, String> {
+    let payload = row
+        .get("payload")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing payload field")?;
+
+    serde_json::from_str(payload).map_err(|e| format!("Failed to parse todos: {}", e))
+}
+
+fn row_to_task_worktree(row: &serde_json::Value) -> Result<TaskWorktree, String> {
+    let payload = row
+        .get("payload")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing payload field")?;
+
+    serde_json::from_str(payload).map_err(|e| format!("Failed to parse task worktree: {}", e))
+}
+
+fn row_to_webhook_delivery(row: &serde_json::Value) -> Result<WebhookDelivery, String> {
+    let payload = row
+        .get("payload")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing payload field")?;
+    let payload = serde_json::from_str(payload)
+        .map_err(|e| format!("Failed to parse webhook payload: {}", e))?;
+    let string = |field: &str| {
+        row.get(field)
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+
+    Ok(WebhookDelivery {
+        id: string("id").unwrap_or_default(),
+        webhook_id: string("webhook_id").unwrap_or_default(),
+        project_id: string("project_id").unwrap_or_default(),
+        session_id: string("session_id"),
+        task_id: string("task_id"),
+        event: string("event").unwrap_or_default(),
+        payload,
+        status: string("status")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(WebhookDeliveryStatus::Failed),
+        attempts: row.get("attempts").and_then(|v| v.as_i64()).unwrap_or(0),
+        response_status: row.get("response_status").and_then(|v| v.as_i64()),
+        error: string("error"),
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+        updated_at: row.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0),
+    })
+}
+
+fn row_to_runtime_event(row: &serde_json::Value) -> Result<RuntimeEventRecord, String> {
+    let payload = row
+        .get("payload")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing payload field")?;
+    let event = serde_json::from_str(payload)
+        .map_err(|e| format!("Failed to parse runtime event: {}", e))?;
+
+    Ok(RuntimeEventRecord {
+        sequence: row.get("sequence").and_then(|v| v.as_i64()).unwrap_or(0),
+        session_id: row
+            .get("session_id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        task_id: row
+            .get("task_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        event_type: row
+            .get("event_type")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        event,
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+    })
+}
+
+fn row_to_pending_approval(row: &serde_json::Value) -> Result<PendingApproval, String> {
+    let payload = row
+        .get("payload")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing payload field")?;
+
+    serde_json::from_str(payload).map_err(|e| format!("Failed to parse pending approval: {}", e))
 }
 
 #[cfg(test)]
@@ -477,11 +1707,15 @@ mod tests {
             id: "test-session-1".to_string(),
             project_id: Some("project-1".to_string()),
             title: Some("Test Session".to_string()),
+            summary: None,
             status: SessionStatus::Created,
             created_at: chrono::Utc::now().timestamp(),
             updated_at: chrono::Utc::now().timestamp(),
             last_event_id: None,
             metadata: Some(serde_json::json!({"key": "value"})),
+            starred: false,
+            archived_at: None,
+            user_id: None,
         };
 
         repo.create_session(&session)
@@ -500,6 +1734,240 @@ mod tests {
         assert_eq!(retrieved.title, Some("Test Session".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_list_sessions_pages_with_cursor() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+        let session = |id: &str, updated_at: i64| Session {
+            id: id.to_string(),
+            project_id: None,
+            title: None,
+            summary: None,
+            status: SessionStatus::Created,
+            created_at: updated_at,
+            updated_at,
+            last_event_id: None,
+            metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
+        };
+        for (id, updated_at) in [("sess-a", 100), ("sess-b", 200), ("sess-c", 200)] {
+            repo.create_session(&session(id, updated_at)).await.unwrap();
+        }
+
+        let ids = |page: &Page<Session>| -> Vec<String> {
+            page.items.iter().map(|s| s.id.clone()).collect()
+        };
+        let mut request = PageRequest {
+            limit: Some(2),
+            ..PageRequest::default()
+        };
+        let first = repo
+            .list_sessions(None, None, None, false, SessionSort::UpdatedAt, &request)
+            .await
+            .unwrap();
+        assert_eq!(ids(&first), vec!["sess-c", "sess-b"]);
+        assert_eq!(first.total, 3);
+
+        // A session created between pages does not shift the next page
+        repo.create_session(&session("sess-d", 300)).await.unwrap();
+        request.cursor = first.next_cursor;
+        let second = repo
+            .list_sessions(None, None, None, false, SessionSort::UpdatedAt, &request)
+            .await
+            .unwrap();
+        assert_eq!(ids(&second), vec!["sess-a"]);
+        assert_eq!(second.next_cursor, None);
+        assert_eq!(second.total, 4);
+
+        // Cursors only continue the sort they were issued for
+        assert!(repo
+            .list_sessions(None, None, None, false, SessionSort::CreatedAt, &request)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_user_sessions_and_tool_call_session() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+        for (id, user_id) in [("sess-a", Some("user-1")), ("sess-b", None)] {
+            repo.create_session(&Session {
+                id: id.to_string(),
+                project_id: None,
+                title: None,
+                summary: None,
+                status: SessionStatus::Created,
+                created_at: 100,
+                updated_at: 100,
+                last_event_id: None,
+                metadata: None,
+                starred: false,
+                archived_at: None,
+                user_id: user_id.map(str::to_string),
+            })
+            .await
+            .unwrap();
+        }
+
+        let page = repo
+            .list_sessions(
+                None,
+                Some("user-1"),
+                None,
+                false,
+                SessionSort::UpdatedAt,
+                &PageRequest::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].id, "sess-a");
+        assert_eq!(page.items[0].user_id.as_deref(), Some("user-1"));
+
+        repo.create_message(&Message {
+            id: "msg-1".to_string(),
+            session_id: "sess-b".to_string(),
+            role: MessageRole::Assistant,
+            content: MessageContent::ToolCalls {
+                calls: vec![ToolCall {
+                    id: "call-1".to_string(),
+                    name: "execute_shell".to_string(),
+                    input: serde_json::json!({}),
+                }],
+            },
+            created_at: 100,
+            tool_call_id: None,
+            parent_id: None,
+            pinned: false,
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            repo.get_tool_call_session("call-1").await.unwrap(),
+            Some("sess-b".to_string())
+        );
+        assert_eq!(repo.get_tool_call_session("call-2").await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_fts_query_and_snippets() {
+        assert_eq!(fts_query("fix the pars").unwrap(), r#""fix" "the" "pars"*"#);
+        assert_eq!(
+            fts_query(r#"say "hi" - OR"#).unwrap(),
+            r#""say" """hi""" "OR"*"#
+        );
+        assert_eq!(fts_query("  - "), None);
+
+        let (snippet, highlights) = parse_snippet("é \u{E000}bc\u{E001} d \u{E000}e\u{E001}");
+        assert_eq!(snippet, "é bc d e");
+        assert_eq!(
+            highlights,
+            vec![
+                HighlightRange { start: 2, end: 4 },
+                HighlightRange { start: 7, end: 8 }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_sessions() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+        let session = |id: &str, project_id: &str, title: &str| Session {
+            id: id.to_string(),
+            project_id: Some(project_id.to_string()),
+            title: Some(title.to_string()),
+            summary: None,
+            status: SessionStatus::Created,
+            created_at: 100,
+            updated_at: 100,
+            last_event_id: None,
+            metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
+        };
+        repo.create_session(&session("sess-1", "project-a", "Refactor the parser"))
+            .await
+            .unwrap();
+        repo.create_session(&session("sess-2", "project-b", "Release notes"))
+            .await
+            .unwrap();
+        repo.create_message(&Message {
+            id: "msg-1".to_string(),
+            session_id: "sess-2".to_string(),
+            role: MessageRole::User,
+            content: MessageContent::Text {
+                text: "The tokenizer crashes on émoji input".to_string(),
+            },
+            created_at: 200,
+            tool_call_id: None,
+            parent_id: None,
+            pinned: false,
+        })
+        .await
+        .unwrap();
+
+        let hits = repo.search_sessions("pars", None, None, 10).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].kind, SearchHitKind::Title);
+        assert_eq!(hits[0].session_id, "sess-1");
+
+        // Words match in any order, without diacritics and by prefix
+        let hits = repo
+            .search_sessions("emoji crash", None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        let hit = &hits[0];
+        assert_eq!(hit.kind, SearchHitKind::Message);
+        assert_eq!(hit.message_id.as_deref(), Some("msg-1"));
+        assert_eq!(hit.session_title.as_deref(), Some("Release notes"));
+        let highlighted: Vec<String> = hit
+            .highlights
+            .iter()
+            .map(|range| {
+                hit.snippet
+                    .chars()
+                    .skip(range.start)
+                    .take(range.end - range.start)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(highlighted, vec!["crashes", "émoji"]);
+
+        assert!(repo
+            .search_sessions("emoji", Some("project-a"), None, 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Renamed and deleted sessions are reindexed
+        repo.update_session_title("sess-1", "Lexer cleanup")
+            .await
+            .unwrap();
+        assert!(repo
+            .search_sessions("parser", None, None, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            repo.search_sessions("lexer", None, None, 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        repo.delete_session("sess-2").await.unwrap();
+        assert!(repo
+            .search_sessions("tokenizer", None, None, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn test_update_session_status() {
         let (db, _temp) = create_test_db().await;
@@ -509,11 +1977,15 @@ mod tests {
             id: "test-session-2".to_string(),
             project_id: None,
             title: None,
+            summary: None,
             status: SessionStatus::Created,
             created_at: chrono::Utc::now().timestamp(),
             updated_at: chrono::Utc::now().timestamp(),
             last_event_id: None,
             metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
         };
 
         repo.create_session(&session)
@@ -540,11 +2012,15 @@ mod tests {
             id: "test-session-3".to_string(),
             project_id: None,
             title: None,
+            summary: None,
             status: SessionStatus::Created,
             created_at: chrono::Utc::now().timestamp(),
             updated_at: chrono::Utc::now().timestamp(),
             last_event_id: None,
             metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
         };
         repo.create_session(&session)
             .await
@@ -560,6 +2036,7 @@ mod tests {
             created_at: chrono::Utc::now().timestamp(),
             tool_call_id: None,
             parent_id: None,
+            pinned: false,
         };
 
         repo.create_message(&message)
@@ -573,4 +2050,534 @@ mod tests {
         assert_eq!(messages.len(), 1);
         assert_eq!(messages[0].id, "msg-1");
     }
+
+    #[tokio::test]
+    async fn test_messages_since_and_tool_results() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+
+        let session = Session {
+            id: "test-session-since".to_string(),
+            project_id: None,
+            title: None,
+            summary: None,
+            status: SessionStatus::Created,
+            created_at: 100,
+            updated_at: 100,
+            last_event_id: None,
+            metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
+        };
+        repo.create_session(&session).await.unwrap();
+
+        let message = |id: &str, created_at: i64, content: MessageContent| Message {
+            id: id.to_string(),
+            session_id: session.id.clone(),
+            role: MessageRole::Assistant,
+            content,
+            created_at,
+            tool_call_id: None,
+            parent_id: None,
+            pinned: false,
+        };
+        let text = |text: &str| MessageContent::Text {
+            text: text.to_string(),
+        };
+        repo.create_message(&message("msg-1", 100, text("one")))
+            .await
+            .unwrap();
+        repo.create_message(&message(
+            "msg-2",
+            200,
+            MessageContent::ToolCalls {
+                calls: vec![ToolCall {
+                    id: "call-1".to_string(),
+                    name: "read_file".to_string(),
+                    input: serde_json::json!({"path": "a.txt"}),
+                }],
+            },
+        ))
+        .await
+        .unwrap();
+        let mut result = message(
+            "msg-3",
+            200,
+            MessageContent::ToolResult {
+                result: serde_json::json!("contents"),
+            },
+        );
+        result.role = MessageRole::Tool;
+        result.tool_call_id = Some("call-1".to_string());
+        repo.create_message(&result).await.unwrap();
+        repo.create_message(&message("msg-4", 300, text("four")))
+            .await
+            .unwrap();
+
+        // Pages forward from the start time, oldest first
+        let ids = |messages: Vec<Message>| -> Vec<String> {
+            messages.into_iter().map(|m| m.id).collect()
+        };
+        let since = repo
+            .get_messages_since(&session.id, 200, Some(2), None)
+            .await
+            .unwrap();
+        assert_eq!(ids(since), vec!["msg-2", "msg-3"]);
+        let since = repo
+            .get_messages_since(&session.id, 200, None, Some("msg-4"))
+            .await
+            .unwrap();
+        assert_eq!(ids(since), vec!["msg-2", "msg-3"]);
+
+        assert_eq!(
+            repo.get_message("msg-2").await.unwrap().unwrap().created_at,
+            200
+        );
+        assert!(repo.get_message("missing").await.unwrap().is_none());
+        let results = repo
+            .get_tool_results(&session.id, &["call-1".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(ids(results), vec!["msg-3"]);
+    }
+
+    #[tokio::test]
+    async fn test_pending_approvals() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+
+        let session = Session {
+            id: "test-session-4".to_string(),
+            project_id: None,
+            title: None,
+            summary: None,
+            status: SessionStatus::WaitingForAction,
+            created_at: chrono::Utc::now().timestamp(),
+            updated_at: chrono::Utc::now().timestamp(),
+            last_event_id: None,
+            metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
+        };
+        repo.create_session(&session)
+            .await
+            .expect("Failed to create session");
+
+        let tool_call = ToolCall {
+            id: "call-1".to_string(),
+            name: "write_file".to_string(),
+            input: serde_json::json!({"path": "a.txt", "content": "hi"}),
+        };
+        let approval = PendingApproval {
+            tool_call_id: tool_call.id.clone(),
+            task_id: "task-1".to_string(),
+            session_id: "test-session-4".to_string(),
+            agent_id: None,
+            tool_call,
+            remaining_calls: vec![],
+            settings: TaskSettings::default(),
+            workspace_root: "/tmp".to_string(),
+            worktree_path: None,
+            usage: BudgetUsage::default(),
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        repo.create_pending_approval(&approval)
+            .await
+            .expect("Failed to create pending approval");
+
+        let listed = repo
+            .list_pending_approvals(Some("test-session-4"))
+            .await
+            .expect("Failed to list pending approvals");
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].tool_call.name, "write_file");
+
+        let taken = repo
+            .take_pending_approval("call-1")
+            .await
+            .expect("Failed to take pending approval");
+        assert_eq!(taken.map(|a| a.task_id), Some("task-1".to_string()));
+
+        // A decision can only be applied once
+        let taken_again = repo
+            .take_pending_approval("call-1")
+            .await
+            .expect("Failed to take pending approval");
+        assert!(taken_again.is_none());
+        assert!(repo.list_pending_approvals(None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pinned_messages_and_compactions() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+
+        let session = Session {
+            id: "test-session-5".to_string(),
+            project_id: None,
+            title: None,
+            summary: None,
+            status: SessionStatus::Running,
+            created_at: chrono::Utc::now().timestamp(),
+            updated_at: chrono::Utc::now().timestamp(),
+            last_event_id: None,
+            metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
+        };
+        repo.create_session(&session)
+            .await
+            .expect("Failed to create session");
+
+        let message = Message {
+            id: "msg-pinned".to_string(),
+            session_id: "test-session-5".to_string(),
+            role: MessageRole::User,
+            content: MessageContent::Text {
+                text: "Always use tabs".to_string(),
+            },
+            created_at: chrono::Utc::now().timestamp(),
+            tool_call_id: None,
+            parent_id: None,
+            pinned: false,
+        };
+        repo.create_message(&message)
+            .await
+            .expect("Failed to create message");
+
+        assert!(repo.set_message_pinned("msg-pinned", true).await.unwrap());
+        assert!(!repo.set_message_pinned("missing", true).await.unwrap());
+        let messages = repo
+            .get_messages("test-session-5", None, None)
+            .await
+            .unwrap();
+        assert!(messages[0].pinned);
+
+        assert!(repo
+            .get_latest_compaction("test-session-5")
+            .await
+            .unwrap()
+            .is_none());
+        for (id, summary) in [("compaction-1", "first"), ("compaction-2", "second")] {
+            let compaction = ContextCompaction {
+                id: id.to_string(),
+                session_id: "test-session-5".to_string(),
+                summary: summary.to_string(),
+                first_kept_message_id: "msg-pinned".to_string(),
+                compacted_count: 3,
+                tokens_before: 9000,
+                tokens_after: 1200,
+                created_at: chrono::Utc::now().timestamp(),
+            };
+            repo.create_compaction(&compaction)
+                .await
+                .expect("Failed to create compaction");
+        }
+
+        let latest = repo
+            .get_latest_compaction("test-session-5")
+            .await
+            .unwrap()
+            .expect("Compaction should exist");
+        assert_eq!(latest.summary, "second");
+        assert_eq!(latest.tokens_before, 9000);
+        assert_eq!(
+            repo.list_compactions("test-session-5").await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plans() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+
+        let session = Session {
+            id: "test-session-6".to_string(),
+            project_id: None,
+            title: None,
+            summary: None,
+            status: SessionStatus::PlanReady,
+            created_at: chrono::Utc::now().timestamp(),
+            updated_at: chrono::Utc::now().timestamp(),
+            last_event_id: None,
+            metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
+        };
+        repo.create_session(&session)
+            .await
+            .expect("Failed to create session");
+        assert_eq!(
+            repo.get_session("test-session-6")
+                .await
+                .unwrap()
+                .map(|s| s.status),
+            Some(SessionStatus::PlanReady)
+        );
+
+        let plan = Plan {
+            id: "plan-1".to_string(),
+            session_id: "test-session-6".to_string(),
+            task_id: "task-1".to_string(),
+            agent_id: None,
+            summary: "Rename the config loader".to_string(),
+            steps: vec![
+                "Rename the module".to_string(),
+                "Update imports".to_string(),
+            ],
+            settings: TaskSettings::default(),
+            workspace_root: "/tmp".to_string(),
+            worktree_path: None,
+            created_at: chrono::Utc::now().timestamp(),
+            executed_at: None,
+        };
+        repo.create_plan(&plan)
+            .await
+            .expect("Failed to create plan");
+
+        let pending = repo
+            .get_pending_plan("test-session-6")
+            .await
+            .unwrap()
+            .expect("Plan should be pending");
+        assert_eq!(pending.steps.len(), 2);
+
+        // A plan is only executed once
+        assert!(repo.mark_plan_executed("plan-1", 42).await.unwrap());
+        assert!(!repo.mark_plan_executed("plan-1", 43).await.unwrap());
+        assert!(repo
+            .get_pending_plan("test-session-6")
+            .await
+            .unwrap()
+            .is_none());
+        let plans = repo.list_plans("test-session-6").await.unwrap();
+        assert_eq!(plans[0].executed_at, Some(42));
+    }
+
+    #[tokio::test]
+    async fn test_todos() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+
+        let now = chrono::Utc::now().timestamp();
+        let session = Session {
+            id: "test-session-todos".to_string(),
+            project_id: None,
+            title: None,
+            summary: None,
+            status: SessionStatus::Running,
+            created_at: now,
+            updated_at: now,
+            last_event_id: None,
+            metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
+        };
+        repo.create_session(&session)
+            .await
+            .expect("Failed to create session");
+        assert!(repo
+            .get_todos("test-session-todos")
+            .await
+            .unwrap()
+            .is_none());
+
+        let mut todos = TodoList {
+            session_id: "test-session-todos".to_string(),
+            items: vec![TodoItem {
+                id: "1".to_string(),
+                content: "Write the migration".to_string(),
+                status: TodoStatus::InProgress,
+            }],
+            updated_at: now,
+        };
+        repo.save_todos(&todos).await.expect("Failed to save todos");
+        todos.items[0].status = TodoStatus::Completed;
+        repo.save_todos(&todos).await.expect("Failed to save todos");
+
+        let saved = repo
+            .get_todos("test-session-todos")
+            .await
+            .unwrap()
+            .expect("Todos should exist");
+        assert_eq!(saved, todos);
+        assert_eq!(saved.progress(), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_stream_states() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+
+        let now = chrono::Utc::now().timestamp();
+        let session = Session {
+            id: "test-session-7".to_string(),
+            project_id: None,
+            title: None,
+            summary: None,
+            status: SessionStatus::Running,
+            created_at: now,
+            updated_at: now,
+            last_event_id: None,
+            metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
+        };
+        repo.create_session(&session)
+            .await
+            .expect("Failed to create session");
+
+        let mut state = StreamState {
+            task_id: "task-1".to_string(),
+            session_id: "test-session-7".to_string(),
+            agent_id: None,
+            messages: vec![],
+            text: "Partial".to_string(),
+            tool_calls: vec![],
+            usage: BudgetUsage::default(),
+            settings: TaskSettings::default(),
+            workspace_root: "/tmp".to_string(),
+            worktree_path: None,
+            created_at: now,
+            updated_at: now,
+            interrupted_at: None,
+            interrupted_message_id: None,
+        };
+        repo.save_stream_state(&state)
+            .await
+            .expect("Failed to save stream state");
+        state.text.push_str(" answer");
+        repo.save_stream_state(&state)
+            .await
+            .expect("Failed to update stream state");
+
+        let states = repo
+            .list_stream_states(Some("test-session-7"))
+            .await
+            .unwrap();
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].text, "Partial answer");
+
+        let taken = repo.take_stream_state("task-1").await.unwrap();
+        assert!(taken.is_some());
+        assert!(repo.take_stream_state("task-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_task_worktrees() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+
+        let now = chrono::Utc::now().timestamp();
+        let session = Session {
+            id: "test-session-8".to_string(),
+            project_id: None,
+            title: None,
+            summary: None,
+            status: SessionStatus::Running,
+            created_at: now,
+            updated_at: now,
+            last_event_id: None,
+            metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
+        };
+        repo.create_session(&session)
+            .await
+            .expect("Failed to create session");
+
+        let worktree = TaskWorktree {
+            task_id: "task-1".to_string(),
+            session_id: "test-session-8".to_string(),
+            project_path: "/tmp/project".to_string(),
+            path: "/tmp/worktrees/task-1".to_string(),
+            branch: "talkcody-task-task-1".to_string(),
+            base_commit: "abc123".to_string(),
+            created_at: now,
+        };
+        repo.create_task_worktree(&worktree)
+            .await
+            .expect("Failed to create task worktree");
+
+        let stored = repo.get_task_worktree("task-1").await.unwrap().unwrap();
+        assert_eq!(stored.branch, "talkcody-task-task-1");
+        assert_eq!(
+            repo.list_task_worktrees("test-session-8")
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        repo.delete_task_worktree("task-1").await.unwrap();
+        assert!(repo.get_task_worktree("task-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_runtime_event_log() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ChatHistoryRepository::new(db);
+
+        let now = chrono::Utc::now().timestamp();
+        let session = Session {
+            id: "test-session-9".to_string(),
+            project_id: None,
+            title: None,
+            summary: None,
+            status: SessionStatus::Running,
+            created_at: now,
+            updated_at: now,
+            last_event_id: None,
+            metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
+        };
+        repo.create_session(&session)
+            .await
+            .expect("Failed to create session");
+
+        let first = repo
+            .append_runtime_event(
+                "test-session-9",
+                None,
+                "token",
+                &serde_json::json!({ "type": "token", "token": "Hel" }),
+            )
+            .await
+            .unwrap();
+        let second = repo
+            .append_runtime_event(
+                "test-session-9",
+                Some("task-1"),
+                "taskCompleted",
+                &serde_json::json!({ "type": "taskCompleted" }),
+            )
+            .await
+            .unwrap();
+        assert!(second > first);
+
+        let events = repo
+            .list_runtime_events("test-session-9", None, None)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event["token"], "Hel");
+        assert_eq!(events[1].task_id.as_deref(), Some("task-1"));
+
+        let resumed = repo
+            .list_runtime_events("test-session-9", Some(first), None)
+            .await
+            .unwrap();
+        assert_eq!(resumed.len(), 1);
+        assert_eq!(resumed[0].sequence, second);
+    }
 }
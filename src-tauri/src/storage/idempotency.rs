@@ -0,0 +1,171 @@
+//! Idempotency Repository
+//! Stores the responses of requests sent with an idempotency key in
+//! settings.db, so retries of them are answered with the original response
+
+use crate::database::Database;
+use std::sync::Arc;
+
+/// The response to a request sent with an idempotency key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotentResponse {
+    /// Client the key belongs to, so clients can not replay each other's
+    pub scope: String,
+    pub key: String,
+    /// Hash of the request's method, path and body
+    pub fingerprint: String,
+    pub status: u16,
+    pub body: String,
+    pub created_at: i64,
+}
+
+/// Repository for stored idempotent responses
+#[derive(Clone)]
+pub struct IdempotencyRepository {
+    db: Arc<Database>,
+}
+
+impl IdempotencyRepository {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Store a response, replacing one stored for the same key
+    pub async fn save_response(&self, response: &IdempotentResponse) -> Result<(), String> {
+        let sql = r#"
+            INSERT OR REPLACE INTO idempotency_keys (scope, key, fingerprint, status, body, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+        "#;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(response.scope),
+                    serde_json::json!(response.key),
+                    serde_json::json!(response.fingerprint),
+                    serde_json::json!(response.status),
+                    serde_json::json!(response.body),
+                    serde_json::json!(response.created_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the response stored for a client's key
+    pub async fn get_response(
+        &self,
+        scope: &str,
+        key: &str,
+    ) -> Result<Option<IdempotentResponse>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM idempotency_keys WHERE scope = ? AND key = ?",
+                vec![serde_json::json!(scope), serde_json::json!(key)],
+            )
+            .await?;
+
+        Ok(result.rows.first().map(row_to_response))
+    }
+
+    /// Delete responses stored before `created_at`; returns how many were
+    pub async fn delete_responses_before(&self, created_at: i64) -> Result<u64, String> {
+        let result = self
+            .db
+            .execute(
+                "DELETE FROM idempotency_keys WHERE created_at < ?",
+                vec![serde_json::json!(created_at)],
+            )
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+}
+
+fn string_field(row: &serde_json::Value, field: &str) -> String {
+    row.get(field)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+fn row_to_response(row: &serde_json::Value) -> IdempotentResponse {
+    IdempotentResponse {
+        scope: string_field(row, "scope"),
+        key: string_field(row, "key"),
+        fingerprint: string_field(row, "fingerprint"),
+        status: row.get("status").and_then(|v| v.as_u64()).unwrap_or(200) as u16,
+        body: string_field(row, "body"),
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn create_test_db() -> (Arc<Database>, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect()
+            .await
+            .expect("Failed to connect to test database");
+
+        // Run migrations
+        let migrations = super::super::migrations::settings_migrations();
+        let runner = super::super::migrations::MigrationRunner::new(&db, &migrations);
+        runner.init().await.expect("Failed to init migrations");
+        runner.migrate().await.expect("Failed to run migrations");
+
+        (db, temp_dir)
+    }
+
+    fn response(scope: &str, key: &str, created_at: i64) -> IdempotentResponse {
+        IdempotentResponse {
+            scope: scope.to_string(),
+            key: key.to_string(),
+            fingerprint: "abc".to_string(),
+            status: 200,
+            body: r#"{"sessionId":"s1"}"#.to_string(),
+            created_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_idempotent_responses() {
+        let (db, _temp_dir) = create_test_db().await;
+        let repo = IdempotencyRepository::new(db);
+
+        let first = response("key-1", "retry-1", 100);
+        repo.save_response(&first).await.unwrap();
+        repo.save_response(&response("key-2", "retry-1", 200))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repo.get_response("key-1", "retry-1").await.unwrap(),
+            Some(first)
+        );
+        assert!(repo
+            .get_response("key-1", "retry-2")
+            .await
+            .unwrap()
+            .is_none());
+
+        assert_eq!(repo.delete_responses_before(150).await.unwrap(), 1);
+        assert!(repo
+            .get_response("key-1", "retry-1")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repo
+            .get_response("key-2", "retry-1")
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
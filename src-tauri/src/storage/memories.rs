@@ -0,0 +1,226 @@
+//! Memories Repository
+//! Handles CRUD operations for long-term agent memories in agents.db
+
+use crate::database::Database;
+use crate::storage::models::{Memory, MemoryKind};
+use std::sync::Arc;
+
+/// Repository for memory operations
+#[derive(Clone)]
+pub struct MemoriesRepository {
+    db: Arc<Database>,
+}
+
+impl MemoriesRepository {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Create a new memory
+    pub async fn create_memory(&self, memory: &Memory) -> Result<(), String> {
+        let sql = r#"
+            INSERT INTO memories (id, project_id, kind, content, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+        "#;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(memory.id),
+                    serde_json::json!(memory.project_id),
+                    serde_json::json!(memory.kind.as_str()),
+                    serde_json::json!(memory.content),
+                    serde_json::json!(memory.created_at),
+                    serde_json::json!(memory.updated_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get a memory by ID
+    pub async fn get_memory(&self, memory_id: &str) -> Result<Option<Memory>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM memories WHERE id = ?",
+                vec![serde_json::json!(memory_id)],
+            )
+            .await?;
+
+        Ok(result.rows.first().map(row_to_memory))
+    }
+
+    /// List global memories plus those of `project_id`, oldest first
+    pub async fn list_memories(&self, project_id: Option<&str>) -> Result<Vec<Memory>, String> {
+        let mut sql = "SELECT * FROM memories WHERE project_id IS NULL".to_string();
+        let mut params: Vec<serde_json::Value> = vec![];
+
+        if let Some(pid) = project_id {
+            sql.push_str(" OR project_id = ?");
+            params.push(serde_json::json!(pid));
+        }
+
+        sql.push_str(" ORDER BY created_at ASC, rowid ASC");
+
+        let result = self.db.query(&sql, params).await?;
+
+        Ok(result.rows.iter().map(row_to_memory).collect())
+    }
+
+    /// Update a memory. Returns `false` when it does not exist.
+    pub async fn update_memory(
+        &self,
+        memory_id: &str,
+        updates: MemoryUpdates,
+    ) -> Result<bool, String> {
+        let mut fields = Vec::new();
+        let mut params: Vec<serde_json::Value> = vec![];
+
+        if let Some(kind) = updates.kind {
+            fields.push("kind = ?");
+            params.push(serde_json::json!(kind.as_str()));
+        }
+
+        if let Some(content) = updates.content {
+            fields.push("content = ?");
+            params.push(serde_json::json!(content));
+        }
+
+        fields.push("updated_at = ?");
+        params.push(serde_json::json!(chrono::Utc::now().timestamp()));
+        params.push(serde_json::json!(memory_id));
+
+        let sql = format!("UPDATE memories SET {} WHERE id = ?", fields.join(", "));
+        let result = self.db.execute(&sql, params).await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Delete a memory. Returns `false` when it does not exist.
+    pub async fn delete_memory(&self, memory_id: &str) -> Result<bool, String> {
+        let result = self
+            .db
+            .execute(
+                "DELETE FROM memories WHERE id = ?",
+                vec![serde_json::json!(memory_id)],
+            )
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+}
+
+/// Updates for a memory (all fields optional)
+#[derive(Debug, Default)]
+pub struct MemoryUpdates {
+    pub kind: Option<MemoryKind>,
+    pub content: Option<String>,
+}
+
+// ============== Row Conversions ==============
+
+fn row_to_memory(row: &serde_json::Value) -> Memory {
+    Memory {
+        id: row
+            .get("id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        project_id: row
+            .get("project_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        kind: row
+            .get("kind")
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(MemoryKind::Fact),
+        content: row
+            .get("content")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+        updated_at: row.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn create_test_db() -> (Arc<Database>, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect()
+            .await
+            .expect("Failed to connect to test database");
+
+        // Run migrations
+        let migrations = super::super::migrations::agents_migrations();
+        let runner = super::super::migrations::MigrationRunner::new(&db, &migrations);
+        runner.init().await.expect("Failed to init migrations");
+        runner.migrate().await.expect("Failed to run migrations");
+
+        (db, temp_dir)
+    }
+
+    fn memory(id: &str, project_id: Option<&str>, content: &str) -> Memory {
+        Memory {
+            id: id.to_string(),
+            project_id: project_id.map(str::to_string),
+            kind: MemoryKind::Fact,
+            content: content.to_string(),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memories_are_scoped_by_project() {
+        let (db, _temp) = create_test_db().await;
+        let repo = MemoriesRepository::new(db);
+
+        repo.create_memory(&memory("global", None, "Prefers tabs"))
+            .await
+            .unwrap();
+        repo.create_memory(&memory("mem-a", Some("project-a"), "Uses pnpm"))
+            .await
+            .unwrap();
+        repo.create_memory(&memory("mem-b", Some("project-b"), "Uses cargo"))
+            .await
+            .unwrap();
+
+        let project_a = repo.list_memories(Some("project-a")).await.unwrap();
+        let ids: Vec<&str> = project_a.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["global", "mem-a"]);
+        assert_eq!(repo.list_memories(None).await.unwrap().len(), 1);
+
+        let updated = repo
+            .update_memory(
+                "global",
+                MemoryUpdates {
+                    kind: Some(MemoryKind::Preference),
+                    content: Some("Prefers spaces".to_string()),
+                },
+            )
+            .await
+            .unwrap();
+        assert!(updated);
+        let global = repo.get_memory("global").await.unwrap().unwrap();
+        assert_eq!(global.kind, MemoryKind::Preference);
+        assert_eq!(global.content, "Prefers spaces");
+
+        assert!(repo.delete_memory("mem-a").await.unwrap());
+        assert!(!repo.delete_memory("mem-a").await.unwrap());
+        assert!(!repo
+            .update_memory("mem-a", MemoryUpdates::default())
+            .await
+            .unwrap());
+    }
+}
@@ -96,13 +96,12 @@ impl<'a> MigrationRunner<'a> {
     }
 
     async fn apply_migration(&self, migration: &Migration) -> Result<(), String> {
-        // Execute migration in transaction
-        self.db.execute(migration.up_sql, vec![]).await?;
-
-        // Record migration
+        // Migrations may hold several statements, such as a table and its
+        // indexes; they and their record are kept only if all of them apply
         let now = chrono::Utc::now().timestamp();
         self.db
-            .execute(
+            .execute_script(
+                migration.up_sql,
                 "INSERT INTO _migrations (version, name, applied_at) VALUES (?, ?, ?)",
                 vec![
                     serde_json::json!(migration.version),
@@ -110,9 +109,7 @@ impl<'a> MigrationRunner<'a> {
                     serde_json::json!(now),
                 ],
             )
-            .await?;
-
-        Ok(())
+            .await
     }
 }
 
@@ -200,6 +197,314 @@ pub fn chat_history_migrations() -> MigrationRegistry {
         down_sql: Some("DROP TABLE attachments;"),
     });
 
+    registry.register(Migration {
+        version: 5,
+        name: "create_pending_approvals_table",
+        up_sql: r#"
+            CREATE TABLE pending_approvals (
+                tool_call_id TEXT PRIMARY KEY,
+                task_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_pending_approvals_session ON pending_approvals(session_id);
+            CREATE INDEX idx_pending_approvals_task ON pending_approvals(task_id);
+        "#,
+        down_sql: Some("DROP TABLE pending_approvals;"),
+    });
+
+    registry.register(Migration {
+        version: 6,
+        name: "add_context_compaction",
+        up_sql: r#"
+            ALTER TABLE messages ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+            CREATE TABLE context_compactions (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                summary TEXT NOT NULL,
+                first_kept_message_id TEXT NOT NULL,
+                compacted_count INTEGER NOT NULL,
+                tokens_before INTEGER NOT NULL,
+                tokens_after INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_context_compactions_session ON context_compactions(session_id);
+        "#,
+        down_sql: Some("DROP TABLE context_compactions; ALTER TABLE messages DROP COLUMN pinned;"),
+    });
+
+    registry.register(Migration {
+        version: 7,
+        name: "create_checkpoints_table",
+        up_sql: r#"
+            CREATE TABLE checkpoints (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                task_id TEXT NOT NULL,
+                tool_call_id TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_checkpoints_session ON checkpoints(session_id);
+        "#,
+        down_sql: Some("DROP TABLE checkpoints;"),
+    });
+
+    registry.register(Migration {
+        version: 8,
+        name: "create_budget_pauses_table",
+        up_sql: r#"
+            CREATE TABLE budget_pauses (
+                task_id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_budget_pauses_session ON budget_pauses(session_id);
+        "#,
+        down_sql: Some("DROP TABLE budget_pauses;"),
+    });
+
+    registry.register(Migration {
+        version: 9,
+        name: "create_plans_table",
+        up_sql: r#"
+            CREATE TABLE plans (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                task_id TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                executed_at INTEGER,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_plans_session ON plans(session_id, created_at);
+        "#,
+        down_sql: Some("DROP TABLE plans;"),
+    });
+
+    registry.register(Migration {
+        version: 10,
+        name: "create_stream_states_table",
+        up_sql: r#"
+            CREATE TABLE stream_states (
+                task_id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_stream_states_session ON stream_states(session_id);
+        "#,
+        down_sql: Some("DROP TABLE stream_states;"),
+    });
+
+    registry.register(Migration {
+        version: 11,
+        name: "add_session_summary",
+        up_sql: r#"
+            ALTER TABLE sessions ADD COLUMN summary TEXT;
+        "#,
+        down_sql: Some("ALTER TABLE sessions DROP COLUMN summary;"),
+    });
+
+    registry.register(Migration {
+        version: 12,
+        name: "create_task_worktrees_table",
+        up_sql: r#"
+            CREATE TABLE task_worktrees (
+                task_id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_task_worktrees_session ON task_worktrees(session_id);
+        "#,
+        down_sql: Some("DROP TABLE task_worktrees;"),
+    });
+
+    registry.register(Migration {
+        version: 13,
+        name: "create_runtime_events_table",
+        up_sql: r#"
+            CREATE TABLE runtime_events (
+                sequence INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                task_id TEXT,
+                event_type TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_runtime_events_session ON runtime_events(session_id, sequence);
+        "#,
+        down_sql: Some("DROP TABLE runtime_events;"),
+    });
+
+    registry.register(Migration {
+        version: 14,
+        name: "add_session_archival",
+        up_sql: r#"
+            ALTER TABLE sessions ADD COLUMN starred INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE sessions ADD COLUMN archived_at INTEGER;
+            CREATE INDEX idx_sessions_archived_at ON sessions(archived_at);
+        "#,
+        down_sql: Some(
+            "DROP INDEX idx_sessions_archived_at; ALTER TABLE sessions DROP COLUMN archived_at; ALTER TABLE sessions DROP COLUMN starred;",
+        ),
+    });
+
+    registry.register(Migration {
+        version: 15,
+        name: "create_todos_table",
+        up_sql: r#"
+            CREATE TABLE todos (
+                session_id TEXT PRIMARY KEY,
+                payload TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+        "#,
+        down_sql: Some("DROP TABLE todos;"),
+    });
+
+    registry.register(Migration {
+        version: 16,
+        name: "create_webhook_deliveries_table",
+        up_sql: r#"
+            CREATE TABLE webhook_deliveries (
+                id TEXT PRIMARY KEY,
+                webhook_id TEXT NOT NULL,
+                project_id TEXT NOT NULL,
+                session_id TEXT,
+                task_id TEXT,
+                event TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                response_status INTEGER,
+                error TEXT,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE INDEX idx_webhook_deliveries_webhook ON webhook_deliveries(webhook_id, created_at);
+            CREATE INDEX idx_webhook_deliveries_project ON webhook_deliveries(project_id, created_at);
+        "#,
+        down_sql: Some("DROP TABLE webhook_deliveries;"),
+    });
+
+    // Full-text indexes of session titles and the text of messages, keyed by
+    // the rowid of the indexed row and kept current by triggers
+    registry.register(Migration {
+        version: 17,
+        name: "create_search_index",
+        up_sql: r#"
+            CREATE VIRTUAL TABLE sessions_fts USING fts5(title, tokenize = 'unicode61 remove_diacritics 2');
+            CREATE VIRTUAL TABLE messages_fts USING fts5(text, tokenize = 'unicode61 remove_diacritics 2');
+
+            INSERT INTO sessions_fts (rowid, title)
+                SELECT rowid, title FROM sessions WHERE title IS NOT NULL;
+            INSERT INTO messages_fts (rowid, text)
+                SELECT rowid, json_extract(content, '$.text') FROM messages
+                WHERE json_extract(content, '$.type') = 'text';
+
+            CREATE TRIGGER sessions_fts_insert AFTER INSERT ON sessions WHEN new.title IS NOT NULL BEGIN
+                INSERT INTO sessions_fts (rowid, title) VALUES (new.rowid, new.title);
+            END;
+            CREATE TRIGGER sessions_fts_update AFTER UPDATE OF title ON sessions BEGIN
+                DELETE FROM sessions_fts WHERE rowid = old.rowid;
+                INSERT INTO sessions_fts (rowid, title)
+                    SELECT new.rowid, new.title WHERE new.title IS NOT NULL;
+            END;
+            CREATE TRIGGER sessions_fts_delete AFTER DELETE ON sessions BEGIN
+                DELETE FROM sessions_fts WHERE rowid = old.rowid;
+            END;
+            CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages
+                WHEN json_extract(new.content, '$.type') = 'text' BEGIN
+                INSERT INTO messages_fts (rowid, text)
+                    VALUES (new.rowid, json_extract(new.content, '$.text'));
+            END;
+            CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+                DELETE FROM messages_fts WHERE rowid = old.rowid;
+            END;
+        "#,
+        down_sql: Some(
+            "DROP TRIGGER messages_fts_delete; DROP TRIGGER messages_fts_insert; DROP TRIGGER sessions_fts_delete; DROP TRIGGER sessions_fts_update; DROP TRIGGER sessions_fts_insert; DROP TABLE messages_fts; DROP TABLE sessions_fts;",
+        ),
+    });
+
+    registry.register(Migration {
+        version: 18,
+        name: "add_session_user",
+        up_sql: r#"
+            ALTER TABLE sessions ADD COLUMN user_id TEXT;
+            CREATE INDEX idx_sessions_user ON sessions(user_id);
+        "#,
+        down_sql: Some("DROP INDEX idx_sessions_user; ALTER TABLE sessions DROP COLUMN user_id;"),
+    });
+
+    registry.register(Migration {
+        version: 19,
+        name: "create_projects_table",
+        up_sql: r#"
+            CREATE TABLE projects (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                workspace_path TEXT NOT NULL,
+                default_settings TEXT,
+                user_id TEXT,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE INDEX idx_projects_user ON projects(user_id);
+        "#,
+        down_sql: Some("DROP TABLE projects;"),
+    });
+
+    registry.register(Migration {
+        version: 20,
+        name: "create_outbound_messages_table",
+        up_sql: r#"
+            CREATE TABLE outbound_messages (
+                id TEXT PRIMARY KEY,
+                integration_id TEXT NOT NULL,
+                recipient TEXT NOT NULL,
+                content TEXT NOT NULL,
+                edit_message_id TEXT,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at INTEGER NOT NULL,
+                last_error TEXT,
+                created_at INTEGER NOT NULL
+            );
+            CREATE INDEX idx_outbound_messages_due ON outbound_messages(integration_id, next_attempt_at);
+        "#,
+        down_sql: Some("DROP TABLE outbound_messages;"),
+    });
+
+    registry.register(Migration {
+        version: 21,
+        name: "create_integration_threads_table",
+        up_sql: r#"
+            CREATE TABLE integration_threads (
+                integration_id TEXT NOT NULL,
+                chat_id TEXT NOT NULL,
+                message_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (integration_id, chat_id, message_id),
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_integration_threads_session ON integration_threads(session_id);
+        "#,
+        down_sql: Some("DROP TABLE integration_threads;"),
+    });
+
     registry
 }
 
@@ -242,6 +547,23 @@ pub fn agents_migrations() -> MigrationRegistry {
         down_sql: Some("DROP TABLE agent_sessions;"),
     });
 
+    registry.register(Migration {
+        version: 3,
+        name: "create_memories_table",
+        up_sql: r#"
+            CREATE TABLE memories (
+                id TEXT PRIMARY KEY,
+                project_id TEXT,
+                kind TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE INDEX idx_memories_project ON memories(project_id);
+        "#,
+        down_sql: Some("DROP TABLE memories;"),
+    });
+
     registry
 }
 
@@ -276,6 +598,85 @@ pub fn settings_migrations() -> MigrationRegistry {
         down_sql: Some("DROP TABLE task_settings;"),
     });
 
+    registry.register(Migration {
+        version: 3,
+        name: "create_api_keys_table",
+        up_sql: r#"
+            CREATE TABLE api_keys (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                salt TEXT NOT NULL,
+                key_hash TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                last_used_at INTEGER,
+                revoked_at INTEGER
+            );
+        "#,
+        down_sql: Some("DROP TABLE api_keys;"),
+    });
+
+    registry.register(Migration {
+        version: 4,
+        name: "create_refresh_tokens_table",
+        up_sql: r#"
+            CREATE TABLE refresh_tokens (
+                id TEXT PRIMARY KEY,
+                family_id TEXT NOT NULL,
+                api_key_id TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL,
+                revoked_at INTEGER
+            );
+            CREATE INDEX idx_refresh_tokens_family ON refresh_tokens(family_id);
+            CREATE INDEX idx_refresh_tokens_api_key ON refresh_tokens(api_key_id);
+        "#,
+        down_sql: Some("DROP TABLE refresh_tokens;"),
+    });
+
+    registry.register(Migration {
+        version: 5,
+        name: "create_users_table",
+        up_sql: r#"
+            CREATE TABLE users (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                created_at INTEGER NOT NULL
+            );
+            ALTER TABLE api_keys ADD COLUMN user_id TEXT;
+            CREATE INDEX idx_api_keys_user ON api_keys(user_id);
+        "#,
+        down_sql: Some(
+            "DROP INDEX idx_api_keys_user; ALTER TABLE api_keys DROP COLUMN user_id; DROP TABLE users;",
+        ),
+    });
+
+    registry.register(Migration {
+        version: 6,
+        name: "create_idempotency_keys_table",
+        up_sql: r#"
+            CREATE TABLE idempotency_keys (
+                scope TEXT NOT NULL,
+                key TEXT NOT NULL,
+                fingerprint TEXT NOT NULL,
+                status INTEGER NOT NULL,
+                body TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (scope, key)
+            );
+            CREATE INDEX idx_idempotency_keys_created ON idempotency_keys(created_at);
+        "#,
+        down_sql: Some("DROP TABLE idempotency_keys;"),
+    });
+
+    registry.register(Migration {
+        version: 7,
+        name: "add_api_key_scopes",
+        up_sql: r#"
+            ALTER TABLE api_keys ADD COLUMN scopes TEXT;
+        "#,
+        down_sql: Some("ALTER TABLE api_keys DROP COLUMN scopes;"),
+    });
+
     registry
 }
 
@@ -308,22 +709,54 @@ pub async fn run_all_migrations(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::Database;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_failed_migration_applies_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Database::new(db_path.to_string_lossy().to_string());
+        db.connect().await.unwrap();
+        let mut registry = MigrationRegistry::new("test");
+        registry.register(Migration {
+            version: 1,
+            name: "create_notes",
+            up_sql: r#"
+                CREATE TABLE notes (id TEXT PRIMARY KEY);
+                CREATE INDEX idx_notes_missing ON notes(missing);
+            "#,
+            down_sql: None,
+        });
+        let runner = MigrationRunner::new(&db, &registry);
+
+        assert!(runner.migrate().await.is_err());
+        assert_eq!(runner.current_version().await.unwrap(), 0);
+        let tables = db
+            .query(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'notes'",
+                vec![],
+            )
+            .await
+            .unwrap();
+        assert!(tables.rows.is_empty());
+    }
 
     #[test]
     fn test_chat_history_migrations_count() {
         let registry = chat_history_migrations();
-        assert_eq!(registry.migrations().len(), 4);
+        assert_eq!(registry.migrations().len(), 21);
     }
 
     #[test]
     fn test_agents_migrations_count() {
         let registry = agents_migrations();
-        assert_eq!(registry.migrations().len(), 2);
+        assert_eq!(registry.migrations().len(), 3);
     }
 
     #[test]
     fn test_settings_migrations_count() {
         let registry = settings_migrations();
-        assert_eq!(registry.migrations().len(), 2);
+        assert_eq!(registry.migrations().len(), 7);
     }
 }
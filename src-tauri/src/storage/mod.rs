@@ -1,28 +1,44 @@
 //! Storage Layer for Cloud Backend
 //!
 //! Provides SQLite repositories for:
-//! - chat_history.db: Sessions, messages, events, attachments
-//! - agents.db: Agent configurations and agent-session associations  
-//! - settings.db: Application settings and task-specific settings
+//! - chat_history.db: Projects, sessions, messages, events, attachments, webhook
+//!   deliveries, outbound integration messages and the sessions of IM threads
+//! - agents.db: Agent configurations, agent-session associations and long-term memories
+//! - settings.db: Application settings, task-specific settings and server API keys
+//!   with their refresh tokens
 //!
 //! All repositories use the shared Database abstraction from database.rs
 
 pub mod agents;
+pub mod api_keys;
 pub mod attachments;
 pub mod chat_history;
+pub mod idempotency;
+pub mod memories;
 pub mod migrations;
 pub mod models;
+pub mod outbox;
+pub mod pagination;
+pub mod projects;
 pub mod settings;
+pub mod threads;
 
 use crate::database::Database;
 use std::path::PathBuf;
 use std::sync::Arc;
 
 pub use agents::{AgentUpdates, AgentsRepository};
+pub use api_keys::{ApiKeysRepository, RefreshToken, StoredApiKey};
 pub use attachments::AttachmentsRepository;
 pub use chat_history::ChatHistoryRepository;
+pub use idempotency::{IdempotencyRepository, IdempotentResponse};
+pub use memories::{MemoriesRepository, MemoryUpdates};
 pub use models::*;
+pub use outbox::{OutboxRepository, QueuedMessage};
+pub use pagination::{Page, PageRequest, SortOrder};
+pub use projects::{ProjectUpdates, ProjectsRepository};
 pub use settings::SettingsRepository;
+pub use threads::ThreadsRepository;
 
 /// Main storage manager that owns all repositories
 /// Provides unified access to all database operations
@@ -34,8 +50,20 @@ pub struct Storage {
     pub agents: AgentsRepository,
     /// Settings repository (settings.db)
     pub settings: SettingsRepository,
+    /// Long-term memories repository (agents.db)
+    pub memories: MemoriesRepository,
     /// Attachments repository (chat_history.db + filesystem)
     pub attachments: AttachmentsRepository,
+    /// Server API keys repository (settings.db)
+    pub api_keys: ApiKeysRepository,
+    /// Projects repository (chat_history.db)
+    pub projects: ProjectsRepository,
+    /// Responses to requests with idempotency keys (settings.db)
+    pub idempotency: IdempotencyRepository,
+    /// Messages waiting to go out through integrations (chat_history.db)
+    pub outbox: OutboxRepository,
+    /// Sessions IM messages belong to (chat_history.db)
+    pub threads: ThreadsRepository,
 }
 
 impl Storage {
@@ -79,8 +107,14 @@ impl Storage {
         // Create repositories
         // Clone chat_history_db for attachments (both use the same DB)
         let chat_history_db_for_attachments = chat_history_db.clone();
+        let projects = ProjectsRepository::new(chat_history_db.clone());
+        let outbox = OutboxRepository::new(chat_history_db.clone());
+        let threads = ThreadsRepository::new(chat_history_db.clone());
         let chat_history = ChatHistoryRepository::new(chat_history_db);
+        let memories = MemoriesRepository::new(agents_db.clone());
         let agents = AgentsRepository::new(agents_db);
+        let api_keys = ApiKeysRepository::new(settings_db.clone());
+        let idempotency = IdempotencyRepository::new(settings_db.clone());
         let settings = SettingsRepository::new(settings_db);
         let attachments =
             AttachmentsRepository::new(chat_history_db_for_attachments, attachments_root);
@@ -89,7 +123,13 @@ impl Storage {
             chat_history,
             agents,
             settings,
+            memories,
             attachments,
+            api_keys,
+            projects,
+            idempotency,
+            outbox,
+            threads,
         })
     }
 
@@ -163,11 +203,15 @@ mod tests {
             id: "test-session".to_string(),
             project_id: Some("project-1".to_string()),
             title: Some("Test Session".to_string()),
+            summary: None,
             status: SessionStatus::Created,
             created_at: chrono::Utc::now().timestamp(),
             updated_at: chrono::Utc::now().timestamp(),
             last_event_id: None,
             metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
         };
 
         storage
@@ -23,6 +23,10 @@ pub enum SessionStatus {
     Running,
     /// Waiting for user action (approval, tool response)
     WaitingForAction,
+    /// Agent is analysing in plan mode with read-only tools
+    Planning,
+    /// A plan was submitted and waits to be executed
+    PlanReady,
     /// Session completed successfully
     Completed,
     /// Session ended with error
@@ -37,6 +41,8 @@ impl SessionStatus {
             SessionStatus::Created => "created",
             SessionStatus::Running => "running",
             SessionStatus::WaitingForAction => "waiting_for_action",
+            SessionStatus::Planning => "planning",
+            SessionStatus::PlanReady => "plan_ready",
             SessionStatus::Completed => "completed",
             SessionStatus::Error => "error",
             SessionStatus::Cancelled => "cancelled",
@@ -52,6 +58,8 @@ impl std::str::FromStr for SessionStatus {
             "created" => Ok(SessionStatus::Created),
             "running" => Ok(SessionStatus::Running),
             "waiting_for_action" => Ok(SessionStatus::WaitingForAction),
+            "planning" => Ok(SessionStatus::Planning),
+            "plan_ready" => Ok(SessionStatus::PlanReady),
             "completed" => Ok(SessionStatus::Completed),
             "error" => Ok(SessionStatus::Error),
             "cancelled" => Ok(SessionStatus::Cancelled),
@@ -60,6 +68,24 @@ impl std::str::FromStr for SessionStatus {
     }
 }
 
+/// Column sessions are listed by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SessionSort {
+    #[default]
+    UpdatedAt,
+    CreatedAt,
+}
+
+impl SessionSort {
+    pub fn column(&self) -> &'static str {
+        match self {
+            SessionSort::UpdatedAt => "updated_at",
+            SessionSort::CreatedAt => "created_at",
+        }
+    }
+}
+
 /// A chat session containing messages and metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -67,6 +93,9 @@ pub struct Session {
     pub id: SessionId,
     pub project_id: Option<String>,
     pub title: Option<String>,
+    /// Running summary of the conversation, generated as the session goes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
     pub status: SessionStatus,
     pub created_at: i64,
     pub updated_at: i64,
@@ -74,6 +103,16 @@ pub struct Session {
     pub last_event_id: Option<EventId>,
     /// Additional metadata as JSON object
     pub metadata: Option<serde_json::Value>,
+    /// Starred sessions are kept by the retention policy
+    #[serde(default)]
+    pub starred: bool,
+    /// When the session was archived; archived sessions are hidden from
+    /// default lists until restored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<i64>,
+    /// User the session belongs to; `None` for sessions of the machine's owner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
 }
 
 /// Role of a message sender
@@ -124,6 +163,9 @@ pub struct Message {
     pub tool_call_id: Option<ToolCallId>,
     /// Parent message ID for threading
     pub parent_id: Option<MessageId>,
+    /// Pinned messages survive context compaction verbatim
+    #[serde(default)]
+    pub pinned: bool,
 }
 
 /// Content of a message - can be text or structured content
@@ -136,6 +178,14 @@ pub enum MessageContent {
     ToolCalls { calls: Vec<ToolCall> },
     #[serde(rename = "tool_result")]
     ToolResult { result: serde_json::Value },
+    /// Reasoning an interleaved-thinking model produced before its tool
+    /// calls; the provider may require it back, signature included
+    #[serde(rename = "reasoning")]
+    Reasoning {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        provider_metadata: Option<serde_json::Value>,
+    },
 }
 
 /// A tool call from the assistant
@@ -161,6 +211,8 @@ pub enum EventType {
     ToolCall,
     /// Tool execution result
     ToolResult,
+    /// Token usage of model responses
+    Usage,
     /// Error occurred
     Error,
 }
@@ -173,6 +225,7 @@ impl EventType {
             EventType::MessageFinal => "message.final",
             EventType::ToolCall => "tool.call",
             EventType::ToolResult => "tool.result",
+            EventType::Usage => "usage",
             EventType::Error => "error",
         }
     }
@@ -188,6 +241,7 @@ impl std::str::FromStr for EventType {
             "message.final" => Ok(EventType::MessageFinal),
             "tool.call" => Ok(EventType::ToolCall),
             "tool.result" => Ok(EventType::ToolResult),
+            "usage" => Ok(EventType::Usage),
             "error" => Ok(EventType::Error),
             _ => Err(format!("Unknown event type: {}", s)),
         }
@@ -239,11 +293,351 @@ pub struct TaskSettings {
     pub auto_approve_plan: Option<bool>,
     /// Enable auto code review
     pub auto_code_review: Option<bool>,
+    /// Restrict the agent to read-only tools until it submits a plan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_mode: Option<bool>,
+    /// Restrict the agent to read-only tools for the whole task, as for tasks
+    /// started by clients that may not run tools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_tools: Option<bool>,
+    /// Kinds of tools the session may use; every kind when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_capabilities: Option<Vec<ToolCapability>>,
+    /// Name of a custom agent defined in the workspace's `.talkcody/agents`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// Models used for each phase of the task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models: Option<PhaseModels>,
+    /// Spending limits checked by the agent loop
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget: Option<TaskBudget>,
     /// Additional custom settings
     #[serde(flatten)]
     pub extra: HashMap<String, serde_json::Value>,
 }
 
+impl TaskSettings {
+    /// Whether the session may use tools needing `capability`
+    pub fn permits(&self, capability: ToolCapability) -> bool {
+        self.tool_capabilities
+            .as_ref()
+            .map_or(true, |capabilities| capabilities.contains(&capability))
+    }
+}
+
+/// What a tool does, which a session must permit to use it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ToolCapability {
+    /// Read the workspace, or keep the session's notes, todos and plans
+    FilesystemRead,
+    /// Change files in the workspace
+    FilesystemWrite,
+    /// Run commands and scripts
+    Shell,
+    /// Reach other hosts, such as search engines, web servers and databases
+    Network,
+    /// Push commits with `git push` from the shell
+    GitPush,
+}
+
+/// Phase of a task that makes its own LLM requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ModelPhase {
+    Title,
+    Planning,
+    Coding,
+    Summarization,
+}
+
+/// Model for each phase of a task, as `model` or `model@provider`. Unset
+/// phases fall back to the task's default model.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseModels {
+    pub title: Option<String>,
+    pub planning: Option<String>,
+    pub coding: Option<String>,
+    pub summarization: Option<String>,
+}
+
+impl PhaseModels {
+    pub fn get(&self, phase: ModelPhase) -> Option<&str> {
+        match phase {
+            ModelPhase::Title => self.title.as_deref(),
+            ModelPhase::Planning => self.planning.as_deref(),
+            ModelPhase::Coding => self.coding.as_deref(),
+            ModelPhase::Summarization => self.summarization.as_deref(),
+        }
+    }
+}
+
+/// Per-task spending limits. Unset limits are not enforced.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskBudget {
+    pub max_cost_usd: Option<f64>,
+    /// Input plus output tokens
+    pub max_tokens: Option<u64>,
+    /// LLM round trips
+    pub max_iterations: Option<u32>,
+    /// Time spent running, excluding time waiting for the user
+    pub max_duration_secs: Option<u64>,
+}
+
+/// Resources a task has consumed so far
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetUsage {
+    pub cost_usd: f64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub iterations: u32,
+    pub duration_ms: u64,
+}
+
+/// A budget limit a task ran into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BudgetLimit {
+    Cost,
+    Tokens,
+    Iterations,
+    Duration,
+}
+
+/// A task paused because it exceeded its budget, persisted until the user
+/// decides whether it may continue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetPause {
+    pub task_id: TaskId,
+    pub session_id: SessionId,
+    pub agent_id: Option<AgentId>,
+    pub exceeded: Vec<BudgetLimit>,
+    pub usage: BudgetUsage,
+    /// Tool calls from the last assistant turn that have not run yet
+    pub remaining_calls: Vec<ToolCall>,
+    pub settings: TaskSettings,
+    pub workspace_root: String,
+    pub worktree_path: Option<String>,
+    pub created_at: i64,
+}
+
+/// Output of a task's streaming response, saved while it arrives so a run
+/// cut short by a crash can be restored after a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamState {
+    pub task_id: TaskId,
+    pub session_id: SessionId,
+    pub agent_id: Option<AgentId>,
+    /// Messages of the run before the current response, not stored yet
+    pub messages: Vec<Message>,
+    /// Text streamed so far
+    pub text: String,
+    /// Complete tool calls received so far
+    pub tool_calls: Vec<ToolCall>,
+    pub usage: BudgetUsage,
+    pub settings: TaskSettings,
+    pub workspace_root: String,
+    pub worktree_path: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// Set when the run was found cut short on startup
+    #[serde(default)]
+    pub interrupted_at: Option<i64>,
+    /// Stored message holding the partial text of an interrupted run
+    #[serde(default)]
+    pub interrupted_message_id: Option<String>,
+}
+
+/// Plan submitted by a task running in plan mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Plan {
+    pub id: String,
+    pub session_id: SessionId,
+    pub task_id: TaskId,
+    pub agent_id: Option<AgentId>,
+    pub summary: String,
+    pub steps: Vec<String>,
+    /// Settings and workspace of the planning task, reused to execute the plan
+    pub settings: TaskSettings,
+    pub workspace_root: String,
+    pub worktree_path: Option<String>,
+    pub created_at: i64,
+    /// When a task was started to execute the plan
+    pub executed_at: Option<i64>,
+}
+
+/// Status of an item on a session's todo list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoStatus {
+    Pending,
+    InProgress,
+    Completed,
+    Cancelled,
+}
+
+/// Item on the todo list the agent keeps with the `todo` tool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoItem {
+    pub id: String,
+    pub content: String,
+    pub status: TodoStatus,
+}
+
+/// Todo list of a session, replaced as a whole on every update
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoList {
+    pub session_id: SessionId,
+    pub items: Vec<TodoItem>,
+    pub updated_at: i64,
+}
+
+impl TodoList {
+    /// Items completed and items not cancelled
+    pub fn progress(&self) -> (usize, usize) {
+        let completed = self
+            .items
+            .iter()
+            .filter(|item| item.status == TodoStatus::Completed)
+            .count();
+        let total = self
+            .items
+            .iter()
+            .filter(|item| item.status != TodoStatus::Cancelled)
+            .count();
+        (completed, total)
+    }
+}
+
+/// Git worktree an isolated task runs in, kept until its branch is merged
+/// back or discarded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskWorktree {
+    pub task_id: TaskId,
+    pub session_id: SessionId,
+    /// Repository the worktree was created from
+    pub project_path: String,
+    pub path: String,
+    pub branch: String,
+    pub base_commit: String,
+    pub created_at: i64,
+}
+
+/// A runtime event in a session's append-only event log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeEventRecord {
+    /// Position in the log; increases across all sessions
+    pub sequence: i64,
+    pub session_id: SessionId,
+    pub task_id: Option<TaskId>,
+    pub event_type: String,
+    /// The serialized event
+    pub event: serde_json::Value,
+    pub created_at: i64,
+}
+
+/// Where a search matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SearchHitKind {
+    /// The session's title
+    Title,
+    /// The text of one of the session's messages
+    Message,
+}
+
+/// Part of a snippet that matched, in characters from its start
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HighlightRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A session title or message matching a full-text search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub session_id: SessionId,
+    pub session_title: Option<String>,
+    pub kind: SearchHitKind,
+    /// Set for message hits
+    pub message_id: Option<MessageId>,
+    /// Text around the matches
+    pub snippet: String,
+    pub highlights: Vec<HighlightRange>,
+    /// BM25 score; lower ranks first
+    pub rank: f64,
+    pub created_at: i64,
+}
+
+/// Outcome of a webhook delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WebhookDeliveryStatus {
+    /// Not yet accepted; attempts are still being made
+    Pending,
+    Delivered,
+    /// Every attempt failed
+    Failed,
+}
+
+impl WebhookDeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookDeliveryStatus::Pending => "pending",
+            WebhookDeliveryStatus::Delivered => "delivered",
+            WebhookDeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for WebhookDeliveryStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(WebhookDeliveryStatus::Pending),
+            "delivered" => Ok(WebhookDeliveryStatus::Delivered),
+            "failed" => Ok(WebhookDeliveryStatus::Failed),
+            _ => Err(format!("Unknown webhook delivery status: {}", s)),
+        }
+    }
+}
+
+/// A webhook POST of one event, with the result of its latest attempt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub project_id: String,
+    pub session_id: Option<SessionId>,
+    pub task_id: Option<TaskId>,
+    pub event: String,
+    /// The body that was POSTed
+    pub payload: serde_json::Value,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i64,
+    /// HTTP status of the latest response
+    pub response_status: Option<i64>,
+    /// Why the latest attempt failed
+    pub error: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
 /// Attachment/file upload metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -292,6 +686,134 @@ impl std::str::FromStr for AttachmentOrigin {
     }
 }
 
+/// Kind of a long-term memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MemoryKind {
+    /// Something true about the project or the user's environment
+    Fact,
+    /// How the user wants the agent to work
+    Preference,
+}
+
+impl MemoryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryKind::Fact => "fact",
+            MemoryKind::Preference => "preference",
+        }
+    }
+}
+
+impl std::str::FromStr for MemoryKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fact" => Ok(MemoryKind::Fact),
+            "preference" => Ok(MemoryKind::Preference),
+            _ => Err(format!("Unknown memory kind: {}", s)),
+        }
+    }
+}
+
+/// A project sessions can belong to, with the workspace and settings its
+/// tasks default to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    /// Directory tasks of the project run in unless they name another
+    pub workspace_path: String,
+    /// Settings of tasks of the project that set none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_settings: Option<TaskSettings>,
+    /// User the project belongs to; `None` for projects of the machine's owner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Long-term memory of the agent, kept across sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Memory {
+    pub id: String,
+    /// Project the memory belongs to; `None` for global memories
+    pub project_id: Option<String>,
+    pub kind: MemoryKind,
+    pub content: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Key accepted by the HTTP server; only a salted hash of its secret is stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    /// When the key was revoked; revoked keys are rejected
+    pub revoked_at: Option<i64>,
+    /// User the key belongs to, whose sessions are all it reaches; `None`
+    /// for keys of the machine's owner, which reach every session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// What requests made with the key may do; keys created before scopes
+    /// existed have every scope
+    #[serde(default = "ApiKeyScope::all")]
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+/// Permission an API key grants to the requests made with it and the tokens
+/// issued for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApiKeyScope {
+    /// Read sessions, their messages, tasks and events
+    ReadSessions,
+    /// Create and change sessions and start tasks
+    WriteSessions,
+    /// Let tasks run tools that need approval or modify files, and decide
+    /// on tool calls
+    ExecuteTools,
+    /// Administer the server: keys, users, pairing, stats and policies
+    Admin,
+}
+
+impl ApiKeyScope {
+    pub fn all() -> Vec<ApiKeyScope> {
+        vec![
+            ApiKeyScope::ReadSessions,
+            ApiKeyScope::WriteSessions,
+            ApiKeyScope::ExecuteTools,
+            ApiKeyScope::Admin,
+        ]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::ReadSessions => "read-sessions",
+            ApiKeyScope::WriteSessions => "write-sessions",
+            ApiKeyScope::ExecuteTools => "execute-tools",
+            ApiKeyScope::Admin => "admin",
+        }
+    }
+}
+
+/// Team member using the machine remotely through their own API keys
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub id: String,
+    pub name: String,
+    pub created_at: i64,
+}
+
 /// User action types for session control
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -301,6 +823,70 @@ pub enum UserAction {
     Cancel,
 }
 
+/// A tool call waiting for user approval, persisted so the task can resume
+/// from storage when the decision arrives, including after a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingApproval {
+    pub tool_call_id: ToolCallId,
+    pub task_id: TaskId,
+    pub session_id: SessionId,
+    pub agent_id: Option<AgentId>,
+    pub tool_call: ToolCall,
+    /// Tool calls from the same assistant turn that have not run yet
+    pub remaining_calls: Vec<ToolCall>,
+    pub settings: TaskSettings,
+    pub workspace_root: String,
+    pub worktree_path: Option<String>,
+    /// Budget consumed before the task paused
+    #[serde(default)]
+    pub usage: BudgetUsage,
+    pub created_at: i64,
+}
+
+/// A summary that replaced older messages of a session in the model context.
+/// Messages before `first_kept_message_id` are represented by `summary`,
+/// except for pinned messages which are always kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextCompaction {
+    pub id: String,
+    pub session_id: SessionId,
+    pub summary: String,
+    pub first_kept_message_id: MessageId,
+    /// Number of messages folded into the summary by this compaction
+    pub compacted_count: usize,
+    /// Estimated context size before and after compaction
+    pub tokens_before: u32,
+    pub tokens_after: u32,
+    pub created_at: i64,
+}
+
+/// Snapshot of files taken before a file-modifying tool call ran
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    pub id: String,
+    pub session_id: SessionId,
+    pub task_id: TaskId,
+    pub tool_call_id: ToolCallId,
+    pub tool_name: String,
+    pub files: Vec<FileSnapshot>,
+    pub created_at: i64,
+}
+
+/// Original state of a single file in a checkpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSnapshot {
+    /// Absolute path of the file
+    pub path: String,
+    /// Sha256 of the original content; `None` when the file did not exist
+    pub hash: Option<String>,
+    /// Base64 encoded original content; `None` when the file did not exist
+    pub content: Option<String>,
+}
+
 /// Workspace information for a session
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -0,0 +1,314 @@
+//! Outbox Repository
+//! Messages waiting to go out through an integration, kept in
+//! chat_history.db so a restart does not drop them
+
+use crate::database::Database;
+use std::sync::Arc;
+
+/// A message waiting to be sent, or an edit waiting to be applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedMessage {
+    pub id: String,
+    pub integration_id: String,
+    pub recipient: String,
+    pub content: String,
+    /// Message to edit instead of sending a new one
+    pub edit_message_id: Option<String>,
+    pub attempts: u32,
+    /// Earliest time of the next attempt, in milliseconds
+    pub next_attempt_at: i64,
+    pub last_error: Option<String>,
+    /// Milliseconds; messages to a recipient go out in this order
+    pub created_at: i64,
+}
+
+/// Repository for queued outbound messages
+#[derive(Clone)]
+pub struct OutboxRepository {
+    db: Arc<Database>,
+}
+
+impl OutboxRepository {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Queue a message. An edit of a message that already has an edit queued
+    /// replaces that edit's content, so only the latest is applied. Returns
+    /// the ID the message is queued under.
+    pub async fn enqueue(&self, message: &QueuedMessage) -> Result<String, String> {
+        if let Some(edit_message_id) = &message.edit_message_id {
+            let result = self
+                .db
+                .query(
+                    "SELECT id FROM outbound_messages WHERE integration_id = ? AND recipient = ? AND edit_message_id = ?",
+                    vec![
+                        serde_json::json!(message.integration_id),
+                        serde_json::json!(message.recipient),
+                        serde_json::json!(edit_message_id),
+                    ],
+                )
+                .await?;
+            if let Some(id) = result.rows.first().map(|row| string_field(row, "id")) {
+                self.db
+                    .execute(
+                        "UPDATE outbound_messages SET content = ? WHERE id = ?",
+                        vec![serde_json::json!(message.content), serde_json::json!(id)],
+                    )
+                    .await?;
+                return Ok(id);
+            }
+        }
+
+        let sql = r#"
+            INSERT INTO outbound_messages (id, integration_id, recipient, content, edit_message_id, attempts, next_attempt_at, last_error, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(message.id),
+                    serde_json::json!(message.integration_id),
+                    serde_json::json!(message.recipient),
+                    serde_json::json!(message.content),
+                    serde_json::json!(message.edit_message_id),
+                    serde_json::json!(message.attempts),
+                    serde_json::json!(message.next_attempt_at),
+                    serde_json::json!(message.last_error),
+                    serde_json::json!(message.created_at),
+                ],
+            )
+            .await?;
+
+        Ok(message.id.clone())
+    }
+
+    /// Messages of an integration due by `now`, oldest first. A message waits
+    /// while an older one to the same recipient waits for its retry.
+    pub async fn due(
+        &self,
+        integration_id: &str,
+        now: i64,
+        limit: usize,
+    ) -> Result<Vec<QueuedMessage>, String> {
+        let sql = format!(
+            r#"
+            SELECT * FROM outbound_messages AS queued
+            WHERE integration_id = ? AND next_attempt_at <= ?
+              AND NOT EXISTS (
+                SELECT 1 FROM outbound_messages AS earlier
+                WHERE earlier.integration_id = queued.integration_id
+                  AND earlier.recipient = queued.recipient
+                  AND earlier.created_at < queued.created_at
+                  AND earlier.next_attempt_at > ?
+              )
+            ORDER BY created_at, rowid
+            LIMIT {}
+        "#,
+            limit
+        );
+
+        let result = self
+            .db
+            .query(
+                &sql,
+                vec![
+                    serde_json::json!(integration_id),
+                    serde_json::json!(now),
+                    serde_json::json!(now),
+                ],
+            )
+            .await?;
+
+        Ok(result.rows.iter().map(row_to_queued_message).collect())
+    }
+
+    /// Time the next message of an integration is due, if it has any queued
+    pub async fn next_attempt_at(&self, integration_id: &str) -> Result<Option<i64>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT MIN(next_attempt_at) AS next_attempt_at FROM outbound_messages WHERE integration_id = ?",
+                vec![serde_json::json!(integration_id)],
+            )
+            .await?;
+
+        Ok(result
+            .rows
+            .first()
+            .and_then(|row| row.get("next_attempt_at"))
+            .and_then(|v| v.as_i64()))
+    }
+
+    /// Record a failed attempt and when to make the next
+    pub async fn record_failure(&self, message: &QueuedMessage) -> Result<(), String> {
+        self.db
+            .execute(
+                "UPDATE outbound_messages SET attempts = ?, next_attempt_at = ?, last_error = ? WHERE id = ?",
+                vec![
+                    serde_json::json!(message.attempts),
+                    serde_json::json!(message.next_attempt_at),
+                    serde_json::json!(message.last_error),
+                    serde_json::json!(message.id),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Remove a message once sent. An edit whose content was replaced while
+    /// it was being sent stays queued with the new content.
+    pub async fn complete(&self, message: &QueuedMessage) -> Result<(), String> {
+        self.db
+            .execute(
+                "DELETE FROM outbound_messages WHERE id = ? AND content = ?",
+                vec![
+                    serde_json::json!(message.id),
+                    serde_json::json!(message.content),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Remove a message, sent or not
+    pub async fn delete(&self, id: &str) -> Result<(), String> {
+        self.db
+            .execute(
+                "DELETE FROM outbound_messages WHERE id = ?",
+                vec![serde_json::json!(id)],
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn string_field(row: &serde_json::Value, field: &str) -> String {
+    row.get(field)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+fn optional_string_field(row: &serde_json::Value, field: &str) -> Option<String> {
+    row.get(field).and_then(|v| v.as_str()).map(str::to_string)
+}
+
+fn row_to_queued_message(row: &serde_json::Value) -> QueuedMessage {
+    QueuedMessage {
+        id: string_field(row, "id"),
+        integration_id: string_field(row, "integration_id"),
+        recipient: string_field(row, "recipient"),
+        content: string_field(row, "content"),
+        edit_message_id: optional_string_field(row, "edit_message_id"),
+        attempts: row.get("attempts").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+        next_attempt_at: row
+            .get("next_attempt_at")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0),
+        last_error: optional_string_field(row, "last_error"),
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn create_test_db() -> (Arc<Database>, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect()
+            .await
+            .expect("Failed to connect to test database");
+
+        // Run migrations
+        let migrations = super::super::migrations::chat_history_migrations();
+        let runner = super::super::migrations::MigrationRunner::new(&db, &migrations);
+        runner.init().await.expect("Failed to init migrations");
+        runner.migrate().await.expect("Failed to run migrations");
+
+        (db, temp_dir)
+    }
+
+    fn message(id: &str, recipient: &str, edit_message_id: Option<&str>, at: i64) -> QueuedMessage {
+        QueuedMessage {
+            id: id.to_string(),
+            integration_id: "telegram".to_string(),
+            recipient: recipient.to_string(),
+            content: format!("content of {}", id),
+            edit_message_id: edit_message_id.map(str::to_string),
+            attempts: 0,
+            next_attempt_at: at,
+            last_error: None,
+            created_at: at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outbox() {
+        let (db, _temp_dir) = create_test_db().await;
+        let repo = OutboxRepository::new(db);
+
+        repo.enqueue(&message("m1", "chat-1", None, 100))
+            .await
+            .unwrap();
+        repo.enqueue(&message("m2", "chat-1", Some("sent-1"), 200))
+            .await
+            .unwrap();
+        repo.enqueue(&message("m3", "chat-2", None, 300))
+            .await
+            .unwrap();
+        // A second edit of the same message replaces the first
+        assert_eq!(
+            repo.enqueue(&message("m4", "chat-1", Some("sent-1"), 400))
+                .await
+                .unwrap(),
+            "m2"
+        );
+
+        let due = repo.due("telegram", 1000, 10).await.unwrap();
+        let ids: Vec<&str> = due.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["m1", "m2", "m3"]);
+        assert_eq!(due[1].content, "content of m4");
+        assert!(repo.due("feishu", 1000, 10).await.unwrap().is_empty());
+
+        // A message waiting for its retry holds back later ones to its recipient
+        let mut failed = due[0].clone();
+        failed.attempts = 1;
+        failed.next_attempt_at = 2000;
+        failed.last_error = Some("429 Too Many Requests".to_string());
+        repo.record_failure(&failed).await.unwrap();
+        let ids: Vec<String> = repo
+            .due("telegram", 1000, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, ["m3"]);
+        assert_eq!(repo.next_attempt_at("telegram").await.unwrap(), Some(200));
+
+        // An edit replaced while being sent stays queued with the new content
+        repo.enqueue(&message("m5", "chat-1", Some("sent-1"), 500))
+            .await
+            .unwrap();
+        repo.complete(&due[1]).await.unwrap();
+        repo.complete(&due[2]).await.unwrap();
+        let queued = repo.due("telegram", 3000, 10).await.unwrap();
+        let ids: Vec<&str> = queued.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["m1", "m2"]);
+        assert_eq!(queued[1].content, "content of m5");
+
+        repo.delete("m1").await.unwrap();
+        repo.complete(&queued[1]).await.unwrap();
+        assert_eq!(repo.next_attempt_at("telegram").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,205 @@
+//! Cursor Pagination
+//!
+//! List queries page with opaque cursors instead of offsets, so rows inserted
+//! while a client pages through a list neither shift nor repeat results. Rows
+//! are ordered by a sort column with the row ID breaking ties; a cursor holds
+//! both values of the last row of a page, and the next page starts after it.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+/// Rows per page when a request sets no limit
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Most rows a page holds
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// Direction of a sort
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+
+    /// Comparison selecting rows after a cursor
+    fn after(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        }
+    }
+}
+
+/// Position after the last row of a page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// Sort column the cursor was issued for
+    #[serde(rename = "s")]
+    pub sort: String,
+    #[serde(rename = "k")]
+    pub key: i64,
+    #[serde(rename = "i")]
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, String> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| format!("Invalid cursor: {}", cursor))
+    }
+}
+
+/// Which page of a list to return
+#[derive(Debug, Clone, Default)]
+pub struct PageRequest {
+    pub limit: Option<usize>,
+    /// `next_cursor` of the previous page; the first page when None
+    pub cursor: Option<String>,
+    pub order: SortOrder,
+}
+
+impl PageRequest {
+    /// Cursor of the request, checked to be issued for sorting by `column`
+    pub fn cursor_for(&self, column: &str) -> Result<Option<Cursor>, String> {
+        let Some(cursor) = &self.cursor else {
+            return Ok(None);
+        };
+        let cursor = Cursor::decode(cursor)?;
+        if cursor.sort != column {
+            return Err(format!("Cursor was issued for sorting by {}", cursor.sort));
+        }
+        Ok(Some(cursor))
+    }
+
+    fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+/// A page of a list
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page; None on the last page
+    pub next_cursor: Option<String>,
+    /// Rows matching the filters across all pages, as of this page
+    pub total: i64,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            total: self.total,
+        }
+    }
+}
+
+/// Restrict a query to the rows after the request's cursor and append its
+/// order and limit. One row more than the page holds is selected, telling
+/// `into_page` whether another page follows.
+pub(crate) fn push_page_clause(
+    sql: &mut String,
+    params: &mut Vec<serde_json::Value>,
+    column: &str,
+    request: &PageRequest,
+) -> Result<(), String> {
+    if let Some(cursor) = request.cursor_for(column)? {
+        let after = request.order.after();
+        sql.push_str(&format!(
+            " AND ({column} {after} ? OR ({column} = ? AND id {after} ?))"
+        ));
+        params.push(serde_json::json!(cursor.key));
+        params.push(serde_json::json!(cursor.key));
+        params.push(serde_json::json!(cursor.id));
+    }
+
+    let order = request.order.as_sql();
+    sql.push_str(&format!(
+        " ORDER BY {column} {order}, id {order} LIMIT {}",
+        request.limit() + 1
+    ));
+    Ok(())
+}
+
+/// Page of rows selected with `push_page_clause`
+pub(crate) fn into_page<T>(
+    mut rows: Vec<T>,
+    column: &str,
+    request: &PageRequest,
+    total: i64,
+    key: impl Fn(&T) -> (i64, String),
+) -> Page<T> {
+    let next_cursor = if rows.len() > request.limit() {
+        rows.truncate(request.limit());
+        rows.last().map(|row| {
+            let (key, id) = key(row);
+            Cursor {
+                sort: column.to_string(),
+                key,
+                id,
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    Page {
+        items: rows,
+        next_cursor,
+        total,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor {
+            sort: "updated_at".to_string(),
+            key: 1_700_000_000,
+            id: "sess_1".to_string(),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(Cursor::decode("not a cursor").is_err());
+
+        let request = PageRequest {
+            cursor: Some(cursor.encode()),
+            ..PageRequest::default()
+        };
+        let mut sql = String::new();
+        let mut params = vec![];
+        assert!(push_page_clause(&mut sql, &mut params, "created_at", &request).is_err());
+        push_page_clause(&mut sql, &mut params, "updated_at", &request).unwrap();
+        assert_eq!(
+            sql,
+            " AND (updated_at < ? OR (updated_at = ? AND id < ?)) ORDER BY updated_at DESC, id DESC LIMIT 51"
+        );
+        assert_eq!(params.len(), 3);
+    }
+}
@@ -0,0 +1,258 @@
+//! Projects Repository
+//! Handles CRUD operations for projects in chat_history.db
+
+use crate::database::Database;
+use crate::storage::models::{Project, TaskSettings};
+use std::sync::Arc;
+
+/// Repository for project operations
+#[derive(Clone)]
+pub struct ProjectsRepository {
+    db: Arc<Database>,
+}
+
+impl ProjectsRepository {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Create a new project
+    pub async fn create_project(&self, project: &Project) -> Result<(), String> {
+        let sql = r#"
+            INSERT INTO projects (id, name, workspace_path, default_settings, user_id, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(project.id),
+                    serde_json::json!(project.name),
+                    serde_json::json!(project.workspace_path),
+                    settings_json(project.default_settings.as_ref())?,
+                    serde_json::json!(project.user_id),
+                    serde_json::json!(project.created_at),
+                    serde_json::json!(project.updated_at),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get a project by ID
+    pub async fn get_project(&self, project_id: &str) -> Result<Option<Project>, String> {
+        let result = self
+            .db
+            .query(
+                "SELECT * FROM projects WHERE id = ?",
+                vec![serde_json::json!(project_id)],
+            )
+            .await?;
+
+        Ok(result.rows.first().map(row_to_project))
+    }
+
+    /// List projects, optionally of one user, by name
+    pub async fn list_projects(&self, user_id: Option<&str>) -> Result<Vec<Project>, String> {
+        let mut sql = "SELECT * FROM projects".to_string();
+        let mut params: Vec<serde_json::Value> = vec![];
+
+        if let Some(uid) = user_id {
+            sql.push_str(" WHERE user_id = ?");
+            params.push(serde_json::json!(uid));
+        }
+
+        sql.push_str(" ORDER BY name COLLATE NOCASE ASC, rowid ASC");
+
+        let result = self.db.query(&sql, params).await?;
+
+        Ok(result.rows.iter().map(row_to_project).collect())
+    }
+
+    /// Update a project. Returns `false` when it does not exist.
+    pub async fn update_project(
+        &self,
+        project_id: &str,
+        updates: ProjectUpdates,
+    ) -> Result<bool, String> {
+        let mut fields = Vec::new();
+        let mut params: Vec<serde_json::Value> = vec![];
+
+        if let Some(name) = updates.name {
+            fields.push("name = ?");
+            params.push(serde_json::json!(name));
+        }
+
+        if let Some(workspace_path) = updates.workspace_path {
+            fields.push("workspace_path = ?");
+            params.push(serde_json::json!(workspace_path));
+        }
+
+        if let Some(default_settings) = updates.default_settings {
+            fields.push("default_settings = ?");
+            params.push(settings_json(default_settings.as_ref())?);
+        }
+
+        fields.push("updated_at = ?");
+        params.push(serde_json::json!(chrono::Utc::now().timestamp()));
+        params.push(serde_json::json!(project_id));
+
+        let sql = format!("UPDATE projects SET {} WHERE id = ?", fields.join(", "));
+        let result = self.db.execute(&sql, params).await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Delete a project. Its sessions are kept. Returns `false` when it does
+    /// not exist.
+    pub async fn delete_project(&self, project_id: &str) -> Result<bool, String> {
+        let result = self
+            .db
+            .execute(
+                "DELETE FROM projects WHERE id = ?",
+                vec![serde_json::json!(project_id)],
+            )
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+}
+
+/// Updates for a project (all fields optional)
+#[derive(Debug, Default)]
+pub struct ProjectUpdates {
+    pub name: Option<String>,
+    pub workspace_path: Option<String>,
+    /// `Some(None)` clears the default settings
+    pub default_settings: Option<Option<TaskSettings>>,
+}
+
+// ============== Row Conversions ==============
+
+fn settings_json(settings: Option<&TaskSettings>) -> Result<serde_json::Value, String> {
+    settings
+        .map(|settings| {
+            serde_json::to_string(settings)
+                .map_err(|e| format!("Failed to serialize project settings: {}", e))
+        })
+        .transpose()
+        .map(|json| serde_json::json!(json))
+}
+
+fn row_to_project(row: &serde_json::Value) -> Project {
+    Project {
+        id: row
+            .get("id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        name: row
+            .get("name")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        workspace_path: row
+            .get("workspace_path")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        default_settings: row
+            .get("default_settings")
+            .and_then(|v| v.as_str())
+            .and_then(|s| serde_json::from_str(s).ok()),
+        user_id: row
+            .get("user_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        created_at: row.get("created_at").and_then(|v| v.as_i64()).unwrap_or(0),
+        updated_at: row.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn create_test_db() -> (Arc<Database>, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect()
+            .await
+            .expect("Failed to connect to test database");
+
+        // Run migrations
+        let migrations = super::super::migrations::chat_history_migrations();
+        let runner = super::super::migrations::MigrationRunner::new(&db, &migrations);
+        runner.init().await.expect("Failed to init migrations");
+        runner.migrate().await.expect("Failed to run migrations");
+
+        (db, temp_dir)
+    }
+
+    fn project(id: &str, name: &str, user_id: Option<&str>) -> Project {
+        Project {
+            id: id.to_string(),
+            name: name.to_string(),
+            workspace_path: "/tmp".to_string(),
+            default_settings: None,
+            user_id: user_id.map(str::to_string),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_project_crud() {
+        let (db, _temp) = create_test_db().await;
+        let repo = ProjectsRepository::new(db);
+
+        repo.create_project(&project("proj-b", "backend", None))
+            .await
+            .unwrap();
+        repo.create_project(&project("proj-a", "App", Some("user-1")))
+            .await
+            .unwrap();
+
+        let names = |projects: Vec<Project>| -> Vec<String> {
+            projects.into_iter().map(|p| p.name).collect()
+        };
+        assert_eq!(
+            names(repo.list_projects(None).await.unwrap()),
+            vec!["App", "backend"]
+        );
+        assert_eq!(
+            names(repo.list_projects(Some("user-1")).await.unwrap()),
+            vec!["App"]
+        );
+
+        let settings = TaskSettings {
+            agent: Some("reviewer".to_string()),
+            ..TaskSettings::default()
+        };
+        assert!(repo
+            .update_project(
+                "proj-b",
+                ProjectUpdates {
+                    name: Some("Backend".to_string()),
+                    default_settings: Some(Some(settings)),
+                    ..ProjectUpdates::default()
+                },
+            )
+            .await
+            .unwrap());
+        let updated = repo.get_project("proj-b").await.unwrap().unwrap();
+        assert_eq!(updated.name, "Backend");
+        assert_eq!(
+            updated.default_settings.and_then(|s| s.agent).as_deref(),
+            Some("reviewer")
+        );
+
+        assert!(repo.delete_project("proj-b").await.unwrap());
+        assert!(!repo.delete_project("proj-b").await.unwrap());
+        assert!(repo.get_project("proj-b").await.unwrap().is_none());
+    }
+}
@@ -194,6 +194,24 @@ impl SettingsRepository {
         if updates.auto_code_review.is_some() {
             settings.auto_code_review = updates.auto_code_review;
         }
+        if updates.plan_mode.is_some() {
+            settings.plan_mode = updates.plan_mode;
+        }
+        if updates.read_only_tools.is_some() {
+            settings.read_only_tools = updates.read_only_tools;
+        }
+        if updates.tool_capabilities.is_some() {
+            settings.tool_capabilities = updates.tool_capabilities;
+        }
+        if updates.agent.is_some() {
+            settings.agent = updates.agent;
+        }
+        if updates.models.is_some() {
+            settings.models = updates.models;
+        }
+        if updates.budget.is_some() {
+            settings.budget = updates.budget;
+        }
 
         // Merge extra settings
         for (key, value) in updates.extra {
@@ -338,6 +356,12 @@ mod tests {
             auto_approve_edits: Some(true),
             auto_approve_plan: Some(false),
             auto_code_review: Some(true),
+            plan_mode: None,
+            read_only_tools: None,
+            tool_capabilities: None,
+            agent: None,
+            models: None,
+            budget: None,
             extra: Default::default(),
         };
 
@@ -366,6 +390,12 @@ mod tests {
             auto_approve_edits: Some(true),
             auto_approve_plan: Some(false),
             auto_code_review: None,
+            plan_mode: None,
+            read_only_tools: None,
+            tool_capabilities: None,
+            agent: None,
+            models: None,
+            budget: None,
             extra: Default::default(),
         };
         repo.set_task_settings("task-2", &initial).await.unwrap();
@@ -375,6 +405,12 @@ mod tests {
             auto_approve_edits: None,      // Keep existing
             auto_approve_plan: Some(true), // Update
             auto_code_review: Some(false), // Set new
+            plan_mode: None,
+            read_only_tools: None,
+            tool_capabilities: None,
+            agent: None,
+            models: None,
+            budget: None,
             extra: Default::default(),
         };
 
@@ -0,0 +1,165 @@
+//! Threads Repository
+//! Which session each IM message belongs to, so a reply to a message
+//! continues its session across restarts
+
+use crate::database::Database;
+use std::sync::Arc;
+
+/// Repository mapping integration messages to sessions
+#[derive(Clone)]
+pub struct ThreadsRepository {
+    db: Arc<Database>,
+}
+
+impl ThreadsRepository {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Record that a message in a chat belongs to a session
+    pub async fn link(
+        &self,
+        integration_id: &str,
+        chat_id: &str,
+        message_id: &str,
+        session_id: &str,
+    ) -> Result<(), String> {
+        let sql = r#"
+            INSERT INTO integration_threads (integration_id, chat_id, message_id, session_id, created_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(integration_id, chat_id, message_id) DO UPDATE SET session_id = excluded.session_id
+        "#;
+
+        self.db
+            .execute(
+                sql,
+                vec![
+                    serde_json::json!(integration_id),
+                    serde_json::json!(chat_id),
+                    serde_json::json!(message_id),
+                    serde_json::json!(session_id),
+                    serde_json::json!(chrono::Utc::now().timestamp()),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Session a message in a chat belongs to, if the session still exists
+    pub async fn session_for(
+        &self,
+        integration_id: &str,
+        chat_id: &str,
+        message_id: &str,
+    ) -> Result<Option<String>, String> {
+        let sql = r#"
+            SELECT t.session_id FROM integration_threads t
+            JOIN sessions s ON s.id = t.session_id
+            WHERE t.integration_id = ? AND t.chat_id = ? AND t.message_id = ?
+        "#;
+
+        let result = self
+            .db
+            .query(
+                sql,
+                vec![
+                    serde_json::json!(integration_id),
+                    serde_json::json!(chat_id),
+                    serde_json::json!(message_id),
+                ],
+            )
+            .await?;
+
+        Ok(result
+            .rows
+            .first()
+            .and_then(|row| row.get("session_id"))
+            .and_then(|v| v.as_str())
+            .map(str::to_string))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{ChatHistoryRepository, Session, SessionStatus};
+    use tempfile::TempDir;
+
+    async fn create_test_db() -> (Arc<Database>, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect()
+            .await
+            .expect("Failed to connect to test database");
+
+        // Run migrations
+        let migrations = super::super::migrations::chat_history_migrations();
+        let runner = super::super::migrations::MigrationRunner::new(&db, &migrations);
+        runner.init().await.expect("Failed to init migrations");
+        runner.migrate().await.expect("Failed to run migrations");
+
+        (db, temp_dir)
+    }
+
+    fn session(id: &str) -> Session {
+        Session {
+            id: id.to_string(),
+            project_id: None,
+            title: None,
+            summary: None,
+            status: SessionStatus::Created,
+            created_at: 0,
+            updated_at: 0,
+            last_event_id: None,
+            metadata: None,
+            starred: false,
+            archived_at: None,
+            user_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_threads() {
+        let (db, _temp_dir) = create_test_db().await;
+        let chat_history = ChatHistoryRepository::new(db.clone());
+        let repo = ThreadsRepository::new(db);
+        chat_history
+            .create_session(&session("session-1"))
+            .await
+            .unwrap();
+        chat_history
+            .create_session(&session("session-2"))
+            .await
+            .unwrap();
+
+        repo.link("telegram", "chat-1", "7", "session-1")
+            .await
+            .unwrap();
+        assert_eq!(
+            repo.session_for("telegram", "chat-1", "7").await.unwrap(),
+            Some("session-1".to_string())
+        );
+        // Message IDs are only unique within a chat
+        assert_eq!(
+            repo.session_for("telegram", "chat-2", "7").await.unwrap(),
+            None
+        );
+
+        repo.link("telegram", "chat-1", "7", "session-2")
+            .await
+            .unwrap();
+        assert_eq!(
+            repo.session_for("telegram", "chat-1", "7").await.unwrap(),
+            Some("session-2".to_string())
+        );
+
+        // A deleted session is not continued
+        chat_history.delete_session("session-2").await.unwrap();
+        assert_eq!(
+            repo.session_for("telegram", "chat-1", "7").await.unwrap(),
+            None
+        );
+    }
+}
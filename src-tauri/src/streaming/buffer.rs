@@ -1,14 +1,21 @@
 //! Event Buffer
 //!
 //! Buffers events for SSE streaming with resume capability.
-//! Persists events to storage and maintains in-memory cache.
+//! Every event gets a numeric ID that increases across sessions, so a client
+//! can resume after the last ID it saw. Recent events are kept in memory;
+//! events pushed out of the in-memory cache are persisted to storage.
 
 use crate::storage::models::{EventId, SessionEvent, SessionId};
 use crate::storage::Storage;
 use crate::streaming::events::StreamingEvent;
+use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
+
+/// Live events a subscriber may fall behind by before it is dropped
+const LIVE_CHANNEL_CAPACITY: usize = 1024;
 
 /// Event buffer for managing streaming events
 pub struct EventBuffer {
@@ -18,14 +25,22 @@ pub struct EventBuffer {
     max_memory_events: usize,
     /// Storage for persistence
     storage: Option<Arc<Storage>>,
+    /// ID of the next event
+    next_id: AtomicU64,
+    /// Events as they are added, for live streams
+    live: broadcast::Sender<StreamingEvent>,
 }
 
 impl EventBuffer {
     pub fn new(max_memory_events: usize) -> Self {
+        // Starting from the clock keeps IDs increasing across restarts
+        let first_id = chrono::Utc::now().timestamp_micros().max(1) as u64;
         Self {
             cache: RwLock::new(HashMap::new()),
             max_memory_events,
             storage: None,
+            next_id: AtomicU64::new(first_id),
+            live: broadcast::channel(LIVE_CHANNEL_CAPACITY).0,
         }
     }
 
@@ -34,85 +49,99 @@ impl EventBuffer {
         self
     }
 
-    /// Add an event to the buffer
-    pub async fn add_event(&self, event: StreamingEvent) -> Result<(), String> {
-        let session_event: SessionEvent = event.into();
-
-        // Add to in-memory cache
-        {
-            let mut cache = self.cache.write().await;
-            let events = cache
-                .entry(session_event.session_id.clone())
-                .or_insert_with(Vec::new);
-
-            events.push(session_event.clone());
-
-            // Trim to max size
-            if events.len() > self.max_memory_events {
-                *events = events.split_off(events.len() - self.max_memory_events);
+    /// Add an event to the buffer, assigning its ID. Returns the event with
+    /// its ID as it was sent to live subscribers.
+    pub async fn add_event(&self, event: StreamingEvent) -> Result<StreamingEvent, String> {
+        let mut cache = self.cache.write().await;
+        let event = event.with_event_id(self.next_id.fetch_add(1, Ordering::SeqCst).to_string());
+        let session_event: SessionEvent = event.clone().into();
+        let events = cache
+            .entry(session_event.session_id.clone())
+            .or_insert_with(Vec::new);
+        events.push(session_event);
+
+        // Trim to max size, moving the oldest events to storage. This happens
+        // under the lock so a replay never misses an event in between.
+        if events.len() > self.max_memory_events {
+            let overflow: Vec<SessionEvent> = events
+                .drain(..events.len() - self.max_memory_events)
+                .collect();
+            if let Some(storage) = &self.storage {
+                for session_event in &overflow {
+                    if let Err(e) = storage.chat_history.create_event(session_event).await {
+                        log::warn!("Failed to persist event {}: {}", session_event.id, e);
+                    }
+                }
             }
         }
 
-        // Persist to storage if available
-        if let Some(storage) = &self.storage {
-            storage.chat_history.create_event(&session_event).await?;
-        }
+        // Sent under the lock so subscribers receive events in ID order
+        let _ = self.live.send(event.clone());
+        Ok(event)
+    }
 
-        Ok(())
+    /// Receive events of all sessions as they are added
+    pub fn subscribe(&self) -> broadcast::Receiver<StreamingEvent> {
+        self.live.subscribe()
     }
 
-    /// Get events for a session, optionally after a specific event ID
+    /// Get events for a session, optionally after a specific event ID. Events
+    /// after an ID older than the in-memory cache are read from storage first.
     pub async fn get_events(
         &self,
         session_id: &str,
         after_event_id: Option<&str>,
         limit: Option<usize>,
     ) -> Result<Vec<StreamingEvent>, String> {
-        // First check in-memory cache
+        let after = after_event_id
+            .map(|id| {
+                id.parse::<u64>()
+                    .map_err(|_| format!("Invalid event ID '{}'", id))
+            })
+            .transpose()?;
+
+        // Held while reading storage so no event moves there in between
         let cache = self.cache.read().await;
+        let cached = cache.get(session_id).map(Vec::as_slice).unwrap_or_default();
+        let oldest_cached = cached.first().and_then(|e| e.id.parse::<u64>().ok());
+
+        let mut result = Vec::new();
+        let older_events_needed = match (oldest_cached, after) {
+            (None, _) => true,
+            (Some(oldest), Some(after)) => after < oldest,
+            (Some(_), None) => false,
+        };
+        if older_events_needed {
+            if let Some(storage) = &self.storage {
+                for event in storage
+                    .chat_history
+                    .get_events(session_id, after_event_id, None)
+                    .await?
+                {
+                    result.push(event.try_into()?);
+                }
+            }
+        }
 
-        if let Some(events) = cache.get(session_id) {
-            let mut result: Vec<StreamingEvent> = events
+        result.extend(
+            cached
                 .iter()
-                .filter(|e| {
-                    if let Some(after_id) = after_event_id {
-                        e.id > after_id.to_string()
-                    } else {
-                        true
-                    }
+                .filter(|e| match after {
+                    Some(after) => e.id.parse::<u64>().is_ok_and(|id| id > after),
+                    None => true,
                 })
                 .cloned()
-                .filter_map(|e| e.try_into().ok())
-                .collect();
-
-            // Apply limit
-            if let Some(lim) = limit {
-                if result.len() > lim {
-                    result = result.split_off(result.len() - lim);
-                }
-            }
+                .filter_map(|e| e.try_into().ok()),
+        );
 
-            // If we have enough events from cache, return them
-            if !result.is_empty() {
-                return Ok(result);
+        // Apply limit
+        if let Some(lim) = limit {
+            if result.len() > lim {
+                result = result.split_off(result.len() - lim);
             }
         }
-        drop(cache);
-
-        // Fall back to storage
-        if let Some(storage) = &self.storage {
-            let events = storage
-                .chat_history
-                .get_events(session_id, after_event_id, limit)
-                .await?;
-
-            return events
-                .into_iter()
-                .map(|e| e.try_into())
-                .collect::<Result<Vec<_>, _>>();
-        }
 
-        Ok(vec![])
+        Ok(result)
     }
 
     /// Get the last event ID for a session
@@ -132,6 +161,24 @@ impl EventBuffer {
         cache.remove(session_id);
     }
 
+    /// Move every event held in memory to storage, such as before a
+    /// shutdown. Returns the number of events moved; without storage the
+    /// events stay in memory.
+    pub async fn persist_all(&self) -> Result<usize, String> {
+        let Some(storage) = &self.storage else {
+            return Ok(0);
+        };
+        let mut cache = self.cache.write().await;
+        let mut persisted = 0;
+        for (_, events) in cache.drain() {
+            for session_event in &events {
+                storage.chat_history.create_event(session_event).await?;
+                persisted += 1;
+            }
+        }
+        Ok(persisted)
+    }
+
     /// Get memory usage stats
     pub async fn get_stats(&self) -> BufferStats {
         let cache = self.cache.read().await;
@@ -147,7 +194,8 @@ impl EventBuffer {
 }
 
 /// Buffer statistics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct BufferStats {
     pub total_sessions: usize,
     pub total_events: usize,
@@ -157,49 +205,63 @@ pub struct BufferStats {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::streaming::events::{StatusEventData, TokenEventData};
+    use crate::storage::models::{Session, SessionStatus};
+    use crate::streaming::events::TokenEventData;
+    use tempfile::TempDir;
+
+    fn token(session_id: &str, i: usize) -> StreamingEvent {
+        StreamingEvent::Token {
+            event_id: String::new(),
+            session_id: session_id.to_string(),
+            data: TokenEventData {
+                token: format!("token{}", i),
+            },
+        }
+    }
+
+    fn ids(events: &[StreamingEvent]) -> Vec<u64> {
+        events
+            .iter()
+            .map(|e| e.event_id().parse().unwrap())
+            .collect()
+    }
 
     #[tokio::test]
     async fn test_event_buffer() {
         let buffer = EventBuffer::new(100);
+        let mut live = buffer.subscribe();
 
-        let event = StreamingEvent::Token {
-            event_id: "evt-1".to_string(),
-            session_id: "sess-1".to_string(),
-            data: TokenEventData {
-                token: "Hello".to_string(),
-            },
-        };
-
-        buffer
-            .add_event(event.clone())
+        let first = buffer
+            .add_event(token("sess-1", 0))
             .await
             .expect("Failed to add event");
+        let second = buffer.add_event(token("sess-2", 1)).await.unwrap();
+        assert!(ids(&[first.clone(), second.clone()]).is_sorted_by(|a, b| a < b));
 
         let events = buffer.get_events("sess-1", None, None).await.unwrap();
         assert_eq!(events.len(), 1);
+        assert_eq!(live.recv().await.unwrap().event_id(), first.event_id());
+        assert_eq!(live.recv().await.unwrap().event_id(), second.event_id());
     }
 
     #[tokio::test]
     async fn test_get_events_after_id() {
         let buffer = EventBuffer::new(100);
 
+        let mut added = Vec::new();
         for i in 0..5 {
-            let event = StreamingEvent::Token {
-                event_id: format!("evt-{}", i),
-                session_id: "sess-1".to_string(),
-                data: TokenEventData {
-                    token: format!("token{}", i),
-                },
-            };
-            buffer.add_event(event).await.unwrap();
+            added.push(buffer.add_event(token("sess-1", i)).await.unwrap());
         }
 
         let events = buffer
-            .get_events("sess-1", Some("evt-2"), None)
+            .get_events("sess-1", Some(added[2].event_id()), None)
             .await
             .unwrap();
-        assert_eq!(events.len(), 2); // evt-3 and evt-4
+        assert_eq!(ids(&events), ids(&added[3..])); // events 3 and 4
+        assert!(buffer
+            .get_events("sess-1", Some("evt-2"), None)
+            .await
+            .is_err());
     }
 
     #[tokio::test]
@@ -207,17 +269,67 @@ mod tests {
         let buffer = EventBuffer::new(3);
 
         for i in 0..5 {
-            let event = StreamingEvent::Token {
-                event_id: format!("evt-{}", i),
-                session_id: "sess-1".to_string(),
-                data: TokenEventData {
-                    token: format!("token{}", i),
-                },
-            };
-            buffer.add_event(event).await.unwrap();
+            buffer.add_event(token("sess-1", i)).await.unwrap();
         }
 
         let stats = buffer.get_stats().await;
         assert_eq!(stats.total_events, 3); // Trimmed to max
     }
+
+    #[tokio::test]
+    async fn test_replay_reads_overflow_from_storage() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::new(
+            temp_dir.path().to_path_buf(),
+            temp_dir.path().join("attachments"),
+        )
+        .await
+        .unwrap();
+        let now = chrono::Utc::now().timestamp();
+        storage
+            .chat_history
+            .create_session(&Session {
+                id: "sess-1".to_string(),
+                project_id: None,
+                title: None,
+                summary: None,
+                status: SessionStatus::Created,
+                created_at: now,
+                updated_at: now,
+                last_event_id: None,
+                metadata: None,
+                starred: false,
+                archived_at: None,
+                user_id: None,
+            })
+            .await
+            .unwrap();
+        let buffer = EventBuffer::new(2).with_storage(Arc::new(storage));
+
+        let mut added = Vec::new();
+        for i in 0..5 {
+            added.push(buffer.add_event(token("sess-1", i)).await.unwrap());
+        }
+
+        // Events 1 and 2 were moved to storage, 3 and 4 are in memory
+        let events = buffer
+            .get_events("sess-1", Some(added[0].event_id()), None)
+            .await
+            .unwrap();
+        assert_eq!(ids(&events), ids(&added[1..]));
+        let events = buffer
+            .get_events("sess-1", Some(added[3].event_id()), None)
+            .await
+            .unwrap();
+        assert_eq!(ids(&events), ids(&added[4..]));
+
+        // Persisting empties memory; replays then read everything from storage
+        assert_eq!(buffer.persist_all().await.unwrap(), 2);
+        assert_eq!(buffer.get_stats().await.total_events, 0);
+        let events = buffer
+            .get_events("sess-1", Some(added[0].event_id()), None)
+            .await
+            .unwrap();
+        assert_eq!(ids(&events), ids(&added[1..]));
+    }
 }
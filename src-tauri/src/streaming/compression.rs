@@ -0,0 +1,274 @@
+//! Stream Compression
+//!
+//! Shrinks SSE and WebSocket payloads for clients on slow links:
+//! - gzip or deflate compression, negotiated per connection
+//! - Delta encoding of large payloads against the previous payload of the
+//!   same event type, for clients that opt in
+
+use crate::storage::models::EventId;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Payloads smaller than this are always sent whole
+pub const DELTA_MIN_SIZE: usize = 4 * 1024;
+
+/// Content encoding of a compressed stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamEncoding {
+    Gzip,
+    Deflate,
+}
+
+impl StreamEncoding {
+    /// Pick an encoding from an Accept-Encoding header, preferring gzip
+    pub fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let accepted: Vec<&str> = accept_encoding
+            .split(',')
+            .filter_map(|part| {
+                let mut params = part.split(';').map(str::trim);
+                let name = params.next()?;
+                let refused = params.any(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .is_some_and(|q| q <= 0.0)
+                });
+                (!refused).then_some(name)
+            })
+            .collect();
+
+        [Self::Gzip, Self::Deflate].into_iter().find(|encoding| {
+            accepted
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(encoding.as_str()))
+        })
+    }
+
+    /// Parse an encoding name such as `gzip`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "gzip" => Some(Self::Gzip),
+            "deflate" => Some(Self::Deflate),
+            _ => None,
+        }
+    }
+
+    /// Name of the encoding in Content-Encoding headers
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+}
+
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+/// Compressor for a long-lived response body. Every chunk is flushed so the
+/// client can decode it as soon as it arrives.
+pub struct StreamCompressor {
+    encoder: Encoder,
+}
+
+impl StreamCompressor {
+    pub fn new(encoding: StreamEncoding) -> Self {
+        let encoder = match encoding {
+            StreamEncoding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::fast())),
+            StreamEncoding::Deflate => {
+                Encoder::Deflate(ZlibEncoder::new(Vec::new(), Compression::fast()))
+            }
+        };
+        Self { encoder }
+    }
+
+    /// Compress the next chunk of the stream
+    pub fn compress(&mut self, chunk: &[u8]) -> Result<Vec<u8>, String> {
+        let output = match &mut self.encoder {
+            Encoder::Gzip(encoder) => {
+                write_flushed(encoder, chunk)?;
+                encoder.get_mut()
+            }
+            Encoder::Deflate(encoder) => {
+                write_flushed(encoder, chunk)?;
+                encoder.get_mut()
+            }
+        };
+        Ok(std::mem::take(output))
+    }
+}
+
+fn write_flushed(encoder: &mut impl Write, chunk: &[u8]) -> Result<(), String> {
+    encoder
+        .write_all(chunk)
+        .and_then(|_| encoder.flush())
+        .map_err(|e| format!("Failed to compress stream: {}", e))
+}
+
+/// Compress a whole message, such as a WebSocket frame
+pub fn compress_message(encoding: StreamEncoding, message: &[u8]) -> Result<Vec<u8>, String> {
+    let compressed = match encoding {
+        StreamEncoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(message).and_then(|_| encoder.finish())
+        }
+        StreamEncoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(message).and_then(|_| encoder.finish())
+        }
+    };
+    compressed.map_err(|e| format!("Failed to compress message: {}", e))
+}
+
+/// A payload expressed as an edit of the previous payload of its event type.
+/// Offsets count UTF-16 code units, as JavaScript strings do, so a client
+/// rebuilds it as `base.slice(0, prefix) + insert + base.slice(base.length - suffix)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayloadDelta {
+    /// Event whose payload the delta applies to
+    pub base: EventId,
+    /// Length of the unchanged start of the base
+    pub prefix: usize,
+    /// Length of the unchanged end of the base
+    pub suffix: usize,
+    /// Text replacing the rest of the base
+    pub insert: String,
+}
+
+/// Delta encoder for one stream
+#[derive(Default)]
+pub struct DeltaEncoder {
+    /// Last payload sent per event type
+    previous: HashMap<String, (EventId, String)>,
+}
+
+impl DeltaEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encode a payload as a delta of the previous one of its type, or return
+    /// None when it should be sent whole. The client must keep the last
+    /// payload of every event type to apply deltas.
+    pub fn encode(
+        &mut self,
+        event_type: &str,
+        event_id: &EventId,
+        payload: &str,
+    ) -> Option<PayloadDelta> {
+        let previous = self.previous.insert(
+            event_type.to_string(),
+            (event_id.clone(), payload.to_string()),
+        );
+        let (base_id, base) = previous?;
+        if payload.len() < DELTA_MIN_SIZE {
+            return None;
+        }
+
+        let prefix_bytes = common_prefix(&base, payload);
+        let suffix_bytes = common_suffix(&base[prefix_bytes..], &payload[prefix_bytes..]);
+        let insert = &payload[prefix_bytes..payload.len() - suffix_bytes];
+        // Deltas that save little are not worth the client's bookkeeping
+        if insert.len() > payload.len() / 2 {
+            return None;
+        }
+
+        Some(PayloadDelta {
+            base: base_id,
+            prefix: utf16_len(&base[..prefix_bytes]),
+            suffix: utf16_len(&base[base.len() - suffix_bytes..]),
+            insert: insert.to_string(),
+        })
+    }
+}
+
+/// Byte length of the common start of two strings, on a char boundary
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((index, _), _)| index)
+}
+
+/// Byte length of the common end of two strings, on a char boundary
+fn common_suffix(a: &str, b: &str) -> usize {
+    a.chars()
+        .rev()
+        .zip(b.chars().rev())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum()
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.chars().map(char::len_utf16).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_negotiate_encoding() {
+        assert_eq!(
+            StreamEncoding::negotiate("deflate, gzip;q=0.8"),
+            Some(StreamEncoding::Gzip)
+        );
+        assert_eq!(
+            StreamEncoding::negotiate("gzip;q=0, deflate"),
+            Some(StreamEncoding::Deflate)
+        );
+        assert_eq!(StreamEncoding::negotiate("br, identity"), None);
+        assert_eq!(StreamEncoding::parse("GZIP"), Some(StreamEncoding::Gzip));
+    }
+
+    #[test]
+    fn test_stream_compressor_flushes_each_chunk() {
+        let mut compressor = StreamCompressor::new(StreamEncoding::Gzip);
+        let mut body = compressor.compress(b"data: one\n\n").unwrap();
+        assert!(!body.is_empty());
+        body.extend(compressor.compress(b"data: two\n\n").unwrap());
+
+        // A flushed stream decodes up to its last chunk without a trailer
+        let mut decoded = Vec::new();
+        let _ = GzDecoder::new(body.as_slice()).read_to_end(&mut decoded);
+        assert_eq!(decoded, b"data: one\n\ndata: two\n\n");
+    }
+
+    #[test]
+    fn test_delta_encoding() {
+        let mut encoder = DeltaEncoder::new();
+        let base = format!("{{\"output\":\"é-old{}\"}}", "x".repeat(DELTA_MIN_SIZE));
+        let next = format!("{{\"output\":\"é-new{}\"}}", "x".repeat(DELTA_MIN_SIZE));
+
+        assert_eq!(encoder.encode("tool.result", &"1".to_string(), &base), None);
+        assert_eq!(encoder.encode("token", &"2".to_string(), "hi"), None);
+        let delta = encoder
+            .encode("tool.result", &"3".to_string(), &next)
+            .unwrap();
+        assert_eq!(delta.base, "1");
+        assert_eq!(delta.insert, "new");
+
+        // Rebuild the payload the way a JavaScript client would
+        let base: Vec<u16> = base.encode_utf16().collect();
+        let mut rebuilt = base[..delta.prefix].to_vec();
+        rebuilt.extend(delta.insert.encode_utf16());
+        rebuilt.extend(&base[base.len() - delta.suffix..]);
+        assert_eq!(String::from_utf16(&rebuilt).unwrap(), next);
+
+        // Unrelated payloads are sent whole
+        let other = "y".repeat(DELTA_MIN_SIZE);
+        assert_eq!(
+            encoder.encode("tool.result", &"4".to_string(), &other),
+            None
+        );
+    }
+}
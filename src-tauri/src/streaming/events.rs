@@ -2,8 +2,12 @@
 //!
 //! Defines event types for SSE streaming and conversion between internal and external formats.
 
-use crate::storage::models::{EventId, EventType, SessionEvent, SessionId};
+use crate::core::types::RuntimeEvent;
+use crate::storage::models::{
+    EventId, EventType, MessageContent, MessageRole, SessionEvent, SessionId,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 /// Event envelope for streaming
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,6 +58,15 @@ pub enum StreamingEvent {
         session_id: SessionId,
         data: ToolResultEventData,
     },
+    /// Token usage of the model responses of a message
+    #[serde(rename = "usage")]
+    Usage {
+        #[serde(rename = "eventId")]
+        event_id: EventId,
+        #[serde(rename = "sessionId")]
+        session_id: SessionId,
+        data: UsageEventData,
+    },
     /// Error occurred
     #[serde(rename = "error")]
     Error {
@@ -102,13 +115,71 @@ pub struct ToolResultEventData {
     pub output: serde_json::Value,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageEventData {
+    pub input_tokens: i32,
+    pub output_tokens: i32,
+    pub cached_input_tokens: Option<i32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ErrorEventData {
     pub message: String,
 }
 
+/// Categories of events a stream subscriber may limit itself to. Final
+/// messages and errors belong to none and are always delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EventCategory {
+    Tokens,
+    Tools,
+    Status,
+    Usage,
+}
+
+impl EventCategory {
+    /// Parse a comma-separated list of categories, such as `tools,status`
+    pub fn parse_list(list: &str) -> Result<HashSet<EventCategory>, String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| name.parse())
+            .collect()
+    }
+}
+
+impl std::str::FromStr for EventCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tokens" => Ok(EventCategory::Tokens),
+            "tools" => Ok(EventCategory::Tools),
+            "status" => Ok(EventCategory::Status),
+            "usage" => Ok(EventCategory::Usage),
+            _ => Err(format!("Unknown event category: {}", s)),
+        }
+    }
+}
+
 impl StreamingEvent {
+    /// The event with its ID replaced
+    pub fn with_event_id(mut self, id: EventId) -> Self {
+        match &mut self {
+            StreamingEvent::Status { event_id, .. }
+            | StreamingEvent::Token { event_id, .. }
+            | StreamingEvent::MessageFinal { event_id, .. }
+            | StreamingEvent::ToolCall { event_id, .. }
+            | StreamingEvent::ToolResult { event_id, .. }
+            | StreamingEvent::Usage { event_id, .. }
+            | StreamingEvent::Error { event_id, .. } => *event_id = id,
+        }
+        self
+    }
+
     /// Get the event ID
     pub fn event_id(&self) -> &EventId {
         match self {
@@ -117,6 +188,7 @@ impl StreamingEvent {
             StreamingEvent::MessageFinal { event_id, .. } => event_id,
             StreamingEvent::ToolCall { event_id, .. } => event_id,
             StreamingEvent::ToolResult { event_id, .. } => event_id,
+            StreamingEvent::Usage { event_id, .. } => event_id,
             StreamingEvent::Error { event_id, .. } => event_id,
         }
     }
@@ -129,6 +201,7 @@ impl StreamingEvent {
             StreamingEvent::MessageFinal { session_id, .. } => Some(session_id),
             StreamingEvent::ToolCall { session_id, .. } => Some(session_id),
             StreamingEvent::ToolResult { session_id, .. } => Some(session_id),
+            StreamingEvent::Usage { session_id, .. } => Some(session_id),
             StreamingEvent::Error { session_id, .. } => session_id.as_ref(),
         }
     }
@@ -141,21 +214,40 @@ impl StreamingEvent {
             StreamingEvent::MessageFinal { .. } => EventType::MessageFinal,
             StreamingEvent::ToolCall { .. } => EventType::ToolCall,
             StreamingEvent::ToolResult { .. } => EventType::ToolResult,
+            StreamingEvent::Usage { .. } => EventType::Usage,
             StreamingEvent::Error { .. } => EventType::Error,
         }
     }
 
-    /// Convert to SSE event string
-    pub fn to_sse_string(&self) -> String {
-        let event_type = match self {
+    /// Category subscribers filter the event by
+    pub fn category(&self) -> Option<EventCategory> {
+        match self {
+            StreamingEvent::Status { .. } => Some(EventCategory::Status),
+            StreamingEvent::Token { .. } => Some(EventCategory::Tokens),
+            StreamingEvent::ToolCall { .. } | StreamingEvent::ToolResult { .. } => {
+                Some(EventCategory::Tools)
+            }
+            StreamingEvent::Usage { .. } => Some(EventCategory::Usage),
+            StreamingEvent::MessageFinal { .. } | StreamingEvent::Error { .. } => None,
+        }
+    }
+
+    /// Name of the event in an SSE stream
+    pub fn sse_event_name(&self) -> &'static str {
+        match self {
             StreamingEvent::Status { .. } => "status",
             StreamingEvent::Token { .. } => "token",
             StreamingEvent::MessageFinal { .. } => "message.final",
             StreamingEvent::ToolCall { .. } => "tool.call",
             StreamingEvent::ToolResult { .. } => "tool.result",
+            StreamingEvent::Usage { .. } => "usage",
             StreamingEvent::Error { .. } => "error",
-        };
+        }
+    }
 
+    /// Convert to SSE event string
+    pub fn to_sse_string(&self) -> String {
+        let event_type = self.sse_event_name();
         let event_id = self.event_id();
         let data = serde_json::to_string(self).unwrap_or_default();
 
@@ -166,6 +258,97 @@ impl StreamingEvent {
     }
 }
 
+impl StreamingEvent {
+    /// Stream event of a runtime event of `session_id`, without an ID until
+    /// the buffer assigns one. Events stream clients have no event type for,
+    /// such as plans and todos, are left to the WebSocket stream.
+    pub fn from_runtime_event(event: &RuntimeEvent, session_id: &str) -> Option<Self> {
+        let event_id = EventId::new();
+        let session_id = session_id.to_string();
+        let status = |message: String| StreamingEvent::Status {
+            event_id: EventId::new(),
+            session_id: session_id.clone(),
+            data: StatusEventData { message },
+        };
+
+        match event {
+            RuntimeEvent::Token { token, .. } => Some(StreamingEvent::Token {
+                event_id,
+                session_id,
+                data: TokenEventData {
+                    token: token.clone(),
+                },
+            }),
+            RuntimeEvent::MessageCreated { message, .. } => {
+                let MessageContent::Text { text } = &message.content else {
+                    return None;
+                };
+                (message.role == MessageRole::Assistant).then(|| StreamingEvent::MessageFinal {
+                    event_id,
+                    session_id,
+                    data: MessageFinalEventData {
+                        message_id: message.id.clone(),
+                        content: text.clone(),
+                    },
+                })
+            }
+            RuntimeEvent::ToolCallRequested { request, .. } => Some(StreamingEvent::ToolCall {
+                event_id,
+                session_id,
+                data: ToolCallEventData {
+                    tool_call_id: request.tool_call_id.clone(),
+                    name: request.name.clone(),
+                    input: request.input.clone(),
+                },
+            }),
+            RuntimeEvent::ToolCallCompleted { result, .. } => {
+                let output = match &result.error {
+                    Some(error) if !result.success => serde_json::json!({ "error": error }),
+                    _ => result.output.clone(),
+                };
+                Some(StreamingEvent::ToolResult {
+                    event_id,
+                    session_id,
+                    data: ToolResultEventData {
+                        tool_call_id: result.tool_call_id.clone(),
+                        output,
+                    },
+                })
+            }
+            RuntimeEvent::Usage {
+                input_tokens,
+                output_tokens,
+                cached_input_tokens,
+                ..
+            } => Some(StreamingEvent::Usage {
+                event_id,
+                session_id,
+                data: UsageEventData {
+                    input_tokens: *input_tokens,
+                    output_tokens: *output_tokens,
+                    cached_input_tokens: *cached_input_tokens,
+                },
+            }),
+            RuntimeEvent::TaskStateChanged { state, .. } => serde_json::to_value(state)
+                .ok()
+                .and_then(|state| state.as_str().map(str::to_string))
+                .map(status),
+            RuntimeEvent::TaskQueuePositionChanged { position, .. } => {
+                Some(status(format!("Queued at position {}", position)))
+            }
+            RuntimeEvent::BudgetExceeded { message, .. } => Some(status(message.clone())),
+            RuntimeEvent::Error { message, .. } => Some(StreamingEvent::Error {
+                event_id,
+                session_id: Some(session_id),
+                data: ErrorEventData {
+                    message: message.clone(),
+                },
+            }),
+            _ => None,
+        }
+    }
+}
+
 /// Convert from storage SessionEvent to StreamingEvent
 impl TryFrom<SessionEvent> for StreamingEvent {
     type Error = String;
@@ -219,6 +402,15 @@ impl TryFrom<SessionEvent> for StreamingEvent {
                     data,
                 })
             }
+            EventType::Usage => {
+                let data: UsageEventData = serde_json::from_value(payload)
+                    .map_err(|e| format!("Failed to parse usage event: {}", e))?;
+                Ok(StreamingEvent::Usage {
+                    event_id: event.id,
+                    session_id: event.session_id,
+                    data,
+                })
+            }
             EventType::Error => {
                 let data: ErrorEventData = serde_json::from_value(payload)
                     .map_err(|e| format!("Failed to parse error event: {}", e))?;
@@ -286,6 +478,16 @@ impl From<StreamingEvent> for SessionEvent {
                 EventType::ToolResult,
                 serde_json::to_value(data).unwrap(),
             ),
+            StreamingEvent::Usage {
+                event_id,
+                session_id,
+                data,
+            } => (
+                event_id,
+                session_id,
+                EventType::Usage,
+                serde_json::to_value(data).unwrap(),
+            ),
             StreamingEvent::Error {
                 event_id,
                 session_id,
@@ -331,6 +533,17 @@ mod tests {
         assert!(sse.contains("event: token"));
     }
 
+    #[test]
+    fn test_event_categories() {
+        let categories = EventCategory::parse_list("tools, status").unwrap();
+        assert_eq!(
+            categories,
+            HashSet::from([EventCategory::Tools, EventCategory::Status])
+        );
+        assert!(EventCategory::parse_list("tools,diffs").is_err());
+        assert!(EventCategory::parse_list("").unwrap().is_empty());
+    }
+
     #[test]
     fn test_streaming_event_to_session_event() {
         let streaming = StreamingEvent::Status {
@@ -346,4 +559,70 @@ mod tests {
         assert_eq!(session_event.session_id, "sess-2");
         assert_eq!(session_event.event_type, EventType::Status);
     }
+
+    #[test]
+    fn test_streaming_event_from_runtime_event() {
+        use crate::core::types::{RuntimeTaskState, ToolResult};
+        use crate::storage::models::Message;
+
+        let message = |role, text: &str| RuntimeEvent::MessageCreated {
+            session_id: "sess-1".to_string(),
+            message: Message {
+                id: "msg-1".to_string(),
+                session_id: "sess-1".to_string(),
+                role,
+                content: MessageContent::Text {
+                    text: text.to_string(),
+                },
+                created_at: 0,
+                tool_call_id: None,
+                parent_id: None,
+                pinned: false,
+            },
+        };
+        let event =
+            StreamingEvent::from_runtime_event(&message(MessageRole::Assistant, "Done"), "sess-1")
+                .unwrap();
+        assert_eq!(event.event_type(), EventType::MessageFinal);
+        assert_eq!(event.session_id(), Some(&"sess-1".to_string()));
+        assert!(event.event_id().is_empty());
+        // The user's own messages are not streamed back
+        assert!(
+            StreamingEvent::from_runtime_event(&message(MessageRole::User, "Hi"), "sess-1")
+                .is_none()
+        );
+
+        let state = RuntimeEvent::TaskStateChanged {
+            task_id: "task-1".to_string(),
+            state: RuntimeTaskState::WaitingForUser,
+            previous_state: RuntimeTaskState::Running,
+        };
+        match StreamingEvent::from_runtime_event(&state, "sess-1") {
+            Some(StreamingEvent::Status { data, .. }) => assert_eq!(data.message, "waitingForUser"),
+            other => panic!("Unexpected event: {:?}", other),
+        }
+
+        let failed = RuntimeEvent::ToolCallCompleted {
+            task_id: "task-1".to_string(),
+            result: ToolResult {
+                tool_call_id: "call-1".to_string(),
+                success: false,
+                output: serde_json::Value::Null,
+                error: Some("Permission denied".to_string()),
+            },
+        };
+        match StreamingEvent::from_runtime_event(&failed, "sess-1") {
+            Some(StreamingEvent::ToolResult { data, .. }) => {
+                assert_eq!(data.tool_call_id, "call-1");
+                assert_eq!(data.output["error"], "Permission denied");
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+
+        let completed = RuntimeEvent::TaskCompleted {
+            task_id: "task-1".to_string(),
+            session_id: "sess-1".to_string(),
+        };
+        assert!(StreamingEvent::from_runtime_event(&completed, "sess-1").is_none());
+    }
 }
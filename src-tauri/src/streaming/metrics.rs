@@ -0,0 +1,238 @@
+//! Streaming Metrics
+//!
+//! Counters of the streaming layer for diagnosing clients that stopped
+//! updating: events coalesced or dropped on their way to clients, the
+//! connected SSE and WebSocket subscribers, and how far behind each one is.
+
+use crate::storage::models::SessionId;
+use crate::streaming::buffer::BufferStats;
+use crate::streaming::throttle::ConsumerLag;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Transport a subscriber receives events over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SubscriberTransport {
+    Sse,
+    WebSocket,
+}
+
+/// A connected subscriber and how far behind it is
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriberStats {
+    pub id: u64,
+    pub transport: SubscriberTransport,
+    pub session_ids: Vec<SessionId>,
+    /// Events waiting to be sent to the subscriber
+    pub queued: usize,
+    /// Time the subscriber took to write its last event
+    pub write_latency_ms: u64,
+    /// Events the subscriber missed because it fell behind
+    pub dropped_events: u64,
+    pub connected_at: i64,
+}
+
+/// Lag of the subscribers of one session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionLagStats {
+    pub session_id: SessionId,
+    pub subscribers: usize,
+    pub max_queued: usize,
+    pub max_write_latency_ms: u64,
+}
+
+/// Snapshot of the streaming layer
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamingStats {
+    pub buffer: BufferStats,
+    /// Events merged into another event, such as consecutive tokens
+    pub coalesced_events: u64,
+    /// Events never delivered, such as superseded status events
+    pub dropped_events: u64,
+    pub active_subscribers: usize,
+    pub subscribers: Vec<SubscriberStats>,
+    pub sessions: Vec<SessionLagStats>,
+}
+
+/// Counters shared by the throttler and the stream routes
+#[derive(Debug, Default)]
+pub struct StreamingMetrics {
+    coalesced: AtomicU64,
+    dropped: AtomicU64,
+    next_subscriber_id: AtomicU64,
+    subscribers: Mutex<HashMap<u64, SubscriberStats>>,
+}
+
+impl StreamingMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_coalesced(&self, count: u64) {
+        self.coalesced.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self, count: u64) {
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Track a connected subscriber until the returned handle is dropped
+    pub fn register(
+        self: &Arc<Self>,
+        transport: SubscriberTransport,
+        session_ids: Vec<SessionId>,
+    ) -> SubscriberHandle {
+        let id = self.next_subscriber_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.lock().insert(
+            id,
+            SubscriberStats {
+                id,
+                transport,
+                session_ids,
+                queued: 0,
+                write_latency_ms: 0,
+                dropped_events: 0,
+                connected_at: chrono::Utc::now().timestamp(),
+            },
+        );
+        SubscriberHandle {
+            metrics: self.clone(),
+            id,
+        }
+    }
+
+    /// Snapshot of the counters along with the buffer's stats
+    pub fn snapshot(&self, buffer: BufferStats) -> StreamingStats {
+        let mut subscribers: Vec<SubscriberStats> = self.lock().values().cloned().collect();
+        subscribers.sort_by_key(|subscriber| subscriber.id);
+
+        let mut sessions: BTreeMap<&SessionId, SessionLagStats> = BTreeMap::new();
+        for subscriber in &subscribers {
+            for session_id in &subscriber.session_ids {
+                let lag = sessions
+                    .entry(session_id)
+                    .or_insert_with(|| SessionLagStats {
+                        session_id: session_id.clone(),
+                        subscribers: 0,
+                        max_queued: 0,
+                        max_write_latency_ms: 0,
+                    });
+                lag.subscribers += 1;
+                lag.max_queued = lag.max_queued.max(subscriber.queued);
+                lag.max_write_latency_ms =
+                    lag.max_write_latency_ms.max(subscriber.write_latency_ms);
+            }
+        }
+        let sessions = sessions.into_values().collect();
+
+        StreamingStats {
+            buffer,
+            coalesced_events: self.coalesced.load(Ordering::Relaxed),
+            dropped_events: self.dropped.load(Ordering::Relaxed),
+            active_subscribers: subscribers.len(),
+            subscribers,
+            sessions,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, SubscriberStats>> {
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A registered subscriber; it is removed from the metrics when dropped
+pub struct SubscriberHandle {
+    metrics: Arc<StreamingMetrics>,
+    id: u64,
+}
+
+impl SubscriberHandle {
+    /// Replace the sessions the subscriber follows
+    pub fn set_sessions(&self, session_ids: Vec<SessionId>) {
+        self.update(|subscriber| subscriber.session_ids = session_ids);
+    }
+
+    pub fn record_lag(&self, lag: &ConsumerLag) {
+        self.update(|subscriber| {
+            subscriber.queued = lag.queued;
+            subscriber.write_latency_ms = lag.write_latency.as_millis() as u64;
+        });
+    }
+
+    /// Count events the subscriber missed, in its own and the global counter
+    pub fn record_dropped(&self, count: u64) {
+        self.metrics.record_dropped(count);
+        self.update(|subscriber| subscriber.dropped_events += count);
+    }
+
+    fn update(&self, f: impl FnOnce(&mut SubscriberStats)) {
+        if let Some(subscriber) = self.metrics.lock().get_mut(&self.id) {
+            f(subscriber);
+        }
+    }
+}
+
+impl Drop for SubscriberHandle {
+    fn drop(&mut self) {
+        self.metrics.lock().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_subscriber_lag_per_session() {
+        let metrics = Arc::new(StreamingMetrics::new());
+        let sse = metrics.register(SubscriberTransport::Sse, vec!["sess-1".to_string()]);
+        let ws = metrics.register(SubscriberTransport::WebSocket, Vec::new());
+        ws.set_sessions(vec!["sess-1".to_string(), "sess-2".to_string()]);
+        sse.record_lag(&ConsumerLag {
+            queued: 40,
+            write_latency: Duration::from_millis(5),
+        });
+        ws.record_lag(&ConsumerLag {
+            queued: 3,
+            write_latency: Duration::from_millis(300),
+        });
+        ws.record_dropped(7);
+        metrics.record_coalesced(2);
+
+        let buffer = BufferStats {
+            total_sessions: 0,
+            total_events: 0,
+            max_events_per_session: 1000,
+        };
+        let stats = metrics.snapshot(buffer.clone());
+        assert_eq!(stats.active_subscribers, 2);
+        assert_eq!(stats.coalesced_events, 2);
+        assert_eq!(stats.dropped_events, 7);
+        assert_eq!(stats.subscribers[1].dropped_events, 7);
+        assert_eq!(
+            stats.sessions[0],
+            SessionLagStats {
+                session_id: "sess-1".to_string(),
+                subscribers: 2,
+                max_queued: 40,
+                max_write_latency_ms: 300,
+            }
+        );
+        assert_eq!(stats.sessions[1].subscribers, 1);
+
+        // Disconnected subscribers are no longer listed
+        drop(sse);
+        drop(ws);
+        let stats = metrics.snapshot(buffer);
+        assert_eq!(stats.active_subscribers, 0);
+        assert!(stats.sessions.is_empty());
+        assert_eq!(stats.dropped_events, 7);
+    }
+}
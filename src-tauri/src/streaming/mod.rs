@@ -1,14 +1,21 @@
 //! Streaming Layer
 //!
-//! Handles event streaming for SSE with buffering, throttling, and resume capability.
+//! Handles event streaming for SSE with buffering, throttling, compression, and resume capability.
 
 pub mod buffer;
+pub mod compression;
 pub mod events;
+pub mod metrics;
 pub mod throttle;
 
 pub use buffer::{BufferStats, EventBuffer};
+pub use compression::{DeltaEncoder, StreamCompressor, StreamEncoding};
 pub use events::*;
-pub use throttle::{EventThrottler, StreamingManager, ThrottleConfig};
+pub use metrics::{StreamingMetrics, StreamingStats, SubscriberHandle, SubscriberTransport};
+pub use throttle::{
+    ConsumerLag, EventThrottler, StreamingManager, ThrottleConfig, ThrottlePolicies,
+    ThrottlePolicy, THROTTLE_POLICIES_KEY,
+};
 
 /// Create a new streaming manager with default configuration
 pub fn create_manager() -> StreamingManager {
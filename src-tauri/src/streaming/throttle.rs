@@ -3,37 +3,97 @@
 //! Throttles streaming events to match desktop behavior:
 //! - Edit updates at ~1s cadence
 //! - Token streaming with debouncing
+//! - Per event type policies: token, status and usage events are held and
+//!   merged until their interval passes or their message ends, while tool,
+//!   final message and error events are sent immediately
 //! - Message length caps
+//! - Coalescing for consumers that fall behind: consecutive tokens are
+//!   merged and superseded status events dropped, while tool, final message
+//!   and error events are always delivered
 
+use crate::storage::models::SessionId;
+use crate::storage::Storage;
 use crate::streaming::events::StreamingEvent;
+use crate::streaming::metrics::{StreamingMetrics, StreamingStats};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
+/// Setting holding the `ThrottlePolicies` of streams
+pub const THROTTLE_POLICIES_KEY: &str = "stream_throttle_policies";
+
+/// When events of one type reach stream clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum ThrottlePolicy {
+    /// Every event is sent as it arrives
+    Immediate,
+    /// Events are merged and sent at most once per interval
+    Interval { millis: u64 },
+    /// Events are merged and sent when the session's message ends
+    AtEnd,
+}
+
+/// Throttle policies of the event types that may be held back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ThrottlePolicies {
+    pub token: ThrottlePolicy,
+    pub status: ThrottlePolicy,
+    pub usage: ThrottlePolicy,
+}
+
+impl Default for ThrottlePolicies {
+    fn default() -> Self {
+        Self {
+            token: ThrottlePolicy::Interval { millis: 50 }, // 20 tokens/sec max
+            status: ThrottlePolicy::Interval { millis: 1000 },
+            usage: ThrottlePolicy::AtEnd,
+        }
+    }
+}
+
 /// Throttling configuration
 #[derive(Debug, Clone)]
 pub struct ThrottleConfig {
-    /// Minimum interval between token events
-    pub token_interval: Duration,
-    /// Minimum interval between status events
-    pub status_interval: Duration,
+    /// When token, status and usage events are sent
+    pub policies: ThrottlePolicies,
     /// Maximum message length before truncation
     pub max_message_length: usize,
     /// Debounce duration for aggregating tokens
     pub debounce_duration: Duration,
+    /// Queued events at which a consumer counts as lagging
+    pub lag_queue_depth: usize,
+    /// Time to write one event at which a consumer counts as lagging
+    pub lag_write_latency: Duration,
+    /// Most queued events coalesced at once for a lagging consumer
+    pub max_coalesced_events: usize,
 }
 
 impl Default for ThrottleConfig {
     fn default() -> Self {
         Self {
-            token_interval: Duration::from_millis(50), // 20 tokens/sec max
-            status_interval: Duration::from_secs(1),
+            policies: ThrottlePolicies::default(),
             max_message_length: 100_000,
             debounce_duration: Duration::from_millis(100),
+            lag_queue_depth: 32,
+            lag_write_latency: Duration::from_millis(200),
+            max_coalesced_events: 256,
         }
     }
 }
 
+/// How far a stream consumer is behind the events produced for it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConsumerLag {
+    /// Events waiting in the consumer's channel
+    pub queued: usize,
+    /// Time the consumer took to write its previous event
+    pub write_latency: Duration,
+}
+
 /// Event throttler for streaming
 pub struct EventThrottler {
     config: ThrottleConfig,
@@ -41,6 +101,10 @@ pub struct EventThrottler {
     last_event_times: RwLock<HashMap<(String, String), Instant>>,
     /// Accumulated tokens per session for debouncing
     token_buffers: RwLock<HashMap<String, String>>,
+    /// Events held back per session, at most one per event type
+    held_events: RwLock<HashMap<String, Vec<StreamingEvent>>>,
+    /// Counts of the events merged or dropped
+    metrics: Arc<StreamingMetrics>,
 }
 
 impl EventThrottler {
@@ -49,18 +113,32 @@ impl EventThrottler {
             config,
             last_event_times: RwLock::new(HashMap::new()),
             token_buffers: RwLock::new(HashMap::new()),
+            held_events: RwLock::new(HashMap::new()),
+            metrics: Arc::new(StreamingMetrics::new()),
+        }
+    }
+
+    /// Counters of the streaming layer, shared with stream subscribers
+    pub fn metrics(&self) -> &Arc<StreamingMetrics> {
+        &self.metrics
+    }
+
+    /// Throttle policy of an event; tool, final message and error events are
+    /// never held back
+    pub fn policy(&self, event: &StreamingEvent) -> ThrottlePolicy {
+        match event {
+            StreamingEvent::Token { .. } => self.config.policies.token,
+            StreamingEvent::Status { .. } => self.config.policies.status,
+            StreamingEvent::Usage { .. } => self.config.policies.usage,
+            _ => ThrottlePolicy::Immediate,
         }
     }
 
     /// Check if an event should be throttled
     pub async fn should_throttle(&self, event: &StreamingEvent) -> bool {
-        let session_id = event.session_id().map(|s| s.as_str()).unwrap_or("global");
-        let event_type = format!("{:?}", event.event_type());
-        let key = (session_id.to_string(), event_type);
-
-        let interval = match event {
-            StreamingEvent::Token { .. } => self.config.token_interval,
-            StreamingEvent::Status { .. } => self.config.status_interval,
+        let key = throttle_key(event);
+        let interval = match self.policy(event) {
+            ThrottlePolicy::Interval { millis } => Duration::from_millis(millis),
             _ => Duration::ZERO, // No throttling for other events
         };
 
@@ -82,6 +160,81 @@ impl EventThrottler {
         false
     }
 
+    /// Pass an event through its type's policy. Returns the events to send
+    /// now in order: held events of the session come before an immediate
+    /// event, and held usage follows the event that ends a message.
+    pub async fn admit(&self, event: StreamingEvent) -> Vec<StreamingEvent> {
+        let session_id = event.session_id().map(|s| s.as_str()).unwrap_or("global");
+        let session_id = session_id.to_string();
+        let ends_message = matches!(
+            event,
+            StreamingEvent::MessageFinal { .. } | StreamingEvent::Error { .. }
+        );
+
+        let mut held_events = self.held_events.write().await;
+        let held = held_events.entry(session_id.clone()).or_default();
+        let mut ready = Vec::new();
+        if self.policy(&event) == ThrottlePolicy::Immediate {
+            let (at_end, earlier): (Vec<_>, Vec<_>) = held
+                .drain(..)
+                .partition(|held| self.policy(held) == ThrottlePolicy::AtEnd);
+            ready.extend(earlier);
+            ready.push(event);
+            if ends_message {
+                ready.extend(at_end);
+            } else {
+                *held = at_end;
+            }
+        } else {
+            hold(held, event, &self.metrics);
+        }
+
+        // Held events whose interval passed are sent along
+        let mut times = self.last_event_times.write().await;
+        let now = Instant::now();
+        let mut index = 0;
+        while index < held.len() {
+            let due = match self.policy(&held[index]) {
+                ThrottlePolicy::Interval { millis } => times
+                    .get(&throttle_key(&held[index]))
+                    .is_none_or(|last| now.duration_since(*last) >= Duration::from_millis(millis)),
+                _ => false,
+            };
+            if due {
+                ready.push(held.remove(index));
+            } else {
+                index += 1;
+            }
+        }
+        for event in &ready {
+            times.insert(throttle_key(event), now);
+        }
+
+        if held.is_empty() {
+            held_events.remove(&session_id);
+        }
+        ready
+    }
+
+    /// Release every event held for a session, such as when its stream ends
+    pub async fn flush(&self, session_id: &str) -> Vec<StreamingEvent> {
+        self.held_events
+            .write()
+            .await
+            .remove(session_id)
+            .unwrap_or_default()
+    }
+
+    /// Release the events held for every session, grouped by session
+    pub async fn flush_all(&self) -> Vec<StreamingEvent> {
+        self.held_events
+            .write()
+            .await
+            .drain()
+            .flat_map(|(_, held)| held)
+            .collect()
+    }
+
     /// Accumulate tokens for debouncing
     pub async fn accumulate_token(&self, session_id: &str, token: &str) -> Option<String> {
         let mut buffers = self.token_buffers.write().await;
@@ -118,6 +271,59 @@ impl EventThrottler {
         }
     }
 
+    /// Whether a consumer is far enough behind to coalesce its events
+    pub fn is_lagging(&self, lag: &ConsumerLag) -> bool {
+        lag.queued >= self.config.lag_queue_depth
+            || lag.write_latency >= self.config.lag_write_latency
+    }
+
+    /// Most events to take from a lagging consumer's queue at once
+    pub fn max_coalesced_events(&self) -> usize {
+        self.config.max_coalesced_events
+    }
+
+    /// Coalesce a lagging consumer's queued events, in order: consecutive
+    /// tokens of a session are merged into one event carrying the last ID, and
+    /// a status event is dropped when a later one of its session follows.
+    pub fn coalesce(&self, events: Vec<StreamingEvent>) -> Vec<StreamingEvent> {
+        // Index of the last status event of each session
+        let mut last_status: HashMap<SessionId, usize> = HashMap::new();
+        for (index, event) in events.iter().enumerate() {
+            if let StreamingEvent::Status { session_id, .. } = event {
+                last_status.insert(session_id.clone(), index);
+            }
+        }
+
+        let mut coalesced: Vec<StreamingEvent> = Vec::with_capacity(events.len());
+        for (index, event) in events.into_iter().enumerate() {
+            match (coalesced.last_mut(), event) {
+                (
+                    Some(StreamingEvent::Token {
+                        event_id,
+                        session_id,
+                        data,
+                    }),
+                    StreamingEvent::Token {
+                        event_id: next_id,
+                        session_id: next_session_id,
+                        data: next,
+                    },
+                ) if *session_id == next_session_id => {
+                    data.token.push_str(&next.token);
+                    *event_id = next_id;
+                    self.metrics.record_coalesced(1);
+                }
+                (_, StreamingEvent::Status { ref session_id, .. })
+                    if last_status.get(session_id) != Some(&index) =>
+                {
+                    self.metrics.record_dropped(1);
+                }
+                (_, event) => coalesced.push(event),
+            }
+        }
+        coalesced
+    }
+
     /// Clear state for a session
     pub async fn clear_session(&self, session_id: &str) {
         let mut times = self.last_event_times.write().await;
@@ -132,6 +338,59 @@ impl EventThrottler {
 
         let mut buffers = self.token_buffers.write().await;
         buffers.remove(session_id);
+
+        self.held_events.write().await.remove(session_id);
+    }
+}
+
+/// Key of an event's last send time
+fn throttle_key(event: &StreamingEvent) -> (String, String) {
+    let session_id = event.session_id().map(|s| s.as_str()).unwrap_or("global");
+    (session_id.to_string(), format!("{:?}", event.event_type()))
+}
+
+/// Hold an event back, merged into the held event of its type: tokens are
+/// appended, usage is summed and a later status replaces an earlier one
+fn hold(held: &mut Vec<StreamingEvent>, event: StreamingEvent, metrics: &StreamingMetrics) {
+    let event_type = event.event_type();
+    match (
+        held.iter_mut().find(|held| held.event_type() == event_type),
+        event,
+    ) {
+        (
+            Some(StreamingEvent::Token { event_id, data, .. }),
+            StreamingEvent::Token {
+                event_id: next_id,
+                data: next,
+                ..
+            },
+        ) => {
+            data.token.push_str(&next.token);
+            *event_id = next_id;
+            metrics.record_coalesced(1);
+        }
+        (
+            Some(StreamingEvent::Usage { event_id, data, .. }),
+            StreamingEvent::Usage {
+                event_id: next_id,
+                data: next,
+                ..
+            },
+        ) => {
+            data.input_tokens += next.input_tokens;
+            data.output_tokens += next.output_tokens;
+            data.cached_input_tokens = match (data.cached_input_tokens, next.cached_input_tokens) {
+                (Some(cached), Some(next)) => Some(cached + next),
+                (cached, next) => cached.or(next),
+            };
+            *event_id = next_id;
+            metrics.record_coalesced(1);
+        }
+        (Some(existing), event) => {
+            *existing = event;
+            metrics.record_dropped(1);
+        }
+        (None, event) => held.push(event),
     }
 }
 
@@ -160,10 +419,53 @@ impl StreamingManager {
         self
     }
 
+    /// Persist events that overflow the buffer's memory to storage
+    pub fn with_storage(mut self, storage: Arc<Storage>) -> Self {
+        self.buffer = self.buffer.with_storage(storage);
+        self
+    }
+
     pub fn with_throttle_config(mut self, config: ThrottleConfig) -> Self {
+        let metrics = self.throttler.metrics.clone();
         self.throttler = EventThrottler::new(config);
+        self.throttler.metrics = metrics;
         self
     }
+
+    /// Buffer stats and the counters of events and subscribers
+    pub async fn get_stats(&self) -> StreamingStats {
+        let buffer = self.buffer.get_stats().await;
+        self.throttler.metrics.snapshot(buffer)
+    }
+
+    /// Publish an event to stream clients as its throttle policy allows.
+    /// Returns the events added to the buffer.
+    pub async fn publish(&self, event: StreamingEvent) -> Result<Vec<StreamingEvent>, String> {
+        let mut published = Vec::new();
+        for event in self.throttler.admit(event).await {
+            published.push(self.buffer.add_event(event).await?);
+        }
+        Ok(published)
+    }
+
+    /// Publish the events held back for a session
+    pub async fn flush(&self, session_id: &str) -> Result<Vec<StreamingEvent>, String> {
+        let mut published = Vec::new();
+        for event in self.throttler.flush(session_id).await {
+            published.push(self.buffer.add_event(event).await?);
+        }
+        Ok(published)
+    }
+
+    /// Publish the events held back for every session, then move the
+    /// buffered events to storage so clients resuming after a restart can
+    /// replay them. Returns the number of events stored.
+    pub async fn shutdown(&self) -> Result<usize, String> {
+        for event in self.throttler.flush_all().await {
+            self.buffer.add_event(event).await?;
+        }
+        self.buffer.persist_all().await
+    }
 }
 
 impl Default for StreamingManager {
@@ -175,12 +477,86 @@ impl Default for StreamingManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::streaming::events::TokenEventData;
+    use crate::storage::models::EventType;
+    use crate::streaming::events::{
+        MessageFinalEventData, StatusEventData, TokenEventData, ToolCallEventData, UsageEventData,
+    };
 
     #[tokio::test]
     async fn test_throttle_config() {
         let config = ThrottleConfig::default();
-        assert_eq!(config.token_interval, Duration::from_millis(50));
+        assert_eq!(
+            config.policies.token,
+            ThrottlePolicy::Interval { millis: 50 }
+        );
+
+        let policies: ThrottlePolicies = serde_json::from_value(
+            serde_json::json!({ "token": { "mode": "interval", "millis": 30 } }),
+        )
+        .unwrap();
+        assert_eq!(policies.token, ThrottlePolicy::Interval { millis: 30 });
+        assert_eq!(policies.usage, ThrottlePolicy::AtEnd);
+    }
+
+    #[tokio::test]
+    async fn test_admit_holds_events_per_policy() {
+        let throttler = EventThrottler::default();
+        let token = |token: &str| StreamingEvent::Token {
+            event_id: String::new(),
+            session_id: "sess-1".to_string(),
+            data: TokenEventData {
+                token: token.to_string(),
+            },
+        };
+        let usage = |tokens: i32| StreamingEvent::Usage {
+            event_id: String::new(),
+            session_id: "sess-1".to_string(),
+            data: UsageEventData {
+                input_tokens: tokens,
+                output_tokens: tokens,
+                cached_input_tokens: None,
+            },
+        };
+        let types = |events: Vec<StreamingEvent>| -> Vec<EventType> {
+            events.iter().map(|e| e.event_type()).collect()
+        };
+
+        // The first token goes out, later ones wait for the interval
+        assert_eq!(
+            types(throttler.admit(token("Hel")).await),
+            [EventType::Token]
+        );
+        assert!(throttler.admit(token("lo")).await.is_empty());
+        assert!(throttler.admit(token("!")).await.is_empty());
+        assert!(throttler.admit(usage(10)).await.is_empty());
+        assert!(throttler.admit(usage(5)).await.is_empty());
+
+        // A final message releases held tokens before it and usage after it
+        let released = throttler
+            .admit(StreamingEvent::MessageFinal {
+                event_id: String::new(),
+                session_id: "sess-1".to_string(),
+                data: MessageFinalEventData {
+                    message_id: "msg-1".to_string(),
+                    content: "Hello!".to_string(),
+                },
+            })
+            .await;
+        assert_eq!(
+            types(released.clone()),
+            [EventType::Token, EventType::MessageFinal, EventType::Usage]
+        );
+        match (&released[0], &released[2]) {
+            (
+                StreamingEvent::Token { data: token, .. },
+                StreamingEvent::Usage { data: usage, .. },
+            ) => {
+                assert_eq!(token.token, "lo!");
+                assert_eq!(usage.input_tokens, 15);
+            }
+            other => panic!("Unexpected events {:?}", other),
+        }
+        assert!(throttler.flush("sess-1").await.is_empty());
     }
 
     #[tokio::test]
@@ -199,6 +575,56 @@ mod tests {
         assert_eq!(flushed, Some("Hi there".to_string()));
     }
 
+    #[test]
+    fn test_coalesce_for_lagging_consumer() {
+        let throttler = EventThrottler::default();
+        let token = |id: &str, session_id: &str, token: &str| StreamingEvent::Token {
+            event_id: id.to_string(),
+            session_id: session_id.to_string(),
+            data: TokenEventData {
+                token: token.to_string(),
+            },
+        };
+        let status = |id: &str, message: &str| StreamingEvent::Status {
+            event_id: id.to_string(),
+            session_id: "sess-1".to_string(),
+            data: StatusEventData {
+                message: message.to_string(),
+            },
+        };
+        let tool_call = StreamingEvent::ToolCall {
+            event_id: "4".to_string(),
+            session_id: "sess-1".to_string(),
+            data: ToolCallEventData {
+                tool_call_id: "call-1".to_string(),
+                name: "read_file".to_string(),
+                input: serde_json::json!({}),
+            },
+        };
+
+        assert!(!throttler.is_lagging(&ConsumerLag::default()));
+        assert!(throttler.is_lagging(&ConsumerLag {
+            queued: 100,
+            write_latency: Duration::ZERO,
+        }));
+
+        let coalesced = throttler.coalesce(vec![
+            status("1", "running"),
+            token("2", "sess-1", "Hel"),
+            token("3", "sess-1", "lo"),
+            tool_call,
+            token("5", "sess-1", "!"),
+            token("6", "sess-2", "?"),
+            status("7", "waitingForUser"),
+        ]);
+        let ids: Vec<&str> = coalesced.iter().map(|e| e.event_id().as_str()).collect();
+        assert_eq!(ids, ["3", "4", "5", "6", "7"]);
+        match &coalesced[0] {
+            StreamingEvent::Token { data, .. } => assert_eq!(data.token, "Hello"),
+            other => panic!("Expected a token, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_message_length_cap() {
         let throttler = EventThrottler::default();
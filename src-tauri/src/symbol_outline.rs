@@ -0,0 +1,552 @@
+//! Tree-sitter symbol outlines and workspace symbol search.
+//!
+//! Extracts functions, classes, structs and other definitions per file using the same
+//! definition queries as `code_navigation`, so outlines work without a language server.
+//! Outlines are cached per file and dropped when FileWatcher reports a change; the
+//! cache also re-checks size and modification time for roots that are not watched.
+
+use crate::code_navigation::{CodeNavigationService, SUPPORTED_LANGUAGES};
+use crate::file_watcher::FileSystemChange;
+use crate::text_file::{read_text_file, skip_for_search, TextContent};
+use crate::walker::{WalkerConfig, WorkspaceWalker};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::SystemTime;
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Language, Node, Parser, Query, QueryCursor, Tree};
+
+/// Files larger than this are not parsed
+const MAX_OUTLINE_FILE_SIZE: u64 = 1024 * 1024;
+/// The cache is cleared when it grows past this many files
+const MAX_CACHED_OUTLINES: usize = 20_000;
+const DEFAULT_MAX_SYMBOL_RESULTS: usize = 200;
+/// Ancestors inspected when looking for the node that spans a definition
+const MAX_DEFINITION_DEPTH: usize = 4;
+
+/// Node kinds whose name qualifies the definitions nested inside them
+const CONTAINER_KINDS: &[&str] = &[
+    "class_definition",
+    "class_declaration",
+    "class_specifier",
+    "struct_specifier",
+    "interface_declaration",
+    "impl_item",
+    "trait_item",
+];
+
+/// Top-level node kinds that import other modules
+const IMPORT_KINDS: &[&str] = &[
+    "use_declaration",
+    "extern_crate_declaration",
+    "import_statement",
+    "import_from_statement",
+    "future_import_statement",
+    "import_declaration",
+    "preproc_include",
+];
+
+/// A definition found in a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutlineSymbol {
+    pub name: String,
+    pub kind: String,
+    /// Enclosing class, impl or trait, if any
+    pub container: Option<String>,
+    /// 1-based line range of the whole definition
+    pub start_line: u32,
+    pub end_line: u32,
+    /// 1-based column of the symbol name
+    pub name_column: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileOutline {
+    pub file_path: String,
+    /// `None` when the file's language is not supported
+    pub language: Option<String>,
+    pub symbols: Vec<OutlineSymbol>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolMatch {
+    pub file_path: String,
+    #[serde(flatten)]
+    pub symbol: OutlineSymbol,
+}
+
+struct Grammar {
+    language: Language,
+    query: Query,
+}
+
+struct CachedOutline {
+    size: u64,
+    modified: Option<SystemTime>,
+    symbols: Arc<Vec<OutlineSymbol>>,
+}
+
+fn grammars() -> &'static HashMap<&'static str, Grammar> {
+    static GRAMMARS: OnceLock<HashMap<&'static str, Grammar>> = OnceLock::new();
+    GRAMMARS.get_or_init(|| {
+        let mut grammars = HashMap::new();
+        for &lang_id in SUPPORTED_LANGUAGES {
+            let Some(language) = CodeNavigationService::language_for(lang_id) else {
+                continue;
+            };
+            match Query::new(
+                &language,
+                CodeNavigationService::get_definition_query(lang_id),
+            ) {
+                Ok(query) => {
+                    grammars.insert(lang_id, Grammar { language, query });
+                }
+                Err(e) => log::error!("Failed to create outline query for {}: {:?}", lang_id, e),
+            }
+        }
+        grammars
+    })
+}
+
+fn cache() -> &'static RwLock<HashMap<PathBuf, CachedOutline>> {
+    static OUTLINES: OnceLock<RwLock<HashMap<PathBuf, CachedOutline>>> = OnceLock::new();
+    OUTLINES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Closest ancestor that spans the whole definition (e.g. `function_definition` for a
+/// C function whose name sits inside a `function_declarator`)
+fn definition_node(name_node: Node) -> Node {
+    let mut current = name_node;
+    for _ in 0..MAX_DEFINITION_DEPTH {
+        let Some(parent) = current.parent() else {
+            break;
+        };
+        let kind = parent.kind();
+        if kind.ends_with("_definition")
+            || kind.ends_with("_declaration")
+            || kind.ends_with("_item")
+            || kind.ends_with("_specifier")
+            || kind == "variable_declarator"
+            || kind == "type_spec"
+        {
+            return parent;
+        }
+        current = parent;
+    }
+    name_node.parent().unwrap_or(name_node)
+}
+
+fn container_name(definition: Node, source: &[u8]) -> Option<String> {
+    let mut current = definition.parent();
+    while let Some(node) = current {
+        if CONTAINER_KINDS.contains(&node.kind()) {
+            let name = node
+                .child_by_field_name("name")
+                .or_else(|| node.child_by_field_name("type"))?;
+            return name.utf8_text(source).ok().map(str::to_string);
+        }
+        current = node.parent();
+    }
+    None
+}
+
+fn parse(content: &str, lang_id: &str) -> Option<(&'static Grammar, Tree)> {
+    let grammar = grammars().get(lang_id)?;
+    let mut parser = Parser::new();
+    parser.set_language(&grammar.language).ok()?;
+    let tree = parser.parse(content, None)?;
+    Some((grammar, tree))
+}
+
+/// Extract the outline of a source string
+pub fn extract_outline(content: &str, lang_id: &str) -> Vec<OutlineSymbol> {
+    let Some((grammar, tree)) = parse(content, lang_id) else {
+        return Vec::new();
+    };
+
+    let source = content.as_bytes();
+    let mut symbols = Vec::new();
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(&grammar.query, tree.root_node(), source);
+    while let Some(m) = matches.next() {
+        for capture in m.captures {
+            let Ok(name) = capture.node.utf8_text(source) else {
+                continue;
+            };
+            let definition = definition_node(capture.node);
+            let container = container_name(definition, source);
+            let capture_name = grammar.query.capture_names()[capture.index as usize];
+            let mut kind = CodeNavigationService::get_symbol_kind(capture_name);
+            if kind == "function" && container.is_some() {
+                kind = "method".to_string();
+            }
+
+            symbols.push(OutlineSymbol {
+                name: name.to_string(),
+                kind,
+                container,
+                start_line: definition.start_position().row as u32 + 1,
+                end_line: definition.end_position().row as u32 + 1,
+                name_column: capture.node.start_position().column as u32 + 1,
+            });
+        }
+    }
+
+    // Exported declarations are matched both with and without their export statement
+    symbols.sort_by(|a, b| {
+        (a.start_line, a.name_column, &a.name).cmp(&(b.start_line, b.name_column, &b.name))
+    });
+    symbols.dedup();
+    symbols
+}
+
+/// Top-level imports of a source string, each collapsed onto one line
+pub fn extract_imports(content: &str, lang_id: &str) -> Vec<String> {
+    let Some((_, tree)) = parse(content, lang_id) else {
+        return Vec::new();
+    };
+
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    root.children(&mut cursor)
+        .filter(|node| IMPORT_KINDS.contains(&node.kind()))
+        .filter_map(|node| node.utf8_text(content.as_bytes()).ok())
+        .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect()
+}
+
+/// Outline of a file on disk, served from the cache when the file is unchanged
+fn outline_file(path: &Path, lang_id: &str) -> Result<Arc<Vec<OutlineSymbol>>, String> {
+    let metadata = std::fs::metadata(path)
+        .map_err(|e| format!("Failed to read metadata for {}: {}", path.display(), e))?;
+    let size = metadata.len();
+    let modified = metadata.modified().ok();
+
+    if let Ok(cache) = cache().read() {
+        if let Some(cached) = cache.get(path) {
+            if cached.size == size && cached.modified == modified {
+                return Ok(cached.symbols.clone());
+            }
+        }
+    }
+
+    let content = match read_text_file(path, MAX_OUTLINE_FILE_SIZE)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+    {
+        TextContent::Text(content) => content,
+        TextContent::Skipped(skipped) => return Err(skipped.message()),
+    };
+    let symbols = Arc::new(extract_outline(&content, lang_id));
+
+    if let Ok(mut cache) = cache().write() {
+        if cache.len() >= MAX_CACHED_OUTLINES {
+            cache.clear();
+        }
+        cache.insert(
+            path.to_path_buf(),
+            CachedOutline {
+                size,
+                modified,
+                symbols: symbols.clone(),
+            },
+        );
+    }
+    Ok(symbols)
+}
+
+/// Drop cached outlines for changed paths (and anything below them, for directories)
+pub fn invalidate_changes(changes: &[FileSystemChange]) {
+    let stale: Vec<&Path> = changes
+        .iter()
+        .flat_map(|change| match change {
+            FileSystemChange::Created { path }
+            | FileSystemChange::Modified { path }
+            | FileSystemChange::Deleted { path } => vec![path.as_path()],
+            FileSystemChange::Renamed { old_path, new_path } => {
+                vec![old_path.as_path(), new_path.as_path()]
+            }
+        })
+        .collect();
+    if stale.is_empty() {
+        return;
+    }
+
+    if let Ok(mut cache) = cache().write() {
+        cache.retain(|path, _| !stale.iter().any(|s| path.starts_with(s)));
+    }
+}
+
+/// Drop all cached outlines under a root
+pub fn invalidate_root(root: &Path) {
+    if let Ok(mut cache) = cache().write() {
+        cache.retain(|path, _| !path.starts_with(root));
+    }
+}
+
+/// Rank of a symbol name against a lowercase query; lower is better
+fn match_rank(name: &str, query: &str) -> Option<u8> {
+    let name = name.to_lowercase();
+    if name == query {
+        Some(0)
+    } else if name.starts_with(query) {
+        Some(1)
+    } else if name.contains(query) {
+        Some(2)
+    } else {
+        // Subsequence match, e.g. "hfs" for "HighPerformanceFileSearch"
+        let mut chars = name.chars();
+        query.chars().all(|q| chars.any(|c| c == q)).then_some(3)
+    }
+}
+
+/// Search definitions across all supported source files under `root`
+pub fn search_workspace_symbols(root: &str, query: &str, max_results: usize) -> Vec<SymbolMatch> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let files: Vec<(PathBuf, String)> =
+        WorkspaceWalker::new(root, WalkerConfig::for_content_search())
+            .build()
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+            .filter_map(|entry| {
+                let path = entry.into_path();
+                let lang_id =
+                    CodeNavigationService::get_lang_id_from_path(&path.to_string_lossy())?;
+                let size = path.metadata().ok()?.len();
+                skip_for_search(&path, size, MAX_OUTLINE_FILE_SIZE)
+                    .is_none()
+                    .then_some((path, lang_id))
+            })
+            .collect();
+
+    let mut matches: Vec<(u8, SymbolMatch)> = files
+        .par_iter()
+        .flat_map_iter(|(path, lang_id)| {
+            let symbols = outline_file(path, lang_id).unwrap_or_default();
+            let file_path = path.to_string_lossy().to_string();
+            symbols
+                .iter()
+                .filter_map(|symbol| {
+                    match_rank(&symbol.name, &query).map(|rank| {
+                        (
+                            rank,
+                            SymbolMatch {
+                                file_path: file_path.clone(),
+                                symbol: symbol.clone(),
+                            },
+                        )
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    matches.sort_by(|(rank_a, a), (rank_b, b)| {
+        rank_a
+            .cmp(rank_b)
+            .then_with(|| a.symbol.name.len().cmp(&b.symbol.name.len()))
+            .then_with(|| a.file_path.cmp(&b.file_path))
+            .then_with(|| a.symbol.start_line.cmp(&b.symbol.start_line))
+    });
+    matches.truncate(max_results);
+    matches.into_iter().map(|(_, m)| m).collect()
+}
+
+/// Symbol outline of a single file
+#[tauri::command]
+pub async fn get_file_outline(file_path: String) -> Result<FileOutline, String> {
+    tauri::async_runtime::spawn_blocking(move || -> Result<FileOutline, String> {
+        let language = CodeNavigationService::get_lang_id_from_path(&file_path);
+        let symbols = match &language {
+            Some(lang_id) => outline_file(Path::new(&file_path), lang_id)?.to_vec(),
+            None => Vec::new(),
+        };
+        Ok(FileOutline {
+            file_path,
+            language,
+            symbols,
+        })
+    })
+    .await
+    .map_err(|e| format!("Outline task failed: {}", e))?
+}
+
+/// Find definitions by name across the workspace
+#[tauri::command]
+pub async fn search_symbols(
+    root_path: String,
+    query: String,
+    max_results: Option<usize>,
+) -> Result<Vec<SymbolMatch>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        search_workspace_symbols(
+            &root_path,
+            &query,
+            max_results.unwrap_or(DEFAULT_MAX_SYMBOL_RESULTS),
+        )
+    })
+    .await
+    .map_err(|e| format!("Symbol search task failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn names(symbols: &[OutlineSymbol]) -> Vec<(&str, &str, Option<&str>)> {
+        symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.kind.as_str(), s.container.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_outline() {
+        let code = r#"
+struct Config {
+    name: String,
+}
+
+impl Config {
+    fn new() -> Self {
+        todo!()
+    }
+}
+
+fn main() {}
+"#;
+        let symbols = extract_outline(code, "rust");
+        assert_eq!(
+            names(&symbols),
+            vec![
+                ("Config", "struct", None),
+                ("new", "method", Some("Config")),
+                ("main", "function", None),
+            ]
+        );
+        // Ranges cover the whole definition, not just the name
+        assert_eq!((symbols[0].start_line, symbols[0].end_line), (2, 4));
+        assert_eq!((symbols[1].start_line, symbols[1].end_line), (7, 9));
+    }
+
+    #[test]
+    fn test_python_outline() {
+        let code =
+            "class Greeter:\n    def greet(self):\n        pass\n\ndef helper():\n    pass\n";
+        assert_eq!(
+            names(&extract_outline(code, "python")),
+            vec![
+                ("Greeter", "class", None),
+                ("greet", "method", Some("Greeter")),
+                ("helper", "function", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_typescript_outline_dedups_exports() {
+        let code = "export class Store {\n  load() {}\n}\nexport function createStore() {}\n";
+        assert_eq!(
+            names(&extract_outline(code, "typescript")),
+            vec![
+                ("Store", "class", None),
+                ("load", "method", Some("Store")),
+                ("createStore", "function", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_c_function_range() {
+        let code = "int add(int a, int b) {\n  return a + b;\n}\n";
+        let symbols = extract_outline(code, "c");
+        assert_eq!(names(&symbols), vec![("add", "function", None)]);
+        assert_eq!((symbols[0].start_line, symbols[0].end_line), (1, 3));
+    }
+
+    #[test]
+    fn test_extract_imports() {
+        let code = "use std::fs;\nuse std::{\n    io,\n    path::Path,\n};\n\nfn main() {}\n";
+        assert_eq!(
+            extract_imports(code, "rust"),
+            vec!["use std::fs;", "use std::{ io, path::Path, };"]
+        );
+        let code = "import os\nfrom typing import List\n\ndef main():\n    import sys\n";
+        assert_eq!(
+            extract_imports(code, "python"),
+            vec!["import os", "from typing import List"]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_language() {
+        assert!(extract_outline("anything", "cobol").is_empty());
+    }
+
+    #[test]
+    fn test_match_rank() {
+        assert_eq!(match_rank("Config", "config"), Some(0));
+        assert_eq!(match_rank("ConfigLoader", "config"), Some(1));
+        assert_eq!(match_rank("load_config", "config"), Some(2));
+        assert_eq!(match_rank("HighPerformanceFileSearch", "hpfs"), Some(3));
+        assert_eq!(match_rank("main", "config"), None);
+    }
+
+    #[test]
+    fn test_outline_cache_invalidation() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("lib.rs");
+        fs::write(&file, "fn first() {}\n").unwrap();
+
+        assert_eq!(outline_file(&file, "rust").unwrap()[0].name, "first");
+        assert!(cache().read().unwrap().contains_key(&file));
+
+        invalidate_changes(&[FileSystemChange::Modified { path: file.clone() }]);
+        assert!(!cache().read().unwrap().contains_key(&file));
+
+        fs::write(&file, "fn second() {}\n").unwrap();
+        assert_eq!(outline_file(&file, "rust").unwrap()[0].name, "second");
+
+        invalidate_root(temp_dir.path());
+        assert!(!cache().read().unwrap().contains_key(&file));
+    }
+
+    #[test]
+    fn test_search_workspace_symbols() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("node_modules/pkg")).unwrap();
+        fs::write(
+            temp_dir.path().join("src/store.ts"),
+            "export function loadConfig() {}\nexport class Config {}\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("src/main.py"),
+            "def config_path():\n    pass\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("node_modules/pkg/index.js"),
+            "function Config() {}\n",
+        )
+        .unwrap();
+
+        let results = search_workspace_symbols(temp_dir.path().to_str().unwrap(), "config", 10);
+        let found: Vec<&str> = results.iter().map(|m| m.symbol.name.as_str()).collect();
+        assert_eq!(found, vec!["Config", "config_path", "loadConfig"]);
+        assert!(results[0].file_path.ends_with("store.ts"));
+
+        assert!(search_workspace_symbols(temp_dir.path().to_str().unwrap(), "  ", 10).is_empty());
+    }
+}
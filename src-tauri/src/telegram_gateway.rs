@@ -1,15 +1,21 @@
+use crate::integrations::router::{ChatRouter, ChatRouting};
+use crate::integrations::types::{ChannelType, IncomingMessage};
+use crate::integrations::{IntegrationAdapter, TelegramAdapter};
+use axum::http::HeaderMap;
 use bytes::Bytes;
 use rand::Rng;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tauri::{AppHandle, Manager, Runtime, State};
 use tokio::sync::{watch, Mutex};
 use tokio::time::sleep;
 use uuid::Uuid;
 
+/// Integration ID of the gateway's messages, and key of its access list
+const TELEGRAM_INTEGRATION_ID: &str = "telegram";
 const TELEGRAM_CONFIG_FILE: &str = "telegram-remote.json";
 const TELEGRAM_STATE_FILE: &str = "telegram-remote-state.json";
 const TELEGRAM_ATTACHMENTS_DIR: &str = "attachments";
@@ -27,6 +33,10 @@ pub struct TelegramConfig {
     pub token: String,
     pub allowed_chat_ids: Vec<i64>,
     pub poll_timeout_secs: u64,
+    /// Secret token given to `setWebhook`; webhook deliveries are refused
+    /// while it is empty
+    #[serde(default)]
+    pub webhook_secret: String,
 }
 
 impl Default for TelegramConfig {
@@ -36,6 +46,7 @@ impl Default for TelegramConfig {
             token: String::new(),
             allowed_chat_ids: Vec::new(),
             poll_timeout_secs: DEFAULT_POLL_TIMEOUT_SECS,
+            webhook_secret: String::new(),
         }
     }
 }
@@ -53,19 +64,6 @@ pub struct TelegramRemoteAttachment {
     pub caption: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct TelegramInboundMessage {
-    pub chat_id: i64,
-    pub message_id: i64,
-    pub text: String,
-    pub username: Option<String>,
-    pub first_name: Option<String>,
-    pub last_name: Option<String>,
-    pub date: i64,
-    pub attachments: Option<Vec<TelegramRemoteAttachment>>,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TelegramSendMessageRequest {
@@ -117,7 +115,7 @@ impl TelegramGateway {
     }
 }
 
-type TelegramGatewayState = Arc<Mutex<TelegramGateway>>;
+pub type TelegramGatewayState = Arc<Mutex<TelegramGateway>>;
 
 fn config_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf, String> {
     let app_data_dir = app_handle
@@ -408,80 +406,22 @@ async fn poll_loop(
                                     continue;
                                 }
 
-                                let (text, attachments) = match build_message_payload(
-                                    &client,
-                                    &config.token,
-                                    &message,
-                                    attachments_dir.as_ref(),
-                                )
-                                .await
-                                {
-                                    Ok(payload) => payload,
-                                    Err(error) => {
-                                        log::warn!(
-                                            "[TelegramGateway] Failed to build message payload: {}",
-                                            error
-                                        );
-                                        (String::new(), Vec::new())
-                                    }
-                                };
-
-                                if text.trim().is_empty() && attachments.is_empty() {
-                                    log::debug!(
-                                        "[TelegramGateway] Ignoring empty message chat_id={} message_id={}",
-                                        message.chat.id,
-                                        message.message_id
+                                // Access is checked before attachments are downloaded
+                                let Some(routing) = app_handle.try_state::<ChatRouting>() else {
+                                    log::warn!(
+                                        "[TelegramGateway] Dropping message chat_id={}: the runtime is not ready",
+                                        message.chat.id
                                     );
                                     continue;
-                                }
-
-                                log::debug!(
-                                    "[TelegramGateway] Inbound message chat_id={} message_id={} text_len={} attachments={}",
-                                    message.chat.id,
-                                    message.message_id,
-                                    text.len(),
-                                    attachments.len()
-                                );
-
-                                let payload = TelegramInboundMessage {
-                                    chat_id: message.chat.id,
-                                    message_id: message.message_id,
-                                    text,
-                                    username: message
-                                        .from
-                                        .as_ref()
-                                        .and_then(|user| user.username.clone()),
-                                    first_name: message
-                                        .from
-                                        .as_ref()
-                                        .and_then(|user| user.first_name.clone()),
-                                    last_name: message
-                                        .from
-                                        .as_ref()
-                                        .and_then(|user| user.last_name.clone()),
-                                    date: message.date,
-                                    attachments: if attachments.is_empty() {
-                                        None
-                                    } else {
-                                        Some(attachments)
-                                    },
                                 };
-
-                                match app_handle.emit("telegram-inbound-message", payload) {
-                                    Ok(_) => {
-                                        log::debug!(
-                                            "[TelegramGateway] Emitted inbound message chat_id={} message_id={}",
-                                            message.chat.id,
-                                            message.message_id
-                                        );
-                                    }
-                                    Err(error) => {
-                                        log::error!(
-                                            "[TelegramGateway] Failed to emit message: {}",
-                                            error
-                                        );
-                                    }
-                                }
+                                let router = chat_router(&routing, &config.token);
+                                tauri::async_runtime::spawn(route_message(
+                                    router,
+                                    client.clone(),
+                                    config.token.clone(),
+                                    message,
+                                    attachments_dir.clone(),
+                                ));
                             }
                         }
 
@@ -533,6 +473,176 @@ async fn poll_loop(
     log::info!("[TelegramGateway] Polling loop stopped");
 }
 
+/// Router of the gateway's messages, answering through the bot
+fn chat_router(routing: &ChatRouting, token: &str) -> Arc<ChatRouter> {
+    routing.router(TELEGRAM_INTEGRATION_ID, token, || {
+        Arc::new(TelegramAdapter::new(
+            TELEGRAM_INTEGRATION_ID,
+            crate::integrations::TelegramConfig {
+                bot_token: token.to_string(),
+                webhook_url: None,
+                secret_token: None,
+            },
+        )) as Arc<dyn IntegrationAdapter>
+    })
+}
+
+/// Adapter verifying webhook deliveries, with the token and secret it was
+/// made with; kept so its replay guard remembers earlier deliveries
+static WEBHOOK_ADAPTER: OnceLock<std::sync::Mutex<Option<(String, Arc<TelegramAdapter>)>>> =
+    OnceLock::new();
+
+fn webhook_adapter(config: &TelegramConfig) -> Arc<TelegramAdapter> {
+    let credentials = format!("{}:{}", config.token, config.webhook_secret);
+    let mut cached = WEBHOOK_ADAPTER
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some((made_with, adapter)) = cached.as_ref() {
+        if *made_with == credentials {
+            return adapter.clone();
+        }
+    }
+    let adapter = Arc::new(TelegramAdapter::new(
+        TELEGRAM_INTEGRATION_ID,
+        crate::integrations::TelegramConfig {
+            bot_token: config.token.clone(),
+            webhook_url: None,
+            secret_token: Some(config.webhook_secret.clone()),
+        },
+    ));
+    *cached = Some((credentials, adapter.clone()));
+    adapter
+}
+
+/// Verify a webhook delivery and pass its message on to the agent like a
+/// polled one. The agent's reply is sent once its task finishes.
+pub async fn receive_webhook(
+    app_handle: &AppHandle,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<(), String> {
+    let gateway = app_handle
+        .try_state::<TelegramGatewayState>()
+        .ok_or_else(|| "Telegram gateway is not ready".to_string())?;
+    let config = gateway.lock().await.config.clone();
+    if config.token.is_empty() {
+        return Err("Telegram bot token is not configured".to_string());
+    }
+    let messages = webhook_adapter(&config).receive_webhook(headers, body)?;
+
+    let routing = app_handle
+        .try_state::<ChatRouting>()
+        .ok_or_else(|| "Core runtime is not ready".to_string())?;
+    let router = chat_router(&routing, &config.token);
+    for message in messages {
+        let allowed = message.chat_id.parse::<i64>().is_ok_and(|chat_id| {
+            !is_group_chat(&None, chat_id) && is_chat_allowed(&config, chat_id)
+        });
+        if !allowed {
+            log::debug!(
+                "[TelegramGateway] Ignoring webhook message chat_id={}",
+                message.chat_id
+            );
+            continue;
+        }
+        let router = router.clone();
+        tauri::async_runtime::spawn(async move { router.receive(message).await });
+    }
+    Ok(())
+}
+
+/// A message as the router takes it, before attachments are added
+fn incoming_message(message: &TelegramMessage) -> IncomingMessage {
+    let sender = message.from.as_ref();
+    let content = [message.text.as_ref(), message.caption.as_ref()]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    IncomingMessage {
+        integration_id: TELEGRAM_INTEGRATION_ID.to_string(),
+        channel_type: ChannelType::Telegram,
+        sender_id: sender
+            .and_then(|user| user.id)
+            .unwrap_or(message.chat.id)
+            .to_string(),
+        sender_name: sender.and_then(|user| user.username.clone().or(user.first_name.clone())),
+        chat_id: message.chat.id.to_string(),
+        message_id: message.message_id.to_string(),
+        content,
+        timestamp: message.date * 1000,
+        reply_to: message
+            .reply_to_message
+            .as_ref()
+            .map(|reply| reply.message_id.to_string()),
+        media: Vec::new(),
+    }
+}
+
+/// Pass a message on to the agent if its sender is on the access list,
+/// with the paths of its downloaded attachments
+async fn route_message(
+    router: Arc<ChatRouter>,
+    client: Client,
+    token: String,
+    message: TelegramMessage,
+    attachments_dir: Option<PathBuf>,
+) {
+    let mut incoming = incoming_message(&message);
+    if !router.admit(&incoming).await {
+        log::debug!(
+            "[TelegramGateway] Dropped message chat_id={} message_id={}: sender not allowed",
+            message.chat.id,
+            message.message_id
+        );
+        return;
+    }
+
+    let attachments =
+        match build_message_payload(&client, &token, &message, attachments_dir.as_ref()).await {
+            Ok((_, attachments)) => attachments,
+            Err(error) => {
+                log::warn!(
+                    "[TelegramGateway] Failed to build message payload: {}",
+                    error
+                );
+                Vec::new()
+            }
+        };
+    if incoming.content.trim().is_empty() && attachments.is_empty() {
+        log::debug!(
+            "[TelegramGateway] Ignoring empty message chat_id={} message_id={}",
+            message.chat.id,
+            message.message_id
+        );
+        return;
+    }
+    for attachment in &attachments {
+        incoming.content.push_str(&format!(
+            "\n[Attached {}: {}]",
+            attachment.attachment_type, attachment.file_path
+        ));
+    }
+
+    log::debug!(
+        "[TelegramGateway] Inbound message chat_id={} message_id={} text_len={} attachments={}",
+        message.chat.id,
+        message.message_id,
+        incoming.content.len(),
+        attachments.len()
+    );
+    if let Err(error) = router.handle(incoming).await {
+        log::warn!(
+            "[TelegramGateway] Failed to handle message chat_id={} message_id={}: {}",
+            message.chat.id,
+            message.message_id,
+            error
+        );
+    }
+}
+
 async fn save_attachment_file(
     attachments_dir: &PathBuf,
     filename: &str,
@@ -820,6 +930,7 @@ struct TelegramMessage {
     pub caption: Option<String>,
     pub chat: TelegramChat,
     pub from: Option<TelegramUser>,
+    pub reply_to_message: Option<TelegramReplyTo>,
     pub photo: Option<Vec<TelegramPhotoSize>>,
     pub voice: Option<TelegramVoice>,
     pub audio: Option<TelegramAudio>,
@@ -835,11 +946,17 @@ struct TelegramChat {
 
 #[derive(Debug, Deserialize, Serialize)]
 struct TelegramUser {
+    pub id: Option<i64>,
     pub username: Option<String>,
     pub first_name: Option<String>,
     pub last_name: Option<String>,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+struct TelegramReplyTo {
+    pub message_id: i64,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct TelegramPhotoSize {
     pub file_id: String,
@@ -1067,15 +1184,20 @@ pub struct TelegramGatewayStatus {
 pub async fn telegram_get_status(
     state: State<'_, TelegramGatewayState>,
 ) -> Result<TelegramGatewayStatus, String> {
+    Ok(gateway_status(&state).await)
+}
+
+/// Status of the gateway, also reported by the server's diagnostics
+pub async fn gateway_status(state: &TelegramGatewayState) -> TelegramGatewayStatus {
     let gateway = state.lock().await;
-    Ok(TelegramGatewayStatus {
+    TelegramGatewayStatus {
         running: gateway.running,
         last_update_id: gateway.last_update_id,
         last_poll_at_ms: gateway.last_poll_at_ms,
         last_error: gateway.last_error.clone(),
         last_error_at_ms: gateway.last_error_at_ms,
         backoff_ms: gateway.backoff_ms,
-    })
+    }
 }
 
 #[tauri::command]
@@ -1235,3 +1357,137 @@ mod tests {
         assert_eq!(ids, vec![123, 456]);
     }
 }
+
+#[cfg(test)]
+mod routing_tests {
+    use super::*;
+    use crate::core::LlmClient;
+    use crate::llm::types::{StreamEvent, StreamTextRequest};
+    use crate::server::config::ServerConfig;
+    use crate::server::state::ServerStateFactory;
+    use axum::extract::Path;
+    use axum::Json;
+    use serde_json::{json, Value};
+    use tempfile::TempDir;
+
+    type Sent = Arc<std::sync::Mutex<Vec<Value>>>;
+
+    /// LLM client that answers every request with a fixed text response
+    struct FixedResponseLlm;
+
+    #[async_trait::async_trait]
+    impl LlmClient for FixedResponseLlm {
+        async fn stream(
+            &self,
+            _request: StreamTextRequest,
+            on_event: &mut (dyn FnMut(StreamEvent) + Send),
+        ) -> Result<(), String> {
+            on_event(StreamEvent::TextDelta {
+                text: "Hello from the agent".to_string(),
+            });
+            on_event(StreamEvent::Done {
+                finish_reason: Some("stop".to_string()),
+            });
+            Ok(())
+        }
+    }
+
+    async fn bot_api(
+        axum::extract::State(sent): axum::extract::State<Sent>,
+        Path((_token, method)): Path<(String, String)>,
+        Json(params): Json<Value>,
+    ) -> Json<Value> {
+        match method.as_str() {
+            "sendChatAction" => Json(json!({"ok": true, "result": true})),
+            _ => {
+                if method == "sendMessage" {
+                    sent.lock().unwrap().push(params);
+                }
+                Json(json!({"ok": true, "result": {"message_id": 7}}))
+            }
+        }
+    }
+
+    /// A Bot API server recording the messages sent through it
+    async fn mock_bot_api() -> (String, Sent) {
+        let sent = Sent::default();
+        let app = axum::Router::new()
+            .route("/:token/:method", axum::routing::post(bot_api))
+            .with_state(sent.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, sent)
+    }
+
+    fn message(sender_id: i64, text: &str) -> TelegramMessage {
+        serde_json::from_value(json!({
+            "message_id": 1,
+            "date": 1_700_000_000,
+            "text": text,
+            "chat": {"id": sender_id, "type": "private"},
+            "from": {"id": sender_id, "username": "alice"},
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_routes_allowed_messages_to_the_agent() {
+        let temp_dir = TempDir::new().unwrap();
+        let config =
+            ServerConfig::new(temp_dir.path().to_path_buf(), temp_dir.path().to_path_buf());
+        let (event_tx, _event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let state = ServerStateFactory::create(config, Arc::new(FixedResponseLlm), event_tx)
+            .await
+            .unwrap();
+        let (api_base, sent) = mock_bot_api().await;
+        state
+            .routing
+            .router(TELEGRAM_INTEGRATION_ID, "test_token", || {
+                Arc::new(
+                    TelegramAdapter::new(
+                        TELEGRAM_INTEGRATION_ID,
+                        crate::integrations::TelegramConfig {
+                            bot_token: "test_token".to_string(),
+                            webhook_url: None,
+                            secret_token: None,
+                        },
+                    )
+                    .with_api_base(&api_base),
+                )
+            });
+        let router = chat_router(&state.routing, "test_token");
+
+        // A sender not on the access list gets no answer
+        route_message(
+            router.clone(),
+            Client::new(),
+            "test_token".to_string(),
+            message(1001, "say hello"),
+            None,
+        )
+        .await;
+        assert!(sent.lock().unwrap().is_empty());
+
+        state
+            .routing
+            .access()
+            .allow_user(TELEGRAM_INTEGRATION_ID, "1001")
+            .await
+            .unwrap();
+        route_message(
+            router,
+            Client::new(),
+            "test_token".to_string(),
+            message(1001, "say hello"),
+            None,
+        )
+        .await;
+        // The progress message, then the answer
+        let sent = sent.lock().unwrap().clone();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0]["text"], "Working...");
+        assert_eq!(sent[1]["chat_id"], "1001");
+        assert_eq!(sent[1]["text"], "Hello from the agent");
+    }
+}
@@ -0,0 +1,227 @@
+//! Shared binary detection and size limits for file search and read paths.
+//!
+//! Search and read tools use these helpers so that binary or oversized files are
+//! reported with a structured `SkippedFile` marker instead of lossy garbage,
+//! UTF-8 decoding errors, or multi-megabyte payloads.
+
+use crate::constants::is_binary_extension;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Default per-file size limit for content search
+pub const DEFAULT_MAX_SEARCH_FILE_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Bytes inspected when sniffing file content
+const SNIFF_LEN: usize = 8 * 1024;
+
+/// Why a file was not searched or returned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "camelCase")]
+pub enum SkippedFile {
+    Binary {
+        path: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    TooLarge {
+        path: String,
+        size: u64,
+        max_size: u64,
+    },
+}
+
+impl SkippedFile {
+    pub fn message(&self) -> String {
+        match self {
+            Self::Binary { path } => format!("Binary file skipped: {}", path),
+            Self::TooLarge {
+                path,
+                size,
+                max_size,
+            } => format!(
+                "File too large, skipped: {} ({} bytes, max: {})",
+                path, size, max_size
+            ),
+        }
+    }
+}
+
+/// Content of a file read as text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextContent {
+    Text(String),
+    Skipped(SkippedFile),
+}
+
+/// Heuristic binary check over the leading bytes of a file: any NUL byte, or
+/// non-UTF-8 data dominated by control characters.
+pub fn is_binary_content(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(SNIFF_LEN)];
+    if sample.contains(&0) {
+        return true;
+    }
+
+    match std::str::from_utf8(sample) {
+        Ok(_) => false,
+        // The sample may end in the middle of a multi-byte character
+        Err(e) if e.error_len().is_none() => false,
+        Err(_) => {
+            // Legacy 8-bit encodings are text; control characters are not
+            let control = sample
+                .iter()
+                .filter(|&&b| b < 0x09 || (0x0e..0x20).contains(&b) || b == 0x7f)
+                .count();
+            control * 10 > sample.len()
+        }
+    }
+}
+
+fn skip_by_size(path: &Path, size: u64, max_size: u64) -> Option<SkippedFile> {
+    (size > max_size).then(|| SkippedFile::TooLarge {
+        path: path.to_string_lossy().to_string(),
+        size,
+        max_size,
+    })
+}
+
+/// Cheap pre-filter for search walkers, based on size and extension only.
+/// Content sniffing is left to the searcher, which reads the file anyway.
+pub fn skip_for_search(path: &Path, size: u64, max_size: u64) -> Option<SkippedFile> {
+    if let Some(skipped) = skip_by_size(path, size, max_size) {
+        return Some(skipped);
+    }
+
+    let binary_extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| is_binary_extension(&ext.to_lowercase()));
+    binary_extension.then(|| SkippedFile::Binary {
+        path: path.to_string_lossy().to_string(),
+    })
+}
+
+/// Read a file as text, returning a `SkippedFile` marker for binary or oversized files.
+/// Binary detection looks at the content, so text formats such as SVG are still readable.
+/// Text that is not valid UTF-8 is decoded lossily.
+pub fn read_text_file(path: &Path, max_size: u64) -> io::Result<TextContent> {
+    let metadata = std::fs::metadata(path)?;
+    if let Some(skipped) = skip_by_size(path, metadata.len(), max_size) {
+        return Ok(TextContent::Skipped(skipped));
+    }
+
+    let mut bytes = Vec::with_capacity(metadata.len() as usize);
+    // The size limit also bounds files that grow between the metadata call and the read
+    File::open(path)?
+        .take(max_size + 1)
+        .read_to_end(&mut bytes)?;
+
+    if bytes.len() as u64 > max_size {
+        return Ok(TextContent::Skipped(SkippedFile::TooLarge {
+            path: path.to_string_lossy().to_string(),
+            size: bytes.len() as u64,
+            max_size,
+        }));
+    }
+    if is_binary_content(&bytes) {
+        return Ok(TextContent::Skipped(SkippedFile::Binary {
+            path: path.to_string_lossy().to_string(),
+        }));
+    }
+
+    let text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    };
+    Ok(TextContent::Text(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_is_binary_content() {
+        assert!(!is_binary_content(b"fn main() {}\n"));
+        assert!(!is_binary_content("héllo wörld".as_bytes()));
+        assert!(is_binary_content(b"PK\x03\x04\x00\x00"));
+        // Latin-1 text is not valid UTF-8 but is still text
+        assert!(!is_binary_content(b"caf\xe9 cr\xe8me"));
+        assert!(is_binary_content(b"\x01\x02\x03\x04\xff\xfe\x05\x06"));
+        // A multi-byte character cut at the sniff boundary is not binary
+        let mut text = "a".repeat(SNIFF_LEN - 1).into_bytes();
+        text.extend("é".as_bytes());
+        assert!(!is_binary_content(&text));
+    }
+
+    #[test]
+    fn test_read_text_file() {
+        let dir = TempDir::new().unwrap();
+        let text = dir.path().join("notes.txt");
+        let binary = dir.path().join("blob.dat");
+        let svg = dir.path().join("logo.svg");
+        let large = dir.path().join("large.txt");
+        fs::write(&text, "hello").unwrap();
+        fs::write(&binary, b"\x00\x01\x02").unwrap();
+        fs::write(&svg, "<svg></svg>").unwrap();
+        fs::write(&large, "x".repeat(64)).unwrap();
+
+        assert_eq!(
+            read_text_file(&text, 1024).unwrap(),
+            TextContent::Text("hello".to_string())
+        );
+        assert!(matches!(
+            read_text_file(&binary, 1024).unwrap(),
+            TextContent::Skipped(SkippedFile::Binary { .. })
+        ));
+        // Extensions alone never hide text content from reads
+        assert_eq!(
+            read_text_file(&svg, 1024).unwrap(),
+            TextContent::Text("<svg></svg>".to_string())
+        );
+        assert_eq!(
+            read_text_file(&large, 32).unwrap(),
+            TextContent::Skipped(SkippedFile::TooLarge {
+                path: large.to_string_lossy().to_string(),
+                size: 64,
+                max_size: 32,
+            })
+        );
+    }
+
+    #[test]
+    fn test_skip_for_search() {
+        assert!(skip_for_search(Path::new("/repo/src/main.rs"), 100, 1024).is_none());
+        assert_eq!(
+            skip_for_search(Path::new("/repo/logo.PNG"), 100, 1024),
+            Some(SkippedFile::Binary {
+                path: "/repo/logo.PNG".to_string()
+            })
+        );
+        assert!(matches!(
+            skip_for_search(Path::new("/repo/dump.sql"), 2048, 1024),
+            Some(SkippedFile::TooLarge { size: 2048, .. })
+        ));
+    }
+
+    #[test]
+    fn test_skipped_file_serialization() {
+        let skipped = SkippedFile::TooLarge {
+            path: "/repo/big.log".to_string(),
+            size: 10,
+            max_size: 5,
+        };
+        assert_eq!(
+            serde_json::to_value(&skipped).unwrap(),
+            serde_json::json!({
+                "reason": "tooLarge",
+                "path": "/repo/big.log",
+                "size": 10,
+                "maxSize": 5
+            })
+        );
+    }
+}
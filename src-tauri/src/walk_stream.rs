@@ -0,0 +1,494 @@
+//! Streaming, cancellable directory walks.
+//!
+//! `list_project_files` and `search_files_by_glob` collect everything before returning,
+//! which blocks for seconds on huge trees. The commands here run the same walker presets
+//! but push entries to the frontend in batches over a Tauri channel, and stop as soon as
+//! `cancel_walk_stream` is called with the stream's ID.
+
+use crate::constants::is_binary_extension;
+use crate::glob::HighPerformanceGlob;
+use crate::walker::{validate_path_in_workspace, WalkerConfig, WorkspaceWalker};
+use ignore::{WalkParallel, WalkState};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tauri::ipc::Channel;
+
+/// Entries per streamed batch
+const BATCH_SIZE: usize = 200;
+/// Flush a partial batch when no new entries arrived for this long
+const BATCH_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+const DEFAULT_MAX_LIST_ENTRIES: usize = 50_000;
+const DEFAULT_MAX_GLOB_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalkEntry {
+    pub path: String,
+    /// `/`-separated path relative to the walk root
+    pub relative_path: String,
+    pub is_directory: bool,
+    pub size: u64,
+    /// Seconds since the Unix epoch
+    pub modified_time: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalkSummary {
+    pub total: usize,
+    /// The walk was stopped by `cancel_walk_stream`
+    pub cancelled: bool,
+    /// The walk stopped at the entry limit
+    pub truncated: bool,
+    pub elapsed_ms: u64,
+}
+
+/// Messages sent over the stream channel; `done` is always the last one
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum WalkStreamEvent {
+    Batch { entries: Vec<WalkEntry> },
+    Done(WalkSummary),
+}
+
+/// Shared flag checked by walker threads between entries
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+fn active_streams() -> &'static Mutex<HashMap<String, CancellationToken>> {
+    static STREAMS: OnceLock<Mutex<HashMap<String, CancellationToken>>> = OnceLock::new();
+    STREAMS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Registers a stream's token for the duration of the walk
+struct StreamRegistration {
+    stream_id: String,
+    token: CancellationToken,
+}
+
+impl StreamRegistration {
+    fn register(stream_id: &str) -> Result<Self, String> {
+        let mut streams = active_streams()
+            .lock()
+            .map_err(|e| format!("Failed to lock walk streams: {}", e))?;
+        if streams.contains_key(stream_id) {
+            return Err(format!("Walk stream already running: {}", stream_id));
+        }
+        let token = CancellationToken::default();
+        streams.insert(stream_id.to_string(), token.clone());
+        Ok(Self {
+            stream_id: stream_id.to_string(),
+            token,
+        })
+    }
+}
+
+impl Drop for StreamRegistration {
+    fn drop(&mut self) {
+        if let Ok(mut streams) = active_streams().lock() {
+            streams.remove(&self.stream_id);
+        }
+    }
+}
+
+fn normalize_seps(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Run a parallel walk, passing accepted entries to `on_batch` in batches until the walk
+/// finishes, `max_entries` is reached, or `token` is cancelled.
+pub fn stream_walk<F>(
+    walker: WalkParallel,
+    root: &Path,
+    accept: F,
+    max_entries: usize,
+    token: &CancellationToken,
+    mut on_batch: impl FnMut(Vec<WalkEntry>),
+) -> WalkSummary
+where
+    F: Fn(&Path, &str, bool) -> bool + Sync,
+{
+    let start = Instant::now();
+    let max_entries = max_entries.max(1);
+    let found = AtomicUsize::new(0);
+    let truncated = AtomicBool::new(false);
+    let (tx, rx) = channel::<WalkEntry>();
+
+    let mut total = 0;
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            walker.run(|| {
+                let tx = tx.clone();
+                let accept = &accept;
+                let found = &found;
+                let truncated = &truncated;
+                Box::new(move |result| {
+                    if token.is_cancelled() || truncated.load(Ordering::Relaxed) {
+                        return WalkState::Quit;
+                    }
+
+                    let entry = match result {
+                        Ok(entry) if entry.depth() > 0 => entry,
+                        _ => return WalkState::Continue,
+                    };
+                    let Some(file_type) = entry.file_type() else {
+                        return WalkState::Continue;
+                    };
+                    let is_directory = file_type.is_dir();
+                    let path = entry.path();
+                    let relative_path = normalize_seps(path.strip_prefix(root).unwrap_or(path));
+                    if !accept(path, &relative_path, is_directory) {
+                        return WalkState::Continue;
+                    }
+
+                    if found.fetch_add(1, Ordering::Relaxed) >= max_entries {
+                        truncated.store(true, Ordering::Relaxed);
+                        return WalkState::Quit;
+                    }
+
+                    let metadata = entry.metadata().ok();
+                    let walk_entry = WalkEntry {
+                        path: path.to_string_lossy().to_string(),
+                        relative_path,
+                        is_directory,
+                        size: metadata.as_ref().map(|m| m.len()).unwrap_or(0),
+                        modified_time: metadata
+                            .and_then(|m| m.modified().ok())
+                            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                            .map(|d| d.as_secs())
+                            .unwrap_or(0),
+                    };
+                    if tx.send(walk_entry).is_err() {
+                        return WalkState::Quit;
+                    }
+                    WalkState::Continue
+                })
+            });
+            drop(tx);
+        });
+
+        // Collector forwards batches while the walkers run
+        let mut batch: Vec<WalkEntry> = Vec::with_capacity(BATCH_SIZE);
+        loop {
+            match rx.recv_timeout(BATCH_FLUSH_INTERVAL) {
+                Ok(entry) => {
+                    batch.push(entry);
+                    if batch.len() >= BATCH_SIZE {
+                        total += batch.len();
+                        on_batch(std::mem::take(&mut batch));
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if !batch.is_empty() {
+                        total += batch.len();
+                        on_batch(std::mem::take(&mut batch));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        // Entries that raced with a cancellation are dropped rather than delivered late
+        if !batch.is_empty() && !token.is_cancelled() {
+            total += batch.len();
+            on_batch(batch);
+        }
+    });
+
+    WalkSummary {
+        total,
+        cancelled: token.is_cancelled(),
+        truncated: truncated.load(Ordering::Relaxed),
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    }
+}
+
+/// Run `walk` on a blocking thread under `stream_id`, forwarding batches and the final
+/// summary to `on_event`.
+async fn run_stream<W>(
+    stream_id: String,
+    on_event: Channel<WalkStreamEvent>,
+    walk: W,
+) -> Result<WalkSummary, String>
+where
+    W: FnOnce(&CancellationToken, &mut dyn FnMut(Vec<WalkEntry>)) -> WalkSummary + Send + 'static,
+{
+    let registration = StreamRegistration::register(&stream_id)?;
+
+    let summary = tauri::async_runtime::spawn_blocking(move || {
+        let token = registration.token.clone();
+        let mut send_batch = |entries: Vec<WalkEntry>| {
+            // The frontend went away; stop walking instead of sending into the void
+            if on_event.send(WalkStreamEvent::Batch { entries }).is_err() {
+                token.cancel();
+            }
+        };
+        let summary = walk(&registration.token, &mut send_batch);
+        if let Err(e) = on_event.send(WalkStreamEvent::Done(summary.clone())) {
+            log::warn!("Failed to send walk stream completion: {}", e);
+        }
+        summary
+    })
+    .await
+    .map_err(|e| format!("Walk stream task failed: {}", e))?;
+
+    log::info!(
+        "Walk stream {} sent {} entries in {}ms (cancelled: {}, truncated: {})",
+        stream_id,
+        summary.total,
+        summary.elapsed_ms,
+        summary.cancelled,
+        summary.truncated
+    );
+    Ok(summary)
+}
+
+/// Streaming variant of `list_project_files`
+#[tauri::command]
+pub async fn stream_list_files(
+    directory_path: String,
+    recursive: Option<bool>,
+    max_depth: Option<usize>,
+    max_entries: Option<usize>,
+    stream_id: String,
+    on_event: Channel<WalkStreamEvent>,
+) -> Result<WalkSummary, String> {
+    let root = PathBuf::from(&directory_path);
+    if !root.is_dir() {
+        return Err("Directory does not exist".into());
+    }
+
+    let depth = if recursive.unwrap_or(false) {
+        max_depth
+    } else {
+        Some(1)
+    };
+    let max_entries = max_entries.unwrap_or(DEFAULT_MAX_LIST_ENTRIES);
+
+    run_stream(stream_id, on_event, move |token, on_batch| {
+        let config = WalkerConfig::for_list_files().with_max_depth(depth);
+        let walker = WorkspaceWalker::new(&directory_path, config).build_parallel();
+        let accept = |path: &Path, _: &str, is_directory: bool| {
+            is_directory
+                || !path
+                    .extension()
+                    .and_then(OsStr::to_str)
+                    .is_some_and(is_binary_extension)
+        };
+        stream_walk(walker, &root, accept, max_entries, token, on_batch)
+    })
+    .await
+}
+
+/// Streaming variant of `search_files_by_glob`. Entries arrive in walk order; unlike
+/// the collecting command they are not sorted by modification time.
+#[tauri::command]
+pub async fn stream_glob(
+    root_path: String,
+    pattern: String,
+    max_entries: Option<usize>,
+    stream_id: String,
+    on_event: Channel<WalkStreamEvent>,
+) -> Result<WalkSummary, String> {
+    let root = PathBuf::from(&root_path);
+    if !root.is_dir() {
+        return Err(format!("Directory does not exist: {}", root_path));
+    }
+    if pattern.trim().is_empty() {
+        return Err("Glob pattern cannot be empty".into());
+    }
+
+    let pattern = pattern.replace('\\', "/");
+    let max_entries = max_entries.unwrap_or(DEFAULT_MAX_GLOB_ENTRIES);
+
+    run_stream(stream_id, on_event, move |token, on_batch| {
+        let glob = HighPerformanceGlob::new();
+        let walker =
+            WorkspaceWalker::new(&root_path, WalkerConfig::for_glob(&root_path)).build_parallel();
+        let accept = |path: &Path, relative_path: &str, _: bool| {
+            glob.glob_match(relative_path, &pattern) && validate_path_in_workspace(path, &root)
+        };
+        stream_walk(walker, &root, accept, max_entries, token, on_batch)
+    })
+    .await
+}
+
+/// Cancel a running walk stream. Returns `false` if no stream with this ID is running.
+#[tauri::command]
+pub fn cancel_walk_stream(stream_id: String) -> Result<bool, String> {
+    let streams = active_streams()
+        .lock()
+        .map_err(|e| format!("Failed to lock walk streams: {}", e))?;
+    Ok(match streams.get(&stream_id) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn create_test_directory(files: usize) -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/nested")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("node_modules/pkg")).unwrap();
+        for i in 0..files {
+            fs::write(temp_dir.path().join(format!("src/file{}.ts", i)), "").unwrap();
+        }
+        fs::write(temp_dir.path().join("src/nested/deep.rs"), "").unwrap();
+        fs::write(temp_dir.path().join("src/logo.png"), "").unwrap();
+        fs::write(temp_dir.path().join("node_modules/pkg/index.js"), "").unwrap();
+        temp_dir
+    }
+
+    fn walk_all(
+        root: &Path,
+        config: WalkerConfig,
+        accept: impl Fn(&Path, &str, bool) -> bool + Sync,
+        max_entries: usize,
+        token: &CancellationToken,
+    ) -> (Vec<Vec<WalkEntry>>, WalkSummary) {
+        let walker = WorkspaceWalker::new(root.to_str().unwrap(), config).build_parallel();
+        let mut batches = Vec::new();
+        let summary = stream_walk(walker, root, accept, max_entries, token, |batch| {
+            batches.push(batch)
+        });
+        (batches, summary)
+    }
+
+    #[test]
+    fn test_stream_walk_batches_all_entries() {
+        let temp_dir = create_test_directory(450);
+        let (batches, summary) = walk_all(
+            temp_dir.path(),
+            WalkerConfig::for_list_files(),
+            |_, _, is_directory| !is_directory,
+            usize::MAX,
+            &CancellationToken::default(),
+        );
+
+        let mut paths: Vec<String> = batches
+            .iter()
+            .flatten()
+            .map(|e| e.relative_path.clone())
+            .collect();
+        paths.sort();
+        // 450 .ts files, deep.rs and logo.png; node_modules is excluded
+        assert_eq!(paths.len(), 452);
+        assert!(paths.contains(&"src/nested/deep.rs".to_string()));
+        assert!(!paths.iter().any(|p| p.starts_with("node_modules")));
+        assert!(batches.len() >= 3);
+        assert!(batches.iter().all(|b| b.len() <= BATCH_SIZE));
+        assert_eq!(summary.total, 452);
+        assert!(!summary.cancelled);
+        assert!(!summary.truncated);
+    }
+
+    #[test]
+    fn test_stream_walk_respects_limit() {
+        let temp_dir = create_test_directory(50);
+        let (batches, summary) = walk_all(
+            temp_dir.path(),
+            WalkerConfig::for_list_files(),
+            |_, _, _| true,
+            10,
+            &CancellationToken::default(),
+        );
+
+        assert_eq!(batches.iter().map(Vec::len).sum::<usize>(), 10);
+        assert_eq!(summary.total, 10);
+        assert!(summary.truncated);
+    }
+
+    #[test]
+    fn test_stream_walk_cancelled_before_start() {
+        let temp_dir = create_test_directory(50);
+        let token = CancellationToken::default();
+        token.cancel();
+        let (batches, summary) = walk_all(
+            temp_dir.path(),
+            WalkerConfig::for_list_files(),
+            |_, _, _| true,
+            usize::MAX,
+            &token,
+        );
+
+        assert!(batches.is_empty());
+        assert_eq!(summary.total, 0);
+        assert!(summary.cancelled);
+    }
+
+    #[test]
+    fn test_stream_walk_glob_filter() {
+        let temp_dir = create_test_directory(5);
+        let root = temp_dir.path().to_path_buf();
+        let glob = HighPerformanceGlob::new();
+        let (batches, _) = walk_all(
+            &root,
+            WalkerConfig::for_glob(root.to_str().unwrap()),
+            |_, relative_path, _| glob.glob_match(relative_path, "**/*.rs"),
+            usize::MAX,
+            &CancellationToken::default(),
+        );
+
+        let paths: Vec<&str> = batches
+            .iter()
+            .flatten()
+            .map(|e| e.relative_path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["src/nested/deep.rs"]);
+    }
+
+    #[test]
+    fn test_stream_registration() {
+        let registration = StreamRegistration::register("walk-test").unwrap();
+        assert!(StreamRegistration::register("walk-test").is_err());
+
+        assert!(cancel_walk_stream("walk-test".to_string()).unwrap());
+        assert!(registration.token.is_cancelled());
+
+        drop(registration);
+        assert!(!cancel_walk_stream("walk-test".to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_walk_stream_event_serialization() {
+        let done = WalkStreamEvent::Done(WalkSummary {
+            total: 3,
+            cancelled: false,
+            truncated: true,
+            elapsed_ms: 12,
+        });
+        assert_eq!(
+            serde_json::to_value(&done).unwrap(),
+            serde_json::json!({
+                "event": "done",
+                "total": 3,
+                "cancelled": false,
+                "truncated": true,
+                "elapsedMs": 12
+            })
+        );
+    }
+}
@@ -8,8 +8,9 @@
 //! - **Canonical Path Validation**: Validates that paths stay within the workspace
 //! - **Configurable Presets**: Ready-to-use configurations for file search, content search, glob, and directory listing
 //! - **Shared Exclusion Logic**: Centralized directory exclusion handling
+//! - **Project Ignore File**: `.talkcodyignore` patterns apply to every preset, independent of .gitignore
 
-use crate::constants::{should_exclude_dir, DEFAULT_MAX_DEPTH};
+use crate::constants::{should_exclude_dir, DEFAULT_MAX_DEPTH, TALKCODY_IGNORE_FILENAME};
 use ignore::{Walk, WalkBuilder, WalkParallel};
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
@@ -31,6 +32,8 @@ pub struct WalkerConfig {
     pub workspace_root: Option<PathBuf>,
     /// Additional directories to exclude (on top of defaults)
     pub additional_excludes: Vec<String>,
+    /// Respect .talkcodyignore files. Default: `true`
+    pub respect_talkcodyignore: bool,
 }
 
 impl Default for WalkerConfig {
@@ -43,6 +46,7 @@ impl Default for WalkerConfig {
             allow_github_dir: false,
             workspace_root: None,
             additional_excludes: Vec::new(),
+            respect_talkcodyignore: true,
         }
     }
 }
@@ -67,6 +71,7 @@ impl WalkerConfig {
             allow_github_dir: true, // Allow .github for CI/CD files
             workspace_root: None,
             additional_excludes: Vec::new(),
+            respect_talkcodyignore: true,
         }
     }
 
@@ -83,6 +88,7 @@ impl WalkerConfig {
             allow_github_dir: false,
             workspace_root: None,
             additional_excludes: Vec::new(),
+            respect_talkcodyignore: true,
         }
     }
 
@@ -99,6 +105,7 @@ impl WalkerConfig {
             allow_github_dir: false,
             workspace_root: Some(PathBuf::from(workspace_root)),
             additional_excludes: Vec::new(),
+            respect_talkcodyignore: true,
         }
     }
 
@@ -115,6 +122,7 @@ impl WalkerConfig {
             allow_github_dir: false,
             workspace_root: None,
             additional_excludes: Vec::new(),
+            respect_talkcodyignore: true,
         }
     }
 
@@ -162,6 +170,13 @@ impl WalkerConfig {
         self
     }
 
+    /// Set respect_talkcodyignore option.
+    #[allow(dead_code)]
+    pub fn with_talkcodyignore(mut self, respect: bool) -> Self {
+        self.respect_talkcodyignore = respect;
+        self
+    }
+
     /// Add additional directories to exclude.
     pub fn with_additional_excludes(mut self, excludes: Vec<String>) -> Self {
         self.additional_excludes = excludes;
@@ -200,6 +215,14 @@ impl WorkspaceWalker {
             builder.standard_filters(false);
         }
 
+        // .talkcodyignore is independent of the gitignore setting. Parent directories are
+        // still consulted so walks rooted in a subdirectory see the project-level file.
+        if config.respect_talkcodyignore {
+            builder
+                .add_custom_ignore_filename(TALKCODY_IGNORE_FILENAME)
+                .parents(true);
+        }
+
         Self { builder, config }
     }
 
@@ -542,4 +565,75 @@ mod tests {
 
         assert!(!found_custom, "custom_exclude directory should be excluded");
     }
+
+    fn create_talkcodyignore_directory() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("generated/api")).unwrap();
+        fs::write(
+            temp_dir.path().join(".talkcodyignore"),
+            "generated/\n*.snap\n",
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(temp_dir.path().join("src/main.snap"), "snapshot").unwrap();
+        fs::write(temp_dir.path().join("generated/api/client.ts"), "").unwrap();
+
+        temp_dir
+    }
+
+    fn walked_paths(root: &Path, config: WalkerConfig) -> Vec<String> {
+        WorkspaceWalker::new(root.to_str().unwrap(), config)
+            .build()
+            .flatten()
+            .map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_presets_respect_talkcodyignore() {
+        let temp_dir = create_talkcodyignore_directory();
+        let root = temp_dir.path();
+
+        for config in [
+            WalkerConfig::for_file_search(),
+            WalkerConfig::for_content_search(),
+            WalkerConfig::for_glob(root.to_str().unwrap()),
+            WalkerConfig::for_list_files(),
+        ] {
+            assert!(config.respect_talkcodyignore);
+            let paths = walked_paths(root, config);
+            assert!(paths.contains(&"src/main.rs".to_string()));
+            assert!(!paths.iter().any(|p| p.starts_with("generated")));
+            assert!(!paths.contains(&"src/main.snap".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_talkcodyignore_applies_from_parent_directory() {
+        let temp_dir = create_talkcodyignore_directory();
+        let src = temp_dir.path().join("src");
+
+        let paths = walked_paths(&src, WalkerConfig::for_content_search());
+        assert!(paths.contains(&"main.rs".to_string()));
+        assert!(!paths.contains(&"main.snap".to_string()));
+    }
+
+    #[test]
+    fn test_talkcodyignore_can_be_disabled() {
+        let temp_dir = create_talkcodyignore_directory();
+
+        let config = WalkerConfig::for_file_search().with_talkcodyignore(false);
+        let paths = walked_paths(temp_dir.path(), config);
+        assert!(paths.contains(&"generated/api/client.ts".to_string()));
+        assert!(paths.contains(&"src/main.snap".to_string()));
+    }
 }
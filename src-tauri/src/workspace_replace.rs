@@ -0,0 +1,792 @@
+//! Workspace-wide search and replace.
+//!
+//! `replace_in_workspace` runs in two steps. Without a `previewId` it walks the workspace
+//! with `WorkspaceWalker::build_parallel`, computes every replacement in memory and returns
+//! a preview (per-line before/after and match counts) without touching the disk. Called
+//! again with the returned `previewId`, it writes exactly the previewed changes: all files
+//! are verified against the content they had at preview time, written to temp files, then
+//! renamed into place, and any failure restores the files already replaced. The result
+//! carries a unified diff that reverts the change (`git apply` from the workspace root).
+
+use crate::file_watcher::PauseGuard;
+use crate::text_file::{is_binary_content, skip_for_search, DEFAULT_MAX_SEARCH_FILE_SIZE};
+use crate::walker::{WalkerConfig, WorkspaceWalker};
+use ignore::WalkState;
+use regex::{NoExpand, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Lines of unchanged context around each hunk of the undo patch
+const PATCH_CONTEXT_LINES: usize = 3;
+/// Previews that were not applied within this window are discarded
+const PREVIEW_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Options for `replace_in_workspace`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReplaceOptions {
+    /// Treat the pattern as a regular expression; the replacement may then use `$1`/`${name}`
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+    /// Only match on word boundaries
+    pub whole_word: bool,
+    /// Restrict the replacement to these file extensions
+    pub file_types: Option<Vec<String>>,
+    /// Directory names excluded in addition to the defaults
+    pub exclude_dirs: Vec<String>,
+    /// Files larger than this (in bytes) are skipped
+    pub max_file_size: u64,
+    /// Stop collecting after this many changed files
+    pub max_files: usize,
+    /// Apply a previously returned preview instead of computing a new one
+    pub preview_id: Option<String>,
+}
+
+impl Default for ReplaceOptions {
+    fn default() -> Self {
+        Self {
+            is_regex: false,
+            case_sensitive: false,
+            whole_word: false,
+            file_types: None,
+            exclude_dirs: Vec::new(),
+            max_file_size: DEFAULT_MAX_SEARCH_FILE_SIZE,
+            max_files: 500,
+            preview_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineChange {
+    /// 1-based line number in the original file
+    pub line_number: usize,
+    pub before: String,
+    /// May span several lines when the replacement contains newlines
+    pub after: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileReplacePreview {
+    pub path: String,
+    pub match_count: usize,
+    pub lines: Vec<LineChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplacePreview {
+    pub preview_id: String,
+    pub files: Vec<FileReplacePreview>,
+    pub total_matches: usize,
+    /// More files matched than `maxFiles`; only the listed files will be changed
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceApplied {
+    pub files_changed: usize,
+    pub total_matches: usize,
+    /// Unified diff that reverts the change, relative to the workspace root
+    pub undo_patch: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum ReplaceResult {
+    Preview(ReplacePreview),
+    Applied(ReplaceApplied),
+}
+
+/// One line of the original file and, if it changed, its replacement.
+/// Both keep their line terminator.
+#[derive(Debug, Clone)]
+struct Segment {
+    original: String,
+    replaced: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct PlannedFile {
+    path: PathBuf,
+    relative_path: String,
+    original_hash: String,
+    segments: Vec<Segment>,
+    match_count: usize,
+}
+
+impl PlannedFile {
+    fn new_content(&self) -> String {
+        self.segments
+            .iter()
+            .map(|s| s.replaced.as_deref().unwrap_or(&s.original))
+            .collect()
+    }
+
+    fn preview(&self) -> FileReplacePreview {
+        let lines = self
+            .segments
+            .iter()
+            .enumerate()
+            .filter_map(|(i, segment)| {
+                segment.replaced.as_ref().map(|replaced| LineChange {
+                    line_number: i + 1,
+                    before: trim_terminator(&segment.original).to_string(),
+                    after: trim_terminator(replaced).to_string(),
+                })
+            })
+            .collect();
+        FileReplacePreview {
+            path: self.path.to_string_lossy().to_string(),
+            match_count: self.match_count,
+            lines,
+        }
+    }
+}
+
+struct StoredPreview {
+    root: PathBuf,
+    files: Vec<PlannedFile>,
+    created_at: Instant,
+}
+
+fn previews() -> &'static Mutex<HashMap<String, StoredPreview>> {
+    static PREVIEWS: OnceLock<Mutex<HashMap<String, StoredPreview>>> = OnceLock::new();
+    PREVIEWS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn trim_terminator(line: &str) -> &str {
+    line.trim_end_matches(['\n', '\r'])
+}
+
+fn content_hash(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn build_regex(pattern: &str, options: &ReplaceOptions) -> Result<Regex, String> {
+    let pattern = if options.is_regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+    let pattern = if options.whole_word {
+        format!(r"\b(?:{})\b", pattern)
+    } else {
+        pattern
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid search pattern: {}", e))
+}
+
+/// Replace matches line by line. Returns `None` when nothing matched.
+fn plan_content(
+    content: &str,
+    regex: &Regex,
+    replacement: &str,
+    expand: bool,
+) -> Option<(Vec<Segment>, usize)> {
+    let mut match_count = 0;
+    let mut changed = false;
+    let segments = content
+        .split_inclusive('\n')
+        .map(|line| {
+            let body = trim_terminator(line);
+            let count = regex.find_iter(body).count();
+            if count == 0 {
+                return Segment {
+                    original: line.to_string(),
+                    replaced: None,
+                };
+            }
+
+            match_count += count;
+            let new_body = if expand {
+                regex.replace_all(body, replacement)
+            } else {
+                regex.replace_all(body, NoExpand(replacement))
+            };
+            let replaced = match new_body {
+                Cow::Borrowed(_) => None,
+                Cow::Owned(new_body) if new_body == body => None,
+                Cow::Owned(new_body) => Some(format!("{}{}", new_body, &line[body.len()..])),
+            };
+            changed |= replaced.is_some();
+            Segment {
+                original: line.to_string(),
+                replaced,
+            }
+        })
+        .collect();
+
+    changed.then_some((segments, match_count))
+}
+
+/// Walk the workspace and plan replacements for every matching file
+fn plan_workspace(
+    root: &Path,
+    regex: &Regex,
+    replacement: &str,
+    options: &ReplaceOptions,
+) -> (Vec<PlannedFile>, bool) {
+    let file_types: Option<HashSet<String>> = options
+        .file_types
+        .as_ref()
+        .map(|types| types.iter().map(|t| t.to_lowercase()).collect());
+    let max_files = options.max_files.max(1);
+    let expand = options.is_regex;
+
+    let config =
+        WalkerConfig::for_content_search().with_additional_excludes(options.exclude_dirs.clone());
+    let walker = WorkspaceWalker::new(&root.to_string_lossy(), config).build_parallel();
+
+    let found = AtomicUsize::new(0);
+    let truncated = AtomicBool::new(false);
+    let (tx, rx) = channel::<PlannedFile>();
+
+    walker.run(|| {
+        let tx = tx.clone();
+        let file_types = &file_types;
+        let found = &found;
+        let truncated = &truncated;
+        Box::new(move |result| {
+            if truncated.load(Ordering::Relaxed) {
+                return WalkState::Quit;
+            }
+            let entry = match result {
+                Ok(entry) => entry,
+                Err(_) => return WalkState::Continue,
+            };
+            if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+                return WalkState::Continue;
+            }
+
+            let path = entry.path();
+            if let Some(types) = file_types {
+                let matches_type = path
+                    .extension()
+                    .and_then(OsStr::to_str)
+                    .is_some_and(|ext| types.contains(&ext.to_lowercase()));
+                if !matches_type {
+                    return WalkState::Continue;
+                }
+            }
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            if skip_for_search(path, size, options.max_file_size).is_some() {
+                return WalkState::Continue;
+            }
+
+            let Ok(bytes) = fs::read(path) else {
+                return WalkState::Continue;
+            };
+            if is_binary_content(&bytes) {
+                return WalkState::Continue;
+            }
+            // Never rewrite files that are not valid UTF-8
+            let Ok(content) = std::str::from_utf8(&bytes) else {
+                return WalkState::Continue;
+            };
+            let Some((segments, match_count)) = plan_content(content, regex, replacement, expand)
+            else {
+                return WalkState::Continue;
+            };
+
+            if found.fetch_add(1, Ordering::Relaxed) >= max_files {
+                truncated.store(true, Ordering::Relaxed);
+                return WalkState::Quit;
+            }
+            let planned = PlannedFile {
+                path: path.to_path_buf(),
+                relative_path: path
+                    .strip_prefix(root)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+                original_hash: content_hash(&bytes),
+                segments,
+                match_count,
+            };
+            if tx.send(planned).is_err() {
+                return WalkState::Quit;
+            }
+            WalkState::Continue
+        })
+    });
+    drop(tx);
+
+    let mut files: Vec<PlannedFile> = rx.into_iter().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    (files, truncated.load(Ordering::Relaxed))
+}
+
+fn push_lines(out: &mut String, prefix: char, text: &str) {
+    for line in text.split_inclusive('\n') {
+        out.push(prefix);
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+fn line_count(text: &str) -> usize {
+    text.split_inclusive('\n').count()
+}
+
+fn hunk_range(start_before: usize, count: usize) -> String {
+    // An empty range points at the line before it
+    let start = if count == 0 {
+        start_before
+    } else {
+        start_before + 1
+    };
+    format!("{},{}", start, count)
+}
+
+/// Unified diff for one planned file. With `reverse` the diff goes from the replaced
+/// content back to the original, i.e. it undoes the replacement.
+fn unified_diff(file: &PlannedFile, reverse: bool) -> String {
+    let sides = |segment: &Segment| -> (String, String) {
+        let original = segment.original.clone();
+        let replaced = segment.replaced.clone().unwrap_or_else(|| original.clone());
+        if reverse {
+            (replaced, original)
+        } else {
+            (original, replaced)
+        }
+    };
+
+    let changed: Vec<usize> = file
+        .segments
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.replaced.is_some())
+        .map(|(i, _)| i)
+        .collect();
+
+    // Group changes whose context windows touch into a single hunk
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for &index in &changed {
+        match groups.last_mut() {
+            Some((_, last)) if index - *last <= PATCH_CONTEXT_LINES * 2 + 1 => *last = index,
+            _ => groups.push((index, index)),
+        }
+    }
+
+    let mut out = format!(
+        "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n",
+        file.relative_path
+    );
+    for (first, last) in groups {
+        let start = first.saturating_sub(PATCH_CONTEXT_LINES);
+        let end = (last + 1 + PATCH_CONTEXT_LINES).min(file.segments.len());
+
+        let (mut from_before, mut to_before) = (0, 0);
+        for segment in &file.segments[..start] {
+            let (from, to) = sides(segment);
+            from_before += line_count(&from);
+            to_before += line_count(&to);
+        }
+
+        let mut body = String::new();
+        let (mut from_count, mut to_count) = (0, 0);
+        for segment in &file.segments[start..end] {
+            let (from, to) = sides(segment);
+            from_count += line_count(&from);
+            to_count += line_count(&to);
+            if segment.replaced.is_some() {
+                push_lines(&mut body, '-', &from);
+                push_lines(&mut body, '+', &to);
+            } else {
+                push_lines(&mut body, ' ', &from);
+            }
+        }
+
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(from_before, from_count),
+            hunk_range(to_before, to_count)
+        ));
+        out.push_str(&body);
+    }
+    out
+}
+
+fn temp_path_for(path: &Path, preview_id: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.replace-tmp", name, preview_id))
+}
+
+/// Write all planned files or none of them
+fn apply_planned(files: &[PlannedFile], preview_id: &str) -> Result<(), String> {
+    // Refuse to overwrite anything edited since the preview was computed
+    let mut originals = Vec::with_capacity(files.len());
+    for file in files {
+        let bytes = fs::read(&file.path)
+            .map_err(|e| format!("Failed to read {}: {}", file.path.display(), e))?;
+        if content_hash(&bytes) != file.original_hash {
+            return Err(format!(
+                "File changed since preview: {}. Run the replacement again to refresh the preview.",
+                file.path.display()
+            ));
+        }
+        originals.push(bytes);
+    }
+
+    let mut temps: Vec<PathBuf> = Vec::with_capacity(files.len());
+    let cleanup = |temps: &[PathBuf]| {
+        for temp in temps {
+            let _ = fs::remove_file(temp);
+        }
+    };
+    for file in files {
+        let temp = temp_path_for(&file.path, preview_id);
+        let written = fs::write(&temp, file.new_content()).and_then(|_| {
+            let permissions = fs::metadata(&file.path)?.permissions();
+            fs::set_permissions(&temp, permissions)
+        });
+        temps.push(temp);
+        if let Err(e) = written {
+            cleanup(&temps);
+            return Err(format!("Failed to write {}: {}", file.path.display(), e));
+        }
+    }
+
+    for (index, (file, temp)) in files.iter().zip(&temps).enumerate() {
+        if let Err(e) = fs::rename(temp, &file.path) {
+            // Put back the files that were already replaced
+            for (restored, original) in files[..index].iter().zip(&originals) {
+                if let Err(restore_err) = fs::write(&restored.path, original) {
+                    log::error!(
+                        "Failed to restore {} after aborted replace: {}",
+                        restored.path.display(),
+                        restore_err
+                    );
+                }
+            }
+            cleanup(&temps[index..]);
+            return Err(format!("Failed to replace {}: {}", file.path.display(), e));
+        }
+    }
+    Ok(())
+}
+
+fn store_preview(root: PathBuf, files: Vec<PlannedFile>) -> Result<String, String> {
+    let preview_id = uuid::Uuid::new_v4().simple().to_string();
+    let mut store = previews()
+        .lock()
+        .map_err(|e| format!("Failed to lock replace previews: {}", e))?;
+    store.retain(|_, preview| preview.created_at.elapsed() < PREVIEW_TTL);
+    store.insert(
+        preview_id.clone(),
+        StoredPreview {
+            root,
+            files,
+            created_at: Instant::now(),
+        },
+    );
+    Ok(preview_id)
+}
+
+fn take_preview(preview_id: &str, root: &Path) -> Result<StoredPreview, String> {
+    let mut store = previews()
+        .lock()
+        .map_err(|e| format!("Failed to lock replace previews: {}", e))?;
+    let preview = store
+        .remove(preview_id)
+        .filter(|preview| preview.created_at.elapsed() < PREVIEW_TTL)
+        .ok_or_else(|| format!("Replace preview not found or expired: {}", preview_id))?;
+    if preview.root != root {
+        return Err(format!(
+            "Replace preview {} belongs to a different workspace",
+            preview_id
+        ));
+    }
+    Ok(preview)
+}
+
+/// Compute a preview, or apply the preview named in `options.preview_id`
+pub fn replace_workspace(
+    root: &str,
+    pattern: &str,
+    replacement: &str,
+    options: &ReplaceOptions,
+) -> Result<ReplaceResult, String> {
+    let root = PathBuf::from(root);
+    if !root.is_dir() {
+        return Err(format!("Directory does not exist: {}", root.display()));
+    }
+
+    if let Some(preview_id) = &options.preview_id {
+        let preview = take_preview(preview_id, &root)?;
+        apply_planned(&preview.files, preview_id)?;
+
+        let undo_patch = preview
+            .files
+            .iter()
+            .map(|file| unified_diff(file, true))
+            .collect();
+        return Ok(ReplaceResult::Applied(ReplaceApplied {
+            files_changed: preview.files.len(),
+            total_matches: preview.files.iter().map(|f| f.match_count).sum(),
+            undo_patch,
+        }));
+    }
+
+    if pattern.is_empty() {
+        return Err("Search pattern cannot be empty".into());
+    }
+    let regex = build_regex(pattern, options)?;
+    let (files, truncated) = plan_workspace(&root, &regex, replacement, options);
+
+    let file_previews: Vec<FileReplacePreview> = files.iter().map(PlannedFile::preview).collect();
+    let total_matches = files.iter().map(|f| f.match_count).sum();
+    let preview_id = store_preview(root, files)?;
+    Ok(ReplaceResult::Preview(ReplacePreview {
+        preview_id,
+        files: file_previews,
+        total_matches,
+        truncated,
+    }))
+}
+
+/// Search and replace across a workspace. The first call returns a preview; passing its
+/// `previewId` back in `options` applies exactly the previewed changes.
+#[tauri::command]
+pub async fn replace_in_workspace(
+    root: String,
+    pattern: String,
+    replacement: String,
+    options: Option<ReplaceOptions>,
+) -> Result<ReplaceResult, String> {
+    let options = options.unwrap_or_default();
+    // Applying rewrites many files at once; emit one refresh afterwards
+    let _pause = options.preview_id.is_some().then(PauseGuard::acquire);
+    tauri::async_runtime::spawn_blocking(move || {
+        replace_workspace(&root, &pattern, &replacement, &options)
+    })
+    .await
+    .map_err(|e| format!("Replace task failed: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_test_directory() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("node_modules/pkg")).unwrap();
+        fs::write(
+            temp_dir.path().join("src/a.ts"),
+            "const oldName = 1;\nconsole.log(oldName);\n",
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("src/b.ts"), "// nothing here\n").unwrap();
+        fs::write(temp_dir.path().join("src/c.rs"), "fn oldName() {}").unwrap();
+        fs::write(temp_dir.path().join("src/blob.bin"), b"oldName\x00\x01").unwrap();
+        fs::write(temp_dir.path().join("node_modules/pkg/index.js"), "oldName").unwrap();
+        temp_dir
+    }
+
+    fn preview(
+        root: &Path,
+        pattern: &str,
+        replacement: &str,
+        options: ReplaceOptions,
+    ) -> ReplacePreview {
+        match replace_workspace(root.to_str().unwrap(), pattern, replacement, &options).unwrap() {
+            ReplaceResult::Preview(preview) => preview,
+            ReplaceResult::Applied(_) => panic!("expected a preview"),
+        }
+    }
+
+    fn apply(root: &Path, preview_id: &str) -> Result<ReplaceApplied, String> {
+        let options = ReplaceOptions {
+            preview_id: Some(preview_id.to_string()),
+            ..Default::default()
+        };
+        match replace_workspace(root.to_str().unwrap(), "", "", &options)? {
+            ReplaceResult::Applied(applied) => Ok(applied),
+            ReplaceResult::Preview(_) => panic!("expected an applied result"),
+        }
+    }
+
+    #[test]
+    fn test_preview_does_not_modify_files() {
+        let temp_dir = create_test_directory();
+        let preview = preview(
+            temp_dir.path(),
+            "oldName",
+            "newName",
+            ReplaceOptions::default(),
+        );
+
+        let paths: Vec<&str> = preview.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].ends_with("a.ts"));
+        assert!(paths[1].ends_with("c.rs"));
+        assert_eq!(preview.total_matches, 3);
+        assert!(!preview.truncated);
+        assert_eq!(
+            preview.files[0].lines[1],
+            LineChange {
+                line_number: 2,
+                before: "console.log(oldName);".to_string(),
+                after: "console.log(newName);".to_string(),
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("src/a.ts")).unwrap(),
+            "const oldName = 1;\nconsole.log(oldName);\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_and_undo_patch() {
+        let temp_dir = create_test_directory();
+        let preview = preview(
+            temp_dir.path(),
+            "oldName",
+            "newName",
+            ReplaceOptions::default(),
+        );
+        let applied = apply(temp_dir.path(), &preview.preview_id).unwrap();
+
+        assert_eq!(applied.files_changed, 2);
+        assert_eq!(applied.total_matches, 3);
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("src/a.ts")).unwrap(),
+            "const newName = 1;\nconsole.log(newName);\n"
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("src/c.rs")).unwrap(),
+            "fn newName() {}"
+        );
+        assert_eq!(
+            applied.undo_patch,
+            "diff --git a/src/a.ts b/src/a.ts\n--- a/src/a.ts\n+++ b/src/a.ts\n\
+             @@ -1,2 +1,2 @@\n\
+             -const newName = 1;\n+const oldName = 1;\n\
+             -console.log(newName);\n+console.log(oldName);\n\
+             diff --git a/src/c.rs b/src/c.rs\n--- a/src/c.rs\n+++ b/src/c.rs\n\
+             @@ -1,1 +1,1 @@\n\
+             -fn newName() {}\n\\ No newline at end of file\n\
+             +fn oldName() {}\n\\ No newline at end of file\n"
+        );
+        // Untouched files are left alone and no temp files remain
+        assert_eq!(
+            fs::read(temp_dir.path().join("src/blob.bin")).unwrap(),
+            b"oldName\x00\x01"
+        );
+        assert_eq!(
+            fs::read_dir(temp_dir.path().join("src")).unwrap().count(),
+            4
+        );
+
+        // A preview can only be applied once
+        assert!(apply(temp_dir.path(), &preview.preview_id).is_err());
+    }
+
+    #[test]
+    fn test_apply_rejects_files_changed_since_preview() {
+        let temp_dir = create_test_directory();
+        let preview = preview(
+            temp_dir.path(),
+            "oldName",
+            "newName",
+            ReplaceOptions::default(),
+        );
+        fs::write(temp_dir.path().join("src/c.rs"), "fn oldName() { edited }").unwrap();
+
+        let err = apply(temp_dir.path(), &preview.preview_id).unwrap_err();
+        assert!(err.contains("changed since preview"));
+        // Nothing was written, including files that were still unchanged
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("src/a.ts")).unwrap(),
+            "const oldName = 1;\nconsole.log(oldName);\n"
+        );
+    }
+
+    #[test]
+    fn test_regex_replacement_with_captures() {
+        let options = ReplaceOptions {
+            is_regex: true,
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let regex = build_regex(r"(\w+)Name", &options).unwrap();
+        let (segments, count) =
+            plan_content("oldName newName\r\nrest\n", &regex, "${1}_name", true).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            segments[0].replaced.as_deref(),
+            Some("old_name new_name\r\n")
+        );
+        assert!(segments[1].replaced.is_none());
+    }
+
+    #[test]
+    fn test_literal_replacement_is_not_expanded() {
+        let options = ReplaceOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        let regex = build_regex("foo", &options).unwrap();
+        let (segments, count) = plan_content("Foo foobar foo\n", &regex, "$1", false).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(segments[0].replaced.as_deref(), Some("$1 foobar $1\n"));
+        assert!(plan_content("foobar\n", &regex, "x", false).is_none());
+    }
+
+    #[test]
+    fn test_undo_patch_hunks_with_context() {
+        let content: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        let regex = build_regex("line 10", &ReplaceOptions::default()).unwrap();
+        let (segments, _) = plan_content(&content, &regex, "ten\nTEN", false).unwrap();
+        let file = PlannedFile {
+            path: PathBuf::from("/w/notes.txt"),
+            relative_path: "notes.txt".to_string(),
+            original_hash: String::new(),
+            segments,
+            match_count: 1,
+        };
+
+        assert_eq!(
+            unified_diff(&file, false),
+            "diff --git a/notes.txt b/notes.txt\n--- a/notes.txt\n+++ b/notes.txt\n\
+             @@ -7,7 +7,8 @@\n line 7\n line 8\n line 9\n-line 10\n+ten\n+TEN\n line 11\n line 12\n line 13\n"
+        );
+        assert!(unified_diff(&file, true).contains("@@ -7,8 +7,7 @@\n"));
+    }
+
+    #[test]
+    fn test_preview_respects_max_files() {
+        let temp_dir = create_test_directory();
+        let options = ReplaceOptions {
+            max_files: 1,
+            ..Default::default()
+        };
+        let preview = preview(temp_dir.path(), "oldName", "newName", options);
+        assert_eq!(preview.files.len(), 1);
+        assert!(preview.truncated);
+    }
+}
@@ -120,6 +120,12 @@ export function LspDownloadPrompt() {
                       <span>Downloading...</span>
                     </>
                   )}
+                  {progress.status === 'verifying' && (
+                    <>
+                      <Loader2 className="h-3 w-3 animate-spin" />
+                      <span>Verifying checksum...</span>
+                    </>
+                  )}
                   {progress.status === 'extracting' && (
                     <>
                       <Loader2 className="h-3 w-3 animate-spin" />
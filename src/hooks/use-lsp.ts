@@ -14,6 +14,7 @@ import {
   getLspLanguageIdForPath,
   getServerConfig,
   hasLspSupport,
+  loadCustomServers,
 } from '@/services/lsp/lsp-servers';
 import { type PendingDownload, useLspStore } from '@/stores/lsp-store';
 
@@ -83,6 +84,8 @@ export function useLsp({
   const [error, setError] = useState<string | null>(null);
   const [serverId, setServerId] = useState<string | null>(null);
   const [workspaceRoot, setWorkspaceRoot] = useState<string | null>(null);
+  // Bumped after user-defined servers are reloaded, so the language below is recomputed
+  const [, setCustomServersVersion] = useState(0);
 
   const { enabled: storeEnabled, setDiagnostics, addPendingDownload } = useLspStore();
   const isEnabled = enabled && storeEnabled;
@@ -98,6 +101,23 @@ export function useLsp({
   // Get language for the current file (server key, e.g., 'typescript' for both .ts and .tsx)
   const language = filePath ? getLanguageIdForPath(filePath) : null;
 
+  // Pick up edits to ~/.talkcody/lsp-servers.json when switching files
+  useEffect(() => {
+    if (!filePath) {
+      return;
+    }
+
+    let cancelled = false;
+    loadCustomServers().then(() => {
+      if (!cancelled) {
+        setCustomServersVersion((version) => version + 1);
+      }
+    });
+    return () => {
+      cancelled = true;
+    };
+  }, [filePath]);
+
   // Compute the correct workspace root based on rootPatterns (async)
   useEffect(() => {
     if (!filePath || !language || !rootPath) {
@@ -2,9 +2,11 @@ import { listen } from '@tauri-apps/api/event';
 import { useCallback, useEffect, useRef } from 'react';
 import { logger } from '@/lib/logger';
 import { fastDirectoryTreeService } from '@/services/fast-directory-tree-service';
+import { lspService } from '@/services/lsp/lsp-service';
 import { WindowManagerService } from '@/services/window-manager-service';
 import { useGitStore } from '@/stores/git-store';
 import { useRepositoryStore } from '@/stores/window-scoped-repository-store';
+import type { GitStatusChange } from '@/types/git';
 
 const GIT_STATUS_DEBOUNCE_DELAY = 300; // ms
 const FILE_TREE_DEBOUNCE_DELAY = 200; // ms
@@ -151,7 +153,8 @@ export function useRepositoryWatcher() {
     });
 
     // Listen for git status changes (from .git directory watcher)
-    const unlistenGitStatus = listen('git-status-changed', () => {
+    const unlistenGitStatus = listen<GitStatusChange | null>('git-status-changed', (event) => {
+      logger.debug('Git status changed:', event.payload?.kinds, event.payload?.refs);
       debouncedRefreshGitStatus();
     });
 
@@ -184,4 +187,14 @@ export function useRepositoryWatcher() {
     debouncedRefreshFileTree,
     debouncedRefreshGitStatusForFileChange,
   ]);
+
+  // Shut down language servers when the project is closed or switched
+  useEffect(() => {
+    if (!rootPath) {
+      return;
+    }
+    return () => {
+      lspService.stopAllForRoot(rootPath).catch(logger.error);
+    };
+  }, [rootPath]);
 }
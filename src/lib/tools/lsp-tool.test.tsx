@@ -26,6 +26,7 @@ const mockImplementation = vi.hoisted(() => vi.fn());
 const mockPrepareCallHierarchy = vi.hoisted(() => vi.fn());
 const mockIncomingCalls = vi.hoisted(() => vi.fn());
 const mockOutgoingCalls = vi.hoisted(() => vi.fn());
+const mockTypeHierarchy = vi.hoisted(() => vi.fn());
 
 vi.mock('@tauri-apps/plugin-fs', () => ({
   exists: mockExists,
@@ -53,6 +54,7 @@ vi.mock('@/services/lsp/lsp-servers', () => ({
   getServerConfig: mockGetServerConfig,
   findWorkspaceRoot: mockFindWorkspaceRoot,
   getLspLanguageIdForPath: mockGetLspLanguageIdForPath,
+  loadCustomServers: vi.fn(),
 }));
 
 vi.mock('@/services/lsp/lsp-connection-manager', () => ({
@@ -77,6 +79,7 @@ vi.mock('@/services/lsp/lsp-service', () => ({
     prepareCallHierarchy: mockPrepareCallHierarchy,
     incomingCalls: mockIncomingCalls,
     outgoingCalls: mockOutgoingCalls,
+    typeHierarchy: mockTypeHierarchy,
     getServerStatus: mockGetServerStatus,
     decrementRefCount: vi.fn(),
   },
@@ -197,6 +200,47 @@ describe('lspTool', () => {
     expect(result.success).toBe(true);
   });
 
+  it('resolves incoming calls at the 0-based position', async () => {
+    mockIncomingCalls.mockResolvedValue([{ from: { name: 'caller' }, fromRanges: [] }]);
+
+    const result = await lspTool.execute(
+      {
+        operation: 'incomingCalls',
+        filePath: 'src/index.ts',
+        line: 3,
+        character: 5,
+      },
+      baseContext
+    );
+
+    expect(mockIncomingCalls).toHaveBeenCalledWith('server-1', '/repo/src/index.ts', 2, 4);
+    expect(mockPrepareCallHierarchy).not.toHaveBeenCalled();
+    expect(result.success).toBe(true);
+  });
+
+  it('passes the type hierarchy direction from the operation', async () => {
+    mockTypeHierarchy.mockResolvedValue([{ name: 'Base' }]);
+
+    const result = await lspTool.execute(
+      {
+        operation: 'supertypes',
+        filePath: 'src/index.ts',
+        line: 1,
+        character: 1,
+      },
+      baseContext
+    );
+
+    expect(mockTypeHierarchy).toHaveBeenCalledWith(
+      'server-1',
+      '/repo/src/index.ts',
+      0,
+      0,
+      'supertypes'
+    );
+    expect(result.success).toBe(true);
+  });
+
   it('treats empty results as success', async () => {
     mockDocumentSymbol.mockResolvedValue([]);
 
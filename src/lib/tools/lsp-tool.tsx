@@ -6,10 +6,6 @@ import { createTool } from '@/lib/create-tool';
 import { logger } from '@/lib/logger';
 import { getLocale, type SupportedLocale } from '@/locales';
 import { lspConnectionManager } from '@/services/lsp/lsp-connection-manager';
-import type {
-  CallHierarchyIncomingCall,
-  CallHierarchyOutgoingCall,
-} from '@/services/lsp/lsp-protocol';
 import {
   findWorkspaceRoot,
   getLanguageDisplayName,
@@ -17,6 +13,7 @@ import {
   getLspLanguageIdForPath,
   getServerConfig,
   hasLspSupport,
+  loadCustomServers,
 } from '@/services/lsp/lsp-servers';
 import { lspService } from '@/services/lsp/lsp-service';
 import { repositoryService } from '@/services/repository-service';
@@ -34,6 +31,8 @@ const operations = [
   'prepareCallHierarchy',
   'incomingCalls',
   'outgoingCalls',
+  'supertypes',
+  'subtypes',
 ] as const;
 
 type LspOperation = (typeof operations)[number];
@@ -46,6 +45,8 @@ const positionRequiredOperations = new Set<LspOperation>([
   'prepareCallHierarchy',
   'incomingCalls',
   'outgoingCalls',
+  'supertypes',
+  'subtypes',
 ]);
 
 function requiresPosition(operation: LspOperation): boolean {
@@ -73,41 +74,9 @@ function isEmptyResult(result: unknown): boolean {
   return false;
 }
 
-async function resolveCallHierarchy(
-  serverId: string,
-  filePath: string,
-  line: number,
-  character: number,
-  direction: 'incoming' | 'outgoing'
-): Promise<CallHierarchyIncomingCall[] | CallHierarchyOutgoingCall[] | null> {
-  const items = await lspService.prepareCallHierarchy(serverId, filePath, line, character);
-  if (!items || items.length === 0) {
-    return [];
-  }
-
-  const results = await Promise.all(
-    items.map(async (item) => {
-      if (direction === 'incoming') {
-        return lspService.incomingCalls(serverId, item);
-      }
-      return lspService.outgoingCalls(serverId, item);
-    })
-  );
-
-  const flattened = results
-    .flat()
-    .filter((entry): entry is CallHierarchyIncomingCall | CallHierarchyOutgoingCall => !!entry);
-
-  if (direction === 'incoming') {
-    return flattened.filter((entry): entry is CallHierarchyIncomingCall => 'from' in entry);
-  }
-
-  return flattened.filter((entry): entry is CallHierarchyOutgoingCall => 'to' in entry);
-}
-
 export const lspTool = createTool({
   name: 'lsp',
-  description: `Perform Language Server Protocol (LSP) operations like go-to-definition, references, hover, and symbols.
+  description: `Perform Language Server Protocol (LSP) operations like go-to-definition, references, hover, symbols, and call/type hierarchies.
 
 Provide a file path and a 1-based line/character position as shown in editors.`,
   inputSchema: z.object({
@@ -166,6 +135,8 @@ Provide a file path and a 1-based line/character position as shown in editors.`,
         };
       }
 
+      // Pick up edits to ~/.talkcody/lsp-servers.json
+      await loadCustomServers();
       language = getLanguageIdForPath(resolvedPath);
       if (!language || !hasLspSupport(language)) {
         return {
@@ -266,21 +237,19 @@ Provide a file path and a 1-based line/character position as shown in editors.`,
           );
           break;
         case 'incomingCalls':
-          data = await resolveCallHierarchy(
-            serverId,
-            resolvedPath,
-            lspLine,
-            lspCharacter,
-            'incoming'
-          );
+          data = await lspService.incomingCalls(serverId, resolvedPath, lspLine, lspCharacter);
           break;
         case 'outgoingCalls':
-          data = await resolveCallHierarchy(
+          data = await lspService.outgoingCalls(serverId, resolvedPath, lspLine, lspCharacter);
+          break;
+        case 'supertypes':
+        case 'subtypes':
+          data = await lspService.typeHierarchy(
             serverId,
             resolvedPath,
             lspLine,
             lspCharacter,
-            'outgoing'
+            operation
           );
           break;
         default:
@@ -213,6 +213,14 @@ export class LlmClient {
     await invoke('llm_set_setting', { key, value });
   }
 
+  async getApiKeys(): Promise<Record<string, string>> {
+    return invoke<Record<string, string>>('llm_get_api_keys');
+  }
+
+  async getCustomProviderApiKey(providerId: string): Promise<string | null> {
+    return invoke<string | null>('llm_get_custom_provider_api_key', { providerId });
+  }
+
   async startClaudeOAuth(): Promise<{ url: string; verifier: string; state: string }> {
     return invoke('llm_claude_oauth_start');
   }
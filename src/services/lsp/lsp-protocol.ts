@@ -283,6 +283,14 @@ export interface CallHierarchyOutgoingCall {
   fromRanges: Range[];
 }
 
+// ============================================================================
+// LSP Type Hierarchy
+// ============================================================================
+
+export type TypeHierarchyItem = CallHierarchyItem;
+
+export type TypeHierarchyDirection = 'supertypes' | 'subtypes';
+
 // ============================================================================
 // LSP Initialize
 // ============================================================================
@@ -344,6 +352,9 @@ export interface ClientCapabilities {
     symbol?: {
       dynamicRegistration?: boolean;
     };
+    didChangeWatchedFiles?: {
+      dynamicRegistration?: boolean;
+    };
   };
 }
 
@@ -13,7 +13,13 @@ vi.mock('@tauri-apps/api/path', () => ({
   dirname: mockDirname,
 }));
 
-import { findWorkspaceRoot } from './lsp-servers';
+import {
+  findWorkspaceRoot,
+  getLanguageIdForPath,
+  getLspLanguageIdForPath,
+  hasLspSupport,
+  setCustomServers,
+} from './lsp-servers';
 
 const ROOT_PATTERNS = ['package.json'];
 
@@ -72,3 +78,26 @@ describe('findWorkspaceRoot', () => {
     expect(root).toBe('/repo/apps/web');
   });
 });
+
+describe('custom LSP servers', () => {
+  beforeEach(() => {
+    setCustomServers({});
+  });
+
+  it('resolves user-defined extensions before built-in ones', () => {
+    expect(getLanguageIdForPath('/repo/main.zig')).toBeNull();
+
+    setCustomServers({
+      zls: { command: 'zls', args: [], languages: ['zig'], extensions: ['.zig', '.ZON'] },
+      deno: { command: 'deno', args: ['lsp'], languages: ['deno'], extensions: ['.ts'] },
+    });
+
+    expect(getLanguageIdForPath('/repo/main.zig')).toBe('zig');
+    expect(getLanguageIdForPath('/repo/build.zig.zon')).toBe('zig');
+    expect(getLspLanguageIdForPath('/repo/main.zig')).toBe('zig');
+    expect(getLanguageIdForPath('/repo/mod.ts')).toBe('deno');
+    expect(getLanguageIdForPath('/repo/app.tsx')).toBe('typescript');
+    expect(hasLspSupport('zig')).toBe(true);
+    expect(hasLspSupport('haskell')).toBe(false);
+  });
+});
@@ -5,8 +5,10 @@
 // When adding a new language, only modify LSP_SERVERS - all other
 // mappings are generated automatically.
 
+import { invoke } from '@tauri-apps/api/core';
 import { dirname, normalize } from '@tauri-apps/api/path';
 import { exists } from '@tauri-apps/plugin-fs';
+import { logger } from '@/lib/logger';
 
 /**
  * Extension-specific LSP language ID mapping
@@ -252,6 +254,64 @@ export const SERVER_DISPLAY_NAMES: Readonly<Record<string, string>> = Object.fre
   )
 );
 
+// ============================================================================
+// User-defined Servers (~/.talkcody/lsp-servers.json)
+// ============================================================================
+
+/**
+ * User-defined LSP server, as returned by lsp_list_custom_servers
+ */
+export interface CustomLspServerConfig {
+  command: string;
+  args: string[];
+  /** Language IDs served by this server */
+  languages: string[];
+  extensions: string[];
+}
+
+/**
+ * Extension to language mapping of user-defined servers, consulted before
+ * the built-in ones (lowercase extension -> first language of the server)
+ */
+let customExtensionToLanguage: Record<string, string> = {};
+let customLanguages: Set<string> = new Set();
+
+/**
+ * Replace the user-defined servers used by the lookups below
+ */
+export function setCustomServers(servers: Record<string, CustomLspServerConfig>): void {
+  const extensionToLanguage: Record<string, string> = {};
+  const languages = new Set<string>();
+  for (const server of Object.values(servers)) {
+    const language = server.languages[0];
+    if (!language) {
+      continue;
+    }
+    for (const lang of server.languages) {
+      languages.add(lang);
+    }
+    for (const ext of server.extensions) {
+      extensionToLanguage[ext.toLowerCase()] ??= language;
+    }
+  }
+  customExtensionToLanguage = extensionToLanguage;
+  customLanguages = languages;
+}
+
+/**
+ * Reload the user-defined servers from the backend, which rereads the config
+ * file only when it changed
+ */
+export async function loadCustomServers(): Promise<void> {
+  try {
+    setCustomServers(
+      await invoke<Record<string, CustomLspServerConfig>>('lsp_list_custom_servers')
+    );
+  } catch (error) {
+    logger.warn(`[LSP] Failed to load custom LSP servers: ${error}`);
+  }
+}
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
@@ -261,7 +321,7 @@ export const SERVER_DISPLAY_NAMES: Readonly<Record<string, string>> = Object.fre
  */
 export function getLanguageIdForExtension(extension: string): string | null {
   const ext = extension.startsWith('.') ? extension : `.${extension}`;
-  return EXTENSION_TO_SERVER[ext] ?? null;
+  return customExtensionToLanguage[ext.toLowerCase()] ?? EXTENSION_TO_SERVER[ext] ?? null;
 }
 
 /**
@@ -269,7 +329,7 @@ export function getLanguageIdForExtension(extension: string): string | null {
  */
 export function getLanguageIdForPath(filePath: string): string | null {
   const ext = filePath.substring(filePath.lastIndexOf('.'));
-  return EXTENSION_TO_SERVER[ext] ?? null;
+  return customExtensionToLanguage[ext.toLowerCase()] ?? EXTENSION_TO_SERVER[ext] ?? null;
 }
 
 /**
@@ -283,7 +343,7 @@ export function getServerConfig(language: string): LspServerConfig | null {
  * Check if a language has LSP support
  */
 export function hasLspSupport(language: string): boolean {
-  return language in LSP_SERVERS;
+  return language in LSP_SERVERS || customLanguages.has(language);
 }
 
 /**
@@ -307,7 +367,7 @@ export function monacoToLspLanguage(monacoLanguage: string): string | null {
  */
 export function getLspLanguageIdForPath(filePath: string): string | null {
   const ext = filePath.substring(filePath.lastIndexOf('.'));
-  return EXTENSION_TO_LSP_LANGUAGE_ID[ext] ?? null;
+  return customExtensionToLanguage[ext.toLowerCase()] ?? EXTENSION_TO_LSP_LANGUAGE_ID[ext] ?? null;
 }
 
 /**
@@ -28,6 +28,8 @@ import {
   type TextDocumentContentChangeEvent,
   type TextDocumentItem,
   type TextDocumentPositionParams,
+  type TypeHierarchyDirection,
+  type TypeHierarchyItem,
   type VersionedTextDocumentIdentifier,
 } from './lsp-protocol';
 import { getServerConfig } from './lsp-servers';
@@ -47,6 +49,11 @@ interface LspMessageEvent {
   message: string;
 }
 
+interface LspServerStoppedEvent {
+  serverId: string;
+  reason: string;
+}
+
 interface LspServerStatus {
   available: boolean;
   installed: boolean;
@@ -57,7 +64,7 @@ interface LspServerStatus {
 
 interface LspDownloadProgress {
   language: string;
-  status: 'downloading' | 'extracting' | 'completed' | 'error';
+  status: 'downloading' | 'verifying' | 'extracting' | 'completed' | 'error';
   progress?: number; // 0.0 - 1.0
   message?: string;
 }
@@ -94,6 +101,7 @@ class LspService {
   private pendingRequests: Map<number, PendingRequest> = new Map();
   private messageId = 0;
   private unlistenFn: UnlistenFn | null = null;
+  private unlistenStoppedFn: UnlistenFn | null = null;
   private initialized = false;
 
   private diagnosticsCallbacks: Set<DiagnosticsCallback> = new Set();
@@ -124,6 +132,12 @@ class LspService {
       this.handleMessage(event.payload);
     });
 
+    // The backend stops idle servers on its own; forget them so they get restarted on demand
+    this.unlistenStoppedFn = await listen<LspServerStoppedEvent>('lsp-server-stopped', (event) => {
+      logger.info(`[LSP] Server ${event.payload.serverId} stopped by backend: ${event.payload.reason}`);
+      this.forgetServer(event.payload.serverId);
+    });
+
     this.initialized = true;
     logger.info('[LSP] LSP service initialized');
   }
@@ -144,6 +158,10 @@ class LspService {
       this.unlistenFn();
       this.unlistenFn = null;
     }
+    if (this.unlistenStoppedFn) {
+      this.unlistenStoppedFn();
+      this.unlistenStoppedFn = null;
+    }
 
     // Clear pending requests
     for (const [_id, pending] of this.pendingRequests) {
@@ -315,6 +333,36 @@ class LspService {
     this.servers.delete(serverId);
   }
 
+  /**
+   * Stop all servers for a project root (called when the project is closed)
+   */
+  async stopAllForRoot(rootPath: string): Promise<void> {
+    const stopped = await invoke<string[]>('lsp_stop_all_for_root', { rootPath });
+    for (const serverId of stopped) {
+      this.forgetServer(serverId);
+    }
+  }
+
+  /**
+   * Drop local state for a server that is no longer running
+   */
+  private forgetServer(serverId: string): void {
+    for (const [id, pending] of this.pendingRequests) {
+      if (pending.serverId !== serverId) {
+        continue;
+      }
+      clearTimeout(pending.timeout);
+      pending.reject(new Error('LSP server stopped'));
+      this.pendingRequests.delete(id);
+    }
+
+    const conn = this.servers.get(serverId);
+    if (conn?.cleanupTimer) {
+      clearTimeout(conn.cleanupTimer);
+    }
+    this.servers.delete(serverId);
+  }
+
   /**
    * Get server for a language and root path
    */
@@ -517,6 +565,10 @@ class LspService {
         workspace: {
           workspaceFolders: true,
           symbol: {},
+          // Registrations are handled by the Rust backend, which forwards file watcher events
+          didChangeWatchedFiles: {
+            dynamicRegistration: true,
+          },
         },
       },
       workspaceFolders: [
@@ -818,21 +870,21 @@ class LspService {
   }
 
   /**
-   * Get incoming calls for call hierarchy items
+   * Get the callers of the symbol at a position (resolved by the backend)
    */
   async incomingCalls(
     serverId: string,
-    item: CallHierarchyItem
+    filePath: string,
+    line: number,
+    character: number
   ): Promise<CallHierarchyIncomingCall[] | null> {
-    const params = { item };
-
     try {
-      const result = await this.sendRequest<CallHierarchyIncomingCall[] | null>(
+      return await invoke<CallHierarchyIncomingCall[]>('lsp_incoming_calls', {
         serverId,
-        LSP_METHODS.CALL_HIERARCHY_INCOMING,
-        params
-      );
-      return result;
+        filePath,
+        line,
+        character,
+      });
     } catch (e) {
       logger.debug(`[LSP] CallHierarchy incoming request failed: ${e}`);
       return null;
@@ -840,27 +892,51 @@ class LspService {
   }
 
   /**
-   * Get outgoing calls for call hierarchy items
+   * Get the callees of the symbol at a position (resolved by the backend)
    */
   async outgoingCalls(
     serverId: string,
-    item: CallHierarchyItem
+    filePath: string,
+    line: number,
+    character: number
   ): Promise<CallHierarchyOutgoingCall[] | null> {
-    const params = { item };
-
     try {
-      const result = await this.sendRequest<CallHierarchyOutgoingCall[] | null>(
+      return await invoke<CallHierarchyOutgoingCall[]>('lsp_outgoing_calls', {
         serverId,
-        LSP_METHODS.CALL_HIERARCHY_OUTGOING,
-        params
-      );
-      return result;
+        filePath,
+        line,
+        character,
+      });
     } catch (e) {
       logger.debug(`[LSP] CallHierarchy outgoing request failed: ${e}`);
       return null;
     }
   }
 
+  /**
+   * Get the supertypes or subtypes of the type at a position (resolved by the backend)
+   */
+  async typeHierarchy(
+    serverId: string,
+    filePath: string,
+    line: number,
+    character: number,
+    direction: TypeHierarchyDirection
+  ): Promise<TypeHierarchyItem[] | null> {
+    try {
+      return await invoke<TypeHierarchyItem[]>('lsp_type_hierarchy', {
+        serverId,
+        filePath,
+        line,
+        character,
+        direction,
+      });
+    } catch (e) {
+      logger.debug(`[LSP] TypeHierarchy ${direction} request failed: ${e}`);
+      return null;
+    }
+  }
+
   /**
    * Get completions at a position
    */
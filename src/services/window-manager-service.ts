@@ -198,6 +198,21 @@ export class WindowManagerService {
     }
   }
 
+  /**
+   * Run a bulk operation with file watching paused so listeners get a single
+   * refresh afterwards instead of one event per touched file
+   */
+  static async withFileWatchingPaused<T>(operation: () => Promise<T>): Promise<T> {
+    await invoke('file_watcher_pause');
+    try {
+      return await operation();
+    } finally {
+      await invoke('file_watcher_resume').catch((error) => {
+        logger.error('Failed to resume file watching:', error);
+      });
+    }
+  }
+
   /**
    * Open a project in a new window or focus existing window if already open
    */
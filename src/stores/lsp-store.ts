@@ -40,7 +40,7 @@ export interface PendingDownload {
 
 export interface DownloadProgress {
   language: string;
-  status: 'downloading' | 'extracting' | 'completed' | 'error';
+  status: 'downloading' | 'verifying' | 'extracting' | 'completed' | 'error';
   progress?: number;
   message?: string;
 }
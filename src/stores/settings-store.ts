@@ -5,6 +5,7 @@ import { GROK_CODE_FAST } from '@/providers/config/model-config';
 import { PROVIDER_CONFIGS } from '@/providers/config/provider-config';
 import type { TursoClient } from '@/services/database/turso-client';
 import { databaseService } from '@/services/database-service';
+import { llmClient } from '@/services/llm/llm-client';
 import { taskStore } from '@/stores/task-store';
 import type { ApiKeySettings, CustomProviderApiKeys } from '@/types/api-keys';
 import type { ShortcutAction, ShortcutConfig, ShortcutSettings } from '@/types/shortcuts';
@@ -12,6 +13,9 @@ import { DEFAULT_SHORTCUTS } from '@/types/shortcuts';
 
 export const DEFAULT_PROJECT = 'default';
 
+// Settings value of an API key the backend keeps in the OS keychain
+const KEYCHAIN_PLACEHOLDER = 'keychain:stored';
+
 // Generate default API key settings from provider configs
 function generateDefaultApiKeySettings(): Record<string, string> {
   const settings: Record<string, string> = {};
@@ -631,6 +635,19 @@ export const useSettingsStore = create<SettingsStore>((set, get) => ({
         const value = rawSettings[`api_key_${providerId}`];
         apiKeys[key] = value || undefined;
       }
+      if (Object.values(apiKeys).includes(KEYCHAIN_PLACEHOLDER)) {
+        try {
+          const storedKeys = await llmClient.getApiKeys();
+          for (const providerId of providerIds) {
+            const key = providerId as keyof ApiKeySettings;
+            if (apiKeys[key] === KEYCHAIN_PLACEHOLDER) {
+              apiKeys[key] = storedKeys[providerId] || undefined;
+            }
+          }
+        } catch (error) {
+          logger.error('[initialize] Failed to read API keys from the keychain:', error);
+        }
+      }
       logger.debug('[initialize] Parsed API keys', {
         apiKeyCount: Object.keys(apiKeys).length,
         keysWithValues: Object.keys(apiKeys).filter((k) => apiKeys[k as keyof ApiKeySettings])
@@ -1054,7 +1071,8 @@ export const useSettingsStore = create<SettingsStore>((set, get) => ({
 
   // Custom Provider API Keys
   setCustomProviderApiKey: async (providerId: string, apiKey: string) => {
-    await settingsDb.set(`custom_api_key_${providerId}`, apiKey);
+    // The backend keeps it in the OS keychain
+    await llmClient.setSetting(`custom_api_key_${providerId}`, apiKey);
     logger.info('Updated custom provider API key', {
       provider: providerId,
       hasKey: !!apiKey,
@@ -1474,8 +1492,8 @@ export const settingsManager = {
   setCustomProviderApiKey: (providerId: string, apiKey: string) =>
     useSettingsStore.getState().setCustomProviderApiKey(providerId, apiKey),
   getCustomProviderApiKey: async (providerId: string) => {
-    await settingsDb.initialize();
-    return settingsDb.get(`custom_api_key_${providerId}`);
+    // Read through the backend, as the settings row may only hold the keychain placeholder
+    return (await llmClient.getCustomProviderApiKey(providerId)) ?? '';
   },
   getCustomProviderApiKeys: () => useSettingsStore.getState().getCustomProviderApiKeys(),
 
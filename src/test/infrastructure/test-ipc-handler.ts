@@ -234,6 +234,12 @@ export class TestIPCHandler {
     if (cmd === 'llm_set_setting') {
       return null;
     }
+    if (cmd === 'llm_get_api_keys') {
+      return {};
+    }
+    if (cmd === 'llm_get_custom_provider_api_key') {
+      return null;
+    }
     if (cmd === 'llm_register_custom_provider') {
       return null;
     }
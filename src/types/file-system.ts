@@ -34,6 +34,13 @@ export interface IndexingProgress {
   currentFile?: string;
 }
 
+/** Payload item of the `file-system-changes` event emitted by the file watcher */
+export type FileSystemChange =
+  | { kind: 'created'; path: string }
+  | { kind: 'modified'; path: string }
+  | { kind: 'deleted'; path: string }
+  | { kind: 'renamed'; oldPath: string; newPath: string };
+
 export interface PendingExternalChange {
   filePath: string;
   diskContent: string;
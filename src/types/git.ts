@@ -75,6 +75,17 @@ export interface CommitInfo {
   timestamp: number;
 }
 
+/** Kind of change detected inside the .git directory */
+export type GitChangeKind = 'index' | 'head' | 'localRef' | 'remoteRef' | 'operationState';
+
+/** Payload of the `git-status-changed` event */
+export interface GitStatusChange {
+  kinds: GitChangeKind[];
+  /** Affected refs relative to the git directory, e.g. `HEAD` or `refs/heads/main` */
+  refs: string[];
+  paths: string[];
+}
+
 // Helper types for UI components
 export type LineChange = [number, DiffLineType];
 
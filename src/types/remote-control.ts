@@ -112,6 +112,7 @@ export interface TelegramRemoteConfig {
   token: string;
   allowedChatIds: number[];
   pollTimeoutSecs: number;
+  webhookSecret?: string;
 }
 
 export interface TelegramInboundMessage {
@@ -35,8 +35,11 @@ export interface TaskSettings {
   autoApprovePlan?: boolean; // When true, auto-approve plan for this task
   autoCodeReview?: boolean; // When true, auto-run code review for this task
   ralphLoopEnabled?: boolean; // When true, run Ralph Loop for this task
+  toolCapabilities?: ToolCapability[]; // When set, only tools of these capabilities are offered
 }
 
+export type ToolCapability = 'filesystemRead' | 'filesystemWrite' | 'shell' | 'network' | 'gitPush';
+
 export interface CreateProjectData {
   name: string;
   description?: string;